@@ -0,0 +1,12 @@
+-- Emergency banner announcements: a separate kind with severity and optional expiry
+ALTER TABLE announcements ADD COLUMN IF NOT EXISTS kind TEXT NOT NULL DEFAULT 'standard';
+ALTER TABLE announcements ADD COLUMN IF NOT EXISTS severity TEXT;
+ALTER TABLE announcements ADD COLUMN IF NOT EXISTS expires_at TIMESTAMPTZ;
+
+ALTER TABLE announcements ADD CONSTRAINT announcements_kind_check
+    CHECK (kind IN ('standard', 'banner'));
+ALTER TABLE announcements ADD CONSTRAINT announcements_severity_check
+    CHECK (severity IS NULL OR severity IN ('info', 'warning', 'critical'));
+
+-- Banner lookup runs on every page load
+CREATE INDEX IF NOT EXISTS idx_announcements_kind_created_at ON announcements(kind, created_at DESC);
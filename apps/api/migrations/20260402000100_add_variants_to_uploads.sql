@@ -0,0 +1,5 @@
+-- Resized/webp variants are stored as their own objects, linked to the original upload
+ALTER TABLE uploads ADD COLUMN IF NOT EXISTS variant TEXT NOT NULL DEFAULT 'original';
+ALTER TABLE uploads ADD COLUMN IF NOT EXISTS parent_id UUID REFERENCES uploads(id) ON DELETE CASCADE;
+
+CREATE INDEX IF NOT EXISTS idx_uploads_parent_id ON uploads(parent_id);
@@ -0,0 +1,5 @@
+-- Set once an upload is referenced by a post, project, or profile; unattached uploads are garbage collected
+ALTER TABLE uploads ADD COLUMN IF NOT EXISTS attached_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_uploads_unattached ON uploads(created_at)
+    WHERE attached_at IS NULL AND parent_id IS NULL;
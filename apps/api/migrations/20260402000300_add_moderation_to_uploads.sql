@@ -0,0 +1,10 @@
+-- Images flagged by the moderation hook are held under a quarantine/ prefix until an admin reviews them
+ALTER TABLE uploads
+    ADD COLUMN IF NOT EXISTS moderation_status TEXT NOT NULL DEFAULT 'approved'
+        CHECK (moderation_status IN ('approved', 'pending')),
+    ADD COLUMN IF NOT EXISTS moderation_labels TEXT[] NOT NULL DEFAULT '{}',
+    ADD COLUMN IF NOT EXISTS reviewed_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    ADD COLUMN IF NOT EXISTS reviewed_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_uploads_pending_review ON uploads(created_at)
+    WHERE moderation_status = 'pending' AND parent_id IS NULL;
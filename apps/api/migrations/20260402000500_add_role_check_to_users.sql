@@ -0,0 +1,3 @@
+-- Roles: moderators get a subset of admin powers (bans, suspensions, upload review, content removal)
+ALTER TABLE users DROP CONSTRAINT IF EXISTS users_role_check;
+ALTER TABLE users ADD CONSTRAINT users_role_check CHECK (role IN ('user', 'moderator', 'admin'));
@@ -0,0 +1,22 @@
+-- One row per day, refreshed by the stats job so /admin/stats never scans the big tables
+CREATE TABLE IF NOT EXISTS daily_stats (
+    day DATE PRIMARY KEY,
+    signups INTEGER NOT NULL DEFAULT 0,
+    posts_created INTEGER NOT NULL DEFAULT 0,
+    projects_created INTEGER NOT NULL DEFAULT 0,
+    -- Distinct users with session activity that day / in the 7 days up to it
+    daily_active_users INTEGER NOT NULL DEFAULT 0,
+    weekly_active_users INTEGER NOT NULL DEFAULT 0,
+    storage_bytes BIGINT NOT NULL DEFAULT 0,
+    computed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Backfill what can be reconstructed from created_at; activity and storage start from now
+INSERT INTO daily_stats (day, signups, posts_created, projects_created)
+SELECT
+    d::date,
+    (SELECT COUNT(*) FROM users WHERE created_at::date = d::date),
+    (SELECT COUNT(*) FROM posts WHERE created_at::date = d::date),
+    (SELECT COUNT(*) FROM projects WHERE created_at::date = d::date)
+FROM generate_series(CURRENT_DATE - INTERVAL '90 days', CURRENT_DATE - INTERVAL '1 day', INTERVAL '1 day') AS d
+ON CONFLICT (day) DO NOTHING;
@@ -0,0 +1,6 @@
+-- Verification links now expire; give tokens that are already out there a week
+ALTER TABLE local_auths ADD COLUMN IF NOT EXISTS verification_token_expires_at TIMESTAMPTZ;
+
+UPDATE local_auths
+SET verification_token_expires_at = NOW() + INTERVAL '7 days'
+WHERE verification_token IS NOT NULL;
@@ -0,0 +1,9 @@
+-- Which optional emails a user gets. No row means the defaults (everything on).
+-- Verification and password reset emails are always sent regardless.
+CREATE TABLE IF NOT EXISTS email_preferences (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    security BOOLEAN NOT NULL DEFAULT TRUE,
+    product_updates BOOLEAN NOT NULL DEFAULT TRUE,
+    activity BOOLEAN NOT NULL DEFAULT TRUE,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,8 @@
+-- The weekly digest is opt-in, and goes out Monday morning in the user's time zone
+ALTER TABLE email_preferences
+    ADD COLUMN IF NOT EXISTS weekly_digest BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN IF NOT EXISTS timezone TEXT NOT NULL DEFAULT 'UTC',
+    ADD COLUMN IF NOT EXISTS digest_sent_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_email_preferences_weekly_digest
+    ON email_preferences(user_id) WHERE weekly_digest;
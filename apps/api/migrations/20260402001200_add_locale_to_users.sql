@@ -0,0 +1,2 @@
+-- Language for emails; see i18n.rs for the supported ones
+ALTER TABLE users ADD COLUMN IF NOT EXISTS locale TEXT NOT NULL DEFAULT 'en';
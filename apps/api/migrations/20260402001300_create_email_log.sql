@@ -0,0 +1,22 @@
+-- Every email queued for sending, so admins can see what a user was sent and resend it.
+-- `message` is the rendered email, kept for resends; rows are purged after 30 days.
+CREATE TABLE IF NOT EXISTS email_log (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID REFERENCES users(id) ON DELETE CASCADE,
+    recipient TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    message JSONB NOT NULL,
+    status TEXT NOT NULL DEFAULT 'queued'
+        CHECK (status IN ('queued', 'retrying', 'sent', 'failed')),
+    provider TEXT,
+    provider_message_id TEXT,
+    attempts INT NOT NULL DEFAULT 0,
+    last_error TEXT,
+    resent_from UUID REFERENCES email_log(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    sent_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_email_log_user_id ON email_log(user_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_email_log_created_at ON email_log(created_at);
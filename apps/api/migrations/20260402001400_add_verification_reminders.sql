@@ -0,0 +1,3 @@
+-- Unverified signups get up to two reminder emails; see verification_reminders.rs
+ALTER TABLE local_auths ADD COLUMN IF NOT EXISTS verification_reminders INT NOT NULL DEFAULT 0;
+ALTER TABLE local_auths ADD COLUMN IF NOT EXISTS verification_reminded_at TIMESTAMPTZ;
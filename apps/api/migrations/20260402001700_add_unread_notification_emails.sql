@@ -0,0 +1,14 @@
+-- Notifications still unread after a few hours are emailed; see notifications::email_unread.
+-- Set once a notification has been emailed, either right away or in a summary.
+ALTER TABLE notifications ADD COLUMN IF NOT EXISTS emailed_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_notifications_unemailed
+    ON notifications(created_at) WHERE read_at IS NULL AND emailed_at IS NULL;
+
+-- Hours in the user's time zone when no notification email goes out, e.g. 22 to 7.
+-- Equal hours (the default) mean no quiet hours.
+ALTER TABLE email_preferences
+    ADD COLUMN IF NOT EXISTS quiet_hours_start INT NOT NULL DEFAULT 0
+        CHECK (quiet_hours_start BETWEEN 0 AND 23),
+    ADD COLUMN IF NOT EXISTS quiet_hours_end INT NOT NULL DEFAULT 0
+        CHECK (quiet_hours_end BETWEEN 0 AND 23);
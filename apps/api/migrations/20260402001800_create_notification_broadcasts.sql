@@ -0,0 +1,25 @@
+-- In-app notifications sent by admins to everyone, or to the users matching a segment.
+-- The segment columns are ANDed; NULL means any. Delivered once send_at has passed.
+CREATE TABLE IF NOT EXISTS notification_broadcasts (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    author_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    content TEXT NOT NULL,
+    link TEXT,
+    role TEXT,
+    locale TEXT,
+    joined_after TIMESTAMPTZ,
+    joined_before TIMESTAMPTZ,
+    send_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    sent_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_notification_broadcasts_unsent
+    ON notification_broadcasts(send_at) WHERE sent_at IS NULL;
+
+-- Which broadcast a notification came from, for the delivery and read report
+ALTER TABLE notifications ADD COLUMN IF NOT EXISTS broadcast_id UUID
+    REFERENCES notification_broadcasts(id) ON DELETE CASCADE;
+
+CREATE INDEX IF NOT EXISTS idx_notifications_broadcast_id
+    ON notifications(broadcast_id) WHERE broadcast_id IS NOT NULL;
@@ -0,0 +1,5 @@
+-- An image attached to a direct message. It's uploaded with category=message, which keeps
+-- it under private/ and hands out signed URLs only to the conversation's members.
+ALTER TABLE messages ADD COLUMN IF NOT EXISTS attachment_id UUID REFERENCES uploads(id) ON DELETE SET NULL;
+CREATE INDEX IF NOT EXISTS idx_messages_attachment_id ON messages(attachment_id)
+    WHERE attachment_id IS NOT NULL;
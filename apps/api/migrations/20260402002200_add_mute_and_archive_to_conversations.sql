@@ -0,0 +1,4 @@
+-- Each member can mute a conversation (no notifications for new messages until muted_until,
+-- 'infinity' for indefinitely) and archive it out of their inbox
+ALTER TABLE conversation_members ADD COLUMN IF NOT EXISTS muted_until TIMESTAMPTZ;
+ALTER TABLE conversation_members ADD COLUMN IF NOT EXISTS archived BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,10 @@
+-- Messages a member deleted for themselves; the other member still sees them
+CREATE TABLE IF NOT EXISTS hidden_messages (
+    message_id UUID NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, message_id)
+);
+
+-- For the retention job
+CREATE INDEX IF NOT EXISTS idx_messages_created_at ON messages(created_at);
@@ -0,0 +1,16 @@
+-- Searches users keep to hear about new results; the columns mirror GET /search's parameters.
+CREATE TABLE IF NOT EXISTS saved_searches (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    q TEXT NOT NULL,
+    search_type TEXT NOT NULL DEFAULT 'all',
+    author TEXT,
+    status TEXT,
+    tag TEXT,
+    -- Results created since then are new; moved forward by every check
+    checked_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_saved_searches_user_id ON saved_searches(user_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_saved_searches_checked_at ON saved_searches(checked_at);
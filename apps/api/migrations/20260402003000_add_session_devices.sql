@@ -0,0 +1,6 @@
+-- What the user agent was parsed into when the session was created (see session.rs).
+-- Rows from before this are parsed when listed.
+ALTER TABLE active_sessions
+    ADD COLUMN IF NOT EXISTS browser TEXT,
+    ADD COLUMN IF NOT EXISTS os TEXT,
+    ADD COLUMN IF NOT EXISTS device TEXT;
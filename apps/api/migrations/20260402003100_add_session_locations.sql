@@ -0,0 +1,5 @@
+-- Roughly where the session was signed in from, looked up from its IP (see session.rs).
+-- NULL until the lookup finishes, and for private addresses.
+ALTER TABLE active_sessions
+    ADD COLUMN IF NOT EXISTS city TEXT,
+    ADD COLUMN IF NOT EXISTS country TEXT;
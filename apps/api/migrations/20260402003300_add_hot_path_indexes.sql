@@ -0,0 +1,18 @@
+-- Indexes for the queries that were scanning: the feed and a user's posts and projects
+-- (newest first), a user's sessions, and case-insensitive username lookups (mentions).
+--
+-- Existing deployments should run `ANALYZE posts, projects, users, active_sessions, local_auths;`
+-- after migrating, so the planner has statistics to pick these up straight away.
+
+-- Replace the author/owner only indexes; these also serve lookups by author/owner alone
+DROP INDEX IF EXISTS idx_posts_author_id;
+CREATE INDEX IF NOT EXISTS idx_posts_author_id_created_at ON posts (author_id, created_at DESC);
+
+DROP INDEX IF EXISTS idx_projects_owner_id;
+CREATE INDEX IF NOT EXISTS idx_projects_owner_id_created_at ON projects (owner_id, created_at DESC);
+
+CREATE INDEX IF NOT EXISTS idx_users_username_lower ON users (LOWER(username));
+
+-- Already created with the table, so a no-op; local_auths.email needs nothing either,
+-- as its UNIQUE constraint is backed by an index
+CREATE INDEX IF NOT EXISTS idx_active_sessions_user_id ON active_sessions (user_id);
@@ -0,0 +1,6 @@
+-- When a public profile or project last changed, for Last-Modified on their pages
+ALTER TABLE users ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
+ALTER TABLE projects ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
+
+UPDATE users SET updated_at = created_at WHERE created_at IS NOT NULL;
+UPDATE projects SET updated_at = created_at;
@@ -0,0 +1,15 @@
+-- Links between accounts that share an email, waiting for the owner of that email to confirm.
+-- Either an OAuth login to attach to user_id (provider set), or a duplicate account
+-- (merge_user_id) to fold into it. The token is emailed, so confirming proves ownership.
+CREATE TABLE IF NOT EXISTS account_merges (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    merge_user_id UUID REFERENCES users(id) ON DELETE CASCADE,
+    provider TEXT,
+    provider_id TEXT,
+    provider_email TEXT,
+    token TEXT NOT NULL UNIQUE,
+    expires_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK ((merge_user_id IS NULL) <> (provider IS NULL))
+);
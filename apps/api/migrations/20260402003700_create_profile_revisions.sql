@@ -0,0 +1,28 @@
+-- Profile fields as they were before each change, so users and moderators can see what a
+-- profile used to say and put it back. changed_by is whoever made the change: the user,
+-- or a moderator reverting it.
+CREATE TABLE IF NOT EXISTS profile_revisions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    changed_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    username TEXT NOT NULL,
+    display_name TEXT NOT NULL,
+    bio TEXT,
+    location TEXT,
+    website TEXT,
+    pronouns TEXT,
+    major TEXT,
+    avatar_url TEXT,
+    avatar_original_url TEXT,
+    avatar_crop_x DOUBLE PRECISION,
+    avatar_crop_y DOUBLE PRECISION,
+    avatar_zoom DOUBLE PRECISION,
+    banner_url TEXT,
+    banner_original_url TEXT,
+    banner_crop_x DOUBLE PRECISION,
+    banner_crop_y DOUBLE PRECISION,
+    banner_zoom DOUBLE PRECISION,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_profile_revisions_user ON profile_revisions(user_id, created_at DESC);
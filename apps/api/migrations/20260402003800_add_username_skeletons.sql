@@ -0,0 +1,11 @@
+-- What a username looks like at a glance, so lookalikes (ad0 for ado, rn for m) can be
+-- refused at signup and rename. Must match validation::username_skeleton.
+CREATE OR REPLACE FUNCTION username_skeleton(username TEXT) RETURNS TEXT
+    LANGUAGE SQL IMMUTABLE STRICT PARALLEL SAFE
+    AS $$ SELECT replace(replace(translate(lower(username), '01', 'ol'), 'rn', 'm'), 'vv', 'w') $$;
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS username_skeleton TEXT
+    GENERATED ALWAYS AS (username_skeleton(username)) STORED;
+
+-- Not unique: accounts made before this may already look alike
+CREATE INDEX IF NOT EXISTS idx_users_username_skeleton ON users(username_skeleton);
@@ -0,0 +1,3 @@
+-- Points from received reactions, accepted applications and completed projects, kept up
+-- to date by the reputation job (see reputation.rs)
+ALTER TABLE users ADD COLUMN IF NOT EXISTS reputation INTEGER NOT NULL DEFAULT 0;
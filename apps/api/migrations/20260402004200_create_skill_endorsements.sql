@@ -0,0 +1,13 @@
+-- Users vouching for a skill on someone else's profile, once per skill each. `skill` is
+-- spelled as in the endorsed user's skills; endorsements of skills they've since removed
+-- are kept but not shown.
+CREATE TABLE IF NOT EXISTS skill_endorsements (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    endorser_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    skill TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, skill, endorser_id),
+    CHECK (user_id <> endorser_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_skill_endorsements_endorser_id ON skill_endorsements(endorser_id);
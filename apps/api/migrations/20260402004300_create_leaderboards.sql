@@ -0,0 +1,20 @@
+-- When applications were decided and projects completed, for reputation earned in a period
+ALTER TABLE applications ADD COLUMN IF NOT EXISTS reviewed_at TIMESTAMPTZ;
+ALTER TABLE projects ADD COLUMN IF NOT EXISTS completed_at TIMESTAMPTZ;
+UPDATE projects SET completed_at = updated_at WHERE status = 'completed' AND completed_at IS NULL;
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS leaderboard_opt_out BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- The top of each leaderboard, rebuilt by the leaderboards job (see leaderboards.rs)
+CREATE TABLE IF NOT EXISTS leaderboard_entries (
+    period TEXT NOT NULL,
+    metric TEXT NOT NULL,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    score BIGINT NOT NULL,
+    refreshed_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (period, metric, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_leaderboard_entries_score
+    ON leaderboard_entries(period, metric, score DESC);
+CREATE INDEX IF NOT EXISTS idx_leaderboard_entries_user_id ON leaderboard_entries(user_id);
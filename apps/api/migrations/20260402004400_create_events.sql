@@ -0,0 +1,39 @@
+-- Meetups and other events, hosted by a user and optionally on behalf of one of their projects
+CREATE TABLE IF NOT EXISTS events (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    host_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    project_id UUID REFERENCES projects(id) ON DELETE CASCADE,
+    title TEXT NOT NULL,
+    description TEXT,
+    starts_at TIMESTAMPTZ NOT NULL,
+    ends_at TIMESTAMPTZ NOT NULL,
+    location TEXT,
+    online_url TEXT,
+    -- NULL for no limit
+    capacity INTEGER CHECK (capacity > 0),
+    -- When attendees were reminded it's coming up
+    reminded_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK (ends_at > starts_at)
+);
+
+CREATE INDEX IF NOT EXISTS idx_events_starts_at ON events(starts_at);
+CREATE INDEX IF NOT EXISTS idx_events_host_id ON events(host_id);
+CREATE INDEX IF NOT EXISTS idx_events_project_id ON events(project_id) WHERE project_id IS NOT NULL;
+
+CREATE TABLE IF NOT EXISTS event_rsvps (
+    event_id UUID NOT NULL REFERENCES events(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (event_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_event_rsvps_user_id ON event_rsvps(user_id);
+
+-- Events go in the feed alongside posts and projects
+ALTER TABLE feed_items ADD COLUMN IF NOT EXISTS event_id UUID UNIQUE REFERENCES events(id) ON DELETE CASCADE;
+ALTER TABLE feed_items DROP CONSTRAINT IF EXISTS feed_items_check;
+ALTER TABLE feed_items DROP CONSTRAINT IF EXISTS feed_items_one_item;
+ALTER TABLE feed_items ADD CONSTRAINT feed_items_one_item
+    CHECK (num_nonnulls(post_id, project_id, event_id) = 1);
@@ -0,0 +1,37 @@
+-- Paid and volunteer openings, posted by a user and optionally on behalf of one of their projects
+CREATE TABLE IF NOT EXISTS listings (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    poster_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    project_id UUID REFERENCES projects(id) ON DELETE CASCADE,
+    role TEXT NOT NULL,
+    description TEXT,
+    skills TEXT[] NOT NULL DEFAULT '{}',
+    compensation_type TEXT NOT NULL CHECK (compensation_type IN ('paid', 'volunteer')),
+    -- Free text, e.g. "$40/hour"
+    compensation TEXT,
+    -- NULL for no deadline
+    deadline TIMESTAMPTZ,
+    status TEXT NOT NULL DEFAULT 'open' CHECK (status IN ('open', 'closed')),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    search_vector tsvector GENERATED ALWAYS AS (
+        setweight(to_tsvector('simple', role), 'A')
+            || setweight(to_tsvector('simple', COALESCE(description, '')), 'B')
+    ) STORED
+);
+
+CREATE INDEX IF NOT EXISTS idx_listings_open ON listings(created_at DESC) WHERE status = 'open';
+CREATE INDEX IF NOT EXISTS idx_listings_poster_id ON listings(poster_id);
+CREATE INDEX IF NOT EXISTS idx_listings_project_id ON listings(project_id) WHERE project_id IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_listings_skills ON listings USING GIN (skills);
+CREATE INDEX IF NOT EXISTS idx_listings_search_vector ON listings USING GIN (search_vector);
+
+-- Applications are to a project or a listing
+ALTER TABLE applications ALTER COLUMN project_id DROP NOT NULL;
+ALTER TABLE applications ADD COLUMN IF NOT EXISTS listing_id UUID REFERENCES listings(id) ON DELETE CASCADE;
+ALTER TABLE applications DROP CONSTRAINT IF EXISTS applications_one_target;
+ALTER TABLE applications ADD CONSTRAINT applications_one_target
+    CHECK (num_nonnulls(project_id, listing_id) = 1);
+ALTER TABLE applications DROP CONSTRAINT IF EXISTS applications_listing_id_applicant_id_key;
+ALTER TABLE applications ADD CONSTRAINT applications_listing_id_applicant_id_key
+    UNIQUE (listing_id, applicant_id);
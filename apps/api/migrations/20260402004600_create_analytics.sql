@@ -0,0 +1,24 @@
+-- First-party analytics. Events are anonymous: no user, session or IP is kept. They stay raw
+-- for a week, and are rolled up into daily counts that are kept.
+CREATE TABLE IF NOT EXISTS analytics_events (
+    id BIGSERIAL PRIMARY KEY,
+    kind TEXT NOT NULL CHECK (kind IN ('page_view', 'interaction')),
+    -- Without the query string
+    path TEXT NOT NULL,
+    -- What was interacted with, e.g. "signup_button"; NULL for page views
+    name TEXT,
+    -- The referring site's host, for page views from elsewhere
+    referrer TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_analytics_events_created_at ON analytics_events(created_at);
+
+-- Per UTC day: page views by path, interactions by name and page views by referrer
+CREATE TABLE IF NOT EXISTS analytics_daily (
+    day DATE NOT NULL,
+    metric TEXT NOT NULL CHECK (metric IN ('page_view', 'interaction', 'referrer')),
+    key TEXT NOT NULL,
+    count BIGINT NOT NULL,
+    PRIMARY KEY (day, metric, key)
+);
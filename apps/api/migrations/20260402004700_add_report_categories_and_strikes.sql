@@ -0,0 +1,20 @@
+-- What a report is about; older reports were free text only
+ALTER TABLE message_reports
+    ADD COLUMN IF NOT EXISTS category TEXT NOT NULL DEFAULT 'other'
+        CHECK (category IN ('spam', 'harassment', 'hate', 'sexual', 'violence', 'other')),
+    -- The sender's side, once they appeal an upheld report. An appeal reopens the report
+    -- as 'appealed' until an admin decides it again.
+    ADD COLUMN IF NOT EXISTS appeal TEXT,
+    ADD COLUMN IF NOT EXISTS appealed_at TIMESTAMPTZ;
+
+ALTER TABLE message_reports DROP CONSTRAINT IF EXISTS message_reports_status_check;
+ALTER TABLE message_reports ADD CONSTRAINT message_reports_status_check
+    CHECK (status IN ('open', 'dismissed', 'actioned', 'appealed'));
+
+-- Upheld reports against the user, less any overturned on appeal
+ALTER TABLE users ADD COLUMN IF NOT EXISTS strikes INT NOT NULL DEFAULT 0;
+
+-- Warnings are recorded alongside bans and suspensions
+ALTER TABLE moderation_actions DROP CONSTRAINT IF EXISTS moderation_actions_action_check;
+ALTER TABLE moderation_actions ADD CONSTRAINT moderation_actions_action_check
+    CHECK (action IN ('ban', 'suspend', 'lift', 'warn'));
@@ -0,0 +1,16 @@
+-- Content held by the spam checks (see spam.rs), with why. Held content is shown to its author
+-- as usual but to nobody else until a moderator approves it.
+ALTER TABLE posts
+    ADD COLUMN IF NOT EXISTS held_at TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS held_reasons TEXT[];
+ALTER TABLE projects
+    ADD COLUMN IF NOT EXISTS held_at TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS held_reasons TEXT[];
+ALTER TABLE applications
+    ADD COLUMN IF NOT EXISTS held_at TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS held_reasons TEXT[];
+
+-- The review queue
+CREATE INDEX IF NOT EXISTS idx_posts_held ON posts(held_at) WHERE held_at IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_projects_held ON projects(held_at) WHERE held_at IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_applications_held ON applications(held_at) WHERE held_at IS NOT NULL;
@@ -0,0 +1,18 @@
+-- How long each class of data is kept (see retention.rs). NULL keeps it until something else
+-- removes it (sessions still expire, held content can still be reviewed).
+CREATE TABLE IF NOT EXISTS retention_policies (
+    data_class TEXT PRIMARY KEY
+        CHECK (data_class IN ('sessions', 'audit_log', 'analytics_events', 'email_log', 'held_content')),
+    days INT CHECK (days > 0),
+    updated_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- What was kept before policies were configurable
+INSERT INTO retention_policies (data_class, days) VALUES
+    ('sessions', NULL),
+    ('audit_log', NULL),
+    ('analytics_events', 7),
+    ('email_log', 30),
+    ('held_content', NULL)
+ON CONFLICT (data_class) DO NOTHING;
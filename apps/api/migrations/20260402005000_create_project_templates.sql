@@ -0,0 +1,52 @@
+-- Starting points for new projects (POST /projects/from-template/:id), managed by admins
+CREATE TABLE IF NOT EXISTS project_templates (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name TEXT NOT NULL UNIQUE,
+    -- What the template is for, shown when picking one
+    summary TEXT NOT NULL DEFAULT '',
+    -- Scaffolding for the project's description, with headings to fill in
+    description TEXT NOT NULL DEFAULT '',
+    looking_for TEXT[] NOT NULL DEFAULT '{}',
+    tags TEXT[] NOT NULL DEFAULT '{}',
+    -- Copied to project_milestones, in order
+    milestones TEXT[] NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- A project's plan, checked off by its owner
+CREATE TABLE IF NOT EXISTS project_milestones (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    project_id UUID NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    position INT NOT NULL,
+    title TEXT NOT NULL,
+    completed_at TIMESTAMPTZ,
+    UNIQUE (project_id, position)
+);
+
+INSERT INTO project_templates (name, summary, description, looking_for, tags, milestones) VALUES
+(
+    'Hackathon',
+    'A team building something in a weekend',
+    E'## The idea\n\n## What we''ll demo\n\n## Team and roles\n\n## Event and dates\n',
+    ARRAY['Frontend Developer', 'Backend Developer', 'Designer', 'Pitch Lead'],
+    ARRAY['hackathon'],
+    ARRAY['Pick the idea', 'Working prototype', 'Demo ready', 'Submitted']
+),
+(
+    'Research Group',
+    'People studying a question together',
+    E'## Research question\n\n## Background\n\n## Methods\n\n## How we meet\n',
+    ARRAY['Researcher', 'Data Analyst', 'Writer'],
+    ARRAY['research'],
+    ARRAY['Literature review', 'Data collected', 'Analysis done', 'Write-up published']
+),
+(
+    'Open-Source Tool',
+    'A tool built in the open, with contributors',
+    E'## What it does\n\n## Who it''s for\n\n## Tech stack\n\n## How to contribute\n',
+    ARRAY['Maintainer', 'Contributor', 'Technical Writer'],
+    ARRAY['open-source'],
+    ARRAY['Repository and license', 'First release', 'Docs and contributing guide', 'First outside contribution']
+)
+ON CONFLICT (name) DO NOTHING;
@@ -0,0 +1,15 @@
+-- More lookalikes, from Unicode's confusables (TR39) narrowed to what usernames can contain:
+-- i and 1 read as l, 0 as o, and rn, vv and cl as m, w and d. Must match
+-- validation::username_skeleton.
+CREATE OR REPLACE FUNCTION username_skeleton(username TEXT) RETURNS TEXT
+    LANGUAGE SQL IMMUTABLE STRICT PARALLEL SAFE
+    AS $$
+        SELECT replace(replace(replace(translate(lower(username), '01i', 'oll'), 'rn', 'm'), 'vv', 'w'), 'cl', 'd')
+    $$;
+
+-- Stored skeletons don't follow the function, so compute them again
+ALTER TABLE users DROP COLUMN IF EXISTS username_skeleton;
+ALTER TABLE users ADD COLUMN username_skeleton TEXT
+    GENERATED ALWAYS AS (username_skeleton(username)) STORED;
+
+CREATE INDEX IF NOT EXISTS idx_users_username_skeleton ON users(username_skeleton);
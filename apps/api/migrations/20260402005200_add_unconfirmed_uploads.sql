@@ -0,0 +1,5 @@
+-- Presigned uploads are 'unconfirmed' until the client confirms them and they're screened;
+-- until then they can't be attached to anything
+ALTER TABLE uploads DROP CONSTRAINT IF EXISTS uploads_moderation_status_check;
+ALTER TABLE uploads ADD CONSTRAINT uploads_moderation_status_check
+    CHECK (moderation_status IN ('approved', 'pending', 'unconfirmed'));
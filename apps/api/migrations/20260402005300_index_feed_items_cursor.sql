@@ -0,0 +1,3 @@
+-- /feed pages on (created_at, id), so each page starts from its cursor in this index
+CREATE INDEX IF NOT EXISTS idx_feed_items_created_at_id ON feed_items (created_at DESC, id DESC);
+DROP INDEX IF EXISTS idx_feed_items_created_at;
@@ -5,38 +5,207 @@ use argon2::{
 use axum::{
     extract::{Path, Query, State},
     http::StatusCode,
-    response::IntoResponse,
+    response::{IntoResponse, Response},
     Json,
 };
+use futures_util::StreamExt;
 use serde::{Deserialize, Serialize};
-use sqlx::{PgPool, Row};
+use sqlx::PgPool;
+use std::sync::Arc;
 use tower_sessions::Session;
 use uuid::Uuid;
 
+use crate::cache::Cache;
+use crate::error::{ApiError, Message};
+use crate::extractors::{AdminUser, ModeratorUser};
+use crate::i18n;
+use crate::search_index::Indexer;
+use crate::session_store::SessionBackend;
+use crate::state::AppState;
+use crate::validation::{Validate, ValidatedJson, ValidationErrors};
+
+// Longest ban/suspension reason we store
+const MAX_REASON_LENGTH: usize = 500;
+
 #[derive(Deserialize)]
 pub struct ResetPasswordRequest {
     pub new_password: String,
 }
 
+impl Validate for ResetPasswordRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.password("new_password", &self.new_password);
+        errors.into_result()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct PurgeObjectRequest {
+    pub object_key: String,
+}
+
+impl Validate for PurgeObjectRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.required("object_key", &self.object_key);
+        errors.into_result()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct UpdateRoleRequest {
+    pub role: String,
+}
+
+impl Validate for UpdateRoleRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.one_of("role", &self.role.trim().to_lowercase(), ROLES);
+        errors.into_result()
+    }
+}
+
 #[derive(Deserialize)]
-pub struct AuditLogQuery {
-    pub limit: Option<i64>,
+pub struct BanRequest {
+    pub reason: String,
+}
+
+impl Validate for BanRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.length("reason", &self.reason, 1, MAX_REASON_LENGTH);
+        errors.into_result()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SuspendRequest {
+    pub duration_hours: i64,
+    pub reason: String,
+}
+
+impl Validate for SuspendRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.range(
+            "duration_hours",
+            self.duration_hours,
+            1,
+            MAX_SUSPENSION_HOURS,
+        );
+        errors.length("reason", &self.reason, 1, MAX_REASON_LENGTH);
+        errors.into_result()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct LiftRestrictionRequest {
+    pub reason: Option<String>,
+}
+
+impl Validate for LiftRestrictionRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        if let Some(reason) = &self.reason {
+            errors.length("reason", reason, 0, MAX_REASON_LENGTH);
+        }
+        errors.into_result()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ReviewUploadRequest {
+    pub approve: bool,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct QuarantinedUpload {
+    pub id: Uuid,
+    pub owner_id: Uuid,
+    pub owner_username: String,
+    /// Signed, and only valid for a few minutes: quarantined objects aren't public
+    pub url: String,
+    #[serde(skip)]
+    pub object_key: String,
+    pub content_type: String,
+    pub moderation_labels: Vec<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct ListUsersQuery {
+    /// Matches username, display name or email
+    pub q: Option<String>,
+    pub role: Option<String>,
+    pub verified: Option<bool>,
+    pub banned: Option<bool>,
+    /// "local" (has an email/password login) or "oauth" (OAuth only)
+    pub signup_method: Option<String>,
+    pub created_after: Option<chrono::DateTime<chrono::Utc>>,
+    pub created_before: Option<chrono::DateTime<chrono::Utc>>,
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+    /// "json" (the default, paginated) or "csv" (every matching user, streamed)
+    pub format: Option<String>,
 }
 
 #[derive(Serialize, sqlx::FromRow)]
-pub struct AuditLogEntry {
+pub struct AdminUserSummary {
     pub id: Uuid,
-    pub action: String,
-    pub details: Option<String>,
-    pub actor_user_id: Option<Uuid>,
-    pub actor_username: Option<String>,
-    pub target_user_id: Option<Uuid>,
-    pub target_username: Option<String>,
-    pub ip_address: Option<String>,
-    pub user_agent: Option<String>,
+    pub username: String,
+    pub display_name: String,
+    pub avatar_url: Option<String>,
+    pub role: String,
+    pub email: Option<String>,
+    pub verified: bool,
+    pub signup_method: String,
+    pub banned_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub suspended_until: Option<chrono::DateTime<chrono::Utc>>,
     pub created_at: chrono::DateTime<chrono::Utc>,
 }
 
+const USER_CSV_HEADER: &[&str] = &[
+    "id",
+    "username",
+    "display_name",
+    "role",
+    "email",
+    "verified",
+    "signup_method",
+    "banned_at",
+    "suspended_until",
+    "created_at",
+];
+
+impl AdminUserSummary {
+    fn csv_row(&self) -> [String; 10] {
+        let time = |t: Option<chrono::DateTime<chrono::Utc>>| {
+            t.map(|t| t.to_rfc3339()).unwrap_or_default()
+        };
+        [
+            self.id.to_string(),
+            self.username.clone(),
+            self.display_name.clone(),
+            self.role.clone(),
+            self.email.clone().unwrap_or_default(),
+            self.verified.to_string(),
+            self.signup_method.clone(),
+            time(self.banned_at),
+            time(self.suspended_until),
+            self.created_at.to_rfc3339(),
+        ]
+    }
+}
+
+#[derive(Serialize)]
+pub struct UserListPage {
+    pub users: Vec<AdminUserSummary>,
+    pub page: i64,
+    pub per_page: i64,
+    pub total: i64,
+}
+
 #[derive(Serialize)]
 pub struct SecurityAnalytics {
     pub total_users: i64,
@@ -47,131 +216,163 @@ pub struct SecurityAnalytics {
     pub password_resets_7d: i64,
 }
 
-async fn require_admin(session: &Session, pool: &PgPool) -> Result<Uuid, (StatusCode, String)> {
-    let user_id: Uuid = match session.get("user_id").await {
-        Ok(Some(id)) => id,
-        Ok(None) => return Err((StatusCode::UNAUTHORIZED, "Not logged in".to_string())),
-        Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
-    };
+/// Roles that can moderate users and content
+pub const MODERATION_ROLES: &[&str] = &["admin", "moderator"];
 
-    let requester = sqlx::query!("SELECT role FROM users WHERE id = $1", user_id)
-        .fetch_optional(pool)
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+/// Assignable roles, least to most privileged
+const ROLES: &[&str] = &["user", "moderator", "admin"];
 
-    match requester {
-        Some(u) if u.role == "admin" => Ok(user_id),
-        _ => Err((StatusCode::FORBIDDEN, "Admins only".to_string())),
-    }
+pub fn can_moderate(role: &str) -> bool {
+    MODERATION_ROLES.contains(&role)
 }
 
-async fn session_context(
-    session: &Session,
-    pool: &PgPool,
-) -> Result<(Option<String>, Option<String>), (StatusCode, String)> {
-    let Some(session_id) = session.id().map(|id| id.to_string()) else {
-        return Ok((None, None));
+/// Filterable, paginated user list for the admin dashboard, or with `format=csv`
+/// a download of every user matching the filters
+pub async fn list_users(
+    State(pool): State<PgPool>,
+    _: AdminUser,
+    Query(query): Query<ListUsersQuery>,
+) -> Result<Response, ApiError> {
+    let per_page = query.per_page.unwrap_or(50).clamp(1, 200);
+    let page = query.page.unwrap_or(1).max(1);
+
+    let search = query
+        .q
+        .as_deref()
+        .map(str::trim)
+        .filter(|q| !q.is_empty())
+        .map(|q| format!("%{}%", q.replace('%', "\\%").replace('_', "\\_")));
+
+    let signup_method = match query.signup_method.as_deref() {
+        None => None,
+        Some(m @ ("local" | "oauth")) => Some(m.to_string()),
+        Some(_) => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "signup_method must be local or oauth".to_string(),
+            )
+                .into())
+        }
     };
 
-    let row = sqlx::query("SELECT ip_address, user_agent FROM active_sessions WHERE session_id = $1")
-    .bind(session_id)
-    .fetch_optional(pool)
-    .await
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-
-    if let Some(r) = row {
-        let ip_address: Option<String> = r
-            .try_get("ip_address")
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-        let user_agent: Option<String> = r
-            .try_get("user_agent")
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-        Ok((ip_address, user_agent))
-    } else {
-        Ok((None, None))
+    // OAuth-only accounts have no local_auths row; their provider already verified the email
+    let base = r#"
+        FROM users u
+        LEFT JOIN local_auths l ON l.user_id = u.id
+        WHERE ($1::text IS NULL OR u.username ILIKE $1 OR u.display_name ILIKE $1 OR l.email ILIKE $1)
+          AND ($2::text IS NULL OR u.role = $2)
+          AND ($3::bool IS NULL OR COALESCE(l.verified, TRUE) = $3)
+          AND ($4::bool IS NULL OR (u.banned_at IS NOT NULL) = $4)
+          AND ($5::text IS NULL OR (CASE WHEN l.user_id IS NULL THEN 'oauth' ELSE 'local' END) = $5)
+          AND ($6::timestamptz IS NULL OR u.created_at >= $6)
+          AND ($7::timestamptz IS NULL OR u.created_at < $7)
+    "#;
+
+    let columns = r#"
+        SELECT
+            u.id,
+            u.username,
+            u.display_name,
+            u.avatar_url,
+            u.role,
+            l.email,
+            COALESCE(l.verified, TRUE) AS verified,
+            CASE WHEN l.user_id IS NULL THEN 'oauth' ELSE 'local' END AS signup_method,
+            u.banned_at,
+            u.suspended_until,
+            u.created_at
+    "#;
+
+    match query.format.as_deref() {
+        None | Some("json") => {}
+        Some("csv") => {
+            let sql = format!("{} {} ORDER BY u.created_at DESC", columns, base);
+            let (mut writer, response) = crate::export::csv("users.csv", USER_CSV_HEADER);
+            tokio::spawn(async move {
+                let mut users = sqlx::query_as::<_, AdminUserSummary>(&sql)
+                    .bind(&search)
+                    .bind(&query.role)
+                    .bind(query.verified)
+                    .bind(query.banned)
+                    .bind(&signup_method)
+                    .bind(query.created_after)
+                    .bind(query.created_before)
+                    .fetch(&pool);
+                while let Some(user) = users.next().await {
+                    match user {
+                        Ok(user) => {
+                            if !writer.row(&user.csv_row()).await {
+                                break;
+                            }
+                        }
+                        Err(e) => return writer.fail(e).await,
+                    }
+                }
+                writer.finish().await;
+            });
+            return Ok(response);
+        }
+        Some(_) => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "format must be json or csv".to_string(),
+            )
+                .into())
+        }
     }
-}
 
-async fn insert_audit_log(
-    pool: &PgPool,
-    action: &str,
-    details: Option<&str>,
-    actor_user_id: Option<Uuid>,
-    target_user_id: Option<Uuid>,
-    ip_address: Option<&str>,
-    user_agent: Option<&str>,
-) -> Result<(), (StatusCode, String)> {
-    sqlx::query(
-        r#"
-        INSERT INTO audit_logs (action, details, actor_user_id, target_user_id, ip_address, user_agent)
-        VALUES ($1, $2, $3, $4, $5, $6)
-        "#,
-    )
-    .bind(action)
-    .bind(details)
-    .bind(actor_user_id)
-    .bind(target_user_id)
-    .bind(ip_address)
-    .bind(user_agent)
-    .execute(pool)
-    .await
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-
-    Ok(())
-}
-
-pub async fn list_audit_logs(
-    State(pool): State<PgPool>,
-    session: Session,
-    Query(query): Query<AuditLogQuery>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    require_admin(&session, &pool).await?;
-
-    let limit = query.limit.unwrap_or(100).clamp(1, 500);
+    let total: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*)::bigint {}", base))
+        .bind(&search)
+        .bind(&query.role)
+        .bind(query.verified)
+        .bind(query.banned)
+        .bind(&signup_method)
+        .bind(query.created_after)
+        .bind(query.created_before)
+        .fetch_one(&pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    let logs = sqlx::query_as::<_, AuditLogEntry>(
-        r#"
-        SELECT
-            al.id,
-            al.action,
-            al.details,
-            al.actor_user_id,
-            actor.username AS actor_username,
-            al.target_user_id,
-            target.username AS target_username,
-            al.ip_address,
-            al.user_agent,
-            al.created_at
-        FROM audit_logs al
-        LEFT JOIN users actor ON actor.id = al.actor_user_id
-        LEFT JOIN users target ON target.id = al.target_user_id
-        ORDER BY al.created_at DESC
-        LIMIT $1
-        "#,
-    )
-    .bind(limit)
+    let users = sqlx::query_as::<_, AdminUserSummary>(&format!(
+        "{} {} ORDER BY u.created_at DESC LIMIT $8 OFFSET $9",
+        columns, base
+    ))
+    .bind(&search)
+    .bind(&query.role)
+    .bind(query.verified)
+    .bind(query.banned)
+    .bind(&signup_method)
+    .bind(query.created_after)
+    .bind(query.created_before)
+    .bind(per_page)
+    .bind((page - 1) * per_page)
     .fetch_all(&pool)
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    Ok(Json(logs))
+    Ok(Json(UserListPage {
+        users,
+        page,
+        per_page,
+        total,
+    })
+    .into_response())
 }
 
 pub async fn get_security_analytics(
     State(pool): State<PgPool>,
-    session: Session,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    require_admin(&session, &pool).await?;
-
+    _: AdminUser,
+) -> Result<impl IntoResponse, ApiError> {
     let total_users: i64 = sqlx::query_scalar("SELECT COUNT(*)::bigint FROM users")
         .fetch_one(&pool)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    let admin_users: i64 = sqlx::query_scalar("SELECT COUNT(*)::bigint FROM users WHERE role = 'admin'")
-        .fetch_one(&pool)
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let admin_users: i64 =
+        sqlx::query_scalar("SELECT COUNT(*)::bigint FROM users WHERE role = 'admin'")
+            .fetch_one(&pool)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
     let users_with_password: i64 = sqlx::query_scalar("SELECT COUNT(*)::bigint FROM local_auths")
         .fetch_one(&pool)
@@ -211,28 +412,19 @@ pub async fn get_security_analytics(
 
 pub async fn reset_user_password(
     State(pool): State<PgPool>,
+    AdminUser(admin): AdminUser,
     session: Session,
     Path(target_user_id): Path<Uuid>,
-    Json(payload): Json<ResetPasswordRequest>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    let admin_user_id = require_admin(&session, &pool).await?;
-
-    // 3. Validate new password
-    if payload.new_password.len() < 6 {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            "Password must be at least 6 characters".to_string(),
-        ));
-    }
-
-    // 4. Hash new password
+    ValidatedJson(payload): ValidatedJson<ResetPasswordRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    // 1. Hash new password
     let salt = SaltString::generate(&mut OsRng);
     let password_hash = Argon2::default()
         .hash_password(payload.new_password.as_bytes(), &salt)
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
         .to_string();
 
-    // 5. Update password in database
+    // 2. Update password in database
     // We need to check if local_auths exists for this user. If not, we might need to create it?
     // For now, let's assume we are resetting existing passwords or allowing setting one if it exists.
     // If the user is OAuth only, they might not have a local_auth record.
@@ -255,37 +447,446 @@ pub async fn reset_user_password(
             .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
         if user_exists.is_none() {
-            return Err((StatusCode::NOT_FOUND, "User not found".to_string()));
+            return Err(ApiError::new(StatusCode::NOT_FOUND, &i18n::USER_NOT_FOUND));
         }
 
         // If user exists but no local_auth, we should probably CREATE one.
         // But we need an email. We can try to fetch it from the user logic or just say "User has no email login setup".
         // For simplicity in this first version, let's error if they don't have local_auth.
         // OR we could try to INSERT if we had the email.
-        return Err((
+        return Err(ApiError::new(
             StatusCode::BAD_REQUEST,
-            "User does not have email login set up. Cannot reset password.".to_string(),
+            &i18n::NO_EMAIL_LOGIN,
         ));
     }
 
-    // 6. Log the action (verify logging works)
+    // 3. Log the action (verify logging works)
     tracing::info!(
         "Admin {} reset password for user {}",
-        admin_user_id,
+        admin.id,
         target_user_id
     );
 
-    let (ip_address, user_agent) = session_context(&session, &pool).await?;
-    insert_audit_log(
+    crate::audit::record(
         &pool,
+        &session,
+        admin.id,
         "admin.password_reset",
+        Some(target_user_id),
         Some("Admin reset user password"),
-        Some(admin_user_id),
+    )
+    .await?;
+
+    Ok((StatusCode::OK, Message::new(&i18n::PASSWORD_RESET)))
+}
+
+pub async fn purge_object(
+    State(state): State<AppState>,
+    AdminUser(admin): AdminUser,
+    session: Session,
+    ValidatedJson(payload): ValidatedJson<PurgeObjectRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let object_key = payload.object_key.trim();
+
+    let deleted = crate::upload::purge_object(&state, object_key)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    crate::audit::record(
+        &state.pool,
+        &session,
+        admin.id,
+        "admin.upload_purged",
+        None,
+        Some(object_key),
+    )
+    .await?;
+
+    Ok(Json(serde_json::json!({ "deleted": deleted })))
+}
+
+/// Uploads held by the moderation hook, oldest first
+pub async fn list_quarantined_uploads(
+    State(state): State<AppState>,
+    _: ModeratorUser,
+) -> Result<impl IntoResponse, ApiError> {
+    let mut uploads = sqlx::query_as::<_, QuarantinedUpload>(
+        r#"
+        SELECT up.id, up.owner_id, u.username AS owner_username, up.url, up.object_key,
+               up.content_type, up.moderation_labels, up.created_at
+        FROM uploads up
+        JOIN users u ON u.id = up.owner_id
+        WHERE up.moderation_status = 'pending' AND up.parent_id IS NULL
+        ORDER BY up.created_at
+        "#,
+    )
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    for upload in &mut uploads {
+        let original = [("original".to_string(), upload.object_key.clone())];
+        let mut urls = crate::upload::review_urls(&state, original)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+        upload.url = urls.remove("original").unwrap_or_default();
+    }
+
+    Ok(Json(uploads))
+}
+
+pub async fn review_upload(
+    State(state): State<AppState>,
+    ModeratorUser(moderator): ModeratorUser,
+    session: Session,
+    Path(upload_id): Path<Uuid>,
+    Json(payload): Json<ReviewUploadRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let found = if payload.approve {
+        crate::upload::approve_quarantined(&state, upload_id, moderator.id).await
+    } else {
+        crate::upload::reject_quarantined(&state, upload_id).await
+    }
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    if !found {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            &i18n::PENDING_UPLOAD_NOT_FOUND,
+        ));
+    }
+
+    let details = format!("upload {}", upload_id);
+    crate::audit::record(
+        &state.pool,
+        &session,
+        moderator.id,
+        if payload.approve {
+            "moderation.upload_approved"
+        } else {
+            "moderation.upload_rejected"
+        },
+        None,
+        Some(&details),
+    )
+    .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Longest suspension; anything beyond this should be a ban
+const MAX_SUSPENSION_HOURS: i64 = 365 * 24;
+
+/// Shared checks for ban/suspend: admins can't be restricted,
+/// and moderators can't restrict other moderators
+async fn validate_restriction(
+    pool: &PgPool,
+    moderator_id: Uuid,
+    moderator_role: &str,
+    target_user_id: Uuid,
+) -> Result<(), ApiError> {
+    if moderator_id == target_user_id {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            &i18n::CANNOT_RESTRICT_SELF,
+        ));
+    }
+
+    let target = sqlx::query!("SELECT role FROM users WHERE id = $1", target_user_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or(ApiError::new(StatusCode::NOT_FOUND, &i18n::USER_NOT_FOUND))?;
+
+    if target.role == "admin" {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            &i18n::CANNOT_RESTRICT_ADMIN,
+        ));
+    }
+    if target.role == "moderator" && moderator_role != "admin" {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            &i18n::CANNOT_RESTRICT_MODERATOR,
+        ));
+    }
+
+    Ok(())
+}
+
+pub(crate) async fn insert_moderation_action(
+    pool: &PgPool,
+    target_user_id: Uuid,
+    actor_user_id: Uuid,
+    action: &str,
+    reason: &str,
+    expires_at: Option<chrono::DateTime<chrono::Utc>>,
+) -> Result<(), ApiError> {
+    sqlx::query!(
+        r#"
+        INSERT INTO moderation_actions (target_user_id, actor_user_id, action, reason, expires_at)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        target_user_id,
+        actor_user_id,
+        action,
+        reason,
+        expires_at
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(())
+}
+
+/// Ban a user, hiding everything they posted, and sign them out everywhere.
+/// Returns how many sessions were revoked.
+pub(crate) async fn apply_ban(
+    state: &AppState,
+    sessions: &SessionBackend,
+    target_user_id: Uuid,
+    actor_user_id: Uuid,
+    reason: &str,
+) -> Result<u64, ApiError> {
+    let pool = &state.pool;
+    let username = sqlx::query_scalar!(
+        "UPDATE users SET banned_at = COALESCE(banned_at, NOW()) WHERE id = $1 RETURNING username",
+        target_user_id
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    insert_moderation_action(pool, target_user_id, actor_user_id, "ban", reason, None).await?;
+
+    // Their profile and posts disappear from public view
+    crate::user::invalidate_profile(&*state.cache, &username).await;
+    crate::feed::invalidate(&*state.cache).await;
+    state.search_index.sync_user(pool, target_user_id).await;
+
+    crate::session::revoke_user_sessions(pool, sessions, target_user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into())
+}
+
+/// Suspend a user until `until` and sign them out everywhere.
+/// Returns how many sessions were revoked.
+pub(crate) async fn apply_suspension(
+    pool: &PgPool,
+    sessions: &SessionBackend,
+    target_user_id: Uuid,
+    actor_user_id: Uuid,
+    reason: &str,
+    until: chrono::DateTime<chrono::Utc>,
+) -> Result<u64, ApiError> {
+    sqlx::query!(
+        "UPDATE users SET suspended_until = $2 WHERE id = $1",
+        target_user_id,
+        until
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    insert_moderation_action(
+        pool,
+        target_user_id,
+        actor_user_id,
+        "suspend",
+        reason,
+        Some(until),
+    )
+    .await?;
+
+    crate::session::revoke_user_sessions(pool, sessions, target_user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into())
+}
+
+pub async fn ban_user(
+    State(sessions): State<SessionBackend>,
+    State(state): State<AppState>,
+    ModeratorUser(moderator): ModeratorUser,
+    session: Session,
+    Path(target_user_id): Path<Uuid>,
+    ValidatedJson(payload): ValidatedJson<BanRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let reason = payload.reason.trim();
+    validate_restriction(&state.pool, moderator.id, &moderator.role, target_user_id).await?;
+
+    let revoked = apply_ban(&state, &sessions, target_user_id, moderator.id, reason).await?;
+
+    crate::audit::record(
+        &state.pool,
+        &session,
+        moderator.id,
+        "moderation.user_banned",
+        Some(target_user_id),
+        Some(reason),
+    )
+    .await?;
+
+    Ok(Json(serde_json::json!({ "revoked_sessions": revoked })))
+}
+
+pub async fn suspend_user(
+    State(pool): State<PgPool>,
+    State(sessions): State<SessionBackend>,
+    ModeratorUser(moderator): ModeratorUser,
+    session: Session,
+    Path(target_user_id): Path<Uuid>,
+    ValidatedJson(payload): ValidatedJson<SuspendRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let reason = payload.reason.trim();
+    validate_restriction(&pool, moderator.id, &moderator.role, target_user_id).await?;
+
+    let suspended_until = chrono::Utc::now() + chrono::Duration::hours(payload.duration_hours);
+    let revoked = apply_suspension(
+        &pool,
+        &sessions,
+        target_user_id,
+        moderator.id,
+        reason,
+        suspended_until,
+    )
+    .await?;
+
+    let details = format!("until {}: {}", suspended_until.to_rfc3339(), reason);
+    crate::audit::record(
+        &pool,
+        &session,
+        moderator.id,
+        "moderation.user_suspended",
         Some(target_user_id),
-        ip_address.as_deref(),
-        user_agent.as_deref(),
+        Some(&details),
     )
     .await?;
 
-    Ok((StatusCode::OK, "Password reset successfully".to_string()))
+    Ok(Json(serde_json::json!({
+        "suspended_until": suspended_until,
+        "revoked_sessions": revoked
+    })))
+}
+
+/// Lift a ban and/or suspension
+pub async fn unban_user(
+    State(pool): State<PgPool>,
+    State(cache): State<Arc<dyn Cache>>,
+    State(search_index): State<Indexer>,
+    ModeratorUser(moderator): ModeratorUser,
+    session: Session,
+    Path(target_user_id): Path<Uuid>,
+    ValidatedJson(payload): ValidatedJson<LiftRestrictionRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let reason = payload.reason.as_deref().unwrap_or("").trim();
+
+    let username = sqlx::query_scalar!(
+        "UPDATE users SET banned_at = NULL, suspended_until = NULL WHERE id = $1 RETURNING username",
+        target_user_id
+    )
+    .fetch_optional(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or(ApiError::new(StatusCode::NOT_FOUND, &i18n::USER_NOT_FOUND))?;
+
+    insert_moderation_action(&pool, target_user_id, moderator.id, "lift", reason, None).await?;
+
+    crate::user::invalidate_profile(&*cache, &username).await;
+    crate::feed::invalidate(&*cache).await;
+    search_index.sync_user(&pool, target_user_id).await;
+
+    crate::audit::record(
+        &pool,
+        &session,
+        moderator.id,
+        "moderation.user_unbanned",
+        Some(target_user_id),
+        Some(reason).filter(|r| !r.is_empty()),
+    )
+    .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn update_user_role(
+    State(pool): State<PgPool>,
+    State(sessions): State<SessionBackend>,
+    AdminUser(admin): AdminUser,
+    session: Session,
+    Path(target_user_id): Path<Uuid>,
+    ValidatedJson(payload): ValidatedJson<UpdateRoleRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let role = payload.role.trim().to_lowercase();
+    let previous_role = set_role(&pool, &sessions, target_user_id, &role).await?;
+
+    if previous_role != role {
+        let details = format!("{} -> {}", previous_role, role);
+        crate::audit::record(
+            &pool,
+            &session,
+            admin.id,
+            "admin.role_changed",
+            Some(target_user_id),
+            Some(&details),
+        )
+        .await?;
+    }
+
+    Ok(Json(serde_json::json!({ "role": role })))
+}
+
+/// Give a user one of `user`, `moderator` or `admin`, signing them out everywhere if it
+/// changed. Returns the role they had. The last admin can't be demoted.
+pub async fn set_role(
+    pool: &PgPool,
+    sessions: &SessionBackend,
+    user_id: Uuid,
+    role: &str,
+) -> Result<String, ApiError> {
+    if !ROLES.contains(&role) {
+        return Err((StatusCode::BAD_REQUEST, format!("Unknown role: {}", role)).into());
+    }
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    // Lock the admin rows so two concurrent demotions can't both pass the last-admin check
+    let admins: Vec<Uuid> =
+        sqlx::query_scalar!("SELECT id FROM users WHERE role = 'admin' FOR UPDATE")
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let previous_role = sqlx::query_scalar!("SELECT role FROM users WHERE id = $1", user_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or(ApiError::new(StatusCode::NOT_FOUND, &i18n::USER_NOT_FOUND))?;
+
+    if previous_role == role {
+        return Ok(previous_role);
+    }
+
+    if previous_role == "admin" && admins.len() <= 1 {
+        return Err(ApiError::new(StatusCode::BAD_REQUEST, &i18n::LAST_ADMIN));
+    }
+
+    sqlx::query!("UPDATE users SET role = $2 WHERE id = $1", user_id, role)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    // Their sessions have the old role cached; make them log in again
+    crate::session::revoke_user_sessions(pool, sessions, user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(previous_role)
 }
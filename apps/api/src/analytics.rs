@@ -0,0 +1,253 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+use crate::error::ApiError;
+use crate::extractors::AdminUser;
+use crate::state::AppState;
+use crate::validation::{Validate, ValidatedJson, ValidationErrors};
+
+const MAX_BATCH: usize = 50;
+// How many days /admin/analytics returns by default, and at most
+const DEFAULT_DAYS: i64 = 30;
+const MAX_DAYS: i64 = 365;
+// Paths, interactions and referrers listed in /admin/analytics
+const TOP_KEYS: i64 = 20;
+
+#[derive(Deserialize)]
+pub struct IngestRequest {
+    pub events: Vec<ClientEvent>,
+}
+
+#[derive(Deserialize)]
+pub struct ClientEvent {
+    /// page_view or interaction
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub path: String,
+    /// What was interacted with; required for interactions
+    pub name: Option<String>,
+    /// document.referrer; only its host is kept
+    pub referrer: Option<String>,
+}
+
+impl Validate for IngestRequest {
+    fn normalize(&mut self) {
+        for event in &mut self.events {
+            // Query strings and fragments can carry tokens and search terms
+            if let Some(end) = event.path.find(['?', '#']) {
+                event.path.truncate(end);
+            }
+            event.referrer = event
+                .referrer
+                .as_deref()
+                .and_then(|referrer| Url::parse(referrer).ok())
+                .and_then(|url| url.host_str().map(str::to_lowercase));
+            if event.kind != "interaction" {
+                event.name = None;
+            }
+        }
+    }
+
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        if self.events.is_empty() || self.events.len() > MAX_BATCH {
+            errors.add(
+                "events",
+                format!("Must have between 1 and {} events", MAX_BATCH),
+            );
+        }
+        for event in &self.events {
+            errors.one_of("type", &event.kind, &["page_view", "interaction"]);
+            if !event.path.starts_with('/') {
+                errors.add("path", "Must start with /");
+            }
+            errors.length("path", &event.path, 1, 200);
+            if event.kind == "interaction" {
+                errors.length("name", event.name.as_deref().unwrap_or_default(), 1, 100);
+            }
+        }
+        errors.into_result()
+    }
+}
+
+#[derive(Serialize)]
+pub struct DailyAnalytics {
+    pub day: chrono::NaiveDate,
+    pub page_views: i64,
+    pub interactions: i64,
+}
+
+#[derive(Serialize)]
+pub struct KeyCount {
+    pub key: String,
+    pub count: i64,
+}
+
+#[derive(Serialize)]
+pub struct AnalyticsReport {
+    /// Oldest first; today's counts are partial
+    pub days: Vec<DailyAnalytics>,
+    pub top_pages: Vec<KeyCount>,
+    pub top_interactions: Vec<KeyCount>,
+    pub top_referrers: Vec<KeyCount>,
+}
+
+#[derive(Deserialize)]
+pub struct AnalyticsQuery {
+    pub days: Option<i64>,
+}
+
+/// Record a batch of anonymous events from the web client. Nothing identifying is stored,
+/// not even for logged in users, and links within the site don't count as referrers.
+pub async fn ingest(
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<IngestRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let own_host = Url::parse(&state.config.frontend_url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_lowercase));
+
+    let mut kinds = Vec::with_capacity(payload.events.len());
+    let mut paths = Vec::with_capacity(payload.events.len());
+    let mut names = Vec::with_capacity(payload.events.len());
+    let mut referrers = Vec::with_capacity(payload.events.len());
+    for event in payload.events {
+        kinds.push(event.kind);
+        paths.push(event.path);
+        names.push(event.name);
+        referrers.push(
+            event
+                .referrer
+                .filter(|host| Some(host) != own_host.as_ref()),
+        );
+    }
+
+    // One insert for the whole batch
+    sqlx::query!(
+        r#"
+        INSERT INTO analytics_events (kind, path, name, referrer)
+        SELECT * FROM UNNEST($1::text[], $2::text[], $3::text[], $4::text[])
+        "#,
+        &kinds,
+        &paths,
+        &names as &[Option<String>],
+        &referrers as &[Option<String>]
+    )
+    .execute(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Count every day still held raw into analytics_daily, then drop raw events older than
+/// the analytics_events retention policy. Whole days are dropped, and only after being
+/// counted, so each kept count is complete once its day is over. Returns how many raw
+/// events were dropped.
+pub async fn aggregate(pool: &PgPool) -> Result<u64, String> {
+    let raw_days = crate::retention::days(pool, crate::retention::DataClass::AnalyticsEvents)
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+
+    sqlx::query!(
+        r#"
+        WITH events AS (
+            SELECT (created_at AT TIME ZONE 'UTC')::date AS day, kind, path, name, referrer
+            FROM analytics_events
+        )
+        INSERT INTO analytics_daily (day, metric, key, count)
+        SELECT day, 'page_view', path, COUNT(*) FROM events
+        WHERE kind = 'page_view'
+        GROUP BY day, path
+        UNION ALL
+        SELECT day, 'interaction', name, COUNT(*) FROM events
+        WHERE kind = 'interaction'
+        GROUP BY day, name
+        UNION ALL
+        SELECT day, 'referrer', referrer, COUNT(*) FROM events
+        WHERE kind = 'page_view' AND referrer IS NOT NULL
+        GROUP BY day, referrer
+        ON CONFLICT (day, metric, key) DO UPDATE SET count = EXCLUDED.count
+        "#
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let dropped = sqlx::query!(
+        r#"
+        DELETE FROM analytics_events
+        WHERE $1::int IS NOT NULL
+          AND created_at < (date_trunc('day', NOW() AT TIME ZONE 'UTC') - make_interval(days => $1))
+            AT TIME ZONE 'UTC'
+        "#,
+        raw_days
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    tx.commit().await.map_err(|e| e.to_string())?;
+    Ok(dropped.rows_affected())
+}
+
+/// Page views and interactions per day, with the top pages, interactions and referrers,
+/// over the last `days` days (30 by default)
+pub async fn get_report(
+    State(pool): State<PgPool>,
+    _: AdminUser,
+    Query(query): Query<AnalyticsQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let days = query.days.unwrap_or(DEFAULT_DAYS).clamp(1, MAX_DAYS) as i32;
+
+    let series = sqlx::query_as!(
+        DailyAnalytics,
+        r#"
+        SELECT day,
+            COALESCE(SUM(count) FILTER (WHERE metric = 'page_view'), 0)::bigint AS "page_views!",
+            COALESCE(SUM(count) FILTER (WHERE metric = 'interaction'), 0)::bigint AS "interactions!"
+        FROM analytics_daily
+        WHERE day > (NOW() AT TIME ZONE 'UTC')::date - $1::int
+        GROUP BY day
+        ORDER BY day
+        "#,
+        days
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let top = |metric: &'static str| {
+        sqlx::query_as!(
+            KeyCount,
+            r#"
+            SELECT key, SUM(count)::bigint AS "count!"
+            FROM analytics_daily
+            WHERE metric = $1 AND day > (NOW() AT TIME ZONE 'UTC')::date - $2::int
+            GROUP BY key
+            ORDER BY 2 DESC, key
+            LIMIT $3
+            "#,
+            metric,
+            days,
+            TOP_KEYS
+        )
+        .fetch_all(&pool)
+    };
+    let internal = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+
+    Ok(Json(AnalyticsReport {
+        days: series,
+        top_pages: top("page_view").await.map_err(internal)?,
+        top_interactions: top("interaction").await.map_err(internal)?,
+        top_referrers: top("referrer").await.map_err(internal)?,
+    }))
+}
@@ -1,14 +1,26 @@
-use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 use tower_sessions::Session;
 
+use crate::error::{ApiError, Message};
+use crate::extractors::{AdminUser, AuthUser};
+use crate::i18n;
+use crate::validation::{Validate, ValidatedJson, ValidationErrors};
+
 #[derive(Serialize)]
 pub struct Announcement {
     pub id: uuid::Uuid,
     pub content: String,
     pub author_id: uuid::Uuid,
     pub created_at: chrono::DateTime<chrono::Utc>,
+    pub reactions: serde_json::Value, // emoji -> count
+    pub viewer_reactions: Vec<String>,
 }
 
 #[derive(Serialize)]
@@ -18,21 +30,124 @@ pub struct AnnouncementWithAuthor {
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub author_name: String,
     pub author_avatar: Option<String>,
+    pub reactions: serde_json::Value, // emoji -> count
+    pub viewer_reactions: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct BannerAnnouncement {
+    pub id: uuid::Uuid,
+    pub content: String,
+    pub severity: String,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
 }
 
 #[derive(Deserialize)]
 pub struct CreateAnnouncementRequest {
     pub content: String,
+    pub kind: Option<String>,     // "standard" (default) or "banner"
+    pub severity: Option<String>, // banners only: "info" (default), "warning", "critical"
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
+impl Validate for CreateAnnouncementRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.length("content", &self.content, 1, MAX_ANNOUNCEMENT_LENGTH);
+
+        let kind = self.kind.as_deref().unwrap_or("standard");
+        errors.one_of("kind", kind, ANNOUNCEMENT_KINDS);
+        if let (Some(severity), "banner") = (&self.severity, kind) {
+            errors.one_of("severity", severity, BANNER_SEVERITIES);
+        }
+
+        if let Some(expires_at) = self.expires_at {
+            if expires_at <= chrono::Utc::now() {
+                errors.add("expires_at", "Must be in the future");
+            }
+        }
+        errors.into_result()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ReactRequest {
+    pub emoji: String,
+}
+
+impl Validate for ReactRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.one_of("emoji", &self.emoji, ANNOUNCEMENT_REACTIONS);
+        errors.into_result()
+    }
+}
+
+#[derive(Serialize)]
+pub struct ReactResponse {
+    pub reacted: bool,
+    pub reactions: serde_json::Value,
+}
+
+const ANNOUNCEMENT_KINDS: &[&str] = &["standard", "banner"];
+const BANNER_SEVERITIES: &[&str] = &["info", "warning", "critical"];
+const MAX_ANNOUNCEMENT_LENGTH: usize = 5000;
+
+pub const ANNOUNCEMENT_REACTIONS: &[&str] = &["👍", "🎉", "❤️"];
+
+/// The newest announcement. Banners aren't announcements here; they come from get_banner.
 pub async fn get_latest(
     State(pool): State<PgPool>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+    viewer: Option<AuthUser>,
+) -> Result<impl IntoResponse, ApiError> {
+    // Logged-in viewer (if any), used to mark which reactions are theirs
+    let viewer = viewer.map(|user| user.id);
+
     let announcement = sqlx::query_as!(
         Announcement,
         r#"
-        SELECT id, content, author_id, created_at
+        SELECT
+            a.id,
+            a.content,
+            a.author_id,
+            a.created_at,
+            COALESCE(
+                (SELECT jsonb_object_agg(emoji, n) FROM (
+                    SELECT emoji, COUNT(*) AS n FROM announcement_reactions
+                    WHERE announcement_id = a.id GROUP BY emoji
+                ) counts),
+                '{}'::jsonb
+            ) as "reactions!",
+            ARRAY(
+                SELECT emoji FROM announcement_reactions
+                WHERE announcement_id = a.id AND user_id = $1
+            ) as "viewer_reactions!"
+        FROM announcements a
+        WHERE a.kind = 'standard'
+        ORDER BY a.created_at DESC
+        LIMIT 1
+        "#,
+        viewer
+    )
+    .fetch_optional(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(announcement))
+}
+
+/// Get the active emergency banner, if any.
+/// Polled on every page load, so it is unauthenticated and briefly cacheable.
+pub async fn get_banner(State(pool): State<PgPool>) -> Result<impl IntoResponse, ApiError> {
+    let banner = sqlx::query_as!(
+        BannerAnnouncement,
+        r#"
+        SELECT id, content, severity as "severity!", expires_at, created_at
         FROM announcements
+        WHERE kind = 'banner'
+          AND severity IS NOT NULL
+          AND (expires_at IS NULL OR expires_at > NOW())
         ORDER BY created_at DESC
         LIMIT 1
         "#
@@ -41,13 +156,20 @@ pub async fn get_latest(
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    Ok(Json(announcement))
+    Ok((
+        [(header::CACHE_CONTROL, "public, max-age=30")],
+        Json(banner),
+    ))
 }
 
 /// Get last 10 announcements with author info
 pub async fn get_recent(
     State(pool): State<PgPool>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+    viewer: Option<AuthUser>,
+) -> Result<impl IntoResponse, ApiError> {
+    // Logged-in viewer (if any), used to mark which reactions are theirs
+    let viewer = viewer.map(|user| user.id);
+
     let announcements = sqlx::query_as!(
         AnnouncementWithAuthor,
         r#"
@@ -56,12 +178,25 @@ pub async fn get_recent(
             a.content,
             a.created_at,
             u.display_name as author_name,
-            u.avatar_url as author_avatar
+            u.avatar_url as author_avatar,
+            COALESCE(
+                (SELECT jsonb_object_agg(emoji, n) FROM (
+                    SELECT emoji, COUNT(*) AS n FROM announcement_reactions
+                    WHERE announcement_id = a.id GROUP BY emoji
+                ) counts),
+                '{}'::jsonb
+            ) as "reactions!",
+            ARRAY(
+                SELECT emoji FROM announcement_reactions
+                WHERE announcement_id = a.id AND user_id = $1
+            ) as "viewer_reactions!"
         FROM announcements a
         JOIN users u ON a.author_id = u.id
+        WHERE a.kind = 'standard'
         ORDER BY a.created_at DESC
         LIMIT 10
-        "#
+        "#,
+        viewer
     )
     .fetch_all(&pool)
     .await
@@ -73,7 +208,11 @@ pub async fn get_recent(
 /// Get all announcements with author info
 pub async fn get_all(
     State(pool): State<PgPool>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+    viewer: Option<AuthUser>,
+) -> Result<impl IntoResponse, ApiError> {
+    // Logged-in viewer (if any), used to mark which reactions are theirs
+    let viewer = viewer.map(|user| user.id);
+
     let announcements = sqlx::query_as!(
         AnnouncementWithAuthor,
         r#"
@@ -82,11 +221,24 @@ pub async fn get_all(
             a.content,
             a.created_at,
             u.display_name as author_name,
-            u.avatar_url as author_avatar
+            u.avatar_url as author_avatar,
+            COALESCE(
+                (SELECT jsonb_object_agg(emoji, n) FROM (
+                    SELECT emoji, COUNT(*) AS n FROM announcement_reactions
+                    WHERE announcement_id = a.id GROUP BY emoji
+                ) counts),
+                '{}'::jsonb
+            ) as "reactions!",
+            ARRAY(
+                SELECT emoji FROM announcement_reactions
+                WHERE announcement_id = a.id AND user_id = $1
+            ) as "viewer_reactions!"
         FROM announcements a
         JOIN users u ON a.author_id = u.id
+        WHERE a.kind = 'standard'
         ORDER BY a.created_at DESC
-        "#
+        "#,
+        viewer
     )
     .fetch_all(&pool)
     .await
@@ -95,11 +247,10 @@ pub async fn get_all(
     Ok(Json(announcements))
 }
 
-/// Get total count of announcements
-pub async fn get_count(
-    State(pool): State<PgPool>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    let count: Option<i64> = sqlx::query_scalar!(r#"SELECT count(*) FROM announcements"#)
+/// Get total count of announcements (banners aren't counted)
+pub async fn get_count(State(pool): State<PgPool>) -> Result<impl IntoResponse, ApiError> {
+    let count: Option<i64> =
+        sqlx::query_scalar!(r#"SELECT count(*) FROM announcements WHERE kind = 'standard'"#)
         .fetch_one(&pool)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
@@ -109,40 +260,102 @@ pub async fn get_count(
 
 pub async fn create(
     State(pool): State<PgPool>,
+    AdminUser(admin): AdminUser,
     session: Session,
-    Json(payload): Json<CreateAnnouncementRequest>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    // 1. Get logged in user ID
-    let user_id: uuid::Uuid = match session.get("user_id").await {
-        Ok(Some(id)) => id,
-        Ok(None) => return Err((StatusCode::UNAUTHORIZED, "Not logged in".to_string())),
-        Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
-    };
-
-    // 2. Check if user is admin
-    let user = sqlx::query!("SELECT role FROM users WHERE id = $1", user_id)
-        .fetch_optional(&pool)
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    ValidatedJson(payload): ValidatedJson<CreateAnnouncementRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    // 1. Severity only applies to banners
+    let kind = payload.kind.as_deref().unwrap_or("standard");
+    let severity = (kind == "banner").then(|| payload.severity.as_deref().unwrap_or("info"));
+
+    // 2. Create Announcement
+    let announcement_id = sqlx::query_scalar!(
+        "INSERT INTO announcements (content, author_id, kind, severity, expires_at) VALUES ($1, $2, $3, $4, $5) RETURNING id",
+        payload.content,
+        admin.id,
+        kind,
+        severity,
+        payload.expires_at
+    )
+    .fetch_one(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    let role = match user {
-        Some(u) => u.role,
-        None => return Err((StatusCode::UNAUTHORIZED, "User not found".to_string())),
-    };
+    let details = format!("{} announcement {}", kind, announcement_id);
+    crate::audit::record(
+        &pool,
+        &session,
+        admin.id,
+        "announcement.created",
+        None,
+        Some(&details),
+    )
+    .await?;
 
-    if role != "admin" {
-        return Err((StatusCode::FORBIDDEN, "Admins only".to_string()));
-    }
+    Ok((
+        StatusCode::CREATED,
+        Message::new(&i18n::ANNOUNCEMENT_CREATED),
+    ))
+}
 
-    // 3. Create Announcement
-    sqlx::query!(
-        "INSERT INTO announcements (content, author_id) VALUES ($1, $2)",
-        payload.content,
-        user_id
+/// Toggle the current user's reaction on an announcement and return the new counts
+pub async fn react(
+    State(pool): State<PgPool>,
+    AuthUser { id: user_id, .. }: AuthUser,
+    Path(announcement_id): Path<uuid::Uuid>,
+    ValidatedJson(payload): ValidatedJson<ReactRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    // Remove the reaction if it exists, otherwise add it
+    let removed = sqlx::query!(
+        "DELETE FROM announcement_reactions WHERE announcement_id = $1 AND user_id = $2 AND emoji = $3",
+        announcement_id,
+        user_id,
+        payload.emoji
     )
     .execute(&pool)
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    Ok((StatusCode::CREATED, "Announcement created"))
+    let reacted = removed.rows_affected() == 0;
+    if reacted {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO announcement_reactions (announcement_id, user_id, emoji)
+            VALUES ($1, $2, $3)
+            ON CONFLICT DO NOTHING
+            "#,
+            announcement_id,
+            user_id,
+            payload.emoji
+        )
+        .execute(&pool)
+        .await;
+
+        match result {
+            Ok(_) => {}
+            Err(sqlx::Error::Database(db_err)) if db_err.is_foreign_key_violation() => {
+                return Err(ApiError::new(
+                    StatusCode::NOT_FOUND,
+                    &i18n::ANNOUNCEMENT_NOT_FOUND,
+                ));
+            }
+            Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into()),
+        }
+    }
+
+    let reactions = sqlx::query_scalar!(
+        r#"
+        SELECT COALESCE(jsonb_object_agg(emoji, n), '{}'::jsonb) as "reactions!"
+        FROM (
+            SELECT emoji, COUNT(*) AS n FROM announcement_reactions
+            WHERE announcement_id = $1 GROUP BY emoji
+        ) counts
+        "#,
+        announcement_id
+    )
+    .fetch_one(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(ReactResponse { reacted, reactions }))
 }
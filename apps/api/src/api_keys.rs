@@ -0,0 +1,227 @@
+use axum::{
+    extract::{Path, Request, State},
+    http::{HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use tower_sessions::Session;
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::extractors::AdminUser;
+use crate::i18n;
+use crate::validation::{Validate, ValidatedJson, ValidationErrors};
+
+const MAX_NAME_LENGTH: usize = 100;
+// Every key starts with this, so leaked keys are easy to spot (e.g. by secret scanners)
+const KEY_PREFIX: &str = "prx_";
+// Characters of the key kept in the clear to tell keys apart
+const SHOWN_PREFIX_CHARS: usize = 12;
+
+/// The API key a /public/v1 request was made with, set by `require_key`
+#[derive(Clone, Copy, Debug)]
+pub struct ApiKeyId(pub Uuid);
+
+/// A key as listed for admins, with its usage
+#[derive(Serialize)]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub name: String,
+    /// e.g. "prx_1a2b3c4d", the rest is never shown again
+    pub key_prefix: String,
+    pub created_by: Option<Uuid>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub requests_today: i64,
+    pub requests_total: i64,
+}
+
+#[derive(Deserialize)]
+pub struct CreateApiKeyRequest {
+    /// The application the key is for, e.g. "Stats bot"
+    pub name: String,
+}
+
+impl Validate for CreateApiKeyRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.length("name", self.name.trim(), 1, MAX_NAME_LENGTH);
+        errors.into_result()
+    }
+}
+
+fn hash_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+/// The key from `Authorization: Bearer <key>` or `X-API-Key: <key>`
+fn presented_key(headers: &HeaderMap) -> Option<&str> {
+    let bearer = headers
+        .get("authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "));
+    bearer
+        .or_else(|| headers.get("x-api-key").and_then(|h| h.to_str().ok()))
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+}
+
+/// Middleware for /public/v1: reject requests without a valid, unrevoked key, and count
+/// the ones served against it. Rate limits further in are keyed by the key.
+pub async fn require_key(
+    State(pool): State<PgPool>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let Some(key) = presented_key(request.headers()) else {
+        return ApiError::new(StatusCode::UNAUTHORIZED, &i18n::API_KEY_REQUIRED).into_response();
+    };
+
+    let api_key_id = sqlx::query_scalar!(
+        "UPDATE api_keys SET last_used_at = NOW() WHERE key_hash = $1 AND revoked_at IS NULL RETURNING id",
+        hash_key(key)
+    )
+    .fetch_optional(&pool)
+    .await;
+    let api_key_id = match api_key_id {
+        Ok(Some(id)) => id,
+        Ok(None) => {
+            return ApiError::new(StatusCode::UNAUTHORIZED, &i18n::INVALID_API_KEY).into_response()
+        }
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+        }
+    };
+
+    request.extensions_mut().insert(ApiKeyId(api_key_id));
+    let response = next.run(request).await;
+
+    // Rate limited requests weren't served, so they don't count
+    if response.status() != StatusCode::TOO_MANY_REQUESTS {
+        let counted = sqlx::query!(
+            r#"
+            INSERT INTO api_key_usage (api_key_id, day, requests)
+            VALUES ($1, CURRENT_DATE, 1)
+            ON CONFLICT (api_key_id, day) DO UPDATE SET requests = api_key_usage.requests + 1
+            "#,
+            api_key_id
+        )
+        .execute(&pool)
+        .await;
+        if let Err(e) = counted {
+            tracing::error!("Failed to count usage of API key {}: {}", api_key_id, e);
+        }
+    }
+
+    response
+}
+
+/// Every key, newest first, with today's and all time request counts (admins only)
+pub async fn list(State(pool): State<PgPool>, _: AdminUser) -> Result<impl IntoResponse, ApiError> {
+    let keys = sqlx::query_as!(
+        ApiKey,
+        r#"
+        SELECT
+            k.id, k.name, k.key_prefix, k.created_by, k.last_used_at, k.revoked_at, k.created_at,
+            COALESCE((SELECT u.requests FROM api_key_usage u
+                      WHERE u.api_key_id = k.id AND u.day = CURRENT_DATE), 0) as "requests_today!",
+            COALESCE((SELECT SUM(u.requests) FROM api_key_usage u
+                      WHERE u.api_key_id = k.id), 0)::bigint as "requests_total!"
+        FROM api_keys k
+        ORDER BY k.created_at DESC
+        "#
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(keys))
+}
+
+/// Issue a key for an application. The key is only ever in this response.
+pub async fn create(
+    State(pool): State<PgPool>,
+    AdminUser(admin): AdminUser,
+    session: Session,
+    ValidatedJson(payload): ValidatedJson<CreateApiKeyRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let name = payload.name.trim();
+    let key = format!("{}{}", KEY_PREFIX, hex::encode(rand::random::<[u8; 32]>()));
+    let key_prefix = &key[..SHOWN_PREFIX_CHARS];
+
+    let id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO api_keys (name, key_prefix, key_hash, created_by)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id
+        "#,
+        name,
+        key_prefix,
+        hash_key(&key),
+        admin.id
+    )
+    .fetch_one(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    crate::audit::record(
+        &pool,
+        &session,
+        admin.id,
+        "admin.api_key_created",
+        None,
+        Some(&format!("{} ({})", name, key_prefix)),
+    )
+    .await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(serde_json::json!({
+            "id": id,
+            "name": name,
+            "key_prefix": key_prefix,
+            "key": key
+        })),
+    ))
+}
+
+/// Revoke a key; requests made with it are rejected from now on
+pub async fn revoke(
+    State(pool): State<PgPool>,
+    AdminUser(admin): AdminUser,
+    session: Session,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let key = sqlx::query!(
+        r#"
+        UPDATE api_keys SET revoked_at = COALESCE(revoked_at, NOW())
+        WHERE id = $1
+        RETURNING name, key_prefix
+        "#,
+        id
+    )
+    .fetch_optional(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or(ApiError::new(
+        StatusCode::NOT_FOUND,
+        &i18n::API_KEY_NOT_FOUND,
+    ))?;
+
+    crate::audit::record(
+        &pool,
+        &session,
+        admin.id,
+        "admin.api_key_revoked",
+        None,
+        Some(&format!("{} ({})", key.name, key.key_prefix)),
+    )
+    .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -6,15 +6,65 @@ use axum::{
 };
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
-use tower_sessions::Session;
 use uuid::Uuid;
 
+use crate::error::ApiError;
+use crate::extractors::{AuthUser, VerifiedUser};
+use crate::i18n;
+use crate::notifications::NotificationKind;
+use crate::spam::Content;
+use crate::state::AppState;
+use crate::validation::{Validate, ValidatedJson, ValidationErrors};
+
 #[derive(Deserialize)]
 pub struct ApplyRequest {
     pub message: String,
     pub links: Vec<String>,
 }
 
+impl Validate for ApplyRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.length("message", &self.message, 1, 2000);
+        if self.links.len() > 10 {
+            errors.add("links", "At most 10 links");
+        }
+        for link in &self.links {
+            errors.url("links", link);
+        }
+        errors.into_result()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ReviewRequest {
+    /// accepted, rejected, or pending to undo a decision
+    pub status: String,
+}
+
+impl Validate for ReviewRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        if !["pending", "accepted", "rejected"].contains(&self.status.as_str()) {
+            errors.add("status", "Must be pending, accepted or rejected");
+        }
+        errors.into_result()
+    }
+}
+
+/// An application as its reviewer sees it
+#[derive(Serialize)]
+pub struct Application {
+    pub id: Uuid,
+    pub username: String,
+    pub display_name: String,
+    pub avatar_url: Option<String>,
+    pub message: String,
+    pub links: Vec<String>,
+    pub status: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
 #[derive(Serialize)]
 pub struct ApplyResponse {
     pub id: Uuid,
@@ -22,46 +72,293 @@ pub struct ApplyResponse {
 }
 
 pub async fn apply(
-    State(pool): State<PgPool>,
+    State(state): State<AppState>,
     Path(project_id): Path<Uuid>,
-    session: Session,
-    Json(payload): Json<ApplyRequest>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    let user_id: Uuid = match session.get("user_id").await {
-        Ok(Some(id)) => id,
-        Ok(None) => return Err((StatusCode::UNAUTHORIZED, "Not logged in".to_string())),
-        Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
-    };
+    VerifiedUser(AuthUser { id: user_id, role }): VerifiedUser,
+    ValidatedJson(payload): ValidatedJson<ApplyRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let held = check_spam(&state, user_id, &role, &payload).await?;
+    let application = insert(
+        &state.pool,
+        Some(project_id),
+        None,
+        user_id,
+        &payload,
+        held.as_deref(),
+    )
+    .await?;
+    if held.is_none() {
+        notify_reviewer(&state, application.id).await;
+    }
+    Ok((StatusCode::CREATED, Json(application)))
+}
+
+/// Apply to a listing that's still open. The poster is notified like a project owner.
+pub async fn apply_to_listing(
+    State(state): State<AppState>,
+    Path(listing_id): Path<Uuid>,
+    VerifiedUser(AuthUser { id: user_id, role }): VerifiedUser,
+    ValidatedJson(payload): ValidatedJson<ApplyRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let listing = sqlx::query!(
+        r#"
+        SELECT l.poster_id, l.role,
+            l.status = 'open' AND (l.deadline IS NULL OR l.deadline > NOW()) AS "open!"
+        FROM listings l
+        JOIN users u ON u.id = l.poster_id
+        WHERE l.id = $1 AND u.banned_at IS NULL
+        "#,
+        listing_id
+    )
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or(ApiError::new(
+        StatusCode::NOT_FOUND,
+        &i18n::LISTING_NOT_FOUND,
+    ))?;
 
-    if payload.message.trim().is_empty() {
-        return Err((StatusCode::BAD_REQUEST, "Message cannot be empty".to_string()));
+    if listing.poster_id == user_id {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            &i18n::CANNOT_APPLY_OWN_LISTING,
+        ));
+    }
+    if !listing.open {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            &i18n::LISTING_CLOSED,
+        ));
+    }
+
+    let held = check_spam(&state, user_id, &role, &payload).await?;
+    let application = insert(
+        &state.pool,
+        None,
+        Some(listing_id),
+        user_id,
+        &payload,
+        held.as_deref(),
+    )
+    .await?;
+    if held.is_none() {
+        notify_reviewer(&state, application.id).await;
     }
 
-    let result = sqlx::query!(
+    Ok((StatusCode::CREATED, Json(application)))
+}
+
+/// Why an application looks like spam, if it does (see spam.rs)
+async fn check_spam(
+    state: &AppState,
+    applicant_id: Uuid,
+    role: &str,
+    payload: &ApplyRequest,
+) -> Result<Option<Vec<String>>, ApiError> {
+    let settings = crate::settings::get(state).await;
+    crate::spam::check(
+        state,
+        &settings,
+        applicant_id,
+        role,
+        Content::Application,
+        &payload.message,
+        &payload.links,
+    )
+    .await
+}
+
+/// An application to exactly one of a project or a listing, once per applicant.
+/// Held ones wait for a moderator before the reviewer sees them.
+async fn insert(
+    pool: &PgPool,
+    project_id: Option<Uuid>,
+    listing_id: Option<Uuid>,
+    applicant_id: Uuid,
+    payload: &ApplyRequest,
+    held_reasons: Option<&[String]>,
+) -> Result<ApplyResponse, ApiError> {
+    let result = sqlx::query_as!(
+        ApplyResponse,
         r#"
-        INSERT INTO applications (project_id, applicant_id, message, links)
-        VALUES ($1, $2, $3, $4)
+        INSERT INTO applications
+            (project_id, listing_id, applicant_id, message, links, held_at, held_reasons)
+        VALUES ($1, $2, $3, $4, $5, CASE WHEN $6::text[] IS NOT NULL THEN NOW() END, $6)
         RETURNING id, created_at
         "#,
         project_id,
-        user_id,
+        listing_id,
+        applicant_id,
         payload.message,
-        &payload.links
+        &payload.links,
+        held_reasons
     )
-    .fetch_one(&pool)
+    .fetch_one(pool)
     .await;
 
     match result {
-        Ok(row) => Ok((
-            StatusCode::CREATED,
-            Json(ApplyResponse {
-                id: row.id,
-                created_at: row.created_at,
-            }),
-        )),
+        Ok(application) => Ok(application),
         Err(sqlx::Error::Database(db_err)) if db_err.constraint() == Some("applications_project_id_applicant_id_key") => {
-            Err((StatusCode::CONFLICT, "You have already applied to this project".to_string()))
+            Err(ApiError::new(StatusCode::CONFLICT, &i18n::ALREADY_APPLIED_TO_PROJECT))
+        }
+        Err(sqlx::Error::Database(db_err)) if db_err.constraint() == Some("applications_listing_id_applicant_id_key") => {
+            Err(ApiError::new(StatusCode::CONFLICT, &i18n::ALREADY_APPLIED_TO_LISTING))
         }
-        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into()),
+    }
+}
+
+/// Accept or reject an application to one of the user's projects. Accepted applications
+/// count towards the applicant's reputation.
+pub async fn review(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+    Path((project_id, application_id)): Path<(Uuid, Uuid)>,
+    ValidatedJson(payload): ValidatedJson<ReviewRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    decide(&pool, user.id, Some(project_id), None, application_id, &payload.status).await
+}
+
+/// Accept or reject an application to one of the user's listings, as for projects
+pub async fn review_for_listing(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+    Path((listing_id, application_id)): Path<(Uuid, Uuid)>,
+    ValidatedJson(payload): ValidatedJson<ReviewRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    decide(&pool, user.id, None, Some(listing_id), application_id, &payload.status).await
+}
+
+/// Set the status of an application to the given project or listing, if `owner_id` owns it
+async fn decide(
+    pool: &PgPool,
+    owner_id: Uuid,
+    project_id: Option<Uuid>,
+    listing_id: Option<Uuid>,
+    application_id: Uuid,
+    status: &str,
+) -> Result<StatusCode, ApiError> {
+    let updated = sqlx::query!(
+        r#"
+        UPDATE applications a
+        SET status = $5,
+            reviewed_at = CASE
+                WHEN $5 = 'pending' THEN NULL
+                WHEN a.status = $5 THEN a.reviewed_at
+                ELSE NOW()
+            END
+        WHERE a.id = $1
+          AND a.project_id IS NOT DISTINCT FROM $2
+          AND a.listing_id IS NOT DISTINCT FROM $3
+          AND (
+              EXISTS (SELECT 1 FROM projects p WHERE p.id = a.project_id AND p.owner_id = $4)
+              OR EXISTS (SELECT 1 FROM listings l WHERE l.id = a.listing_id AND l.poster_id = $4)
+          )
+        "#,
+        application_id,
+        project_id,
+        listing_id,
+        owner_id,
+        status
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    if updated.rows_affected() == 0 {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            &i18n::APPLICATION_NOT_FOUND,
+        ));
+    }
+    Ok(StatusCode::OK)
+}
+
+/// Applications to one of the user's listings, oldest first
+pub async fn list_for_listing(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+    Path(listing_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let owned = sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM listings WHERE id = $1 AND poster_id = $2) AS "owned!""#,
+        listing_id,
+        user.id
+    )
+    .fetch_one(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if !owned {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            &i18n::LISTING_NOT_FOUND,
+        ));
+    }
+
+    let applications = sqlx::query_as!(
+        Application,
+        r#"
+        SELECT a.id, u.username, u.display_name, u.avatar_url, a.message, a.links, a.status,
+            a.created_at
+        FROM applications a
+        JOIN users u ON u.id = a.applicant_id
+        WHERE a.listing_id = $1 AND u.banned_at IS NULL AND a.held_at IS NULL
+        ORDER BY a.created_at
+        "#,
+        listing_id
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(applications))
+}
+
+/// Tell the project owner or listing poster about a new application. It's in either way,
+/// so a failure here is only logged.
+pub(crate) async fn notify_reviewer(state: &AppState, application_id: Uuid) {
+    let notified = async {
+        let application = sqlx::query!(
+            r#"
+            SELECT a.applicant_id, a.project_id, a.listing_id,
+                   COALESCE(p.owner_id, l.poster_id) AS "reviewer_id!",
+                   p.title AS "project_title?", p.slug AS "project_slug?",
+                   l.role AS "listing_role?"
+            FROM applications a
+            LEFT JOIN projects p ON p.id = a.project_id
+            LEFT JOIN listings l ON l.id = a.listing_id
+            WHERE a.id = $1
+            "#,
+            application_id
+        )
+        .fetch_one(&state.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let data = match application.listing_id {
+            Some(listing_id) => serde_json::json!({
+                "listing_id": listing_id,
+                "listing_role": application.listing_role,
+                "application_id": application_id,
+            }),
+            None => serde_json::json!({
+                "project_id": application.project_id,
+                "project_title": application.project_title,
+                "project_slug": application.project_slug,
+                "application_id": application_id,
+            }),
+        };
+        crate::notifications::notify(
+            state,
+            application.reviewer_id,
+            NotificationKind::Application,
+            application.applicant_id,
+            data,
+        )
+        .await
+    };
+
+    if let Err(e) = notified.await {
+        tracing::error!("Failed to notify reviewer of application {}: {}", application_id, e);
     }
 }
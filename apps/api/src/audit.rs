@@ -0,0 +1,342 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use futures_util::{stream::BoxStream, StreamExt};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Row};
+use tower_sessions::Session;
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::extractors::AdminUser;
+
+// Page size bounds for GET /admin/audit-log
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+
+#[derive(Deserialize)]
+pub struct AuditLogQuery {
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+    /// Exact action (e.g. "admin.role_changed"), or a prefix ending in "." (e.g. "moderation.")
+    pub action: Option<String>,
+    pub actor_user_id: Option<Uuid>,
+    pub target_user_id: Option<Uuid>,
+    pub since: Option<chrono::DateTime<chrono::Utc>>,
+    pub until: Option<chrono::DateTime<chrono::Utc>>,
+    /// "csv" or "json" to download every matching entry instead of a page
+    pub format: Option<String>,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct AuditLogEntry {
+    pub id: Uuid,
+    pub action: String,
+    pub details: Option<String>,
+    pub actor_user_id: Option<Uuid>,
+    pub actor_username: Option<String>,
+    pub target_user_id: Option<Uuid>,
+    pub target_username: Option<String>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Serialize)]
+pub struct AuditLogPage {
+    pub entries: Vec<AuditLogEntry>,
+    pub page: i64,
+    pub per_page: i64,
+    pub total: i64,
+}
+
+/// IP and user agent of the current session, as tracked in active_sessions
+async fn session_context(
+    session: &Session,
+    pool: &PgPool,
+) -> Result<(Option<String>, Option<String>), ApiError> {
+    let Some(session_id) = session.id().map(|id| id.to_string()) else {
+        return Ok((None, None));
+    };
+
+    let row =
+        sqlx::query("SELECT ip_address, user_agent FROM active_sessions WHERE session_id = $1")
+            .bind(session_id)
+            .fetch_optional(pool)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    if let Some(r) = row {
+        let ip_address: Option<String> = r
+            .try_get("ip_address")
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        let user_agent: Option<String> = r
+            .try_get("user_agent")
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        Ok((ip_address, user_agent))
+    } else {
+        Ok((None, None))
+    }
+}
+
+/// Write an audit log entry
+pub async fn insert(
+    pool: &PgPool,
+    action: &str,
+    details: Option<&str>,
+    actor_user_id: Option<Uuid>,
+    target_user_id: Option<Uuid>,
+    ip_address: Option<&str>,
+    user_agent: Option<&str>,
+) -> Result<(), ApiError> {
+    sqlx::query(
+        r#"
+        INSERT INTO audit_logs (action, details, actor_user_id, target_user_id, ip_address, user_agent)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+    )
+    .bind(action)
+    .bind(details)
+    .bind(actor_user_id)
+    .bind(target_user_id)
+    .bind(ip_address)
+    .bind(user_agent)
+    .execute(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(())
+}
+
+/// Record a privileged action taken by the logged in user, with their session's IP and user agent.
+/// Actions are namespaced by who may take them: "admin.*", "moderation.*", "announcement.*".
+pub async fn record(
+    pool: &PgPool,
+    session: &Session,
+    actor_user_id: Uuid,
+    action: &str,
+    target_user_id: Option<Uuid>,
+    details: Option<&str>,
+) -> Result<(), ApiError> {
+    let (ip_address, user_agent) = session_context(session, pool).await?;
+    insert(
+        pool,
+        action,
+        details,
+        Some(actor_user_id),
+        target_user_id,
+        ip_address.as_deref(),
+        user_agent.as_deref(),
+    )
+    .await
+}
+
+const FILTERS: &str = r#"
+    WHERE ($1::text IS NULL OR al.action = $1)
+      AND ($2::text IS NULL OR al.action LIKE $2)
+      AND ($3::uuid IS NULL OR al.actor_user_id = $3)
+      AND ($4::uuid IS NULL OR al.target_user_id = $4)
+      AND ($5::timestamptz IS NULL OR al.created_at >= $5)
+      AND ($6::timestamptz IS NULL OR al.created_at < $6)
+"#;
+
+const AUDIT_CSV_HEADER: &[&str] = &[
+    "id",
+    "action",
+    "details",
+    "actor_user_id",
+    "actor_username",
+    "target_user_id",
+    "target_username",
+    "ip_address",
+    "user_agent",
+    "created_at",
+];
+
+/// The audit log filters, shared by the page and the downloads
+struct Filters {
+    action: Option<String>,
+    action_prefix: Option<String>,
+    actor_user_id: Option<Uuid>,
+    target_user_id: Option<Uuid>,
+    since: Option<chrono::DateTime<chrono::Utc>>,
+    until: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl Filters {
+    /// Matching entries, newest first, as they're read; no `limit` streams them all
+    fn entries<'a>(
+        &'a self,
+        pool: &'a PgPool,
+        sql: &'a str,
+        limit: Option<i64>,
+        offset: i64,
+    ) -> BoxStream<'a, Result<AuditLogEntry, sqlx::Error>> {
+        sqlx::query_as::<_, AuditLogEntry>(sql)
+            .bind(&self.action)
+            .bind(&self.action_prefix)
+            .bind(self.actor_user_id)
+            .bind(self.target_user_id)
+            .bind(self.since)
+            .bind(self.until)
+            .bind(limit)
+            .bind(offset)
+            .fetch(pool)
+    }
+}
+
+// LIMIT NULL is no limit at all
+fn entries_sql() -> String {
+    format!(
+        r#"
+        SELECT
+            al.id,
+            al.action,
+            al.details,
+            al.actor_user_id,
+            actor.username AS actor_username,
+            al.target_user_id,
+            target.username AS target_username,
+            al.ip_address,
+            al.user_agent,
+            al.created_at
+        FROM audit_logs al
+        LEFT JOIN users actor ON actor.id = al.actor_user_id
+        LEFT JOIN users target ON target.id = al.target_user_id
+        {}
+        ORDER BY al.created_at DESC
+        LIMIT $7 OFFSET $8
+        "#,
+        FILTERS
+    )
+}
+
+impl AuditLogEntry {
+    fn csv_row(&self) -> [String; 10] {
+        let id = |id: Option<Uuid>| id.map(|id| id.to_string()).unwrap_or_default();
+        [
+            self.id.to_string(),
+            self.action.clone(),
+            self.details.clone().unwrap_or_default(),
+            id(self.actor_user_id),
+            self.actor_username.clone().unwrap_or_default(),
+            id(self.target_user_id),
+            self.target_username.clone().unwrap_or_default(),
+            self.ip_address.clone().unwrap_or_default(),
+            self.user_agent.clone().unwrap_or_default(),
+            self.created_at.to_rfc3339(),
+        ]
+    }
+}
+
+/// Paginated, filterable audit log (admins only). With `format=csv` or `format=json` it's
+/// a download of every matching entry instead.
+pub async fn list(
+    State(pool): State<PgPool>,
+    _: AdminUser,
+    Query(query): Query<AuditLogQuery>,
+) -> Result<Response, ApiError> {
+    let per_page = query
+        .per_page
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let page = query.page.unwrap_or(1).max(1);
+
+    // "moderation." matches every moderation action; anything else must match exactly
+    let (action, action_prefix) = match query.action.as_deref() {
+        Some(a) if a.ends_with('.') => (None, Some(format!("{}%", a))),
+        Some(a) => (Some(a.to_string()), None),
+        None => (None, None),
+    };
+    let filters = Filters {
+        action,
+        action_prefix,
+        actor_user_id: query.actor_user_id,
+        target_user_id: query.target_user_id,
+        since: query.since,
+        until: query.until,
+    };
+
+    match query.format.as_deref() {
+        None => {}
+        Some("csv") => {
+            let (mut writer, response) = crate::export::csv("audit-log.csv", AUDIT_CSV_HEADER);
+            tokio::spawn(async move {
+                let sql = entries_sql();
+                let mut entries = filters.entries(&pool, &sql, None, 0);
+                while let Some(entry) = entries.next().await {
+                    match entry {
+                        Ok(entry) => {
+                            if !writer.row(&entry.csv_row()).await {
+                                break;
+                            }
+                        }
+                        Err(e) => return writer.fail(e).await,
+                    }
+                }
+                writer.finish().await;
+            });
+            return Ok(response);
+        }
+        Some("json") => {
+            let (mut writer, response) = crate::export::json("audit-log.json");
+            tokio::spawn(async move {
+                let sql = entries_sql();
+                let mut entries = filters.entries(&pool, &sql, None, 0);
+                while let Some(entry) = entries.next().await {
+                    match entry {
+                        Ok(entry) => {
+                            if !writer.item(&entry).await {
+                                break;
+                            }
+                        }
+                        Err(e) => return writer.fail(e).await,
+                    }
+                }
+                writer.finish().await;
+            });
+            return Ok(response);
+        }
+        Some(_) => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "format must be csv or json".to_string(),
+            )
+                .into())
+        }
+    }
+
+    let total: i64 = sqlx::query_scalar(&format!(
+        "SELECT COUNT(*)::bigint FROM audit_logs al {}",
+        FILTERS
+    ))
+    .bind(&filters.action)
+    .bind(&filters.action_prefix)
+    .bind(filters.actor_user_id)
+    .bind(filters.target_user_id)
+    .bind(filters.since)
+    .bind(filters.until)
+    .fetch_one(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let sql = entries_sql();
+    let entries = filters
+        .entries(&pool, &sql, Some(per_page), (page - 1) * per_page)
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(AuditLogPage {
+        entries,
+        page,
+        per_page,
+        total,
+    })
+    .into_response())
+}
@@ -3,7 +3,7 @@ use argon2::{
     Argon2,
 };
 use axum::{
-    extract::{ConnectInfo, Path, Query, State},
+    extract::{Path, Query, State},
     http::StatusCode,
     response::{IntoResponse, Redirect},
     Json,
@@ -14,10 +14,20 @@ use oauth2::{
 };
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
-use std::net::SocketAddr;
 use tower_sessions::Session;
 use uuid::Uuid;
 
+use crate::client_ip::ClientIp;
+use crate::error::{ApiError, Message};
+use crate::extractors::AuthUser;
+use crate::geoip::GeoIp;
+use crate::i18n::{self, Locale};
+use crate::state::AppState;
+use crate::validation::{
+    normalize_line, normalize_text, Validate, ValidatedJson, ValidationErrors,
+    DISPLAY_NAME_MAX_LENGTH, USERNAME_MAX_LENGTH,
+};
+
 // request structure we get from the frontend
 #[derive(Deserialize)]
 pub struct SignupRequest {
@@ -25,8 +35,90 @@ pub struct SignupRequest {
     pub password: String,
     pub username: String,
     pub display_name: String,
+    /// Browser language (e.g. "es-MX"), for emails. Unsupported ones get English.
+    pub locale: Option<String>,
+    /// Required while the site is invite only
+    pub invite_code: Option<String>,
+    /// From the link emailed when let in from the waitlist
+    pub waitlist_token: Option<String>,
+    /// Kept with the waitlist entry when the waitlist is on
+    pub interest: Option<String>,
+}
+
+impl Validate for SignupRequest {
+    fn normalize(&mut self) {
+        normalize_line(&mut self.username);
+        normalize_line(&mut self.display_name);
+        self.interest.iter_mut().for_each(normalize_text);
+    }
+
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.email("email", &self.email);
+        errors.password("password", &self.password);
+        errors.username("username", &self.username);
+        errors.length("display_name", &self.display_name, 1, DISPLAY_NAME_MAX_LENGTH);
+        if let Some(interest) = &self.interest {
+            errors.length("interest", interest, 0, crate::waitlist::MAX_INTEREST_LENGTH);
+        }
+        errors.into_result()
+    }
+}
+
+/// A name from Google or GitHub, cleaned up and cut to length like one typed at signup
+fn provider_display_name(name: &str, fallback: &str) -> String {
+    let mut name = name.to_string();
+    normalize_line(&mut name);
+    if name.is_empty() {
+        name = fallback.to_string();
+    }
+    name.chars().take(DISPLAY_NAME_MAX_LENGTH).collect()
+}
+
+/// A username for someone signing up with Google or GitHub, from their email or login. It
+/// gets the same checks as one typed at signup; if it fails them, is taken or looks like one
+/// that is (ad0 for ado), a number goes on the end until it doesn't.
+pub async fn oauth_username(pool: &PgPool, wanted: &str) -> Result<String, sqlx::Error> {
+    // Leave room for the number
+    let mut base: String = wanted
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+        .take(USERNAME_MAX_LENGTH - 3)
+        .collect();
+    if !base.chars().any(|c| c.is_ascii_alphanumeric()) {
+        base = "user".to_string();
+    }
+
+    for n in 1..100 {
+        let candidate = if n == 1 {
+            base.clone()
+        } else {
+            format!("{}{}", base, n)
+        };
+        let mut errors = ValidationErrors::default();
+        errors.username("username", &candidate);
+        if errors.into_result().is_err() {
+            continue;
+        }
+        let lookalike = sqlx::query_scalar!(
+            "SELECT username FROM users WHERE username_skeleton = username_skeleton($1)",
+            candidate
+        )
+        .fetch_optional(pool)
+        .await?;
+        if lookalike.is_none() {
+            return Ok(candidate);
+        }
+    }
+
+    Ok(format!("user-{}", &Uuid::new_v4().simple().to_string()[..8]))
 }
 
+/// How long an email verification link stays valid
+pub(crate) const VERIFICATION_TOKEN_TTL_DAYS: i64 = 7;
+pub const PASSWORD_RESET_TOKEN_TTL_HOURS: i64 = 1;
+
 pub const RESERVED_USERNAMES: &[&str] = &[
     "login",
     "signup",
@@ -56,12 +148,22 @@ pub struct LoginRequest {
     pub password: String,
 }
 
+impl Validate for LoginRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.required("email", &self.email);
+        errors.required("password", &self.password);
+        errors.into_result()
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct GoogleUser {
     pub sub: String,
     pub email: String,
+    #[serde(default)]
+    pub email_verified: bool,
     pub name: String,
-    pub picture: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -70,55 +172,39 @@ pub struct GithubUser {
     pub email: Option<String>,
     pub name: Option<String>,
     pub login: String,
-    pub avatar_url: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct AuthRequest {
     pub code: String,
-    pub state: String,
-}
-
-#[derive(Serialize)]
-struct ResendEmailRequest {
-    from: String,
-    to: Vec<String>,
-    subject: String,
-    html: String,
 }
 
-async fn send_email(to: &str, subject: &str, html_body: &str) -> Result<(), String> {
-    let api_key =
-        std::env::var("RESEND_API_KEY").map_err(|_| "RESEND_API_KEY not set".to_string())?;
-    // Optionally allow configuring the FROM address, default to team@joinpraxis.me
-    let from_email =
-        std::env::var("MAIL_FROM").unwrap_or_else(|_| "team@joinpraxis.me".to_string());
-
-    let client = reqwest::Client::new();
-    let body = ResendEmailRequest {
-        from: from_email,
-        to: vec![to.to_string()],
-        subject: subject.to_string(),
-        html: html_body.to_string(),
-    };
+/// Clear verification, password reset and account merge tokens past their expiry.
+/// Returns the number of tokens cleared.
+pub async fn purge_expired_tokens(pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let resets = sqlx::query!(
+        r#"
+        UPDATE local_auths SET password_reset_token = NULL, password_reset_expires_at = NULL
+        WHERE password_reset_expires_at < NOW()
+        "#
+    )
+    .execute(pool)
+    .await?;
 
-    let res = client
-        .post("https://api.resend.com/emails")
-        .bearer_auth(api_key)
-        .json(&body)
-        .send()
-        .await
-        .map_err(|e| format!("Failed to send email request: {}", e))?;
+    let verifications = sqlx::query!(
+        r#"
+        UPDATE local_auths SET verification_token = NULL, verification_token_expires_at = NULL
+        WHERE verification_token_expires_at < NOW()
+        "#
+    )
+    .execute(pool)
+    .await?;
 
-    if !res.status().is_success() {
-        let text = res
-            .text()
-            .await
-            .unwrap_or_else(|_| "Unknown error".to_string());
-        return Err(format!("Resend API error: {}", text));
-    }
+    let merges = sqlx::query!("DELETE FROM account_merges WHERE expires_at < NOW()")
+        .execute(pool)
+        .await?;
 
-    Ok(())
+    Ok(resets.rows_affected() + verifications.rows_affected() + merges.rows_affected())
 }
 
 /*
@@ -130,28 +216,70 @@ async fn send_email(to: &str, subject: &str, html_body: &str) -> Result<(), Stri
 *
 * Returns:
 * success: Ok(impl IntoResponse
-* OR an error tuple: Err((StatusCode, String))
+* OR an error tuple: Err(ApiError)
 */
 pub async fn signup(
-    State(pool): State<PgPool>,
+    State(state): State<AppState>,
     session: Session,
     headers: axum::http::HeaderMap,
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
-    Json(payload): Json<SignupRequest>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+    ClientIp(ip): ClientIp,
+    ValidatedJson(payload): ValidatedJson<SignupRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let settings = crate::settings::get(&state).await;
+    if !settings.signups_enabled {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            &i18n::SIGNUPS_DISABLED,
+        ));
+    }
+    let invite_code = payload
+        .invite_code
+        .as_deref()
+        .filter(|code| !code.trim().is_empty());
+    let waitlist_token = payload
+        .waitlist_token
+        .as_deref()
+        .filter(|token| !token.trim().is_empty());
+    let locale = payload
+        .locale
+        .as_deref()
+        .map(Locale::from_tag)
+        .unwrap_or_default();
+
+    // An invite code or a waitlist link gets past both gates
+    let admitted = invite_code.is_some() || waitlist_token.is_some();
+    if settings.waitlist && !admitted {
+        crate::waitlist::add(
+            &state.pool,
+            &payload.email,
+            payload.interest.as_deref(),
+            locale,
+        )
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        // Same reply as joining the waitlist directly
+        return Ok((StatusCode::ACCEPTED, Message::new(&i18n::WAITLIST_JOINED)));
+    }
+    if settings.invite_only && !admitted {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            &i18n::SIGNUPS_INVITE_ONLY,
+        ));
+    }
+
     // check if email already exists
     let email_exists = sqlx::query!(
         "SELECT user_id FROM local_auths WHERE email = $1",
         payload.email
     )
-    .fetch_optional(&pool) // returns Some(row) if found, None if not
+    .fetch_optional(&state.pool) // returns Some(row) if found, None if not
     .await
     // convert crashes/errors into an HTTP 500 error string
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
     // is_some(): if row found, email is taken and return HTTP 409 conflict error
     if email_exists.is_some() {
-        return Err((StatusCode::CONFLICT, "Email already exists".to_string()));
+        return Err(ApiError::new(StatusCode::CONFLICT, &i18n::EMAIL_TAKEN));
     }
 
     // Sanitize inputs
@@ -160,21 +288,29 @@ pub async fn signup(
     let safe_username = payload.username.to_lowercase();
     let safe_display_name = &payload.display_name;
 
-    if RESERVED_USERNAMES.contains(&safe_username.as_str()) {
-        return Err((StatusCode::BAD_REQUEST, "Username is reserved".to_string()));
-    }
-
-    // check if username already exists
-    let username_exists = sqlx::query!("SELECT id FROM users WHERE username = $1", safe_username)
-        .fetch_optional(&pool)
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    // check if username already exists, or one that looks the same (ad0 for ado)
+    let lookalike = sqlx::query_scalar!(
+        "SELECT username FROM users WHERE username_skeleton = username_skeleton($1)",
+        safe_username
+    )
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    if username_exists.is_some() {
-        return Err((
-            StatusCode::CONFLICT,
-            "That username is already taken".to_string(),
-        ));
+    match lookalike {
+        Some(taken) if taken == safe_username => {
+            return Err(ApiError::new(
+                StatusCode::CONFLICT,
+                &i18n::USERNAME_ALREADY_TAKEN,
+            ));
+        }
+        Some(_) => {
+            return Err(ApiError::new(
+                StatusCode::CONFLICT,
+                &i18n::USERNAME_TOO_SIMILAR,
+            ));
+        }
+        None => {}
     }
 
     // create random salt string
@@ -189,10 +325,13 @@ pub async fn signup(
 
     // Generate Verification Token
     let verification_token = Uuid::new_v4().to_string();
+    let verification_expires_at =
+        chrono::Utc::now() + chrono::Duration::days(VERIFICATION_TOKEN_TTL_DAYS);
 
     // start SQL transaction to insert `users` and `local_auths` tables
     // Transaction ensures everything or nothing is executed
-    let mut tx = pool
+    let mut tx = state
+        .pool
         .begin()
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
@@ -200,9 +339,10 @@ pub async fn signup(
     // Create User
     let user_id = sqlx::query!(
         // `RETURNING id` is a Postgres feature that returns the UUID it just generated
-        "INSERT INTO users (username, display_name) VALUES ($1, $2) RETURNING id",
+        "INSERT INTO users (username, display_name, locale) VALUES ($1, $2, $3) RETURNING id",
         safe_username,
-        safe_display_name
+        safe_display_name,
+        locale.as_str()
     )
     .fetch_one(&mut *tx)
     .await
@@ -211,51 +351,62 @@ pub async fn signup(
 
     // Create Local Auth
     sqlx::query!(
-        "INSERT INTO local_auths (user_id, email, password_hash, verification_token) VALUES ($1, $2, $3, $4)",
+        "INSERT INTO local_auths (user_id, email, password_hash, verification_token, verification_token_expires_at) VALUES ($1, $2, $3, $4, $5)",
         user_id,
         payload.email,
         password_hash,
-        verification_token
+        verification_token,
+        verification_expires_at
     )
     .execute(&mut *tx)
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
+    if let Some(code) = invite_code {
+        let redeemed = crate::invites::redeem(&mut tx, code, user_id)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        if !redeemed {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                &i18n::INVALID_INVITE,
+            ));
+        }
+    }
+    if let Some(token) = waitlist_token {
+        let redeemed = crate::waitlist::redeem(&mut tx, token, user_id)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        if !redeemed {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                &i18n::INVALID_SIGNUP_LINK,
+            ));
+        }
+    }
+
     tx.commit()
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    state.search_index.sync_user(&state.pool, user_id).await;
 
-    // Send Verification Email via Resend
-    let frontend_url =
-        std::env::var("FRONTEND_URL").unwrap_or_else(|_| "http://localhost:3000".to_string());
-
-    let verify_link = format!("{}/verify-email?token={}", frontend_url, verification_token);
-    let email_body = format!(
-        r#"
-        <div style="font-family: sans-serif; max-width: 600px; margin: 0 auto;">
-            <h2>Welcome to Praxis!</h2>
-            <p>Please verify your email address by clicking the button below:</p>
-            <a href="{}" style="display: inline-block; background-color: #000; color: #fff; padding: 10px 20px; text-decoration: none; border-radius: 5px; margin: 20px 0;">Verify Email</a>
-            <p>Or copy and paste this link into your browser:</p>
-            <p><a href="{}">{}</a></p>
-        </div>
-        "#,
-        verify_link, verify_link, verify_link
-    );
-
-    // We spawn this so it doesn't block the response, or we can await it if we want to ensure it sent.
-    // Awaiting is safer for now to report errors, but might slow down signup.
-    // Logging error if it fails but not failing the signup is a good middle ground.
-    if let Err(e) = send_email(&payload.email, "Verify your email", &email_body).await {
+    // Queue the verification email; the job worker retries if sending fails
+    if let Err(e) =
+        crate::email::send_verification_email(&state, &payload.email, &verification_token, locale)
+            .await
+    {
         tracing::error!(
-            "Failed to send verification email to {}: {}",
+            "Failed to queue verification email to {}: {}",
             payload.email,
             e
         );
         // Note: User is created but email failed. They can use "Resend Verification" later.
     }
 
-    // Log the user in
+    // Log the user in, under a fresh session ID
+    crate::session::rotate_id(&state.pool, &session)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
     session
         .insert("user_id", user_id)
         .await
@@ -274,11 +425,12 @@ pub async fn signup(
 
         // This is async but not critical path for response success, but good to await
         crate::session::create_session(
-            &pool,
+            &state.pool,
+            &state.geoip,
             user_id,
             session_id.to_string(),
             &headers,
-            Some(addr.ip().to_string()),
+            Some(ip.to_string()),
             expires_at,
         )
         .await
@@ -290,10 +442,7 @@ pub async fn signup(
 
     tracing::info!("Signup successful for user_id: {}", user_id);
 
-    Ok((
-        StatusCode::CREATED,
-        "User created successfully. Please verify your email.".to_string(),
-    ))
+    Ok((StatusCode::CREATED, Message::new(&i18n::SIGNED_UP)))
 }
 
 #[derive(Deserialize)]
@@ -301,12 +450,25 @@ pub struct VerifyEmailRequest {
     pub token: String,
 }
 
+impl Validate for VerifyEmailRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.required("token", &self.token);
+        errors.into_result()
+    }
+}
+
 pub async fn verify_email(
     State(pool): State<PgPool>,
-    Json(payload): Json<VerifyEmailRequest>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+    ValidatedJson(payload): ValidatedJson<VerifyEmailRequest>,
+) -> Result<impl IntoResponse, ApiError> {
     let result = sqlx::query!(
-        "UPDATE local_auths SET verified = TRUE, verification_token = NULL WHERE verification_token = $1 RETURNING user_id",
+        r#"
+        UPDATE local_auths SET verified = TRUE, verification_token = NULL, verification_token_expires_at = NULL
+        WHERE verification_token = $1
+          AND (verification_token_expires_at IS NULL OR verification_token_expires_at > NOW())
+        RETURNING user_id
+        "#,
         payload.token
     )
     .fetch_optional(&pool)
@@ -314,10 +476,10 @@ pub async fn verify_email(
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
     match result {
-        Some(_) => Ok((StatusCode::OK, "Email verified successfully".to_string())),
-        None => Err((
+        Some(_) => Ok((StatusCode::OK, Message::new(&i18n::EMAIL_VERIFIED))),
+        None => Err(ApiError::new(
             StatusCode::BAD_REQUEST,
-            "Invalid or expired verification token".to_string(),
+            &i18n::INVALID_VERIFICATION_TOKEN,
         )),
     }
 }
@@ -327,82 +489,91 @@ pub struct ResendVerificationRequest {
     pub email: String,
 }
 
+impl Validate for ResendVerificationRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.email("email", &self.email);
+        errors.into_result()
+    }
+}
+
 pub async fn resend_verification(
-    State(pool): State<PgPool>,
-    Json(payload): Json<ResendVerificationRequest>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<ResendVerificationRequest>,
+) -> Result<impl IntoResponse, ApiError> {
     // Check if user exists and is not verified
     let row = sqlx::query!(
-        "SELECT verified FROM local_auths WHERE email = $1",
+        r#"
+        SELECT l.verified, u.locale
+        FROM local_auths l
+        JOIN users u ON u.id = l.user_id
+        WHERE l.email = $1
+        "#,
         payload.email
     )
-    .fetch_optional(&pool)
+    .fetch_optional(&state.pool)
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
     if let Some(record) = row {
         if record.verified.unwrap_or(false) {
-            return Err((
+            return Err(ApiError::new(
                 StatusCode::BAD_REQUEST,
-                "Email already verified".to_string(),
+                &i18n::EMAIL_ALREADY_VERIFIED,
             ));
         }
 
         // Generate new token
         let verification_token = Uuid::new_v4().to_string();
+        let verification_expires_at =
+            chrono::Utc::now() + chrono::Duration::days(VERIFICATION_TOKEN_TTL_DAYS);
 
         // Update DB
         sqlx::query!(
-            "UPDATE local_auths SET verification_token = $1 WHERE email = $2",
+            "UPDATE local_auths SET verification_token = $1, verification_token_expires_at = $2 WHERE email = $3",
             verification_token,
+            verification_expires_at,
             payload.email
         )
-        .execute(&pool)
+        .execute(&state.pool)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-        // Send Email via Resend
-        let frontend_url =
-            std::env::var("FRONTEND_URL").unwrap_or_else(|_| "http://localhost:3000".to_string());
-
-        let verify_link = format!("{}/verify-email?token={}", frontend_url, verification_token);
-        let email_body = format!(
-            r#"
-            <div style="font-family: sans-serif; max-width: 600px; margin: 0 auto;">
-                <h2>Verify your email</h2>
-                <p>You requested a new verification link. Click below to verify:</p>
-                <a href="{}" style="display: inline-block; background-color: #000; color: #fff; padding: 10px 20px; text-decoration: none; border-radius: 5px; margin: 20px 0;">Verify Email</a>
-            </div>
-            "#,
-            verify_link
-        );
-
-        if let Err(e) = send_email(&payload.email, "Verify your email", &email_body).await {
-            tracing::error!("Failed to resend verification email: {}", e);
+        if let Err(e) = crate::email::send_verification_email(
+            &state,
+            &payload.email,
+            &verification_token,
+            Locale::from_tag(&record.locale),
+        )
+        .await
+        {
+            tracing::error!("Failed to queue verification email: {}", e);
             return Err((
                 StatusCode::INTERNAL_SERVER_ERROR,
                 "Failed to send email".to_string(),
-            ));
+            )
+                .into());
         }
 
-        Ok((StatusCode::OK, "Verification email sent".to_string()))
+        Ok((StatusCode::OK, Message::new(&i18n::VERIFICATION_SENT)))
     } else {
         // Return OK even if email not found to prevent enumeration, or Bad Request?
         // For now, let's be honest for UX (or Bad Request if typical auth)
         // Better security practice: "If that email exists, we sent a link."
         Ok((
             StatusCode::OK,
-            "If that email exists, we sent a verification link.".to_string(),
+            Message::new(&i18n::VERIFICATION_SENT_IF_EXISTS),
         ))
     }
 }
 pub async fn login(
     State(pool): State<PgPool>,
+    State(geoip): State<GeoIp>,
     session: Session,
     headers: axum::http::HeaderMap,
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
-    Json(payload): Json<LoginRequest>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+    ClientIp(ip): ClientIp,
+    ValidatedJson(payload): ValidatedJson<LoginRequest>,
+) -> Result<impl IntoResponse, ApiError> {
     // find user by email
     let user = sqlx::query!(
         "SELECT user_id, password_hash FROM local_auths WHERE email = $1",
@@ -416,9 +587,9 @@ pub async fn login(
     let user = match user {
         Some(u) => u,
         None => {
-            return Err((
+            return Err(ApiError::new(
                 StatusCode::UNAUTHORIZED,
-                "Invalid email or password".to_string(),
+                &i18n::INVALID_CREDENTIALS,
             ));
         }
     };
@@ -428,9 +599,9 @@ pub async fn login(
         Ok(hash) => hash,
         Err(e) => {
             tracing::error!("Corrupted password hash for user {}: {}", user.user_id, e);
-            return Err((
+            return Err(ApiError::new(
                 StatusCode::UNAUTHORIZED,
-                "Invalid email or password".to_string(),
+                &i18n::INVALID_CREDENTIALS,
             ));
         }
     };
@@ -440,12 +611,17 @@ pub async fn login(
         .verify_password(payload.password.as_bytes(), &parsed_hash)
         .map_err(|e| {
             tracing::warn!("Failed login attempt for user {}: {}", user.user_id, e);
-            (
-                StatusCode::UNAUTHORIZED,
-                "Invalid email or password".to_string(),
-            )
+            ApiError::new(StatusCode::UNAUTHORIZED, &i18n::INVALID_CREDENTIALS)
         })?;
 
+    // Banned/suspended accounts can't log in
+    crate::user::ensure_can_login(&pool, user.user_id).await?;
+
+    // A fresh session ID for the logged in (or 2FA pending) session
+    crate::session::rotate_id(&pool, &session)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
     // Check if user has 2FA enabled
     let has_2fa = crate::totp::has_2fa_enabled(&pool, user.user_id).await?;
 
@@ -458,10 +634,8 @@ pub async fn login(
 
         tracing::info!("2FA required for user_id: {}", user.user_id);
 
-        return Ok(Json(serde_json::json!({
-            "requires_2fa": true,
-            "message": "2FA verification required"
-        })));
+        return Ok(Message::new(&i18n::TWO_FACTOR_REQUIRED)
+            .json(serde_json::json!({ "requires_2fa": true })));
     }
     // No 2FA - complete login directly
     session
@@ -479,10 +653,11 @@ pub async fn login(
         let expires_at = chrono::Utc::now() + chrono::Duration::hours(24);
         crate::session::create_session(
             &pool,
+            &geoip,
             user.user_id,
             session_id.to_string(),
             &headers,
-            Some(addr.ip().to_string()),
+            Some(ip.to_string()),
             expires_at,
         )
         .await
@@ -495,10 +670,7 @@ pub async fn login(
     tracing::info!("Login successful for user_id: {}", user.user_id);
 
     // return success
-    Ok(Json(serde_json::json!({
-        "success": true,
-        "message": "Login successful"
-    })))
+    Ok(Message::new(&i18n::LOGGED_IN).json(serde_json::json!({ "success": true })))
 }
 
 // google oauth handling
@@ -524,7 +696,11 @@ fn oauth_client() -> BasicClient {
     .set_redirect_uri(RedirectUrl::new(redirect_url).expect("Missing GOOGLE_REDIRECT_URL"))
 }
 
-pub async fn google_login() -> impl IntoResponse {
+pub async fn google_login(
+    session: Session,
+    Query(invite): Query<crate::invites::InviteQuery>,
+) -> impl IntoResponse {
+    crate::invites::save_to_session(&session, invite).await;
     let client = oauth_client();
 
     // generate random csrf token and create auth URL
@@ -540,12 +716,13 @@ pub async fn google_login() -> impl IntoResponse {
 
 // google oauth callback
 pub async fn google_callback(
-    State(pool): State<PgPool>,
+    State(state): State<AppState>,
+    current_user: Option<AuthUser>,
     session: Session,
     headers: axum::http::HeaderMap,
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    ClientIp(ip): ClientIp,
     Query(query): Query<AuthRequest>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+) -> Result<impl IntoResponse, ApiError> {
     let client = oauth_client();
 
     // exchange code for token
@@ -556,8 +733,8 @@ pub async fn google_callback(
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
     // get user info by token
-    let client = reqwest::Client::new();
-    let google_user: GoogleUser = client
+    let google_user: GoogleUser = state
+        .http_client
         .get("https://www.googleapis.com/oauth2/v3/userinfo")
         .bearer_auth(token.access_token().secret())
         .send()
@@ -568,27 +745,17 @@ pub async fn google_callback(
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
     // Get frontend URL for redirects
-    let frontend_url =
-        std::env::var("FRONTEND_URL").unwrap_or_else(|_| "http://localhost:3000".to_string());
-    let frontend_url = frontend_url
-        .split(',')
-        .next()
-        .unwrap_or("http://localhost:3000")
-        .trim()
-        .to_string();
+    let frontend_url = &state.config.frontend_url;
 
     // Check if user is already logged in (linking flow from settings page)
-    let existing_session_user: Option<Uuid> = session
-        .get("user_id")
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let existing_session_user = current_user.map(|user| user.id);
 
     // Check if this Google account is already linked to a user
     let oauth_user = sqlx::query!(
         "SELECT user_id FROM oauth_connections WHERE provider = 'google' AND provider_id = $1",
         google_user.sub
     )
-    .fetch_optional(&pool)
+    .fetch_optional(&state.pool)
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
@@ -615,42 +782,98 @@ pub async fn google_callback(
     } else {
         // Check local_auths by email
         let local_user = sqlx::query!(
-            "SELECT user_id FROM local_auths WHERE email = $1",
+            "SELECT user_id, verified FROM local_auths WHERE email = $1",
             google_user.email
         )
-        .fetch_optional(&pool)
+        .fetch_optional(&state.pool)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
         if let Some(lu) = local_user {
+            // Only sign in to it if both sides proved they own the email
+            if !(lu.verified.unwrap_or(false) && google_user.email_verified) {
+                crate::merge::request_link(
+                    &state,
+                    lu.user_id,
+                    "google",
+                    &google_user.sub,
+                    &google_user.email,
+                )
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+                return Ok(Redirect::to(&format!(
+                    "{}/login?error=merge_required",
+                    frontend_url
+                )));
+            }
             lu.user_id
         } else {
+            let settings = crate::settings::get(&state).await;
+            if !settings.signups_enabled {
+                return Ok(Redirect::to(&format!(
+                    "{}/login?error=signups_disabled",
+                    frontend_url
+                )));
+            }
+            let invite_code = crate::invites::take_from_session(&session).await;
+            if settings.waitlist && invite_code.is_none() {
+                crate::waitlist::add(&state.pool, &google_user.email, None, Locale::default())
+                    .await
+                    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+                return Ok(Redirect::to(&format!(
+                    "{}/login?error=waitlisted",
+                    frontend_url
+                )));
+            }
+            if settings.invite_only && invite_code.is_none() {
+                return Ok(Redirect::to(&format!(
+                    "{}/login?error=invite_required",
+                    frontend_url
+                )));
+            }
+
             // Create new user
-            let mut tx = pool
+            let mut tx = state
+                .pool
                 .begin()
                 .await
                 .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
+            let username = oauth_username(
+                &state.pool,
+                google_user.email.split('@').next().unwrap_or_default(),
+            )
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
             let new_user_id = sqlx::query!(
                 "INSERT INTO users (username, display_name) VALUES ($1, $2) RETURNING id",
-                google_user
-                    .email
-                    .split('@')
-                    .next()
-                    .unwrap_or("user")
-                    .to_lowercase(),
-                google_user.name
+                username,
+                provider_display_name(&google_user.name, &google_user.email)
             )
             .fetch_one(&mut *tx)
             .await
             .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
             .id;
 
+            if let Some(code) = &invite_code {
+                let redeemed = crate::invites::redeem(&mut tx, code, new_user_id)
+                    .await
+                    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+                if !redeemed {
+                    return Ok(Redirect::to(&format!(
+                        "{}/login?error=invalid_invite",
+                        frontend_url
+                    )));
+                }
+            }
+
             // No local_auth record for OAuth users - they can set a password later
 
             tx.commit()
                 .await
                 .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            state.search_index.sync_user(&state.pool, new_user_id).await;
 
             new_user_id
         }
@@ -666,7 +889,7 @@ pub async fn google_callback(
         token.access_token().secret(),
         google_user.email
     )
-    .execute(&pool)
+    .execute(&state.pool)
     .await
     .map_err(|e| {
         tracing::error!("Failed to upsert oauth_connection: {}", e);
@@ -675,6 +898,18 @@ pub async fn google_callback(
 
     // Set session and create active session (only for login, not linking)
     if !is_linking {
+        if let Some(restriction) = crate::user::account_restriction(&state.pool, user_id).await? {
+            return Ok(Redirect::to(&format!(
+                "{}/login?error={}",
+                frontend_url,
+                restriction.code()
+            )));
+        }
+
+        crate::session::rotate_id(&state.pool, &session)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
         session
             .insert("user_id", user_id)
             .await
@@ -688,11 +923,12 @@ pub async fn google_callback(
         if let Some(session_id) = session.id() {
             let expires_at = chrono::Utc::now() + chrono::Duration::hours(24);
             crate::session::create_session(
-                &pool,
+                &state.pool,
+                &state.geoip,
                 user_id,
                 session_id.to_string(),
                 &headers,
-                Some(addr.ip().to_string()),
+                Some(ip.to_string()),
                 expires_at,
             )
             .await
@@ -733,7 +969,11 @@ fn github_oauth_client() -> BasicClient {
     .set_redirect_uri(RedirectUrl::new(redirect_url).expect("Invalid GITHUB_REDIRECT_URL"))
 }
 
-pub async fn github_login() -> impl IntoResponse {
+pub async fn github_login(
+    session: Session,
+    Query(invite): Query<crate::invites::InviteQuery>,
+) -> impl IntoResponse {
+    crate::invites::save_to_session(&session, invite).await;
     let client = github_oauth_client();
 
     // generate random csrf token and create auth URL
@@ -747,12 +987,13 @@ pub async fn github_login() -> impl IntoResponse {
 }
 
 pub async fn github_callback(
-    State(pool): State<PgPool>,
+    State(state): State<AppState>,
+    current_user: Option<AuthUser>,
     session: Session,
     headers: axum::http::HeaderMap,
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    ClientIp(ip): ClientIp,
     Query(query): Query<AuthRequest>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+) -> Result<impl IntoResponse, ApiError> {
     let client = github_oauth_client();
 
     // exchange code for token
@@ -766,7 +1007,7 @@ pub async fn github_callback(
         })?;
 
     // get user info from GitHub
-    let http_client = reqwest::Client::new();
+    let http_client = &state.http_client;
     let user_resp = http_client
         .get("https://api.github.com/user")
         .header("User-Agent", "praxis-app")
@@ -806,8 +1047,9 @@ pub async fn github_callback(
     // If it is null, call /user/emails.
     // For this implementation, let's add a quick fetch for emails if null.
 
-    let email = if let Some(e) = github_user.email {
-        e
+    // GitHub only shows verified addresses on profiles
+    let (email, email_verified) = if let Some(e) = github_user.email {
+        (e, true)
     } else {
         #[derive(Deserialize)]
         struct GithubEmail {
@@ -832,28 +1074,18 @@ pub async fn github_callback(
             .iter()
             .find(|e| e.primary && e.verified)
             .or_else(|| emails.first()) // fallback to any email?
-            .map(|e| e.email.clone())
-            .ok_or((
+            .map(|e| (e.email.clone(), e.verified))
+            .ok_or(ApiError::new(
                 StatusCode::BAD_REQUEST,
-                "No email found for GitHub user".to_string(),
+                &i18n::GITHUB_NO_EMAIL,
             ))?
     };
 
     // Get frontend URL for redirects
-    let frontend_url =
-        std::env::var("FRONTEND_URL").unwrap_or_else(|_| "http://localhost:3000".to_string());
-    let frontend_url = frontend_url
-        .split(',')
-        .next()
-        .unwrap_or("http://localhost:3000")
-        .trim()
-        .to_string();
+    let frontend_url = &state.config.frontend_url;
 
     // Check if user is already logged in (linking flow from settings page)
-    let existing_session_user: Option<Uuid> = session
-        .get("user_id")
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let existing_session_user = current_user.map(|user| user.id);
 
     // Check if this GitHub account is already linked to a user
     let github_provider_id = github_user.id.to_string();
@@ -861,7 +1093,7 @@ pub async fn github_callback(
         "SELECT user_id FROM oauth_connections WHERE provider = 'github' AND provider_id = $1",
         github_provider_id
     )
-    .fetch_optional(&pool)
+    .fetch_optional(&state.pool)
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
@@ -887,27 +1119,76 @@ pub async fn github_callback(
         ))?
     } else {
         // Check local_auths by email
-        let local_user = sqlx::query!("SELECT user_id FROM local_auths WHERE email = $1", email)
-            .fetch_optional(&pool)
-            .await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        let local_user = sqlx::query!(
+            "SELECT user_id, verified FROM local_auths WHERE email = $1",
+            email
+        )
+        .fetch_optional(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
         if let Some(lu) = local_user {
+            // Only sign in to it if both sides proved they own the email
+            if !(lu.verified.unwrap_or(false) && email_verified) {
+                crate::merge::request_link(
+                    &state,
+                    lu.user_id,
+                    "github",
+                    &github_provider_id,
+                    &email,
+                )
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+                return Ok(Redirect::to(&format!(
+                    "{}/login?error=merge_required",
+                    frontend_url
+                )));
+            }
             lu.user_id
         } else {
+            let settings = crate::settings::get(&state).await;
+            if !settings.signups_enabled {
+                return Ok(Redirect::to(&format!(
+                    "{}/login?error=signups_disabled",
+                    frontend_url
+                )));
+            }
+            let invite_code = crate::invites::take_from_session(&session).await;
+            if settings.waitlist && invite_code.is_none() {
+                crate::waitlist::add(&state.pool, &email, None, Locale::default())
+                    .await
+                    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+                return Ok(Redirect::to(&format!(
+                    "{}/login?error=waitlisted",
+                    frontend_url
+                )));
+            }
+            if settings.invite_only && invite_code.is_none() {
+                return Ok(Redirect::to(&format!(
+                    "{}/login?error=invite_required",
+                    frontend_url
+                )));
+            }
+
             // Create new user
-            let mut tx = pool
+            let mut tx = state
+                .pool
                 .begin()
                 .await
                 .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-            let display_name = github_user
-                .name
-                .unwrap_or_else(|| github_user.login.clone());
+            let display_name = provider_display_name(
+                github_user.name.as_deref().unwrap_or_default(),
+                &github_user.login,
+            );
+
+            let username = oauth_username(&state.pool, &github_user.login)
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
             let new_user_id = sqlx::query!(
                 "INSERT INTO users (username, display_name) VALUES ($1, $2) RETURNING id",
-                github_user.login.to_lowercase(),
+                username,
                 display_name
             )
             .fetch_one(&mut *tx)
@@ -915,11 +1196,24 @@ pub async fn github_callback(
             .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
             .id;
 
+            if let Some(code) = &invite_code {
+                let redeemed = crate::invites::redeem(&mut tx, code, new_user_id)
+                    .await
+                    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+                if !redeemed {
+                    return Ok(Redirect::to(&format!(
+                        "{}/login?error=invalid_invite",
+                        frontend_url
+                    )));
+                }
+            }
+
             // No local_auth record for OAuth users - they can set a password later
 
             tx.commit()
                 .await
                 .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            state.search_index.sync_user(&state.pool, new_user_id).await;
 
             new_user_id
         }
@@ -935,7 +1229,7 @@ pub async fn github_callback(
         token.access_token().secret(),
         email
     )
-    .execute(&pool)
+    .execute(&state.pool)
     .await
     .map_err(|e| {
         tracing::error!("Failed to upsert oauth_connection: {}", e);
@@ -944,6 +1238,18 @@ pub async fn github_callback(
 
     // Set session and create active session (only for login, not linking)
     if !is_linking {
+        if let Some(restriction) = crate::user::account_restriction(&state.pool, user_id).await? {
+            return Ok(Redirect::to(&format!(
+                "{}/login?error={}",
+                frontend_url,
+                restriction.code()
+            )));
+        }
+
+        crate::session::rotate_id(&state.pool, &session)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
         session
             .insert("user_id", user_id)
             .await
@@ -957,11 +1263,12 @@ pub async fn github_callback(
         if let Some(session_id) = session.id() {
             let expires_at = chrono::Utc::now() + chrono::Duration::hours(24);
             crate::session::create_session(
-                &pool,
+                &state.pool,
+                &state.geoip,
                 user_id,
                 session_id.to_string(),
                 &headers,
-                Some(addr.ip().to_string()),
+                Some(ip.to_string()),
                 expires_at,
             )
             .await
@@ -981,7 +1288,7 @@ pub async fn github_callback(
 
 pub async fn logout(session: Session) -> impl IntoResponse {
     let _ = session.delete().await;
-    Ok::<_, (StatusCode, String)>((StatusCode::OK, "Logged out successfully".to_string()))
+    Ok::<_, ApiError>((StatusCode::OK, Message::new(&i18n::LOGGED_OUT)))
 }
 
 #[derive(Deserialize)]
@@ -990,18 +1297,27 @@ pub struct ChangePasswordRequest {
     pub new_password: String,
 }
 
+impl Validate for ChangePasswordRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.required("current_password", &self.current_password);
+        errors.password("new_password", &self.new_password);
+        if self.new_password == self.current_password {
+            errors.add(
+                "new_password",
+                "New password cannot be the same as current password",
+            );
+        }
+        errors.into_result()
+    }
+}
+
 pub async fn change_password(
     State(pool): State<PgPool>,
+    AuthUser { id: user_id, .. }: AuthUser,
     session: Session,
-    Json(payload): Json<ChangePasswordRequest>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    // Get user_id from session
-    let user_id: Uuid = session
-        .get("user_id")
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-        .ok_or((StatusCode::UNAUTHORIZED, "Not logged in".to_string()))?;
-
+    ValidatedJson(payload): ValidatedJson<ChangePasswordRequest>,
+) -> Result<impl IntoResponse, ApiError> {
     // Get current password hash
     let user = sqlx::query!(
         "SELECT password_hash FROM local_auths WHERE user_id = $1",
@@ -1010,7 +1326,7 @@ pub async fn change_password(
     .fetch_optional(&pool)
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-    .ok_or((StatusCode::NOT_FOUND, "User not found".to_string()))?;
+    .ok_or(ApiError::new(StatusCode::NOT_FOUND, &i18n::USER_NOT_FOUND))?;
 
     // Verify current password
     let parsed_hash = PasswordHash::new(&user.password_hash).map_err(|e| {
@@ -1023,28 +1339,7 @@ pub async fn change_password(
 
     Argon2::default()
         .verify_password(payload.current_password.as_bytes(), &parsed_hash)
-        .map_err(|_| {
-            (
-                StatusCode::UNAUTHORIZED,
-                "Current password is incorrect".to_string(),
-            )
-        })?;
-
-    // Validate new password (minimum length)
-    if payload.new_password.len() < 6 {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            "New password must be at least 6 characters".to_string(),
-        ));
-    }
-
-    // Check if new password is the same as current password
-    if payload.new_password == payload.current_password {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            "New password cannot be the same as current password".to_string(),
-        ));
-    }
+        .map_err(|_| ApiError::new(StatusCode::UNAUTHORIZED, &i18n::CURRENT_PASSWORD_INCORRECT))?;
 
     // Hash new password
     let salt = SaltString::generate(&mut OsRng);
@@ -1063,9 +1358,13 @@ pub async fn change_password(
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
+    crate::session::rotate_id(&pool, &session)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
     tracing::info!("Password changed for user_id: {}", user_id);
 
-    Ok((StatusCode::OK, "Password changed successfully".to_string()))
+    Ok((StatusCode::OK, Message::new(&i18n::PASSWORD_CHANGED)))
 }
 
 #[derive(Deserialize)]
@@ -1073,23 +1372,37 @@ pub struct ForgotPasswordRequest {
     pub email: String,
 }
 
+impl Validate for ForgotPasswordRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.email("email", &self.email);
+        errors.into_result()
+    }
+}
+
 pub async fn forgot_password(
-    State(pool): State<PgPool>,
-    Json(payload): Json<ForgotPasswordRequest>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<ForgotPasswordRequest>,
+) -> Result<impl IntoResponse, ApiError> {
     // Check if user exists
     let user = sqlx::query!(
-        "SELECT user_id FROM local_auths WHERE email = $1",
+        r#"
+        SELECT l.user_id, u.locale
+        FROM local_auths l
+        JOIN users u ON u.id = l.user_id
+        WHERE l.email = $1
+        "#,
         payload.email
     )
-    .fetch_optional(&pool)
+    .fetch_optional(&state.pool)
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
     if let Some(u) = user {
         // Generate reset token and expiry (1 hour)
         let reset_token = Uuid::new_v4().to_string();
-        let expires_at = chrono::Utc::now() + chrono::Duration::hours(1);
+        let expires_at =
+            chrono::Utc::now() + chrono::Duration::hours(PASSWORD_RESET_TOKEN_TTL_HOURS);
 
         sqlx::query!(
             "UPDATE local_auths SET password_reset_token = $1, password_reset_expires_at = $2 WHERE user_id = $3",
@@ -1097,42 +1410,29 @@ pub async fn forgot_password(
             expires_at,
             u.user_id
         )
-        .execute(&pool)
+        .execute(&state.pool)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-        // Send Email
-        let frontend_url =
-            std::env::var("FRONTEND_URL").unwrap_or_else(|_| "http://localhost:3000".to_string());
-
-        let reset_link = format!("{}/reset-password?token={}", frontend_url, reset_token);
-        let email_body = format!(
-            r#"
-            <div style="font-family: sans-serif; max-width: 600px; margin: 0 auto;">
-                <h2>Reset Your Password</h2>
-                <p>We received a request to reset your password. Click the link below to verify it's you:</p>
-                <a href="{}" style="display: inline-block; background-color: #000; color: #fff; padding: 10px 20px; text-decoration: none; border-radius: 5px; margin: 20px 0;">Reset Password</a>
-                <p>If you didn't request this, you can safely ignore this email.</p>
-                <p>Link expires in 1 hour.</p>
-            </div>
-            "#,
-            reset_link
-        );
-
-        if let Err(e) = send_email(&payload.email, "Reset your password", &email_body).await {
-            tracing::error!("Failed to send reset password email: {}", e);
+        if let Err(e) = crate::email::send_password_reset_email(
+            &state,
+            &payload.email,
+            &reset_token,
+            Locale::from_tag(&u.locale),
+        )
+        .await
+        {
+            tracing::error!("Failed to queue reset password email: {}", e);
             return Err((
                 StatusCode::INTERNAL_SERVER_ERROR,
                 "Failed to send email".to_string(),
-            ));
+            )
+                .into());
         }
     }
 
     // Always return OK to prevent email enumeration
-    Ok((
-        StatusCode::OK,
-        "If an account exists with that email, a reset link has been sent.".to_string(),
-    ))
+    Ok((StatusCode::OK, Message::new(&i18n::RESET_SENT)))
 }
 
 #[derive(Deserialize)]
@@ -1141,10 +1441,19 @@ pub struct ResetPasswordRequest {
     pub new_password: String,
 }
 
+impl Validate for ResetPasswordRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.required("token", &self.token);
+        errors.password("new_password", &self.new_password);
+        errors.into_result()
+    }
+}
+
 pub async fn reset_password(
     State(pool): State<PgPool>,
-    Json(payload): Json<ResetPasswordRequest>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+    ValidatedJson(payload): ValidatedJson<ResetPasswordRequest>,
+) -> Result<impl IntoResponse, ApiError> {
     // Find user by token and check expiry
     let record = sqlx::query!(
         "SELECT user_id, password_reset_expires_at FROM local_auths WHERE password_reset_token = $1",
@@ -1157,30 +1466,25 @@ pub async fn reset_password(
     let record = match record {
         Some(r) => r,
         None => {
-            return Err((
+            return Err(ApiError::new(
                 StatusCode::BAD_REQUEST,
-                "Invalid or expired reset token".to_string(),
+                &i18n::INVALID_RESET_TOKEN,
             ))
         }
     };
 
     if let Some(expires_at) = record.password_reset_expires_at {
         if chrono::Utc::now() > expires_at {
-            return Err((
+            return Err(ApiError::new(
                 StatusCode::BAD_REQUEST,
-                "Reset token has expired".to_string(),
+                &i18n::RESET_TOKEN_EXPIRED,
             ));
         }
     } else {
         // Should not happen if token exists, but fail safe
-        return Err((StatusCode::BAD_REQUEST, "Invalid token state".to_string()));
-    }
-
-    // Validate new password
-    if payload.new_password.len() < 6 {
-        return Err((
+        return Err(ApiError::new(
             StatusCode::BAD_REQUEST,
-            "Password must be at least 6 characters".to_string(),
+            &i18n::INVALID_TOKEN_STATE,
         ));
     }
 
@@ -1211,10 +1515,7 @@ pub async fn reset_password(
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    Ok((
-        StatusCode::OK,
-        "Password has been reset successfully. You can now login.".to_string(),
-    ))
+    Ok((StatusCode::OK, Message::new(&i18n::PASSWORD_RESET_LOG_IN)))
 }
 
 #[derive(Deserialize)]
@@ -1223,19 +1524,21 @@ pub struct SetPasswordRequest {
     pub new_password: String,
 }
 
+impl Validate for SetPasswordRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.email("email", &self.email);
+        errors.password("new_password", &self.new_password);
+        errors.into_result()
+    }
+}
+
 /// Set password for OAuth-only users (creates local_auth record)
 pub async fn set_password(
     State(pool): State<PgPool>,
-    session: Session,
-    Json(payload): Json<SetPasswordRequest>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    // Get user_id from session
-    let user_id: Uuid = session
-        .get("user_id")
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-        .ok_or((StatusCode::UNAUTHORIZED, "Not logged in".to_string()))?;
-
+    AuthUser { id: user_id, .. }: AuthUser,
+    ValidatedJson(payload): ValidatedJson<SetPasswordRequest>,
+) -> Result<impl IntoResponse, ApiError> {
     // Check if user already has a password
     let existing = sqlx::query!(
         "SELECT user_id FROM local_auths WHERE user_id = $1",
@@ -1246,9 +1549,9 @@ pub async fn set_password(
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
     if existing.is_some() {
-        return Err((
+        return Err(ApiError::new(
             StatusCode::BAD_REQUEST,
-            "Password already set. Use change-password instead.".to_string(),
+            &i18n::PASSWORD_ALREADY_SET,
         ));
     }
 
@@ -1262,15 +1565,7 @@ pub async fn set_password(
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
     if email_exists.is_some() {
-        return Err((StatusCode::BAD_REQUEST, "Email already in use".to_string()));
-    }
-
-    // Validate new password
-    if payload.new_password.len() < 6 {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            "Password must be at least 6 characters".to_string(),
-        ));
+        return Err(ApiError::new(StatusCode::BAD_REQUEST, &i18n::EMAIL_IN_USE));
     }
 
     // Hash new password
@@ -1293,7 +1588,7 @@ pub async fn set_password(
 
     tracing::info!("Password set for OAuth user_id: {}", user_id);
 
-    Ok((StatusCode::OK, "Password set successfully".to_string()))
+    Ok((StatusCode::OK, Message::new(&i18n::PASSWORD_SET)))
 }
 
 async fn async_http_client_logging(
@@ -1345,14 +1640,8 @@ pub struct LinkedAccount {
 
 pub async fn list_linked_accounts(
     State(pool): State<PgPool>,
-    session: Session,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    let user_id: Uuid = session
-        .get("user_id")
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-        .ok_or((StatusCode::UNAUTHORIZED, "Not logged in".to_string()))?;
-
+    AuthUser { id: user_id, .. }: AuthUser,
+) -> Result<impl IntoResponse, ApiError> {
     let accounts = sqlx::query!(
         "SELECT provider, provider_email FROM oauth_connections WHERE user_id = $1",
         user_id
@@ -1374,15 +1663,9 @@ pub async fn list_linked_accounts(
 
 pub async fn unlink_account(
     State(pool): State<PgPool>,
-    session: Session,
+    AuthUser { id: user_id, .. }: AuthUser,
     Path(provider): Path<String>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    let user_id: Uuid = session
-        .get("user_id")
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-        .ok_or((StatusCode::UNAUTHORIZED, "Not logged in".to_string()))?;
-
+) -> Result<impl IntoResponse, ApiError> {
     // Safety check: user must have another auth method
     let has_password = sqlx::query!(
         "SELECT user_id FROM local_auths WHERE user_id = $1",
@@ -1413,10 +1696,9 @@ pub async fn unlink_account(
     .unwrap_or(0);
 
     if !has_password && other_oauth_count == 0 && passkey_count == 0 {
-        return Err((
+        return Err(ApiError::new(
             StatusCode::BAD_REQUEST,
-            "Cannot unlink your only sign-in method. Add a password or link another account first."
-                .to_string(),
+            &i18n::ONLY_SIGN_IN_METHOD,
         ));
     }
 
@@ -1430,13 +1712,16 @@ pub async fn unlink_account(
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
     if result.rows_affected() == 0 {
-        return Err((StatusCode::NOT_FOUND, "Account not linked".to_string()));
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            &i18n::ACCOUNT_NOT_LINKED,
+        ));
     }
 
     tracing::info!("User {} unlinked {} account", user_id, provider);
 
     Ok((
         StatusCode::OK,
-        format!("{} account unlinked successfully", provider),
+        Message::new(&i18n::ACCOUNT_UNLINKED).arg("provider", provider),
     ))
 }
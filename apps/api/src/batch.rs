@@ -0,0 +1,39 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::validation::{Validate, ValidationErrors};
+
+// Most IDs a batch lookup takes at once
+const MAX_BATCH_SIZE: usize = 100;
+
+/// Body of the `POST .../batch` lookups: `{"ids": [...]}`
+#[derive(Deserialize)]
+pub struct BatchRequest {
+    pub ids: Vec<Uuid>,
+}
+
+impl Validate for BatchRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        if self.ids.is_empty() {
+            errors.add("ids", "Required");
+        } else if self.ids.len() > MAX_BATCH_SIZE {
+            errors.add("ids", format!("At most {} IDs at a time", MAX_BATCH_SIZE));
+        }
+        errors.into_result()
+    }
+}
+
+/// Results keyed by the requested ID, with null for IDs that matched nothing (or that
+/// the caller can't see), so clients can tell those apart from ones they didn't ask for
+pub fn by_id<T>(
+    requested: &[Uuid],
+    found: impl IntoIterator<Item = (Uuid, T)>,
+) -> HashMap<Uuid, Option<T>> {
+    let mut results: HashMap<Uuid, Option<T>> = requested.iter().map(|id| (*id, None)).collect();
+    for (id, item) in found {
+        results.insert(id, Some(item));
+    }
+    results
+}
@@ -0,0 +1,214 @@
+use api::scheduler;
+use api::state::{AppState, Config};
+use clap::{Parser, Subcommand, ValueEnum};
+use dotenvy::dotenv;
+use sqlx::postgres::PgPoolOptions;
+use std::env;
+use std::process::ExitCode;
+use uuid::Uuid;
+
+// run with 'cargo run --bin praxis-admin -- <command>' ('-- --help' lists them)
+
+/// Admin tasks for a Praxis database (DATABASE_URL)
+#[derive(Parser)]
+#[command(name = "praxis-admin")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Make a user an admin or moderator
+    Promote {
+        username: String,
+        #[arg(long, value_enum, default_value_t = Role::Admin)]
+        role: Role,
+    },
+    /// Make a user a regular user again
+    Demote { username: String },
+    /// Mark a user's email verified without the emailed link
+    VerifyEmail { username: String },
+    /// Create sample users, each with a post and a project
+    Seed {
+        #[arg(long, default_value_t = 5)]
+        users: usize,
+    },
+    /// Delete a user with everything they made and their uploads
+    PurgeUser {
+        username: String,
+        /// Don't ask first
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Replace a secret after a leak
+    RotateSecret {
+        #[arg(value_enum)]
+        secret: Secret,
+    },
+    /// Run maintenance jobs now instead of waiting for the scheduler
+    RunJob {
+        /// Leave out to list them
+        name: Option<String>,
+        /// Run every job
+        #[arg(long, conflicts_with = "name")]
+        all: bool,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Role {
+    Admin,
+    Moderator,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Secret {
+    /// EMAIL_SIGNING_SECRET, which signs unsubscribe links
+    EmailSigning,
+    /// Every login session (signs everyone out)
+    Sessions,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    dotenv().ok();
+    let cli = Cli::parse();
+
+    // Nothing to connect to for these
+    match &cli.command {
+        Command::RotateSecret {
+            secret: Secret::EmailSigning,
+        } => {
+            println!(
+                "EMAIL_SIGNING_SECRET={}",
+                hex::encode(rand::random::<[u8; 32]>())
+            );
+            println!(
+                "Set this on the API and restart it. Unsubscribe links already sent stop working."
+            );
+            return ExitCode::SUCCESS;
+        }
+        Command::RunJob {
+            name: None,
+            all: false,
+        } => {
+            for (name, period) in scheduler::JOBS {
+                println!("{:<24} every {}s", name, period.as_secs());
+            }
+            return ExitCode::SUCCESS;
+        }
+        _ => {}
+    }
+
+    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let pool = PgPoolOptions::new()
+        .connect(&database_url)
+        .await
+        .expect("Failed to connect to DB");
+    let state = AppState::new(pool, None, Config::from_env()).await;
+
+    match run(&state, cli.command).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("⚠️  {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(state: &AppState, command: Command) -> Result<(), String> {
+    match command {
+        Command::Promote { username, role } => {
+            let role = match role {
+                Role::Admin => "admin",
+                Role::Moderator => "moderator",
+            };
+            set_role(state, &username, role).await
+        }
+        Command::Demote { username } => set_role(state, &username, "user").await,
+        Command::VerifyEmail { username } => {
+            let user_id = find(state, &username).await?;
+            let verified = api::user::verify_email(&state.pool, user_id)
+                .await
+                .map_err(|e| e.to_string())?;
+            if !verified {
+                return Err(format!("'{}' has no email login to verify.", username));
+            }
+            println!("✅ Verified the email of '{}'.", username);
+            Ok(())
+        }
+        Command::Seed { users } => {
+            let usernames = api::demo::seed(state, users).await?;
+            println!(
+                "✅ Created {} users: {}",
+                usernames.len(),
+                usernames.join(", ")
+            );
+            Ok(())
+        }
+        Command::PurgeUser { username, yes } => {
+            let user_id = find(state, &username).await?;
+            if !yes && !confirm(&format!("Delete '{}' and everything they made?", username)) {
+                return Err("Nothing deleted.".to_string());
+            }
+            api::user::purge(state, user_id)
+                .await
+                .map_err(|e| e.to_string())?;
+            println!("✅ Deleted '{}'.", username);
+            Ok(())
+        }
+        Command::RotateSecret { secret } => match secret {
+            Secret::Sessions => {
+                let revoked =
+                    api::session::revoke_all_sessions(&state.pool, &state.sessions).await?;
+                println!("✅ Signed everyone out ({} sessions).", revoked);
+                Ok(())
+            }
+            Secret::EmailSigning => unreachable!("handled before connecting"),
+        },
+        Command::RunJob { name, all } => {
+            let names: Vec<&str> = match &name {
+                Some(name) => vec![name.as_str()],
+                None if all => scheduler::JOBS.iter().map(|(name, _)| *name).collect(),
+                None => unreachable!("handled before connecting"),
+            };
+            for name in names {
+                let Some(result) = scheduler::run(state, name).await else {
+                    return Err(format!(
+                        "No job called '{}'. Run without a name to list them.",
+                        name
+                    ));
+                };
+                println!("✅ {}: {} rows", name, result?);
+            }
+            Ok(())
+        }
+    }
+}
+
+async fn find(state: &AppState, username: &str) -> Result<Uuid, String> {
+    api::user::find_id(&state.pool, username)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("No user found with username '{}'.", username))
+}
+
+async fn set_role(state: &AppState, username: &str, role: &str) -> Result<(), String> {
+    let user_id = find(state, username).await?;
+    let previous = api::admin::set_role(&state.pool, &state.sessions, user_id, role)
+        .await
+        .map_err(|e| e.to_string())?;
+    if previous == role {
+        println!("'{}' is already {}.", username, role);
+    } else {
+        println!("✅ '{}' is now {} (was {}).", username, role, previous);
+    }
+    Ok(())
+}
+
+fn confirm(question: &str) -> bool {
+    println!("{} [y/N]", question);
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer).is_ok() && answer.trim().eq_ignore_ascii_case("y")
+}
@@ -0,0 +1,34 @@
+use api::search_index::{self, Meilisearch};
+use dotenvy::dotenv;
+use sqlx::postgres::PgPoolOptions;
+use std::env;
+
+// run with 'cargo run --bin reindex_search'
+// Rebuilds the Meilisearch index (MEILISEARCH_URL) from Postgres
+
+#[tokio::main]
+async fn main() {
+    dotenv().ok();
+    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let Some(meilisearch) = Meilisearch::from_env(reqwest::Client::new()) else {
+        eprintln!(
+            "MEILISEARCH_URL isn't set, so search runs on Postgres and there's nothing to reindex."
+        );
+        return;
+    };
+
+    let pool = PgPoolOptions::new()
+        .connect(&database_url)
+        .await
+        .expect("Failed to connect to DB");
+
+    meilisearch
+        .configure()
+        .await
+        .expect("Failed to configure the index");
+    let count = search_index::reindex(&pool, &meilisearch)
+        .await
+        .expect("Failed to reindex");
+
+    println!("✅ Indexed {} users, posts and projects.", count);
+}
@@ -0,0 +1,351 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tower_sessions::Session;
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::extractors::AdminUser;
+use crate::i18n::{self, Locale};
+use crate::notifications::NotificationKind;
+use crate::ws::Event;
+use crate::state::AppState;
+use crate::validation::{Validate, ValidatedJson, ValidationErrors};
+
+const MAX_BROADCAST_LENGTH: usize = 1000;
+const MAX_LINK_LENGTH: usize = 500;
+const ROLES: &[&str] = &["user", "moderator", "admin"];
+// How many GET /admin/notifications/broadcasts returns
+const RECENT_BROADCASTS: i64 = 50;
+
+/// A broadcast with its delivery and read counts
+#[derive(Serialize)]
+pub struct BroadcastReport {
+    pub id: Uuid,
+    pub author_id: Option<Uuid>,
+    pub content: String,
+    pub link: Option<String>,
+    pub role: Option<String>,
+    pub locale: Option<String>,
+    pub joined_after: Option<DateTime<Utc>>,
+    pub joined_before: Option<DateTime<Utc>>,
+    pub send_at: DateTime<Utc>,
+    /// None until it's delivered
+    pub sent_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    /// Notifications created; users who turned broadcasts off in-app don't get one
+    pub delivered: i64,
+    pub read: i64,
+}
+
+/// Who gets it: everyone, narrowed by any of these that are set
+#[derive(Default, Deserialize)]
+pub struct Segment {
+    pub role: Option<String>,
+    pub locale: Option<String>,
+    pub joined_after: Option<DateTime<Utc>>,
+    pub joined_before: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateBroadcastRequest {
+    pub content: String,
+    /// A path in the frontend, e.g. "/projects"
+    pub link: Option<String>,
+    #[serde(default)]
+    pub segment: Segment,
+    /// Sent right away when left out
+    pub send_at: Option<DateTime<Utc>>,
+}
+
+impl Validate for CreateBroadcastRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.length("content", &self.content, 1, MAX_BROADCAST_LENGTH);
+        if let Some(link) = &self.link {
+            errors.length("link", link, 1, MAX_LINK_LENGTH);
+            if !link.starts_with('/') {
+                errors.add("link", "Must be a path, e.g. /projects");
+            }
+        }
+
+        if let Some(role) = &self.segment.role {
+            errors.one_of("segment.role", role, ROLES);
+        }
+        if let Some(locale) = &self.segment.locale {
+            errors.one_of("segment.locale", locale, Locale::SUPPORTED);
+        }
+        if let (Some(after), Some(before)) = (self.segment.joined_after, self.segment.joined_before)
+        {
+            if after >= before {
+                errors.add("segment.joined_before", "Must be after joined_after");
+            }
+        }
+
+        if let Some(send_at) = self.send_at {
+            if send_at <= Utc::now() {
+                errors.add("send_at", "Must be in the future");
+            }
+        }
+        errors.into_result()
+    }
+}
+
+async fn load_reports(
+    pool: &PgPool,
+    id: Option<Uuid>,
+) -> Result<Vec<BroadcastReport>, sqlx::Error> {
+    sqlx::query_as!(
+        BroadcastReport,
+        r#"
+        SELECT b.id, b.author_id, b.content, b.link, b.role, b.locale,
+               b.joined_after, b.joined_before, b.send_at, b.sent_at, b.created_at,
+               COUNT(n.id) AS "delivered!", COUNT(n.read_at) AS "read!"
+        FROM notification_broadcasts b
+        LEFT JOIN notifications n ON n.broadcast_id = b.id
+        WHERE ($1::uuid IS NULL OR b.id = $1)
+        GROUP BY b.id
+        ORDER BY b.created_at DESC
+        LIMIT $2
+        "#,
+        id,
+        RECENT_BROADCASTS
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// The latest broadcasts, sent or scheduled, newest first
+pub async fn list(State(pool): State<PgPool>, _: AdminUser) -> Result<impl IntoResponse, ApiError> {
+    let reports = load_reports(&pool, None)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(reports))
+}
+
+pub async fn get(
+    State(pool): State<PgPool>,
+    _: AdminUser,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let report = load_reports(&pool, Some(id))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .pop()
+        .ok_or(ApiError::new(
+            StatusCode::NOT_FOUND,
+            &i18n::BROADCAST_NOT_FOUND,
+        ))?;
+
+    Ok(Json(report))
+}
+
+/// Without `send_at` it's delivered before responding; otherwise the scheduler sends it
+pub async fn create(
+    State(state): State<AppState>,
+    AdminUser(admin): AdminUser,
+    session: Session,
+    ValidatedJson(payload): ValidatedJson<CreateBroadcastRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO notification_broadcasts
+            (author_id, content, link, role, locale, joined_after, joined_before, send_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, COALESCE($8, NOW()))
+        RETURNING id
+        "#,
+        admin.id,
+        payload.content,
+        payload.link,
+        payload.segment.role,
+        payload.segment.locale,
+        payload.segment.joined_after,
+        payload.segment.joined_before,
+        payload.send_at
+    )
+    .fetch_one(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let details = format!("broadcast {}", id);
+    crate::audit::record(
+        &state.pool,
+        &session,
+        admin.id,
+        "notification.broadcast",
+        None,
+        Some(&details),
+    )
+    .await?;
+
+    if payload.send_at.is_none() {
+        deliver(&state, id)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    }
+
+    let report = load_reports(&state.pool, Some(id))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .pop()
+        .ok_or(ApiError::new(
+            StatusCode::NOT_FOUND,
+            &i18n::BROADCAST_NOT_FOUND,
+        ))?;
+
+    Ok((StatusCode::CREATED, Json(report)))
+}
+
+/// Only scheduled broadcasts can be cancelled; sent ones are a 409
+pub async fn cancel(
+    State(pool): State<PgPool>,
+    AdminUser(admin): AdminUser,
+    session: Session,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let sent_at = sqlx::query_scalar!(
+        "SELECT sent_at FROM notification_broadcasts WHERE id = $1",
+        id
+    )
+    .fetch_optional(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or(ApiError::new(
+        StatusCode::NOT_FOUND,
+        &i18n::BROADCAST_NOT_FOUND,
+    ))?;
+
+    let result = sqlx::query!(
+        "DELETE FROM notification_broadcasts WHERE id = $1 AND sent_at IS NULL",
+        id
+    )
+    .execute(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    // Checked after the delete too, in case the scheduler sent it in between
+    if sent_at.is_some() || result.rows_affected() == 0 {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            &i18n::BROADCAST_ALREADY_SENT,
+        ));
+    }
+
+    let details = format!("broadcast {}", id);
+    crate::audit::record(
+        &pool,
+        &session,
+        admin.id,
+        "notification.broadcast_cancelled",
+        None,
+        Some(&details),
+    )
+    .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Deliver every scheduled broadcast due at `now`. Returns how many were delivered.
+pub async fn send_due(state: &AppState, now: DateTime<Utc>) -> Result<u64, String> {
+    let due = sqlx::query_scalar!(
+        "SELECT id FROM notification_broadcasts WHERE sent_at IS NULL AND send_at <= $1",
+        now
+    )
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let mut sent = 0;
+    for id in due {
+        match deliver(state, id).await {
+            Ok(Some(_)) => sent += 1,
+            Ok(None) => {}
+            Err(e) => tracing::error!("Failed to deliver broadcast {}: {}", id, e),
+        }
+    }
+
+    Ok(sent)
+}
+
+/// Create the broadcast's notifications for everyone in its segment, unless it's already
+/// been sent. Returns how many were created, or None if it was already sent.
+async fn deliver(state: &AppState, id: Uuid) -> Result<Option<u64>, String> {
+    let kind = NotificationKind::Broadcast;
+    // Claiming and delivering in one transaction means a failed delivery can be retried,
+    // and a second instance waits on the claim and then finds it sent
+    let mut tx = state.pool.begin().await.map_err(|e| e.to_string())?;
+
+    let broadcast = sqlx::query!(
+        r#"
+        UPDATE notification_broadcasts SET sent_at = NOW()
+        WHERE id = $1 AND sent_at IS NULL
+        RETURNING author_id, content, link, role, locale, joined_after, joined_before
+        "#,
+        id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+    let Some(broadcast) = broadcast else {
+        return Ok(None);
+    };
+
+    let data = serde_json::json!({
+        "broadcast_id": id,
+        "content": broadcast.content,
+        "link": broadcast.link,
+    });
+    // Banned users and the author are left out, as are users who turned broadcasts off
+    let notifications = sqlx::query!(
+        r#"
+        INSERT INTO notifications (user_id, kind, actor_id, data, broadcast_id)
+        SELECT u.id, $2, $3, $4, $1
+        FROM users u
+        LEFT JOIN notification_preferences np ON np.user_id = u.id AND np.kind = $2
+        WHERE u.banned_at IS NULL
+          AND u.id IS DISTINCT FROM $3
+          AND COALESCE(np.in_app, $5)
+          AND ($6::text IS NULL OR u.role = $6)
+          AND ($7::text IS NULL OR u.locale = $7)
+          AND ($8::timestamptz IS NULL OR u.created_at > $8)
+          AND ($9::timestamptz IS NULL OR u.created_at < $9)
+        RETURNING id, user_id, created_at
+        "#,
+        id,
+        kind.as_str(),
+        broadcast.author_id,
+        data,
+        kind.default_channels().in_app,
+        broadcast.role,
+        broadcast.locale,
+        broadcast.joined_after,
+        broadcast.joined_before
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    for notification in &notifications {
+        state.realtime.send_to(
+            notification.user_id,
+            Event::Notification {
+                id: notification.id,
+                kind: kind.as_str().to_string(),
+                actor_id: broadcast.author_id,
+                data: data.clone(),
+                created_at: notification.created_at,
+            },
+        );
+    }
+
+    Ok(Some(notifications.len() as u64))
+}
@@ -0,0 +1,119 @@
+use async_trait::async_trait;
+use redis::{aio::ConnectionManager, AsyncCommands};
+use serde::{de::DeserializeOwned, Serialize};
+use std::time::Duration;
+
+/// A shared key/value cache for hot reads. Failures are logged and treated as misses,
+/// so a flaky cache only costs a trip to the database.
+#[async_trait]
+pub trait Cache: Send + Sync {
+    async fn get(&self, key: &str) -> Option<String>;
+    async fn set(&self, key: &str, value: String, ttl: Duration);
+    async fn delete(&self, key: &str);
+}
+
+impl dyn Cache + '_ {
+    pub async fn get_json<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let value = self.get(key).await?;
+        match serde_json::from_str(&value) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                tracing::warn!("Ignoring unreadable cache entry {}: {}", key, e);
+                None
+            }
+        }
+    }
+
+    pub async fn set_json<T: Serialize>(&self, key: &str, value: &T, ttl: Duration) {
+        match serde_json::to_string(value) {
+            Ok(value) => self.set(key, value, ttl).await,
+            Err(e) => tracing::warn!("Failed to serialize cache entry {}: {}", key, e),
+        }
+    }
+}
+
+/// Used when REDIS_URL isn't set: every read is a miss, so everything comes from Postgres
+pub struct NoCache;
+
+#[async_trait]
+impl Cache for NoCache {
+    async fn get(&self, _key: &str) -> Option<String> {
+        None
+    }
+
+    async fn set(&self, _key: &str, _value: String, _ttl: Duration) {}
+
+    async fn delete(&self, _key: &str) {}
+}
+
+pub struct RedisCache {
+    conn: ConnectionManager,
+}
+
+impl RedisCache {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self { conn }
+    }
+
+    // Keeps cache entries apart from sessions when both live in the same Redis
+    fn key(key: &str) -> String {
+        format!("cache:{}", key)
+    }
+}
+
+#[async_trait]
+impl Cache for RedisCache {
+    async fn get(&self, key: &str) -> Option<String> {
+        let mut conn = self.conn.clone();
+        conn.get::<_, Option<String>>(Self::key(key))
+            .await
+            .unwrap_or_else(|e| {
+                tracing::warn!("Cache get {} failed: {}", key, e);
+                None
+            })
+    }
+
+    async fn set(&self, key: &str, value: String, ttl: Duration) {
+        let mut conn = self.conn.clone();
+        if let Err(e) = conn
+            .set_ex::<_, _, ()>(Self::key(key), value, ttl.as_secs().max(1))
+            .await
+        {
+            tracing::warn!("Cache set {} failed: {}", key, e);
+        }
+    }
+
+    async fn delete(&self, key: &str) {
+        let mut conn = self.conn.clone();
+        if let Err(e) = conn.del::<_, ()>(Self::key(key)).await {
+            tracing::warn!("Cache delete {} failed: {}", key, e);
+        }
+    }
+}
+
+/// Connect to REDIS_URL if it's set. Returns None (Postgres only) when it isn't,
+/// or when Redis can't be reached at startup.
+pub async fn connect_from_env() -> Option<ConnectionManager> {
+    let url = std::env::var("REDIS_URL")
+        .ok()
+        .filter(|url| !url.is_empty())?;
+
+    let client = match redis::Client::open(url) {
+        Ok(client) => client,
+        Err(e) => {
+            tracing::error!("Invalid REDIS_URL, running without Redis: {}", e);
+            return None;
+        }
+    };
+
+    match ConnectionManager::new(client).await {
+        Ok(conn) => {
+            tracing::info!("Connected to Redis");
+            Some(conn)
+        }
+        Err(e) => {
+            tracing::error!("Failed to connect to Redis, running without it: {}", e);
+            None
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use async_trait::async_trait;
+use axum::{
+    extract::{ConnectInfo, FromRequestParts, Request, State},
+    http::{request::Parts, HeaderMap, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use ipnet::IpNet;
+use std::net::{IpAddr, SocketAddr};
+
+use crate::error::ApiError;
+use crate::state::AppState;
+
+/// The proxies in front of the API (TRUSTED_PROXIES, comma separated addresses or CIDR ranges).
+/// X-Forwarded-For is only believed when the request comes from one of them.
+#[derive(Clone, Debug, Default)]
+pub struct TrustedProxies(Vec<IpNet>);
+
+impl TrustedProxies {
+    pub fn from_env() -> Self {
+        let value = std::env::var("TRUSTED_PROXIES").unwrap_or_default();
+        let proxies = value
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .filter_map(|entry| {
+                let parsed = entry
+                    .parse::<IpNet>()
+                    .or_else(|_| entry.parse::<IpAddr>().map(IpNet::from));
+                if parsed.is_err() {
+                    tracing::warn!("Ignoring invalid TRUSTED_PROXIES entry {}", entry);
+                }
+                parsed.ok()
+            })
+            .collect();
+        Self(proxies)
+    }
+
+    pub fn new(proxies: Vec<IpNet>) -> Self {
+        Self(proxies)
+    }
+
+    fn contains(&self, ip: &IpAddr) -> bool {
+        self.0.iter().any(|net| net.contains(ip))
+    }
+
+    /// The address of whoever sent the request. That's the peer, unless the peer is a trusted
+    /// proxy: then it's the right-most X-Forwarded-For hop not added by one. Entries further
+    /// left were written by the client and can be anything.
+    pub fn client_ip(&self, headers: &HeaderMap, peer: IpAddr) -> IpAddr {
+        if !self.contains(&peer) {
+            return peer;
+        }
+
+        let hops = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .collect::<Vec<_>>();
+        let mut client = peer;
+        for hop in hops.into_iter().rev() {
+            match hop.parse::<IpAddr>() {
+                Ok(ip) => {
+                    client = ip;
+                    if !self.contains(&ip) {
+                        break;
+                    }
+                }
+                // Nothing left of garbage can be trusted
+                Err(_) => break,
+            }
+        }
+        client
+    }
+}
+
+/// Where the request came from, as resolved by `resolve`
+#[derive(Clone, Copy, Debug)]
+pub struct ClientIp(pub IpAddr);
+
+/// Middleware: work out the client's address once, for rate limits and session tracking
+pub async fn resolve(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let ip = state
+        .config
+        .trusted_proxies
+        .client_ip(request.headers(), addr.ip());
+    request.extensions_mut().insert(ClientIp(ip));
+    next.run(request).await
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientIp
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts.extensions.get::<ClientIp>().copied().ok_or(
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Client address not resolved".to_string(),
+            )
+                .into(),
+        )
+    }
+}
@@ -0,0 +1,314 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use serde::Serialize;
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::{ConnectOptions, Executor, PgPool, Row};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::error::ApiError;
+use crate::extractors::AdminUser;
+
+// Probes kept for /admin/db/pool: 10 minutes' worth at one every 10 seconds
+const MAX_SAMPLES: usize = 60;
+// A probe waiting longer than this means requests are queueing for connections
+const SLOW_ACQUIRE: Duration = Duration::from_secs(1);
+
+/// Recent probes of how long it takes to get a connection, in this process
+static SAMPLES: Mutex<VecDeque<Sample>> = Mutex::new(VecDeque::new());
+
+/// Connection pool settings, from the DATABASE_* env vars
+pub struct PoolConfig {
+    /// DATABASE_MAX_CONNECTIONS, 5 by default
+    pub max_connections: u32,
+    /// DATABASE_MIN_CONNECTIONS, kept open even when idle; 0 by default
+    pub min_connections: u32,
+    /// DATABASE_ACQUIRE_TIMEOUT_SECONDS: how long a query waits for a free connection
+    /// before failing, 5 by default
+    pub acquire_timeout: Duration,
+    /// DATABASE_STATEMENT_TIMEOUT_MS: Postgres cancels statements running longer than
+    /// this, so one slow query can't hold a connection forever. 30 seconds by default, 0 for none.
+    pub statement_timeout: Option<Duration>,
+    /// DATABASE_SLOW_QUERY_MS: statements taking longer are logged as warnings, with the
+    /// route of the request that ran them. 500 by default.
+    pub slow_query: Duration,
+    /// DATABASE_PGBOUNCER=true when DATABASE_URL goes through PgBouncer in transaction mode
+    pub pgbouncer: bool,
+}
+
+impl PoolConfig {
+    pub fn from_env() -> Self {
+        let max_connections = env_number("DATABASE_MAX_CONNECTIONS").unwrap_or(5).max(1) as u32;
+        let min_connections = env_number("DATABASE_MIN_CONNECTIONS")
+            .unwrap_or(0)
+            .min(max_connections as u64) as u32;
+
+        Self {
+            max_connections,
+            min_connections,
+            acquire_timeout: Duration::from_secs(
+                env_number("DATABASE_ACQUIRE_TIMEOUT_SECONDS").unwrap_or(5),
+            ),
+            statement_timeout: Some(env_number("DATABASE_STATEMENT_TIMEOUT_MS").unwrap_or(30_000))
+                .filter(|ms| *ms > 0)
+                .map(Duration::from_millis),
+            slow_query: Duration::from_millis(env_number("DATABASE_SLOW_QUERY_MS").unwrap_or(500)),
+            pgbouncer: std::env::var("DATABASE_PGBOUNCER")
+                .is_ok_and(|v| matches!(v.trim(), "true" | "1")),
+        }
+    }
+}
+
+fn env_number(name: &str) -> Option<u64> {
+    std::env::var(name).ok().and_then(|v| v.trim().parse().ok())
+}
+
+/// Open the pool the API and its background jobs share. Fails with an explanation when
+/// DATABASE_URL turns out to be a transaction mode pooler that the settings don't allow for.
+pub async fn connect(options: PgConnectOptions, config: &PoolConfig) -> Result<PgPool, String> {
+    // sqlx logs every statement with how long it took (at debug, under RUST_LOG=sqlx=debug)
+    // inside the span of the request that ran it, which names the route
+    let mut options = options.log_slow_statements(log::LevelFilter::Warn, config.slow_query);
+    if config.pgbouncer {
+        // Each transaction can land on a different server connection, so nothing prepared
+        // in one can be reused later. PgBouncer also rejects the `options` startup parameter.
+        options = options.statement_cache_capacity(0);
+        if config.statement_timeout.is_some() {
+            tracing::info!(
+                "DATABASE_PGBOUNCER is set, so statement_timeout isn't sent on connect; \
+                 set it on the database role instead (ALTER ROLE ... SET statement_timeout)"
+            );
+        }
+    } else if let Some(timeout) = config.statement_timeout {
+        // Sent when each connection starts, so it covers every query on it
+        options = options.options([("statement_timeout", format!("{}ms", timeout.as_millis()))]);
+    }
+
+    let pool = PgPoolOptions::new()
+        .max_connections(config.max_connections)
+        .min_connections(config.min_connections)
+        .acquire_timeout(config.acquire_timeout)
+        .connect_with(options)
+        .await
+        .map_err(|e| {
+            if e.to_string().contains("unsupported startup parameter") {
+                format!(
+                    "{}. DATABASE_URL looks like PgBouncer: set DATABASE_PGBOUNCER=true",
+                    e
+                )
+            } else {
+                e.to_string()
+            }
+        })?;
+
+    if config.pgbouncer {
+        check_prepared_statements(&pool).await?;
+    } else {
+        check_not_multiplexed(&pool).await?;
+    }
+    Ok(pool)
+}
+
+/// A transaction mode pooler hands each transaction whichever server connection is free,
+/// which shows up as the backend changing between statements on one of our connections.
+/// The probe uses the simple query protocol, so it works through a pooler either way.
+async fn check_not_multiplexed(pool: &PgPool) -> Result<(), String> {
+    let mut conn = pool.acquire().await.map_err(|e| e.to_string())?;
+    let mut backends = Vec::new();
+    for _ in 0..5 {
+        let row = conn
+            .fetch_one("SELECT pg_backend_pid()")
+            .await
+            .map_err(|e| e.to_string())?;
+        backends.push(row.try_get::<i32, _>(0).map_err(|e| e.to_string())?);
+    }
+    backends.dedup();
+
+    if backends.len() > 1 {
+        return Err(
+            "DATABASE_URL goes through a pooler in transaction mode (the server connection \
+             changed between statements), which breaks prepared statements. Set \
+             DATABASE_PGBOUNCER=true, or connect in session mode or directly"
+                .to_string(),
+        );
+    }
+    Ok(())
+}
+
+/// Queries with parameters are always prepared, so PgBouncer has to pass prepared statements
+/// through (PgBouncer 1.21+ with max_prepared_statements set). Without that they fail once
+/// a transaction lands on a server connection that hasn't seen the statement.
+async fn check_prepared_statements(pool: &PgPool) -> Result<(), String> {
+    for n in 0..5 {
+        let result = sqlx::query_scalar::<_, i32>("SELECT $1::int")
+            .bind(n)
+            .fetch_one(pool)
+            .await;
+        match result {
+            Ok(_) => {}
+            Err(sqlx::Error::Database(e))
+                if matches!(e.code().as_deref(), Some("26000" | "42P05")) =>
+            {
+                return Err(format!(
+                    "{}. PgBouncer isn't passing prepared statements through: set \
+                     max_prepared_statements in pgbouncer.ini (PgBouncer 1.21+)",
+                    e
+                ))
+            }
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+    Ok(())
+}
+
+/// Open a pool on the read replica at DATABASE_READ_URL, with the same settings as the
+/// primary. None when it isn't set, or can't be reached, so reads go to the primary.
+pub async fn connect_replica(config: &PoolConfig) -> Option<PgPool> {
+    let url = std::env::var("DATABASE_READ_URL").ok().filter(|url| !url.is_empty())?;
+    let options = match url.parse() {
+        Ok(options) => options,
+        Err(e) => {
+            tracing::error!("Invalid DATABASE_READ_URL, reading from the primary: {}", e);
+            return None;
+        }
+    };
+    match connect(options, config).await {
+        Ok(pool) => Some(pool),
+        Err(e) => {
+            tracing::error!(
+                "Failed to connect to DATABASE_READ_URL, reading from the primary: {}",
+                e
+            );
+            None
+        }
+    }
+}
+
+/// Picks the pool for a query: the replica for handlers that only read and can show
+/// slightly stale data (feed, search, public pages), the primary for everything else
+#[derive(Clone)]
+pub struct DbRouter {
+    primary: PgPool,
+    replica: Option<PgPool>,
+}
+
+impl DbRouter {
+    pub fn new(primary: PgPool, replica: Option<PgPool>) -> Self {
+        Self { primary, replica }
+    }
+
+    /// The replica, or the primary without one. Lags the primary a little, so don't use it
+    /// to read back what the same request just wrote.
+    pub fn read(&self) -> &PgPool {
+        self.replica.as_ref().unwrap_or(&self.primary)
+    }
+
+    pub fn write(&self) -> &PgPool {
+        &self.primary
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Sample {
+    /// None when no connection came free within the acquire timeout
+    acquire: Option<Duration>,
+    in_use: u32,
+}
+
+/// Time getting a connection, the way a request would. Run every few seconds so
+/// /admin/db/pool can show whether requests have been waiting on the pool.
+pub async fn sample(pool: &PgPool) -> Result<u64, String> {
+    let started = Instant::now();
+    let acquire = match pool.acquire().await {
+        Ok(_) => Some(started.elapsed()),
+        Err(sqlx::Error::PoolTimedOut) => None,
+        Err(e) => return Err(e.to_string()),
+    };
+    let in_use = pool.size().saturating_sub(pool.num_idle() as u32);
+
+    match acquire {
+        Some(waited) if waited >= SLOW_ACQUIRE => tracing::warn!(
+            "Waited {:?} for a database connection ({} of {} in use)",
+            waited,
+            in_use,
+            pool.options().get_max_connections()
+        ),
+        None => tracing::warn!(
+            "Timed out waiting for a database connection ({} of {} in use)",
+            in_use,
+            pool.options().get_max_connections()
+        ),
+        _ => {}
+    }
+
+    let mut samples = SAMPLES.lock().unwrap();
+    if samples.len() == MAX_SAMPLES {
+        samples.pop_front();
+    }
+    samples.push_back(Sample { acquire, in_use });
+    Ok(0)
+}
+
+#[derive(Serialize)]
+pub struct PoolStats {
+    pub max_connections: u32,
+    pub min_connections: u32,
+    /// Connections open right now, busy or idle
+    pub open: u32,
+    pub idle: u32,
+    pub in_use: u32,
+    /// in_use / max_connections, from 0 to 1
+    pub utilization: f64,
+    /// The most connections in use at once across recent probes
+    pub peak_in_use: u32,
+    /// How long recent probes waited for a connection, in milliseconds
+    pub acquire_ms_p50: Option<f64>,
+    pub acquire_ms_p95: Option<f64>,
+    pub acquire_ms_max: Option<f64>,
+    /// Recent probes that gave up waiting; any at all means the pool is too small
+    pub acquire_timeouts: usize,
+    pub samples: usize,
+}
+
+/// How busy the connection pool is, so we can tell when the API is starved for
+/// connections (admins only)
+pub async fn pool_stats(
+    State(pool): State<PgPool>,
+    _: AdminUser,
+) -> Result<impl IntoResponse, ApiError> {
+    // One fresh probe, so there's something to show before the first scheduled one
+    sample(&pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    let samples: Vec<Sample> = SAMPLES.lock().unwrap().iter().copied().collect();
+    let mut waits: Vec<f64> = samples
+        .iter()
+        .filter_map(|s| s.acquire)
+        .map(|d| d.as_secs_f64() * 1000.0)
+        .collect();
+    waits.sort_by(f64::total_cmp);
+    let percentile = |p: f64| {
+        (!waits.is_empty()).then(|| waits[((waits.len() - 1) as f64 * p).round() as usize])
+    };
+
+    let max_connections = pool.options().get_max_connections();
+    let open = pool.size();
+    let idle = pool.num_idle() as u32;
+    let in_use = open.saturating_sub(idle);
+
+    Ok(Json(PoolStats {
+        max_connections,
+        min_connections: pool.options().get_min_connections(),
+        open,
+        idle,
+        in_use,
+        utilization: in_use as f64 / max_connections.max(1) as f64,
+        peak_in_use: samples.iter().map(|s| s.in_use).max().unwrap_or(in_use),
+        acquire_ms_p50: percentile(0.5),
+        acquire_ms_p95: percentile(0.95),
+        acquire_ms_max: waits.last().copied(),
+        acquire_timeouts: samples.iter().filter(|s| s.acquire.is_none()).count(),
+        samples: samples.len(),
+    }))
+}
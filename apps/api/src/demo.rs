@@ -0,0 +1,91 @@
+use crate::state::AppState;
+
+// Cycled through, so any number of users gets varied content
+const POSTS: &[&str] = &[
+    "Just pushed the first working build. Feedback welcome!",
+    "Anyone else going to the hackathon this weekend?",
+    "Looking for someone who knows their way around shaders.",
+    "Wrote up what we learned from our first user interviews.",
+];
+const PROJECTS: &[(&str, &str, &[&str], &[&str])] = &[
+    (
+        "Campus Rideshare",
+        "Matching students heading the same way.",
+        &["Designer", "Backend developer"],
+        &["mobile", "maps"],
+    ),
+    (
+        "Study Buddy",
+        "Find people taking the same courses.",
+        &["Frontend developer"],
+        &["education", "web"],
+    ),
+    (
+        "Robot Arm",
+        "A cheap 3D printed arm for the robotics club.",
+        &["Mechanical engineer"],
+        &["hardware", "robotics"],
+    ),
+];
+
+/// Sample users, each with a verified email, a post and a project, for trying things out
+/// locally. Returns the usernames.
+pub async fn seed(state: &AppState, users: usize) -> Result<Vec<String>, String> {
+    let mut usernames = Vec::with_capacity(users);
+    for i in 0..users {
+        let user = crate::user::insert_test_user(&state.pool)
+            .await
+            .map_err(|e| e.to_string())?;
+        crate::user::verify_email(&state.pool, user.id)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let content = POSTS[i % POSTS.len()];
+        let (title, description, looking_for, tags) = PROJECTS[i % PROJECTS.len()];
+        let slug = title.to_lowercase().replace(' ', "-");
+        let looking_for: Vec<String> = looking_for.iter().map(|s| s.to_string()).collect();
+        let tags: Vec<String> = tags.iter().map(|s| s.to_string()).collect();
+        let created = sqlx::query!(
+            r#"
+            WITH post AS (
+                INSERT INTO posts (author_id, content) VALUES ($1, $2)
+                RETURNING id, author_id, created_at
+            ), project AS (
+                INSERT INTO projects (owner_id, title, slug, description, looking_for, tags)
+                VALUES ($1, $3, $4, $5, $6, $7)
+                RETURNING id, owner_id, created_at
+            ), items AS (
+                INSERT INTO feed_items (post_id, project_id, author_id, created_at)
+                SELECT id, NULL, author_id, created_at FROM post
+                UNION ALL
+                SELECT NULL, id, owner_id, created_at FROM project
+            )
+            SELECT post.id AS post_id, project.id AS project_id FROM post, project
+            "#,
+            user.id,
+            content,
+            title,
+            slug,
+            description,
+            &looking_for,
+            &tags
+        )
+        .fetch_one(&state.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        state.search_index.sync_user(&state.pool, user.id).await;
+        state
+            .search_index
+            .sync_post(&state.pool, created.post_id)
+            .await;
+        state
+            .search_index
+            .sync_project(&state.pool, created.project_id)
+            .await;
+        usernames.push(user.username);
+    }
+
+    crate::feed::invalidate(&*state.cache).await;
+    Ok(usernames)
+}
@@ -0,0 +1,222 @@
+use askama::Template;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::email::{self, Message};
+use crate::email_preferences::EmailCategory;
+use crate::i18n::{EmailStrings, Locale};
+use crate::state::AppState;
+
+// The digest goes out Monday from 9am, in the recipient's time zone
+const SEND_HOUR: i32 = 9;
+const MAX_APPLICATIONS: i64 = 10;
+const MAX_POSTS: usize = 5;
+const EXCERPT_CHARS: usize = 140;
+
+struct DigestApplication {
+    applicant: String,
+    project_title: String,
+    project_link: String,
+}
+
+struct DigestPost {
+    author_id: Uuid,
+    author: String,
+    excerpt: String,
+    link: String,
+}
+
+#[derive(Template)]
+#[template(path = "email/weekly_digest.html")]
+struct WeeklyDigestHtml<'a> {
+    t: &'a EmailStrings,
+    greeting: &'a str,
+    applications: &'a [DigestApplication],
+    posts: &'a [&'a DigestPost],
+    dashboard_link: &'a str,
+    unsubscribe_url: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "email/weekly_digest.txt")]
+struct WeeklyDigestText<'a> {
+    t: &'a EmailStrings,
+    greeting: &'a str,
+    applications: &'a [DigestApplication],
+    posts: &'a [&'a DigestPost],
+    dashboard_link: &'a str,
+    unsubscribe_url: &'a str,
+}
+
+/// Queue the weekly digest for everyone who's due at `now`: opted in, verified, not banned,
+/// past 9am Monday where they are, and not sent one in the last 6 days.
+/// Returns how many digests were queued.
+pub async fn send_due(state: &AppState, now: DateTime<Utc>) -> Result<u64, String> {
+    // Claiming by setting digest_sent_at first means two instances never both send one.
+    // A digest that then fails to queue is skipped for the week rather than sent twice.
+    let recipients = sqlx::query!(
+        r#"
+        UPDATE email_preferences p
+        SET digest_sent_at = $1
+        FROM users u
+        JOIN local_auths l ON l.user_id = u.id
+        WHERE p.user_id = u.id
+          AND p.weekly_digest
+          AND l.verified IS TRUE
+          AND u.banned_at IS NULL
+          AND EXTRACT(ISODOW FROM $1 AT TIME ZONE p.timezone) = 1
+          AND EXTRACT(HOUR FROM $1 AT TIME ZONE p.timezone) >= $2
+          AND (p.digest_sent_at IS NULL OR p.digest_sent_at < $1 - INTERVAL '6 days')
+        RETURNING u.id, u.username, u.display_name, u.locale, l.email
+        "#,
+        now,
+        SEND_HOUR as f64
+    )
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    if recipients.is_empty() {
+        return Ok(0);
+    }
+
+    let posts = recent_posts(state, now).await?;
+    let dashboard_link = format!("{}/dashboard", state.config.frontend_url);
+
+    let mut sent = 0;
+    for recipient in recipients {
+        let applications = new_applications(state, recipient.id, &recipient.username, now).await?;
+        // Nobody wants to read about their own posts
+        let posts: Vec<&DigestPost> = posts
+            .iter()
+            .filter(|post| post.author_id != recipient.id)
+            .take(MAX_POSTS)
+            .collect();
+
+        if applications.is_empty() && posts.is_empty() {
+            continue;
+        }
+
+        let t = Locale::from_tag(&recipient.locale).email();
+        let greeting = t.digest_greeting(&recipient.display_name);
+        let queued = email::queue_optional(
+            state,
+            "weekly_digest",
+            recipient.id,
+            EmailCategory::WeeklyDigest,
+            |unsubscribe_url| {
+                let html = email::render(WeeklyDigestHtml {
+                    t,
+                    greeting: &greeting,
+                    applications: &applications,
+                    posts: &posts,
+                    dashboard_link: &dashboard_link,
+                    unsubscribe_url,
+                })?;
+                let text = email::render(WeeklyDigestText {
+                    t,
+                    greeting: &greeting,
+                    applications: &applications,
+                    posts: &posts,
+                    dashboard_link: &dashboard_link,
+                    unsubscribe_url,
+                })?;
+
+                Ok(Message {
+                    to: recipient.email.clone(),
+                    subject: t.digest_subject.to_string(),
+                    html,
+                    text,
+                    unsubscribe_url: None,
+                })
+            },
+        )
+        .await;
+
+        match queued {
+            Ok(true) => sent += 1,
+            Ok(false) => {}
+            Err(e) => tracing::error!("Failed to queue digest for {}: {}", recipient.username, e),
+        }
+    }
+
+    Ok(sent)
+}
+
+async fn new_applications(
+    state: &AppState,
+    owner_id: Uuid,
+    owner_username: &str,
+    now: DateTime<Utc>,
+) -> Result<Vec<DigestApplication>, String> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT applicant.username AS applicant, p.title, p.slug
+        FROM applications a
+        JOIN projects p ON p.id = a.project_id
+        JOIN users applicant ON applicant.id = a.applicant_id
+        WHERE p.owner_id = $1 AND a.held_at IS NULL
+          AND a.created_at > $2::timestamptz - INTERVAL '7 days' AND a.created_at <= $2
+        ORDER BY a.created_at DESC
+        LIMIT $3
+        "#,
+        owner_id,
+        now,
+        MAX_APPLICATIONS
+    )
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| DigestApplication {
+            applicant: row.applicant,
+            project_title: row.title,
+            project_link: format!(
+                "{}/{}/{}",
+                state.config.frontend_url, owner_username, row.slug
+            ),
+        })
+        .collect())
+}
+
+// Posts have no likes or replies to rank by yet, so this is simply the week's latest.
+// One extra so a recipient's own post can be dropped and still leave a full list.
+async fn recent_posts(state: &AppState, now: DateTime<Utc>) -> Result<Vec<DigestPost>, String> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT p.author_id, p.content, u.username
+        FROM posts p
+        JOIN users u ON u.id = p.author_id
+        WHERE p.created_at > $1::timestamptz - INTERVAL '7 days' AND p.created_at <= $1
+          AND u.banned_at IS NULL AND p.held_at IS NULL
+        ORDER BY p.created_at DESC
+        LIMIT $2
+        "#,
+        now,
+        MAX_POSTS as i64 + 1
+    )
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| DigestPost {
+            author_id: row.author_id,
+            excerpt: excerpt(&row.content),
+            link: format!("{}/{}", state.config.frontend_url, row.username),
+            author: row.username,
+        })
+        .collect())
+}
+
+fn excerpt(content: &str) -> String {
+    let content = content.trim();
+    if content.chars().count() <= EXCERPT_CHARS {
+        return content.to_string();
+    }
+    let cut: String = content.chars().take(EXCERPT_CHARS).collect();
+    format!("{}...", cut.trim_end())
+}
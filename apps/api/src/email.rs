@@ -0,0 +1,699 @@
+use askama::Template;
+use async_trait::async_trait;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use lettre::message::header::{ContentType, HeaderName, HeaderValue};
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+use crate::email_log;
+use crate::email_preferences::{self, EmailCategory};
+use crate::error::ApiError;
+use crate::i18n::{self, EmailStrings, Locale};
+use crate::jobs::{self, Job};
+use crate::state::AppState;
+
+/// Delivers transactional email (verification links, password resets)
+#[async_trait]
+pub trait EmailSender: Send + Sync {
+    /// Recorded in email_log, e.g. "resend"
+    fn name(&self) -> &'static str;
+
+    /// Returns the provider's id for the message, when it gives one
+    async fn send(&self, message: &Message) -> Result<Option<String>, String>;
+
+    /// Emails captured instead of sent, newest first. Only the `memory` sender keeps any.
+    fn mailbox(&self) -> Option<Vec<Message>> {
+        None
+    }
+}
+
+/// A rendered email: HTML plus a plaintext alternative for clients that don't show HTML
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Message {
+    pub to: String,
+    pub subject: String,
+    pub html: String,
+    // Missing from emails queued before plaintext bodies existed
+    #[serde(default)]
+    pub text: String,
+    /// Sent as List-Unsubscribe on optional emails, so mail clients can offer one-click unsubscribe
+    #[serde(default)]
+    pub unsubscribe_url: Option<String>,
+}
+
+/// Pick the sender from EMAIL_PROVIDER: `smtp`, `resend`, `sendgrid`, `log` (emails are
+/// logged, not sent) or `memory` (kept for GET /dev/mailbox, outside production only). Without it, EMAIL_DEV_MODE=true means `log`, and so does running outside
+/// production without RESEND_API_KEY; otherwise Resend.
+/// Panics on an unknown provider or incomplete SMTP settings, rather than dropping email.
+pub fn sender_from_env(client: reqwest::Client, is_production: bool) -> Arc<dyn EmailSender> {
+    let provider = match std::env::var("EMAIL_PROVIDER") {
+        Ok(provider) if !provider.is_empty() => provider.to_lowercase(),
+        _ => {
+            let dev_mode = match std::env::var("EMAIL_DEV_MODE") {
+                Ok(value) => matches!(value.to_lowercase().as_str(), "1" | "true" | "yes"),
+                Err(_) => !is_production && std::env::var("RESEND_API_KEY").is_err(),
+            };
+            if dev_mode { "log" } else { "resend" }.to_string()
+        }
+    };
+
+    let sender: Arc<dyn EmailSender> = match provider.as_str() {
+        "log" => {
+            tracing::info!("Email dev mode: emails are logged, not sent");
+            return Arc::new(LogSender);
+        }
+        "memory" if is_production => panic!("EMAIL_PROVIDER=memory is for local development"),
+        "memory" => {
+            tracing::info!("Email dev mode: emails are kept in memory, see GET /dev/mailbox");
+            return Arc::new(MemorySender::default());
+        }
+        "resend" => Arc::new(ResendSender::from_env(client)),
+        "sendgrid" => Arc::new(SendGridSender::from_env(client)),
+        "smtp" => Arc::new(SmtpSender::from_env().expect("Invalid SMTP settings")),
+        other => panic!("Unknown EMAIL_PROVIDER {:?}", other),
+    };
+    tracing::info!("Sending email with {}", provider);
+    sender
+}
+
+// Optionally allow configuring the FROM address, default to team@joinpraxis.me
+fn mail_from() -> String {
+    std::env::var("MAIL_FROM").unwrap_or_else(|_| "team@joinpraxis.me".to_string())
+}
+
+// Each email has an HTML and a plaintext template under templates/email/,
+// both extending the shared layout and rendered from the same fields.
+// `t` holds the wording in the recipient's language.
+
+#[derive(Template)]
+#[template(path = "email/verify_email.html")]
+struct VerifyEmailHtml<'a> {
+    t: &'a EmailStrings,
+    link: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "email/verify_email.txt")]
+struct VerifyEmailText<'a> {
+    t: &'a EmailStrings,
+    link: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "email/verify_reminder.html")]
+struct VerifyReminderHtml<'a> {
+    t: &'a EmailStrings,
+    link: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "email/verify_reminder.txt")]
+struct VerifyReminderText<'a> {
+    t: &'a EmailStrings,
+    link: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "email/reset_password.html")]
+struct ResetPasswordHtml<'a> {
+    t: &'a EmailStrings,
+    link: &'a str,
+    expires: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "email/reset_password.txt")]
+struct ResetPasswordText<'a> {
+    t: &'a EmailStrings,
+    link: &'a str,
+    expires: &'a str,
+}
+
+/// Queue the email verification link for a new (or re-requested) signup
+pub async fn send_verification_email(
+    state: &AppState,
+    to: &str,
+    token: &str,
+    locale: Locale,
+) -> Result<(), String> {
+    let link = format!("{}/verify-email?token={}", state.config.frontend_url, token);
+    let t = locale.email();
+
+    let message = Message {
+        to: to.to_string(),
+        subject: t.verify_subject.to_string(),
+        html: render(VerifyEmailHtml { t, link: &link })?,
+        text: render(VerifyEmailText { t, link: &link })?,
+        unsubscribe_url: None,
+    };
+    queue(state, "verify_email", message).await
+}
+
+/// Queue a nudge for a signup that still isn't verified, with a working verification link
+pub async fn send_verification_reminder(
+    state: &AppState,
+    to: &str,
+    token: &str,
+    locale: Locale,
+) -> Result<(), String> {
+    let link = format!("{}/verify-email?token={}", state.config.frontend_url, token);
+    let t = locale.email();
+
+    let message = Message {
+        to: to.to_string(),
+        subject: t.reminder_subject.to_string(),
+        html: render(VerifyReminderHtml { t, link: &link })?,
+        text: render(VerifyReminderText { t, link: &link })?,
+        unsubscribe_url: None,
+    };
+    queue(state, "verify_reminder", message).await
+}
+
+#[derive(Template)]
+#[template(path = "email/confirm_merge.html")]
+struct ConfirmMergeHtml<'a> {
+    t: &'a EmailStrings,
+    body: &'a str,
+    link: &'a str,
+    expires: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "email/confirm_merge.txt")]
+struct ConfirmMergeText<'a> {
+    t: &'a EmailStrings,
+    body: &'a str,
+    link: &'a str,
+    expires: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "email/waitlist_invite.html")]
+struct WaitlistInviteHtml<'a> {
+    t: &'a EmailStrings,
+    link: &'a str,
+    expires: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "email/waitlist_invite.txt")]
+struct WaitlistInviteText<'a> {
+    t: &'a EmailStrings,
+    link: &'a str,
+    expires: &'a str,
+}
+
+/// Queue a password reset link
+pub async fn send_password_reset_email(
+    state: &AppState,
+    to: &str,
+    token: &str,
+    locale: Locale,
+) -> Result<(), String> {
+    let link = format!(
+        "{}/reset-password?token={}",
+        state.config.frontend_url, token
+    );
+    let t = locale.email();
+    let expires = t.reset_expires(crate::auth::PASSWORD_RESET_TOKEN_TTL_HOURS);
+
+    let message = Message {
+        to: to.to_string(),
+        subject: t.reset_subject.to_string(),
+        html: render(ResetPasswordHtml {
+            t,
+            link: &link,
+            expires: &expires,
+        })?,
+        text: render(ResetPasswordText {
+            t,
+            link: &link,
+            expires: &expires,
+        })?,
+        unsubscribe_url: None,
+    };
+    queue(state, "reset_password", message).await
+}
+
+/// Queue the link that confirms linking or merging accounts; `body` says which
+pub async fn send_merge_confirmation(
+    state: &AppState,
+    to: &str,
+    token: &str,
+    body: &str,
+    locale: Locale,
+) -> Result<(), String> {
+    let link = format!("{}/merge?token={}", state.config.frontend_url, token);
+    let t = locale.email();
+    let expires = t.reset_expires(crate::merge::MERGE_TOKEN_TTL_HOURS);
+
+    let message = Message {
+        to: to.to_string(),
+        subject: t.merge_subject.to_string(),
+        html: render(ConfirmMergeHtml {
+            t,
+            body,
+            link: &link,
+            expires: &expires,
+        })?,
+        text: render(ConfirmMergeText {
+            t,
+            body,
+            link: &link,
+            expires: &expires,
+        })?,
+        unsubscribe_url: None,
+    };
+    queue(state, "confirm_merge", message).await
+}
+
+/// Queue the signup link for someone let in from the waitlist
+pub async fn send_waitlist_invite(
+    state: &AppState,
+    to: &str,
+    token: &str,
+    locale: Locale,
+) -> Result<(), String> {
+    let link = format!(
+        "{}/signup?waitlist_token={}",
+        state.config.frontend_url, token
+    );
+    let t = locale.email();
+    let expires = t.waitlist_expires(crate::waitlist::WAITLIST_TOKEN_TTL_DAYS);
+
+    let message = Message {
+        to: to.to_string(),
+        subject: t.waitlist_subject.to_string(),
+        html: render(WaitlistInviteHtml {
+            t,
+            link: &link,
+            expires: &expires,
+        })?,
+        text: render(WaitlistInviteText {
+            t,
+            link: &link,
+            expires: &expires,
+        })?,
+        unsubscribe_url: None,
+    };
+    queue(state, "waitlist_invite", message).await
+}
+
+/// Queue an email the user can opt out of, unless they have. `build` gets the unsubscribe
+/// link to put in the footer. Returns whether the email was queued.
+pub async fn queue_optional(
+    state: &AppState,
+    kind: &str,
+    user_id: Uuid,
+    category: EmailCategory,
+    build: impl FnOnce(&str) -> Result<Message, String>,
+) -> Result<bool, String> {
+    let prefs = email_preferences::load(&state.pool, user_id)
+        .await
+        .map_err(|e| e.to_string())?;
+    if !prefs.allows(category) {
+        return Ok(false);
+    }
+
+    let unsubscribe_url = email_preferences::unsubscribe_url(&state.config, user_id, category);
+    let mut message = build(&unsubscribe_url)?;
+    message.unsubscribe_url = Some(unsubscribe_url);
+    queue(state, kind, message).await.map(|_| true)
+}
+
+pub(crate) fn render(template: impl Template) -> Result<String, String> {
+    template
+        .render()
+        .map_err(|e| format!("Failed to render email: {}", e))
+}
+
+// Sending happens in the job worker, which retries if the provider is down
+async fn queue(state: &AppState, kind: &str, message: Message) -> Result<(), String> {
+    queue_logged(state, kind, message, None).await.map(|_| ())
+}
+
+/// Log the email as `kind` (e.g. "verify_email") and queue it. Returns the email_log id.
+pub(crate) async fn queue_logged(
+    state: &AppState,
+    kind: &str,
+    message: Message,
+    resent_from: Option<Uuid>,
+) -> Result<Uuid, String> {
+    let log_id = email_log::insert(&state.pool, kind, &message, resent_from)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let job = Job::SendEmail {
+        log_id: Some(log_id),
+        message,
+    };
+    jobs::enqueue(&state.pool, &job)
+        .await
+        .map(|_| log_id)
+        .map_err(|e| e.to_string())
+}
+
+/// Dev mode: writes emails to the log, so links can be copied from the terminal
+pub struct LogSender;
+
+#[async_trait]
+impl EmailSender for LogSender {
+    fn name(&self) -> &'static str {
+        "log"
+    }
+
+    async fn send(&self, message: &Message) -> Result<Option<String>, String> {
+        // The plaintext body has the links without any HTML around them
+        tracing::info!(
+            "[email dev mode] To: {} | Subject: {}\n{}",
+            message.to,
+            message.subject,
+            message.text
+        );
+        Ok(None)
+    }
+}
+
+// Enough for a few signups and resets without growing forever
+const MAILBOX_SIZE: usize = 100;
+
+/// Dev mode: keeps the latest emails for GET /dev/mailbox, so verification and reset flows
+/// can be clicked through without a mail server
+#[derive(Default)]
+pub struct MemorySender {
+    sent: Mutex<VecDeque<Message>>,
+}
+
+#[async_trait]
+impl EmailSender for MemorySender {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    async fn send(&self, message: &Message) -> Result<Option<String>, String> {
+        let mut sent = self.sent.lock().unwrap();
+        if sent.len() == MAILBOX_SIZE {
+            sent.pop_back();
+        }
+        sent.push_front(message.clone());
+        Ok(None)
+    }
+
+    fn mailbox(&self) -> Option<Vec<Message>> {
+        Some(self.sent.lock().unwrap().iter().cloned().collect())
+    }
+}
+
+#[derive(Deserialize)]
+pub struct MailboxQuery {
+    /// Only emails to this address
+    pub to: Option<String>,
+}
+
+/// Emails captured by the `memory` sender, newest first. 404 unless EMAIL_PROVIDER=memory,
+/// which is refused in production: bodies include live verification and reset links.
+pub async fn dev_mailbox(
+    State(state): State<AppState>,
+    Query(query): Query<MailboxQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let mailbox = state
+        .email_sender
+        .mailbox()
+        .filter(|_| !state.config.is_production)
+        .ok_or(ApiError::new(StatusCode::NOT_FOUND, &i18n::NOT_FOUND))?;
+
+    let emails: Vec<Message> = match query.to {
+        Some(to) => mailbox
+            .into_iter()
+            .filter(|email| email.to.eq_ignore_ascii_case(&to))
+            .collect(),
+        None => mailbox,
+    };
+
+    Ok(Json(emails))
+}
+
+#[derive(Serialize)]
+struct ResendEmailRequest {
+    from: String,
+    to: Vec<String>,
+    subject: String,
+    html: String,
+    text: String,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    headers: HashMap<&'static str, String>,
+}
+
+/// Sends email through the Resend HTTP API
+pub struct ResendSender {
+    client: reqwest::Client,
+    api_key: Option<String>,
+    from: String,
+}
+
+impl ResendSender {
+    pub fn from_env(client: reqwest::Client) -> Self {
+        Self {
+            client,
+            api_key: std::env::var("RESEND_API_KEY").ok(),
+            from: mail_from(),
+        }
+    }
+}
+
+#[async_trait]
+impl EmailSender for ResendSender {
+    fn name(&self) -> &'static str {
+        "resend"
+    }
+
+    async fn send(&self, message: &Message) -> Result<Option<String>, String> {
+        let api_key = self
+            .api_key
+            .as_deref()
+            .ok_or_else(|| "RESEND_API_KEY not set".to_string())?;
+
+        let body = ResendEmailRequest {
+            from: self.from.clone(),
+            to: vec![message.to.clone()],
+            subject: message.subject.clone(),
+            html: message.html.clone(),
+            text: message.text.clone(),
+            headers: unsubscribe_headers(message),
+        };
+
+        let res = self
+            .client
+            .post("https://api.resend.com/emails")
+            .bearer_auth(api_key)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to send email request: {}", e))?;
+
+        if !res.status().is_success() {
+            let text = res
+                .text()
+                .await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            return Err(format!("Resend API error: {}", text));
+        }
+
+        #[derive(Deserialize)]
+        struct ResendEmailResponse {
+            id: Option<String>,
+        }
+        Ok(res
+            .json::<ResendEmailResponse>()
+            .await
+            .ok()
+            .and_then(|body| body.id))
+    }
+}
+
+/// Sends email through the SendGrid v3 HTTP API
+pub struct SendGridSender {
+    client: reqwest::Client,
+    api_key: Option<String>,
+    from: String,
+}
+
+impl SendGridSender {
+    pub fn from_env(client: reqwest::Client) -> Self {
+        Self {
+            client,
+            api_key: std::env::var("SENDGRID_API_KEY").ok(),
+            from: mail_from(),
+        }
+    }
+}
+
+#[async_trait]
+impl EmailSender for SendGridSender {
+    fn name(&self) -> &'static str {
+        "sendgrid"
+    }
+
+    async fn send(&self, message: &Message) -> Result<Option<String>, String> {
+        let api_key = self
+            .api_key
+            .as_deref()
+            .ok_or_else(|| "SENDGRID_API_KEY not set".to_string())?;
+
+        // SendGrid wants text/plain first, and rejects empty bodies
+        let mut content = Vec::new();
+        if !message.text.is_empty() {
+            content.push(serde_json::json!({ "type": "text/plain", "value": message.text }));
+        }
+        content.push(serde_json::json!({ "type": "text/html", "value": message.html }));
+
+        let mut body = serde_json::json!({
+            "personalizations": [{ "to": [{ "email": message.to }] }],
+            "from": { "email": self.from },
+            "subject": message.subject,
+            "content": content,
+        });
+        let headers = unsubscribe_headers(message);
+        if !headers.is_empty() {
+            body["headers"] = serde_json::json!(headers);
+        }
+
+        let res = self
+            .client
+            .post("https://api.sendgrid.com/v3/mail/send")
+            .bearer_auth(api_key)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to send email request: {}", e))?;
+
+        if !res.status().is_success() {
+            let text = res
+                .text()
+                .await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            return Err(format!("SendGrid API error: {}", text));
+        }
+
+        Ok(res
+            .headers()
+            .get("x-message-id")
+            .and_then(|id| id.to_str().ok())
+            .map(str::to_string))
+    }
+}
+
+/// Sends email through an SMTP relay
+pub struct SmtpSender {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpSender {
+    /// SMTP_HOST, plus optional SMTP_PORT (587 with STARTTLS by default; 465 uses implicit TLS),
+    /// SMTP_USERNAME and SMTP_PASSWORD. SMTP_TLS=false is for local mail catchers only.
+    pub fn from_env() -> Result<Self, String> {
+        let host = std::env::var("SMTP_HOST").map_err(|_| "SMTP_HOST not set".to_string())?;
+        let port = match std::env::var("SMTP_PORT") {
+            Ok(port) => port
+                .parse::<u16>()
+                .map_err(|_| format!("Invalid SMTP_PORT {:?}", port))?,
+            Err(_) => 587,
+        };
+        let tls = std::env::var("SMTP_TLS")
+            .map(|value| !matches!(value.to_lowercase().as_str(), "0" | "false" | "no"))
+            .unwrap_or(true);
+
+        let mut builder = if !tls {
+            AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&host)
+        } else if port == 465 {
+            AsyncSmtpTransport::<Tokio1Executor>::relay(&host).map_err(|e| e.to_string())?
+        } else {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&host)
+                .map_err(|e| e.to_string())?
+        }
+        .port(port);
+
+        if let (Ok(username), Ok(password)) = (
+            std::env::var("SMTP_USERNAME"),
+            std::env::var("SMTP_PASSWORD"),
+        ) {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+
+        let from = mail_from()
+            .parse()
+            .map_err(|e| format!("Invalid MAIL_FROM: {}", e))?;
+
+        Ok(Self {
+            transport: builder.build(),
+            from,
+        })
+    }
+}
+
+#[async_trait]
+impl EmailSender for SmtpSender {
+    fn name(&self) -> &'static str {
+        "smtp"
+    }
+
+    async fn send(&self, message: &Message) -> Result<Option<String>, String> {
+        let to: Mailbox = message
+            .to
+            .parse()
+            .map_err(|e| format!("Invalid recipient {}: {}", message.to, e))?;
+
+        let mut builder = lettre::Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(&message.subject);
+        for (name, value) in unsubscribe_headers(message) {
+            builder = builder.raw_header(HeaderValue::new(
+                HeaderName::new_from_ascii_str(name),
+                value,
+            ));
+        }
+
+        let email = if message.text.is_empty() {
+            builder
+                .header(ContentType::TEXT_HTML)
+                .body(message.html.clone())
+        } else {
+            builder.multipart(MultiPart::alternative_plain_html(
+                message.text.clone(),
+                message.html.clone(),
+            ))
+        }
+        .map_err(|e| format!("Failed to build email: {}", e))?;
+
+        // Servers usually answer with something like "2.0.0 Ok: queued as 4F1C2"
+        self.transport
+            .send(email)
+            .await
+            .map(|response| response.first_line().map(str::to_string))
+            .map_err(|e| format!("SMTP error: {}", e))
+    }
+}
+
+fn unsubscribe_headers(message: &Message) -> HashMap<&'static str, String> {
+    let mut headers = HashMap::new();
+    if let Some(url) = &message.unsubscribe_url {
+        headers.insert("List-Unsubscribe", format!("<{}>", url));
+        headers.insert(
+            "List-Unsubscribe-Post",
+            "List-Unsubscribe=One-Click".to_string(),
+        );
+    }
+    headers
+}
@@ -0,0 +1,177 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::Serialize;
+use sqlx::PgPool;
+use tower_sessions::Session;
+use uuid::Uuid;
+
+use crate::email::{self, Message};
+use crate::error::ApiError;
+use crate::extractors::AdminUser;
+use crate::i18n;
+use crate::state::AppState;
+
+// How many of a user's emails GET /admin/users/:id/emails returns
+const RECENT_EMAILS: i64 = 50;
+
+/// One row of email_log, without the body (reset links are credentials)
+#[derive(Serialize)]
+pub struct EmailLogEntry {
+    pub id: Uuid,
+    pub recipient: String,
+    pub kind: String,
+    pub subject: String,
+    pub status: String,
+    pub provider: Option<String>,
+    pub provider_message_id: Option<String>,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub resent_from: Option<Uuid>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub sent_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Log an email about to be queued. The user is matched by address, since
+/// verification and reset emails are sent to an address rather than a user.
+pub async fn insert(
+    pool: &PgPool,
+    kind: &str,
+    message: &Message,
+    resent_from: Option<Uuid>,
+) -> Result<Uuid, sqlx::Error> {
+    let payload = serde_json::to_value(message).expect("messages always serialize");
+    sqlx::query_scalar!(
+        r#"
+        INSERT INTO email_log (user_id, recipient, kind, subject, message, resent_from)
+        VALUES ((SELECT user_id FROM local_auths WHERE email = $1), $1, $2, $3, $4, $5)
+        RETURNING id
+        "#,
+        message.to,
+        kind,
+        message.subject,
+        payload,
+        resent_from
+    )
+    .fetch_one(pool)
+    .await
+}
+
+/// Update the log after a send attempt. Failures are `retrying` until the job gives up.
+pub async fn record_attempt(
+    pool: &PgPool,
+    log_id: Uuid,
+    provider: &str,
+    result: &Result<Option<String>, String>,
+    last_attempt: bool,
+) {
+    let (status, provider_message_id, error) = match result {
+        Ok(message_id) => ("sent", message_id.as_deref(), None),
+        Err(e) if last_attempt => ("failed", None, Some(e.as_str())),
+        Err(e) => ("retrying", None, Some(e.as_str())),
+    };
+
+    let updated = sqlx::query!(
+        r#"
+        UPDATE email_log
+        SET status = $2, provider = $3, provider_message_id = $4, last_error = $5,
+            attempts = attempts + 1,
+            sent_at = CASE WHEN $2 = 'sent' THEN NOW() ELSE sent_at END
+        WHERE id = $1
+        "#,
+        log_id,
+        status,
+        provider,
+        provider_message_id,
+        error
+    )
+    .execute(pool)
+    .await;
+
+    // The email itself went out (or not) regardless; don't fail the job over its log
+    if let Err(e) = updated {
+        tracing::error!("Failed to update email log {}: {}", log_id, e);
+    }
+}
+
+/// Recent emails sent to a user, newest first (admins only)
+pub async fn list_for_user(
+    State(pool): State<PgPool>,
+    _: AdminUser,
+    Path(user_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let entries = sqlx::query_as!(
+        EmailLogEntry,
+        r#"
+        SELECT id, recipient, kind, subject, status, provider, provider_message_id,
+               attempts, last_error, resent_from, created_at, sent_at
+        FROM email_log
+        WHERE user_id = $1
+        ORDER BY created_at DESC
+        LIMIT $2
+        "#,
+        user_id,
+        RECENT_EMAILS
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(entries))
+}
+
+/// Queue a logged email again, unchanged, for "I never got it".
+/// Links in it (e.g. password resets) may have expired since; the original row is untouched.
+pub async fn resend(
+    State(state): State<AppState>,
+    AdminUser(admin): AdminUser,
+    session: Session,
+    Path(log_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let original = sqlx::query!(
+        "SELECT user_id, kind, message FROM email_log WHERE id = $1",
+        log_id
+    )
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or(ApiError::new(StatusCode::NOT_FOUND, &i18n::EMAIL_NOT_FOUND))?;
+
+    let message: Message = serde_json::from_value(original.message)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let new_id = email::queue_logged(&state, &original.kind, message, Some(log_id))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    crate::audit::record(
+        &state.pool,
+        &session,
+        admin.id,
+        "admin.email_resent",
+        original.user_id,
+        Some(&format!("{} ({})", original.kind, log_id)),
+    )
+    .await?;
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(serde_json::json!({ "id": new_id })),
+    ))
+}
+
+/// Delete log entries over `days` old (the email_log retention policy). Returns the
+/// number removed.
+pub async fn purge_old(pool: &PgPool, days: i32) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        "DELETE FROM email_log WHERE created_at < NOW() - make_interval(days => $1)",
+        days
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
@@ -0,0 +1,318 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sqlx::PgPool;
+use std::str::FromStr;
+use uuid::Uuid;
+
+use crate::error::{ApiError, Message};
+use crate::extractors::AuthUser;
+use crate::i18n;
+use crate::state::{AppState, Config};
+use crate::validation::{Validate, ValidatedJson, ValidationErrors};
+
+/// Kinds of optional email a user can turn off
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmailCategory {
+    /// Alerts about the account (e.g. a new sign-in), not verification or password resets
+    Security,
+    ProductUpdates,
+    Activity,
+    /// Opt-in, unlike the others
+    WeeklyDigest,
+}
+
+impl EmailCategory {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Security => "security",
+            Self::ProductUpdates => "product_updates",
+            Self::Activity => "activity",
+            Self::WeeklyDigest => "weekly_digest",
+        }
+    }
+}
+
+impl FromStr for EmailCategory {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "security" => Ok(Self::Security),
+            "product_updates" => Ok(Self::ProductUpdates),
+            "activity" => Ok(Self::Activity),
+            "weekly_digest" => Ok(Self::WeeklyDigest),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A user's email_preferences row; users without one get the defaults
+#[derive(Clone, Debug, Serialize)]
+pub struct EmailPreferences {
+    pub security: bool,
+    pub product_updates: bool,
+    pub activity: bool,
+    pub weekly_digest: bool,
+    /// IANA name (e.g. "Europe/Berlin"), used to time the digest
+    pub timezone: String,
+    /// Local hours (0-23) during which the unread notification summary waits,
+    /// e.g. 22 to 7. Equal hours mean no quiet hours.
+    pub quiet_hours_start: i32,
+    pub quiet_hours_end: i32,
+}
+
+impl Default for EmailPreferences {
+    fn default() -> Self {
+        Self {
+            security: true,
+            product_updates: true,
+            activity: true,
+            weekly_digest: false,
+            timezone: "UTC".to_string(),
+            quiet_hours_start: 0,
+            quiet_hours_end: 0,
+        }
+    }
+}
+
+impl EmailPreferences {
+    pub fn allows(&self, category: EmailCategory) -> bool {
+        match category {
+            EmailCategory::Security => self.security,
+            EmailCategory::ProductUpdates => self.product_updates,
+            EmailCategory::Activity => self.activity,
+            EmailCategory::WeeklyDigest => self.weekly_digest,
+        }
+    }
+
+    fn set(&mut self, category: EmailCategory, enabled: bool) {
+        match category {
+            EmailCategory::Security => self.security = enabled,
+            EmailCategory::ProductUpdates => self.product_updates = enabled,
+            EmailCategory::Activity => self.activity = enabled,
+            EmailCategory::WeeklyDigest => self.weekly_digest = enabled,
+        }
+    }
+}
+
+pub async fn load(pool: &PgPool, user_id: Uuid) -> Result<EmailPreferences, sqlx::Error> {
+    let prefs = sqlx::query_as!(
+        EmailPreferences,
+        r#"
+        SELECT security, product_updates, activity, weekly_digest, timezone,
+               quiet_hours_start, quiet_hours_end
+        FROM email_preferences WHERE user_id = $1
+        "#,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(prefs.unwrap_or_default())
+}
+
+async fn save(pool: &PgPool, user_id: Uuid, prefs: &EmailPreferences) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO email_preferences
+            (user_id, security, product_updates, activity, weekly_digest, timezone,
+             quiet_hours_start, quiet_hours_end, updated_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, NOW())
+        ON CONFLICT (user_id) DO UPDATE
+        SET security = $2, product_updates = $3, activity = $4, weekly_digest = $5,
+            timezone = $6, quiet_hours_start = $7, quiet_hours_end = $8, updated_at = NOW()
+        "#,
+        user_id,
+        prefs.security,
+        prefs.product_updates,
+        prefs.activity,
+        prefs.weekly_digest,
+        prefs.timezone,
+        prefs.quiet_hours_start,
+        prefs.quiet_hours_end
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_preferences(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+) -> Result<impl IntoResponse, ApiError> {
+    let prefs = load(&pool, user.id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(prefs))
+}
+
+/// Fields left out keep their current value
+#[derive(Deserialize)]
+pub struct UpdateEmailPreferences {
+    pub security: Option<bool>,
+    pub product_updates: Option<bool>,
+    pub activity: Option<bool>,
+    pub weekly_digest: Option<bool>,
+    pub timezone: Option<String>,
+    pub quiet_hours_start: Option<i32>,
+    pub quiet_hours_end: Option<i32>,
+}
+
+impl Validate for UpdateEmailPreferences {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        if let Some(timezone) = &self.timezone {
+            errors.length("timezone", timezone, 1, 64);
+        }
+        if let Some(hour) = self.quiet_hours_start {
+            errors.range("quiet_hours_start", hour.into(), 0, 23);
+        }
+        if let Some(hour) = self.quiet_hours_end {
+            errors.range("quiet_hours_end", hour.into(), 0, 23);
+        }
+        errors.into_result()
+    }
+}
+
+pub async fn update_preferences(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+    ValidatedJson(payload): ValidatedJson<UpdateEmailPreferences>,
+) -> Result<Response, ApiError> {
+    // Postgres does the time zone math for the digest, so it decides what's valid
+    if let Some(timezone) = &payload.timezone {
+        let known = sqlx::query_scalar!(
+            r#"SELECT EXISTS(SELECT 1 FROM pg_timezone_names WHERE name = $1) AS "known!""#,
+            timezone
+        )
+        .fetch_one(&pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+        if !known {
+            let mut errors = ValidationErrors::default();
+            errors.add("timezone", "Unknown time zone");
+            return Ok(errors.into_response());
+        }
+    }
+
+    let mut prefs = load(&pool, user.id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let changes = [
+        (EmailCategory::Security, payload.security),
+        (EmailCategory::ProductUpdates, payload.product_updates),
+        (EmailCategory::Activity, payload.activity),
+        (EmailCategory::WeeklyDigest, payload.weekly_digest),
+    ];
+    for (category, enabled) in changes {
+        if let Some(enabled) = enabled {
+            prefs.set(category, enabled);
+        }
+    }
+    if let Some(timezone) = payload.timezone {
+        prefs.timezone = timezone;
+    }
+    prefs.quiet_hours_start = payload.quiet_hours_start.unwrap_or(prefs.quiet_hours_start);
+    prefs.quiet_hours_end = payload.quiet_hours_end.unwrap_or(prefs.quiet_hours_end);
+
+    save(&pool, user.id, &prefs)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(prefs).into_response())
+}
+
+// --- Unsubscribe links --- //
+
+fn signer(config: &Config, user_id: Uuid, category: EmailCategory) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(&config.email_signing_secret)
+        .expect("HMAC accepts keys of any length");
+    mac.update(format!("unsubscribe:{}:{}", user_id, category.as_str()).as_bytes());
+    mac
+}
+
+/// `<user id>.<category>.<signature>`: anyone holding it can turn off that one category
+/// for that one user, and nothing else. It doesn't expire, like the emails it's in.
+pub fn unsubscribe_token(config: &Config, user_id: Uuid, category: EmailCategory) -> String {
+    let signature = signer(config, user_id, category).finalize().into_bytes();
+    format!(
+        "{}.{}.{}",
+        user_id,
+        category.as_str(),
+        BASE64_URL_SAFE_NO_PAD.encode(signature)
+    )
+}
+
+fn verify_token(config: &Config, token: &str) -> Option<(Uuid, EmailCategory)> {
+    let mut parts = token.split('.');
+    let user_id = Uuid::parse_str(parts.next()?).ok()?;
+    let category = EmailCategory::from_str(parts.next()?).ok()?;
+    let signature = BASE64_URL_SAFE_NO_PAD.decode(parts.next()?).ok()?;
+    if parts.next().is_some() {
+        return None;
+    }
+
+    signer(config, user_id, category)
+        .verify_slice(&signature)
+        .ok()
+        .map(|_| (user_id, category))
+}
+
+/// Goes through the frontend's /api proxy, so it works from any mail client
+pub fn unsubscribe_url(config: &Config, user_id: Uuid, category: EmailCategory) -> String {
+    format!(
+        "{}/api/email/unsubscribe?token={}",
+        config.frontend_url,
+        unsubscribe_token(config, user_id, category)
+    )
+}
+
+#[derive(Deserialize)]
+pub struct UnsubscribeQuery {
+    pub token: String,
+}
+
+/// GET for links clicked in an email, POST for one-click unsubscribe from the mail client
+/// (RFC 8058). No login needed: the signed token is the proof.
+pub async fn unsubscribe(
+    State(state): State<AppState>,
+    Query(query): Query<UnsubscribeQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let (user_id, category) = verify_token(&state.config, &query.token).ok_or(ApiError::new(
+        StatusCode::BAD_REQUEST,
+        &i18n::INVALID_UNSUBSCRIBE_LINK,
+    ))?;
+
+    let mut prefs = load(&state.pool, user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    prefs.set(category, false);
+
+    // The user may have deleted their account since the email went out
+    if let Err(e) = save(&state.pool, user_id, &prefs).await {
+        if e.as_database_error()
+            .is_some_and(|e| e.is_foreign_key_violation())
+        {
+            return Err(ApiError::new(
+                StatusCode::NOT_FOUND,
+                &i18n::ACCOUNT_NOT_FOUND,
+            ));
+        }
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into());
+    }
+
+    Ok(Message::new(&i18n::UNSUBSCRIBED).json(serde_json::json!({ "category": category })))
+}
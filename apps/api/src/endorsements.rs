@@ -0,0 +1,228 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::extractors::{AuthUser, VerifiedUser};
+use crate::i18n;
+use crate::notifications::NotificationKind;
+use crate::state::AppState;
+use crate::user::invalidate_profile;
+use crate::validation::{normalize_line, Validate, ValidatedJson, ValidationErrors};
+
+// Endorsers shown with each skill on a profile
+const TOP_ENDORSERS: i64 = 3;
+
+#[derive(Deserialize)]
+pub struct EndorseRequest {
+    pub skill: String,
+}
+
+impl Validate for EndorseRequest {
+    fn normalize(&mut self) {
+        normalize_line(&mut self.skill);
+    }
+
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.length("skill", &self.skill, 1, 50);
+        errors.into_result()
+    }
+}
+
+/// How many people vouch for one of a user's skills
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SkillEndorsements {
+    pub skill: String,
+    pub count: i64,
+    /// The endorsers with the most reputation, then the earliest
+    pub top_endorsers: Vec<Endorser>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Endorser {
+    pub username: String,
+    pub display_name: String,
+    pub avatar_url: Option<String>,
+}
+
+struct Target {
+    id: Uuid,
+    username: String,
+    skill: String,
+}
+
+/// The user and how they spell `skill` in their profile, or 404. Matches ignoring case,
+/// so endorsing "rust" counts for "Rust".
+async fn find_target(
+    pool: &PgPool,
+    me: Uuid,
+    username: &str,
+    skill: &str,
+) -> Result<Target, ApiError> {
+    let user = sqlx::query!(
+        r#"
+        SELECT u.id, u.username,
+            (SELECT s FROM unnest(u.skills) s WHERE lower(s) = lower($2) LIMIT 1) AS skill
+        FROM users u
+        WHERE u.username = $1 AND u.banned_at IS NULL
+        "#,
+        username.to_lowercase(),
+        skill
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or(ApiError::new(StatusCode::NOT_FOUND, &i18n::USER_NOT_FOUND))?;
+
+    if user.id == me {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            &i18n::CANNOT_ENDORSE_SELF,
+        ));
+    }
+    let skill = user.skill.ok_or(ApiError::new(
+        StatusCode::NOT_FOUND,
+        &i18n::SKILL_NOT_LISTED,
+    ))?;
+    Ok(Target {
+        id: user.id,
+        username: user.username,
+        skill,
+    })
+}
+
+/// Endorse one of another user's listed skills, once per skill. They're notified.
+pub async fn endorse(
+    State(state): State<AppState>,
+    VerifiedUser(user): VerifiedUser,
+    Path(username): Path<String>,
+    ValidatedJson(payload): ValidatedJson<EndorseRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let target = find_target(&state.pool, user.id, &username, &payload.skill).await?;
+    let blocked = crate::relationships::blocked_either_way(&state.pool, user.id, target.id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if blocked {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            &i18n::CANNOT_ENDORSE_USER,
+        ));
+    }
+
+    let inserted = sqlx::query!(
+        r#"
+        INSERT INTO skill_endorsements (user_id, endorser_id, skill) VALUES ($1, $2, $3)
+        ON CONFLICT DO NOTHING
+        "#,
+        target.id,
+        user.id,
+        target.skill
+    )
+    .execute(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if inserted.rows_affected() == 0 {
+        return Err(ApiError::new(StatusCode::CONFLICT, &i18n::ALREADY_ENDORSED));
+    }
+
+    invalidate_profile(&*state.cache, &target.username).await;
+
+    // The endorsement stands either way, so a failure here is only logged
+    if let Err(e) = crate::notifications::notify(
+        &state,
+        target.id,
+        NotificationKind::Endorsement,
+        user.id,
+        serde_json::json!({ "skill": target.skill }),
+    )
+    .await
+    {
+        tracing::error!("Failed to notify {} of an endorsement: {}", target.id, e);
+    }
+
+    Ok(StatusCode::CREATED)
+}
+
+/// Take back an endorsement; one that was never made is a 404
+pub async fn unendorse(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path((username, skill)): Path<(String, String)>,
+) -> Result<impl IntoResponse, ApiError> {
+    let target = find_target(&state.pool, user.id, &username, &skill).await?;
+    let deleted = sqlx::query!(
+        "DELETE FROM skill_endorsements WHERE user_id = $1 AND endorser_id = $2 AND skill = $3",
+        target.id,
+        user.id,
+        target.skill
+    )
+    .execute(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if deleted.rows_affected() == 0 {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            &i18n::ENDORSEMENT_NOT_FOUND,
+        ));
+    }
+
+    invalidate_profile(&*state.cache, &target.username).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Endorsements of each user's currently listed skills, most endorsed first. Endorsements
+/// from banned users don't count.
+pub async fn for_users(
+    pool: &PgPool,
+    user_ids: &[Uuid],
+) -> Result<HashMap<Uuid, Vec<SkillEndorsements>>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT user_id AS "user_id!", skill AS "skill!", count AS "count!",
+            username AS "username!", display_name AS "display_name!", avatar_url
+        FROM (
+            SELECT e.user_id, e.skill, u.username, u.display_name, u.avatar_url,
+                COUNT(*) OVER (PARTITION BY e.user_id, e.skill) AS count,
+                ROW_NUMBER() OVER (
+                    PARTITION BY e.user_id, e.skill ORDER BY u.reputation DESC, e.created_at
+                ) AS rank
+            FROM skill_endorsements e
+            JOIN users t ON t.id = e.user_id
+            JOIN users u ON u.id = e.endorser_id
+            WHERE e.user_id = ANY($1) AND e.skill = ANY(t.skills) AND u.banned_at IS NULL
+        ) ranked
+        WHERE rank <= $2
+        ORDER BY user_id, count DESC, skill, rank
+        "#,
+        user_ids,
+        TOP_ENDORSERS
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut by_user: HashMap<Uuid, Vec<SkillEndorsements>> = HashMap::new();
+    for row in rows {
+        let skills = by_user.entry(row.user_id).or_default();
+        let endorser = Endorser {
+            username: row.username,
+            display_name: row.display_name,
+            avatar_url: row.avatar_url,
+        };
+        match skills.last_mut() {
+            Some(last) if last.skill == row.skill => last.top_endorsers.push(endorser),
+            _ => skills.push(SkillEndorsements {
+                skill: row.skill,
+                count: row.count,
+                top_endorsers: vec![endorser],
+            }),
+        }
+    }
+    Ok(by_user)
+}
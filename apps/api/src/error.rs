@@ -0,0 +1,350 @@
+use std::fmt;
+
+use axum::{
+    body::{Body, HttpBody},
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use tower_sessions::Session;
+use uuid::Uuid;
+
+use crate::i18n::{ApiMessage, Locale};
+use crate::state::AppState;
+
+// Error bodies are short messages; a longer one is logged as unreadable
+const MAX_ERROR_BODY: usize = 64 * 1024;
+
+/// Logs every server error (5xx) with a new error ID, which is also sent back in `X-Error-Id`.
+/// In production the body becomes a generic message with that ID: handlers put sqlx, reqwest
+/// and argon2 errors straight into it, and those describe our schema and infrastructure.
+pub async fn sanitize(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+    if !response.status().is_server_error() {
+        return response;
+    }
+
+    let error_id = Uuid::new_v4().simple().to_string();
+    let (mut parts, body) = response.into_parts();
+    let detail = match axum::body::to_bytes(body, MAX_ERROR_BODY).await {
+        Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+        Err(e) => format!("(unreadable error body: {})", e),
+    };
+    tracing::error!(error_id, status = parts.status.as_u16(), "{}", detail);
+
+    let body = if state.config.is_production {
+        parts.headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("text/plain; charset=utf-8"),
+        );
+        format!(
+            "{} (error ID {})",
+            parts.status.canonical_reason().unwrap_or("Server error"),
+            error_id
+        )
+    } else {
+        detail
+    };
+    parts.headers.remove(header::CONTENT_LENGTH);
+    if let Ok(value) = HeaderValue::from_str(&error_id) {
+        parts.headers.insert("x-error-id", value);
+    }
+    Response::from_parts(parts, Body::from(body))
+}
+
+/// A catalog message (i18n.rs) with the values for its `{name}` placeholders.
+/// As a response it's the English text; `localize` translates it for JSON clients.
+#[derive(Clone, Debug)]
+pub struct Message {
+    entry: &'static ApiMessage,
+    args: Vec<(&'static str, Arg)>,
+}
+
+#[derive(Clone, Debug)]
+enum Arg {
+    Text(String),
+    Message(Message),
+}
+
+impl Message {
+    pub fn new(entry: &'static ApiMessage) -> Self {
+        Self {
+            entry,
+            args: Vec::new(),
+        }
+    }
+
+    pub fn arg(mut self, name: &'static str, value: impl ToString) -> Self {
+        self.args.push((name, Arg::Text(value.to_string())));
+        self
+    }
+
+    /// A placeholder filled with another catalog message, translated along with this one
+    pub fn arg_message(mut self, name: &'static str, value: Message) -> Self {
+        self.args.push((name, Arg::Message(value)));
+        self
+    }
+
+    /// A JSON response with this message as its `message` field, next to the fields in `body`
+    pub fn json(self, mut body: serde_json::Value) -> Response {
+        body["message"] = self.text(Locale::En).into();
+        let mut response = axum::Json(body).into_response();
+        response.extensions_mut().insert(self);
+        response
+    }
+
+    pub fn code(&self) -> &'static str {
+        self.entry.code
+    }
+
+    pub fn text(&self, locale: Locale) -> String {
+        self.args.iter().fold(
+            self.entry.text(locale).to_string(),
+            |text, (name, value)| {
+                let value = match value {
+                    Arg::Text(value) => value.clone(),
+                    Arg::Message(message) => message.text(locale),
+                };
+                text.replace(&format!("{{{}}}", name), &value)
+            },
+        )
+    }
+}
+
+impl IntoResponse for Message {
+    fn into_response(self) -> Response {
+        let mut response = self.text(Locale::En).into_response();
+        response.extensions_mut().insert(self);
+        response
+    }
+}
+
+/// What handlers return when a request fails. User-facing messages come from the catalog, so
+/// they have a code and get translated; developer-facing and server errors are plain text.
+/// `(StatusCode, String)` converts into the latter, so `?` works on those as before.
+#[derive(Debug)]
+pub enum ApiError {
+    Message(StatusCode, Message),
+    Text(StatusCode, String),
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, entry: &'static ApiMessage) -> Self {
+        Self::Message(status, Message::new(entry))
+    }
+
+    /// Fills in a placeholder of a catalog message; text errors have none
+    pub fn arg(self, name: &'static str, value: impl ToString) -> Self {
+        match self {
+            Self::Message(status, message) => Self::Message(status, message.arg(name, value)),
+            text => text,
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::Message(status, _) | Self::Text(status, _) => *status,
+        }
+    }
+}
+
+impl From<(StatusCode, String)> for ApiError {
+    fn from((status, text): (StatusCode, String)) -> Self {
+        Self::Text(status, text)
+    }
+}
+
+impl From<(StatusCode, Message)> for ApiError {
+    fn from((status, message): (StatusCode, Message)) -> Self {
+        Self::Message(status, message)
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Message(_, message) => f.write_str(&message.text(Locale::En)),
+            Self::Text(_, text) => f.write_str(text),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        match self {
+            Self::Message(status, message) => (status, message).into_response(),
+            Self::Text(status, text) => (status, text).into_response(),
+        }
+    }
+}
+
+/// Session key for the cached locale, stored with the user id like the cached role
+const LOCALE_KEY: &str = "locale";
+
+#[derive(Serialize, Deserialize)]
+struct CachedLocale {
+    user_id: Uuid,
+    locale: Locale,
+}
+
+/// For clients that send `Accept: application/json`, turns plain text messages into
+/// `{"code": ..., "message": ...}`, and gives JSON errors with a `message` a `code` too.
+/// Responses carrying a catalog `Message` get its code and are translated into the user's
+/// stored locale, or for logged out users the one `Accept-Language` prefers. Other errors keep
+/// their text, with a code from the status, e.g. "not_found". Other clients get plain text.
+pub async fn localize(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let headers = request.headers();
+    let wants_json = headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|accept| accept.contains("application/json"));
+    if !wants_json {
+        return next.run(request).await;
+    }
+    let accept_language = headers
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(Locale::from_accept_language);
+    let session = request.extensions().get::<Session>().cloned();
+    // Before the handler runs, as logging out flushes the session
+    let cached_locale = match &session {
+        Some(session) => cached_locale(session).await,
+        None => None,
+    };
+
+    let mut response = next.run(request).await;
+    let message = response.extensions_mut().remove::<Message>();
+    let status = response.status();
+    let is_error = status.is_client_error() || status.is_server_error();
+    // Successful responses without a catalog message are left as they are
+    if message.is_none() && !is_error {
+        return response;
+    }
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let is_text = content_type.starts_with("text/plain");
+    let is_json = content_type.starts_with("application/json");
+    let small = response
+        .body()
+        .size_hint()
+        .upper()
+        .is_some_and(|size| size <= MAX_ERROR_BODY as u64);
+    if !(is_text || is_json) || !small {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let Ok(bytes) = axum::body::to_bytes(body, MAX_ERROR_BODY).await else {
+        return Response::from_parts(parts, Body::empty());
+    };
+    let mut body = if is_json {
+        match serde_json::from_slice::<serde_json::Value>(&bytes) {
+            Ok(body) if body["message"].is_string() => body,
+            _ => return Response::from_parts(parts, Body::from(bytes)),
+        }
+    } else {
+        serde_json::json!({ "message": String::from_utf8_lossy(&bytes) })
+    };
+
+    match message {
+        Some(message) => {
+            let locale = match (cached_locale, &session) {
+                (Some(locale), _) => Some(locale),
+                (None, Some(session)) => user_locale(&state, session).await,
+                (None, None) => None,
+            }
+            .or(accept_language)
+            .unwrap_or_default();
+            body["message"] = message.text(locale).into();
+            if body.get("code").is_none() {
+                body["code"] = message.code().into();
+            }
+            parts.headers.insert(
+                header::CONTENT_LANGUAGE,
+                HeaderValue::from_static(locale.as_str()),
+            );
+        }
+        None => {
+            if body.get("code").is_none() {
+                body["code"] = status_code(status).into();
+            }
+        }
+    }
+
+    parts.headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.append(
+        header::VARY,
+        HeaderValue::from_static("accept, accept-language"),
+    );
+    Response::from_parts(parts, Body::from(body.to_string()))
+}
+
+/// The logged in user's locale as cached in their session, without going to the database
+async fn cached_locale(session: &Session) -> Option<Locale> {
+    let user_id: Uuid = session.get("user_id").await.ok()??;
+    session
+        .get::<CachedLocale>(LOCALE_KEY)
+        .await
+        .ok()
+        .flatten()
+        .filter(|cached| cached.user_id == user_id)
+        .map(|cached| cached.locale)
+}
+
+/// The logged in user's stored locale, if there is one. Looked up once per session, then
+/// cached; changing it in the profile updates the cache.
+async fn user_locale(state: &AppState, session: &Session) -> Option<Locale> {
+    if let Some(locale) = cached_locale(session).await {
+        return Some(locale);
+    }
+    let user_id: Uuid = session.get("user_id").await.ok()??;
+    let locale = sqlx::query_scalar!("SELECT locale FROM users WHERE id = $1", user_id)
+        .fetch_optional(&state.pool)
+        .await
+        .ok()??;
+    let locale = Locale::from_tag(&locale);
+    cache_locale(session, user_id, locale).await;
+    Some(locale)
+}
+
+/// The language to use for messages sent outside a response, e.g. over a WebSocket: the logged
+/// in user's stored locale, or the one `Accept-Language` prefers
+pub async fn request_locale(state: &AppState, session: &Session, headers: &HeaderMap) -> Locale {
+    match user_locale(state, session).await {
+        Some(locale) => locale,
+        None => headers
+            .get(header::ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(Locale::from_accept_language)
+            .unwrap_or_default(),
+    }
+}
+
+/// Remembers the user's locale in their session, for translating messages
+pub async fn cache_locale(session: &Session, user_id: Uuid, locale: Locale) {
+    if let Err(e) = session
+        .insert(LOCALE_KEY, CachedLocale { user_id, locale })
+        .await
+    {
+        tracing::warn!("Failed to cache locale in session: {}", e);
+    }
+}
+
+/// "not_found" for 404 and so on, for errors without a code of their own
+fn status_code(status: StatusCode) -> String {
+    status
+        .canonical_reason()
+        .unwrap_or("error")
+        .to_lowercase()
+        .replace(['-', ' '], "_")
+        .replace('\'', "")
+}
@@ -0,0 +1,126 @@
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+
+// Lists change often and depend on who's asking: always check back before reusing them
+const PRIVATE: &str = "private, no-cache";
+// Public pages fetched without a session are the same for everyone, so browsers and CDNs
+// can keep them for a minute, then serve them stale while revalidating
+const PUBLIC: &str = "public, max-age=60, stale-while-revalidate=300";
+
+/// Tag successful GET responses with a hash of their body, and answer 304 Not Modified
+/// when the client already has that version (If-None-Match).
+/// The whole body is buffered, so only JSON responses are tagged.
+pub async fn etag(request: Request, next: Next) -> Response {
+    tag(request, next, PRIVATE).await
+}
+
+/// `etag` for pages anyone can see (profiles, projects, the feed), which CDNs may also cache
+/// when the request has no session. Handlers can set Last-Modified so If-Modified-Since works
+/// too. Edits change both validators, so caches pick them up once max-age runs out.
+pub async fn public(request: Request, next: Next) -> Response {
+    let anonymous = !request.headers().contains_key(header::COOKIE)
+        && !request.headers().contains_key(header::AUTHORIZATION);
+    let mut response = tag(request, next, if anonymous { PUBLIC } else { PRIVATE }).await;
+    // Logged in requests can get a Set-Cookie, so shared caches must key on it
+    response
+        .headers_mut()
+        .append(header::VARY, HeaderValue::from_static("Cookie"));
+    response
+}
+
+/// A Last-Modified / If-Modified-Since value (RFC 7231, e.g. "Wed, 21 Oct 2015 07:28:00 GMT")
+pub fn http_date(time: DateTime<Utc>) -> String {
+    time.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+async fn tag(request: Request, next: Next, cache_control: &'static str) -> Response {
+    if !matches!(*request.method(), Method::GET | Method::HEAD) {
+        return next.run(request).await;
+    }
+    let if_none_match = request.headers().get(header::IF_NONE_MATCH).cloned();
+    let if_modified_since = request.headers().get(header::IF_MODIFIED_SINCE).cloned();
+
+    let response = next.run(request).await;
+    // Downloads (CSV and JSON exports) are streamed and not worth buffering
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|value| value.as_bytes().starts_with(b"application/json"));
+    let is_download = response.headers().contains_key(header::CONTENT_DISPOSITION);
+    if response.status() != StatusCode::OK || !is_json || is_download {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            tracing::error!("Failed to buffer response for ETag: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    // Weak, since compression changes the bytes on the wire but not the content
+    let digest = Sha256::digest(&bytes);
+    let tag = format!("W/\"{}\"", hex::encode(&digest[..16]));
+    let tag_value = HeaderValue::from_str(&tag).expect("hex is a valid header value");
+    parts.headers.insert(header::ETAG, tag_value);
+    set_cache_control(&mut parts.headers, cache_control);
+
+    // If-None-Match wins when a client sends both (RFC 7232 section 6)
+    let not_modified = match if_none_match {
+        Some(value) => matches(&value, &tag),
+        None => if_modified_since.is_some_and(|since| unmodified_since(&parts.headers, &since)),
+    };
+    if not_modified {
+        let mut response = StatusCode::NOT_MODIFIED.into_response();
+        for name in [header::ETAG, header::LAST_MODIFIED, header::CACHE_CONTROL] {
+            if let Some(value) = parts.headers.get(&name) {
+                response.headers_mut().insert(name, value.clone());
+            }
+        }
+        return response;
+    }
+
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+/// Whether the response's Last-Modified is no later than If-Modified-Since
+fn unmodified_since(headers: &HeaderMap, if_modified_since: &HeaderValue) -> bool {
+    let parse = |value: &HeaderValue| {
+        value
+            .to_str()
+            .ok()
+            .and_then(|v| DateTime::parse_from_rfc2822(v).ok())
+    };
+    match (headers.get(header::LAST_MODIFIED).and_then(parse), parse(if_modified_since)) {
+        (Some(last_modified), Some(since)) => last_modified.timestamp() <= since.timestamp(),
+        _ => false,
+    }
+}
+
+/// If-None-Match is `*` or a comma separated list, compared weakly (ignoring W/)
+fn matches(if_none_match: &HeaderValue, tag: &str) -> bool {
+    let Ok(value) = if_none_match.to_str() else {
+        return false;
+    };
+    let opaque = |t: &str| t.trim().trim_start_matches("W/").to_string();
+
+    value
+        .split(',')
+        .any(|candidate| candidate.trim() == "*" || opaque(candidate) == opaque(tag))
+}
+
+// Unless the handler chose its own
+fn set_cache_control(headers: &mut HeaderMap, cache_control: &'static str) {
+    headers
+        .entry(header::CACHE_CONTROL)
+        .or_insert(HeaderValue::from_static(cache_control));
+}
@@ -0,0 +1,548 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tower_sessions::Session;
+use uuid::Uuid;
+
+use crate::db::DbRouter;
+use crate::error::ApiError;
+use crate::extractors::{AuthUser, VerifiedUser};
+use crate::i18n;
+use crate::notifications::NotificationKind;
+use crate::ws::Event as RealtimeEvent;
+use crate::state::AppState;
+use crate::validation::{
+    normalize_line, normalize_text, Validate, ValidatedJson, ValidationErrors,
+};
+
+const MAX_CAPACITY: i64 = 100_000;
+// Upcoming events listed at once
+const LIST_LIMIT: i64 = 50;
+/// Attendees are reminded this long before an event starts
+pub const REMINDER_HOURS: i32 = 24;
+
+#[derive(Serialize)]
+pub struct Event {
+    pub id: Uuid,
+    pub title: String,
+    pub description: Option<String>,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    pub location: Option<String>,
+    pub online_url: Option<String>,
+    /// None for no limit
+    pub capacity: Option<i32>,
+    /// People who RSVPed
+    pub going: i64,
+    /// Whether the logged in viewer RSVPed
+    pub viewer_going: bool,
+    pub host_id: Uuid,
+    pub host_username: String,
+    pub host_name: String,
+    pub host_avatar: Option<String>,
+    pub project_id: Option<Uuid>,
+    pub project_title: Option<String>,
+    pub project_slug: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct Attendee {
+    pub username: String,
+    pub display_name: String,
+    pub avatar_url: Option<String>,
+    pub rsvped_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct EventQuery {
+    /// Only this project's events
+    pub project_id: Option<Uuid>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateEventRequest {
+    pub title: String,
+    pub description: Option<String>,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    pub location: Option<String>,
+    pub online_url: Option<String>,
+    pub capacity: Option<i32>,
+    /// Host it as one of the user's projects
+    pub project_id: Option<Uuid>,
+}
+
+/// Only the fields given change. Empty strings clear the optional text fields, and
+/// a capacity of 0 removes the limit.
+#[derive(Deserialize)]
+pub struct UpdateEventRequest {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub location: Option<String>,
+    pub online_url: Option<String>,
+    pub capacity: Option<i32>,
+}
+
+fn validate_details(
+    errors: &mut ValidationErrors,
+    description: Option<&str>,
+    location: Option<&str>,
+    online_url: Option<&str>,
+) {
+    if let Some(description) = description {
+        errors.length("description", description, 0, 5000);
+    }
+    if let Some(location) = location {
+        errors.length("location", location, 0, 200);
+    }
+    if let Some(online_url) = online_url.filter(|url| !url.is_empty()) {
+        errors.url("online_url", online_url);
+    }
+}
+
+impl Validate for CreateEventRequest {
+    fn normalize(&mut self) {
+        normalize_line(&mut self.title);
+        self.description.iter_mut().for_each(normalize_text);
+        self.location.iter_mut().for_each(normalize_line);
+    }
+
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.length("title", &self.title, 1, 100);
+        validate_details(
+            &mut errors,
+            self.description.as_deref(),
+            self.location.as_deref(),
+            self.online_url.as_deref(),
+        );
+        if self.starts_at <= Utc::now() {
+            errors.add("starts_at", "Must be in the future");
+        }
+        if self.ends_at <= self.starts_at {
+            errors.add("ends_at", "Must be after starts_at");
+        }
+        if let Some(capacity) = self.capacity {
+            errors.range("capacity", capacity.into(), 1, MAX_CAPACITY);
+        }
+        errors.into_result()
+    }
+}
+
+impl Validate for UpdateEventRequest {
+    fn normalize(&mut self) {
+        self.title.iter_mut().for_each(normalize_line);
+        self.description.iter_mut().for_each(normalize_text);
+        self.location.iter_mut().for_each(normalize_line);
+    }
+
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        if let Some(title) = &self.title {
+            errors.length("title", title, 1, 100);
+        }
+        validate_details(
+            &mut errors,
+            self.description.as_deref(),
+            self.location.as_deref(),
+            self.online_url.as_deref(),
+        );
+        if let Some(capacity) = self.capacity {
+            errors.range("capacity", capacity.into(), 0, MAX_CAPACITY);
+        }
+        errors.into_result()
+    }
+}
+
+/// Upcoming and ongoing events, soonest first
+pub async fn list(
+    State(db): State<DbRouter>,
+    viewer: Option<AuthUser>,
+    Query(query): Query<EventQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let events = sqlx::query_as!(
+        Event,
+        r#"
+        SELECT e.id, e.title, e.description, e.starts_at, e.ends_at, e.location, e.online_url,
+            e.capacity,
+            (SELECT COUNT(*) FROM event_rsvps r WHERE r.event_id = e.id) AS "going!",
+            EXISTS (SELECT 1 FROM event_rsvps r WHERE r.event_id = e.id AND r.user_id = $1)
+                AS "viewer_going!",
+            e.host_id, u.username AS host_username, u.display_name AS host_name,
+            u.avatar_url AS host_avatar, e.project_id, p.title AS "project_title?",
+            p.slug AS "project_slug?", e.created_at
+        FROM events e
+        JOIN users u ON u.id = e.host_id
+        LEFT JOIN projects p ON p.id = e.project_id
+        WHERE e.ends_at > NOW() AND u.banned_at IS NULL
+          AND ($2::uuid IS NULL OR e.project_id = $2)
+        ORDER BY e.starts_at
+        LIMIT $3
+        "#,
+        viewer.map(|user| user.id),
+        query.project_id,
+        LIST_LIMIT
+    )
+    .fetch_all(db.read())
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(events))
+}
+
+pub async fn get(
+    State(db): State<DbRouter>,
+    viewer: Option<AuthUser>,
+    Path(event_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let event = sqlx::query_as!(
+        Event,
+        r#"
+        SELECT e.id, e.title, e.description, e.starts_at, e.ends_at, e.location, e.online_url,
+            e.capacity,
+            (SELECT COUNT(*) FROM event_rsvps r WHERE r.event_id = e.id) AS "going!",
+            EXISTS (SELECT 1 FROM event_rsvps r WHERE r.event_id = e.id AND r.user_id = $2)
+                AS "viewer_going!",
+            e.host_id, u.username AS host_username, u.display_name AS host_name,
+            u.avatar_url AS host_avatar, e.project_id, p.title AS "project_title?",
+            p.slug AS "project_slug?", e.created_at
+        FROM events e
+        JOIN users u ON u.id = e.host_id
+        LEFT JOIN projects p ON p.id = e.project_id
+        WHERE e.id = $1 AND u.banned_at IS NULL
+        "#,
+        event_id,
+        viewer.map(|user| user.id)
+    )
+    .fetch_optional(db.read())
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or(ApiError::new(StatusCode::NOT_FOUND, &i18n::EVENT_NOT_FOUND))?;
+
+    Ok(Json(event))
+}
+
+/// Host an event, optionally for one of the user's projects. It goes in the feed.
+pub async fn create(
+    State(state): State<AppState>,
+    VerifiedUser(user): VerifiedUser,
+    ValidatedJson(payload): ValidatedJson<CreateEventRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    if let Some(project_id) = payload.project_id {
+        let owned = sqlx::query_scalar!(
+            r#"SELECT EXISTS(SELECT 1 FROM projects WHERE id = $1 AND owner_id = $2) AS "owned!""#,
+            project_id,
+            user.id
+        )
+        .fetch_one(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        if !owned {
+            return Err(ApiError::new(
+                StatusCode::NOT_FOUND,
+                &i18n::PROJECT_NOT_FOUND,
+            ));
+        }
+    }
+
+    // Create the event, and its place in the feed
+    let event = sqlx::query!(
+        r#"
+        WITH event AS (
+            INSERT INTO events (
+                host_id, project_id, title, description, starts_at, ends_at, location,
+                online_url, capacity
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, NULLIF($7, ''), NULLIF($8, ''), $9)
+            RETURNING id, host_id, created_at
+        ), item AS (
+            INSERT INTO feed_items (event_id, author_id, created_at)
+            SELECT id, host_id, created_at FROM event
+        )
+        SELECT id AS "id!", created_at AS "created_at!" FROM event
+        "#,
+        user.id,
+        payload.project_id,
+        payload.title,
+        payload.description,
+        payload.starts_at,
+        payload.ends_at,
+        payload.location,
+        payload.online_url,
+        payload.capacity
+    )
+    .fetch_one(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    crate::feed::invalidate(&*state.cache).await;
+    state.realtime.send_to_all(RealtimeEvent::FeedItem {
+        item_type: "event",
+        id: event.id,
+        author_id: user.id,
+    });
+
+    Ok((
+        StatusCode::CREATED,
+        Json(serde_json::json!({
+            "id": event.id,
+            "created_at": event.created_at,
+        })),
+    ))
+}
+
+/// Change an event (host only). Moving its start time means attendees are reminded again.
+pub async fn update(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(event_id): Path<Uuid>,
+    ValidatedJson(payload): ValidatedJson<UpdateEventRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let updated = sqlx::query!(
+        r#"
+        UPDATE events
+        SET title = COALESCE($3, title),
+            description = CASE WHEN $4::text IS NULL THEN description ELSE NULLIF($4, '') END,
+            starts_at = COALESCE($5, starts_at),
+            ends_at = COALESCE($6, ends_at),
+            location = CASE WHEN $7::text IS NULL THEN location ELSE NULLIF($7, '') END,
+            online_url = CASE WHEN $8::text IS NULL THEN online_url ELSE NULLIF($8, '') END,
+            capacity = CASE WHEN $9::int IS NULL THEN capacity ELSE NULLIF($9, 0) END,
+            reminded_at = CASE WHEN COALESCE($5, starts_at) = starts_at THEN reminded_at END,
+            updated_at = NOW()
+        WHERE id = $1 AND host_id = $2
+        "#,
+        event_id,
+        user.id,
+        payload.title,
+        payload.description,
+        payload.starts_at,
+        payload.ends_at,
+        payload.location,
+        payload.online_url,
+        payload.capacity
+    )
+    .execute(&state.pool)
+    .await
+    .map_err(
+        |e| match e.as_database_error().and_then(|e| e.code()).as_deref() {
+            // The events table's ends_at > starts_at check
+            Some("23514") => ApiError::new(StatusCode::BAD_REQUEST, &i18n::EVENT_ENDS_BEFORE_START),
+            _ => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into(),
+        },
+    )?;
+
+    if updated.rows_affected() == 0 {
+        return Err(ApiError::new(StatusCode::NOT_FOUND, &i18n::EVENT_NOT_FOUND));
+    }
+
+    crate::feed::invalidate(&*state.cache).await;
+    Ok(StatusCode::OK)
+}
+
+/// Cancel an event (host, moderator or admin)
+pub async fn delete(
+    State(state): State<AppState>,
+    AuthUser { id: user_id, role }: AuthUser,
+    session: Session,
+    Path(event_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let host_id = sqlx::query_scalar!("SELECT host_id FROM events WHERE id = $1", event_id)
+        .fetch_optional(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or(ApiError::new(StatusCode::NOT_FOUND, &i18n::EVENT_NOT_FOUND))?;
+
+    if host_id != user_id && !crate::admin::can_moderate(&role) {
+        return Err(ApiError::new(StatusCode::FORBIDDEN, &i18n::NOT_YOUR_EVENT));
+    }
+
+    sqlx::query!("DELETE FROM events WHERE id = $1", event_id)
+        .execute(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    crate::feed::invalidate(&*state.cache).await;
+
+    // Removing someone else's event is a moderation action
+    if host_id != user_id {
+        let details = format!("event {}", event_id);
+        crate::audit::record(
+            &state.pool,
+            &session,
+            user_id,
+            "moderation.event_deleted",
+            Some(host_id),
+            Some(&details),
+        )
+        .await?;
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Say you're going. Doing it again is fine; a full or finished event is refused.
+pub async fn rsvp(
+    State(pool): State<PgPool>,
+    VerifiedUser(user): VerifiedUser,
+    Path(event_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    // Locked, so two people can't take the last place at once
+    let event = sqlx::query!(
+        "SELECT capacity, ends_at FROM events WHERE id = $1 FOR UPDATE",
+        event_id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or(ApiError::new(StatusCode::NOT_FOUND, &i18n::EVENT_NOT_FOUND))?;
+
+    if event.ends_at <= Utc::now() {
+        return Err(ApiError::new(StatusCode::BAD_REQUEST, &i18n::EVENT_OVER));
+    }
+
+    let counts = sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "going!", COALESCE(bool_or(user_id = $2), FALSE) AS "already!"
+        FROM event_rsvps WHERE event_id = $1
+        "#,
+        event_id,
+        user.id
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    if counts.already {
+        return Ok(Json(serde_json::json!({ "going": counts.going })));
+    }
+    if event
+        .capacity
+        .is_some_and(|capacity| counts.going >= i64::from(capacity))
+    {
+        return Err(ApiError::new(StatusCode::CONFLICT, &i18n::EVENT_FULL));
+    }
+
+    sqlx::query!(
+        "INSERT INTO event_rsvps (event_id, user_id) VALUES ($1, $2)",
+        event_id,
+        user.id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(serde_json::json!({ "going": counts.going + 1 })))
+}
+
+/// Not going after all; fine if the user hadn't RSVPed
+pub async fn cancel_rsvp(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+    Path(event_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    sqlx::query!(
+        "DELETE FROM event_rsvps WHERE event_id = $1 AND user_id = $2",
+        event_id,
+        user.id
+    )
+    .execute(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Who's going, in the order they RSVPed
+pub async fn attendees(
+    State(db): State<DbRouter>,
+    Path(event_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let attendees = sqlx::query_as!(
+        Attendee,
+        r#"
+        SELECT u.username, u.display_name, u.avatar_url, r.created_at AS rsvped_at
+        FROM event_rsvps r
+        JOIN users u ON u.id = r.user_id
+        WHERE r.event_id = $1 AND u.banned_at IS NULL
+        ORDER BY r.created_at
+        "#,
+        event_id
+    )
+    .fetch_all(db.read())
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(attendees))
+}
+
+/// Notify everyone going to an event starting within the next day. Each event is claimed
+/// first, so attendees are reminded once even with several instances. Returns how many
+/// reminders were sent.
+pub async fn send_reminders(state: &AppState, now: DateTime<Utc>) -> Result<u64, String> {
+    let events = sqlx::query!(
+        r#"
+        UPDATE events SET reminded_at = $1
+        WHERE reminded_at IS NULL
+          AND starts_at > $1
+          AND starts_at <= $1 + make_interval(hours => $2)
+        RETURNING id, host_id, title, starts_at
+        "#,
+        now,
+        REMINDER_HOURS
+    )
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let mut sent = 0;
+    for event in events {
+        let attendees = sqlx::query_scalar!(
+            "SELECT user_id FROM event_rsvps WHERE event_id = $1",
+            event.id
+        )
+        .fetch_all(&state.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        for user_id in attendees {
+            let notified = crate::notifications::notify(
+                state,
+                user_id,
+                NotificationKind::EventReminder,
+                event.host_id,
+                serde_json::json!({
+                    "event_id": event.id,
+                    "title": event.title,
+                    "starts_at": event.starts_at,
+                }),
+            )
+            .await;
+            match notified {
+                Ok(()) => sent += 1,
+                Err(e) => {
+                    tracing::error!("Failed to remind {} of event {}: {}", user_id, event.id, e)
+                }
+            }
+        }
+    }
+    Ok(sent)
+}
@@ -0,0 +1,173 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::cache::Cache;
+use crate::error::ApiError;
+use crate::posts::PostWithAuthor;
+use crate::projects::ProjectWithOwner;
+
+// Nothing invalidates it; a minute-old explore page is fine
+const EXPLORE_CACHE_TTL: Duration = Duration::from_secs(60);
+const TRENDING_POSTS: i64 = 10;
+const TRENDING_DAYS: i32 = 7;
+const ACTIVE_PROJECTS: i64 = 10;
+const NEW_MEMBERS: i64 = 12;
+const POPULAR_TAGS: i64 = 20;
+
+#[derive(Serialize, Deserialize)]
+pub struct NewMember {
+    pub id: uuid::Uuid,
+    pub username: String,
+    pub display_name: String,
+    pub avatar_url: Option<String>,
+    pub bio: Option<String>,
+    pub created_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PopularTag {
+    pub tag: String,
+    /// Open projects with it
+    pub projects: i64,
+}
+
+/// Everything on the explore page, each section with its own limit
+#[derive(Serialize, Deserialize)]
+pub struct Explore {
+    pub trending_posts: Vec<PostWithAuthor>,
+    pub active_projects: Vec<ProjectWithOwner>,
+    pub new_members: Vec<NewMember>,
+    pub popular_tags: Vec<PopularTag>,
+}
+
+/// The explore page in one request
+pub async fn get_explore(
+    State(pool): State<PgPool>,
+    State(cache): State<Arc<dyn Cache>>,
+) -> Result<impl IntoResponse, ApiError> {
+    if let Some(explore) = cache.get_json::<Explore>("explore").await {
+        return Ok(Json(explore));
+    }
+
+    let (trending_posts, active_projects, new_members, popular_tags) = tokio::try_join!(
+        trending_posts(&pool),
+        active_projects(&pool),
+        new_members(&pool),
+        popular_tags(&pool),
+    )
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let explore = Explore {
+        trending_posts,
+        active_projects,
+        new_members,
+        popular_tags,
+    };
+    cache.set_json("explore", &explore, EXPLORE_CACHE_TTL).await;
+
+    Ok(Json(explore))
+}
+
+/// The past week's posts by the most followed authors. Posts have no likes or replies to
+/// rank by yet.
+async fn trending_posts(pool: &PgPool) -> Result<Vec<PostWithAuthor>, sqlx::Error> {
+    sqlx::query_as!(
+        PostWithAuthor,
+        r#"
+        SELECT
+            p.id,
+            p.content,
+            p.image_url,
+            p.created_at,
+            p.author_id,
+            u.display_name as author_name,
+            u.username as author_username,
+            u.avatar_url as author_avatar
+        FROM posts p
+        JOIN users u ON p.author_id = u.id
+        WHERE u.banned_at IS NULL AND p.held_at IS NULL
+          AND p.created_at > NOW() - make_interval(days => $1)
+        ORDER BY (SELECT COUNT(*) FROM follows f WHERE f.followee_id = u.id) DESC,
+                 p.created_at DESC
+        LIMIT $2
+        "#,
+        TRENDING_DAYS,
+        TRENDING_POSTS
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Open projects by their latest activity: creation or the last application
+async fn active_projects(pool: &PgPool) -> Result<Vec<ProjectWithOwner>, sqlx::Error> {
+    sqlx::query_as!(
+        ProjectWithOwner,
+        r#"
+        SELECT
+            p.id,
+            p.slug,
+            p.title,
+            p.description,
+            p.image_url,
+            p.status,
+            p.looking_for as "looking_for!: Vec<String>",
+            p.tags as "tags!: Vec<String>",
+            p.created_at,
+            p.owner_id,
+            u.display_name as owner_name,
+            u.username as owner_username,
+            u.avatar_url as owner_avatar
+        FROM projects p
+        JOIN users u ON p.owner_id = u.id
+        WHERE p.status = 'open' AND u.banned_at IS NULL AND p.held_at IS NULL
+        ORDER BY GREATEST(
+            p.created_at,
+            (SELECT MAX(a.created_at) FROM applications a
+             WHERE a.project_id = p.id AND a.held_at IS NULL)
+        ) DESC
+        LIMIT $1
+        "#,
+        ACTIVE_PROJECTS
+    )
+    .fetch_all(pool)
+    .await
+}
+
+async fn new_members(pool: &PgPool) -> Result<Vec<NewMember>, sqlx::Error> {
+    sqlx::query_as!(
+        NewMember,
+        r#"
+        SELECT id, username, display_name, avatar_url, bio, created_at
+        FROM users
+        WHERE banned_at IS NULL
+        ORDER BY created_at DESC NULLS LAST
+        LIMIT $1
+        "#,
+        NEW_MEMBERS
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Tags on open projects, most used first. Tags differing only in case count as one.
+async fn popular_tags(pool: &PgPool) -> Result<Vec<PopularTag>, sqlx::Error> {
+    sqlx::query_as!(
+        PopularTag,
+        r#"
+        SELECT MIN(t.tag) as "tag!", COUNT(DISTINCT p.id) as "projects!"
+        FROM projects p
+        JOIN users u ON p.owner_id = u.id
+        CROSS JOIN unnest(p.tags) AS t(tag)
+        WHERE p.status = 'open' AND u.banned_at IS NULL AND p.held_at IS NULL
+        GROUP BY lower(t.tag)
+        ORDER BY 2 DESC, 1
+        LIMIT $1
+        "#,
+        POPULAR_TAGS
+    )
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,167 @@
+use axum::{
+    body::{Body, Bytes},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use futures_util::stream;
+use serde::Serialize;
+use tokio::sync::mpsc;
+
+// Chunks read ahead of a slow client before reading more rows waits
+const BUFFERED_CHUNKS: usize = 16;
+// Rows are sent in chunks of about this many bytes, rather than one write per row
+const CHUNK_BYTES: usize = 16 * 1024;
+
+/// The body of a streamed download: rows are batched into chunks, and the bounded
+/// channel makes the row reader wait whenever the client falls behind
+struct Download {
+    tx: mpsc::Sender<Result<Bytes, std::io::Error>>,
+    chunk: Vec<u8>,
+}
+
+impl Download {
+    fn new(filename: &str, content_type: &str) -> (Self, Response) {
+        let (tx, rx) = mpsc::channel(BUFFERED_CHUNKS);
+        let body = stream::unfold(rx, |mut rx| async move {
+            rx.recv().await.map(|chunk| (chunk, rx))
+        });
+        let response = (
+            [
+                (header::CONTENT_TYPE, content_type.to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{}\"", filename),
+                ),
+            ],
+            Body::from_stream(body),
+        )
+            .into_response();
+
+        let download = Self {
+            tx,
+            chunk: Vec::with_capacity(CHUNK_BYTES),
+        };
+        (download, response)
+    }
+
+    /// Returns false once the client has gone
+    async fn write(&mut self, data: &[u8]) -> bool {
+        self.chunk.extend_from_slice(data);
+        if self.chunk.len() < CHUNK_BYTES {
+            return true;
+        }
+        self.flush().await
+    }
+
+    async fn flush(&mut self) -> bool {
+        if self.chunk.is_empty() {
+            return true;
+        }
+        let chunk = std::mem::replace(&mut self.chunk, Vec::with_capacity(CHUNK_BYTES));
+        self.tx.send(Ok(Bytes::from(chunk))).await.is_ok()
+    }
+
+    async fn fail(self, error: impl std::fmt::Display) {
+        tracing::error!("Export failed: {}", error);
+        let _ = self
+            .tx
+            .send(Err(std::io::Error::other(error.to_string())))
+            .await;
+    }
+}
+
+/// Sends rows to a streamed CSV download, from a background task
+pub struct CsvWriter(Download);
+
+impl CsvWriter {
+    /// Returns false once the client has gone, so the caller can stop reading rows
+    pub async fn row<S: AsRef<str>>(&mut self, fields: &[S]) -> bool {
+        self.0.write(line(fields).as_bytes()).await
+    }
+
+    /// Send the last rows. The download is cut short without this.
+    pub async fn finish(mut self) {
+        self.0.flush().await;
+    }
+
+    /// Abort the download, so the client sees a failed rather than a short file
+    pub async fn fail(self, error: impl std::fmt::Display) {
+        self.0.fail(error).await
+    }
+}
+
+/// A CSV download of `filename` starting with the `header` row. Rows are written
+/// to the returned writer and streamed to the client as they come.
+pub fn csv(filename: &str, header: &[&str]) -> (CsvWriter, Response) {
+    let (mut download, response) = Download::new(filename, "text/csv; charset=utf-8");
+    download.chunk.extend_from_slice(line(header).as_bytes());
+    (CsvWriter(download), response)
+}
+
+/// Sends items to a streamed download of one JSON array, from a background task
+pub struct JsonWriter {
+    download: Download,
+    first: bool,
+}
+
+impl JsonWriter {
+    /// Returns false once the client has gone, so the caller can stop reading rows
+    pub async fn item<T: Serialize>(&mut self, item: &T) -> bool {
+        let mut data = if self.first { Vec::new() } else { vec![b','] };
+        self.first = false;
+        if let Err(e) = serde_json::to_writer(&mut data, item) {
+            tracing::error!("Failed to serialize exported item: {}", e);
+            return false;
+        }
+        data.push(b'\n');
+        self.download.write(&data).await
+    }
+
+    /// Close the array and send the last items. The download is cut short without this.
+    pub async fn finish(mut self) {
+        if self.download.write(b"]\n").await {
+            self.download.flush().await;
+        }
+    }
+
+    /// Abort the download, so the client sees a failed rather than a truncated array
+    pub async fn fail(self, error: impl std::fmt::Display) {
+        self.download.fail(error).await
+    }
+}
+
+/// A JSON download of `filename`: an array with one item per line
+pub fn json(filename: &str) -> (JsonWriter, Response) {
+    let (mut download, response) = Download::new(filename, "application/json");
+    download.chunk.extend_from_slice(b"[\n");
+    let writer = JsonWriter {
+        download,
+        first: true,
+    };
+    (writer, response)
+}
+
+fn line<S: AsRef<str>>(fields: &[S]) -> String {
+    let mut line = fields
+        .iter()
+        .map(|field| escape(field.as_ref()))
+        .collect::<Vec<_>>()
+        .join(",");
+    line.push_str("\r\n");
+    line
+}
+
+/// Quote fields as RFC 4180 says, and stop spreadsheets from running user supplied text
+/// (a display name like `=HYPERLINK(...)`) as a formula
+fn escape(field: &str) -> String {
+    let field = if field.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{}", field)
+    } else {
+        field.to_string()
+    };
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field
+    }
+}
@@ -0,0 +1,203 @@
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts},
+    http::{request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tower_sessions::Session;
+use uuid::Uuid;
+
+use crate::admin::can_moderate;
+use crate::error::{ApiError, Message};
+use crate::i18n::{self, Locale};
+use crate::state::AppState;
+
+/// Session key for the cached role. Stored with the user id so a session
+/// reused for a different login never picks up the previous user's role.
+const ROLE_KEY: &str = "role";
+
+#[derive(Serialize, Deserialize)]
+struct CachedRole {
+    user_id: Uuid,
+    role: String,
+}
+
+/// The logged in user, resolved from the session.
+/// Rejects with 401 if nobody is logged in or the user no longer exists.
+/// The role is cached in the session after the first lookup; role changes
+/// revoke the user's sessions so the cache can't go stale.
+#[derive(Clone, Debug)]
+pub struct AuthUser {
+    pub id: Uuid,
+    pub role: String,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AuthUser
+where
+    S: Send + Sync,
+    PgPool: FromRef<S>,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        // Already resolved by another extractor on this request
+        if let Some(user) = parts.extensions.get::<AuthUser>() {
+            return Ok(user.clone());
+        }
+
+        let session = Session::from_request_parts(parts, state)
+            .await
+            .map_err(|(status, msg)| (status, msg.to_string()))?;
+
+        let user_id: Uuid = match session.get("user_id").await {
+            Ok(Some(id)) => id,
+            Ok(None) => {
+                return Err(ApiError::new(
+                    StatusCode::UNAUTHORIZED,
+                    &i18n::NOT_LOGGED_IN,
+                ))
+            }
+            Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into()),
+        };
+
+        let cached = session
+            .get::<CachedRole>(ROLE_KEY)
+            .await
+            .ok()
+            .flatten()
+            .filter(|cached| cached.user_id == user_id);
+
+        let role = match cached {
+            Some(cached) => cached.role,
+            None => {
+                let pool = PgPool::from_ref(state);
+                let role = sqlx::query_scalar!("SELECT role FROM users WHERE id = $1", user_id)
+                    .fetch_optional(&pool)
+                    .await
+                    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+                    // Session outlived the account
+                    .ok_or(ApiError::new(
+                        StatusCode::UNAUTHORIZED,
+                        &i18n::NOT_LOGGED_IN,
+                    ))?;
+
+                let cached = CachedRole {
+                    user_id,
+                    role: role.clone(),
+                };
+                if let Err(e) = session.insert(ROLE_KEY, cached).await {
+                    tracing::warn!("Failed to cache role in session: {}", e);
+                }
+                role
+            }
+        };
+
+        let user = AuthUser { id: user_id, role };
+        parts.extensions.insert(user.clone());
+        Ok(user)
+    }
+}
+
+/// A logged in admin. Rejects with 403 for everyone else.
+#[derive(Clone, Debug)]
+pub struct AdminUser(pub AuthUser);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AdminUser
+where
+    S: Send + Sync,
+    PgPool: FromRef<S>,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let user = AuthUser::from_request_parts(parts, state).await?;
+        if user.role != "admin" {
+            return Err(ApiError::new(StatusCode::FORBIDDEN, &i18n::ADMINS_ONLY));
+        }
+        Ok(AdminUser(user))
+    }
+}
+
+/// A logged in moderator or admin. Rejects with 403 for everyone else.
+#[derive(Clone, Debug)]
+pub struct ModeratorUser(pub AuthUser);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ModeratorUser
+where
+    S: Send + Sync,
+    PgPool: FromRef<S>,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let user = AuthUser::from_request_parts(parts, state).await?;
+        if !can_moderate(&user.role) {
+            return Err(ApiError::new(StatusCode::FORBIDDEN, &i18n::MODERATORS_ONLY));
+        }
+        Ok(ModeratorUser(user))
+    }
+}
+
+/// Whether the user has verified their email. Accounts without a password (Google or GitHub
+/// only) count as verified.
+pub(crate) async fn email_verified(pool: &PgPool, user_id: Uuid) -> Result<bool, sqlx::Error> {
+    Ok(sqlx::query_scalar!(
+        r#"SELECT COALESCE(verified, FALSE) as "verified!" FROM local_auths WHERE user_id = $1"#,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await?
+    .unwrap_or(true))
+}
+
+/// A logged in user allowed to publish: their email is verified, or the site doesn't ask for
+/// that (the require_verified_email setting). Accounts from Google or GitHub count as verified.
+/// Rejects with 403 and `"code": "verification_required"` otherwise, so the frontend can offer
+/// to resend the verification email.
+#[derive(Clone, Debug)]
+pub struct VerifiedUser(pub AuthUser);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for VerifiedUser
+where
+    S: Send + Sync,
+    PgPool: FromRef<S>,
+    AppState: FromRef<S>,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let user = AuthUser::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let state = AppState::from_ref(state);
+        if !crate::settings::get(&state).await.require_verified_email {
+            return Ok(VerifiedUser(user));
+        }
+
+        let verified = email_verified(&state.pool, user.id)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response())?;
+
+        if !verified {
+            let message = Message::new(&i18n::VERIFICATION_REQUIRED);
+            let mut response = (
+                StatusCode::FORBIDDEN,
+                Json(serde_json::json!({
+                    "message": message.text(Locale::En),
+                    "code": message.code(),
+                })),
+            )
+                .into_response();
+            response.extensions_mut().insert(message);
+            return Err(response);
+        }
+        Ok(VerifiedUser(user))
+    }
+}
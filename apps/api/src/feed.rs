@@ -4,170 +4,192 @@ use axum::{
     response::IntoResponse,
     Json,
 };
+use chrono::{DateTime, SecondsFormat, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::cache::Cache;
+use crate::db::DbRouter;
+use crate::error::ApiError;
+use crate::i18n;
+
+// New and deleted items invalidate the cache; this bounds how stale author names can get
+const FEED_CACHE_TTL: Duration = Duration::from_secs(30);
+const FEED_TYPES: [&str; 4] = ["all", "posts", "projects", "events"];
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 100;
 
 #[derive(Deserialize)]
 pub struct FeedQuery {
     #[serde(rename = "type")]
-    pub feed_type: Option<String>, // "posts", "projects", or None for all
+    pub feed_type: Option<String>, // "posts", "projects", "events", or None for all
+    pub limit: Option<i64>,
+    /// `next_cursor` from the previous page
+    pub before: Option<String>,
+}
+
+impl FeedQuery {
+    fn limit(&self) -> i64 {
+        self.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FeedPage {
+    pub items: Vec<FeedItem>,
+    /// Pass as `before` for the next page; None on the last one
+    pub next_cursor: Option<String>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 pub struct FeedItem {
     pub id: uuid::Uuid,
     #[serde(rename = "type")]
-    pub item_type: String, // "post" or "project"
+    pub item_type: String, // "post", "project" or "event"
     pub content: Option<String>,      // post content
-    pub title: Option<String>,        // project title
-    pub description: Option<String>,  // project description
+    pub title: Option<String>,        // project or event title
+    pub description: Option<String>,  // project or event description
     pub image_url: Option<String>,
     pub status: Option<String>,       // project status
     pub slug: Option<String>,         // project slug (null for posts)
     pub looking_for: Vec<String>,     // project looking_for (empty for posts)
+    pub starts_at: Option<chrono::DateTime<chrono::Utc>>, // event start
+    pub ends_at: Option<chrono::DateTime<chrono::Utc>>,   // event end
+    pub location: Option<String>,     // event location
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub author_id: uuid::Uuid,
     pub author_name: String,
     pub author_username: String,
     pub author_avatar: Option<String>,
+    /// Its feed_items row, which the cursor points at
+    #[serde(skip)]
+    pub feed_id: uuid::Uuid,
+}
+
+/// A cursor is the last item's time and feed_items id, so items posted at the same moment
+/// aren't skipped, and the next page starts from the (created_at, id) index
+fn encode_cursor(item: &FeedItem) -> String {
+    format!(
+        "{}_{}",
+        item.created_at.to_rfc3339_opts(SecondsFormat::Micros, true),
+        item.feed_id
+    )
+}
+
+fn decode_cursor(cursor: &str) -> Option<(DateTime<Utc>, uuid::Uuid)> {
+    let (created_at, id) = cursor.split_once('_')?;
+    let created_at = DateTime::parse_from_rfc3339(created_at).ok()?;
+    Some((created_at.with_timezone(&Utc), id.parse().ok()?))
 }
 
-/// Get unified feed of posts and projects
+/// Get unified feed of posts, projects and events, a page at a time
 pub async fn get_feed(
-    State(pool): State<PgPool>,
+    State(db): State<DbRouter>,
+    State(cache): State<Arc<dyn Cache>>,
     Query(query): Query<FeedQuery>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    let feed = match query.feed_type.as_deref() {
-        Some("posts") => get_posts_only(&pool).await?,
-        Some("projects") => get_projects_only(&pool).await?,
-        _ => get_all_items(&pool).await?,
+) -> Result<impl IntoResponse, ApiError> {
+    let feed_type = match query.feed_type.as_deref() {
+        Some("posts") => "posts",
+        Some("projects") => "projects",
+        Some("events") => "events",
+        _ => "all",
     };
+    let limit = query.limit();
+    let before = query
+        .before
+        .as_deref()
+        .map(|cursor| {
+            decode_cursor(cursor).ok_or(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                &i18n::INVALID_CURSOR,
+            ))
+        })
+        .transpose()?;
 
-    Ok(Json(feed))
-}
+    // Only the default first page is cached; that's what nearly every request asks for
+    let cacheable = before.is_none() && limit == DEFAULT_PAGE_SIZE;
+    let cache_key = format!("feed:{}", feed_type);
+    if cacheable {
+        if let Some(page) = cache.get_json::<FeedPage>(&cache_key).await {
+            return Ok(Json(page));
+        }
+    }
 
-async fn get_posts_only(pool: &PgPool) -> Result<Vec<FeedItem>, (StatusCode, String)> {
-    let items = sqlx::query_as!(
-        FeedItem,
-        r#"
-        SELECT
-            p.id,
-            'post' as "item_type!",
-            p.content,
-            NULL::text as title,
-            NULL::text as description,
-            p.image_url,
-            NULL::text as status,
-            NULL::text as slug,
-            '{}'::text[] as "looking_for!: Vec<String>",
-            p.created_at,
-            p.author_id,
-            u.display_name as author_name,
-            u.username as author_username,
-            u.avatar_url as author_avatar
-        FROM posts p
-        JOIN users u ON p.author_id = u.id
-        ORDER BY p.created_at DESC
-        "#
-    )
-    .fetch_all(pool)
-    .await
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let mut items = get_items(db.read(), feed_type, before, limit + 1).await?;
+    let has_more = items.len() as i64 > limit;
+    items.truncate(limit as usize);
+    let next_cursor = has_more.then(|| items.last().map(encode_cursor)).flatten();
+    let page = FeedPage { items, next_cursor };
+    if cacheable {
+        cache.set_json(&cache_key, &page, FEED_CACHE_TTL).await;
+    }
 
-    Ok(items)
+    Ok(Json(page))
 }
 
-async fn get_projects_only(pool: &PgPool) -> Result<Vec<FeedItem>, (StatusCode, String)> {
-    let items = sqlx::query_as!(
-        FeedItem,
-        r#"
-        SELECT
-            p.id,
-            'project' as "item_type!",
-            NULL::text as content,
-            p.title,
-            p.description,
-            p.image_url,
-            p.status,
-            p.slug,
-            p.looking_for as "looking_for!: Vec<String>",
-            p.created_at,
-            p.owner_id as author_id,
-            u.display_name as author_name,
-            u.username as author_username,
-            u.avatar_url as author_avatar
-        FROM projects p
-        JOIN users u ON p.owner_id = u.id
-        ORDER BY p.created_at DESC
-        "#
-    )
-    .fetch_all(pool)
-    .await
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-
-    Ok(items)
+/// Drop the cached feeds, after a post, project or event is added, changed or removed
+pub async fn invalidate(cache: &dyn Cache) {
+    for feed_type in FEED_TYPES {
+        cache.delete(&format!("feed:{}", feed_type)).await;
+    }
 }
 
-async fn get_all_items(pool: &PgPool) -> Result<Vec<FeedItem>, (StatusCode, String)> {
+/// Newest first, from feed_items rather than a union of posts, projects and events
+async fn get_items(
+    pool: &PgPool,
+    feed_type: &str,
+    before: Option<(DateTime<Utc>, uuid::Uuid)>,
+    limit: i64,
+) -> Result<Vec<FeedItem>, ApiError> {
+    let (before_time, before_id) = before.unzip();
     let items = sqlx::query_as!(
         FeedItem,
         r#"
         SELECT
-            id as "id!",
-            item_type as "item_type!",
-            content,
-            title,
-            description,
-            image_url,
-            status,
-            slug,
-            looking_for as "looking_for!: Vec<String>",
-            created_at as "created_at!",
-            author_id as "author_id!",
-            author_name as "author_name!",
-            author_username as "author_username!",
-            author_avatar
-        FROM (
-            SELECT
-                p.id,
-                'post'::text as item_type,
-                p.content,
-                NULL::text as title,
-                NULL::text as description,
-                p.image_url,
-                NULL::text as status,
-                NULL::text as slug,
-                '{}'::text[] as looking_for,
-                p.created_at,
-                p.author_id,
-                u.display_name as author_name,
-                u.username as author_username,
-                u.avatar_url as author_avatar
-            FROM posts p
-            JOIN users u ON p.author_id = u.id
-
-            UNION ALL
-
-            SELECT
-                p.id,
-                'project'::text as item_type,
-                NULL::text as content,
-                p.title,
-                p.description,
-                p.image_url,
-                p.status,
-                p.slug,
-                p.looking_for,
-                p.created_at,
-                p.owner_id as author_id,
-                u.display_name as author_name,
-                u.username as author_username,
-                u.avatar_url as author_avatar
-            FROM projects p
-            JOIN users u ON p.owner_id = u.id
-        ) combined
-        ORDER BY created_at DESC
-        "#
+            COALESCE(po.id, pr.id, ev.id) as "id!",
+            CASE
+                WHEN f.post_id IS NOT NULL THEN 'post'
+                WHEN f.project_id IS NOT NULL THEN 'project'
+                ELSE 'event'
+            END as "item_type!",
+            po.content as "content?",
+            COALESCE(pr.title, ev.title) as "title?",
+            COALESCE(pr.description, ev.description) as "description?",
+            COALESCE(po.image_url, pr.image_url) as image_url,
+            pr.status as "status?",
+            pr.slug as "slug?",
+            COALESCE(pr.looking_for, '{}') as "looking_for!: Vec<String>",
+            ev.starts_at as "starts_at?",
+            ev.ends_at as "ends_at?",
+            ev.location as "location?",
+            f.created_at,
+            f.author_id,
+            u.display_name as author_name,
+            u.username as author_username,
+            u.avatar_url as author_avatar,
+            f.id as feed_id
+        FROM feed_items f
+        JOIN users u ON u.id = f.author_id
+        LEFT JOIN posts po ON po.id = f.post_id
+        LEFT JOIN projects pr ON pr.id = f.project_id
+        LEFT JOIN events ev ON ev.id = f.event_id
+        WHERE u.banned_at IS NULL AND po.held_at IS NULL AND pr.held_at IS NULL
+          AND ($1 = 'all'
+            OR ($1 = 'posts' AND f.post_id IS NOT NULL)
+            OR ($1 = 'projects' AND f.project_id IS NOT NULL)
+            OR ($1 = 'events' AND f.event_id IS NOT NULL))
+          -- Always a bound, rather than "$2 IS NULL OR", so it stays an index condition
+          AND (f.created_at, f.id) < (COALESCE($2::timestamptz, 'infinity'),
+                                      COALESCE($3::uuid, 'ffffffff-ffff-ffff-ffff-ffffffffffff'))
+        ORDER BY f.created_at DESC, f.id DESC
+        LIMIT $4
+        "#,
+        feed_type,
+        before_time,
+        before_id,
+        limit
     )
     .fetch_all(pool)
     .await
@@ -1,44 +1,188 @@
 use axum::{
-    extract::Path,
+    extract::{Path, State},
     http::StatusCode,
     response::{IntoResponse, Json},
 };
-use serde_json::Value;
+use maxminddb::{geoip2, MaxMindDBError};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::sync::Arc;
 
-// Proxy endpoint for ip-api.com
-pub async fn get_geoip(Path(ip): Path<String>) -> Result<impl IntoResponse, (StatusCode, String)> {
-    // Validate IP address format to prevent misuse (basic check)
-    if ip.parse::<std::net::IpAddr>().is_err() {
-        return Err((StatusCode::BAD_REQUEST, "Invalid IP address".to_string()));
+use crate::error::ApiError;
+use crate::i18n;
+
+/// A MaxMind City database (GeoLite2-City or GeoIP2-City), read into memory
+pub type GeoIpReader = maxminddb::Reader<Vec<u8>>;
+
+/// Open the database at GEOIP_DATABASE. None when it isn't set, or can't be read
+/// (logged), in which case lookups go to ip-api.com instead.
+pub fn reader_from_env() -> Option<Arc<GeoIpReader>> {
+    let path = std::env::var("GEOIP_DATABASE").ok()?;
+    match maxminddb::Reader::open_readfile(&path) {
+        Ok(reader) => {
+            tracing::info!("GeoIP lookups use {}", path);
+            Some(Arc::new(reader))
+        }
+        Err(e) => {
+            tracing::warn!(
+                "Failed to open GEOIP_DATABASE {}, using ip-api.com: {}",
+                path,
+                e
+            );
+            None
+        }
     }
+}
 
-    let url = format!(
-        "http://ip-api.com/json/{}?fields=city,regionName,status",
-        ip
-    );
-
-    // Use reqwest to fetch data from ip-api.com
-    // standard reqwest client can handle http
-    let resp = reqwest::get(&url).await.map_err(|e| {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Failed to fetch GeoIP: {}", e),
-        )
-    })?;
-
-    if !resp.status().is_success() {
-        return Err((
-            StatusCode::BAD_GATEWAY,
-            format!("Upstream error: {}", resp.status()),
-        ));
+/// Roughly where an address is; fields the source doesn't know are None
+#[derive(Debug, Default, PartialEq)]
+pub struct Location {
+    pub city: Option<String>,
+    pub region: Option<String>,
+    pub country: Option<String>,
+}
+
+/// Answers GeoIP lookups from the MaxMind database when there is one, otherwise ip-api.com
+#[derive(Clone, Default)]
+pub struct GeoIp {
+    reader: Option<Arc<GeoIpReader>>,
+    http: reqwest::Client,
+}
+
+// ip-api.com's answer for `fields=status,city,regionName,country`
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IpApiResponse {
+    status: String,
+    city: Option<String>,
+    region_name: Option<String>,
+    country: Option<String>,
+}
+
+fn english(names: Option<&BTreeMap<&str, &str>>) -> Option<String> {
+    names
+        .and_then(|names| names.get("en"))
+        .map(|name| name.to_string())
+}
+
+// Empty strings are what ip-api.com sends for fields it doesn't know
+fn known(value: Option<String>) -> Option<String> {
+    value.filter(|value| !value.is_empty())
+}
+
+/// Whether an address could be anywhere on the map; private and loopback ones can't
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            !(ip.is_private() || ip.is_loopback() || ip.is_link_local() || ip.is_unspecified())
+        }
+        IpAddr::V6(ip) => {
+            let first = ip.segments()[0];
+            // fc00::/7 is unique local, fe80::/10 link local
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || first & 0xfe00 == 0xfc00
+                || first & 0xffc0 == 0xfe80)
+        }
     }
+}
+
+impl GeoIp {
+    pub fn new(reader: Option<Arc<GeoIpReader>>, http: reqwest::Client) -> Self {
+        Self { reader, http }
+    }
+
+    /// With the database at GEOIP_DATABASE, if any
+    pub fn from_env(http: reqwest::Client) -> Self {
+        Self::new(reader_from_env(), http)
+    }
+
+    /// None when the address isn't in the database (or ip-api.com has nothing for it)
+    pub async fn lookup(&self, ip: IpAddr) -> Result<Option<Location>, String> {
+        match &self.reader {
+            Some(reader) => lookup_local(reader, ip),
+            None => self.lookup_remote(ip).await,
+        }
+    }
+
+    async fn lookup_remote(&self, ip: IpAddr) -> Result<Option<Location>, String> {
+        let url = format!(
+            "http://ip-api.com/json/{}?fields=status,city,regionName,country",
+            ip
+        );
+
+        // standard reqwest client can handle http
+        let resp = self
+            .http
+            .get(&url)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to fetch GeoIP: {}", e))?;
+        if !resp.status().is_success() {
+            return Err(format!("Upstream error: {}", resp.status()));
+        }
+
+        let data: IpApiResponse = resp
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse GeoIP response: {}", e))?;
+        if data.status != "success" {
+            return Ok(None);
+        }
+        Ok(Some(Location {
+            city: known(data.city),
+            region: known(data.region_name),
+            country: known(data.country),
+        }))
+    }
+}
+
+fn lookup_local(reader: &GeoIpReader, ip: IpAddr) -> Result<Option<Location>, String> {
+    let city = match reader.lookup::<geoip2::City>(ip) {
+        Ok(city) => city,
+        Err(MaxMindDBError::AddressNotFoundError(_)) => return Ok(None),
+        Err(e) => return Err(format!("GeoIP lookup failed: {}", e)),
+    };
+
+    let region = city
+        .subdivisions
+        .as_ref()
+        .and_then(|subdivisions| subdivisions.first())
+        .and_then(|subdivision| subdivision.names.as_ref());
+    Ok(Some(Location {
+        city: english(city.city.as_ref().and_then(|c| c.names.as_ref())),
+        region: english(region),
+        country: english(city.country.as_ref().and_then(|c| c.names.as_ref())),
+    }))
+}
+
+// Same shape as ip-api.com's answer, whichever source replied
+pub async fn get_geoip(
+    State(geoip): State<GeoIp>,
+    Path(ip): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    // Validate IP address format to prevent misuse (basic check)
+    let Ok(addr) = ip.parse::<IpAddr>() else {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            &i18n::INVALID_IP_ADDRESS,
+        ));
+    };
 
-    let data: Value = resp.json().await.map_err(|e| {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Failed to parse GeoIP response: {}", e),
-        )
-    })?;
+    let location = geoip
+        .lookup(addr)
+        .await
+        .map_err(|e| (StatusCode::BAD_GATEWAY, e))?;
 
-    Ok(Json(data))
+    Ok(Json(match location {
+        Some(location) => json!({
+            "status": "success",
+            "city": location.city.unwrap_or_default(),
+            "regionName": location.region.unwrap_or_default(),
+            "country": location.country.unwrap_or_default(),
+        }),
+        None => json!({ "status": "fail" }),
+    }))
 }
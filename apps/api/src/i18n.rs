@@ -0,0 +1,1458 @@
+use serde::{Deserialize, Serialize};
+
+/// Languages emails can be sent in, stored per user in users.locale
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    #[default]
+    En,
+    Es,
+    De,
+}
+
+impl Locale {
+    pub const SUPPORTED: &'static [&'static str] = &["en", "es", "de"];
+
+    /// "es", "es-MX" and "es_MX" all mean Spanish. Anything unsupported gets English.
+    pub fn from_tag(tag: &str) -> Self {
+        Self::parse(tag).unwrap_or_default()
+    }
+
+    /// Like `from_tag`, but None for unsupported languages
+    pub fn parse(tag: &str) -> Option<Self> {
+        let language = tag
+            .split(['-', '_'])
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase();
+        match language.as_str() {
+            "en" => Some(Self::En),
+            "es" => Some(Self::Es),
+            "de" => Some(Self::De),
+            _ => None,
+        }
+    }
+
+    /// The supported language an `Accept-Language` header prefers most, e.g. German for
+    /// "fr-CH, fr;q=0.9, de;q=0.8"
+    pub fn from_accept_language(header: &str) -> Option<Self> {
+        let mut ranges: Vec<(f32, &str)> = header
+            .split(',')
+            .filter_map(|range| {
+                let mut parts = range.split(';');
+                let tag = parts.next()?.trim();
+                let quality = parts
+                    .find_map(|param| param.trim().strip_prefix("q="))
+                    .map_or(Some(1.0), |q| q.parse().ok())?;
+                Some((quality, tag))
+            })
+            .collect();
+        // Stable, so equally weighted languages keep the header's order
+        ranges.sort_by(|a, b| b.0.total_cmp(&a.0));
+        ranges
+            .into_iter()
+            .filter(|(quality, _)| *quality > 0.0)
+            .find_map(|(_, tag)| Self::parse(tag))
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::En => "en",
+            Self::Es => "es",
+            Self::De => "de",
+        }
+    }
+
+    pub fn email(self) -> &'static EmailStrings {
+        match self {
+            Self::En => &EN,
+            Self::Es => &ES,
+            Self::De => &DE,
+        }
+    }
+}
+
+/// Everything the email templates say, in one language.
+/// `{name}`, `{project}`, `{hours}` and `{days}` are filled in by the methods below.
+pub struct EmailStrings {
+    pub lang: &'static str,
+    pub link_fallback: &'static str,
+    pub unsubscribe: &'static str,
+
+    pub verify_subject: &'static str,
+    pub verify_heading: &'static str,
+    pub verify_body: &'static str,
+    pub verify_button: &'static str,
+
+    pub reminder_subject: &'static str,
+    pub reminder_heading: &'static str,
+    pub reminder_body: &'static str,
+    pub reminder_ignore: &'static str,
+
+    pub reset_subject: &'static str,
+    pub reset_heading: &'static str,
+    pub reset_body: &'static str,
+    pub reset_button: &'static str,
+    pub reset_ignore: &'static str,
+    reset_expires_one: &'static str,
+    reset_expires_many: &'static str,
+
+    pub digest_subject: &'static str,
+    digest_greeting: &'static str,
+    pub digest_applications: &'static str,
+    /// Sits between the applicant and the project: "ada applied to Compiler"
+    pub digest_applied_to: &'static str,
+    pub digest_review_button: &'static str,
+    pub digest_review_text: &'static str,
+    pub digest_posts: &'static str,
+    pub digest_unsubscribe: &'static str,
+
+    application_subject: &'static str,
+
+    unread_subject_one: &'static str,
+    unread_subject_many: &'static str,
+    pub unread_heading: &'static str,
+    pub unread_button: &'static str,
+
+    mention_subject: &'static str,
+    /// Sits between the author and an excerpt: "ada mentioned you: Hi @grace"
+    pub mention_action: &'static str,
+    pub mention_button: &'static str,
+
+    message_subject: &'static str,
+    pub message_action: &'static str,
+    pub message_button: &'static str,
+
+    endorsement_subject: &'static str,
+    /// Sits between the endorser and the skill: "ada endorsed your skill: Rust"
+    pub endorsement_action: &'static str,
+    pub endorsement_button: &'static str,
+
+    event_reminder_subject: &'static str,
+    /// Sits between the host and the event: "ada is hosting an event starting soon: Rust meetup"
+    pub event_reminder_action: &'static str,
+    pub event_reminder_button: &'static str,
+
+    sessions_revoked_subject_one: &'static str,
+    sessions_revoked_subject_many: &'static str,
+    pub sessions_revoked_body: &'static str,
+    /// For sessions whose user agent said nothing useful
+    pub sessions_revoked_unknown_device: &'static str,
+    pub sessions_revoked_warning: &'static str,
+    pub sessions_revoked_button: &'static str,
+
+    pub merge_subject: &'static str,
+    pub merge_heading: &'static str,
+    merge_link_body: &'static str,
+    merge_account_body: &'static str,
+    pub merge_button: &'static str,
+    pub merge_ignore: &'static str,
+
+    pub waitlist_subject: &'static str,
+    pub waitlist_heading: &'static str,
+    pub waitlist_body: &'static str,
+    pub waitlist_button: &'static str,
+    waitlist_expires: &'static str,
+}
+
+impl EmailStrings {
+    pub fn reset_expires(&self, hours: i64) -> String {
+        if hours == 1 {
+            self.reset_expires_one.to_string()
+        } else {
+            self.reset_expires_many
+                .replace("{hours}", &hours.to_string())
+        }
+    }
+
+    pub fn digest_greeting(&self, name: &str) -> String {
+        self.digest_greeting.replace("{name}", name)
+    }
+
+    pub fn unread_subject(&self, count: usize) -> String {
+        if count == 1 {
+            self.unread_subject_one.to_string()
+        } else {
+            self.unread_subject_many
+                .replace("{count}", &count.to_string())
+        }
+    }
+
+    pub fn mention_subject(&self, name: &str) -> String {
+        self.mention_subject.replace("{name}", name)
+    }
+
+    pub fn message_subject(&self, name: &str) -> String {
+        self.message_subject.replace("{name}", name)
+    }
+
+    pub fn endorsement_subject(&self, name: &str, skill: &str) -> String {
+        self.endorsement_subject
+            .replace("{name}", name)
+            .replace("{skill}", skill)
+    }
+
+    pub fn event_reminder_subject(&self, event: &str) -> String {
+        self.event_reminder_subject.replace("{event}", event)
+    }
+
+    pub fn sessions_revoked_subject(&self, count: usize) -> String {
+        if count == 1 {
+            self.sessions_revoked_subject_one.to_string()
+        } else {
+            self.sessions_revoked_subject_many
+                .replace("{count}", &count.to_string())
+        }
+    }
+
+    pub fn merge_link_body(&self, provider: &str) -> String {
+        self.merge_link_body.replace("{provider}", provider)
+    }
+
+    pub fn merge_account_body(&self, name: &str) -> String {
+        self.merge_account_body.replace("{name}", name)
+    }
+
+    pub fn waitlist_expires(&self, days: i64) -> String {
+        self.waitlist_expires.replace("{days}", &days.to_string())
+    }
+
+    pub fn application_subject(&self, name: &str, project: &str) -> String {
+        self.application_subject
+            .replace("{name}", name)
+            .replace("{project}", project)
+    }
+}
+
+static EN: EmailStrings = EmailStrings {
+    lang: "en",
+    link_fallback: "Or copy and paste this link into your browser:",
+    unsubscribe: "Unsubscribe",
+
+    verify_subject: "Verify your email",
+    verify_heading: "Welcome to Praxis!",
+    verify_body: "Please verify your email address using the link below:",
+    verify_button: "Verify Email",
+
+    reminder_subject: "Confirm your email to finish signing up",
+    reminder_heading: "You're almost there",
+    reminder_body: "You signed up for Praxis but haven't verified your email address yet. Use the link below to finish:",
+    reminder_ignore: "If you didn't sign up, you can ignore this email.",
+
+    reset_subject: "Reset your password",
+    reset_heading: "Reset Your Password",
+    reset_body:
+        "We received a request to reset your password. Use the link below to choose a new one:",
+    reset_button: "Reset Password",
+    reset_ignore: "If you didn't request this, you can safely ignore this email.",
+    reset_expires_one: "This link expires in 1 hour.",
+    reset_expires_many: "This link expires in {hours} hours.",
+
+    digest_subject: "Your week on Praxis",
+    digest_greeting: "Hi {name}, here's your week on Praxis",
+    digest_applications: "New applications to your projects",
+    digest_applied_to: "applied to",
+    digest_review_button: "Review Applications",
+    digest_review_text: "Review them at",
+    digest_posts: "New on Praxis",
+    digest_unsubscribe: "Unsubscribe from the weekly digest:",
+
+    application_subject: "{name} applied to {project}",
+
+    unread_subject_one: "You have an unread notification on Praxis",
+    unread_subject_many: "You have {count} unread notifications on Praxis",
+    unread_heading: "Here's what you missed",
+    unread_button: "Open Praxis",
+
+    mention_subject: "{name} mentioned you on Praxis",
+    mention_action: "mentioned you:",
+    mention_button: "View",
+
+    message_subject: "{name} sent you a message on Praxis",
+    message_action: "sent you a message",
+    message_button: "Reply",
+
+    endorsement_subject: "{name} endorsed you for {skill} on Praxis",
+    endorsement_action: "endorsed your skill:",
+    endorsement_button: "View profile",
+
+    event_reminder_subject: "Reminder: {event} starts soon",
+    event_reminder_action: "is hosting an event starting soon:",
+    event_reminder_button: "View event",
+
+    sessions_revoked_subject_one: "A device was signed out of your Praxis account",
+    sessions_revoked_subject_many: "{count} devices were signed out of your Praxis account",
+    sessions_revoked_body: "Someone signed into your account signed these out:",
+    sessions_revoked_unknown_device: "Unknown device",
+    sessions_revoked_warning:
+        "If that wasn't you, change your password and review your sessions right away.",
+    sessions_revoked_button: "Review Sessions",
+
+    merge_subject: "Confirm linking your Praxis account",
+    merge_heading: "Link your accounts",
+    merge_link_body: "Someone tried to sign in to Praxis with {provider} using this email address. Confirm below to link {provider} to your account. Until then, it can't be used to sign in.",
+    merge_account_body: "@{name} asked to merge the Praxis account that uses this email address into theirs. Its posts, projects and ways to sign in will move to @{name}, and this account will be deleted.",
+    merge_button: "Confirm",
+    merge_ignore: "If that wasn't you, ignore this email and nothing will change.",
+    waitlist_subject: "Your Praxis invitation is here",
+    waitlist_heading: "You're off the waitlist!",
+    waitlist_body: "Thanks for waiting. Your spot on Praxis is ready: create your account with the link below.",
+    waitlist_button: "Create your account",
+    waitlist_expires: "This link is valid for {days} days.",
+};
+
+static ES: EmailStrings = EmailStrings {
+    lang: "es",
+    link_fallback: "O copia y pega este enlace en tu navegador:",
+    unsubscribe: "Cancelar suscripción",
+
+    verify_subject: "Verifica tu correo electrónico",
+    verify_heading: "¡Bienvenido a Praxis!",
+    verify_body: "Verifica tu dirección de correo electrónico con el siguiente enlace:",
+    verify_button: "Verificar correo",
+
+    reminder_subject: "Confirma tu correo para terminar el registro",
+    reminder_heading: "Ya casi está",
+    reminder_body: "Te registraste en Praxis pero aún no has verificado tu correo electrónico. Usa el siguiente enlace para terminar:",
+    reminder_ignore: "Si no te registraste, puedes ignorar este correo.",
+
+    reset_subject: "Restablece tu contraseña",
+    reset_heading: "Restablece tu contraseña",
+    reset_body: "Recibimos una solicitud para restablecer tu contraseña. Usa el siguiente enlace para elegir una nueva:",
+    reset_button: "Restablecer contraseña",
+    reset_ignore: "Si no lo solicitaste, puedes ignorar este correo.",
+    reset_expires_one: "Este enlace caduca en 1 hora.",
+    reset_expires_many: "Este enlace caduca en {hours} horas.",
+
+    digest_subject: "Tu semana en Praxis",
+    digest_greeting: "Hola {name}, esto es lo que pasó esta semana en Praxis",
+    digest_applications: "Nuevas solicitudes a tus proyectos",
+    digest_applied_to: "se postuló a",
+    digest_review_button: "Ver solicitudes",
+    digest_review_text: "Revísalas en",
+    digest_posts: "Novedades en Praxis",
+    digest_unsubscribe: "Cancelar la suscripción al resumen semanal:",
+
+    application_subject: "{name} se postuló a {project}",
+
+    unread_subject_one: "Tienes una notificación sin leer en Praxis",
+    unread_subject_many: "Tienes {count} notificaciones sin leer en Praxis",
+    unread_heading: "Esto es lo que te perdiste",
+    unread_button: "Abrir Praxis",
+
+    mention_subject: "{name} te mencionó en Praxis",
+    mention_action: "te mencionó:",
+    mention_button: "Ver",
+
+    message_subject: "{name} te envió un mensaje en Praxis",
+    message_action: "te envió un mensaje",
+    message_button: "Responder",
+
+    endorsement_subject: "{name} te recomendó por {skill} en Praxis",
+    endorsement_action: "recomendó tu habilidad:",
+    endorsement_button: "Ver perfil",
+
+    event_reminder_subject: "Recordatorio: {event} empieza pronto",
+    event_reminder_action: "organiza un evento que empieza pronto:",
+    event_reminder_button: "Ver evento",
+
+    sessions_revoked_subject_one: "Se cerró la sesión de un dispositivo en tu cuenta de Praxis",
+    sessions_revoked_subject_many: "Se cerró la sesión de {count} dispositivos en tu cuenta de Praxis",
+    sessions_revoked_body: "Alguien con la sesión iniciada en tu cuenta cerró estas sesiones:",
+    sessions_revoked_unknown_device: "Dispositivo desconocido",
+    sessions_revoked_warning:
+        "Si no fuiste tú, cambia tu contraseña y revisa tus sesiones cuanto antes.",
+    sessions_revoked_button: "Revisar sesiones",
+
+    merge_subject: "Confirma la vinculación de tu cuenta de Praxis",
+    merge_heading: "Vincula tus cuentas",
+    merge_link_body: "Alguien intentó iniciar sesión en Praxis con {provider} usando esta dirección de correo. Confirma a continuación para vincular {provider} a tu cuenta. Hasta entonces, no se podrá usar para iniciar sesión.",
+    merge_account_body: "@{name} pidió fusionar con la suya la cuenta de Praxis que usa esta dirección de correo. Sus publicaciones, proyectos y formas de iniciar sesión pasarán a @{name}, y esta cuenta se eliminará.",
+    merge_button: "Confirmar",
+    merge_ignore: "Si no fuiste tú, ignora este correo y no cambiará nada.",
+    waitlist_subject: "Tu invitación a Praxis ha llegado",
+    waitlist_heading: "¡Ya no estás en la lista de espera!",
+    waitlist_body: "Gracias por esperar. Tu lugar en Praxis está listo: crea tu cuenta con el siguiente enlace.",
+    waitlist_button: "Crear tu cuenta",
+    waitlist_expires: "Este enlace es válido durante {days} días.",
+};
+
+static DE: EmailStrings = EmailStrings {
+    lang: "de",
+    link_fallback: "Oder kopiere diesen Link in deinen Browser:",
+    unsubscribe: "Abmelden",
+
+    verify_subject: "Bestätige deine E-Mail-Adresse",
+    verify_heading: "Willkommen bei Praxis!",
+    verify_body: "Bitte bestätige deine E-Mail-Adresse über den folgenden Link:",
+    verify_button: "E-Mail bestätigen",
+
+    reminder_subject: "Bestätige deine E-Mail-Adresse, um die Anmeldung abzuschließen",
+    reminder_heading: "Fast geschafft",
+    reminder_body: "Du hast dich bei Praxis angemeldet, deine E-Mail-Adresse aber noch nicht bestätigt. Über den folgenden Link schließt du die Anmeldung ab:",
+    reminder_ignore: "Falls du dich nicht angemeldet hast, kannst du diese E-Mail ignorieren.",
+
+    reset_subject: "Setze dein Passwort zurück",
+    reset_heading: "Passwort zurücksetzen",
+    reset_body: "Wir haben eine Anfrage zum Zurücksetzen deines Passworts erhalten. Über den folgenden Link kannst du ein neues wählen:",
+    reset_button: "Passwort zurücksetzen",
+    reset_ignore: "Falls du das nicht angefordert hast, kannst du diese E-Mail ignorieren.",
+    reset_expires_one: "Dieser Link ist 1 Stunde gültig.",
+    reset_expires_many: "Dieser Link ist {hours} Stunden gültig.",
+
+    digest_subject: "Deine Woche auf Praxis",
+    digest_greeting: "Hallo {name}, das war deine Woche auf Praxis",
+    digest_applications: "Neue Bewerbungen für deine Projekte",
+    digest_applied_to: "bewirbt sich für",
+    digest_review_button: "Bewerbungen ansehen",
+    digest_review_text: "Ansehen unter",
+    digest_posts: "Neu auf Praxis",
+    digest_unsubscribe: "Vom Wochenrückblick abmelden:",
+
+    application_subject: "{name} bewirbt sich für {project}",
+
+    unread_subject_one: "Du hast eine ungelesene Benachrichtigung auf Praxis",
+    unread_subject_many: "Du hast {count} ungelesene Benachrichtigungen auf Praxis",
+    unread_heading: "Das hast du verpasst",
+    unread_button: "Praxis öffnen",
+
+    mention_subject: "{name} hat dich auf Praxis erwähnt",
+    mention_action: "hat dich erwähnt:",
+    mention_button: "Ansehen",
+
+    message_subject: "{name} hat dir auf Praxis eine Nachricht geschickt",
+    message_action: "hat dir eine Nachricht geschickt",
+    message_button: "Antworten",
+
+    endorsement_subject: "{name} hat deine Fähigkeit {skill} auf Praxis bestätigt",
+    endorsement_action: "hat deine Fähigkeit bestätigt:",
+    endorsement_button: "Profil ansehen",
+
+    event_reminder_subject: "Erinnerung: {event} beginnt bald",
+    event_reminder_action: "veranstaltet ein Event, das bald beginnt:",
+    event_reminder_button: "Event ansehen",
+
+    sessions_revoked_subject_one: "Ein Gerät wurde von deinem Praxis-Konto abgemeldet",
+    sessions_revoked_subject_many: "{count} Geräte wurden von deinem Praxis-Konto abgemeldet",
+    sessions_revoked_body: "Jemand, der in deinem Konto angemeldet ist, hat diese Sitzungen beendet:",
+    sessions_revoked_unknown_device: "Unbekanntes Gerät",
+    sessions_revoked_warning:
+        "Falls du das nicht warst, ändere sofort dein Passwort und überprüfe deine Sitzungen.",
+    sessions_revoked_button: "Sitzungen überprüfen",
+
+    merge_subject: "Bestätige die Verknüpfung deines Praxis-Kontos",
+    merge_heading: "Konten verknüpfen",
+    merge_link_body: "Jemand hat versucht, sich mit {provider} und dieser E-Mail-Adresse bei Praxis anzumelden. Bestätige unten, um {provider} mit deinem Konto zu verknüpfen. Bis dahin kann es nicht zur Anmeldung verwendet werden.",
+    merge_account_body: "@{name} möchte das Praxis-Konto mit dieser E-Mail-Adresse in das eigene übernehmen. Beiträge, Projekte und Anmeldemethoden gehen an @{name} über, und dieses Konto wird gelöscht.",
+    merge_button: "Bestätigen",
+    merge_ignore: "Falls du das nicht warst, ignoriere diese E-Mail. Es ändert sich nichts.",
+    waitlist_subject: "Deine Einladung zu Praxis ist da",
+    waitlist_heading: "Du bist nicht mehr auf der Warteliste!",
+    waitlist_body: "Danke fürs Warten. Dein Platz bei Praxis ist bereit: Erstelle dein Konto über den folgenden Link.",
+    waitlist_button: "Konto erstellen",
+    waitlist_expires: "Dieser Link ist {days} Tage gültig.",
+};
+
+/// A user-facing API message: a stable `code` for clients, and its text in each language.
+/// Handlers return one by name (`error::ApiError`, `error::Message`) and `error::localize`
+/// translates it. `{name}` placeholders are filled in from the message's arguments.
+#[derive(Debug)]
+pub struct ApiMessage {
+    pub code: &'static str,
+    en: &'static str,
+    es: &'static str,
+    de: &'static str,
+}
+
+impl ApiMessage {
+    pub fn text(&self, locale: Locale) -> &'static str {
+        match locale {
+            Locale::En => self.en,
+            Locale::Es => self.es,
+            Locale::De => self.de,
+        }
+    }
+}
+
+const fn message(
+    code: &'static str,
+    en: &'static str,
+    es: &'static str,
+    de: &'static str,
+) -> ApiMessage {
+    ApiMessage { code, en, es, de }
+}
+
+// Errors that describe our internals (database errors and the like) stay out, and keep English
+// text. The same code may have more than one English wording.
+
+// Accounts and sessions
+pub static NOT_LOGGED_IN: ApiMessage = message(
+    "not_logged_in",
+    "Not logged in",
+    "No has iniciado sesión",
+    "Nicht angemeldet",
+);
+pub static ADMINS_ONLY: ApiMessage = message(
+    "admins_only",
+    "Admins only",
+    "Solo para administradores",
+    "Nur für Admins",
+);
+pub static MODERATORS_ONLY: ApiMessage = message(
+    "moderators_only",
+    "Moderators only",
+    "Solo para moderadores",
+    "Nur für Moderatoren",
+);
+pub static VERIFICATION_REQUIRED: ApiMessage = message(
+    "verification_required",
+    "Verify your email address first",
+    "Primero verifica tu correo electrónico",
+    "Bestätige zuerst deine E-Mail-Adresse",
+);
+pub static VALIDATION_FAILED: ApiMessage = message(
+    "validation_failed",
+    "Validation failed",
+    "Algunos campos no son válidos",
+    "Einige Felder sind ungültig",
+);
+pub static INVALID_CREDENTIALS: ApiMessage = message(
+    "invalid_credentials",
+    "Invalid email or password",
+    "Correo o contraseña incorrectos",
+    "E-Mail-Adresse oder Passwort ist falsch",
+);
+pub static INVALID_PASSWORD: ApiMessage = message(
+    "invalid_password",
+    "Invalid password",
+    "Contraseña incorrecta",
+    "Falsches Passwort",
+);
+pub static PASSWORD_REQUIRED: ApiMessage = message(
+    "password_required",
+    "Password required",
+    "Se requiere la contraseña",
+    "Passwort erforderlich",
+);
+pub static CURRENT_PASSWORD_INCORRECT: ApiMessage = message(
+    "current_password_incorrect",
+    "Current password is incorrect",
+    "La contraseña actual es incorrecta",
+    "Das aktuelle Passwort ist falsch",
+);
+pub static INVALID_CODE: ApiMessage = message(
+    "invalid_code",
+    "Invalid code",
+    "Código no válido",
+    "Ungültiger Code",
+);
+pub static EMAIL_TAKEN: ApiMessage = message(
+    "email_taken",
+    "Email already exists",
+    "Ese correo ya está registrado",
+    "Diese E-Mail-Adresse ist bereits registriert",
+);
+pub static EMAIL_IN_USE: ApiMessage = message(
+    "email_taken",
+    "Email already in use",
+    "Ese correo ya está registrado",
+    "Diese E-Mail-Adresse ist bereits registriert",
+);
+pub static EMAIL_ALREADY_VERIFIED: ApiMessage = message(
+    "email_already_verified",
+    "Email already verified",
+    "Tu correo ya está verificado",
+    "Deine E-Mail-Adresse ist bereits bestätigt",
+);
+pub static USERNAME_TAKEN: ApiMessage = message(
+    "username_taken",
+    "Username already taken",
+    "Ese nombre de usuario ya está en uso",
+    "Dieser Benutzername ist bereits vergeben",
+);
+pub static USERNAME_ALREADY_TAKEN: ApiMessage = message(
+    "username_taken",
+    "That username is already taken",
+    "Ese nombre de usuario ya está en uso",
+    "Dieser Benutzername ist bereits vergeben",
+);
+pub static USERNAME_TOO_SIMILAR: ApiMessage = message(
+    "username_too_similar",
+    "That username is too similar to an existing one",
+    "Ese nombre de usuario se parece demasiado a otro que ya existe",
+    "Dieser Benutzername ist einem bestehenden zu ähnlich",
+);
+pub static TOO_SIMILAR_TO_USERNAME: ApiMessage = message(
+    "username_too_similar",
+    "Too similar to an existing username",
+    "Ese nombre de usuario se parece demasiado a otro que ya existe",
+    "Dieser Benutzername ist einem bestehenden zu ähnlich",
+);
+pub static INVALID_LINK: ApiMessage = message(
+    "invalid_link",
+    "Invalid or expired link",
+    "El enlace no es válido o ha caducado",
+    "Der Link ist ungültig oder abgelaufen",
+);
+pub static INVALID_RESET_TOKEN: ApiMessage = message(
+    "invalid_reset_token",
+    "Invalid or expired reset token",
+    "El enlace para restablecer la contraseña no es válido o ha caducado",
+    "Der Link zum Zurücksetzen ist ungültig oder abgelaufen",
+);
+pub static INVALID_TOKEN_STATE: ApiMessage = message(
+    "invalid_reset_token",
+    "Invalid token state",
+    "El enlace para restablecer la contraseña no es válido o ha caducado",
+    "Der Link zum Zurücksetzen ist ungültig oder abgelaufen",
+);
+pub static RESET_TOKEN_EXPIRED: ApiMessage = message(
+    "reset_token_expired",
+    "Reset token has expired",
+    "El enlace para restablecer la contraseña ha caducado",
+    "Der Link zum Zurücksetzen ist abgelaufen",
+);
+pub static INVALID_VERIFICATION_TOKEN: ApiMessage = message(
+    "invalid_verification_token",
+    "Invalid or expired verification token",
+    "El enlace de verificación no es válido o ha caducado",
+    "Der Bestätigungslink ist ungültig oder abgelaufen",
+);
+pub static SIGNUPS_DISABLED: ApiMessage = message(
+    "signups_disabled",
+    "Signups are currently disabled",
+    "Los registros están desactivados por ahora",
+    "Registrierungen sind derzeit deaktiviert",
+);
+pub static SIGNUPS_INVITE_ONLY: ApiMessage = message(
+    "signups_invite_only",
+    "Signups are invite only",
+    "Solo puedes registrarte con una invitación",
+    "Registrierung nur mit Einladung",
+);
+pub static INVALID_INVITE: ApiMessage = message(
+    "invalid_invite",
+    "That invite code is invalid or used up",
+    "Ese código de invitación no es válido o ya se usó",
+    "Dieser Einladungscode ist ungültig oder aufgebraucht",
+);
+pub static INVALID_SIGNUP_LINK: ApiMessage = message(
+    "invalid_signup_link",
+    "That signup link is invalid or has expired",
+    "Ese enlace de registro no es válido o ha caducado",
+    "Dieser Registrierungslink ist ungültig oder abgelaufen",
+);
+pub static PASSWORD_ALREADY_SET: ApiMessage = message(
+    "password_already_set",
+    "Password already set. Use change-password instead.",
+    "Ya tienes una contraseña. Cámbiala en su lugar.",
+    "Du hast bereits ein Passwort. Ändere es stattdessen.",
+);
+pub static NO_EMAIL_LOGIN: ApiMessage = message(
+    "no_email_login",
+    "User does not have email login set up. Cannot reset password.",
+    "Esta cuenta no inicia sesión con correo, así que no se puede restablecer la contraseña.",
+    "Dieses Konto hat keine E-Mail-Anmeldung, das Passwort kann nicht zurückgesetzt werden.",
+);
+pub static ONLY_SIGN_IN_METHOD: ApiMessage = message("only_sign_in_method", "Cannot unlink your only sign-in method. Add a password or link another account first.", "No puedes desvincular tu único método de inicio de sesión. Añade una contraseña o vincula otra cuenta primero.", "Du kannst deine einzige Anmeldemethode nicht entfernen. Lege zuerst ein Passwort fest oder verknüpfe ein anderes Konto.");
+pub static TOTP_NOT_SET_UP: ApiMessage = message(
+    "totp_not_set_up",
+    "TOTP not set up",
+    "La verificación en dos pasos no está configurada",
+    "Die Zwei-Faktor-Authentifizierung ist nicht eingerichtet",
+);
+pub static NO_2FA_PENDING: ApiMessage = message(
+    "no_2fa_pending",
+    "No 2FA pending",
+    "No hay ninguna verificación en dos pasos pendiente",
+    "Keine Zwei-Faktor-Anmeldung ausstehend",
+);
+pub static MERGE_LOGIN_FIRST: ApiMessage = message(
+    "merge_login_first",
+    "Log in to the account you're merging into first",
+    "Primero inicia sesión en la cuenta que quieres conservar",
+    "Melde dich zuerst bei dem Konto an, das du behalten willst",
+);
+pub static ACCOUNT_LINKED: ApiMessage = message(
+    "account_linked",
+    "{provider} is linked to your account. Sign in with it to continue.",
+    "{provider} está vinculado a tu cuenta. Inicia sesión con él para continuar.",
+    "{provider} ist mit deinem Konto verknüpft. Melde dich damit an, um fortzufahren.",
+);
+pub static PROVIDER_ALREADY_LINKED: ApiMessage = message(
+    "provider_already_linked",
+    "That {provider} account is already linked to an account",
+    "Esa cuenta de {provider} ya está vinculada a otra cuenta",
+    "Dieses {provider}-Konto ist bereits mit einem Konto verknüpft",
+);
+pub static ACCOUNT_NOT_LINKED: ApiMessage = message(
+    "account_not_linked",
+    "Account not linked",
+    "La cuenta no está vinculada",
+    "Das Konto ist nicht verknüpft",
+);
+pub static GITHUB_NO_EMAIL: ApiMessage = message(
+    "github_no_email",
+    "No email found for GitHub user",
+    "Tu cuenta de GitHub no tiene un correo electrónico",
+    "Dein GitHub-Konto hat keine E-Mail-Adresse",
+);
+pub static API_KEY_REQUIRED: ApiMessage = message(
+    "api_key_required",
+    "An API key is required",
+    "Se requiere una clave de API",
+    "Ein API-Schlüssel ist erforderlich",
+);
+pub static INVALID_API_KEY: ApiMessage = message(
+    "invalid_api_key",
+    "Invalid API key",
+    "Clave de API no válida",
+    "Ungültiger API-Schlüssel",
+);
+pub static INVALID_UNSUBSCRIBE_LINK: ApiMessage = message(
+    "invalid_unsubscribe_link",
+    "Invalid unsubscribe link",
+    "El enlace para darse de baja no es válido",
+    "Ungültiger Abmeldelink",
+);
+pub static INVITE_MAX_USES: ApiMessage = message(
+    "invite_max_uses",
+    "An invite can be used at most {max} times",
+    "Una invitación se puede usar como máximo {max} veces",
+    "Eine Einladung kann höchstens {max}-mal verwendet werden",
+);
+pub static TOO_MANY_INVITES: ApiMessage = message(
+    "too_many_invites",
+    "You can have {max} unused invites at a time",
+    "Puedes tener como máximo {max} invitaciones sin usar a la vez",
+    "Du kannst höchstens {max} unbenutzte Einladungen gleichzeitig haben",
+);
+pub static PASSWORD_DATA_INVALID: ApiMessage = message(
+    "password_data_invalid",
+    "Account password data is invalid. Please reset your password to fix this issue.",
+    "Los datos de la contraseña de la cuenta no son válidos. Restablece tu contraseña para solucionarlo.",
+    "Die Passwortdaten des Kontos sind ungültig. Setze dein Passwort zurück, um das zu beheben.",
+);
+pub static NO_PASSKEY_REGISTRATION: ApiMessage = message(
+    "no_passkey_registration",
+    "No registration in progress",
+    "No hay ningún registro en curso",
+    "Keine Registrierung im Gange",
+);
+pub static PASSKEY_REGISTRATION_FAILED: ApiMessage = message(
+    "passkey_registration_failed",
+    "Registration failed: {reason}",
+    "El registro falló: {reason}",
+    "Registrierung fehlgeschlagen: {reason}",
+);
+pub static NO_PASSKEYS: ApiMessage = message(
+    "no_passkeys",
+    "No passkeys registered",
+    "No tienes llaves de acceso registradas",
+    "Keine Passkeys registriert",
+);
+pub static NO_PASSKEY_AUTHENTICATION: ApiMessage = message(
+    "no_passkey_authentication",
+    "No authentication in progress",
+    "No hay ningún inicio de sesión en curso",
+    "Keine Anmeldung im Gange",
+);
+pub static UNKNOWN_CREDENTIAL: ApiMessage = message(
+    "unknown_credential",
+    "Unknown credential",
+    "Credencial desconocida",
+    "Unbekannte Anmeldedaten",
+);
+pub static PASSKEY_AUTHENTICATION_FAILED: ApiMessage = message(
+    "passkey_authentication_failed",
+    "Authentication failed: {reason}",
+    "La autenticación falló: {reason}",
+    "Anmeldung fehlgeschlagen: {reason}",
+);
+pub static ACCOUNT_BANNED: ApiMessage = message(
+    "account_banned",
+    "This account has been banned",
+    "Esta cuenta ha sido bloqueada",
+    "Dieses Konto wurde gesperrt",
+);
+pub static ACCOUNT_SUSPENDED: ApiMessage = message(
+    "account_suspended",
+    "This account is suspended until {until}",
+    "Esta cuenta está suspendida hasta el {until}",
+    "Dieses Konto ist bis {until} gesperrt",
+);
+pub static RATE_LIMITED: ApiMessage = message(
+    "rate_limited",
+    "Too many requests, please slow down",
+    "Demasiadas solicitudes, ve más despacio",
+    "Zu viele Anfragen, bitte etwas langsamer",
+);
+pub static POST_QUOTA_REACHED: ApiMessage = message(
+    "post_quota_reached",
+    "You can make up to {limit} posts an hour. Try again in {wait}.",
+    "Puedes hacer hasta {limit} publicaciones por hora. Inténtalo de nuevo en {wait}.",
+    "Du kannst bis zu {limit} Beiträge pro Stunde verfassen. Versuche es in {wait} erneut.",
+);
+pub static PROJECT_QUOTA_REACHED: ApiMessage = message(
+    "project_quota_reached",
+    "You can create up to {limit} projects a day. Try again in {wait}.",
+    "Puedes crear hasta {limit} proyectos al día. Inténtalo de nuevo en {wait}.",
+    "Du kannst bis zu {limit} Projekte pro Tag erstellen. Versuche es in {wait} erneut.",
+);
+pub static ONE_MINUTE: ApiMessage = message("one_minute", "1 minute", "1 minuto", "1 Minute");
+pub static MINUTES: ApiMessage = message(
+    "minutes",
+    "{count} minutes",
+    "{count} minutos",
+    "{count} Minuten",
+);
+pub static ONE_HOUR: ApiMessage = message("one_hour", "1 hour", "1 hora", "1 Stunde");
+pub static HOURS: ApiMessage =
+    message("hours", "{count} hours", "{count} horas", "{count} Stunden");
+
+// Uploads
+pub static STORAGE_QUOTA_EXCEEDED: ApiMessage = message(
+    "storage_quota_exceeded",
+    "Storage quota exceeded",
+    "Has superado tu espacio de almacenamiento",
+    "Dein Speicherplatz ist aufgebraucht",
+);
+pub static INVALID_FILE_TYPE: ApiMessage = message(
+    "invalid_file_type",
+    "Invalid file type",
+    "Tipo de archivo no válido",
+    "Ungültiger Dateityp",
+);
+pub static UNSUPPORTED_IMAGE_TYPE: ApiMessage = message(
+    "unsupported_image_type",
+    "Unsupported image type",
+    "Tipo de imagen no compatible",
+    "Dieser Bildtyp wird nicht unterstützt",
+);
+pub static IMAGE_TYPE_MISMATCH: ApiMessage = message(
+    "image_type_mismatch",
+    "File content does not match declared type",
+    "El contenido del archivo no coincide con su tipo",
+    "Der Dateiinhalt passt nicht zum angegebenen Typ",
+);
+pub static NO_FILE_UPLOADED: ApiMessage = message(
+    "no_file_uploaded",
+    "No file uploaded",
+    "No se subió ningún archivo",
+    "Keine Datei hochgeladen",
+);
+pub static IMAGE_UNPROCESSABLE: ApiMessage = message(
+    "image_unprocessable",
+    "Could not process image",
+    "No se pudo procesar la imagen",
+    "Das Bild konnte nicht verarbeitet werden",
+);
+pub static UNSUPPORTED_VIDEO_TYPE: ApiMessage = message(
+    "unsupported_video_type",
+    "Unsupported video type",
+    "Tipo de vídeo no compatible",
+    "Dieser Videotyp wird nicht unterstützt",
+);
+pub static WEBSITE_UNREACHABLE: ApiMessage = message(
+    "website_unreachable",
+    "Website could not be reached",
+    "No se pudo acceder al sitio web",
+    "Die Website war nicht erreichbar",
+);
+pub static UPLOAD_TOO_LARGE: ApiMessage = message(
+    "upload_too_large",
+    "{category} uploads are limited to {mb} MB",
+    "{category}: el límite es de {mb} MB",
+    "{category}: höchstens {mb} MB",
+);
+pub static VIDEO_NEEDS_PRESIGN: ApiMessage = message(
+    "video_needs_presign",
+    "Videos must be uploaded via /upload/presign",
+    "Los vídeos se deben subir mediante /upload/presign",
+    "Videos müssen über /upload/presign hochgeladen werden",
+);
+pub static FILE_READ_FAILED: ApiMessage = message(
+    "file_read_failed",
+    "Failed to read file",
+    "No se pudo leer el archivo",
+    "Die Datei konnte nicht gelesen werden",
+);
+pub static UPLOAD_FAILED: ApiMessage = message(
+    "upload_failed",
+    "Failed to upload file",
+    "No se pudo subir el archivo",
+    "Die Datei konnte nicht hochgeladen werden",
+);
+pub static UPLOAD_NOT_RECEIVED: ApiMessage = message(
+    "upload_not_received",
+    "The file hasn't been uploaded yet",
+    "El archivo aún no se ha subido",
+    "Die Datei wurde noch nicht hochgeladen",
+);
+pub static UPLOAD_PREPARE_FAILED: ApiMessage = message(
+    "upload_prepare_failed",
+    "Failed to prepare upload",
+    "No se pudo preparar la subida",
+    "Der Upload konnte nicht vorbereitet werden",
+);
+pub static AVATAR: ApiMessage = message("avatar", "Avatar", "Avatar", "Avatar");
+pub static BANNER: ApiMessage = message("banner", "Banner", "Banner", "Banner");
+pub static POST_IMAGE: ApiMessage = message(
+    "post_image",
+    "Post image",
+    "Imagen de publicación",
+    "Beitragsbild",
+);
+pub static VIDEO: ApiMessage = message("video", "Video", "Vídeo", "Video");
+pub static MESSAGE_ATTACHMENT: ApiMessage = message(
+    "message_attachment",
+    "Message attachment",
+    "Archivo adjunto",
+    "Nachrichtenanhang",
+);
+pub static IMAGE_NOT_OWNED: ApiMessage = message(
+    "image_not_owned",
+    "Image must be one of your uploads",
+    "La imagen debe ser uno de tus archivos subidos",
+    "Das Bild muss eines deiner hochgeladenen Bilder sein",
+);
+
+// Not found
+pub static NOT_FOUND: ApiMessage =
+    message("not_found", "Not found", "No encontrado", "Nicht gefunden");
+pub static USER_NOT_FOUND: ApiMessage = message(
+    "user_not_found",
+    "User not found",
+    "Usuario no encontrado",
+    "Benutzer nicht gefunden",
+);
+pub static PROJECT_NOT_FOUND: ApiMessage = message(
+    "project_not_found",
+    "Project not found",
+    "Proyecto no encontrado",
+    "Projekt nicht gefunden",
+);
+pub static POST_NOT_FOUND: ApiMessage = message(
+    "post_not_found",
+    "Post not found",
+    "Publicación no encontrada",
+    "Beitrag nicht gefunden",
+);
+pub static CONVERSATION_NOT_FOUND: ApiMessage = message(
+    "conversation_not_found",
+    "Conversation not found",
+    "Conversación no encontrada",
+    "Unterhaltung nicht gefunden",
+);
+pub static MESSAGE_NOT_FOUND: ApiMessage = message(
+    "message_not_found",
+    "Message not found",
+    "Mensaje no encontrado",
+    "Nachricht nicht gefunden",
+);
+pub static EVENT_NOT_FOUND: ApiMessage = message(
+    "event_not_found",
+    "Event not found",
+    "Evento no encontrado",
+    "Event nicht gefunden",
+);
+pub static LISTING_NOT_FOUND: ApiMessage = message(
+    "listing_not_found",
+    "Listing not found",
+    "Oferta no encontrada",
+    "Anzeige nicht gefunden",
+);
+pub static APPLICATION_NOT_FOUND: ApiMessage = message(
+    "application_not_found",
+    "Application not found",
+    "Solicitud no encontrada",
+    "Bewerbung nicht gefunden",
+);
+pub static NOTIFICATION_NOT_FOUND: ApiMessage = message(
+    "notification_not_found",
+    "Notification not found",
+    "Notificación no encontrada",
+    "Benachrichtigung nicht gefunden",
+);
+pub static UPLOAD_NOT_FOUND: ApiMessage = message(
+    "upload_not_found",
+    "Upload not found",
+    "Archivo no encontrado",
+    "Datei nicht gefunden",
+);
+pub static INVITE_NOT_FOUND: ApiMessage = message(
+    "invite_not_found",
+    "Invite not found",
+    "Invitación no encontrada",
+    "Einladung nicht gefunden",
+);
+pub static ENDORSEMENT_NOT_FOUND: ApiMessage = message(
+    "endorsement_not_found",
+    "Endorsement not found",
+    "Recomendación no encontrada",
+    "Bestätigung nicht gefunden",
+);
+pub static SKILL_NOT_LISTED: ApiMessage = message(
+    "skill_not_listed",
+    "They don't list that skill",
+    "Esa habilidad no aparece en su perfil",
+    "Diese Fähigkeit steht nicht in ihrem Profil",
+);
+pub static TEMPLATE_NOT_FOUND: ApiMessage = message(
+    "template_not_found",
+    "Template not found",
+    "Plantilla no encontrada",
+    "Vorlage nicht gefunden",
+);
+pub static SAVED_SEARCH_NOT_FOUND: ApiMessage = message(
+    "saved_search_not_found",
+    "Saved search not found",
+    "Búsqueda guardada no encontrada",
+    "Gespeicherte Suche nicht gefunden",
+);
+pub static SESSION_NOT_FOUND: ApiMessage = message(
+    "session_not_found",
+    "Session not found",
+    "Sesión no encontrada",
+    "Sitzung nicht gefunden",
+);
+pub static PASSKEY_NOT_FOUND: ApiMessage = message(
+    "passkey_not_found",
+    "Passkey not found",
+    "Llave de acceso no encontrada",
+    "Passkey nicht gefunden",
+);
+pub static ACCOUNT_NOT_FOUND: ApiMessage = message(
+    "account_not_found",
+    "Account not found",
+    "Cuenta no encontrada",
+    "Konto nicht gefunden",
+);
+pub static API_KEY_NOT_FOUND: ApiMessage = message(
+    "api_key_not_found",
+    "API key not found",
+    "Clave de API no encontrada",
+    "API-Schlüssel nicht gefunden",
+);
+pub static MILESTONE_NOT_FOUND: ApiMessage = message(
+    "milestone_not_found",
+    "Milestone not found",
+    "Hito no encontrado",
+    "Meilenstein nicht gefunden",
+);
+pub static ATTACHMENT_NOT_FOUND: ApiMessage = message(
+    "attachment_not_found",
+    "Attachment not found",
+    "Archivo adjunto no encontrado",
+    "Anhang nicht gefunden",
+);
+pub static REPORT_NOT_FOUND: ApiMessage = message(
+    "report_not_found",
+    "Report not found",
+    "Denuncia no encontrada",
+    "Meldung nicht gefunden",
+);
+pub static REVISION_NOT_FOUND: ApiMessage = message(
+    "revision_not_found",
+    "Revision not found",
+    "Revisión no encontrada",
+    "Version nicht gefunden",
+);
+pub static ANNOUNCEMENT_NOT_FOUND: ApiMessage = message(
+    "announcement_not_found",
+    "Announcement not found",
+    "Anuncio no encontrado",
+    "Ankündigung nicht gefunden",
+);
+pub static BROADCAST_NOT_FOUND: ApiMessage = message(
+    "broadcast_not_found",
+    "Broadcast not found",
+    "Envío no encontrado",
+    "Rundmail nicht gefunden",
+);
+pub static EMAIL_NOT_FOUND: ApiMessage = message(
+    "email_not_found",
+    "Email not found",
+    "Correo no encontrado",
+    "E-Mail nicht gefunden",
+);
+pub static PAGE_NOT_FOUND: ApiMessage = message(
+    "page_not_found",
+    "No page at that path",
+    "No hay ninguna página en esa ruta",
+    "Unter diesem Pfad gibt es keine Seite",
+);
+pub static NOTHING_TO_EMBED: ApiMessage = message(
+    "nothing_to_embed",
+    "Nothing to embed at that URL",
+    "No hay nada que insertar en esa URL",
+    "Unter dieser URL gibt es nichts zum Einbetten",
+);
+pub static PENDING_UPLOAD_NOT_FOUND: ApiMessage = message(
+    "pending_upload_not_found",
+    "No pending upload with that id",
+    "No hay ningún archivo pendiente con ese id",
+    "Keine ausstehende Datei mit dieser ID",
+);
+pub static HELD_ITEM_NOT_FOUND: ApiMessage = message(
+    "held_item_not_found",
+    "Nothing held with that id",
+    "No hay nada retenido con ese id",
+    "Nichts mit dieser ID zurückgehalten",
+);
+
+// Bad requests
+pub static INVALID_CURSOR: ApiMessage = message(
+    "invalid_cursor",
+    "Invalid cursor",
+    "Cursor no válido",
+    "Ungültiger Cursor",
+);
+pub static INVALID_IP_ADDRESS: ApiMessage = message(
+    "invalid_ip_address",
+    "Invalid IP address",
+    "Dirección IP no válida",
+    "Ungültige IP-Adresse",
+);
+pub static INVALID_PATH: ApiMessage = message(
+    "invalid_path",
+    "Invalid path",
+    "Ruta no válida",
+    "Ungültiger Pfad",
+);
+pub static PATH_NOT_ABSOLUTE: ApiMessage = message(
+    "invalid_path",
+    "path must start with /",
+    "La ruta debe empezar por /",
+    "Der Pfad muss mit / beginnen",
+);
+pub static JSON_FORMAT_ONLY: ApiMessage = message(
+    "format_not_supported",
+    "Only the json format is supported",
+    "Solo se admite el formato json",
+    "Nur das Format json wird unterstützt",
+);
+pub static INVALID_WAITLIST_STATUS: ApiMessage = message(
+    "invalid_status",
+    "status must be waiting, approved or joined",
+    "El estado debe ser waiting, approved o joined",
+    "Der Status muss waiting, approved oder joined sein",
+);
+
+// Not allowed
+pub static ORIGIN_NOT_ALLOWED: ApiMessage = message(
+    "origin_not_allowed",
+    "Origin not allowed",
+    "Origen no permitido",
+    "Herkunft nicht erlaubt",
+);
+pub static NOT_YOUR_POST: ApiMessage = message(
+    "not_your_post",
+    "Not your post",
+    "Esta publicación no es tuya",
+    "Das ist nicht dein Beitrag",
+);
+pub static NOT_YOUR_PROJECT: ApiMessage = message(
+    "not_your_project",
+    "Not your project",
+    "Este proyecto no es tuyo",
+    "Das ist nicht dein Projekt",
+);
+pub static NOT_YOUR_EVENT: ApiMessage = message(
+    "not_your_event",
+    "Not your event",
+    "Este evento no es tuyo",
+    "Das ist nicht dein Event",
+);
+pub static NOT_YOUR_LISTING: ApiMessage = message(
+    "not_your_listing",
+    "Not your listing",
+    "Esta oferta no es tuya",
+    "Das ist nicht deine Anzeige",
+);
+pub static CANNOT_MESSAGE_USER: ApiMessage = message(
+    "cannot_message_user",
+    "You can't message this user",
+    "No puedes enviar mensajes a este usuario",
+    "Du kannst diesem Benutzer keine Nachrichten schreiben",
+);
+pub static CANNOT_FOLLOW_USER: ApiMessage = message(
+    "cannot_follow_user",
+    "You can't follow this user",
+    "No puedes seguir a este usuario",
+    "Du kannst diesem Benutzer nicht folgen",
+);
+pub static CANNOT_ENDORSE_USER: ApiMessage = message(
+    "cannot_endorse_user",
+    "You can't endorse this user",
+    "No puedes recomendar a este usuario",
+    "Du kannst diesen Benutzer nicht bestätigen",
+);
+pub static CANNOT_MESSAGE_SELF: ApiMessage = message(
+    "cannot_message_self",
+    "You can't message yourself",
+    "No puedes enviarte mensajes a ti mismo",
+    "Du kannst dir nicht selbst schreiben",
+);
+pub static CANNOT_ENDORSE_SELF: ApiMessage = message(
+    "cannot_endorse_self",
+    "You can't endorse yourself",
+    "No puedes recomendarte a ti mismo",
+    "Du kannst dich nicht selbst bestätigen",
+);
+pub static CANNOT_REPORT_OWN_MESSAGE: ApiMessage = message(
+    "cannot_report_own_message",
+    "You can't report your own message",
+    "No puedes denunciar tu propio mensaje",
+    "Du kannst deine eigene Nachricht nicht melden",
+);
+pub static CANNOT_APPLY_OWN_LISTING: ApiMessage = message(
+    "cannot_apply_own_listing",
+    "You can't apply to your own listing",
+    "No puedes postularte a tu propia oferta",
+    "Du kannst dich nicht auf deine eigene Anzeige bewerben",
+);
+pub static THAT_IS_YOU: ApiMessage =
+    message("that_is_you", "That's you", "Ese eres tú", "Das bist du");
+pub static UNSEND_WINDOW_PASSED: ApiMessage = message(
+    "unsend_window_passed",
+    "Messages can only be unsent within {minutes} minutes",
+    "Solo puedes anular el envío de un mensaje durante {minutes} minutos",
+    "Nachrichten können nur innerhalb von {minutes} Minuten zurückgezogen werden",
+);
+pub static REPUTATION_REQUIRED_FOR_LINKS: ApiMessage = message(
+    "reputation_required_for_links",
+    "You need {reputation} reputation to post links",
+    "Necesitas {reputation} de reputación para publicar enlaces",
+    "Du brauchst {reputation} Reputation, um Links zu posten",
+);
+pub static CANNOT_RESTRICT_SELF: ApiMessage = message(
+    "cannot_restrict_self",
+    "You cannot restrict your own account",
+    "No puedes restringir tu propia cuenta",
+    "Du kannst dein eigenes Konto nicht einschränken",
+);
+pub static CANNOT_RESTRICT_ADMIN: ApiMessage = message(
+    "cannot_restrict_admin",
+    "Admins cannot be banned or suspended",
+    "Los administradores no pueden ser bloqueados ni suspendidos",
+    "Admins können nicht gesperrt oder suspendiert werden",
+);
+pub static CANNOT_RESTRICT_MODERATOR: ApiMessage = message(
+    "cannot_restrict_moderator",
+    "Only admins can restrict moderators",
+    "Solo los administradores pueden restringir a los moderadores",
+    "Nur Admins können Moderatoren einschränken",
+);
+pub static CANNOT_REVERT_STAFF: ApiMessage = message(
+    "cannot_revert_staff",
+    "Only admins can revert staff profiles",
+    "Solo los administradores pueden revertir perfiles del equipo",
+    "Nur Admins können Profile von Teammitgliedern zurücksetzen",
+);
+pub static LAST_ADMIN: ApiMessage = message(
+    "last_admin",
+    "Cannot demote the last admin",
+    "No se puede degradar al último administrador",
+    "Der letzte Admin kann nicht herabgestuft werden",
+);
+pub static LAST_ADMIN_MERGE: ApiMessage = message(
+    "last_admin",
+    "Cannot merge away the last admin",
+    "No se puede unir la cuenta del último administrador",
+    "Das Konto des letzten Admins kann nicht zusammengeführt werden",
+);
+
+// Already done, full or over
+pub static ALREADY_APPLIED_TO_PROJECT: ApiMessage = message(
+    "already_applied",
+    "You have already applied to this project",
+    "Ya te postulaste a este proyecto",
+    "Du hast dich bereits für dieses Projekt beworben",
+);
+pub static ALREADY_APPLIED_TO_LISTING: ApiMessage = message(
+    "already_applied",
+    "You have already applied to this listing",
+    "Ya te postulaste a esta oferta",
+    "Du hast dich bereits auf diese Anzeige beworben",
+);
+pub static ALREADY_ENDORSED: ApiMessage = message(
+    "already_endorsed",
+    "You've already endorsed that skill",
+    "Ya recomendaste esa habilidad",
+    "Du hast diese Fähigkeit bereits bestätigt",
+);
+pub static ALREADY_REPORTED: ApiMessage = message(
+    "already_reported",
+    "You already reported this message",
+    "Ya denunciaste este mensaje",
+    "Du hast diese Nachricht bereits gemeldet",
+);
+pub static ALREADY_APPEALED: ApiMessage = message(
+    "already_appealed",
+    "You already appealed this report",
+    "Ya apelaste esta denuncia",
+    "Du hast gegen diese Meldung bereits Einspruch eingelegt",
+);
+pub static TEMPLATE_NAME_TAKEN: ApiMessage = message(
+    "template_name_taken",
+    "A template with that name already exists",
+    "Ya existe una plantilla con ese nombre",
+    "Es gibt bereits eine Vorlage mit diesem Namen",
+);
+pub static LISTING_CLOSED: ApiMessage = message(
+    "listing_closed",
+    "This listing is no longer taking applications",
+    "Esta oferta ya no acepta solicitudes",
+    "Diese Anzeige nimmt keine Bewerbungen mehr an",
+);
+pub static EVENT_FULL: ApiMessage = message(
+    "event_full",
+    "This event is full",
+    "Este evento está completo",
+    "Dieses Event ist ausgebucht",
+);
+pub static EVENT_OVER: ApiMessage = message(
+    "event_over",
+    "This event is over",
+    "Este evento ya terminó",
+    "Dieses Event ist vorbei",
+);
+pub static EVENT_ENDS_BEFORE_START: ApiMessage = message(
+    "event_ends_before_start",
+    "The event must end after it starts",
+    "El evento debe terminar después de empezar",
+    "Das Event muss nach seinem Beginn enden",
+);
+pub static BROADCAST_ALREADY_SENT: ApiMessage = message(
+    "broadcast_already_sent",
+    "Broadcast already sent",
+    "Este envío ya se realizó",
+    "Die Rundmail wurde bereits gesendet",
+);
+pub static REPORT_ALREADY_RESOLVED: ApiMessage = message(
+    "report_already_resolved",
+    "Report already resolved",
+    "La denuncia ya se resolvió",
+    "Die Meldung wurde bereits bearbeitet",
+);
+pub static POST_TOO_LONG: ApiMessage = message(
+    "post_too_long",
+    "Posts are limited to {max} characters",
+    "Las publicaciones tienen un límite de {max} caracteres",
+    "Beiträge dürfen höchstens {max} Zeichen lang sein",
+);
+pub static TOO_MANY_SAVED_SEARCHES: ApiMessage = message(
+    "too_many_saved_searches",
+    "You can save at most {max} searches",
+    "Puedes guardar como máximo {max} búsquedas",
+    "Du kannst höchstens {max} Suchen speichern",
+);
+
+// Status messages
+pub static LOGGED_OUT: ApiMessage = message(
+    "logged_out",
+    "Logged out successfully",
+    "Sesión cerrada",
+    "Erfolgreich abgemeldet",
+);
+pub static LOGGED_IN: ApiMessage = message(
+    "logged_in",
+    "Login successful",
+    "Sesión iniciada",
+    "Erfolgreich angemeldet",
+);
+pub static TWO_FACTOR_REQUIRED: ApiMessage = message(
+    "2fa_required",
+    "2FA verification required",
+    "Se requiere la verificación en dos pasos",
+    "Zwei-Faktor-Bestätigung erforderlich",
+);
+pub static UNSUBSCRIBED: ApiMessage = message(
+    "unsubscribed",
+    "Unsubscribed",
+    "Suscripción cancelada",
+    "Abgemeldet",
+);
+pub static PROFILE_UPDATED: ApiMessage = message(
+    "profile_updated",
+    "Profile updated successfully",
+    "Perfil actualizado",
+    "Profil aktualisiert",
+);
+pub static PASSWORD_CHANGED: ApiMessage = message(
+    "password_changed",
+    "Password changed successfully",
+    "Contraseña cambiada",
+    "Passwort geändert",
+);
+pub static PASSWORD_SET: ApiMessage = message(
+    "password_set",
+    "Password set successfully",
+    "Contraseña guardada",
+    "Passwort festgelegt",
+);
+pub static ACCOUNT_UNLINKED: ApiMessage = message(
+    "account_unlinked",
+    "{provider} account unlinked successfully",
+    "Cuenta de {provider} desvinculada",
+    "{provider}-Konto entfernt",
+);
+pub static PASSWORD_RESET: ApiMessage = message(
+    "password_reset",
+    "Password reset successfully",
+    "Contraseña restablecida",
+    "Passwort zurückgesetzt",
+);
+pub static PASSWORD_RESET_LOG_IN: ApiMessage = message(
+    "password_reset",
+    "Password has been reset successfully. You can now login.",
+    "Tu contraseña se restableció. Ya puedes iniciar sesión.",
+    "Dein Passwort wurde zurückgesetzt. Du kannst dich jetzt anmelden.",
+);
+pub static EMAIL_VERIFIED: ApiMessage = message(
+    "email_verified",
+    "Email verified successfully",
+    "Correo verificado",
+    "E-Mail-Adresse bestätigt",
+);
+pub static VERIFICATION_SENT: ApiMessage = message(
+    "verification_sent",
+    "Verification email sent",
+    "Te enviamos el correo de verificación",
+    "Bestätigungs-E-Mail gesendet",
+);
+pub static VERIFICATION_SENT_IF_EXISTS: ApiMessage = message(
+    "verification_sent",
+    "If that email exists, we sent a verification link.",
+    "Si ese correo existe, le enviamos un enlace de verificación.",
+    "Falls diese E-Mail-Adresse existiert, haben wir einen Bestätigungslink gesendet.",
+);
+pub static RESET_SENT: ApiMessage = message("reset_sent", "If an account exists with that email, a reset link has been sent.", "Si existe una cuenta con ese correo, le enviamos un enlace para restablecer la contraseña.", "Falls ein Konto mit dieser E-Mail-Adresse existiert, haben wir einen Link zum Zurücksetzen gesendet.");
+pub static SIGNED_UP: ApiMessage = message(
+    "signed_up",
+    "User created successfully. Please verify your email.",
+    "Cuenta creada. Verifica tu correo electrónico.",
+    "Konto erstellt. Bitte bestätige deine E-Mail-Adresse.",
+);
+pub static WAITLIST_JOINED: ApiMessage = message(
+    "waitlist_joined",
+    "You're on the waitlist. We'll email you when your spot is ready.",
+    "Estás en la lista de espera. Te escribiremos cuando tu lugar esté listo.",
+    "Du stehst auf der Warteliste. Wir schreiben dir, sobald dein Platz bereit ist.",
+);
+pub static MERGE_CONFIRMATION_SENT: ApiMessage = message("merge_confirmation_sent", "If another account uses that email, a confirmation link has been sent to it.", "Si otra cuenta usa ese correo, le enviamos un enlace de confirmación.", "Falls ein anderes Konto diese E-Mail-Adresse nutzt, haben wir ihm einen Bestätigungslink gesendet.");
+pub static ACCOUNTS_MERGED: ApiMessage = message(
+    "accounts_merged",
+    "Accounts merged",
+    "Cuentas unidas",
+    "Konten zusammengeführt",
+);
+pub static ANNOUNCEMENT_CREATED: ApiMessage = message(
+    "announcement_created",
+    "Announcement created",
+    "Anuncio creado",
+    "Ankündigung erstellt",
+);
@@ -0,0 +1,271 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, PgPool};
+use tower_sessions::Session;
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::extractors::{AuthUser, VerifiedUser};
+use crate::i18n;
+use crate::validation::{Validate, ValidatedJson, ValidationErrors};
+
+// No 0/O or 1/I, so codes read out or copied by hand still work
+const CODE_ALPHABET: &[u8] = b"23456789ABCDEFGHJKLMNPQRSTUVWXYZ";
+const CODE_LENGTH: usize = 8;
+// Limits for everyone but admins, who hand out codes for launches
+const MAX_ACTIVE_INVITES: i64 = 5;
+const MAX_USES: i32 = 10;
+const ADMIN_MAX_USES: i32 = 10_000;
+const MAX_EXPIRY_DAYS: i64 = 365;
+// Where /auth/google?invite= and /auth/github?invite= keep the code for the callback
+const SESSION_KEY: &str = "invite_code";
+
+/// An invite code as its creator sees it
+#[derive(Serialize)]
+pub struct Invite {
+    pub id: Uuid,
+    pub code: String,
+    pub max_uses: i32,
+    pub uses: i32,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Someone who signed up with one of the user's codes
+#[derive(Serialize)]
+pub struct Referral {
+    pub username: String,
+    pub display_name: String,
+    pub avatar_url: Option<String>,
+    pub code: Option<String>,
+    pub joined_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateInviteRequest {
+    /// How many signups the code allows, 1 by default
+    pub max_uses: Option<i32>,
+    /// None for a code that doesn't expire
+    pub expires_in_days: Option<i64>,
+}
+
+// The cap on max_uses depends on who asks, so it's checked in the handler
+impl Validate for CreateInviteRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        if let Some(max_uses) = self.max_uses {
+            errors.range("max_uses", max_uses.into(), 1, ADMIN_MAX_USES.into());
+        }
+        if let Some(days) = self.expires_in_days {
+            errors.range("expires_in_days", days, 1, MAX_EXPIRY_DAYS);
+        }
+        errors.into_result()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct InviteQuery {
+    pub invite: Option<String>,
+}
+
+fn generate_code() -> String {
+    (0..CODE_LENGTH)
+        .map(|_| CODE_ALPHABET[rand::random::<usize>() % CODE_ALPHABET.len()] as char)
+        .collect()
+}
+
+/// Codes are case-insensitive and forgiving of stray spaces
+fn normalize_code(code: &str) -> String {
+    code.trim().to_uppercase()
+}
+
+/// Create an invite code. Verified users can have a few unused ones at a time for
+/// up to 10 signups each; admins can make codes for many more.
+pub async fn create(
+    State(pool): State<PgPool>,
+    VerifiedUser(user): VerifiedUser,
+    ValidatedJson(payload): ValidatedJson<CreateInviteRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let max_uses = payload.max_uses.unwrap_or(1);
+    let is_admin = user.role == "admin";
+    if !is_admin {
+        if max_uses > MAX_USES {
+            return Err(
+                ApiError::new(StatusCode::BAD_REQUEST, &i18n::INVITE_MAX_USES).arg("max", MAX_USES),
+            );
+        }
+        let active = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) as "count!" FROM invite_codes
+            WHERE created_by = $1 AND revoked_at IS NULL AND uses < max_uses
+              AND (expires_at IS NULL OR expires_at > NOW())
+            "#,
+            user.id
+        )
+        .fetch_one(&pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        if active >= MAX_ACTIVE_INVITES {
+            return Err(
+                ApiError::new(StatusCode::TOO_MANY_REQUESTS, &i18n::TOO_MANY_INVITES)
+                    .arg("max", MAX_ACTIVE_INVITES),
+            );
+        }
+    }
+
+    let expires_at = payload
+        .expires_in_days
+        .map(|days| Utc::now() + chrono::Duration::days(days));
+    let invite = sqlx::query_as!(
+        Invite,
+        r#"
+        INSERT INTO invite_codes (code, created_by, max_uses, expires_at)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id, code, max_uses, uses, expires_at, revoked_at, created_at
+        "#,
+        generate_code(),
+        user.id,
+        max_uses,
+        expires_at
+    )
+    .fetch_one(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok((StatusCode::CREATED, Json(invite)))
+}
+
+/// The user's invite codes, and who signed up with them
+pub async fn list(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+) -> Result<impl IntoResponse, ApiError> {
+    let invites = sqlx::query_as!(
+        Invite,
+        r#"
+        SELECT id, code, max_uses, uses, expires_at, revoked_at, created_at
+        FROM invite_codes
+        WHERE created_by = $1
+        ORDER BY created_at DESC
+        "#,
+        user.id
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let referrals = sqlx::query_as!(
+        Referral,
+        r#"
+        SELECT u.username, u.display_name, u.avatar_url, i.code as "code?",
+            u.created_at as "joined_at!"
+        FROM users u
+        LEFT JOIN invite_codes i ON i.id = u.invite_code_id
+        WHERE u.invited_by = $1 AND u.banned_at IS NULL
+        ORDER BY u.created_at DESC
+        "#,
+        user.id
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let available: i64 = invites
+        .iter()
+        .filter(|i| {
+            i.revoked_at.is_none()
+                && i.uses < i.max_uses
+                && i.expires_at.is_none_or(|at| at > Utc::now())
+        })
+        .map(|i| i64::from(i.max_uses - i.uses))
+        .sum();
+
+    Ok(Json(serde_json::json!({
+        "referral_count": referrals.len(),
+        "available_uses": available,
+        "invites": invites,
+        "referrals": referrals,
+    })))
+}
+
+/// Stop a code from being used again; signups already made with it keep their referral
+pub async fn revoke(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+    Path(invite_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let revoked = sqlx::query!(
+        r#"
+        UPDATE invite_codes SET revoked_at = COALESCE(revoked_at, NOW())
+        WHERE id = $1 AND created_by = $2
+        "#,
+        invite_id,
+        user.id
+    )
+    .execute(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    if revoked.rows_affected() == 0 {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            &i18n::INVITE_NOT_FOUND,
+        ));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Use up one signup from `code` for the new user and record who invited them. False when
+/// the code doesn't exist, is used up, expired or revoked. Run it in the signup transaction,
+/// so a failed signup doesn't spend the code.
+pub async fn redeem(
+    conn: &mut PgConnection,
+    code: &str,
+    user_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let invite = sqlx::query!(
+        r#"
+        UPDATE invite_codes SET uses = uses + 1
+        WHERE code = $1 AND uses < max_uses AND revoked_at IS NULL
+          AND (expires_at IS NULL OR expires_at > NOW())
+        RETURNING id, created_by
+        "#,
+        normalize_code(code)
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+    let Some(invite) = invite else {
+        return Ok(false);
+    };
+
+    sqlx::query!(
+        "UPDATE users SET invited_by = $2, invite_code_id = $3 WHERE id = $1",
+        user_id,
+        invite.created_by,
+        invite.id
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(true)
+}
+
+/// Keep `?invite=` from the start of an OAuth login, for `take_from_session` in the callback
+pub async fn save_to_session(session: &Session, query: InviteQuery) {
+    let Some(code) = query.invite.filter(|code| !code.trim().is_empty()) else {
+        return;
+    };
+    if let Err(e) = session.insert(SESSION_KEY, code).await {
+        tracing::error!("Failed to keep the invite code for OAuth signup: {}", e);
+    }
+}
+
+pub async fn take_from_session(session: &Session) -> Option<String> {
+    session.remove::<String>(SESSION_KEY).await.ok().flatten()
+}
@@ -0,0 +1,189 @@
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::time::Duration;
+use tokio::sync::Notify;
+use uuid::Uuid;
+
+use crate::email::Message;
+use crate::state::AppState;
+
+// Jobs claimed per query
+const BATCH_SIZE: i64 = 10;
+// Fallback for jobs enqueued by other instances, or whose retry came due
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+// First retry after 30s, then doubling: 30s, 1m, 2m, 4m
+const RETRY_BASE_SECONDS: f64 = 30.0;
+
+/// Wakes the worker as soon as something is enqueued in this process
+static WAKE: Notify = Notify::const_new();
+
+/// Work done outside the request, stored in the jobs table until it succeeds
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Job {
+    SendEmail {
+        /// None for emails queued before email_log existed
+        log_id: Option<Uuid>,
+        #[serde(flatten)]
+        message: Message,
+    },
+}
+
+impl Job {
+    fn kind(&self) -> &'static str {
+        match self {
+            Job::SendEmail { .. } => "send_email",
+        }
+    }
+
+    async fn run(self, state: &AppState, last_attempt: bool) -> Result<(), String> {
+        match self {
+            Job::SendEmail { log_id, message } => {
+                let result = state.email_sender.send(&message).await;
+                if let Some(log_id) = log_id {
+                    let provider = state.email_sender.name();
+                    crate::email_log::record_attempt(
+                        &state.pool,
+                        log_id,
+                        provider,
+                        &result,
+                        last_attempt,
+                    )
+                    .await;
+                }
+                result.map(|_| ())
+            }
+        }
+    }
+}
+
+/// Queue a job to run as soon as possible
+pub async fn enqueue(pool: &PgPool, job: &Job) -> Result<Uuid, sqlx::Error> {
+    let payload = serde_json::to_value(job).expect("jobs always serialize");
+    let id = sqlx::query_scalar!(
+        "INSERT INTO jobs (kind, payload) VALUES ($1, $2) RETURNING id",
+        job.kind(),
+        payload
+    )
+    .fetch_one(pool)
+    .await?;
+
+    WAKE.notify_one();
+    Ok(id)
+}
+
+/// Run jobs as they come in, until the process exits
+pub fn spawn_worker(state: AppState) {
+    tokio::spawn(async move {
+        loop {
+            match run_due(&state).await {
+                // A full batch probably means more are waiting
+                Ok(n) if n as i64 == BATCH_SIZE => continue,
+                Ok(_) => {}
+                Err(e) => tracing::error!("Job worker failed to fetch jobs: {}", e),
+            }
+
+            tokio::select! {
+                _ = WAKE.notified() => {}
+                _ = tokio::time::sleep(POLL_INTERVAL) => {}
+            }
+        }
+    });
+}
+
+/// Claim and run one batch of due jobs. Returns how many were run (successfully or not).
+pub async fn run_due(state: &AppState) -> Result<usize, sqlx::Error> {
+    // SKIP LOCKED lets several instances share the queue without running a job twice
+    let claimed = sqlx::query!(
+        r#"
+        UPDATE jobs
+        SET attempts = attempts + 1, locked_until = NOW() + INTERVAL '5 minutes'
+        WHERE id IN (
+            SELECT id FROM jobs
+            WHERE completed_at IS NULL AND failed_at IS NULL
+              AND run_at <= NOW()
+              AND (locked_until IS NULL OR locked_until < NOW())
+            ORDER BY run_at
+            LIMIT $1
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING id, kind, payload, attempts, max_attempts
+        "#,
+        BATCH_SIZE
+    )
+    .fetch_all(&state.pool)
+    .await?;
+
+    let count = claimed.len();
+    for job in claimed {
+        let result = match serde_json::from_value::<Job>(job.payload) {
+            Ok(payload) => payload.run(state, job.attempts >= job.max_attempts).await,
+            Err(e) => Err(format!("Unreadable {} job: {}", job.kind, e)),
+        };
+
+        match result {
+            Ok(()) => {
+                sqlx::query!(
+                    "UPDATE jobs SET completed_at = NOW(), locked_until = NULL WHERE id = $1",
+                    job.id
+                )
+                .execute(&state.pool)
+                .await?;
+            }
+            Err(e) if job.attempts >= job.max_attempts => {
+                tracing::error!(
+                    "Job {} ({}) failed for good after {} attempts: {}",
+                    job.id,
+                    job.kind,
+                    job.attempts,
+                    e
+                );
+                sqlx::query!(
+                    "UPDATE jobs SET failed_at = NOW(), locked_until = NULL, last_error = $2 WHERE id = $1",
+                    job.id,
+                    e
+                )
+                .execute(&state.pool)
+                .await?;
+            }
+            Err(e) => {
+                let delay = RETRY_BASE_SECONDS * 2f64.powi(job.attempts - 1);
+                tracing::warn!(
+                    "Job {} ({}) failed, retrying in {}s: {}",
+                    job.id,
+                    job.kind,
+                    delay,
+                    e
+                );
+                sqlx::query!(
+                    r#"
+                    UPDATE jobs
+                    SET run_at = NOW() + make_interval(secs => $2), locked_until = NULL, last_error = $3
+                    WHERE id = $1
+                    "#,
+                    job.id,
+                    delay,
+                    e
+                )
+                .execute(&state.pool)
+                .await?;
+            }
+        }
+    }
+
+    Ok(count)
+}
+
+/// Delete jobs that finished (either way) over a week ago. Returns the number removed.
+pub async fn purge_finished(pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM jobs
+        WHERE COALESCE(completed_at, failed_at) < NOW() - INTERVAL '7 days'
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
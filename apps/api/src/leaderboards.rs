@@ -0,0 +1,165 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+use crate::db::DbRouter;
+use crate::error::ApiError;
+use crate::reputation::{ACCEPTED_APPLICATION_POINTS, COMPLETED_PROJECT_POINTS, REACTION_POINTS};
+
+const PERIODS: &[&str] = &["week", "month", "all"];
+const METRICS: &[&str] = &["posts", "reputation", "projects"];
+// Places shown per leaderboard. A few more are stored, so people opting out between
+// refreshes don't leave it short.
+const LEADERBOARD_SIZE: i64 = 100;
+const STORED_ENTRIES: i64 = 150;
+
+#[derive(Deserialize)]
+pub struct LeaderboardQuery {
+    /// week (the default), month or all
+    pub period: Option<String>,
+    /// reputation (the default), posts or projects
+    pub metric: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct LeaderboardEntry {
+    pub rank: i64,
+    pub username: String,
+    pub display_name: String,
+    pub avatar_url: Option<String>,
+    pub score: i64,
+}
+
+/// Rebuild every leaderboard: posts and projects created, and reputation earned, in the
+/// past 7 days, 30 days and all time. All time reputation is the score on profiles.
+/// Banned users and those who opted out are left off.
+pub async fn refresh(pool: &PgPool) -> Result<u64, String> {
+    let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+    sqlx::query!("DELETE FROM leaderboard_entries")
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let inserted = sqlx::query!(
+        r#"
+        WITH periods (period, since) AS (
+            VALUES ('week', NOW() - INTERVAL '7 days'),
+                   ('month', NOW() - INTERVAL '30 days'),
+                   ('all', '-infinity'::timestamptz)
+        ), earned (user_id, points, at) AS (
+            SELECT m.sender_id, $2::bigint, MIN(r.created_at)
+            FROM message_reactions r
+            JOIN messages m ON m.id = r.message_id
+            JOIN users reactor ON reactor.id = r.user_id
+            WHERE r.user_id <> m.sender_id AND reactor.banned_at IS NULL
+            GROUP BY m.sender_id, r.message_id, r.user_id
+            UNION ALL
+            SELECT applicant_id, $3::bigint, reviewed_at FROM applications
+            WHERE status = 'accepted'
+            UNION ALL
+            SELECT owner_id, $4::bigint, completed_at FROM projects
+            WHERE status = 'completed'
+        ), scores (period, metric, user_id, score) AS (
+            SELECT p.period, 'posts', po.author_id, COUNT(*)
+            FROM periods p JOIN posts po ON po.created_at >= p.since AND po.held_at IS NULL
+            GROUP BY p.period, po.author_id
+            UNION ALL
+            SELECT p.period, 'projects', pr.owner_id, COUNT(*)
+            FROM periods p JOIN projects pr ON pr.created_at >= p.since AND pr.held_at IS NULL
+            GROUP BY p.period, pr.owner_id
+            UNION ALL
+            SELECT p.period, 'reputation', e.user_id, SUM(e.points)::bigint
+            FROM periods p JOIN earned e ON e.at >= p.since
+            WHERE p.period <> 'all'
+            GROUP BY p.period, e.user_id
+            UNION ALL
+            SELECT 'all', 'reputation', id, reputation FROM users
+        ), ranked AS (
+            SELECT s.period, s.metric, s.user_id, s.score,
+                ROW_NUMBER() OVER (
+                    PARTITION BY s.period, s.metric ORDER BY s.score DESC, u.created_at
+                ) AS rank
+            FROM scores s
+            JOIN users u ON u.id = s.user_id
+            WHERE s.score > 0 AND u.banned_at IS NULL AND NOT u.leaderboard_opt_out
+        )
+        INSERT INTO leaderboard_entries (period, metric, user_id, score)
+        SELECT period, metric, user_id, score FROM ranked WHERE rank <= $1
+        "#,
+        STORED_ENTRIES,
+        i64::from(REACTION_POINTS),
+        i64::from(ACCEPTED_APPLICATION_POINTS),
+        i64::from(COMPLETED_PROJECT_POINTS)
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    tx.commit().await.map_err(|e| e.to_string())?;
+    Ok(inserted.rows_affected())
+}
+
+/// The top 100 for a period and metric, as of the last refresh (every 15 minutes)
+pub async fn get(
+    State(db): State<DbRouter>,
+    Query(query): Query<LeaderboardQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let period = query.period.as_deref().unwrap_or("week");
+    let metric = query.metric.as_deref().unwrap_or("reputation");
+    if !PERIODS.contains(&period) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "period must be week, month or all".to_string(),
+        )
+            .into());
+    }
+    if !METRICS.contains(&metric) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "metric must be posts, reputation or projects".to_string(),
+        )
+            .into());
+    }
+
+    // Ranked again here, so opting out or being banned takes effect straight away
+    let entries = sqlx::query_as!(
+        LeaderboardEntry,
+        r#"
+        SELECT ROW_NUMBER() OVER (ORDER BY e.score DESC, u.created_at) AS "rank!",
+            u.username, u.display_name, u.avatar_url, e.score
+        FROM leaderboard_entries e
+        JOIN users u ON u.id = e.user_id
+        WHERE e.period = $1 AND e.metric = $2
+          AND u.banned_at IS NULL AND NOT u.leaderboard_opt_out
+        ORDER BY e.score DESC, u.created_at
+        LIMIT $3
+        "#,
+        period,
+        metric,
+        LEADERBOARD_SIZE
+    )
+    .fetch_all(db.read())
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let refreshed_at = sqlx::query_scalar!(
+        "SELECT MAX(refreshed_at) FROM leaderboard_entries WHERE period = $1 AND metric = $2",
+        period,
+        metric
+    )
+    .fetch_one(db.read())
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(serde_json::json!({
+        "period": period,
+        "metric": metric,
+        "refreshed_at": refreshed_at,
+        "entries": entries,
+    })))
+}
@@ -0,0 +1,568 @@
+use axum::{
+    extract::DefaultBodyLimit,
+    http::{header, Method},
+    middleware,
+    routing::{delete, get, patch, post},
+    Router,
+};
+use sqlx::{migrate::MigrateError, PgPool};
+use state::AppState;
+use time::Duration;
+use tower_http::{compression::CompressionLayer, cors::CorsLayer, trace::TraceLayer};
+use tower_sessions::{cookie::SameSite, Expiry, SessionManagerLayer};
+use tower_sessions_sqlx_store::PostgresStore;
+
+pub mod admin;
+pub mod analytics;
+mod announcements;
+mod api_keys;
+mod applications;
+mod audit;
+pub mod auth;
+mod batch;
+pub mod broadcasts;
+pub mod cache;
+pub mod client_ip;
+pub mod db;
+pub mod demo;
+pub mod digest;
+pub mod email;
+mod email_log;
+mod error;
+pub mod email_preferences;
+mod endorsements;
+mod etag;
+pub mod events;
+mod explore;
+mod export;
+mod extractors;
+mod feed;
+pub mod geoip;
+mod i18n;
+mod invites;
+pub mod jobs;
+pub mod leaderboards;
+mod listings;
+mod mentions;
+mod merge;
+mod message_reports;
+mod meta;
+pub mod messages;
+mod milestones;
+mod moderation;
+pub mod notifications;
+mod oembed;
+mod passkey;
+mod posts;
+mod profile_history;
+mod project_templates;
+mod projects;
+mod quotas;
+pub mod r2;
+mod rate_limit;
+mod relationships;
+pub mod retention;
+pub mod reputation;
+pub mod saved_searches;
+pub mod scheduler;
+mod search;
+pub mod search_index;
+pub mod session;
+pub mod session_store;
+mod settings;
+mod spam;
+pub mod state;
+mod stats;
+mod strikes;
+mod totp;
+mod upload;
+pub mod user;
+pub mod validation;
+mod waitlist;
+pub mod verification_reminders;
+pub mod ws;
+
+/// Apply pending migrations (ours and the session store's)
+pub async fn migrate(pool: &PgPool) -> Result<(), MigrateError> {
+    run_migrations(pool, true).await
+}
+
+/// `migrate` without the advisory lock that stops two instances migrating at once, which
+/// a transaction mode pooler (DATABASE_PGBOUNCER) can't hold between statements
+pub async fn migrate_unlocked(pool: &PgPool) -> Result<(), MigrateError> {
+    run_migrations(pool, false).await
+}
+
+async fn run_migrations(pool: &PgPool, locking: bool) -> Result<(), MigrateError> {
+    let mut migrator = sqlx::migrate!("./migrations");
+    migrator.set_locking(locking);
+    // A few early migrations are versioned by date only (20260126_...), which sorts them
+    // before full timestamps like 20260105072829 and breaks a fresh database.
+    // Pad them out so migrations always run in the order they were written.
+    migrator
+        .migrations
+        .to_mut()
+        .sort_by_key(|m| format!("{:0<14}", m.version));
+    migrator.run(pool).await?;
+
+    PostgresStore::new(pool.clone()).migrate().await?;
+    Ok(())
+}
+
+/// The full API: every route plus the session, rate limit, CORS and tracing layers.
+/// Serve with `into_make_service_with_connect_info::<SocketAddr>()`.
+pub fn app(state: AppState) -> Router {
+    // --- Setup Session --- //
+    let session_store = state.sessions.clone();
+
+    // Secure cookie setting: Use true in production (requires HTTPS), false in dev
+    let is_production = state.config.is_production;
+
+    // If we use SameSite::None, we MUST use Secure=true, otherwise browsers reject it.
+    // So we force secure=true in production.
+    let secure_cookies = is_production;
+    let same_site = if is_production {
+        SameSite::None
+    } else {
+        SameSite::Lax
+    };
+
+    let session_layer = SessionManagerLayer::new(session_store)
+        .with_secure(secure_cookies)
+        .with_same_site(same_site)
+        .with_expiry(Expiry::OnInactivity(Duration::days(1)));
+
+    // CORS Setup: Allow Frontend URL(s)
+    let frontend_urls: Vec<_> = state
+        .config
+        .frontend_origins
+        .iter()
+        .map(|url| {
+            url.parse::<axum::http::HeaderValue>()
+                .expect("Invalid FRONTEND_URL")
+        })
+        .collect();
+
+    let cors = CorsLayer::new()
+        .allow_origin(frontend_urls)
+        .allow_methods([
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::PATCH,
+            Method::DELETE,
+        ])
+        .allow_headers([header::CONTENT_TYPE, header::AUTHORIZATION, header::ACCEPT])
+        .allow_credentials(true);
+
+    // --- Rate Limiting --- //
+    let auth_limit = rate_limit::RateLimit::auth();
+    let global_limit = rate_limit::RateLimit::global();
+    let public_api_limit = rate_limit::RateLimit::public_api();
+    tokio::spawn(rate_limit::run_cleanup(vec![
+        auth_limit.clone(),
+        global_limit.clone(),
+        public_api_limit.clone(),
+    ]));
+
+    // Global body limit: 10MB, or enough for the largest image upload plus multipart overhead
+    let body_limit = (upload::max_image_upload_bytes() + 64 * 1024).max(10 * 1024 * 1024);
+
+    // create empty web app and run mapped fns if routes are visited
+    // Credential endpoints get a strict rate limit on top of the global one
+    let auth_routes = Router::new()
+        .route("/auth/signup", post(auth::signup))
+        .route("/auth/waitlist", post(waitlist::join))
+        .route("/auth/login", post(auth::login))
+        .route("/auth/verify-email", post(auth::verify_email))
+        .route("/auth/resend-verification", post(auth::resend_verification))
+        .route("/auth/change-password", post(auth::change_password))
+        .route("/auth/set-password", post(auth::set_password))
+        .route("/auth/forgot-password", post(auth::forgot_password))
+        .route("/auth/reset-password", post(auth::reset_password))
+        .route("/auth/merge", post(merge::request_merge))
+        .route("/auth/merge/confirm", post(merge::confirm))
+        .route(
+            "/auth/passkey/auth/start",
+            post(passkey::start_authentication),
+        )
+        .route(
+            "/auth/passkey/auth/finish",
+            post(passkey::finish_authentication),
+        )
+        .route("/auth/totp/verify", post(totp::verify_totp))
+        .route_layer(middleware::from_fn_with_state(
+            auth_limit.clone(),
+            rate_limit::limit,
+        ));
+
+    // Large lists get an ETag so clients can revalidate them with If-None-Match
+    let list_routes = Router::new()
+        .route("/admin/users", get(admin::list_users))
+        .route("/user/all", get(user::get_all))
+        .route("/user/directory", get(user::directory))
+        .route("/user/:username/projects", get(user::list_projects))
+        .route("/announcements", get(announcements::get_all))
+        .route("/posts", get(posts::list))
+        .route("/posts/user/:username", get(posts::list_by_user))
+        .route("/projects", get(projects::list))
+        .route("/search", get(search::search))
+        .route("/explore", get(explore::get_explore))
+        .route("/leaderboard", get(leaderboards::get))
+        .route("/events", get(events::list))
+        .route("/listings", get(listings::list))
+        .route_layer(middleware::from_fn(etag::etag));
+
+    // Pages anyone can see, which browsers and CDNs may cache when fetched without a session
+    let public_routes = Router::new()
+        .route("/user/profile/:username", get(user::get_public_profile))
+        .route("/projects/user/:username/:slug", get(projects::get_by_slug))
+        .route("/feed", get(feed::get_feed))
+        .route_layer(middleware::from_fn(etag::public));
+
+    // Read-only API for bots and stats sites, authenticated and rate limited per API key
+    let public_api_routes = Router::new()
+        .route("/public/v1/projects", get(projects::list))
+        .route(
+            "/public/v1/projects/:username/:slug",
+            get(projects::get_by_slug),
+        )
+        .route("/public/v1/posts", get(posts::list))
+        .route("/public/v1/posts/user/:username", get(posts::list_by_user))
+        .route("/public/v1/users/:username", get(user::get_public_profile))
+        .route_layer(middleware::from_fn_with_state(
+            public_api_limit,
+            rate_limit::limit,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            api_keys::require_key,
+        ));
+
+    Router::new()
+        .route("/", get(root))
+        .merge(auth_routes)
+        .merge(list_routes)
+        .merge(public_routes)
+        .merge(public_api_routes)
+        // OAuth
+        .route("/auth/google", get(auth::google_login))
+        .route("/auth/google/callback", get(auth::google_callback))
+        .route("/auth/github", get(auth::github_login))
+        .route("/auth/github/callback", get(auth::github_callback))
+        .route("/auth/logout", post(auth::logout))
+        // Linked Accounts
+        .route("/auth/linked-accounts", get(auth::list_linked_accounts))
+        .route(
+            "/auth/linked-accounts/:provider",
+            delete(auth::unlink_account),
+        )
+        // Admin Routes
+        .route(
+            "/admin/users/:id/reset-password",
+            post(admin::reset_user_password),
+        )
+        .route("/admin/users/:id/role", patch(admin::update_user_role))
+        .route("/admin/users/:id/ban", post(admin::ban_user))
+        .route("/admin/users/:id/emails", get(email_log::list_for_user))
+        .route(
+            "/admin/users/:id/sessions",
+            get(session::list_for_user).delete(session::revoke_for_user),
+        )
+        .route("/admin/emails/:id/resend", post(email_log::resend))
+        .route("/admin/users/:id/suspend", post(admin::suspend_user))
+        .route("/admin/users/:id/unban", post(admin::unban_user))
+        .route(
+            "/admin/users/:id/profile/history",
+            get(profile_history::list_for_user),
+        )
+        .route(
+            "/admin/users/:id/profile/history/:revision_id/revert",
+            post(profile_history::revert_for_user),
+        )
+        .route("/admin/audit-log", get(audit::list))
+        .route("/admin/waitlist", get(waitlist::list))
+        .route("/admin/waitlist/approve", post(waitlist::approve))
+        .route(
+            "/admin/api-keys",
+            get(api_keys::list).post(api_keys::create),
+        )
+        .route("/admin/api-keys/:id", delete(api_keys::revoke))
+        .route(
+            "/admin/notifications/broadcasts",
+            get(broadcasts::list).post(broadcasts::create),
+        )
+        .route(
+            "/admin/notifications/broadcasts/:id",
+            get(broadcasts::get).delete(broadcasts::cancel),
+        )
+        .route("/admin/message-reports", get(message_reports::list))
+        .route("/admin/message-reports/:id", get(message_reports::get))
+        .route(
+            "/admin/message-reports/:id/resolve",
+            post(message_reports::resolve),
+        )
+        .route("/admin/stats", get(stats::get_stats))
+        .route("/admin/analytics", get(analytics::get_report))
+        .route("/admin/db/pool", get(db::pool_stats))
+        .route(
+            "/admin/retention",
+            get(retention::list).patch(retention::update),
+        )
+        .route(
+            "/admin/settings",
+            get(settings::get_admin).patch(settings::update),
+        )
+        .route("/admin/project-templates", post(project_templates::create))
+        .route(
+            "/admin/project-templates/:id",
+            patch(project_templates::update).delete(project_templates::delete),
+        )
+        .route("/site-settings", get(settings::get_public))
+        .route("/oembed", get(oembed::get_oembed))
+        .route("/meta", get(meta::get_meta))
+        .route("/search/suggest", get(search::suggest))
+        .route(
+            "/search/saved",
+            get(saved_searches::list).post(saved_searches::create),
+        )
+        .route("/search/saved/:id", delete(saved_searches::delete))
+        .route("/admin/uploads/purge", post(admin::purge_object))
+        .route(
+            "/admin/uploads/quarantine",
+            get(admin::list_quarantined_uploads),
+        )
+        .route("/admin/uploads/:id/review", post(admin::review_upload))
+        .route("/admin/held", get(spam::list_held))
+        .route("/admin/held/:kind/:id/review", post(spam::review_held))
+        .route(
+            "/admin/security-analytics",
+            get(admin::get_security_analytics),
+        )
+        // Session Management
+        .route(
+            "/auth/sessions",
+            get(session::list_sessions).delete(session::revoke_all_other_sessions),
+        )
+        .route("/auth/sessions/:id", delete(session::revoke_session))
+        .route("/user/me", get(user::get_me))
+        .route(
+            "/user/email-preferences",
+            get(email_preferences::get_preferences).patch(email_preferences::update_preferences),
+        )
+        .route(
+            "/email/unsubscribe",
+            get(email_preferences::unsubscribe).post(email_preferences::unsubscribe),
+        )
+        .route("/dev/mailbox", get(email::dev_mailbox))
+        .route("/ws", get(ws::connect))
+        .route("/notifications", get(notifications::list))
+        .route(
+            "/notifications/unread-count",
+            get(notifications::unread_count),
+        )
+        .route(
+            "/notifications/preferences",
+            get(notifications::get_preferences).patch(notifications::update_preferences),
+        )
+        .route(
+            "/notifications/read-all",
+            post(notifications::mark_all_read),
+        )
+        .route("/notifications/:id", delete(notifications::delete))
+        .route("/notifications/:id/read", post(notifications::mark_read))
+        .route(
+            "/messages",
+            get(messages::list_conversations).post(messages::send),
+        )
+        .route("/messages/requests", get(messages::list_requests))
+        .route(
+            "/messages/settings",
+            get(messages::get_settings).patch(messages::update_settings),
+        )
+        .route("/messages/search", get(messages::search_all))
+        .route("/messages/conversations/:id", get(messages::get_messages))
+        .route(
+            "/messages/conversations/:id/search",
+            get(messages::search_conversation),
+        )
+        .route(
+            "/messages/conversations/:id/mute",
+            post(messages::mute).delete(messages::unmute),
+        )
+        .route(
+            "/messages/conversations/:id/archive",
+            post(messages::archive).delete(messages::unarchive),
+        )
+        .route("/messages/:id", delete(messages::delete_for_me))
+        .route("/messages/:id/unsend", post(messages::unsend))
+        .route("/messages/:id/reactions", post(messages::react))
+        .route("/messages/:id/report", post(message_reports::report))
+        .route(
+            "/messages/:id/reactions/:emoji",
+            delete(messages::unreact),
+        )
+        .route("/messages/conversations/:id/accept", post(messages::accept))
+        .route(
+            "/messages/conversations/:id/decline",
+            post(messages::decline),
+        )
+        .route("/user/blocks", get(relationships::list_blocks))
+        .route(
+            "/user/:username/follow",
+            post(relationships::follow).delete(relationships::unfollow),
+        )
+        .route("/user/:username/endorsements", post(endorsements::endorse))
+        .route(
+            "/user/:username/endorsements/:skill",
+            delete(endorsements::unendorse),
+        )
+        .route(
+            "/user/:username/block",
+            post(relationships::block).delete(relationships::unblock),
+        )
+        .route("/user/profile", post(user::update_profile))
+        .route(
+            "/user/me/invites",
+            get(invites::list).post(invites::create),
+        )
+        .route("/user/me/invites/:id", delete(invites::revoke))
+        .route("/user/me/strikes", get(strikes::list_mine))
+        .route("/user/me/strikes/:id/appeal", post(strikes::appeal))
+        .route("/user/me/profile/history", get(profile_history::list_mine))
+        .route(
+            "/user/me/profile/history/:id/revert",
+            post(profile_history::revert_mine),
+        )
+        .route("/user/batch", post(user::batch))
+        .route("/user/test", post(user::create_test_user))
+        .route("/user/:id", axum::routing::delete(user::delete_user))
+        // Size limits are enforced per category in upload.rs, under the global body limit
+        .route(
+            "/upload",
+            post(upload::upload_image).layer(DefaultBodyLimit::disable()),
+        )
+        .route("/upload/presign", post(upload::presign_upload))
+        .route("/upload/:id/confirm", post(upload::confirm_upload))
+        .route("/upload/:id", get(upload::get_upload))
+        .route("/geoip/:ip", get(geoip::get_geoip))
+        .route("/analytics/events", post(analytics::ingest))
+        .route("/announcement", get(announcements::get_latest))
+        .route("/announcement", post(announcements::create))
+        .route("/announcement/banner", get(announcements::get_banner))
+        .route("/announcements/recent", get(announcements::get_recent))
+        .route("/announcements/count", get(announcements::get_count))
+        .route("/announcements/:id/reactions", post(announcements::react))
+        .route("/posts", post(posts::create))
+        .route("/posts/batch", post(posts::batch))
+        .route("/posts/:id", delete(posts::delete))
+        .route("/projects/recommended", get(projects::recommended))
+        .route("/projects", post(projects::create))
+        .route("/projects/:id", delete(projects::delete))
+        .route("/projects/:id/status", patch(projects::set_status))
+        .route("/project-templates", get(project_templates::list))
+        .route(
+            "/projects/from-template/:template_id",
+            post(project_templates::create_project),
+        )
+        .route("/projects/:id/milestones", get(milestones::list))
+        .route(
+            "/projects/:id/milestones/:milestone_id",
+            patch(milestones::update),
+        )
+        .route("/projects/:id/apply", post(applications::apply))
+        .route(
+            "/projects/:id/applications/:application_id",
+            patch(applications::review),
+        )
+        .route("/events", post(events::create))
+        .route(
+            "/events/:id",
+            get(events::get).patch(events::update).delete(events::delete),
+        )
+        .route(
+            "/events/:id/rsvp",
+            post(events::rsvp).delete(events::cancel_rsvp),
+        )
+        .route("/events/:id/attendees", get(events::attendees))
+        .route("/listings", post(listings::create))
+        .route(
+            "/listings/:id",
+            get(listings::get)
+                .patch(listings::update)
+                .delete(listings::delete),
+        )
+        .route("/listings/:id/apply", post(applications::apply_to_listing))
+        .route(
+            "/listings/:id/applications",
+            get(applications::list_for_listing),
+        )
+        .route(
+            "/listings/:id/applications/:application_id",
+            patch(applications::review_for_listing),
+        )
+        // Passkeys
+        .route(
+            "/auth/passkey/register/start",
+            post(passkey::start_registration),
+        )
+        .route(
+            "/auth/passkey/register/finish",
+            post(passkey::finish_registration),
+        )
+        .route("/auth/passkey/list", get(passkey::list_passkeys))
+        .route("/auth/passkey/:id", delete(passkey::delete_passkey))
+        // TOTP 2FA
+        .route("/auth/totp/setup", post(totp::setup_totp))
+        .route("/auth/totp/enable", post(totp::enable_totp))
+        .route("/auth/totp/disable", post(totp::disable_totp))
+        .route("/auth/totp/status", get(totp::get_totp_status))
+        .route(
+            "/auth/totp/backup-codes",
+            post(totp::regenerate_backup_codes),
+        )
+        // Images are now served directly from Cloudflare R2
+        // Runs inside the session layer so logged in users are limited by user id
+        .layer(middleware::from_fn_with_state(
+            global_limit.clone(),
+            rate_limit::limit,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            session::track_activity,
+        ))
+        // Outside the rate limits and session tracking, which key on the client's address
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            client_ip::resolve,
+        ))
+        // Sees every handler's errors before they're compressed
+        .layer(middleware::from_fn_with_state(state.clone(), error::sanitize))
+        // Outside sanitize, so production server errors get a code too
+        .layer(middleware::from_fn_with_state(state.clone(), error::localize))
+        .layer(session_layer)
+        .layer(cors)
+        .layer(CompressionLayer::new())
+        .layer(tower_http::limit::RequestBodyLimitLayer::new(body_limit))
+        .layer(TraceLayer::new_for_http().make_span_with(request_span))
+        .with_state(state)
+}
+
+/// Names the route (e.g. "/user/profile/:username") so anything logged while handling the
+/// request, like sqlx's slow query warnings, says which endpoint it came from
+fn request_span(request: &axum::http::Request<axum::body::Body>) -> tracing::Span {
+    let route = request
+        .extensions()
+        .get::<axum::extract::MatchedPath>()
+        .map(|path| path.as_str());
+    tracing::info_span!(
+        "request",
+        method = %request.method(),
+        route,
+        uri = %request.uri(),
+    )
+}
+
+async fn root() -> &'static str {
+    "Hey, it's Praxis API!!!!"
+}
@@ -0,0 +1,395 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tower_sessions::Session;
+use uuid::Uuid;
+
+use crate::db::DbRouter;
+use crate::error::ApiError;
+use crate::extractors::{AuthUser, VerifiedUser};
+use crate::i18n;
+use crate::search::TextQuery;
+use crate::state::AppState;
+use crate::validation::{
+    normalize_line, normalize_text, Validate, ValidatedJson, ValidationErrors,
+};
+
+const COMPENSATION_TYPES: &[&str] = &["paid", "volunteer"];
+const MAX_SKILLS: usize = 10;
+const PAGE_SIZE: i64 = 50;
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct Listing {
+    pub id: Uuid,
+    pub role: String,
+    pub description: Option<String>,
+    pub skills: Vec<String>,
+    /// paid or volunteer
+    pub compensation_type: String,
+    pub compensation: Option<String>,
+    pub deadline: Option<DateTime<Utc>>,
+    /// open or closed
+    pub status: String,
+    pub applicants: i64,
+    pub poster_id: Uuid,
+    pub poster_username: String,
+    pub poster_name: String,
+    pub poster_avatar: Option<String>,
+    pub project_id: Option<Uuid>,
+    pub project_title: Option<String>,
+    pub project_slug: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Filters for the board; all optional
+#[derive(Deserialize)]
+pub struct ListingQuery {
+    /// Words in the role or description, web search syntax
+    pub q: Option<String>,
+    /// Listings wanting this skill, ignoring case
+    pub skill: Option<String>,
+    pub compensation_type: Option<String>,
+    pub project_id: Option<Uuid>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateListingRequest {
+    pub role: String,
+    pub description: Option<String>,
+    pub skills: Option<Vec<String>>,
+    pub compensation_type: String,
+    pub compensation: Option<String>,
+    pub deadline: Option<DateTime<Utc>>,
+    /// Post it for one of the user's projects
+    pub project_id: Option<Uuid>,
+}
+
+/// Only the fields given change. Empty strings clear the optional text fields.
+#[derive(Deserialize)]
+pub struct UpdateListingRequest {
+    pub role: Option<String>,
+    pub description: Option<String>,
+    pub skills: Option<Vec<String>>,
+    pub compensation_type: Option<String>,
+    pub compensation: Option<String>,
+    pub deadline: Option<DateTime<Utc>>,
+    /// open, or closed to stop taking applications
+    pub status: Option<String>,
+}
+
+fn validate_details(
+    errors: &mut ValidationErrors,
+    description: Option<&str>,
+    skills: &[String],
+    compensation: Option<&str>,
+) {
+    if let Some(description) = description {
+        errors.length("description", description, 0, 5000);
+    }
+    if skills.len() > MAX_SKILLS {
+        errors.add("skills", format!("at most {} skills", MAX_SKILLS));
+    }
+    for skill in skills {
+        errors.length("skills", skill, 1, 50);
+    }
+    if let Some(compensation) = compensation {
+        errors.length("compensation", compensation, 0, 100);
+    }
+}
+
+impl Validate for CreateListingRequest {
+    fn normalize(&mut self) {
+        normalize_line(&mut self.role);
+        self.description.iter_mut().for_each(normalize_text);
+        self.skills.iter_mut().flatten().for_each(normalize_line);
+        self.compensation.iter_mut().for_each(normalize_line);
+    }
+
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.length("role", &self.role, 1, 100);
+        validate_details(
+            &mut errors,
+            self.description.as_deref(),
+            self.skills.as_deref().unwrap_or_default(),
+            self.compensation.as_deref(),
+        );
+        errors.one_of(
+            "compensation_type",
+            &self.compensation_type,
+            COMPENSATION_TYPES,
+        );
+        if self.deadline.is_some_and(|deadline| deadline <= Utc::now()) {
+            errors.add("deadline", "Must be in the future");
+        }
+        errors.into_result()
+    }
+}
+
+impl Validate for UpdateListingRequest {
+    fn normalize(&mut self) {
+        self.role.iter_mut().for_each(normalize_line);
+        self.description.iter_mut().for_each(normalize_text);
+        self.skills.iter_mut().flatten().for_each(normalize_line);
+        self.compensation.iter_mut().for_each(normalize_line);
+    }
+
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        if let Some(role) = &self.role {
+            errors.length("role", role, 1, 100);
+        }
+        validate_details(
+            &mut errors,
+            self.description.as_deref(),
+            self.skills.as_deref().unwrap_or_default(),
+            self.compensation.as_deref(),
+        );
+        if let Some(compensation_type) = &self.compensation_type {
+            errors.one_of("compensation_type", compensation_type, COMPENSATION_TYPES);
+        }
+        if let Some(status) = &self.status {
+            errors.one_of("status", status, &["open", "closed"]);
+        }
+        errors.into_result()
+    }
+}
+
+/// Open listings whose deadline hasn't passed, best matches for `q` first, then newest
+pub async fn list(
+    State(db): State<DbRouter>,
+    Query(query): Query<ListingQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let q = query
+        .q
+        .as_deref()
+        .filter(|q| !q.trim().is_empty())
+        .map(crate::search::query_text)
+        .transpose()?;
+    if let Some(compensation_type) = &query.compensation_type {
+        if !COMPENSATION_TYPES.contains(&compensation_type.as_str()) {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "compensation_type must be paid or volunteer".to_string(),
+            )
+                .into());
+        }
+    }
+
+    let text = TextQuery::bound_at(1);
+    let listings = sqlx::query_as::<_, Listing>(&format!(
+        r#"
+        SELECT l.id, l.role, l.description, l.skills, l.compensation_type, l.compensation,
+            l.deadline, l.status,
+            (SELECT COUNT(*) FROM applications a
+             WHERE a.listing_id = l.id AND a.held_at IS NULL) AS applicants,
+            l.poster_id, u.username AS poster_username, u.display_name AS poster_name,
+            u.avatar_url AS poster_avatar, l.project_id, p.title AS project_title,
+            p.slug AS project_slug, l.created_at
+        FROM listings l
+        JOIN users u ON u.id = l.poster_id
+        LEFT JOIN projects p ON p.id = l.project_id
+        WHERE l.status = 'open'
+          AND (l.deadline IS NULL OR l.deadline > NOW())
+          AND u.banned_at IS NULL
+          AND ($1::text IS NULL OR {matches})
+          AND ($2::text IS NULL OR lower($2) = ANY(SELECT lower(s) FROM unnest(l.skills) s))
+          AND ($3::text IS NULL OR l.compensation_type = $3)
+          AND ($4::uuid IS NULL OR l.project_id = $4)
+        ORDER BY COALESCE({rank}, 0) DESC, l.created_at DESC
+        LIMIT $5
+        "#,
+        matches = text.matches("l.search_vector"),
+        rank = text.rank("l.search_vector"),
+    ))
+    .bind(q)
+    .bind(&query.skill)
+    .bind(&query.compensation_type)
+    .bind(query.project_id)
+    .bind(PAGE_SIZE)
+    .fetch_all(db.read())
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(listings))
+}
+
+/// One listing, including closed and expired ones
+pub async fn get(
+    State(db): State<DbRouter>,
+    Path(listing_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let listing = sqlx::query_as!(
+        Listing,
+        r#"
+        SELECT l.id, l.role, l.description, l.skills, l.compensation_type, l.compensation,
+            l.deadline, l.status,
+            (SELECT COUNT(*) FROM applications a
+             WHERE a.listing_id = l.id AND a.held_at IS NULL) AS "applicants!",
+            l.poster_id, u.username AS poster_username, u.display_name AS poster_name,
+            u.avatar_url AS poster_avatar, l.project_id, p.title AS "project_title?",
+            p.slug AS "project_slug?", l.created_at
+        FROM listings l
+        JOIN users u ON u.id = l.poster_id
+        LEFT JOIN projects p ON p.id = l.project_id
+        WHERE l.id = $1 AND u.banned_at IS NULL
+        "#,
+        listing_id
+    )
+    .fetch_optional(db.read())
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or(ApiError::new(
+        StatusCode::NOT_FOUND,
+        &i18n::LISTING_NOT_FOUND,
+    ))?;
+
+    Ok(Json(listing))
+}
+
+/// Post an opening, optionally for one of the user's projects
+pub async fn create(
+    State(state): State<AppState>,
+    VerifiedUser(user): VerifiedUser,
+    ValidatedJson(payload): ValidatedJson<CreateListingRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    if let Some(project_id) = payload.project_id {
+        let owned = sqlx::query_scalar!(
+            r#"SELECT EXISTS(SELECT 1 FROM projects WHERE id = $1 AND owner_id = $2) AS "owned!""#,
+            project_id,
+            user.id
+        )
+        .fetch_one(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        if !owned {
+            return Err(ApiError::new(
+                StatusCode::NOT_FOUND,
+                &i18n::PROJECT_NOT_FOUND,
+            ));
+        }
+    }
+
+    let listing = sqlx::query!(
+        r#"
+        INSERT INTO listings (
+            poster_id, project_id, role, description, skills, compensation_type, compensation,
+            deadline
+        )
+        VALUES ($1, $2, $3, NULLIF($4, ''), $5, $6, NULLIF($7, ''), $8)
+        RETURNING id, created_at
+        "#,
+        user.id,
+        payload.project_id,
+        payload.role,
+        payload.description,
+        &payload.skills.unwrap_or_default(),
+        payload.compensation_type,
+        payload.compensation,
+        payload.deadline
+    )
+    .fetch_one(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(serde_json::json!({
+            "id": listing.id,
+            "created_at": listing.created_at,
+        })),
+    ))
+}
+
+/// Change or close a listing (poster only)
+pub async fn update(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(listing_id): Path<Uuid>,
+    ValidatedJson(payload): ValidatedJson<UpdateListingRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let updated = sqlx::query!(
+        r#"
+        UPDATE listings
+        SET role = COALESCE($3, role),
+            description = CASE WHEN $4::text IS NULL THEN description ELSE NULLIF($4, '') END,
+            skills = COALESCE($5, skills),
+            compensation_type = COALESCE($6, compensation_type),
+            compensation = CASE WHEN $7::text IS NULL THEN compensation ELSE NULLIF($7, '') END,
+            deadline = COALESCE($8, deadline),
+            status = COALESCE($9, status),
+            updated_at = NOW()
+        WHERE id = $1 AND poster_id = $2
+        "#,
+        listing_id,
+        user.id,
+        payload.role,
+        payload.description,
+        payload.skills.as_deref(),
+        payload.compensation_type,
+        payload.compensation,
+        payload.deadline,
+        payload.status
+    )
+    .execute(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    if updated.rows_affected() == 0 {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            &i18n::LISTING_NOT_FOUND,
+        ));
+    }
+    Ok(StatusCode::OK)
+}
+
+/// Take a listing down, with its applications (poster, moderator or admin)
+pub async fn delete(
+    State(state): State<AppState>,
+    AuthUser { id: user_id, role }: AuthUser,
+    session: Session,
+    Path(listing_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let poster_id = sqlx::query_scalar!("SELECT poster_id FROM listings WHERE id = $1", listing_id)
+        .fetch_optional(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or(ApiError::new(
+            StatusCode::NOT_FOUND,
+            &i18n::LISTING_NOT_FOUND,
+        ))?;
+
+    if poster_id != user_id && !crate::admin::can_moderate(&role) {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            &i18n::NOT_YOUR_LISTING,
+        ));
+    }
+
+    sqlx::query!("DELETE FROM listings WHERE id = $1", listing_id)
+        .execute(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    // Removing someone else's listing is a moderation action
+    if poster_id != user_id {
+        let details = format!("listing {}", listing_id);
+        crate::audit::record(
+            &state.pool,
+            &session,
+            user_id,
+            "moderation.listing_deleted",
+            Some(poster_id),
+            Some(&details),
+        )
+        .await?;
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,118 @@
+use uuid::Uuid;
+
+use crate::notifications::NotificationKind;
+use crate::state::AppState;
+
+// More than this in one item looks like spam; the rest are ignored
+const MAX_MENTIONS: usize = 10;
+const EXCERPT_CHARS: usize = 140;
+
+/// Something users can be mentioned in
+pub struct MentionSource<'a> {
+    /// e.g. "post"
+    pub content_type: &'static str,
+    pub content_id: Uuid,
+    pub author_id: Uuid,
+    /// Path in the frontend that shows it, e.g. "/ada#post-<id>"
+    pub link: String,
+    pub text: &'a str,
+}
+
+/// The `@username`s in `text`, lowercased, deduplicated and in order of appearance.
+/// An `@` straight after a letter or digit (an email address) isn't a mention.
+pub fn parse(text: &str) -> Vec<String> {
+    let is_username_char = |c: char| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.');
+    let mut usernames: Vec<String> = Vec::new();
+    let mut previous = None;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        let after_word = previous.is_some_and(|p: char| p.is_alphanumeric() || p == '_');
+        previous = Some(c);
+        if c != '@' || after_word {
+            continue;
+        }
+
+        let start = i + 1;
+        let mut end = start;
+        while let Some(&(j, next)) = chars.peek() {
+            if !is_username_char(next) {
+                break;
+            }
+            end = j + next.len_utf8();
+            previous = Some(next);
+            chars.next();
+        }
+        // "@ada." at the end of a sentence mentions ada
+        let username = text[start..end].trim_end_matches(['.', '-']).to_lowercase();
+        if (3..=39).contains(&username.len()) && !usernames.contains(&username) {
+            usernames.push(username);
+        }
+    }
+
+    usernames.truncate(MAX_MENTIONS);
+    usernames
+}
+
+/// Notify everyone mentioned in `source`, once per item: users already notified about it
+/// aren't again, so it can be called again when the item is edited.
+/// Errors are logged, since the content is saved by now.
+pub async fn notify(state: &AppState, source: MentionSource<'_>) {
+    let usernames = parse(source.text);
+    if usernames.is_empty() {
+        return;
+    }
+
+    let mentioned = sqlx::query_scalar!(
+        r#"
+        SELECT u.id FROM users u
+        WHERE LOWER(u.username) = ANY($1)
+          AND u.banned_at IS NULL
+          AND NOT EXISTS (
+              SELECT 1 FROM notifications n
+              WHERE n.user_id = u.id AND n.kind = $2 AND n.data->>'content_id' = $3
+          )
+        "#,
+        &usernames,
+        NotificationKind::Mention.as_str(),
+        source.content_id.to_string()
+    )
+    .fetch_all(&state.pool)
+    .await;
+    let mentioned = match mentioned {
+        Ok(mentioned) => mentioned,
+        Err(e) => {
+            tracing::error!("Failed to look up mentions in {}: {}", source.content_id, e);
+            return;
+        }
+    };
+
+    let data = serde_json::json!({
+        "content_type": source.content_type,
+        "content_id": source.content_id,
+        "link": source.link,
+        "excerpt": excerpt(source.text),
+    });
+    for user_id in mentioned {
+        if let Err(e) = crate::notifications::notify(
+            state,
+            user_id,
+            NotificationKind::Mention,
+            source.author_id,
+            data.clone(),
+        )
+        .await
+        {
+            tracing::error!("Failed to notify {} of a mention: {}", user_id, e);
+        }
+    }
+}
+
+fn excerpt(text: &str) -> String {
+    let text = text.trim();
+    if text.chars().count() <= EXCERPT_CHARS {
+        return text.to_string();
+    }
+    let cut: String = text.chars().take(EXCERPT_CHARS).collect();
+    format!("{}...", cut.trim_end())
+}
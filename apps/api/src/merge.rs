@@ -0,0 +1,494 @@
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHasher, SaltString},
+    Argon2,
+};
+use axum::{extract::State, http::StatusCode, response::IntoResponse};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::error::{ApiError, Message};
+use crate::extractors::AuthUser;
+use crate::i18n::{self, Locale};
+use crate::state::AppState;
+use crate::validation::{Validate, ValidatedJson, ValidationErrors};
+
+// How long a link to confirm linking or merging accounts stays valid
+pub const MERGE_TOKEN_TTL_HOURS: i64 = 1;
+
+fn provider_name(provider: &str) -> &str {
+    match provider {
+        "google" => "Google",
+        "github" => "GitHub",
+        other => other,
+    }
+}
+
+/// An OAuth login whose email matches a local account, when the account never proved it owns
+/// that email (or the provider didn't). Signing them in would hand the account to whoever
+/// controls either side, so instead email the address a link that attaches the login.
+pub async fn request_link(
+    state: &AppState,
+    user_id: Uuid,
+    provider: &str,
+    provider_id: &str,
+    email: &str,
+) -> Result<(), String> {
+    let locale = sqlx::query_scalar!("SELECT locale FROM users WHERE id = $1", user_id)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    let token = Uuid::new_v4().to_string();
+    let expires_at = chrono::Utc::now() + chrono::Duration::hours(MERGE_TOKEN_TTL_HOURS);
+
+    // Trying again replaces the earlier link
+    sqlx::query!(
+        "DELETE FROM account_merges WHERE provider = $1 AND provider_id = $2",
+        provider,
+        provider_id
+    )
+    .execute(&state.pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    sqlx::query!(
+        r#"
+        INSERT INTO account_merges (user_id, provider, provider_id, provider_email, token, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+        user_id,
+        provider,
+        provider_id,
+        email,
+        token,
+        expires_at
+    )
+    .execute(&state.pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let locale = Locale::from_tag(&locale);
+    let body = locale.email().merge_link_body(provider_name(provider));
+    crate::email::send_merge_confirmation(state, email, &token, &body, locale).await
+}
+
+#[derive(Deserialize)]
+pub struct MergeRequest {
+    /// The email the other account signs in with
+    pub email: String,
+}
+
+impl Validate for MergeRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.email("email", &self.email);
+        errors.into_result()
+    }
+}
+
+/// Ask to fold a duplicate account into the logged in one. The other account's email gets a
+/// confirmation link, which only works while logged in as the account asking.
+pub async fn request_merge(
+    State(state): State<AppState>,
+    AuthUser { id: user_id, .. }: AuthUser,
+    ValidatedJson(payload): ValidatedJson<MergeRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let other = sqlx::query!(
+        r#"
+        SELECT u.id, u.locale FROM users u
+        WHERE u.id <> $2
+          AND (EXISTS (SELECT 1 FROM local_auths l WHERE l.user_id = u.id AND l.email = $1)
+               OR EXISTS (SELECT 1 FROM oauth_connections o WHERE o.user_id = u.id AND o.provider_email = $1))
+        LIMIT 1
+        "#,
+        payload.email,
+        user_id
+    )
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    if let Some(other) = other {
+        let token = Uuid::new_v4().to_string();
+        let expires_at = chrono::Utc::now() + chrono::Duration::hours(MERGE_TOKEN_TTL_HOURS);
+        let username = sqlx::query_scalar!(
+            r#"
+            WITH cleared AS (
+                DELETE FROM account_merges WHERE user_id = $1 AND merge_user_id = $2
+            ), created AS (
+                INSERT INTO account_merges (user_id, merge_user_id, token, expires_at)
+                VALUES ($1, $2, $3, $4)
+            )
+            SELECT username FROM users WHERE id = $1
+            "#,
+            user_id,
+            other.id,
+            token,
+            expires_at
+        )
+        .fetch_one(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+        let locale = Locale::from_tag(&other.locale);
+        let body = locale.email().merge_account_body(&username);
+        crate::email::send_merge_confirmation(&state, &payload.email, &token, &body, locale)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    }
+
+    // The same answer either way, so this can't be used to find out who has an account
+    Ok((
+        StatusCode::ACCEPTED,
+        Message::new(&i18n::MERGE_CONFIRMATION_SENT),
+    ))
+}
+
+#[derive(Deserialize)]
+pub struct ConfirmMergeRequest {
+    pub token: String,
+}
+
+impl Validate for ConfirmMergeRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.required("token", &self.token);
+        errors.into_result()
+    }
+}
+
+/// Confirm a link from `request_link` or `request_merge`, from the emailed token
+pub async fn confirm(
+    State(state): State<AppState>,
+    current_user: Option<AuthUser>,
+    ValidatedJson(payload): ValidatedJson<ConfirmMergeRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let merge = sqlx::query!(
+        r#"
+        SELECT id, user_id, merge_user_id, provider, provider_id, provider_email
+        FROM account_merges
+        WHERE token = $1 AND expires_at > NOW()
+        "#,
+        payload.token
+    )
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or(ApiError::new(StatusCode::BAD_REQUEST, &i18n::INVALID_LINK))?;
+
+    match (merge.merge_user_id, merge.provider, merge.provider_id) {
+        (Some(from), _, _) => {
+            if current_user.map(|user| user.id) != Some(merge.user_id) {
+                return Err(ApiError::new(
+                    StatusCode::FORBIDDEN,
+                    &i18n::MERGE_LOGIN_FIRST,
+                ));
+            }
+            merge_accounts(&state, merge.id, from, merge.user_id).await?;
+            Ok((StatusCode::OK, Message::new(&i18n::ACCOUNTS_MERGED)))
+        }
+        (None, Some(provider), Some(provider_id)) => {
+            link_login(
+                &state,
+                merge.id,
+                merge.user_id,
+                &provider,
+                &provider_id,
+                merge.provider_email.as_deref(),
+            )
+            .await?;
+            Ok((
+                StatusCode::OK,
+                Message::new(&i18n::ACCOUNT_LINKED).arg("provider", provider_name(&provider)),
+            ))
+        }
+        _ => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Invalid account merge".to_string(),
+        )
+            .into()),
+    }
+}
+
+/// Attach the OAuth login to the account. Confirming proved the email is theirs, so it counts as
+/// verified; if it wasn't before, whoever set the password may not own the address, so the
+/// password is replaced (they can reset it) and every session is signed out.
+async fn link_login(
+    state: &AppState,
+    merge_id: Uuid,
+    user_id: Uuid,
+    provider: &str,
+    provider_id: &str,
+    provider_email: Option<&str>,
+) -> Result<(), ApiError> {
+    let mut tx = state
+        .pool
+        .begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let claimed = sqlx::query!("DELETE FROM account_merges WHERE id = $1", merge_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if claimed.rows_affected() == 0 {
+        return Err(ApiError::new(StatusCode::BAD_REQUEST, &i18n::INVALID_LINK));
+    }
+
+    // The access token is filled in by the next sign in with it
+    let linked = sqlx::query!(
+        r#"
+        INSERT INTO oauth_connections (user_id, provider, provider_id, access_token, provider_email)
+        VALUES ($1, $2, $3, '', $4)
+        ON CONFLICT (provider, provider_id) DO NOTHING
+        "#,
+        user_id,
+        provider,
+        provider_id,
+        provider_email
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if linked.rows_affected() == 0 {
+        return Err(
+            ApiError::new(StatusCode::CONFLICT, &i18n::PROVIDER_ALREADY_LINKED)
+                .arg("provider", provider_name(provider)),
+        );
+    }
+
+    let verified = sqlx::query_scalar!(
+        r#"SELECT COALESCE(verified, FALSE) as "verified!" FROM local_auths WHERE user_id = $1 FOR UPDATE"#,
+        user_id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .unwrap_or(true);
+
+    if !verified {
+        let salt = SaltString::generate(&mut OsRng);
+        let unusable = Argon2::default()
+            .hash_password(Uuid::new_v4().as_bytes(), &salt)
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+            .to_string();
+        sqlx::query!(
+            r#"
+            UPDATE local_auths
+            SET verified = TRUE, verification_token = NULL, verification_token_expires_at = NULL,
+                password_hash = $2
+            WHERE user_id = $1
+            "#,
+            user_id,
+            unusable
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    if !verified {
+        crate::session::revoke_user_sessions(&state.pool, &state.sessions, user_id)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    }
+    Ok(())
+}
+
+/// Move the duplicate's sign in methods, posts, projects, uploads, applications, listings,
+/// events and RSVPs to `into`, then delete it. Follows, messages and notifications go with the deleted account.
+/// Nothing changes unless it all does; the duplicate is signed out once it's gone.
+async fn merge_accounts(
+    state: &AppState,
+    merge_id: Uuid,
+    from: Uuid,
+    into: Uuid,
+) -> Result<(), ApiError> {
+    let mut tx = state
+        .pool
+        .begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let claimed = sqlx::query!("DELETE FROM account_merges WHERE id = $1", merge_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if claimed.rows_affected() == 0 {
+        return Err(ApiError::new(StatusCode::BAD_REQUEST, &i18n::INVALID_LINK));
+    }
+
+    // Lock the admin rows, as when changing roles, so the last admin can't be merged away
+    let admins: Vec<Uuid> =
+        sqlx::query_scalar!("SELECT id FROM users WHERE role = 'admin' FOR UPDATE")
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if admins == [from] {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            &i18n::LAST_ADMIN_MERGE,
+        ));
+    }
+
+    sqlx::query!(
+        "UPDATE oauth_connections SET user_id = $2 WHERE user_id = $1",
+        from,
+        into
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    sqlx::query!(
+        "UPDATE passkey_credentials SET user_id = $2 WHERE user_id = $1",
+        from,
+        into
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    // An email and password only if the account kept doesn't have its own
+    sqlx::query!(
+        r#"
+        UPDATE local_auths SET user_id = $2
+        WHERE user_id = $1 AND NOT EXISTS (SELECT 1 FROM local_auths WHERE user_id = $2)
+        "#,
+        from,
+        into
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    // Slugs are unique per owner, so a clashing one gets part of its id appended
+    let projects = sqlx::query_scalar!(
+        r#"
+        UPDATE projects p
+        SET owner_id = $2,
+            slug = CASE WHEN EXISTS (SELECT 1 FROM projects o WHERE o.owner_id = $2 AND o.slug = p.slug)
+                        THEN p.slug || '-' || left(p.id::text, 8) ELSE p.slug END
+        WHERE owner_id = $1
+        RETURNING id
+        "#,
+        from,
+        into
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let posts = sqlx::query_scalar!(
+        "UPDATE posts SET author_id = $2 WHERE author_id = $1 RETURNING id",
+        from,
+        into
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    sqlx::query!(
+        "UPDATE feed_items SET author_id = $2 WHERE author_id = $1",
+        from,
+        into
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    sqlx::query!(
+        "UPDATE uploads SET owner_id = $2 WHERE owner_id = $1",
+        from,
+        into
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    // One application per project or listing: where both applied, the kept account's stays
+    sqlx::query!(
+        r#"
+        UPDATE applications a SET applicant_id = $2
+        WHERE applicant_id = $1
+          AND NOT EXISTS (
+              SELECT 1 FROM applications o
+              WHERE o.applicant_id = $2
+                AND (o.project_id = a.project_id OR o.listing_id = a.listing_id)
+          )
+        "#,
+        from,
+        into
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    sqlx::query!(
+        "UPDATE listings SET poster_id = $2 WHERE poster_id = $1",
+        from,
+        into
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    sqlx::query!(
+        "UPDATE events SET host_id = $2 WHERE host_id = $1",
+        from,
+        into
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    // Likewise one RSVP per event
+    sqlx::query!(
+        r#"
+        UPDATE event_rsvps r SET user_id = $2
+        WHERE user_id = $1
+          AND NOT EXISTS (SELECT 1 FROM event_rsvps o WHERE o.user_id = $2 AND o.event_id = r.event_id)
+        "#,
+        from,
+        into
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    // Taken with the user, but the sessions themselves are only ended after the commit
+    let session_ids = sqlx::query_scalar!(
+        "DELETE FROM active_sessions WHERE user_id = $1 RETURNING session_id",
+        from
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let deleted = sqlx::query_scalar!("DELETE FROM users WHERE id = $1 RETURNING username", from)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    // The merge stands either way; a session left behind has no user to load
+    if let Err(e) = state.sessions.delete_ids(&session_ids).await {
+        tracing::error!("Failed to sign out merged account {}: {}", from, e);
+    }
+
+    crate::user::invalidate_profile(&*state.cache, &deleted).await;
+    crate::feed::invalidate(&*state.cache).await;
+    state.search_index.sync_user(&state.pool, from).await;
+    for id in projects {
+        state.search_index.sync_project(&state.pool, id).await;
+    }
+    for id in posts {
+        state.search_index.sync_post(&state.pool, id).await;
+    }
+    Ok(())
+}
@@ -0,0 +1,506 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tower_sessions::Session;
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::extractors::{AdminUser, AuthUser};
+use crate::i18n;
+use crate::session_store::SessionBackend;
+use crate::state::AppState;
+use crate::validation::{Validate, ValidatedJson, ValidationErrors};
+
+const MAX_REASON_LENGTH: usize = 1000;
+const MAX_NOTE_LENGTH: usize = 1000;
+const STATUSES: &[&str] = &["open", "dismissed", "actioned", "appealed"];
+const RESOLUTIONS: &[&str] = &["dismissed", "actioned"];
+pub(crate) const CATEGORIES: &[&str] =
+    &["spam", "harassment", "hate", "sexual", "violence", "other"];
+// Reports per page of GET /admin/message-reports
+const PAGE_SIZE: i64 = 50;
+// Messages leading up to a report shown to the reviewer
+const CONTEXT_MESSAGES: i64 = 50;
+
+/// A report as listed for admins; the reported content is only in the (logged) detail view
+#[derive(Serialize)]
+pub struct MessageReport {
+    pub id: Uuid,
+    /// None once the message is deleted
+    pub message_id: Option<Uuid>,
+    pub conversation_id: Option<Uuid>,
+    pub reporter_id: Option<Uuid>,
+    pub reporter_username: Option<String>,
+    pub sender_id: Option<Uuid>,
+    pub sender_username: Option<String>,
+    pub category: String,
+    pub reason: String,
+    pub status: String,
+    pub reviewed_by: Option<Uuid>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+    pub note: Option<String>,
+    /// The sender's appeal, once an upheld report is appealed
+    pub appeal: Option<String>,
+    pub appealed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct ContextMessage {
+    pub id: Uuid,
+    pub sender_id: Option<Uuid>,
+    pub content: String,
+    pub attachment_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct MessageReportDetail {
+    #[serde(flatten)]
+    pub report: MessageReport,
+    /// The message as it was when reported
+    pub content: String,
+    pub attachment_id: Option<Uuid>,
+    pub sent_at: DateTime<Utc>,
+    /// The conversation up to the report, newest first
+    pub context: Vec<ContextMessage>,
+}
+
+#[derive(Deserialize)]
+pub struct ReportMessageRequest {
+    /// spam, harassment, hate, sexual, violence or other (the default)
+    pub category: Option<String>,
+    pub reason: String,
+}
+
+impl Validate for ReportMessageRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        if let Some(category) = &self.category {
+            errors.one_of("category", category, CATEGORIES);
+        }
+        errors.length("reason", self.reason.trim(), 1, MAX_REASON_LENGTH);
+        errors.into_result()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ListReportsQuery {
+    /// "open" (the default), "dismissed", "actioned" or "appealed"
+    pub status: Option<String>,
+    pub category: Option<String>,
+    /// "json" (the default, first page only) or "csv" (every report, streamed)
+    pub format: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ResolveReportRequest {
+    /// "dismissed" or "actioned"
+    pub status: String,
+    pub note: Option<String>,
+}
+
+impl Validate for ResolveReportRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.one_of("status", &self.status, RESOLUTIONS);
+        if let Some(note) = &self.note {
+            errors.length("note", note, 0, MAX_NOTE_LENGTH);
+        }
+        errors.into_result()
+    }
+}
+
+/// Report a message someone else sent in one of the user's conversations.
+/// Each user can report a message once.
+pub async fn report(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+    Path(message_id): Path<Uuid>,
+    ValidatedJson(payload): ValidatedJson<ReportMessageRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let message = sqlx::query!(
+        r#"
+        SELECT m.conversation_id, m.sender_id, m.content, m.attachment_id, m.created_at
+        FROM messages m
+        JOIN conversation_members me ON me.conversation_id = m.conversation_id AND me.user_id = $2
+        WHERE m.id = $1
+        "#,
+        message_id,
+        user.id
+    )
+    .fetch_optional(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or(ApiError::new(
+        StatusCode::NOT_FOUND,
+        &i18n::MESSAGE_NOT_FOUND,
+    ))?;
+
+    if message.sender_id == Some(user.id) {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            &i18n::CANNOT_REPORT_OWN_MESSAGE,
+        ));
+    }
+
+    let id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO message_reports
+            (message_id, conversation_id, reporter_id, sender_id, content, attachment_id, sent_at,
+             category, reason)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        ON CONFLICT (message_id, reporter_id) DO NOTHING
+        RETURNING id
+        "#,
+        message_id,
+        message.conversation_id,
+        user.id,
+        message.sender_id,
+        message.content,
+        message.attachment_id,
+        message.created_at,
+        payload.category.as_deref().unwrap_or("other"),
+        payload.reason.trim()
+    )
+    .fetch_optional(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or(ApiError::new(StatusCode::CONFLICT, &i18n::ALREADY_REPORTED))?;
+
+    Ok((StatusCode::CREATED, Json(serde_json::json!({ "id": id }))))
+}
+
+async fn load_reports(
+    pool: &PgPool,
+    id: Option<Uuid>,
+    status: Option<&str>,
+    category: Option<&str>,
+) -> Result<Vec<MessageReport>, sqlx::Error> {
+    sqlx::query_as!(
+        MessageReport,
+        r#"
+        SELECT r.id, r.message_id, r.conversation_id,
+               r.reporter_id, reporter.username AS "reporter_username?",
+               r.sender_id, sender.username AS "sender_username?",
+               r.category, r.reason, r.status, r.reviewed_by, r.reviewed_at, r.note,
+               r.appeal, r.appealed_at, r.created_at
+        FROM message_reports r
+        LEFT JOIN users reporter ON reporter.id = r.reporter_id
+        LEFT JOIN users sender ON sender.id = r.sender_id
+        WHERE ($1::uuid IS NULL OR r.id = $1) AND ($2::text IS NULL OR r.status = $2)
+          AND ($3::text IS NULL OR r.category = $3)
+        ORDER BY r.created_at
+        LIMIT $4
+        "#,
+        id,
+        status,
+        category,
+        PAGE_SIZE
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Reports with a status, open ones by default, oldest first, optionally in one category.
+/// With `format=csv`, a download of all of them rather than the first page.
+pub async fn list(
+    State(pool): State<PgPool>,
+    _: AdminUser,
+    Query(query): Query<ListReportsQuery>,
+) -> Result<Response, ApiError> {
+    let status = query.status.unwrap_or_else(|| "open".to_string());
+    if !STATUSES.contains(&status.as_str()) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "status must be open, dismissed, actioned or appealed".to_string(),
+        )
+            .into());
+    }
+    if let Some(category) = &query.category {
+        if !CATEGORIES.contains(&category.as_str()) {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("category must be one of {}", CATEGORIES.join(", ")),
+            )
+                .into());
+        }
+    }
+
+    match query.format.as_deref() {
+        None | Some("json") => {}
+        Some("csv") => return Ok(export(pool, status, query.category)),
+        Some(_) => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "format must be json or csv".to_string(),
+            )
+                .into())
+        }
+    }
+
+    let reports = load_reports(&pool, None, Some(&status), query.category.as_deref())
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(reports).into_response())
+}
+
+/// Every report with a status as CSV. Like the list, it leaves out the reported content.
+fn export(pool: PgPool, status: String, category: Option<String>) -> Response {
+    let (mut writer, response) = crate::export::csv(
+        &format!("message-reports-{}.csv", status),
+        &[
+            "id",
+            "message_id",
+            "conversation_id",
+            "reporter_id",
+            "reporter_username",
+            "sender_id",
+            "sender_username",
+            "category",
+            "reason",
+            "status",
+            "reviewed_by",
+            "reviewed_at",
+            "note",
+            "appeal",
+            "appealed_at",
+            "created_at",
+        ],
+    );
+
+    tokio::spawn(async move {
+        let mut reports = sqlx::query_as!(
+            MessageReport,
+            r#"
+            SELECT r.id, r.message_id, r.conversation_id,
+                   r.reporter_id, reporter.username AS "reporter_username?",
+                   r.sender_id, sender.username AS "sender_username?",
+                   r.category, r.reason, r.status, r.reviewed_by, r.reviewed_at, r.note,
+                   r.appeal, r.appealed_at, r.created_at
+            FROM message_reports r
+            LEFT JOIN users reporter ON reporter.id = r.reporter_id
+            LEFT JOIN users sender ON sender.id = r.sender_id
+            WHERE r.status = $1 AND ($2::text IS NULL OR r.category = $2)
+            ORDER BY r.created_at
+            "#,
+            status,
+            category
+        )
+        .fetch(&pool);
+
+        let id = |id: Option<Uuid>| id.map(|id| id.to_string()).unwrap_or_default();
+        let time = |t: Option<DateTime<Utc>>| t.map(|t| t.to_rfc3339()).unwrap_or_default();
+        while let Some(report) = reports.next().await {
+            let report = match report {
+                Ok(report) => report,
+                Err(e) => return writer.fail(e).await,
+            };
+            let row = [
+                report.id.to_string(),
+                id(report.message_id),
+                id(report.conversation_id),
+                id(report.reporter_id),
+                report.reporter_username.unwrap_or_default(),
+                id(report.sender_id),
+                report.sender_username.unwrap_or_default(),
+                report.category,
+                report.reason,
+                report.status,
+                id(report.reviewed_by),
+                time(report.reviewed_at),
+                report.note.unwrap_or_default(),
+                report.appeal.unwrap_or_default(),
+                time(report.appealed_at),
+                report.created_at.to_rfc3339(),
+            ];
+            if !writer.row(&row).await {
+                break;
+            }
+        }
+        writer.finish().await;
+    });
+
+    response
+}
+
+/// The reported message and the conversation leading up to it. This is the only way admins
+/// can read direct messages, and every view goes in the audit log.
+pub async fn get(
+    State(pool): State<PgPool>,
+    AdminUser(admin): AdminUser,
+    session: Session,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let report = load_reports(&pool, Some(id), None, None)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .pop()
+        .ok_or(ApiError::new(
+            StatusCode::NOT_FOUND,
+            &i18n::REPORT_NOT_FOUND,
+        ))?;
+
+    let snapshot = sqlx::query!(
+        "SELECT content, attachment_id, sent_at FROM message_reports WHERE id = $1",
+        id
+    )
+    .fetch_one(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    // Logged before anything is shown
+    let details = format!(
+        "report {}, conversation {}",
+        id,
+        report
+            .conversation_id
+            .map(|c| c.to_string())
+            .unwrap_or_else(|| "deleted".to_string())
+    );
+    crate::audit::record(
+        &pool,
+        &session,
+        admin.id,
+        "admin.message_report_viewed",
+        report.sender_id,
+        Some(&details),
+    )
+    .await?;
+
+    let context = sqlx::query_as!(
+        ContextMessage,
+        r#"
+        SELECT id, sender_id, content, attachment_id, created_at FROM messages
+        WHERE conversation_id = $1 AND created_at <= $2
+        ORDER BY created_at DESC
+        LIMIT $3
+        "#,
+        report.conversation_id,
+        report.created_at,
+        CONTEXT_MESSAGES
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(MessageReportDetail {
+        report,
+        content: snapshot.content,
+        attachment_id: snapshot.attachment_id,
+        sent_at: snapshot.sent_at,
+        context,
+    }))
+}
+
+/// Close an open or appealed report. Upholding an open report gives the sender a strike, which
+/// may warn, suspend or ban them (see strikes.rs); dismissing an appealed one takes it back.
+/// Anything further is done with the usual tools.
+pub async fn resolve(
+    State(state): State<AppState>,
+    State(sessions): State<SessionBackend>,
+    AdminUser(admin): AdminUser,
+    session: Session,
+    Path(id): Path<Uuid>,
+    ValidatedJson(payload): ValidatedJson<ResolveReportRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let note = payload
+        .note
+        .as_deref()
+        .map(str::trim)
+        .filter(|n| !n.is_empty());
+
+    let mut tx = state
+        .pool
+        .begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let result = sqlx::query!(
+        r#"
+        UPDATE message_reports r
+        SET status = $2, note = $3, reviewed_by = $4, reviewed_at = NOW()
+        FROM (SELECT id, status FROM message_reports WHERE id = $1 FOR UPDATE) previous
+        WHERE r.id = previous.id AND previous.status IN ('open', 'appealed')
+        RETURNING r.sender_id, previous.status AS "previous_status!"
+        "#,
+        id,
+        payload.status,
+        note,
+        admin.id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let Some(resolved) = result else {
+        let exists = sqlx::query_scalar!(
+            r#"SELECT EXISTS(SELECT 1 FROM message_reports WHERE id = $1) AS "exists!""#,
+            id
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        return Err(if exists {
+            ApiError::new(StatusCode::CONFLICT, &i18n::REPORT_ALREADY_RESOLVED)
+        } else {
+            ApiError::new(StatusCode::NOT_FOUND, &i18n::REPORT_NOT_FOUND)
+        });
+    };
+
+    let appealed = resolved.previous_status == "appealed";
+    let change = match (appealed, payload.status.as_str()) {
+        (false, "actioned") => 1,
+        (true, "dismissed") => -1,
+        _ => 0,
+    };
+    let strikes = match resolved.sender_id {
+        Some(sender_id) if change != 0 => Some(
+            sqlx::query_scalar!(
+                "UPDATE users SET strikes = GREATEST(strikes + $2, 0) WHERE id = $1 RETURNING strikes",
+                sender_id,
+                change
+            )
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?,
+        ),
+        _ => None,
+    };
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let details = if appealed {
+        format!("report {}: {} on appeal", id, payload.status)
+    } else {
+        format!("report {}: {}", id, payload.status)
+    };
+    crate::audit::record(
+        &state.pool,
+        &session,
+        admin.id,
+        "admin.message_report_resolved",
+        resolved.sender_id,
+        Some(&details),
+    )
+    .await?;
+
+    if let (Some(sender_id), Some(strikes), 1) = (resolved.sender_id, strikes, change) {
+        crate::strikes::escalate(
+            &state, &sessions, &session, admin.id, sender_id, id, strikes,
+        )
+        .await?;
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,1165 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::extractors::AuthUser;
+use crate::i18n;
+use crate::notifications::NotificationKind;
+use crate::ws::Event;
+use crate::search::{Sort, TextQuery};
+use crate::state::AppState;
+use crate::validation::{Validate, ValidatedJson, ValidationErrors};
+
+const MAX_MESSAGE_LENGTH: usize = 5000;
+// Conversations per folder, and messages per page of a conversation
+const PAGE_SIZE: i64 = 50;
+// Who may start a conversation: anyone, only people the user follows, or no one.
+// Anyone else's first message lands in the user's requests instead of their inbox.
+const DM_PRIVACY: &[&str] = &["everyone", "following", "nobody"];
+
+// How long after sending a message its sender can unsend it for everyone
+const UNSEND_WINDOW_MINUTES: i32 = 10;
+
+pub const MESSAGE_REACTIONS: &[&str] = &["👍", "❤️", "😂", "😮", "😢", "🎉"];
+
+#[derive(Serialize)]
+pub struct ConversationSummary {
+    pub id: Uuid,
+    /// This user's side: "accepted" (inbox), "request" or "declined"
+    pub status: String,
+    pub other_id: Uuid,
+    pub other_username: String,
+    pub other_name: String,
+    pub other_avatar: Option<String>,
+    pub last_message: Option<String>,
+    pub last_message_at: DateTime<Utc>,
+    pub unread: i64,
+    pub muted: bool,
+    /// When a mute ends; None while muted means indefinitely
+    pub muted_until: Option<DateTime<Utc>>,
+    pub archived: bool,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Message {
+    pub id: Uuid,
+    pub conversation_id: Uuid,
+    pub sender_id: Option<Uuid>,
+    pub content: String,
+    pub attachment: Option<Attachment>,
+    pub reactions: serde_json::Value, // emoji -> count
+    /// The viewer's own reactions; always empty in realtime events
+    pub viewer_reactions: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// An attached image, with signed URLs for the original and each variant
+#[derive(Clone, Debug, Serialize)]
+pub struct Attachment {
+    pub id: Uuid,
+    pub urls: HashMap<String, String>,
+    /// Seconds until the URLs stop working; fetch the messages again for fresh ones
+    pub expires_in: u64,
+}
+
+struct MessageRow {
+    id: Uuid,
+    conversation_id: Uuid,
+    sender_id: Option<Uuid>,
+    content: String,
+    attachment_id: Option<Uuid>,
+    reactions: serde_json::Value,
+    viewer_reactions: Vec<String>,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct SendMessageRequest {
+    /// Username of the recipient
+    pub to: String,
+    pub content: String,
+    /// An upload made with category=message; the content may be empty with one
+    pub attachment_id: Option<Uuid>,
+}
+
+impl Validate for SendMessageRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.required("to", &self.to);
+        let min = if self.attachment_id.is_some() { 0 } else { 1 };
+        errors.length("content", self.content.trim(), min, MAX_MESSAGE_LENGTH);
+        errors.into_result()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ConversationsQuery {
+    /// Archived conversations instead of the rest
+    #[serde(default)]
+    pub archived: bool,
+    /// Only muted (true) or unmuted (false) conversations
+    pub muted: Option<bool>,
+}
+
+#[derive(Deserialize)]
+pub struct MuteRequest {
+    /// Indefinitely when left out
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl Validate for MuteRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        if let Some(until) = self.until {
+            if until <= Utc::now() {
+                errors.add("until", "Must be in the future");
+            }
+        }
+        errors.into_result()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct MessagesQuery {
+    /// Only messages older than this, for paging back
+    pub before: Option<DateTime<Utc>>,
+}
+
+/// A message matching a search, with where the matches are in its content
+#[derive(Serialize)]
+pub struct MessageSearchResult {
+    pub id: Uuid,
+    pub conversation_id: Uuid,
+    pub sender_id: Option<Uuid>,
+    pub content: String,
+    pub created_at: DateTime<Utc>,
+    /// `[start, end)` character offsets into `content`
+    pub highlights: Vec<[usize; 2]>,
+}
+
+#[derive(sqlx::FromRow)]
+struct SearchRow {
+    id: Uuid,
+    conversation_id: Uuid,
+    sender_id: Option<Uuid>,
+    content: String,
+    created_at: DateTime<Utc>,
+    headline: String,
+}
+
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    pub q: String,
+    /// Only messages sent at or after this
+    pub from: Option<DateTime<Utc>>,
+    /// ...and before this
+    pub to: Option<DateTime<Utc>>,
+    /// Only messages from this username
+    pub author: Option<String>,
+    /// "relevance" (the default), "newest" or "engaged" (most reactions)
+    pub sort: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ReactRequest {
+    pub emoji: String,
+}
+
+impl Validate for ReactRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.one_of("emoji", &self.emoji, MESSAGE_REACTIONS);
+        errors.into_result()
+    }
+}
+
+#[derive(Serialize)]
+pub struct ReactResponse {
+    pub reacted: bool,
+    pub reactions: serde_json::Value,
+}
+
+#[derive(Serialize)]
+pub struct MessageSettings {
+    pub dm_privacy: String,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateMessageSettings {
+    pub dm_privacy: String,
+}
+
+impl Validate for UpdateMessageSettings {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.one_of("dm_privacy", &self.dm_privacy, DM_PRIVACY);
+        errors.into_result()
+    }
+}
+
+/// Sign the URLs of any attachments. Only call this for a member of the conversation.
+async fn with_attachments(
+    state: &AppState,
+    rows: Vec<MessageRow>,
+) -> Result<Vec<Message>, ApiError> {
+    let mut messages = Vec::with_capacity(rows.len());
+    for row in rows {
+        let attachment = match row.attachment_id {
+            Some(id) => Some(Attachment {
+                id,
+                urls: crate::upload::signed_urls(state, id)
+                    .await
+                    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?,
+                expires_in: crate::upload::SIGNED_URL_TTL_SECS,
+            }),
+            None => None,
+        };
+        messages.push(Message {
+            id: row.id,
+            conversation_id: row.conversation_id,
+            sender_id: row.sender_id,
+            content: row.content,
+            attachment,
+            reactions: row.reactions,
+            viewer_reactions: row.viewer_reactions,
+            created_at: row.created_at,
+        });
+    }
+    Ok(messages)
+}
+
+fn forbidden() -> ApiError {
+    ApiError::new(StatusCode::FORBIDDEN, &i18n::CANNOT_MESSAGE_USER)
+}
+
+/// Send `content` to `to`, in the conversation between the two (started if there's none).
+/// Blocks stop it either way, and so does the recipient having declined the conversation.
+/// A new conversation goes to the recipient's requests unless their `dm_privacy` lets the
+/// sender straight in; replying to a request accepts it.
+pub async fn send(
+    State(state): State<AppState>,
+    user: AuthUser,
+    ValidatedJson(payload): ValidatedJson<SendMessageRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let recipient = sqlx::query!(
+        r#"
+        SELECT u.id, u.dm_privacy,
+               EXISTS(
+                   SELECT 1 FROM follows WHERE follower_id = u.id AND followee_id = $2
+               ) AS "follows_sender!"
+        FROM users u
+        WHERE u.username = $1 AND u.banned_at IS NULL
+        "#,
+        payload.to.to_lowercase(),
+        user.id
+    )
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or(ApiError::new(StatusCode::NOT_FOUND, &i18n::USER_NOT_FOUND))?;
+
+    if recipient.id == user.id {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            &i18n::CANNOT_MESSAGE_SELF,
+        ));
+    }
+    let blocked = crate::relationships::blocked_either_way(&state.pool, user.id, recipient.id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if blocked {
+        return Err(forbidden());
+    }
+
+    let mut tx = state
+        .pool
+        .begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    // Held until commit, so two first messages between the same pair (either way round)
+    // can't both start a conversation
+    sqlx::query!(
+        r#"
+        SELECT pg_advisory_xact_lock(hashtextextended(
+            LEAST($1::uuid, $2::uuid)::text || GREATEST($1::uuid, $2::uuid)::text, 0
+        ))
+        "#,
+        user.id,
+        recipient.id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let existing = sqlx::query!(
+        r#"
+        SELECT me.conversation_id, them.status AS their_status
+        FROM conversation_members me
+        JOIN conversation_members them
+          ON them.conversation_id = me.conversation_id AND them.user_id = $2
+        WHERE me.user_id = $1
+        "#,
+        user.id,
+        recipient.id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let conversation_id = match existing {
+        Some(existing) if existing.their_status == "declined" => return Err(forbidden()),
+        Some(existing) => {
+            sqlx::query!(
+                r#"
+                UPDATE conversation_members SET status = 'accepted'
+                WHERE conversation_id = $1 AND user_id = $2 AND status = 'request'
+                "#,
+                existing.conversation_id,
+                user.id
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            existing.conversation_id
+        }
+        None => {
+            let their_status = match recipient.dm_privacy.as_str() {
+                "everyone" => "accepted",
+                "following" if recipient.follows_sender => "accepted",
+                "following" => "request",
+                _ => return Err(forbidden()),
+            };
+            let conversation_id =
+                sqlx::query_scalar!("INSERT INTO conversations DEFAULT VALUES RETURNING id")
+                    .fetch_one(&mut *tx)
+                    .await
+                    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            sqlx::query!(
+                r#"
+                INSERT INTO conversation_members (conversation_id, user_id, status, last_read_at)
+                VALUES ($1, $2, 'accepted', NOW()), ($1, $3, $4, NULL)
+                "#,
+                conversation_id,
+                user.id,
+                recipient.id,
+                their_status
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            conversation_id
+        }
+    };
+
+    if let Some(attachment_id) = payload.attachment_id {
+        let attached = crate::upload::attach_private(&mut tx, attachment_id, user.id)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        if !attached {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                &i18n::ATTACHMENT_NOT_FOUND,
+            ));
+        }
+    }
+
+    let message = sqlx::query_as!(
+        MessageRow,
+        r#"
+        INSERT INTO messages (conversation_id, sender_id, content, attachment_id)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id, conversation_id, sender_id, content, attachment_id,
+                  '{}'::jsonb AS "reactions!", ARRAY[]::text[] AS "viewer_reactions!", created_at
+        "#,
+        conversation_id,
+        user.id,
+        payload.content.trim(),
+        payload.attachment_id
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    sqlx::query!(
+        "UPDATE conversations SET last_message_at = $2 WHERE id = $1",
+        conversation_id,
+        message.created_at
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    // A new message brings an archived conversation back, unless the recipient also muted it
+    sqlx::query!(
+        r#"
+        UPDATE conversation_members SET archived = FALSE
+        WHERE conversation_id = $1 AND archived
+          AND (user_id = $2 OR muted_until IS NULL OR muted_until <= NOW())
+        "#,
+        conversation_id,
+        user.id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    notify_recipient(&state, recipient.id, user.id, conversation_id).await;
+
+    let message = with_attachments(&state, vec![message]).await?.remove(0);
+
+    // The sender's other tabs show it too
+    for user_id in [recipient.id, user.id] {
+        state
+            .realtime
+            .send_to(user_id, Event::Message(message.clone()));
+    }
+
+    Ok((StatusCode::CREATED, Json(message)))
+}
+
+/// Notify the recipient of a new message, unless they muted the conversation or still have
+/// an unread notification for it. Errors are logged, since the message is sent by now.
+async fn notify_recipient(
+    state: &AppState,
+    recipient_id: Uuid,
+    sender_id: Uuid,
+    conversation_id: Uuid,
+) {
+    let kind = NotificationKind::Message;
+    let skip = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS(
+            SELECT 1 FROM conversation_members
+            WHERE conversation_id = $1 AND user_id = $2 AND muted_until > NOW()
+        ) OR EXISTS(
+            SELECT 1 FROM notifications
+            WHERE user_id = $2 AND kind = $3 AND read_at IS NULL
+              AND data->>'conversation_id' = $1::text
+        ) AS "skip!"
+        "#,
+        conversation_id,
+        recipient_id,
+        kind.as_str()
+    )
+    .fetch_one(&state.pool)
+    .await;
+
+    let result = match skip {
+        Ok(true) => return,
+        Ok(false) => {
+            let data = serde_json::json!({ "conversation_id": conversation_id });
+            crate::notifications::notify(state, recipient_id, kind, sender_id, data).await
+        }
+        Err(e) => Err(e.to_string()),
+    };
+    if let Err(e) = result {
+        tracing::error!("Failed to notify {} of a message: {}", recipient_id, e);
+    }
+}
+
+async fn list_folder(
+    pool: &PgPool,
+    user_id: Uuid,
+    status: &str,
+    query: &ConversationsQuery,
+) -> Result<Vec<ConversationSummary>, sqlx::Error> {
+    sqlx::query_as!(
+        ConversationSummary,
+        r#"
+        SELECT c.id, me.status, c.last_message_at,
+               u.id AS other_id, u.username AS other_username, u.display_name AS other_name,
+               u.avatar_url AS other_avatar,
+               (SELECT m.content FROM messages m
+                WHERE m.conversation_id = c.id
+                  AND NOT EXISTS (
+                      SELECT 1 FROM hidden_messages h WHERE h.message_id = m.id AND h.user_id = $1
+                  )
+                ORDER BY m.created_at DESC LIMIT 1) AS last_message,
+               (SELECT COUNT(*) FROM messages m
+                WHERE m.conversation_id = c.id
+                  AND m.sender_id IS DISTINCT FROM $1
+                  AND m.created_at > COALESCE(me.last_read_at, '-infinity')) AS "unread!",
+               COALESCE(me.muted_until > NOW(), FALSE) AS "muted!",
+               NULLIF(me.muted_until, 'infinity') AS muted_until,
+               me.archived
+        FROM conversation_members me
+        JOIN conversations c ON c.id = me.conversation_id
+        JOIN conversation_members them
+          ON them.conversation_id = c.id AND them.user_id <> me.user_id
+        JOIN users u ON u.id = them.user_id
+        WHERE me.user_id = $1 AND me.status = $2 AND me.archived = $4
+          AND ($5::bool IS NULL OR COALESCE(me.muted_until > NOW(), FALSE) = $5)
+        -- Muted conversations sink below the rest
+        ORDER BY COALESCE(me.muted_until > NOW(), FALSE), c.last_message_at DESC
+        LIMIT $3
+        "#,
+        user_id,
+        status,
+        PAGE_SIZE,
+        query.archived,
+        query.muted
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// The inbox: accepted conversations, most recently active first with muted ones last.
+/// `?archived=true` lists the archived ones instead.
+pub async fn list_conversations(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+    Query(query): Query<ConversationsQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let conversations = list_folder(&pool, user.id, "accepted", &query)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(conversations))
+}
+
+/// Conversations started by people the user's `dm_privacy` didn't let straight in
+pub async fn list_requests(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+    Query(query): Query<ConversationsQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let conversations = list_folder(&pool, user.id, "request", &query)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(conversations))
+}
+
+/// A page of messages, newest first. Reading it marks the conversation read.
+/// Anyone not in it gets a 404.
+pub async fn get_messages(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(conversation_id): Path<Uuid>,
+    Query(query): Query<MessagesQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE conversation_members SET last_read_at = NOW()
+        WHERE conversation_id = $1 AND user_id = $2
+        "#,
+        conversation_id,
+        user.id
+    )
+    .execute(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if result.rows_affected() == 0 {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            &i18n::CONVERSATION_NOT_FOUND,
+        ));
+    }
+    sqlx::query!(
+        r#"
+        UPDATE notifications SET read_at = NOW()
+        WHERE user_id = $1 AND kind = $2 AND read_at IS NULL
+          AND data->>'conversation_id' = $3::uuid::text
+        "#,
+        user.id,
+        NotificationKind::Message.as_str(),
+        conversation_id
+    )
+    .execute(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let rows = sqlx::query_as!(
+        MessageRow,
+        r#"
+        SELECT
+            m.id, m.conversation_id, m.sender_id, m.content, m.attachment_id,
+            COALESCE(
+                (SELECT jsonb_object_agg(emoji, n) FROM (
+                    SELECT emoji, COUNT(*) AS n FROM message_reactions
+                    WHERE message_id = m.id GROUP BY emoji
+                ) counts),
+                '{}'::jsonb
+            ) AS "reactions!",
+            ARRAY(
+                SELECT emoji FROM message_reactions WHERE message_id = m.id AND user_id = $4
+            ) AS "viewer_reactions!",
+            m.created_at
+        FROM messages m
+        WHERE m.conversation_id = $1 AND ($2::timestamptz IS NULL OR m.created_at < $2)
+          AND NOT EXISTS (
+              SELECT 1 FROM hidden_messages h WHERE h.message_id = m.id AND h.user_id = $4
+          )
+        ORDER BY m.created_at DESC
+        LIMIT $3
+        "#,
+        conversation_id,
+        query.before,
+        PAGE_SIZE,
+        user.id
+    )
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(with_attachments(&state, rows).await?))
+}
+
+/// The best matches for `q` across the user's conversations, or just one of them
+async fn search(
+    pool: &PgPool,
+    user_id: Uuid,
+    conversation_id: Option<Uuid>,
+    params: &SearchQuery,
+) -> Result<Vec<MessageSearchResult>, ApiError> {
+    let q = crate::search::query_text(&params.q)?;
+    let sort = Sort::parse(params.sort.as_deref())?;
+    crate::search::check_range(params.from, params.to)?;
+    let author = params.author.as_deref().map(str::to_lowercase);
+
+    let query = TextQuery::bound_at(3);
+    let rows = sqlx::query_as::<_, SearchRow>(&format!(
+        r#"
+        SELECT id, conversation_id, sender_id, content, created_at, {headline} AS headline
+        FROM (
+            SELECT m.id, m.conversation_id, m.sender_id, m.content, m.created_at,
+                   {rank} AS rank,
+                   (SELECT COUNT(*) FROM message_reactions r WHERE r.message_id = m.id)
+                       AS engagement
+            FROM messages m
+            JOIN conversation_members me
+                ON me.conversation_id = m.conversation_id AND me.user_id = $1
+            WHERE ($2::uuid IS NULL OR m.conversation_id = $2)
+              AND {matches}
+              AND NOT EXISTS (
+                  SELECT 1 FROM hidden_messages h WHERE h.message_id = m.id AND h.user_id = $1
+              )
+              AND ($5::timestamptz IS NULL OR m.created_at >= $5)
+              AND ($6::timestamptz IS NULL OR m.created_at < $6)
+              AND ($7::text IS NULL OR m.sender_id = (SELECT id FROM users WHERE username = $7))
+            ORDER BY {order}
+            LIMIT $4
+        ) matches
+        ORDER BY {order}
+        "#,
+        headline = query.headline("content"),
+        matches = query.matches("m.search_vector"),
+        rank = query.rank("m.search_vector"),
+        order = sort.order_by(),
+    ))
+    .bind(user_id)
+    .bind(conversation_id)
+    .bind(q)
+    .bind(PAGE_SIZE)
+    .bind(params.from)
+    .bind(params.to)
+    .bind(&author)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| MessageSearchResult {
+            highlights: crate::search::highlights(&row.headline),
+            id: row.id,
+            conversation_id: row.conversation_id,
+            sender_id: row.sender_id,
+            content: row.content,
+            created_at: row.created_at,
+        })
+        .collect())
+}
+
+/// Search every conversation the user is in (`?q=`, web search syntax), best matches first
+/// unless `sort` says otherwise
+pub async fn search_all(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+    Query(query): Query<SearchQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    Ok(Json(search(&pool, user.id, None, &query).await?))
+}
+
+/// Search one conversation; anyone not in it gets a 404
+pub async fn search_conversation(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+    Path(conversation_id): Path<Uuid>,
+    Query(query): Query<SearchQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let member = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS(
+            SELECT 1 FROM conversation_members WHERE conversation_id = $1 AND user_id = $2
+        ) AS "member!"
+        "#,
+        conversation_id,
+        user.id
+    )
+    .fetch_one(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if !member {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            &i18n::CONVERSATION_NOT_FOUND,
+        ));
+    }
+
+    Ok(Json(
+        search(&pool, user.id, Some(conversation_id), &query).await?,
+    ))
+}
+
+/// "Delete for me": hide a message from this user only. The other member still has it.
+pub async fn delete_for_me(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(message_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let conversation_id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO hidden_messages (message_id, user_id)
+        SELECT m.id, $2 FROM messages m
+        JOIN conversation_members me ON me.conversation_id = m.conversation_id AND me.user_id = $2
+        WHERE m.id = $1
+        ON CONFLICT DO NOTHING
+        RETURNING (SELECT conversation_id FROM messages WHERE id = $1) AS "conversation_id!"
+        "#,
+        message_id,
+        user.id
+    )
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    // Already hidden is fine; only someone else's (or no) message is a 404
+    if let Some(conversation_id) = conversation_id {
+        state.realtime.send_to(
+            user.id,
+            Event::MessageDeleted {
+                conversation_id,
+                message_id,
+            },
+        );
+    } else {
+        let hidden = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM hidden_messages WHERE message_id = $1 AND user_id = $2
+            ) AS "hidden!"
+            "#,
+            message_id,
+            user.id
+        )
+        .fetch_one(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        if !hidden {
+            return Err(ApiError::new(
+                StatusCode::NOT_FOUND,
+                &i18n::MESSAGE_NOT_FOUND,
+            ));
+        }
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Delete one of the user's own messages for everyone, within 10 minutes of sending it.
+/// Reports of it keep their copy.
+pub async fn unsend(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(message_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let deleted = sqlx::query!(
+        r#"
+        DELETE FROM messages
+        WHERE id = $1 AND sender_id = $2
+          AND created_at > NOW() - make_interval(mins => $3)
+        RETURNING conversation_id, attachment_id
+        "#,
+        message_id,
+        user.id,
+        UNSEND_WINDOW_MINUTES
+    )
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let Some(deleted) = deleted else {
+        let sent = sqlx::query_scalar!(
+            r#"SELECT EXISTS(SELECT 1 FROM messages WHERE id = $1 AND sender_id = $2) AS "sent!""#,
+            message_id,
+            user.id
+        )
+        .fetch_one(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        return Err(if sent {
+            ApiError::new(StatusCode::FORBIDDEN, &i18n::UNSEND_WINDOW_PASSED)
+                .arg("minutes", UNSEND_WINDOW_MINUTES)
+        } else {
+            ApiError::new(StatusCode::NOT_FOUND, &i18n::MESSAGE_NOT_FOUND)
+        });
+    };
+
+    let members = sqlx::query_scalar!(
+        "SELECT user_id FROM conversation_members WHERE conversation_id = $1",
+        deleted.conversation_id
+    )
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    for member in members {
+        state.realtime.send_to(
+            member,
+            Event::MessageDeleted {
+                conversation_id: deleted.conversation_id,
+                message_id,
+            },
+        );
+    }
+
+    if let Some(attachment_id) = deleted.attachment_id {
+        if let Err(e) = crate::upload::release_private(&state, &[attachment_id]).await {
+            tracing::error!("Failed to delete attachment {}: {}", attachment_id, e);
+        }
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Delete messages older than MESSAGE_RETENTION_DAYS, for deployments that don't keep them
+/// forever. Unset (the default), messages are kept. Returns how many were deleted.
+pub async fn purge_old(state: &AppState) -> Result<u64, String> {
+    let Some(days) = std::env::var("MESSAGE_RETENTION_DAYS")
+        .ok()
+        .and_then(|v| v.parse::<i32>().ok())
+        .filter(|days| *days > 0)
+    else {
+        return Ok(0);
+    };
+
+    let attachments = sqlx::query_scalar!(
+        r#"
+        DELETE FROM messages WHERE created_at < NOW() - make_interval(days => $1)
+        RETURNING attachment_id
+        "#,
+        days
+    )
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let deleted = attachments.len() as u64;
+    let attachments: Vec<Uuid> = attachments.into_iter().flatten().collect();
+    crate::upload::release_private(state, &attachments).await?;
+
+    Ok(deleted)
+}
+
+/// Add (`react`) or remove (`unreact`) one of the user's reactions and tell both members.
+/// Only members can react, and not while either has blocked the other.
+async fn set_reaction(
+    state: &AppState,
+    user_id: Uuid,
+    message_id: Uuid,
+    emoji: &str,
+    add: bool,
+) -> Result<ReactResponse, ApiError> {
+    let message = sqlx::query!(
+        r#"
+        SELECT m.conversation_id, them.user_id AS other_id
+        FROM messages m
+        JOIN conversation_members me ON me.conversation_id = m.conversation_id AND me.user_id = $2
+        JOIN conversation_members them
+          ON them.conversation_id = m.conversation_id AND them.user_id <> $2
+        WHERE m.id = $1
+        "#,
+        message_id,
+        user_id
+    )
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or(ApiError::new(
+        StatusCode::NOT_FOUND,
+        &i18n::MESSAGE_NOT_FOUND,
+    ))?;
+
+    let blocked = crate::relationships::blocked_either_way(&state.pool, user_id, message.other_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if blocked {
+        return Err(forbidden());
+    }
+
+    let result = if add {
+        sqlx::query!(
+            r#"
+            INSERT INTO message_reactions (message_id, user_id, emoji)
+            VALUES ($1, $2, $3)
+            ON CONFLICT DO NOTHING
+            "#,
+            message_id,
+            user_id,
+            emoji
+        )
+        .execute(&state.pool)
+        .await
+    } else {
+        sqlx::query!(
+            "DELETE FROM message_reactions WHERE message_id = $1 AND user_id = $2 AND emoji = $3",
+            message_id,
+            user_id,
+            emoji
+        )
+        .execute(&state.pool)
+        .await
+    }
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let reactions = sqlx::query_scalar!(
+        r#"
+        SELECT COALESCE(jsonb_object_agg(emoji, n), '{}'::jsonb) as "reactions!"
+        FROM (
+            SELECT emoji, COUNT(*) AS n FROM message_reactions
+            WHERE message_id = $1 GROUP BY emoji
+        ) counts
+        "#,
+        message_id
+    )
+    .fetch_one(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    // Nothing changed (a repeat), so nothing to tell anyone
+    if result.rows_affected() > 0 {
+        for member in [message.other_id, user_id] {
+            state.realtime.send_to(
+                member,
+                Event::MessageReaction {
+                    conversation_id: message.conversation_id,
+                    message_id,
+                    user_id,
+                    emoji: emoji.to_string(),
+                    reacted: add,
+                    reactions: reactions.clone(),
+                },
+            );
+        }
+    }
+
+    Ok(ReactResponse {
+        reacted: add,
+        reactions,
+    })
+}
+
+/// Adding the same reaction twice is fine
+pub async fn react(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(message_id): Path<Uuid>,
+    ValidatedJson(payload): ValidatedJson<ReactRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let response = set_reaction(&state, user.id, message_id, &payload.emoji, true).await?;
+    Ok(Json(response))
+}
+
+pub async fn unreact(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path((message_id, emoji)): Path<(Uuid, String)>,
+) -> Result<impl IntoResponse, ApiError> {
+    let response = set_reaction(&state, user.id, message_id, &emoji, false).await?;
+    Ok(Json(response))
+}
+
+async fn set_status(
+    pool: &PgPool,
+    user_id: Uuid,
+    conversation_id: Uuid,
+    status: &str,
+) -> Result<StatusCode, ApiError> {
+    let result = sqlx::query!(
+        "UPDATE conversation_members SET status = $3 WHERE conversation_id = $1 AND user_id = $2",
+        conversation_id,
+        user_id,
+        status
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    if result.rows_affected() == 0 {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            &i18n::CONVERSATION_NOT_FOUND,
+        ));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Move a request (or a declined conversation) into the inbox
+pub async fn accept(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+    Path(conversation_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    set_status(&pool, user.id, conversation_id, "accepted").await
+}
+
+/// Hide the conversation and stop the other person sending more. They aren't told.
+pub async fn decline(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+    Path(conversation_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    set_status(&pool, user.id, conversation_id, "declined").await
+}
+
+/// Stop notifications for new messages until `until`, or indefinitely
+pub async fn mute(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+    Path(conversation_id): Path<Uuid>,
+    ValidatedJson(payload): ValidatedJson<MuteRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE conversation_members SET muted_until = COALESCE($3::timestamptz, 'infinity')
+        WHERE conversation_id = $1 AND user_id = $2
+        "#,
+        conversation_id,
+        user.id,
+        payload.until
+    )
+    .execute(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    if result.rows_affected() == 0 {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            &i18n::CONVERSATION_NOT_FOUND,
+        ));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn unmute(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+    Path(conversation_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let result = sqlx::query!(
+        "UPDATE conversation_members SET muted_until = NULL WHERE conversation_id = $1 AND user_id = $2",
+        conversation_id,
+        user.id
+    )
+    .execute(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    if result.rows_affected() == 0 {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            &i18n::CONVERSATION_NOT_FOUND,
+        ));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn set_archived(
+    pool: &PgPool,
+    user_id: Uuid,
+    conversation_id: Uuid,
+    archived: bool,
+) -> Result<StatusCode, ApiError> {
+    let result = sqlx::query!(
+        "UPDATE conversation_members SET archived = $3 WHERE conversation_id = $1 AND user_id = $2",
+        conversation_id,
+        user_id,
+        archived
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    if result.rows_affected() == 0 {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            &i18n::CONVERSATION_NOT_FOUND,
+        ));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Move the conversation out of the inbox (or requests) until the next message, or for good
+/// while it's muted
+pub async fn archive(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+    Path(conversation_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    set_archived(&pool, user.id, conversation_id, true).await
+}
+
+pub async fn unarchive(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+    Path(conversation_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    set_archived(&pool, user.id, conversation_id, false).await
+}
+
+pub async fn get_settings(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+) -> Result<impl IntoResponse, ApiError> {
+    let settings = sqlx::query_as!(
+        MessageSettings,
+        "SELECT dm_privacy FROM users WHERE id = $1",
+        user.id
+    )
+    .fetch_one(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(settings))
+}
+
+/// Only affects new conversations; ones already accepted carry on
+pub async fn update_settings(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+    ValidatedJson(payload): ValidatedJson<UpdateMessageSettings>,
+) -> Result<impl IntoResponse, ApiError> {
+    sqlx::query!(
+        "UPDATE users SET dm_privacy = $2 WHERE id = $1",
+        user.id,
+        payload.dm_privacy
+    )
+    .execute(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(MessageSettings {
+        dm_privacy: payload.dm_privacy,
+    }))
+}
@@ -0,0 +1,128 @@
+use axum::{
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+use crate::error::ApiError;
+use crate::i18n;
+use crate::oembed::{excerpt, route, Target};
+use crate::state::AppState;
+
+#[derive(Deserialize)]
+pub struct MetaQuery {
+    /// A frontend path, e.g. "/alice" or "/alice/compiler". Fragments aren't sent to
+    /// servers, so a post ("/alice#post-{id}") can only be asked about from the browser.
+    pub path: String,
+}
+
+/// What the frontend puts in a page's Open Graph tags
+#[derive(Serialize)]
+pub struct PageMeta {
+    pub title: String,
+    pub description: Option<String>,
+    pub image: Option<String>,
+    /// The canonical URL of the page (og:url)
+    pub url: String,
+    /// og:type: "profile", "article" or "website"
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+}
+
+/// Open Graph metadata for a profile, post or project page, so the frontend's server
+/// renderer doesn't need queries of its own
+pub async fn get_meta(
+    State(state): State<AppState>,
+    Query(query): Query<MetaQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let not_found = || ApiError::new(StatusCode::NOT_FOUND, &i18n::PAGE_NOT_FOUND);
+    if !query.path.starts_with('/') {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            &i18n::PATH_NOT_ABSOLUTE,
+        ));
+    }
+    let frontend_url = &state.config.frontend_url;
+    let url = Url::parse(frontend_url)
+        .and_then(|base| base.join(&query.path))
+        .map_err(|_| ApiError::new(StatusCode::BAD_REQUEST, &i18n::INVALID_PATH))?;
+    let target = route(&url).ok_or_else(not_found)?;
+
+    let meta = match target {
+        Target::Profile(username) => {
+            let user = sqlx::query!(
+                r#"
+                SELECT username, display_name, bio, avatar_url
+                FROM users
+                WHERE username = $1 AND banned_at IS NULL
+                "#,
+                username.to_lowercase()
+            )
+            .fetch_optional(&state.pool)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+            .ok_or_else(not_found)?;
+
+            PageMeta {
+                title: format!("{} (@{})", user.display_name, user.username),
+                description: user.bio.as_deref().map(excerpt),
+                image: user.avatar_url,
+                url: format!("{}/{}", frontend_url, user.username),
+                kind: "profile",
+            }
+        }
+        Target::Post(id) => {
+            let post = sqlx::query!(
+                r#"
+                SELECT p.content, p.image_url, u.display_name, u.username, u.avatar_url
+                FROM posts p
+                JOIN users u ON u.id = p.author_id
+                WHERE p.id = $1 AND u.banned_at IS NULL AND p.held_at IS NULL
+                "#,
+                id
+            )
+            .fetch_optional(&state.pool)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+            .ok_or_else(not_found)?;
+
+            PageMeta {
+                title: format!("{} (@{})", post.display_name, post.username),
+                description: Some(excerpt(&post.content)),
+                image: post.image_url.or(post.avatar_url),
+                url: format!("{}/{}#post-{}", frontend_url, post.username, id),
+                kind: "article",
+            }
+        }
+        Target::Project { username, slug } => {
+            let project = sqlx::query!(
+                r#"
+                SELECT p.title, p.slug, p.description, p.image_url, u.username, u.avatar_url
+                FROM projects p
+                JOIN users u ON u.id = p.owner_id
+                WHERE u.username = $1 AND p.slug = $2 AND u.banned_at IS NULL
+                  AND p.held_at IS NULL
+                "#,
+                username,
+                slug
+            )
+            .fetch_optional(&state.pool)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+            .ok_or_else(not_found)?;
+
+            PageMeta {
+                title: project.title,
+                description: project.description.as_deref().map(excerpt),
+                image: project.image_url.or(project.avatar_url),
+                url: format!("{}/{}/{}", frontend_url, project.username, project.slug),
+                kind: "website",
+            }
+        }
+    };
+
+    Ok(([(header::CACHE_CONTROL, "public, max-age=60")], Json(meta)))
+}
@@ -0,0 +1,103 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::extractors::AuthUser;
+use crate::i18n;
+
+/// A step in a project's plan, e.g. from its template
+#[derive(Serialize)]
+pub struct Milestone {
+    pub id: Uuid,
+    pub title: String,
+    /// None until the owner checks it off
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateMilestoneRequest {
+    pub done: bool,
+}
+
+/// A project's milestones, in order. Held projects' are only shown to their owner.
+pub async fn list(
+    State(pool): State<PgPool>,
+    viewer: Option<AuthUser>,
+    Path(project_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let visible = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS(
+            SELECT 1 FROM projects WHERE id = $1 AND (held_at IS NULL OR owner_id = $2)
+        ) AS "visible!"
+        "#,
+        project_id,
+        viewer.map(|viewer| viewer.id)
+    )
+    .fetch_one(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if !visible {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            &i18n::PROJECT_NOT_FOUND,
+        ));
+    }
+
+    let milestones = sqlx::query_as!(
+        Milestone,
+        r#"
+        SELECT id, title, completed_at FROM project_milestones
+        WHERE project_id = $1 ORDER BY position
+        "#,
+        project_id
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(milestones))
+}
+
+/// Check a milestone off, or back on (owner only)
+pub async fn update(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+    Path((project_id, milestone_id)): Path<(Uuid, Uuid)>,
+    Json(payload): Json<UpdateMilestoneRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let milestone = sqlx::query_as!(
+        Milestone,
+        r#"
+        UPDATE project_milestones m
+        SET completed_at = CASE
+            WHEN NOT $4 THEN NULL
+            ELSE COALESCE(m.completed_at, NOW())
+        END
+        FROM projects p
+        WHERE m.id = $2 AND m.project_id = $1 AND p.id = m.project_id AND p.owner_id = $3
+        RETURNING m.id, m.title, m.completed_at
+        "#,
+        project_id,
+        milestone_id,
+        user.id,
+        payload.done
+    )
+    .fetch_optional(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or(ApiError::new(
+        StatusCode::NOT_FOUND,
+        &i18n::MILESTONE_NOT_FOUND,
+    ))?;
+
+    Ok(Json(milestone))
+}
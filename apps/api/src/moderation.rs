@@ -0,0 +1,98 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+// Score at or above which a classifier label flags an image
+const DEFAULT_MODERATION_THRESHOLD: f64 = 0.8;
+
+#[derive(Debug)]
+pub enum Verdict {
+    Allow,
+    /// Hold for admin review, with the labels that triggered it
+    Flag(Vec<String>),
+}
+
+/// Screens uploaded images before they are published
+#[async_trait]
+pub trait ImageModerator: Send + Sync {
+    async fn screen(&self, data: &[u8], content_type: &str) -> Result<Verdict, String>;
+}
+
+/// Default: everything is allowed
+pub struct NoopModerator;
+
+#[async_trait]
+impl ImageModerator for NoopModerator {
+    async fn screen(&self, _data: &[u8], _content_type: &str) -> Result<Verdict, String> {
+        Ok(Verdict::Allow)
+    }
+}
+
+#[derive(Deserialize)]
+struct ClassifierResponse {
+    // label -> confidence (0.0 - 1.0), e.g. {"nsfw": 0.97, "violence": 0.01}
+    scores: HashMap<String, f64>,
+}
+
+/// POSTs the raw image to an external classifier and flags any label scoring over the threshold
+pub struct HttpClassifier {
+    client: reqwest::Client,
+    url: String,
+    api_key: Option<String>,
+    threshold: f64,
+}
+
+#[async_trait]
+impl ImageModerator for HttpClassifier {
+    async fn screen(&self, data: &[u8], content_type: &str) -> Result<Verdict, String> {
+        let mut request = self
+            .client
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, content_type)
+            .body(data.to_vec());
+        if let Some(key) = &self.api_key {
+            request = request.bearer_auth(key);
+        }
+
+        let resp = request.send().await.map_err(|e| e.to_string())?;
+        if !resp.status().is_success() {
+            return Err(format!("Classifier returned {}", resp.status()));
+        }
+
+        let body: ClassifierResponse = resp.json().await.map_err(|e| e.to_string())?;
+        let mut labels: Vec<String> = body
+            .scores
+            .into_iter()
+            .filter(|(_, score)| *score >= self.threshold)
+            .map(|(label, _)| label)
+            .collect();
+
+        if labels.is_empty() {
+            Ok(Verdict::Allow)
+        } else {
+            labels.sort();
+            Ok(Verdict::Flag(labels))
+        }
+    }
+}
+
+/// The configured moderator: an HttpClassifier if MODERATION_CLASSIFIER_URL is set, otherwise a no-op
+pub fn moderator() -> &'static dyn ImageModerator {
+    static MODERATOR: OnceLock<Box<dyn ImageModerator>> = OnceLock::new();
+
+    MODERATOR
+        .get_or_init(|| match std::env::var("MODERATION_CLASSIFIER_URL") {
+            Ok(url) => Box::new(HttpClassifier {
+                client: reqwest::Client::new(),
+                url,
+                api_key: std::env::var("MODERATION_CLASSIFIER_API_KEY").ok(),
+                threshold: std::env::var("MODERATION_THRESHOLD")
+                    .ok()
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(DEFAULT_MODERATION_THRESHOLD),
+            }),
+            Err(_) => Box::new(NoopModerator),
+        })
+        .as_ref()
+}
@@ -0,0 +1,857 @@
+use askama::Template;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+use uuid::Uuid;
+
+use crate::email::{self, Message};
+use crate::email_preferences::EmailCategory;
+use crate::error::ApiError;
+use crate::extractors::AuthUser;
+use crate::i18n::{self, EmailStrings, Locale};
+use crate::ws::Event;
+use crate::state::AppState;
+use crate::validation::{Validate, ValidatedJson, ValidationErrors};
+
+// How many notifications GET /notifications returns
+const RECENT_NOTIFICATIONS: i64 = 50;
+// Unread in-app notifications are emailed once they're this old...
+const UNREAD_EMAIL_AFTER_HOURS: i32 = 4;
+// ...unless they're older than this (e.g. held back by quiet hours for days)
+const UNREAD_EMAIL_MAX_AGE_HOURS: i32 = 72;
+// How long notifications are kept, unless NOTIFICATION_RETENTION_{READ,UNREAD}_DAYS say otherwise
+const DEFAULT_READ_RETENTION_DAYS: i32 = 90;
+const DEFAULT_UNREAD_RETENTION_DAYS: i32 = 365;
+
+/// Things a user can be notified about
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    /// Someone applied to one of your projects or listings
+    Application,
+    /// A message from the admins, see broadcasts.rs. Only ever delivered in-app.
+    Broadcast,
+    /// Someone endorsed one of your skills, see endorsements.rs
+    Endorsement,
+    /// An event you're going to starts within a day, see events.rs
+    EventReminder,
+    /// Someone @mentioned you, see mentions.rs
+    Mention,
+    /// A direct message, see messages.rs. Not sent for muted conversations.
+    Message,
+    /// New results for a saved search, see saved_searches.rs. Only ever delivered in-app.
+    SavedSearch,
+    /// A report of one of your messages was upheld, see strikes.rs. Only ever delivered in-app.
+    Strike,
+}
+
+impl NotificationKind {
+    pub const ALL: &'static [NotificationKind] = &[
+        Self::Application,
+        Self::Broadcast,
+        Self::Endorsement,
+        Self::EventReminder,
+        Self::Mention,
+        Self::Message,
+        Self::SavedSearch,
+        Self::Strike,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Application => "application",
+            Self::Broadcast => "broadcast",
+            Self::Endorsement => "endorsement",
+            Self::EventReminder => "event_reminder",
+            Self::Mention => "mention",
+            Self::Message => "message",
+            Self::SavedSearch => "saved_search",
+            Self::Strike => "strike",
+        }
+    }
+
+    /// Channels used until the user changes them
+    pub fn default_channels(self) -> Channels {
+        match self {
+            // The weekly digest already rounds these up by email
+            Self::Application => Channels {
+                in_app: true,
+                email: false,
+                push: true,
+            },
+            Self::Broadcast => Channels {
+                in_app: true,
+                email: false,
+                push: false,
+            },
+            Self::Endorsement => Channels {
+                in_app: true,
+                email: false,
+                push: true,
+            },
+            // Too late to be any use in a digest or unread summary
+            Self::EventReminder => Channels {
+                in_app: true,
+                email: true,
+                push: true,
+            },
+            // Left unread, they're still emailed in the unread summary
+            Self::Mention => Channels {
+                in_app: true,
+                email: false,
+                push: true,
+            },
+            Self::Message => Channels {
+                in_app: true,
+                email: false,
+                push: true,
+            },
+            Self::SavedSearch => Channels {
+                in_app: true,
+                email: false,
+                push: false,
+            },
+            Self::Strike => Channels {
+                in_app: true,
+                email: false,
+                push: false,
+            },
+        }
+    }
+}
+
+impl FromStr for NotificationKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "application" => Ok(Self::Application),
+            "broadcast" => Ok(Self::Broadcast),
+            "endorsement" => Ok(Self::Endorsement),
+            "event_reminder" => Ok(Self::EventReminder),
+            "mention" => Ok(Self::Mention),
+            "message" => Ok(Self::Message),
+            "saved_search" => Ok(Self::SavedSearch),
+            "strike" => Ok(Self::Strike),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Where one kind of notification is delivered
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct Channels {
+    pub in_app: bool,
+    /// Also subject to the `activity` email preference (and its unsubscribe links)
+    pub email: bool,
+    pub push: bool,
+}
+
+#[derive(Serialize)]
+pub struct Notification {
+    pub id: Uuid,
+    /// e.g. "application"
+    pub kind: String,
+    pub actor_id: Option<Uuid>,
+    pub actor_username: Option<String>,
+    pub actor_name: Option<String>,
+    pub actor_avatar: Option<String>,
+    pub data: serde_json::Value,
+    pub read_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Notify `user_id` that `actor_id` did something, on each channel they want that kind on.
+/// Nobody is notified about their own actions.
+pub async fn notify(
+    state: &AppState,
+    user_id: Uuid,
+    kind: NotificationKind,
+    actor_id: Uuid,
+    data: serde_json::Value,
+) -> Result<(), String> {
+    if user_id == actor_id {
+        return Ok(());
+    }
+    deliver(state, user_id, kind, Some(actor_id), data).await
+}
+
+/// Like `notify`, for things nobody in particular did (e.g. new saved search results).
+/// These are never emailed, since the email reads "<actor> did something".
+pub async fn notify_without_actor(
+    state: &AppState,
+    user_id: Uuid,
+    kind: NotificationKind,
+    data: serde_json::Value,
+) -> Result<(), String> {
+    deliver(state, user_id, kind, None, data).await
+}
+
+async fn deliver(
+    state: &AppState,
+    user_id: Uuid,
+    kind: NotificationKind,
+    actor_id: Option<Uuid>,
+    data: serde_json::Value,
+) -> Result<(), String> {
+    let channels = load_channels(&state.pool, user_id, kind)
+        .await
+        .map_err(|e| e.to_string())?;
+    let email = channels.email && actor_id.is_some();
+
+    if channels.in_app {
+        // Already emailed ones are left out of the unread summary
+        let notification = sqlx::query!(
+            r#"
+            INSERT INTO notifications (user_id, kind, actor_id, data, emailed_at)
+            VALUES ($1, $2, $3, $4, CASE WHEN $5 THEN NOW() END)
+            RETURNING id, created_at
+            "#,
+            user_id,
+            kind.as_str(),
+            actor_id,
+            data,
+            email
+        )
+        .fetch_one(&state.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        state.realtime.send_to(
+            user_id,
+            Event::Notification {
+                id: notification.id,
+                kind: kind.as_str().to_string(),
+                actor_id,
+                data: data.clone(),
+                created_at: notification.created_at,
+            },
+        );
+    }
+
+    if let (true, Some(actor_id)) = (email, actor_id) {
+        send_email(state, user_id, kind, actor_id, &data).await?;
+    }
+
+    // Push has no delivery yet; the setting is kept for when it does
+
+    Ok(())
+}
+
+/// The user's most recent notifications, newest first, read or not
+pub async fn list(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+) -> Result<impl IntoResponse, ApiError> {
+    let notifications = sqlx::query_as!(
+        Notification,
+        r#"
+        SELECT n.id, n.kind, n.actor_id, n.data, n.read_at, n.created_at,
+               u.username AS "actor_username?", u.display_name AS "actor_name?",
+               u.avatar_url AS actor_avatar
+        FROM notifications n
+        LEFT JOIN users u ON u.id = n.actor_id
+        WHERE n.user_id = $1
+        ORDER BY n.created_at DESC
+        LIMIT $2
+        "#,
+        user.id,
+        RECENT_NOTIFICATIONS
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(notifications))
+}
+
+/// Polled by the frontend (every 30 seconds per tab), so it only touches the partial index
+pub async fn unread_count(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+) -> Result<impl IntoResponse, ApiError> {
+    let count = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "count!" FROM notifications WHERE user_id = $1 AND read_at IS NULL"#,
+        user.id
+    )
+    .fetch_one(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(serde_json::json!({ "count": count })))
+}
+
+/// Marking an already read notification again is fine; someone else's is a 404
+pub async fn mark_read(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE notifications SET read_at = COALESCE(read_at, NOW())
+        WHERE id = $1 AND user_id = $2
+        "#,
+        id,
+        user.id
+    )
+    .execute(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    if result.rows_affected() == 0 {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            &i18n::NOTIFICATION_NOT_FOUND,
+        ));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Removes it for good. Someone else's is a 404.
+pub async fn delete(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let result = sqlx::query!(
+        "DELETE FROM notifications WHERE id = $1 AND user_id = $2",
+        id,
+        user.id
+    )
+    .execute(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    if result.rows_affected() == 0 {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            &i18n::NOTIFICATION_NOT_FOUND,
+        ));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn mark_all_read(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+) -> Result<impl IntoResponse, ApiError> {
+    let result = sqlx::query!(
+        "UPDATE notifications SET read_at = NOW() WHERE user_id = $1 AND read_at IS NULL",
+        user.id
+    )
+    .execute(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(
+        serde_json::json!({ "marked": result.rows_affected() }),
+    ))
+}
+
+fn retention_days(var: &str, default: i32) -> i32 {
+    std::env::var(var)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|days| *days > 0)
+        .unwrap_or(default)
+}
+
+/// Delete read notifications older than 90 days and unread ones older than a year
+/// (NOTIFICATION_RETENTION_READ_DAYS and NOTIFICATION_RETENTION_UNREAD_DAYS).
+/// Broadcast reports count what's left, so they shrink as old notifications go.
+pub async fn purge_old(pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM notifications
+        WHERE (read_at IS NOT NULL AND created_at < NOW() - make_interval(days => $1))
+           OR (read_at IS NULL AND created_at < NOW() - make_interval(days => $2))
+        "#,
+        retention_days(
+            "NOTIFICATION_RETENTION_READ_DAYS",
+            DEFAULT_READ_RETENTION_DAYS
+        ),
+        retention_days(
+            "NOTIFICATION_RETENTION_UNREAD_DAYS",
+            DEFAULT_UNREAD_RETENTION_DAYS
+        )
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+// --- Preferences --- //
+
+async fn load_channels(
+    pool: &PgPool,
+    user_id: Uuid,
+    kind: NotificationKind,
+) -> Result<Channels, sqlx::Error> {
+    let channels = sqlx::query_as!(
+        Channels,
+        r#"
+        SELECT in_app, email, push FROM notification_preferences
+        WHERE user_id = $1 AND kind = $2
+        "#,
+        user_id,
+        kind.as_str()
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(channels.unwrap_or_else(|| kind.default_channels()))
+}
+
+/// Every kind, with the user's channels or the defaults
+async fn load_preferences(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<BTreeMap<&'static str, Channels>, sqlx::Error> {
+    let saved = sqlx::query!(
+        "SELECT kind, in_app, email, push FROM notification_preferences WHERE user_id = $1",
+        user_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut prefs: BTreeMap<_, _> = NotificationKind::ALL
+        .iter()
+        .map(|kind| (kind.as_str(), kind.default_channels()))
+        .collect();
+    for row in saved {
+        // Rows for kinds that no longer exist are ignored
+        if let Ok(kind) = NotificationKind::from_str(&row.kind) {
+            prefs.insert(
+                kind.as_str(),
+                Channels {
+                    in_app: row.in_app,
+                    email: row.email,
+                    push: row.push,
+                },
+            );
+        }
+    }
+
+    Ok(prefs)
+}
+
+/// `{ "application": { "in_app": true, "email": false, "push": true }, ... }`
+pub async fn get_preferences(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+) -> Result<impl IntoResponse, ApiError> {
+    let prefs = load_preferences(&pool, user.id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(prefs))
+}
+
+/// Channels left out keep their current value
+#[derive(Deserialize)]
+pub struct ChannelsUpdate {
+    pub in_app: Option<bool>,
+    pub email: Option<bool>,
+    pub push: Option<bool>,
+}
+
+/// Keyed by kind, e.g. `{ "application": { "email": true } }`
+#[derive(Deserialize)]
+#[serde(transparent)]
+pub struct UpdateNotificationPreferences(pub HashMap<String, ChannelsUpdate>);
+
+impl Validate for UpdateNotificationPreferences {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        for kind in self.0.keys() {
+            if NotificationKind::from_str(kind).is_err() {
+                errors.add("kind", format!("Unknown notification type {:?}", kind));
+            }
+        }
+        errors.into_result()
+    }
+}
+
+pub async fn update_preferences(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+    ValidatedJson(payload): ValidatedJson<UpdateNotificationPreferences>,
+) -> Result<impl IntoResponse, ApiError> {
+    for (kind, update) in payload.0 {
+        let kind = NotificationKind::from_str(&kind).expect("validated above");
+        let mut channels = load_channels(&pool, user.id, kind)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        channels.in_app = update.in_app.unwrap_or(channels.in_app);
+        channels.email = update.email.unwrap_or(channels.email);
+        channels.push = update.push.unwrap_or(channels.push);
+
+        sqlx::query!(
+            r#"
+            INSERT INTO notification_preferences (user_id, kind, in_app, email, push, updated_at)
+            VALUES ($1, $2, $3, $4, $5, NOW())
+            ON CONFLICT (user_id, kind) DO UPDATE
+            SET in_app = $3, email = $4, push = $5, updated_at = NOW()
+            "#,
+            user.id,
+            kind.as_str(),
+            channels.in_app,
+            channels.email,
+            channels.push
+        )
+        .execute(&pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    }
+
+    let prefs = load_preferences(&pool, user.id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(prefs))
+}
+
+// --- Email --- //
+
+#[derive(Template)]
+#[template(path = "email/notification.html")]
+struct NotificationHtml<'a> {
+    t: &'a EmailStrings,
+    actor: &'a str,
+    action: &'a str,
+    target: &'a str,
+    link: &'a str,
+    button: &'a str,
+    unsubscribe_url: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "email/notification.txt")]
+struct NotificationText<'a> {
+    t: &'a EmailStrings,
+    actor: &'a str,
+    action: &'a str,
+    target: &'a str,
+    link: &'a str,
+    unsubscribe_url: &'a str,
+}
+
+// Only verified addresses get notification email, like the digest
+async fn send_email(
+    state: &AppState,
+    user_id: Uuid,
+    kind: NotificationKind,
+    actor_id: Uuid,
+    data: &serde_json::Value,
+) -> Result<(), String> {
+    let recipient = sqlx::query!(
+        r#"
+        SELECT l.email, u.username, u.locale,
+               (SELECT username FROM users WHERE id = $2) AS "actor!"
+        FROM users u
+        JOIN local_auths l ON l.user_id = u.id
+        WHERE u.id = $1 AND l.verified IS TRUE
+        "#,
+        user_id,
+        actor_id
+    )
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    let Some(recipient) = recipient else {
+        return Ok(());
+    };
+
+    let t = Locale::from_tag(&recipient.locale).email();
+    let Some(described) = describe(state, t, kind, &recipient.username, &recipient.actor, data)
+    else {
+        return Ok(());
+    };
+
+    email::queue_optional(
+        state,
+        kind.as_str(),
+        user_id,
+        EmailCategory::Activity,
+        |unsubscribe_url| {
+            Ok(Message {
+                to: recipient.email.clone(),
+                subject: described.subject.clone(),
+                html: email::render(NotificationHtml {
+                    t,
+                    actor: &recipient.actor,
+                    action: described.action,
+                    target: &described.target,
+                    link: &described.link,
+                    button: described.button,
+                    unsubscribe_url,
+                })?,
+                text: email::render(NotificationText {
+                    t,
+                    actor: &recipient.actor,
+                    action: described.action,
+                    target: &described.target,
+                    link: &described.link,
+                    unsubscribe_url,
+                })?,
+                unsubscribe_url: None,
+            })
+        },
+    )
+    .await
+    .map(|_| ())
+}
+
+/// How a notification reads in an email: "<actor> <action> <target>", linking to `link`
+struct Described {
+    subject: String,
+    action: &'static str,
+    target: String,
+    link: String,
+    button: &'static str,
+}
+
+/// `username` is the recipient's, for links to their own things.
+/// None for kinds that are never emailed.
+fn describe(
+    state: &AppState,
+    t: &'static EmailStrings,
+    kind: NotificationKind,
+    username: &str,
+    actor: &str,
+    data: &serde_json::Value,
+) -> Option<Described> {
+    match kind {
+        // To a listing, or else a project
+        NotificationKind::Application => {
+            let (title, link) = match data["listing_id"].as_str() {
+                Some(listing_id) => (
+                    data["listing_role"].as_str().unwrap_or_default(),
+                    format!("{}/listings/{}", state.config.frontend_url, listing_id),
+                ),
+                None => (
+                    data["project_title"].as_str().unwrap_or_default(),
+                    format!(
+                        "{}/{}/{}",
+                        state.config.frontend_url,
+                        username,
+                        data["project_slug"].as_str().unwrap_or_default()
+                    ),
+                ),
+            };
+            Some(Described {
+                subject: t.application_subject(actor, title),
+                action: t.digest_applied_to,
+                target: title.to_string(),
+                link,
+                button: t.digest_review_button,
+            })
+        }
+        NotificationKind::Endorsement => {
+            let skill = data["skill"].as_str().unwrap_or_default();
+            Some(Described {
+                subject: t.endorsement_subject(actor, skill),
+                action: t.endorsement_action,
+                target: skill.to_string(),
+                link: format!("{}/{}", state.config.frontend_url, username),
+                button: t.endorsement_button,
+            })
+        }
+        NotificationKind::EventReminder => {
+            let title = data["title"].as_str().unwrap_or_default();
+            let event_id = data["event_id"].as_str().unwrap_or_default();
+            Some(Described {
+                subject: t.event_reminder_subject(title),
+                action: t.event_reminder_action,
+                target: title.to_string(),
+                link: format!("{}/events/{}", state.config.frontend_url, event_id),
+                button: t.event_reminder_button,
+            })
+        }
+        NotificationKind::Mention => Some(Described {
+            subject: t.mention_subject(actor),
+            action: t.mention_action,
+            target: data["excerpt"].as_str().unwrap_or_default().to_string(),
+            link: format!(
+                "{}{}",
+                state.config.frontend_url,
+                data["link"].as_str().unwrap_or_default()
+            ),
+            button: t.mention_button,
+        }),
+        // The message itself stays out of email
+        NotificationKind::Message => Some(Described {
+            subject: t.message_subject(actor),
+            action: t.message_action,
+            target: String::new(),
+            link: format!(
+                "{}/messages/{}",
+                state.config.frontend_url,
+                data["conversation_id"].as_str().unwrap_or_default()
+            ),
+            button: t.message_button,
+        }),
+        NotificationKind::Broadcast | NotificationKind::SavedSearch | NotificationKind::Strike => {
+            None
+        }
+    }
+}
+
+// --- Unread summary --- //
+
+struct UnreadItem {
+    actor: String,
+    action: &'static str,
+    target: String,
+    link: String,
+}
+
+#[derive(Template)]
+#[template(path = "email/unread_notifications.html")]
+struct UnreadHtml<'a> {
+    t: &'a EmailStrings,
+    subject: &'a str,
+    items: &'a [UnreadItem],
+    dashboard_link: &'a str,
+    unsubscribe_url: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "email/unread_notifications.txt")]
+struct UnreadText<'a> {
+    t: &'a EmailStrings,
+    items: &'a [UnreadItem],
+    dashboard_link: &'a str,
+    unsubscribe_url: &'a str,
+}
+
+/// Email each user one summary of their in-app notifications still unread a few hours on,
+/// for those who don't keep the app open. Each notification is emailed at most once, and
+/// not at all if it already was when it arrived (its type's `email` channel). Waits out the
+/// user's quiet hours, and follows the `activity` email preference. Returns how many were queued.
+pub async fn email_unread(
+    state: &AppState,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<u64, String> {
+    // Claimed like the digest: two instances never both send one, and a summary that
+    // then fails to queue is dropped rather than sent twice
+    let rows = sqlx::query!(
+        r#"
+        UPDATE notifications n
+        SET emailed_at = $1::timestamptz
+        FROM users u
+        JOIN local_auths l ON l.user_id = u.id
+        LEFT JOIN email_preferences p ON p.user_id = u.id
+        WHERE n.user_id = u.id
+          AND n.read_at IS NULL
+          AND n.emailed_at IS NULL
+          -- Broadcasts reach everyone at once; summarising them would be a mass email
+          AND n.broadcast_id IS NULL
+          AND n.created_at <= $1::timestamptz - make_interval(hours => $2)
+          AND n.created_at > $1::timestamptz - make_interval(hours => $3)
+          AND l.verified IS TRUE
+          AND u.banned_at IS NULL
+          AND COALESCE(p.activity, TRUE)
+          AND NOT CASE
+              WHEN p.quiet_hours_start < p.quiet_hours_end THEN
+                  EXTRACT(HOUR FROM $1::timestamptz AT TIME ZONE p.timezone) >= p.quiet_hours_start
+                  AND EXTRACT(HOUR FROM $1::timestamptz AT TIME ZONE p.timezone) < p.quiet_hours_end
+              WHEN p.quiet_hours_start > p.quiet_hours_end THEN
+                  EXTRACT(HOUR FROM $1::timestamptz AT TIME ZONE p.timezone) >= p.quiet_hours_start
+                  OR EXTRACT(HOUR FROM $1::timestamptz AT TIME ZONE p.timezone) < p.quiet_hours_end
+              ELSE FALSE
+          END
+        RETURNING n.user_id, n.kind, n.data, n.created_at, l.email, u.username, u.locale,
+                  (SELECT username FROM users WHERE id = n.actor_id) AS actor
+        "#,
+        now,
+        UNREAD_EMAIL_AFTER_HOURS,
+        UNREAD_EMAIL_MAX_AGE_HOURS
+    )
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let mut by_user: BTreeMap<Uuid, Vec<_>> = BTreeMap::new();
+    for row in rows {
+        by_user.entry(row.user_id).or_default().push(row);
+    }
+
+    let dashboard_link = format!("{}/dashboard", state.config.frontend_url);
+    let mut sent = 0;
+    for (user_id, mut rows) in by_user {
+        rows.sort_by_key(|row| std::cmp::Reverse(row.created_at));
+        let recipient = &rows[0];
+        let t = Locale::from_tag(&recipient.locale).email();
+        let items: Vec<UnreadItem> = rows
+            .iter()
+            .filter_map(|row| {
+                // Kinds that no longer exist, or are never emailed, are skipped
+                let kind = NotificationKind::from_str(&row.kind).ok()?;
+                let actor = row.actor.clone().unwrap_or_default();
+                let described = describe(state, t, kind, &row.username, &actor, &row.data)?;
+                Some(UnreadItem {
+                    actor,
+                    action: described.action,
+                    target: described.target,
+                    link: described.link,
+                })
+            })
+            .collect();
+        if items.is_empty() {
+            continue;
+        }
+
+        let subject = t.unread_subject(items.len());
+        let queued = email::queue_optional(
+            state,
+            "unread_notifications",
+            user_id,
+            EmailCategory::Activity,
+            |unsubscribe_url| {
+                Ok(Message {
+                    to: recipient.email.clone(),
+                    subject: subject.clone(),
+                    html: email::render(UnreadHtml {
+                        t,
+                        subject: &subject,
+                        items: &items,
+                        dashboard_link: &dashboard_link,
+                        unsubscribe_url,
+                    })?,
+                    text: email::render(UnreadText {
+                        t,
+                        items: &items,
+                        dashboard_link: &dashboard_link,
+                        unsubscribe_url,
+                    })?,
+                    unsubscribe_url: None,
+                })
+            },
+        )
+        .await;
+
+        match queued {
+            Ok(true) => sent += 1,
+            Ok(false) => {}
+            Err(e) => tracing::error!(
+                "Failed to queue unread notifications for {}: {}",
+                recipient.username,
+                e
+            ),
+        }
+    }
+
+    Ok(sent)
+}
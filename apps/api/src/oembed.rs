@@ -0,0 +1,177 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::i18n;
+use crate::state::AppState;
+
+// How much of a post or project description goes in the embed
+const EXCERPT_CHARS: usize = 200;
+// How long consumers may cache an embed, in seconds
+const CACHE_AGE: u32 = 3600;
+
+#[derive(Deserialize)]
+pub struct OEmbedQuery {
+    pub url: String,
+    /// Only "json" is supported
+    pub format: Option<String>,
+}
+
+/// An oEmbed "link" response (https://oembed.com). `description` isn't in the spec,
+/// but unfurlers that know it show it under the title.
+#[derive(Serialize)]
+pub struct OEmbed {
+    pub version: &'static str,
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub title: String,
+    pub description: Option<String>,
+    pub author_name: String,
+    pub author_url: String,
+    pub provider_name: &'static str,
+    pub provider_url: String,
+    pub thumbnail_url: Option<String>,
+    pub cache_age: u32,
+}
+
+/// Which frontend page a URL is
+pub enum Target {
+    /// `/{username}`
+    Profile(String),
+    /// `/{username}#post-{id}`, the link mentions use
+    Post(Uuid),
+    /// `/{username}/{slug}`
+    Project { username: String, slug: String },
+}
+
+/// Embed data for a post or project URL, for Discord, Slack and anything else
+/// that unfurls links via oEmbed
+pub async fn get_oembed(
+    State(state): State<AppState>,
+    Query(query): Query<OEmbedQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    if query.format.as_deref().is_some_and(|f| f != "json") {
+        return Err(ApiError::new(
+            StatusCode::NOT_IMPLEMENTED,
+            &i18n::JSON_FORMAT_ONLY,
+        ));
+    }
+
+    let not_found = || ApiError::new(StatusCode::NOT_FOUND, &i18n::NOTHING_TO_EMBED);
+    let target = parse_target(&query.url, &state.config.frontend_origins).ok_or_else(not_found)?;
+    let frontend_url = &state.config.frontend_url;
+
+    let embed = match target {
+        Target::Profile(_) => return Err(not_found()),
+        Target::Post(id) => {
+            let post = sqlx::query!(
+                r#"
+                SELECT p.content, p.image_url, u.display_name, u.username, u.avatar_url
+                FROM posts p
+                JOIN users u ON u.id = p.author_id
+                WHERE p.id = $1 AND u.banned_at IS NULL AND p.held_at IS NULL
+                "#,
+                id
+            )
+            .fetch_optional(&state.pool)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+            .ok_or_else(not_found)?;
+
+            OEmbed {
+                version: "1.0",
+                kind: "link",
+                title: format!("{} (@{})", post.display_name, post.username),
+                description: Some(excerpt(&post.content)),
+                author_url: format!("{}/{}", frontend_url, post.username),
+                author_name: post.display_name,
+                provider_name: "Praxis",
+                provider_url: frontend_url.clone(),
+                thumbnail_url: post.image_url.or(post.avatar_url),
+                cache_age: CACHE_AGE,
+            }
+        }
+        Target::Project { username, slug } => {
+            let project = sqlx::query!(
+                r#"
+                SELECT p.title, p.description, p.image_url, u.display_name, u.username, u.avatar_url
+                FROM projects p
+                JOIN users u ON u.id = p.owner_id
+                WHERE u.username = $1 AND p.slug = $2 AND u.banned_at IS NULL
+                  AND p.held_at IS NULL
+                "#,
+                username,
+                slug
+            )
+            .fetch_optional(&state.pool)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+            .ok_or_else(not_found)?;
+
+            OEmbed {
+                version: "1.0",
+                kind: "link",
+                title: project.title,
+                description: project.description.as_deref().map(excerpt),
+                author_url: format!("{}/{}", frontend_url, project.username),
+                author_name: project.display_name,
+                provider_name: "Praxis",
+                provider_url: frontend_url.clone(),
+                thumbnail_url: project.image_url.or(project.avatar_url),
+                cache_age: CACHE_AGE,
+            }
+        }
+    };
+
+    Ok(Json(embed))
+}
+
+/// Only URLs on one of the frontend's own origins are embeddable
+fn parse_target(url: &str, frontend_origins: &[String]) -> Option<Target> {
+    let url = Url::parse(url).ok()?;
+    let origin = url.origin().ascii_serialization();
+    if !frontend_origins
+        .iter()
+        .any(|o| o.trim_end_matches('/') == origin)
+    {
+        return None;
+    }
+
+    route(&url)
+}
+
+/// The page a frontend URL is, going by its path and fragment alone
+pub fn route(url: &Url) -> Option<Target> {
+    let segments: Vec<&str> = url
+        .path_segments()?
+        .filter(|segment| !segment.is_empty())
+        .collect();
+    match segments[..] {
+        [username] => match url.fragment().and_then(|f| f.strip_prefix("post-")) {
+            Some(id) => Uuid::parse_str(id).ok().map(Target::Post),
+            None => Some(Target::Profile(username.to_string())),
+        },
+        [username, slug] => Some(Target::Project {
+            username: username.to_string(),
+            slug: slug.to_string(),
+        }),
+        _ => None,
+    }
+}
+
+pub fn excerpt(text: &str) -> String {
+    let text = text.trim();
+    if text.chars().count() <= EXCERPT_CHARS {
+        return text.to_string();
+    }
+    let cut: String = text.chars().take(EXCERPT_CHARS).collect();
+    format!("{}...", cut.trim_end())
+}
+
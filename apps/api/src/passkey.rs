@@ -3,7 +3,7 @@ use argon2::{
     Argon2,
 };
 use axum::{
-    extract::{ConnectInfo, State},
+    extract::State,
     http::{HeaderMap, StatusCode},
     response::IntoResponse,
     Json,
@@ -12,11 +12,17 @@ use base64::Engine;
 use oauth2::url::Url;
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
-use std::net::SocketAddr;
 use tower_sessions::Session;
 use uuid::Uuid;
 use webauthn_rs::prelude::*;
 
+use crate::client_ip::ClientIp;
+use crate::error::ApiError;
+use crate::extractors::AuthUser;
+use crate::geoip::GeoIp;
+use crate::i18n;
+use crate::validation::{Validate, ValidatedJson, ValidationErrors};
+
 // WebAuthn configuration builder
 fn create_webauthn() -> Result<Webauthn, WebauthnError> {
     let rp_origin =
@@ -52,6 +58,16 @@ pub struct FinishRegistrationRequest {
     pub name: Option<String>,
 }
 
+impl Validate for FinishRegistrationRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        if let Some(name) = &self.name {
+            errors.length("name", name, 0, 50);
+        }
+        errors.into_result()
+    }
+}
+
 #[derive(Deserialize)]
 pub struct FinishAuthRequest {
     pub credential: PublicKeyCredential,
@@ -65,15 +81,10 @@ pub struct StartRegistrationRequest {
 // Start passkey registration (user must be logged in)
 pub async fn start_registration(
     State(pool): State<PgPool>,
+    AuthUser { id: user_id, .. }: AuthUser,
     session: Session,
     Json(payload): Json<StartRegistrationRequest>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    let user_id: Uuid = session
-        .get("user_id")
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-        .ok_or((StatusCode::UNAUTHORIZED, "Not logged in".to_string()))?;
-
+) -> Result<impl IntoResponse, ApiError> {
     let user = sqlx::query!(
         "SELECT username, display_name FROM users WHERE id = $1",
         user_id
@@ -95,21 +106,22 @@ pub async fn start_registration(
         // User has a local_auth record (email/password or email verified)
         // If they have a password hash, we must verify it.
         if !auth_rec.password_hash.is_empty() {
-            let password = payload
-                .password
-                .ok_or((StatusCode::UNAUTHORIZED, "Password required".to_string()))?;
+            let password = payload.password.ok_or(ApiError::new(
+                StatusCode::UNAUTHORIZED,
+                &i18n::PASSWORD_REQUIRED,
+            ))?;
 
             let parsed_hash = PasswordHash::new(&auth_rec.password_hash).map_err(|e| {
                 tracing::error!("Corrupted password hash for user {}: {}", user_id, e);
-                (
+                ApiError::new(
                     StatusCode::INTERNAL_SERVER_ERROR,
-                    "Account password data is invalid. Please reset your password to fix this issue.".to_string(),
+                    &i18n::PASSWORD_DATA_INVALID,
                 )
             })?;
 
             Argon2::default()
                 .verify_password(password.as_bytes(), &parsed_hash)
-                .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid password".to_string()))?;
+                .map_err(|_| ApiError::new(StatusCode::UNAUTHORIZED, &i18n::INVALID_PASSWORD))?;
         }
     }
 
@@ -148,22 +160,17 @@ pub async fn start_registration(
 // Finish passkey registration
 pub async fn finish_registration(
     State(pool): State<PgPool>,
+    AuthUser { id: user_id, .. }: AuthUser,
     session: Session,
-    Json(payload): Json<FinishRegistrationRequest>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    let user_id: Uuid = session
-        .get("user_id")
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-        .ok_or((StatusCode::UNAUTHORIZED, "Not logged in".to_string()))?;
-
+    ValidatedJson(payload): ValidatedJson<FinishRegistrationRequest>,
+) -> Result<impl IntoResponse, ApiError> {
     let state_json: String = session
         .get("passkey_reg_state")
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-        .ok_or((
+        .ok_or(ApiError::new(
             StatusCode::BAD_REQUEST,
-            "No registration in progress".to_string(),
+            &i18n::NO_PASSKEY_REGISTRATION,
         ))?;
 
     let reg_state: PasskeyRegistration = serde_json::from_str(&state_json)
@@ -175,10 +182,8 @@ pub async fn finish_registration(
     let passkey = webauthn
         .finish_passkey_registration(&payload.credential, &reg_state)
         .map_err(|e| {
-            (
-                StatusCode::BAD_REQUEST,
-                format!("Registration failed: {}", e),
-            )
+            ApiError::new(StatusCode::BAD_REQUEST, &i18n::PASSKEY_REGISTRATION_FAILED)
+                .arg("reason", e)
         })?;
 
     // Serialize the entire passkey for storage
@@ -207,7 +212,7 @@ pub async fn finish_registration(
 pub async fn start_authentication(
     State(pool): State<PgPool>,
     session: Session,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+) -> Result<impl IntoResponse, ApiError> {
     // Get all passkeys for discoverable auth
     let all_passkeys: Vec<Passkey> = sqlx::query!("SELECT public_key FROM passkey_credentials")
         .fetch_all(&pool)
@@ -218,10 +223,7 @@ pub async fn start_authentication(
         .collect();
 
     if all_passkeys.is_empty() {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            "No passkeys registered".to_string(),
-        ));
+        return Err(ApiError::new(StatusCode::BAD_REQUEST, &i18n::NO_PASSKEYS));
     }
 
     let webauthn =
@@ -245,18 +247,19 @@ pub async fn start_authentication(
 // Finish passkey authentication
 pub async fn finish_authentication(
     State(pool): State<PgPool>,
+    State(geoip): State<GeoIp>,
     session: Session,
     headers: HeaderMap,
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    ClientIp(ip): ClientIp,
     Json(payload): Json<FinishAuthRequest>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+) -> Result<impl IntoResponse, ApiError> {
     let state_json: String = session
         .get("passkey_auth_state")
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-        .ok_or((
+        .ok_or(ApiError::new(
             StatusCode::BAD_REQUEST,
-            "No authentication in progress".to_string(),
+            &i18n::NO_PASSKEY_AUTHENTICATION,
         ))?;
 
     let auth_state: PasskeyAuthentication = serde_json::from_str(&state_json)
@@ -307,7 +310,10 @@ pub async fn finish_authentication(
 
     if stored.is_none() {
         tracing::error!("Credential not found: {}", hex::encode(&cred_id_bytes));
-        return Err((StatusCode::UNAUTHORIZED, "Unknown credential".to_string()));
+        return Err(ApiError::new(
+            StatusCode::UNAUTHORIZED,
+            &i18n::UNKNOWN_CREDENTIAL,
+        ));
     }
 
     let stored = stored.unwrap();
@@ -321,10 +327,11 @@ pub async fn finish_authentication(
     let auth_result = webauthn
         .finish_passkey_authentication(&payload.credential, &auth_state)
         .map_err(|e| {
-            (
+            ApiError::new(
                 StatusCode::UNAUTHORIZED,
-                format!("Authentication failed: {}", e),
+                &i18n::PASSKEY_AUTHENTICATION_FAILED,
             )
+            .arg("reason", e)
         })?;
 
     // Update the passkey with new counter
@@ -341,9 +348,16 @@ pub async fn finish_authentication(
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    // Set user session
+    // Banned/suspended accounts can't log in
+    crate::user::ensure_can_login(&pool, stored.user_id).await?;
+
+    // Set user session, under a new session ID
+    crate::session::rotate_id(&pool, &session)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
     session
-        .insert("user_id", stored.user_id.to_string())
+        .insert("user_id", stored.user_id)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
@@ -359,10 +373,11 @@ pub async fn finish_authentication(
         let expires_at = chrono::Utc::now() + chrono::Duration::hours(24);
         crate::session::create_session(
             &pool,
+            &geoip,
             stored.user_id,
             session_id.to_string(),
             &headers,
-            Some(addr.ip().to_string()),
+            Some(ip.to_string()),
             expires_at,
         )
         .await
@@ -381,14 +396,8 @@ pub async fn finish_authentication(
 // List user's passkeys
 pub async fn list_passkeys(
     State(pool): State<PgPool>,
-    session: Session,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    let user_id: Uuid = session
-        .get("user_id")
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-        .ok_or((StatusCode::UNAUTHORIZED, "Not logged in".to_string()))?;
-
+    AuthUser { id: user_id, .. }: AuthUser,
+) -> Result<impl IntoResponse, ApiError> {
     let passkeys = sqlx::query_as!(
         PasskeyInfo,
         r#"SELECT id, name, created_at as "created_at!", last_used_at FROM passkey_credentials WHERE user_id = $1 ORDER BY created_at DESC"#,
@@ -404,15 +413,9 @@ pub async fn list_passkeys(
 // Delete a passkey
 pub async fn delete_passkey(
     State(pool): State<PgPool>,
-    session: Session,
+    AuthUser { id: user_id, .. }: AuthUser,
     axum::extract::Path(passkey_id): axum::extract::Path<Uuid>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    let user_id: Uuid = session
-        .get("user_id")
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-        .ok_or((StatusCode::UNAUTHORIZED, "Not logged in".to_string()))?;
-
+) -> Result<impl IntoResponse, ApiError> {
     let result = sqlx::query!(
         "DELETE FROM passkey_credentials WHERE id = $1 AND user_id = $2",
         passkey_id,
@@ -423,17 +426,17 @@ pub async fn delete_passkey(
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
     if result.rows_affected() == 0 {
-        return Err((StatusCode::NOT_FOUND, "Passkey not found".to_string()));
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            &i18n::PASSKEY_NOT_FOUND,
+        ));
     }
 
     Ok(Json(serde_json::json!({ "success": true })))
 }
 
 // Helper to get user's passkeys
-async fn get_user_passkeys(
-    pool: &PgPool,
-    user_id: Uuid,
-) -> Result<Vec<Passkey>, (StatusCode, String)> {
+async fn get_user_passkeys(pool: &PgPool, user_id: Uuid) -> Result<Vec<Passkey>, ApiError> {
     let rows = sqlx::query!(
         "SELECT public_key FROM passkey_credentials WHERE user_id = $1",
         user_id
@@ -1,9 +1,20 @@
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 use tower_sessions::Session;
+use crate::batch::{self, BatchRequest};
+use crate::db::DbRouter;
+use crate::error::ApiError;
+use crate::extractors::{AuthUser, VerifiedUser};
+use crate::i18n;
+use crate::mentions::MentionSource;
+use crate::quotas::Quota;
+use crate::spam::Content;
+use crate::ws::Event;
+use crate::state::AppState;
+use crate::validation::{normalize_text, Validate, ValidatedJson, ValidationErrors};
 use axum::{extract::{State, Path}, http::StatusCode, response::IntoResponse,Json};
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 pub struct PostWithAuthor {
     pub id: uuid::Uuid,
     pub content: String,
@@ -21,10 +32,26 @@ pub struct CreatePostRequest {
     pub image_url: Option<String>,
 }
 
+// The length limit is a site setting, so it's checked in the handler
+impl Validate for CreatePostRequest {
+    fn normalize(&mut self) {
+        normalize_text(&mut self.content);
+    }
+
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.required("content", &self.content);
+        if let Some(image_url) = &self.image_url {
+            errors.url("image_url", image_url);
+        }
+        errors.into_result()
+    }
+}
+
 /// List all posts with author info (newest first)
 pub async fn list(
     State(pool): State<PgPool>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+) -> Result<impl IntoResponse, ApiError> {
     let posts = sqlx::query_as!(
         PostWithAuthor,
         r#"
@@ -39,6 +66,7 @@ pub async fn list(
             u.avatar_url as author_avatar
         FROM posts p
         JOIN users u ON p.author_id = u.id
+        WHERE u.banned_at IS NULL AND p.held_at IS NULL
         ORDER BY p.created_at DESC
         "#
     )
@@ -49,10 +77,12 @@ pub async fn list(
     Ok(Json(posts))
 }
 
+/// A user's posts, including any held for review if they're the one looking
 pub async fn list_by_user(
     State(pool): State<PgPool>,
+    viewer: Option<AuthUser>,
     Path(username): Path<String>
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+) -> Result<impl IntoResponse, ApiError> {
     let posts = sqlx::query_as!(
         PostWithAuthor,
         r#"
@@ -67,10 +97,12 @@ pub async fn list_by_user(
             u.avatar_url as author_avatar
         FROM posts p
         JOIN users u ON p.author_id = u.id
-        WHERE u.username = $1
+        WHERE u.username = $1 AND u.banned_at IS NULL
+          AND (p.held_at IS NULL OR p.author_id = $2)
         ORDER BY p.created_at DESC
         "#,
-        username
+        username,
+        viewer.map(|viewer| viewer.id)
     )
     .fetch_all(&pool)
     .await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
@@ -79,39 +111,136 @@ pub async fn list_by_user(
 }
 
 
+/// Up to 100 posts by ID at once, keyed by ID (null for deleted or held posts or banned authors)
+pub async fn batch(
+    State(db): State<DbRouter>,
+    ValidatedJson(payload): ValidatedJson<BatchRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let posts = sqlx::query_as!(
+        PostWithAuthor,
+        r#"
+        SELECT
+            p.id,
+            p.content,
+            p.image_url,
+            p.created_at,
+            p.author_id,
+            u.display_name as author_name,
+            u.username as author_username,
+            u.avatar_url as author_avatar
+        FROM posts p
+        JOIN users u ON p.author_id = u.id
+        WHERE p.id = ANY($1) AND u.banned_at IS NULL AND p.held_at IS NULL
+        "#,
+        &payload.ids
+    )
+    .fetch_all(db.read())
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let posts = posts.into_iter().map(|post| (post.id, post));
+    Ok(Json(batch::by_id(&payload.ids, posts)))
+}
+
 /// Create a new post (requires login)
 pub async fn create(
-    State(pool): State<PgPool>,
-    session: Session,
-    Json(payload): Json<CreatePostRequest>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    // Get logged in user ID
-    let user_id: uuid::Uuid = match session.get("user_id").await {
-        Ok(Some(id)) => id,
-        Ok(None) => return Err((StatusCode::UNAUTHORIZED, "Not logged in".to_string())),
-        Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
-    };
-
-    // Validate content is not empty
-    if payload.content.trim().is_empty() {
-        return Err((StatusCode::BAD_REQUEST, "Content cannot be empty".to_string()));
+    State(state): State<AppState>,
+    VerifiedUser(AuthUser { id: user_id, role }): VerifiedUser,
+    ValidatedJson(payload): ValidatedJson<CreatePostRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let settings = crate::settings::get(&state).await;
+    if payload.content.chars().count() as i64 > settings.max_post_length {
+        return Err(ApiError::new(StatusCode::BAD_REQUEST, &i18n::POST_TOO_LONG)
+            .arg("max", settings.max_post_length));
     }
 
-    // Create post
+    crate::quotas::check(&state.pool, &settings, user_id, &role, Quota::Posts).await?;
+    if let Some(image_url) = payload.image_url.as_deref() {
+        crate::upload::check_owned(&state.pool, user_id, image_url).await?;
+    }
+
+    // Keeps fresh spam accounts from posting links
+    if settings.min_reputation_for_links > 0
+        && !crate::admin::can_moderate(&role)
+        && crate::reputation::contains_link(&payload.content)
+    {
+        let reputation = sqlx::query_scalar!("SELECT reputation FROM users WHERE id = $1", user_id)
+            .fetch_one(&state.pool)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        if i64::from(reputation) < settings.min_reputation_for_links {
+            return Err(
+                ApiError::new(StatusCode::FORBIDDEN, &i18n::REPUTATION_REQUIRED_FOR_LINKS)
+                    .arg("reputation", settings.min_reputation_for_links),
+            );
+        }
+    }
+
+    let held = crate::spam::check(
+        &state,
+        &settings,
+        user_id,
+        &role,
+        Content::Post,
+        &payload.content,
+        &[],
+    )
+    .await?;
+
+    // Create post, and its place in the feed
     let post = sqlx::query!(
         r#"
-        INSERT INTO posts (author_id, content, image_url)
-        VALUES ($1, $2, $3)
-        RETURNING id, created_at
+        WITH post AS (
+            INSERT INTO posts (author_id, content, image_url, held_at, held_reasons)
+            VALUES ($1, $2, $3, CASE WHEN $4::text[] IS NOT NULL THEN NOW() END, $4)
+            RETURNING id, author_id, created_at
+        ), item AS (
+            INSERT INTO feed_items (post_id, author_id, created_at)
+            SELECT id, author_id, created_at FROM post
+        )
+        SELECT id as "id!", created_at as "created_at!",
+               (SELECT username FROM users WHERE id = $1) AS "author_username!"
+        FROM post
         "#,
         user_id,
         payload.content,
-        payload.image_url
+        payload.image_url,
+        held.as_deref()
     )
-    .fetch_one(&pool)
+    .fetch_one(&state.pool)
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
+    // Keep the attached image from being garbage collected
+    if let Some(image_url) = payload.image_url.as_deref() {
+        if let Err(e) = crate::upload::mark_attached(&state.pool, user_id, &[image_url]).await {
+            tracing::error!("Failed to mark post image as attached: {}", e);
+        }
+    }
+
+    // Held posts look posted to their author; the rest happens once a moderator approves them
+    if held.is_none() {
+        crate::feed::invalidate(&*state.cache).await;
+        state.search_index.sync_post(&state.pool, post.id).await;
+        state.realtime.send_to_all(Event::FeedItem {
+            item_type: "post",
+            id: post.id,
+            author_id: user_id,
+        });
+
+        crate::mentions::notify(
+            &state,
+            MentionSource {
+                content_type: "post",
+                content_id: post.id,
+                author_id: user_id,
+                link: format!("/{}#post-{}", post.author_username, post.id),
+                text: &payload.content,
+            },
+        )
+        .await;
+    }
+
     Ok((
         StatusCode::CREATED,
         Json(serde_json::json!({
@@ -120,3 +249,55 @@ pub async fn create(
         })),
     ))
 }
+
+/// Delete a post (author, moderator or admin), along with its image
+pub async fn delete(
+    State(state): State<AppState>,
+    AuthUser { id: user_id, role }: AuthUser,
+    session: Session,
+    Path(post_id): Path<uuid::Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let post = sqlx::query!(
+        "SELECT author_id, image_url FROM posts WHERE id = $1",
+        post_id
+    )
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or(ApiError::new(StatusCode::NOT_FOUND, &i18n::POST_NOT_FOUND))?;
+
+    if post.author_id != user_id && !crate::admin::can_moderate(&role) {
+        return Err(ApiError::new(StatusCode::FORBIDDEN, &i18n::NOT_YOUR_POST));
+    }
+
+    sqlx::query!("DELETE FROM posts WHERE id = $1", post_id)
+        .execute(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    crate::feed::invalidate(&*state.cache).await;
+    state.search_index.sync_post(&state.pool, post_id).await;
+
+    // Removing someone else's post is a moderation action
+    if post.author_id != user_id {
+        let details = format!("post {}", post_id);
+        crate::audit::record(
+            &state.pool,
+            &session,
+            user_id,
+            "moderation.post_deleted",
+            Some(post.author_id),
+            Some(&details),
+        )
+        .await?;
+    }
+
+    // Best effort: anything left behind is picked up by the orphan cleanup
+    if let Some(image_url) = post.image_url.as_deref() {
+        if let Err(e) = crate::upload::release_uploads(&state, post.author_id, &[image_url]).await {
+            tracing::error!("Failed to delete post image: {}", e);
+        }
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,212 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::Serialize;
+use sqlx::PgPool;
+use tower_sessions::Session;
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::extractors::{AuthUser, ModeratorUser};
+use crate::i18n;
+use crate::state::AppState;
+use crate::user::invalidate_profile;
+
+// How many of a user's past profiles the history endpoints return
+const HISTORY_LIMIT: i64 = 50;
+
+/// A profile as it was before a change. `created_at` is when it was replaced.
+#[derive(Serialize)]
+pub struct ProfileRevision {
+    pub id: Uuid,
+    pub changed_by: Option<Uuid>,
+    pub username: String,
+    pub display_name: String,
+    pub bio: Option<String>,
+    pub location: Option<String>,
+    pub website: Option<String>,
+    pub pronouns: Option<String>,
+    pub major: Option<String>,
+    pub avatar_url: Option<String>,
+    pub avatar_original_url: Option<String>,
+    pub avatar_crop_x: Option<f64>,
+    pub avatar_crop_y: Option<f64>,
+    pub avatar_zoom: Option<f64>,
+    pub banner_url: Option<String>,
+    pub banner_original_url: Option<String>,
+    pub banner_crop_x: Option<f64>,
+    pub banner_crop_y: Option<f64>,
+    pub banner_zoom: Option<f64>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// The logged in user's earlier profiles, newest first
+pub async fn list_mine(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+) -> Result<impl IntoResponse, ApiError> {
+    Ok(Json(history(&pool, user.id).await?))
+}
+
+/// Put the logged in user's profile back the way it was before a change
+pub async fn revert_mine(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(revision_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    revert(&state, user.id, revision_id, user.id).await?;
+    Ok(StatusCode::OK)
+}
+
+/// A user's earlier profiles, to see what they've been called (moderators only)
+pub async fn list_for_user(
+    State(pool): State<PgPool>,
+    _: ModeratorUser,
+    Path(user_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    Ok(Json(history(&pool, user_id).await?))
+}
+
+/// Undo an abusive profile change, e.g. an offensive name (moderators only)
+pub async fn revert_for_user(
+    State(state): State<AppState>,
+    ModeratorUser(moderator): ModeratorUser,
+    session: Session,
+    Path((user_id, revision_id)): Path<(Uuid, Uuid)>,
+) -> Result<impl IntoResponse, ApiError> {
+    let target = sqlx::query!("SELECT role FROM users WHERE id = $1", user_id)
+        .fetch_optional(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or(ApiError::new(StatusCode::NOT_FOUND, &i18n::USER_NOT_FOUND))?;
+    if target.role != "user" && moderator.role != "admin" && moderator.id != user_id {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            &i18n::CANNOT_REVERT_STAFF,
+        ));
+    }
+
+    let username = revert(&state, user_id, revision_id, moderator.id).await?;
+
+    crate::audit::record(
+        &state.pool,
+        &session,
+        moderator.id,
+        "moderation.profile_reverted",
+        Some(user_id),
+        Some(&format!("to revision {} (@{})", revision_id, username)),
+    )
+    .await?;
+
+    Ok(StatusCode::OK)
+}
+
+async fn history(pool: &PgPool, user_id: Uuid) -> Result<Vec<ProfileRevision>, ApiError> {
+    sqlx::query_as!(
+        ProfileRevision,
+        r#"
+        SELECT
+            id, changed_by, username, display_name, bio, location, website, pronouns, major,
+            avatar_url, avatar_original_url, avatar_crop_x, avatar_crop_y, avatar_zoom,
+            banner_url, banner_original_url, banner_crop_x, banner_crop_y, banner_zoom, created_at
+        FROM profile_revisions
+        WHERE user_id = $1
+        ORDER BY created_at DESC
+        LIMIT $2
+        "#,
+        user_id,
+        HISTORY_LIMIT
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into())
+}
+
+/// Copy a revision's values back onto the profile, keeping the current ones as a new
+/// revision so the revert can itself be undone. Returns the restored username.
+async fn revert(
+    state: &AppState,
+    user_id: Uuid,
+    revision_id: Uuid,
+    changed_by: Uuid,
+) -> Result<String, ApiError> {
+    let reverted = sqlx::query!(
+        r#"
+        WITH target AS (
+            SELECT * FROM profile_revisions WHERE id = $1 AND user_id = $2
+        ), revision AS (
+            INSERT INTO profile_revisions (
+                user_id, changed_by, username, display_name, bio, location, website, pronouns, major,
+                avatar_url, avatar_original_url, avatar_crop_x, avatar_crop_y, avatar_zoom,
+                banner_url, banner_original_url, banner_crop_x, banner_crop_y, banner_zoom
+            )
+            SELECT
+                u.id, $3, u.username, u.display_name, u.bio, u.location, u.website, u.pronouns, u.major,
+                u.avatar_url, u.avatar_original_url, u.avatar_crop_x, u.avatar_crop_y, u.avatar_zoom,
+                u.banner_url, u.banner_original_url, u.banner_crop_x, u.banner_crop_y, u.banner_zoom
+            FROM users u
+            WHERE u.id = $2 AND EXISTS (SELECT 1 FROM target)
+        ), old AS (
+            SELECT username AS old_username FROM users WHERE id = $2
+        )
+        UPDATE users u
+        SET
+            username = t.username,
+            display_name = t.display_name,
+            bio = t.bio,
+            location = t.location,
+            website = t.website,
+            pronouns = t.pronouns,
+            major = t.major,
+            avatar_url = t.avatar_url,
+            avatar_original_url = t.avatar_original_url,
+            avatar_crop_x = t.avatar_crop_x,
+            avatar_crop_y = t.avatar_crop_y,
+            avatar_zoom = t.avatar_zoom,
+            banner_url = t.banner_url,
+            banner_original_url = t.banner_original_url,
+            banner_crop_x = t.banner_crop_x,
+            banner_crop_y = t.banner_crop_y,
+            banner_zoom = t.banner_zoom,
+            updated_at = NOW()
+        FROM target t, old
+        WHERE u.id = $2
+        RETURNING old.old_username, u.username, u.avatar_url, u.avatar_original_url,
+            u.banner_url, u.banner_original_url
+        "#,
+        revision_id,
+        user_id,
+        changed_by
+    )
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(|e| match e.as_database_error().and_then(|e| e.code()).as_deref() {
+        // Someone else has the old username now
+        Some("23505") => ApiError::new(StatusCode::CONFLICT, &i18n::USERNAME_TAKEN),
+        _ => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into(),
+    })?
+    .ok_or(ApiError::new(StatusCode::NOT_FOUND, &i18n::REVISION_NOT_FOUND))?;
+
+    invalidate_profile(&*state.cache, &reverted.old_username).await;
+    invalidate_profile(&*state.cache, &reverted.username).await;
+    state.search_index.sync_user(&state.pool, user_id).await;
+
+    // The restored images have to survive upload garbage collection again
+    let image_urls: Vec<&str> = [
+        &reverted.avatar_url,
+        &reverted.avatar_original_url,
+        &reverted.banner_url,
+        &reverted.banner_original_url,
+    ]
+    .into_iter()
+    .filter_map(|url| url.as_deref())
+    .collect();
+    if let Err(e) = crate::upload::mark_attached(&state.pool, user_id, &image_urls).await {
+        tracing::error!("Failed to mark profile images as attached: {}", e);
+    }
+
+    Ok(reverted.username)
+}
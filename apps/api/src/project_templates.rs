@@ -0,0 +1,347 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tower_sessions::Session;
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::extractors::{AdminUser, VerifiedUser};
+use crate::i18n;
+use crate::projects::{CreateProjectRequest, MAX_TAGS};
+use crate::state::AppState;
+use crate::validation::{
+    normalize_line, normalize_text, Validate, ValidatedJson, ValidationErrors,
+};
+
+const MAX_MILESTONES: usize = 20;
+
+/// A starting point for a new project, e.g. a hackathon team
+#[derive(Serialize)]
+pub struct ProjectTemplate {
+    pub id: Uuid,
+    pub name: String,
+    /// What the template is for
+    pub summary: String,
+    /// Scaffolding for the project's description
+    pub description: String,
+    pub looking_for: Vec<String>,
+    pub tags: Vec<String>,
+    pub milestones: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateTemplateRequest {
+    pub name: String,
+    pub summary: Option<String>,
+    pub description: Option<String>,
+    pub looking_for: Option<Vec<String>>,
+    pub tags: Option<Vec<String>>,
+    pub milestones: Option<Vec<String>>,
+}
+
+/// Only the fields given change
+#[derive(Deserialize)]
+pub struct UpdateTemplateRequest {
+    pub name: Option<String>,
+    pub summary: Option<String>,
+    pub description: Option<String>,
+    pub looking_for: Option<Vec<String>>,
+    pub tags: Option<Vec<String>>,
+    pub milestones: Option<Vec<String>>,
+}
+
+/// The same limits as a project, so whatever a template fills in is a valid project
+fn validate_details(
+    errors: &mut ValidationErrors,
+    summary: Option<&str>,
+    description: Option<&str>,
+    looking_for: &[String],
+    tags: &[String],
+    milestones: &[String],
+) {
+    if let Some(summary) = summary {
+        errors.length("summary", summary, 0, 300);
+    }
+    if let Some(description) = description {
+        errors.length("description", description, 0, 5000);
+    }
+    for role in looking_for {
+        errors.length("looking_for", role, 1, 50);
+    }
+    if tags.len() > MAX_TAGS {
+        errors.add("tags", format!("at most {} tags", MAX_TAGS));
+    }
+    for tag in tags {
+        errors.length("tags", tag, 1, 30);
+    }
+    if milestones.len() > MAX_MILESTONES {
+        errors.add(
+            "milestones",
+            format!("at most {} milestones", MAX_MILESTONES),
+        );
+    }
+    for milestone in milestones {
+        errors.length("milestones", milestone, 1, 100);
+    }
+}
+
+impl Validate for CreateTemplateRequest {
+    fn normalize(&mut self) {
+        normalize_line(&mut self.name);
+        self.summary.iter_mut().for_each(normalize_line);
+        self.description.iter_mut().for_each(normalize_text);
+        self.looking_for
+            .iter_mut()
+            .flatten()
+            .for_each(normalize_line);
+        self.tags.iter_mut().flatten().for_each(normalize_line);
+        self.milestones
+            .iter_mut()
+            .flatten()
+            .for_each(normalize_line);
+    }
+
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.length("name", &self.name, 1, 100);
+        validate_details(
+            &mut errors,
+            self.summary.as_deref(),
+            self.description.as_deref(),
+            self.looking_for.as_deref().unwrap_or_default(),
+            self.tags.as_deref().unwrap_or_default(),
+            self.milestones.as_deref().unwrap_or_default(),
+        );
+        errors.into_result()
+    }
+}
+
+impl Validate for UpdateTemplateRequest {
+    fn normalize(&mut self) {
+        self.name.iter_mut().for_each(normalize_line);
+        self.summary.iter_mut().for_each(normalize_line);
+        self.description.iter_mut().for_each(normalize_text);
+        self.looking_for
+            .iter_mut()
+            .flatten()
+            .for_each(normalize_line);
+        self.tags.iter_mut().flatten().for_each(normalize_line);
+        self.milestones
+            .iter_mut()
+            .flatten()
+            .for_each(normalize_line);
+    }
+
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        if let Some(name) = &self.name {
+            errors.length("name", name, 1, 100);
+        }
+        validate_details(
+            &mut errors,
+            self.summary.as_deref(),
+            self.description.as_deref(),
+            self.looking_for.as_deref().unwrap_or_default(),
+            self.tags.as_deref().unwrap_or_default(),
+            self.milestones.as_deref().unwrap_or_default(),
+        );
+        errors.into_result()
+    }
+}
+
+fn save_error(e: sqlx::Error) -> ApiError {
+    match e.as_database_error().and_then(|e| e.code()).as_deref() {
+        Some("23505") => ApiError::new(StatusCode::CONFLICT, &i18n::TEMPLATE_NAME_TAKEN),
+        _ => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into(),
+    }
+}
+
+async fn find(pool: &PgPool, template_id: Uuid) -> Result<ProjectTemplate, ApiError> {
+    sqlx::query_as!(
+        ProjectTemplate,
+        r#"
+        SELECT id, name, summary, description, looking_for, tags, milestones, created_at,
+               updated_at
+        FROM project_templates WHERE id = $1
+        "#,
+        template_id
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or(ApiError::new(
+        StatusCode::NOT_FOUND,
+        &i18n::TEMPLATE_NOT_FOUND,
+    ))
+}
+
+/// The template catalog, by name
+pub async fn list(State(pool): State<PgPool>) -> Result<impl IntoResponse, ApiError> {
+    let templates = sqlx::query_as!(
+        ProjectTemplate,
+        r#"
+        SELECT id, name, summary, description, looking_for, tags, milestones, created_at,
+               updated_at
+        FROM project_templates ORDER BY name
+        "#
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(templates))
+}
+
+/// Create a project from a template. Anything the request leaves out (description, roles,
+/// tags) comes from the template, and the project starts with the template's milestones.
+pub async fn create_project(
+    State(state): State<AppState>,
+    VerifiedUser(user): VerifiedUser,
+    Path(template_id): Path<Uuid>,
+    ValidatedJson(mut payload): ValidatedJson<CreateProjectRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let template = find(&state.pool, template_id).await?;
+
+    if payload.description.is_none() && !template.description.is_empty() {
+        payload.description = Some(template.description);
+    }
+    payload.looking_for.get_or_insert(template.looking_for);
+    payload.tags.get_or_insert(template.tags);
+
+    let project = crate::projects::insert(&state, user, payload, &template.milestones).await?;
+    Ok((StatusCode::CREATED, Json(project)))
+}
+
+/// Add a template to the catalog (admins only)
+pub async fn create(
+    State(pool): State<PgPool>,
+    AdminUser(admin): AdminUser,
+    session: Session,
+    ValidatedJson(payload): ValidatedJson<CreateTemplateRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let template = sqlx::query_as!(
+        ProjectTemplate,
+        r#"
+        INSERT INTO project_templates (name, summary, description, looking_for, tags, milestones)
+        VALUES ($1, COALESCE($2, ''), COALESCE($3, ''), $4, $5, $6)
+        RETURNING id, name, summary, description, looking_for, tags, milestones, created_at,
+                  updated_at
+        "#,
+        payload.name,
+        payload.summary,
+        payload.description,
+        payload.looking_for.as_deref().unwrap_or_default(),
+        payload.tags.as_deref().unwrap_or_default(),
+        payload.milestones.as_deref().unwrap_or_default()
+    )
+    .fetch_one(&pool)
+    .await
+    .map_err(save_error)?;
+
+    let details = format!("project template {}", template.name);
+    crate::audit::record(
+        &pool,
+        &session,
+        admin.id,
+        "admin.project_template_created",
+        None,
+        Some(&details),
+    )
+    .await?;
+
+    Ok((StatusCode::CREATED, Json(template)))
+}
+
+/// Change a template (admins only). Projects already made from it keep what they had.
+pub async fn update(
+    State(pool): State<PgPool>,
+    AdminUser(admin): AdminUser,
+    session: Session,
+    Path(template_id): Path<Uuid>,
+    ValidatedJson(payload): ValidatedJson<UpdateTemplateRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let template = sqlx::query_as!(
+        ProjectTemplate,
+        r#"
+        UPDATE project_templates
+        SET name = COALESCE($2, name),
+            summary = COALESCE($3, summary),
+            description = COALESCE($4, description),
+            looking_for = COALESCE($5, looking_for),
+            tags = COALESCE($6, tags),
+            milestones = COALESCE($7, milestones),
+            updated_at = NOW()
+        WHERE id = $1
+        RETURNING id, name, summary, description, looking_for, tags, milestones, created_at,
+                  updated_at
+        "#,
+        template_id,
+        payload.name,
+        payload.summary,
+        payload.description,
+        payload.looking_for.as_deref(),
+        payload.tags.as_deref(),
+        payload.milestones.as_deref()
+    )
+    .fetch_optional(&pool)
+    .await
+    .map_err(save_error)?
+    .ok_or(ApiError::new(
+        StatusCode::NOT_FOUND,
+        &i18n::TEMPLATE_NOT_FOUND,
+    ))?;
+
+    let details = format!("project template {}", template.name);
+    crate::audit::record(
+        &pool,
+        &session,
+        admin.id,
+        "admin.project_template_updated",
+        None,
+        Some(&details),
+    )
+    .await?;
+
+    Ok(Json(template))
+}
+
+/// Remove a template from the catalog (admins only)
+pub async fn delete(
+    State(pool): State<PgPool>,
+    AdminUser(admin): AdminUser,
+    session: Session,
+    Path(template_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let name = sqlx::query_scalar!(
+        "DELETE FROM project_templates WHERE id = $1 RETURNING name",
+        template_id
+    )
+    .fetch_optional(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or(ApiError::new(
+        StatusCode::NOT_FOUND,
+        &i18n::TEMPLATE_NOT_FOUND,
+    ))?;
+
+    let details = format!("project template {}", name);
+    crate::audit::record(
+        &pool,
+        &session,
+        admin.id,
+        "admin.project_template_deleted",
+        None,
+        Some(&details),
+    )
+    .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1,9 +1,23 @@
-use axum::{extract::{Path, State}, http::StatusCode, response::IntoResponse, Json};
+use axum::{extract::{Path, State}, http::{header, StatusCode}, response::IntoResponse, Json};
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 use tower_sessions::Session;
 
-#[derive(Serialize)]
+use crate::db::DbRouter;
+use crate::error::ApiError;
+use crate::extractors::{AuthUser, VerifiedUser};
+use crate::i18n;
+use crate::quotas::Quota;
+use crate::spam::Content;
+use crate::ws::Event;
+use crate::state::AppState;
+use crate::validation::{normalize_line, normalize_text, Validate, ValidatedJson, ValidationErrors};
+
+pub(crate) const MAX_TAGS: usize = 10;
+// Projects in the dashboard's "projects for you"
+const RECOMMENDED_LIMIT: i64 = 20;
+
+#[derive(Serialize, Deserialize)]
 pub struct ProjectWithOwner {
     pub id: uuid::Uuid,
     pub slug: String,
@@ -12,6 +26,7 @@ pub struct ProjectWithOwner {
     pub image_url: Option<String>,
     pub status: String,
     pub looking_for: Vec<String>,
+    pub tags: Vec<String>,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub owner_id: uuid::Uuid,
     pub owner_name: String,
@@ -25,6 +40,71 @@ pub struct CreateProjectRequest {
     pub description: Option<String>,
     pub image_url: Option<String>,
     pub looking_for: Option<Vec<String>>,
+    pub tags: Option<Vec<String>>,
+}
+
+#[derive(Serialize)]
+pub struct CreatedProject {
+    pub id: uuid::Uuid,
+    pub slug: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct SetStatusRequest {
+    /// open, closed (not taking applications) or completed
+    pub status: String,
+}
+
+impl Validate for SetStatusRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        if !["open", "closed", "completed"].contains(&self.status.as_str()) {
+            errors.add("status", "Must be open, closed or completed");
+        }
+        errors.into_result()
+    }
+}
+
+/// A project matching some of the viewer's skills
+#[derive(Serialize)]
+pub struct RecommendedProject {
+    #[serde(flatten)]
+    pub project: ProjectWithOwner,
+    /// The roles and tags that matched
+    pub matched: Vec<String>,
+    pub applicants: i64,
+}
+
+impl Validate for CreateProjectRequest {
+    fn normalize(&mut self) {
+        normalize_line(&mut self.title);
+        self.description.iter_mut().for_each(normalize_text);
+        self.looking_for.iter_mut().flatten().for_each(normalize_line);
+        self.tags.iter_mut().flatten().for_each(normalize_line);
+    }
+
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.length("title", &self.title, 1, 100);
+        if let Some(description) = &self.description {
+            errors.length("description", description, 0, 5000);
+        }
+        if let Some(image_url) = &self.image_url {
+            errors.url("image_url", image_url);
+        }
+        for role in self.looking_for.iter().flatten() {
+            errors.length("looking_for", role, 1, 50);
+        }
+        let tags = self.tags.as_deref().unwrap_or_default();
+        if tags.len() > MAX_TAGS {
+            errors.add("tags", format!("at most {} tags", MAX_TAGS));
+        }
+        for tag in tags {
+            errors.length("tags", tag, 1, 30);
+        }
+        errors.into_result()
+    }
 }
 
 /// Generate a URL slug from a title
@@ -46,7 +126,7 @@ fn slugify(title: &str) -> String {
 /// List all projects with owner info (newest first)
 pub async fn list(
     State(pool): State<PgPool>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+) -> Result<impl IntoResponse, ApiError> {
     let projects = sqlx::query_as!(
         ProjectWithOwner,
         r#"
@@ -58,6 +138,7 @@ pub async fn list(
             p.image_url,
             p.status,
             p.looking_for as "looking_for!: Vec<String>",
+            p.tags as "tags!: Vec<String>",
             p.created_at,
             p.owner_id,
             u.display_name as owner_name,
@@ -65,6 +146,7 @@ pub async fn list(
             u.avatar_url as owner_avatar
         FROM projects p
         JOIN users u ON p.owner_id = u.id
+        WHERE u.banned_at IS NULL AND p.held_at IS NULL
         ORDER BY p.created_at DESC
         "#
     )
@@ -75,13 +157,15 @@ pub async fn list(
     Ok(Json(projects))
 }
 
-/// Get a single project by owner username + slug
+/// Get a single project by owner username + slug. Last-Modified covers the project and
+/// how its owner is shown, so caches pick up either changing. Held projects are only
+/// found by their owner.
 pub async fn get_by_slug(
-    State(pool): State<PgPool>,
+    State(db): State<DbRouter>,
+    viewer: Option<AuthUser>,
     Path((username, slug)): Path<(String, String)>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    let project = sqlx::query_as!(
-        ProjectWithOwner,
+) -> Result<impl IntoResponse, ApiError> {
+    let p = sqlx::query!(
         r#"
         SELECT
             p.id,
@@ -90,79 +174,326 @@ pub async fn get_by_slug(
             p.description,
             p.image_url,
             p.status,
-            p.looking_for as "looking_for!: Vec<String>",
+            p.looking_for,
+            p.tags,
             p.created_at,
             p.owner_id,
             u.display_name as owner_name,
             u.username as owner_username,
-            u.avatar_url as owner_avatar
+            u.avatar_url as owner_avatar,
+            GREATEST(p.updated_at, u.updated_at) as "updated_at!"
         FROM projects p
         JOIN users u ON p.owner_id = u.id
-        WHERE u.username = $1 AND p.slug = $2
+        WHERE u.username = $1 AND p.slug = $2 AND u.banned_at IS NULL
+          AND (p.held_at IS NULL OR p.owner_id = $3)
         "#,
         username,
         slug,
+        viewer.map(|viewer| viewer.id),
+    )
+    .fetch_optional(db.read())
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or(ApiError::new(
+        StatusCode::NOT_FOUND,
+        &i18n::PROJECT_NOT_FOUND,
+    ))?;
+
+    let project = ProjectWithOwner {
+        id: p.id,
+        slug: p.slug,
+        title: p.title,
+        description: p.description,
+        image_url: p.image_url,
+        status: p.status,
+        looking_for: p.looking_for,
+        tags: p.tags,
+        created_at: p.created_at,
+        owner_id: p.owner_id,
+        owner_name: p.owner_name,
+        owner_username: p.owner_username,
+        owner_avatar: p.owner_avatar,
+    };
+
+    Ok((
+        [(header::LAST_MODIFIED, crate::etag::http_date(p.updated_at))],
+        Json(project),
+    ))
+}
+
+/// Open projects whose roles or tags match the user's skills (case-insensitively): those with
+/// fewer applicants than roles first, then the newest. Their own projects, ones they already
+/// applied to and ones from users blocked either way are left out.
+pub async fn recommended(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+) -> Result<impl IntoResponse, ApiError> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT * FROM (
+            SELECT
+                p.id,
+                p.slug,
+                p.title,
+                p.description,
+                p.image_url,
+                p.status,
+                p.looking_for,
+                p.tags,
+                p.created_at,
+                p.owner_id,
+                u.display_name as owner_name,
+                u.username as owner_username,
+                u.avatar_url as owner_avatar,
+                ARRAY(
+                    SELECT DISTINCT t FROM unnest(p.looking_for || p.tags) t
+                    WHERE lower(t) IN (SELECT lower(s) FROM unnest(me.skills) s)
+                ) as matched,
+                (SELECT COUNT(*) FROM applications a
+                 WHERE a.project_id = p.id AND a.held_at IS NULL) as applicants
+            FROM projects p
+            JOIN users u ON p.owner_id = u.id
+            JOIN users me ON me.id = $1
+            WHERE p.status = 'open'
+              AND p.owner_id <> $1
+              AND u.banned_at IS NULL
+              AND p.held_at IS NULL
+              AND NOT EXISTS (
+                  SELECT 1 FROM applications a WHERE a.project_id = p.id AND a.applicant_id = $1
+              )
+              AND NOT EXISTS (
+                  SELECT 1 FROM blocks b
+                  WHERE (b.blocker_id = $1 AND b.blocked_id = p.owner_id)
+                     OR (b.blocker_id = p.owner_id AND b.blocked_id = $1)
+              )
+        ) candidates
+        WHERE cardinality(matched) > 0
+        ORDER BY applicants < GREATEST(cardinality(looking_for), 1) DESC, created_at DESC
+        LIMIT $2
+        "#,
+        user.id,
+        RECOMMENDED_LIMIT
     )
-    .fetch_optional(&pool)
+    .fetch_all(&pool)
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    match project {
-        Some(p) => Ok(Json(p)),
-        None => Err((StatusCode::NOT_FOUND, "Project not found".to_string())),
-    }
+    let projects: Vec<RecommendedProject> = rows
+        .into_iter()
+        .map(|p| RecommendedProject {
+            project: ProjectWithOwner {
+                id: p.id,
+                slug: p.slug,
+                title: p.title,
+                description: p.description,
+                image_url: p.image_url,
+                status: p.status,
+                looking_for: p.looking_for,
+                tags: p.tags,
+                created_at: p.created_at,
+                owner_id: p.owner_id,
+                owner_name: p.owner_name,
+                owner_username: p.owner_username,
+                owner_avatar: p.owner_avatar,
+            },
+            matched: p.matched.unwrap_or_default(),
+            applicants: p.applicants.unwrap_or_default(),
+        })
+        .collect();
+
+    Ok(Json(projects))
 }
 
 /// Create a new project (requires login)
 pub async fn create(
-    State(pool): State<PgPool>,
-    session: Session,
-    Json(payload): Json<CreateProjectRequest>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    // Get logged in user ID
-    let user_id: uuid::Uuid = match session.get("user_id").await {
-        Ok(Some(id)) => id,
-        Ok(None) => return Err((StatusCode::UNAUTHORIZED, "Not logged in".to_string())),
-        Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
-    };
+    State(state): State<AppState>,
+    VerifiedUser(user): VerifiedUser,
+    ValidatedJson(payload): ValidatedJson<CreateProjectRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let project = insert(&state, user, payload, &[]).await?;
+    Ok((StatusCode::CREATED, Json(project)))
+}
 
-    // Validate title is not empty
-    if payload.title.trim().is_empty() {
-        return Err((StatusCode::BAD_REQUEST, "Title cannot be empty".to_string()));
+/// Create a project for `user`, with `milestones` in order, after the quota and spam checks
+pub(crate) async fn insert(
+    state: &AppState,
+    AuthUser { id: user_id, role }: AuthUser,
+    payload: CreateProjectRequest,
+    milestones: &[String],
+) -> Result<CreatedProject, ApiError> {
+    let pool = &state.pool;
+    let settings = crate::settings::get(state).await;
+    crate::quotas::check(pool, &settings, user_id, &role, Quota::Projects).await?;
+    if let Some(image_url) = payload.image_url.as_deref() {
+        crate::upload::check_owned(pool, user_id, image_url).await?;
     }
 
+    let text = format!(
+        "{} {}",
+        payload.title,
+        payload.description.as_deref().unwrap_or_default()
+    );
+    let held = crate::spam::check(state, &settings, user_id, &role, Content::Project, &text, &[])
+        .await?;
+
     // Generate slug and ensure uniqueness per owner
     let base_slug = slugify(&payload.title);
-    let slug = find_unique_slug(&pool, user_id, &base_slug).await?;
+    let slug = find_unique_slug(pool, user_id, &base_slug).await?;
 
     let looking_for = payload.looking_for.unwrap_or_default();
+    let tags = payload.tags.unwrap_or_default();
 
-    // Create project
-    let project = sqlx::query!(
+    // Create project, its milestones and its place in the feed
+    let project = sqlx::query_as!(
+        CreatedProject,
         r#"
-        INSERT INTO projects (owner_id, title, slug, description, image_url, looking_for)
-        VALUES ($1, $2, $3, $4, $5, $6)
-        RETURNING id, slug, created_at
+        WITH project AS (
+            INSERT INTO projects
+                (owner_id, title, slug, description, image_url, looking_for, tags, held_at,
+                 held_reasons)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, CASE WHEN $8::text[] IS NOT NULL THEN NOW() END, $8)
+            RETURNING id, owner_id, slug, created_at
+        ), item AS (
+            INSERT INTO feed_items (project_id, author_id, created_at)
+            SELECT id, owner_id, created_at FROM project
+        ), milestone AS (
+            INSERT INTO project_milestones (project_id, position, title)
+            SELECT project.id, m.position::int, m.title
+            FROM project, unnest($9::text[]) WITH ORDINALITY AS m(title, position)
+        )
+        SELECT id as "id!", slug as "slug!", created_at as "created_at!" FROM project
         "#,
         user_id,
         payload.title,
         slug,
         payload.description,
         payload.image_url,
-        &looking_for
+        &looking_for,
+        &tags,
+        held.as_deref(),
+        milestones
     )
-    .fetch_one(&pool)
+    .fetch_one(pool)
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    Ok((
-        StatusCode::CREATED,
-        Json(serde_json::json!({
-            "id": project.id,
-            "slug": project.slug,
-            "created_at": project.created_at
-        })),
-    ))
+    // Keep the attached image from being garbage collected
+    if let Some(image_url) = payload.image_url.as_deref() {
+        if let Err(e) = crate::upload::mark_attached(pool, user_id, &[image_url]).await {
+            tracing::error!("Failed to mark project image as attached: {}", e);
+        }
+    }
+
+    // As for posts, held projects wait for a moderator
+    if held.is_none() {
+        crate::feed::invalidate(&*state.cache).await;
+        state.search_index.sync_project(pool, project.id).await;
+        state.realtime.send_to_all(Event::FeedItem {
+            item_type: "project",
+            id: project.id,
+            author_id: user_id,
+        });
+    }
+
+    Ok(project)
+}
+
+/// Delete a project (owner, moderator or admin), along with its image
+pub async fn delete(
+    State(state): State<AppState>,
+    AuthUser { id: user_id, role }: AuthUser,
+    session: Session,
+    Path(project_id): Path<uuid::Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let project = sqlx::query!(
+        "SELECT owner_id, image_url FROM projects WHERE id = $1",
+        project_id
+    )
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or(ApiError::new(
+        StatusCode::NOT_FOUND,
+        &i18n::PROJECT_NOT_FOUND,
+    ))?;
+
+    if project.owner_id != user_id && !crate::admin::can_moderate(&role) {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            &i18n::NOT_YOUR_PROJECT,
+        ));
+    }
+
+    sqlx::query!("DELETE FROM projects WHERE id = $1", project_id)
+        .execute(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    crate::feed::invalidate(&*state.cache).await;
+    state.search_index.sync_project(&state.pool, project_id).await;
+
+    // Removing someone else's project is a moderation action
+    if project.owner_id != user_id {
+        let details = format!("project {}", project_id);
+        crate::audit::record(
+            &state.pool,
+            &session,
+            user_id,
+            "moderation.project_deleted",
+            Some(project.owner_id),
+            Some(&details),
+        )
+        .await?;
+    }
+
+    // Best effort: anything left behind is picked up by the orphan cleanup
+    if let Some(image_url) = project.image_url.as_deref() {
+        if let Err(e) = crate::upload::release_uploads(&state, project.owner_id, &[image_url]).await {
+            tracing::error!("Failed to delete project image: {}", e);
+        }
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Open, close or complete a project (owner only). Completed projects count towards
+/// the owner's reputation.
+pub async fn set_status(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(project_id): Path<uuid::Uuid>,
+    ValidatedJson(payload): ValidatedJson<SetStatusRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let updated = sqlx::query!(
+        r#"
+        UPDATE projects
+        SET status = $3,
+            completed_at = CASE
+                WHEN $3 <> 'completed' THEN NULL
+                WHEN status = 'completed' THEN completed_at
+                ELSE NOW()
+            END,
+            updated_at = NOW()
+        WHERE id = $1 AND owner_id = $2
+        "#,
+        project_id,
+        user.id,
+        payload.status
+    )
+    .execute(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    if updated.rows_affected() == 0 {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            &i18n::PROJECT_NOT_FOUND,
+        ));
+    }
+
+    crate::feed::invalidate(&*state.cache).await;
+    state.search_index.sync_project(&state.pool, project_id).await;
+    Ok(StatusCode::OK)
 }
 
 /// Find a unique slug for a given owner by appending -2, -3, etc. on conflict
@@ -170,7 +501,7 @@ async fn find_unique_slug(
     pool: &PgPool,
     owner_id: uuid::Uuid,
     base: &str,
-) -> Result<String, (StatusCode, String)> {
+) -> Result<String, ApiError> {
     let mut candidate = base.to_string();
     let mut counter = 2u32;
     loop {
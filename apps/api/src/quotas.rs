@@ -0,0 +1,109 @@
+use axum::http::StatusCode;
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::error::{ApiError, Message};
+use crate::i18n::{self, ApiMessage};
+use crate::settings::SiteSettings;
+
+/// Something each user can only create so much of in a while, set in the site settings
+#[derive(Clone, Copy)]
+pub enum Quota {
+    /// max_posts_per_hour
+    Posts,
+    /// max_projects_per_day
+    Projects,
+}
+
+impl Quota {
+    /// How many, 0 for no limit, and in how many hours
+    fn limit(self, settings: &SiteSettings) -> (i64, i32) {
+        match self {
+            Self::Posts => (settings.max_posts_per_hour, 1),
+            Self::Projects => (settings.max_projects_per_day, 24),
+        }
+    }
+
+    /// What's said once it's used up, with `{limit}` and `{wait}` to fill in
+    fn reached(self) -> &'static ApiMessage {
+        match self {
+            Self::Posts => &i18n::POST_QUOTA_REACHED,
+            Self::Projects => &i18n::PROJECT_QUOTA_REACHED,
+        }
+    }
+}
+
+/// Rejects with 429 once the user has used up a quota, saying when they can go again.
+/// Admins and verified users may be exempt, depending on the settings.
+pub async fn check(
+    pool: &PgPool,
+    settings: &SiteSettings,
+    user_id: Uuid,
+    role: &str,
+    quota: Quota,
+) -> Result<(), ApiError> {
+    let (limit, hours) = quota.limit(settings);
+    if limit == 0 || (settings.quota_exempt_admins && role == "admin") {
+        return Ok(());
+    }
+    if settings.quota_exempt_verified
+        && crate::extractors::email_verified(pool, user_id)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    {
+        return Ok(());
+    }
+
+    // When the limit'th most recent one leaves the window, another is allowed
+    let frees_up_at = match quota {
+        Quota::Posts => {
+            sqlx::query_scalar!(
+                r#"
+                SELECT created_at + make_interval(hours => $3) AS "frees_up_at!" FROM posts
+                WHERE author_id = $1
+                ORDER BY created_at DESC
+                OFFSET $2::bigint - 1 LIMIT 1
+                "#,
+                user_id,
+                limit,
+                hours
+            )
+            .fetch_optional(pool)
+            .await
+        }
+        Quota::Projects => {
+            sqlx::query_scalar!(
+                r#"
+                SELECT created_at + make_interval(hours => $3) AS "frees_up_at!" FROM projects
+                WHERE owner_id = $1
+                ORDER BY created_at DESC
+                OFFSET $2::bigint - 1 LIMIT 1
+                "#,
+                user_id,
+                limit,
+                hours
+            )
+            .fetch_optional(pool)
+            .await
+        }
+    }
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let Some(frees_up_at) = frees_up_at.filter(|at| *at > Utc::now()) else {
+        return Ok(());
+    };
+
+    // Rounded up, so "try again in" is never too soon
+    let minutes = (frees_up_at - Utc::now()).num_seconds() / 60 + 1;
+    let wait = match minutes {
+        1 => Message::new(&i18n::ONE_MINUTE),
+        2..=59 => Message::new(&i18n::MINUTES).arg("count", minutes),
+        60 => Message::new(&i18n::ONE_HOUR),
+        _ => Message::new(&i18n::HOURS).arg("count", (minutes + 59) / 60),
+    };
+    let message = Message::new(quota.reached())
+        .arg("limit", limit)
+        .arg_message("wait", wait);
+    Err((StatusCode::TOO_MANY_REQUESTS, message).into())
+}
@@ -1,56 +1,223 @@
+use async_trait::async_trait;
 use aws_config::BehaviorVersion;
 use aws_credential_types::Credentials;
 use aws_sdk_s3::{
     config::{Builder, Region},
+    presigning::PresigningConfig,
     primitives::ByteStream,
     Client,
 };
 use std::env;
+use std::time::Duration;
 
-/// Creates an S3 client configured for Cloudflare R2
-pub fn create_r2_client() -> Client {
-    let account_id = env::var("R2_ACCOUNT_ID").expect("R2_ACCOUNT_ID must be set");
-    let access_key_id = env::var("R2_ACCESS_KEY_ID").expect("R2_ACCESS_KEY_ID must be set");
-    let secret_access_key =
-        env::var("R2_SECRET_ACCESS_KEY").expect("R2_SECRET_ACCESS_KEY must be set");
-
-    let credentials = Credentials::new(
-        access_key_id,
-        secret_access_key,
-        None, // session token
-        None, // expiry
-        "r2-credentials",
-    );
-
-    let config = Builder::new()
-        .behavior_version(BehaviorVersion::latest())
-        .region(Region::new("auto")) // R2 uses "auto" region
-        .endpoint_url(format!("https://{}.r2.cloudflarestorage.com", account_id))
-        .credentials_provider(credentials)
-        .build();
-
-    Client::from_conf(config)
+/// Object storage for uploads: R2 in production, an in-memory fake in the integration tests
+#[async_trait]
+pub trait ObjectStore: Send + Sync {
+    /// Public URL an object is served from
+    fn public_url(&self, key: &str) -> String;
+
+    /// Uploads bytes and returns the public URL
+    async fn upload(&self, key: &str, data: Vec<u8>, content_type: &str) -> Result<String, String>;
+
+    /// Presigns a PUT for a client-side upload, returning (upload URL, public URL).
+    /// Content type and length are part of the signature, so the client can't exceed them.
+    async fn presign_put(
+        &self,
+        key: &str,
+        content_type: &str,
+        content_length: i64,
+        expires_in: Duration,
+    ) -> Result<(String, String), String>;
+
+    /// Presigns a GET, for objects that aren't served publicly
+    async fn presign_get(&self, key: &str, expires_in: Duration) -> Result<String, String>;
+
+    /// Size of an object in bytes, or None if there's no object at that key
+    async fn head(&self, key: &str) -> Result<Option<i64>, String>;
+
+    async fn download(&self, key: &str) -> Result<Vec<u8>, String>;
+
+    /// Copies an object to a new key and returns the new public URL
+    async fn copy(&self, from_key: &str, to_key: &str) -> Result<String, String>;
+
+    async fn delete(&self, key: &str) -> Result<(), String>;
+}
+
+/// Keys under these prefixes (message attachments, images held for review) are kept in the
+/// private bucket, which is never served publicly; they're only reached through signed URLs
+const PRIVATE_PREFIXES: [&str; 2] = ["private/", "quarantine/"];
+
+/// S3 client configured for Cloudflare R2, along with the buckets it writes to
+#[derive(Clone)]
+pub struct R2Client {
+    client: Client,
+    bucket: String,
+    /// Without it, private keys fail and public uploads still work
+    private_bucket: Option<String>,
+    public_url: String,
+}
+
+impl R2Client {
+    /// Build from R2_* env vars; None if any but R2_PRIVATE_BUCKET_NAME are missing
+    pub fn from_env() -> Option<Self> {
+        let account_id = env::var("R2_ACCOUNT_ID").ok()?;
+        let access_key_id = env::var("R2_ACCESS_KEY_ID").ok()?;
+        let secret_access_key = env::var("R2_SECRET_ACCESS_KEY").ok()?;
+        let bucket = env::var("R2_BUCKET_NAME").ok()?;
+        let public_url = env::var("R2_PUBLIC_URL").ok()?;
+        let private_bucket = env::var("R2_PRIVATE_BUCKET_NAME").ok();
+        if private_bucket.is_none() {
+            tracing::error!(
+                "R2_PRIVATE_BUCKET_NAME not set, message attachments and held images are disabled"
+            );
+        }
+
+        let credentials = Credentials::new(
+            access_key_id,
+            secret_access_key,
+            None, // session token
+            None, // expiry
+            "r2-credentials",
+        );
+
+        let config = Builder::new()
+            .behavior_version(BehaviorVersion::latest())
+            .region(Region::new("auto")) // R2 uses "auto" region
+            .endpoint_url(format!("https://{}.r2.cloudflarestorage.com", account_id))
+            .credentials_provider(credentials)
+            .build();
+
+        Some(Self {
+            client: Client::from_conf(config),
+            bucket,
+            private_bucket,
+            public_url,
+        })
+    }
+
+    fn bucket_for(&self, key: &str) -> Result<&str, String> {
+        if PRIVATE_PREFIXES
+            .iter()
+            .any(|prefix| key.starts_with(prefix))
+        {
+            self.private_bucket
+                .as_deref()
+                .ok_or_else(|| format!("No private bucket for {}", key))
+        } else {
+            Ok(&self.bucket)
+        }
+    }
 }
 
-/// Uploads bytes to R2 and returns the public URL
-pub async fn upload_to_r2(
-    client: &Client,
-    bucket: &str,
-    key: &str,
-    data: Vec<u8>,
-    content_type: &str,
-) -> Result<String, aws_sdk_s3::Error> {
-    let public_url = env::var("R2_PUBLIC_URL").expect("R2_PUBLIC_URL must be set");
-
-    client
-        .put_object()
-        .bucket(bucket)
-        .key(key)
-        .body(ByteStream::from(data))
-        .content_type(content_type)
-        .send()
-        .await?;
-
-    // Return the public URL for the uploaded file
-    Ok(format!("{}/{}", public_url, key))
+#[async_trait]
+impl ObjectStore for R2Client {
+    fn public_url(&self, key: &str) -> String {
+        format!("{}/{}", self.public_url, key)
+    }
+
+    async fn upload(&self, key: &str, data: Vec<u8>, content_type: &str) -> Result<String, String> {
+        self.client
+            .put_object()
+            .bucket(self.bucket_for(key)?)
+            .key(key)
+            .body(ByteStream::from(data))
+            .content_type(content_type)
+            .send()
+            .await
+            .map_err(|e| aws_sdk_s3::Error::from(e).to_string())?;
+
+        // Return the public URL for the uploaded file
+        Ok(self.public_url(key))
+    }
+
+    async fn presign_put(
+        &self,
+        key: &str,
+        content_type: &str,
+        content_length: i64,
+        expires_in: Duration,
+    ) -> Result<(String, String), String> {
+        let presigning = PresigningConfig::expires_in(expires_in).map_err(|e| e.to_string())?;
+        let presigned = self
+            .client
+            .put_object()
+            .bucket(self.bucket_for(key)?)
+            .key(key)
+            .content_type(content_type)
+            .content_length(content_length)
+            .presigned(presigning)
+            .await
+            .map_err(|e| aws_sdk_s3::Error::from(e).to_string())?;
+
+        Ok((presigned.uri().to_string(), self.public_url(key)))
+    }
+
+    async fn presign_get(&self, key: &str, expires_in: Duration) -> Result<String, String> {
+        let presigning = PresigningConfig::expires_in(expires_in).map_err(|e| e.to_string())?;
+        let presigned = self
+            .client
+            .get_object()
+            .bucket(self.bucket_for(key)?)
+            .key(key)
+            .presigned(presigning)
+            .await
+            .map_err(|e| aws_sdk_s3::Error::from(e).to_string())?;
+
+        Ok(presigned.uri().to_string())
+    }
+
+    async fn head(&self, key: &str) -> Result<Option<i64>, String> {
+        let result = self
+            .client
+            .head_object()
+            .bucket(self.bucket_for(key)?)
+            .key(key)
+            .send()
+            .await;
+
+        match result {
+            Ok(head) => Ok(Some(head.content_length().unwrap_or_default())),
+            Err(e) if e.as_service_error().is_some_and(|e| e.is_not_found()) => Ok(None),
+            Err(e) => Err(aws_sdk_s3::Error::from(e).to_string()),
+        }
+    }
+
+    async fn download(&self, key: &str) -> Result<Vec<u8>, String> {
+        let object = self
+            .client
+            .get_object()
+            .bucket(self.bucket_for(key)?)
+            .key(key)
+            .send()
+            .await
+            .map_err(|e| aws_sdk_s3::Error::from(e).to_string())?;
+        let body = object.body.collect().await.map_err(|e| e.to_string())?;
+
+        Ok(body.into_bytes().to_vec())
+    }
+
+    async fn copy(&self, from_key: &str, to_key: &str) -> Result<String, String> {
+        self.client
+            .copy_object()
+            .bucket(self.bucket_for(to_key)?)
+            .copy_source(format!("{}/{}", self.bucket_for(from_key)?, from_key))
+            .key(to_key)
+            .send()
+            .await
+            .map_err(|e| aws_sdk_s3::Error::from(e).to_string())?;
+
+        Ok(self.public_url(to_key))
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), String> {
+        self.client
+            .delete_object()
+            .bucket(self.bucket_for(key)?)
+            .key(key)
+            .send()
+            .await
+            .map_err(|e| aws_sdk_s3::Error::from(e).to_string())?;
+
+        Ok(())
+    }
 }
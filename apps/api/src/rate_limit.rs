@@ -0,0 +1,145 @@
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use governor::{
+    clock::{Clock, DefaultClock},
+    middleware::StateInformationMiddleware,
+    state::keyed::DefaultKeyedStateStore,
+    Quota, RateLimiter,
+};
+use std::num::NonZeroU32;
+use std::sync::Arc;
+use std::time::Duration;
+use tower_sessions::Session;
+use uuid::Uuid;
+
+use crate::api_keys::ApiKeyId;
+use crate::client_ip::ClientIp;
+use crate::error::ApiError;
+use crate::i18n;
+
+type KeyedLimiter =
+    RateLimiter<String, DefaultKeyedStateStore<String>, DefaultClock, StateInformationMiddleware>;
+
+/// A token bucket per client, shared by every route in a group.
+/// Clients are keyed by API key on /public/v1, by user id when logged in, otherwise by IP.
+#[derive(Clone)]
+pub struct RateLimit {
+    name: &'static str,
+    limiter: Arc<KeyedLimiter>,
+}
+
+impl RateLimit {
+    /// Allow `per_minute` requests a minute, all of which may be used in a burst
+    pub fn per_minute(name: &'static str, per_minute: u32) -> Self {
+        let per_minute = NonZeroU32::new(per_minute).expect("rate limit must be non-zero");
+        let limiter = RateLimiter::keyed(Quota::per_minute(per_minute))
+            .with_middleware::<StateInformationMiddleware>();
+
+        Self {
+            name,
+            limiter: Arc::new(limiter),
+        }
+    }
+
+    /// Strict limit for login, signup, password resets and other credential endpoints
+    pub fn auth() -> Self {
+        Self::per_minute("auth", 10)
+    }
+
+    /// Generous limit applied to every route
+    pub fn global() -> Self {
+        Self::per_minute("global", 300)
+    }
+
+    /// Per key limit for the public API, under the global one
+    pub fn public_api() -> Self {
+        Self::per_minute("public_api", 120)
+    }
+
+    /// Forget clients that have fully replenished their quota
+    pub fn cleanup(&self) {
+        self.limiter.retain_recent();
+        self.limiter.shrink_to_fit();
+    }
+}
+
+// A stricter route group's limit is applied further in, so keep whatever it already reported
+fn set_header(headers: &mut HeaderMap, name: &'static str, value: u64) {
+    headers.entry(name).or_insert(HeaderValue::from(value));
+}
+
+/// Middleware: reject with 429 once the client's bucket is empty,
+/// and report the remaining quota in X-RateLimit-* headers
+pub async fn limit(
+    State(limit): State<RateLimit>,
+    ClientIp(ip): ClientIp,
+    session: Option<Session>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let user_id = match &session {
+        Some(session) => session.get::<Uuid>("user_id").await.ok().flatten(),
+        None => None,
+    };
+    let api_key = request.extensions().get::<ApiKeyId>().copied();
+    let key = match (api_key, user_id) {
+        (Some(ApiKeyId(id)), _) => format!("key:{}", id),
+        (None, Some(id)) => format!("user:{}", id),
+        (None, None) => format!("ip:{}", ip),
+    };
+
+    match limit.limiter.check_key(&key) {
+        Ok(snapshot) => {
+            let quota = snapshot.quota();
+            let burst = quota.burst_size().get();
+            let remaining = snapshot.remaining_burst_capacity();
+            // Time until the bucket is full again
+            let reset = quota.replenish_interval() * (burst - remaining);
+
+            let mut response = next.run(request).await;
+            let headers = response.headers_mut();
+            set_header(headers, "x-ratelimit-limit", burst as u64);
+            set_header(headers, "x-ratelimit-remaining", remaining as u64);
+            set_header(
+                headers,
+                "x-ratelimit-reset",
+                reset.as_secs_f64().ceil() as u64,
+            );
+            response
+        }
+        Err(not_until) => {
+            let wait = not_until.wait_time_from(DefaultClock::default().now());
+            let retry_after = wait.max(Duration::from_secs(1)).as_secs_f64().ceil() as u64;
+            tracing::warn!("Rate limit ({}) exceeded for {}", limit.name, key);
+
+            let mut response =
+                ApiError::new(StatusCode::TOO_MANY_REQUESTS, &i18n::RATE_LIMITED).into_response();
+            let headers = response.headers_mut();
+            set_header(
+                headers,
+                "x-ratelimit-limit",
+                not_until.quota().burst_size().get() as u64,
+            );
+            set_header(headers, "x-ratelimit-remaining", 0);
+            set_header(headers, "x-ratelimit-reset", retry_after);
+            set_header(headers, "retry-after", retry_after);
+            response
+        }
+    }
+}
+
+/// Background task: periodically drop idle clients so the limiter maps don't grow forever
+pub async fn run_cleanup(limits: Vec<RateLimit>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(10 * 60));
+
+    loop {
+        interval.tick().await;
+        for limit in &limits {
+            limit.cleanup();
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::extractors::AuthUser;
+use crate::i18n;
+
+#[derive(Serialize)]
+pub struct BlockedUser {
+    pub id: Uuid,
+    pub username: String,
+    pub display_name: String,
+    pub avatar_url: Option<String>,
+    pub blocked_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// The other user's id, or 404. Banned users can't be followed or blocked.
+async fn find_other(pool: &PgPool, me: Uuid, username: &str) -> Result<Uuid, ApiError> {
+    let id = sqlx::query_scalar!(
+        "SELECT id FROM users WHERE username = $1 AND banned_at IS NULL",
+        username.to_lowercase()
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or(ApiError::new(StatusCode::NOT_FOUND, &i18n::USER_NOT_FOUND))?;
+
+    if id == me {
+        return Err(ApiError::new(StatusCode::BAD_REQUEST, &i18n::THAT_IS_YOU));
+    }
+    Ok(id)
+}
+
+/// Whether either user has blocked the other
+pub async fn blocked_either_way(pool: &PgPool, a: Uuid, b: Uuid) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS(
+            SELECT 1 FROM blocks
+            WHERE (blocker_id = $1 AND blocked_id = $2) OR (blocker_id = $2 AND blocked_id = $1)
+        ) AS "blocked!"
+        "#,
+        a,
+        b
+    )
+    .fetch_one(pool)
+    .await
+}
+
+/// Following again is fine; blocked users (either way) can't follow
+pub async fn follow(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+    Path(username): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let other = find_other(&pool, user.id, &username).await?;
+    let blocked = blocked_either_way(&pool, user.id, other)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if blocked {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            &i18n::CANNOT_FOLLOW_USER,
+        ));
+    }
+
+    sqlx::query!(
+        "INSERT INTO follows (follower_id, followee_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+        user.id,
+        other
+    )
+    .execute(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn unfollow(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+    Path(username): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let other = find_other(&pool, user.id, &username).await?;
+    sqlx::query!(
+        "DELETE FROM follows WHERE follower_id = $1 AND followee_id = $2",
+        user.id,
+        other
+    )
+    .execute(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Also unfollows both ways. The blocked user isn't told.
+pub async fn block(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+    Path(username): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let other = find_other(&pool, user.id, &username).await?;
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    sqlx::query!(
+        "INSERT INTO blocks (blocker_id, blocked_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+        user.id,
+        other
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    sqlx::query!(
+        r#"
+        DELETE FROM follows
+        WHERE (follower_id = $1 AND followee_id = $2) OR (follower_id = $2 AND followee_id = $1)
+        "#,
+        user.id,
+        other
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn unblock(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+    Path(username): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let other = find_other(&pool, user.id, &username).await?;
+    sqlx::query!(
+        "DELETE FROM blocks WHERE blocker_id = $1 AND blocked_id = $2",
+        user.id,
+        other
+    )
+    .execute(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Everyone the user has blocked, most recent first
+pub async fn list_blocks(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+) -> Result<impl IntoResponse, ApiError> {
+    let blocked = sqlx::query_as!(
+        BlockedUser,
+        r#"
+        SELECT u.id, u.username, u.display_name, u.avatar_url, b.created_at AS blocked_at
+        FROM blocks b
+        JOIN users u ON u.id = b.blocked_id
+        WHERE b.blocker_id = $1
+        ORDER BY b.created_at DESC
+        "#,
+        user.id
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(blocked))
+}
@@ -0,0 +1,56 @@
+use sqlx::PgPool;
+
+// Points per thing counted towards a user's reputation
+pub const REACTION_POINTS: i32 = 1;
+pub const ACCEPTED_APPLICATION_POINTS: i32 = 10;
+pub const COMPLETED_PROJECT_POINTS: i32 = 25;
+
+/// Work out every user's reputation again from scratch and store it where it changed.
+/// Counts reactions from other users on their messages (each reactor once per message),
+/// their accepted applications and the projects they own that are completed. Reactions
+/// from banned accounts don't count. Returns how many users' scores changed.
+pub async fn recompute(pool: &PgPool) -> Result<u64, String> {
+    let updated = sqlx::query!(
+        r#"
+        WITH points AS (
+            SELECT m.sender_id AS user_id, COUNT(DISTINCT (r.message_id, r.user_id)) * $1 AS points
+            FROM message_reactions r
+            JOIN messages m ON m.id = r.message_id
+            JOIN users reactor ON reactor.id = r.user_id
+            WHERE r.user_id <> m.sender_id AND reactor.banned_at IS NULL
+            GROUP BY m.sender_id
+            UNION ALL
+            SELECT applicant_id, COUNT(*) * $2 FROM applications
+            WHERE status = 'accepted'
+            GROUP BY applicant_id
+            UNION ALL
+            SELECT owner_id, COUNT(*) * $3 FROM projects
+            WHERE status = 'completed'
+            GROUP BY owner_id
+        ), scores AS (
+            SELECT u.id, COALESCE(SUM(p.points), 0)::int AS score
+            FROM users u
+            LEFT JOIN points p ON p.user_id = u.id
+            GROUP BY u.id
+        )
+        UPDATE users u SET reputation = s.score
+        FROM scores s
+        WHERE s.id = u.id AND u.reputation <> s.score
+        "#,
+        i64::from(REACTION_POINTS),
+        i64::from(ACCEPTED_APPLICATION_POINTS),
+        i64::from(COMPLETED_PROJECT_POINTS)
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(updated.rows_affected())
+}
+
+/// Whether text has a web link in it: anything with a scheme, or starting with www.
+pub fn contains_link(text: &str) -> bool {
+    text.split_whitespace().any(|word| {
+        let word = word.trim_start_matches(|c: char| !c.is_alphanumeric());
+        word.contains("://") || word.to_lowercase().starts_with("www.")
+    })
+}
@@ -0,0 +1,275 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use sqlx::PgPool;
+use tower_sessions::Session;
+
+use crate::error::ApiError;
+use crate::extractors::AdminUser;
+use crate::state::AppState;
+
+// Raw analytics are counted a whole day at a time, so they're kept at least this long
+const MIN_ANALYTICS_DAYS: i64 = 2;
+// Longest policy that isn't "forever"
+const MAX_DAYS: i64 = 36_500;
+
+/// A kind of data with its own retention policy, stored in retention_policies
+#[derive(Clone, Copy, PartialEq)]
+pub enum DataClass {
+    Sessions,
+    AuditLog,
+    AnalyticsEvents,
+    EmailLog,
+    HeldContent,
+}
+
+impl DataClass {
+    pub const ALL: [DataClass; 5] = [
+        DataClass::Sessions,
+        DataClass::AuditLog,
+        DataClass::AnalyticsEvents,
+        DataClass::EmailLog,
+        DataClass::HeldContent,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DataClass::Sessions => "sessions",
+            DataClass::AuditLog => "audit_log",
+            DataClass::AnalyticsEvents => "analytics_events",
+            DataClass::EmailLog => "email_log",
+            DataClass::HeldContent => "held_content",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|class| class.as_str() == s)
+    }
+
+    /// What's deleted once it's older than the policy
+    fn describe(self) -> &'static str {
+        match self {
+            DataClass::Sessions => {
+                "Login sessions, with their IP, device and location, idle this long (signing them out)"
+            }
+            DataClass::AuditLog => "Audit log entries",
+            DataClass::AnalyticsEvents => {
+                "Raw analytics events, once counted (the daily counts are kept)"
+            }
+            DataClass::EmailLog => "The record of emails sent",
+            DataClass::HeldContent => {
+                "Posts, projects and applications held by the spam checks and never reviewed"
+            }
+        }
+    }
+
+    fn min_days(self) -> i64 {
+        match self {
+            DataClass::AnalyticsEvents => MIN_ANALYTICS_DAYS,
+            _ => 1,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Policy {
+    pub data_class: &'static str,
+    pub description: &'static str,
+    /// None keeps it until something else removes it
+    pub days: Option<i32>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// How many days a class of data is kept, None for no limit
+pub async fn days(pool: &PgPool, class: DataClass) -> Result<Option<i32>, sqlx::Error> {
+    let days = sqlx::query_scalar!(
+        "SELECT days FROM retention_policies WHERE data_class = $1",
+        class.as_str()
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(days.flatten())
+}
+
+/// Delete whatever has outlived its policy. Raw analytics are left to `analytics::aggregate`,
+/// which counts them first. Returns how many rows were deleted.
+pub async fn apply(state: &AppState) -> Result<u64, String> {
+    let mut deleted = 0;
+    for class in DataClass::ALL {
+        let Some(days) = days(&state.pool, class).await.map_err(|e| e.to_string())? else {
+            continue;
+        };
+
+        deleted += match class {
+            DataClass::Sessions => purge_sessions(state, days).await?,
+            DataClass::AuditLog => sqlx::query!(
+                "DELETE FROM audit_logs WHERE created_at < NOW() - make_interval(days => $1)",
+                days
+            )
+            .execute(&state.pool)
+            .await
+            .map_err(|e| e.to_string())?
+            .rows_affected(),
+            DataClass::AnalyticsEvents => 0,
+            DataClass::EmailLog => crate::email_log::purge_old(&state.pool, days)
+                .await
+                .map_err(|e| e.to_string())?,
+            DataClass::HeldContent => purge_held(state, days).await?,
+        };
+    }
+    Ok(deleted)
+}
+
+async fn purge_sessions(state: &AppState, days: i32) -> Result<u64, String> {
+    let session_ids = sqlx::query_scalar!(
+        r#"
+        DELETE FROM active_sessions WHERE last_active_at < NOW() - make_interval(days => $1)
+        RETURNING session_id
+        "#,
+        days
+    )
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    state.sessions.delete_ids(&session_ids).await?;
+
+    Ok(session_ids.len() as u64)
+}
+
+async fn purge_held(state: &AppState, days: i32) -> Result<u64, String> {
+    let purged = sqlx::query!(
+        r#"
+        WITH post AS (
+            DELETE FROM posts WHERE held_at < NOW() - make_interval(days => $1)
+            RETURNING author_id, image_url
+        ), project AS (
+            DELETE FROM projects WHERE held_at < NOW() - make_interval(days => $1)
+            RETURNING owner_id AS author_id, image_url
+        ), application AS (
+            DELETE FROM applications WHERE held_at < NOW() - make_interval(days => $1)
+            RETURNING applicant_id AS author_id, NULL::text AS image_url
+        )
+        SELECT author_id AS "author_id!", image_url FROM post
+        UNION ALL SELECT author_id, image_url FROM project
+        UNION ALL SELECT author_id, image_url FROM application
+        "#,
+        days
+    )
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    // Best effort: anything left behind is picked up by the orphan cleanup
+    for content in &purged {
+        let Some(image_url) = content.image_url.as_deref() else {
+            continue;
+        };
+        if let Err(e) = crate::upload::release_uploads(state, content.author_id, &[image_url]).await
+        {
+            tracing::error!("Failed to delete images of expired held content: {}", e);
+        }
+    }
+
+    Ok(purged.len() as u64)
+}
+
+async fn load(pool: &PgPool) -> Result<Vec<Policy>, sqlx::Error> {
+    let rows = sqlx::query!("SELECT data_class, days, updated_at FROM retention_policies")
+        .fetch_all(pool)
+        .await?;
+
+    Ok(DataClass::ALL
+        .into_iter()
+        .filter_map(|class| {
+            let row = rows.iter().find(|row| row.data_class == class.as_str())?;
+            Some(Policy {
+                data_class: class.as_str(),
+                description: class.describe(),
+                days: row.days,
+                updated_at: row.updated_at,
+            })
+        })
+        .collect())
+}
+
+/// Every retention policy
+pub async fn list(State(pool): State<PgPool>, _: AdminUser) -> Result<impl IntoResponse, ApiError> {
+    let policies = load(&pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(policies))
+}
+
+/// Change some policies, e.g. `{ "email_log": 60, "audit_log": null }`. Returns them all.
+pub async fn update(
+    State(pool): State<PgPool>,
+    AdminUser(admin): AdminUser,
+    session: Session,
+    Json(changes): Json<Map<String, Value>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let mut updates = Vec::with_capacity(changes.len());
+    for (key, value) in &changes {
+        let class = DataClass::parse(key).ok_or((
+            StatusCode::BAD_REQUEST,
+            format!("Unknown data class: {}", key),
+        ))?;
+        let days = match value.as_i64() {
+            _ if value.is_null() => None,
+            Some(days) if (class.min_days()..=MAX_DAYS).contains(&days) => Some(days as i32),
+            _ => {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    format!(
+                        "{} must be null or from {} to {} days",
+                        key,
+                        class.min_days(),
+                        MAX_DAYS
+                    ),
+                )
+                    .into())
+            }
+        };
+        updates.push((class, days));
+    }
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    for (class, days) in updates {
+        sqlx::query!(
+            r#"
+            UPDATE retention_policies SET days = $2, updated_by = $3, updated_at = NOW()
+            WHERE data_class = $1
+            "#,
+            class.as_str(),
+            days,
+            admin.id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    }
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let details = Value::Object(changes).to_string();
+    crate::audit::record(
+        &pool,
+        &session,
+        admin.id,
+        "admin.retention_updated",
+        None,
+        Some(&details),
+    )
+    .await?;
+
+    let policies = load(&pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(Json(policies))
+}
@@ -0,0 +1,217 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::extractors::AuthUser;
+use crate::i18n;
+use crate::notifications::NotificationKind;
+use crate::search::{SearchParams, MAX_QUERY_LENGTH, SEARCH_TYPES};
+use crate::state::AppState;
+use crate::validation::{Validate, ValidatedJson, ValidationErrors};
+
+const MAX_SAVED_SEARCHES: i64 = 20;
+const MAX_FILTER_LENGTH: usize = 50;
+// Each saved search is checked for new results at most this often
+const CHECK_INTERVAL_MINUTES: i32 = 60;
+
+#[derive(Serialize)]
+pub struct SavedSearch {
+    pub id: Uuid,
+    pub q: String,
+    #[serde(rename = "type")]
+    pub search_type: String,
+    pub author: Option<String>,
+    pub status: Option<String>,
+    pub tag: Option<String>,
+    /// Results created after this haven't been notified about yet
+    pub checked_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// The same as GET /search's parameters, minus paging, dates and sort
+#[derive(Deserialize)]
+pub struct SaveSearchRequest {
+    pub q: String,
+    /// "users", "posts", "projects" or "all" (the default)
+    #[serde(rename = "type")]
+    pub search_type: Option<String>,
+    pub author: Option<String>,
+    pub status: Option<String>,
+    pub tag: Option<String>,
+}
+
+impl Validate for SaveSearchRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.length("q", self.q.trim(), 1, MAX_QUERY_LENGTH);
+        if let Some(search_type) = &self.search_type {
+            errors.one_of("type", search_type, SEARCH_TYPES);
+        }
+        for (field, value) in [
+            ("author", &self.author),
+            ("status", &self.status),
+            ("tag", &self.tag),
+        ] {
+            if let Some(value) = value {
+                errors.length(field, value, 1, MAX_FILTER_LENGTH);
+            }
+        }
+        errors.into_result()
+    }
+}
+
+/// The user's saved searches, newest first
+pub async fn list(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+) -> Result<impl IntoResponse, ApiError> {
+    let searches = sqlx::query_as!(
+        SavedSearch,
+        r#"
+        SELECT id, q, search_type, author, status, tag, checked_at, created_at
+        FROM saved_searches
+        WHERE user_id = $1
+        ORDER BY created_at DESC
+        "#,
+        user.id
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(searches))
+}
+
+/// Save a search; only results created from now on are notified about
+pub async fn create(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+    ValidatedJson(payload): ValidatedJson<SaveSearchRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    // Counted in the insert, so two saves at once can't both slip under the limit
+    let search = sqlx::query_as!(
+        SavedSearch,
+        r#"
+        INSERT INTO saved_searches (user_id, q, search_type, author, status, tag)
+        SELECT $1, $2, $3, $4, $5, $6
+        WHERE (SELECT COUNT(*) FROM saved_searches WHERE user_id = $1) < $7
+        RETURNING id, q, search_type, author, status, tag, checked_at, created_at
+        "#,
+        user.id,
+        payload.q.trim(),
+        payload.search_type.as_deref().unwrap_or("all"),
+        payload.author.map(|a| a.to_lowercase()),
+        payload.status,
+        payload.tag,
+        MAX_SAVED_SEARCHES
+    )
+    .fetch_optional(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or(
+        ApiError::new(StatusCode::BAD_REQUEST, &i18n::TOO_MANY_SAVED_SEARCHES)
+            .arg("max", MAX_SAVED_SEARCHES),
+    )?;
+
+    Ok((StatusCode::CREATED, Json(search)))
+}
+
+pub async fn delete(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let result = sqlx::query!(
+        "DELETE FROM saved_searches WHERE id = $1 AND user_id = $2",
+        id,
+        user.id
+    )
+    .execute(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    if result.rows_affected() == 0 {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            &i18n::SAVED_SEARCH_NOT_FOUND,
+        ));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Notify owners of saved searches not checked for an hour about results created since the
+/// last check, one notification per search. Banned users' searches are left alone.
+/// Returns how many notifications were sent.
+pub async fn check_due(state: &AppState, now: DateTime<Utc>) -> Result<u64, String> {
+    // Claimed like the digest: moving checked_at first means two instances never both
+    // notify, and a check that then fails skips those results rather than repeating them
+    let due = sqlx::query!(
+        r#"
+        UPDATE saved_searches s
+        SET checked_at = $1::timestamptz
+        FROM saved_searches previous, users u
+        WHERE previous.id = s.id
+          AND u.id = s.user_id
+          AND u.banned_at IS NULL
+          AND s.checked_at <= $1::timestamptz - make_interval(mins => $2)
+        RETURNING s.id, s.user_id, s.q, s.search_type, s.author, s.status, s.tag,
+                  previous.checked_at AS since
+        "#,
+        now,
+        CHECK_INTERVAL_MINUTES
+    )
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let mut sent = 0;
+    for search in due {
+        let params = SearchParams {
+            q: search.q,
+            search_type: Some(search.search_type),
+            from: Some(search.since),
+            to: Some(now),
+            author: search.author,
+            status: search.status,
+            tag: search.tag,
+            ..Default::default()
+        };
+        let new_results = match crate::search::count_matches(&state.pool, &params.q, &params).await
+        {
+            Ok(0) => continue,
+            Ok(n) => n,
+            Err(e) => {
+                tracing::error!("Failed to check saved search {}: {}", search.id, e);
+                continue;
+            }
+        };
+
+        let data = serde_json::json!({
+            "saved_search_id": search.id,
+            "q": params.q,
+            "type": params.search_type,
+            "new_results": new_results,
+        });
+        match crate::notifications::notify_without_actor(
+            state,
+            search.user_id,
+            NotificationKind::SavedSearch,
+            data,
+        )
+        .await
+        {
+            Ok(()) => sent += 1,
+            Err(e) => tracing::error!("Failed to notify about saved search {}: {}", search.id, e),
+        }
+    }
+
+    Ok(sent)
+}
@@ -0,0 +1,120 @@
+use std::future::Future;
+use std::time::Duration;
+
+use crate::state::AppState;
+
+/// Every maintenance job, by name, and how often it runs
+pub const JOBS: &[(&str, Duration)] = &[
+    ("expired_sessions", minutes(15)),
+    ("expired_tokens", minutes(60)),
+    ("orphaned_uploads", minutes(60)),
+    ("finished_jobs", minutes(60)),
+    // Deletes whatever the retention policies (/admin/retention) say is too old
+    ("retention", minutes(60)),
+    // Only queues digests for users it's Monday morning for, so it runs often
+    ("weekly_digest", minutes(15)),
+    ("verification_reminders", minutes(60)),
+    ("old_notifications", minutes(60)),
+    ("notification_broadcasts", minutes(1)),
+    // Only emails notifications that have sat unread for a few hours, so it runs often
+    ("unread_notifications", minutes(15)),
+    // Each saved search is only checked hourly, so this spreads them out
+    ("saved_searches", minutes(15)),
+    // Does nothing unless MESSAGE_RETENTION_DAYS is set
+    ("old_messages", minutes(60)),
+    ("reputation", minutes(60)),
+    // Recent standings move quickly, so these are rebuilt often
+    ("leaderboards", minutes(15)),
+    ("event_reminders", minutes(15)),
+    // Feeds /admin/db/pool, and warns when requests are waiting on the pool
+    ("pool_metrics", Duration::from_secs(10)),
+    ("analytics", minutes(60)),
+    ("daily_stats", minutes(60)),
+];
+
+/// Start the job queue worker and every maintenance job. Each maintenance job runs once
+/// at startup and then on its own interval; a failed run is logged and retried at the next tick.
+pub fn start(state: AppState) {
+    crate::jobs::spawn_worker(state.clone());
+
+    for &(name, period) in JOBS {
+        every(state.clone(), name, period, move |state| async move {
+            run(&state, name).await.expect("every job in JOBS runs")
+        });
+    }
+}
+
+/// Run a maintenance job once, now. Returns how many rows it cleaned up, or None if
+/// there's no job by that name.
+pub async fn run(state: &AppState, name: &str) -> Option<Result<u64, String>> {
+    let result = match name {
+        "expired_sessions" => {
+            crate::session::purge_expired_sessions(&state.pool, &state.sessions).await
+        }
+        "expired_tokens" => crate::auth::purge_expired_tokens(&state.pool)
+            .await
+            .map_err(|e| e.to_string()),
+        "orphaned_uploads" => {
+            if state.r2_client.is_none() {
+                tracing::debug!("R2 not configured, skipping orphaned upload cleanup");
+                return Some(Ok(0));
+            }
+            crate::upload::cleanup_orphaned_uploads(state).await
+        }
+        "finished_jobs" => crate::jobs::purge_finished(&state.pool)
+            .await
+            .map_err(|e| e.to_string()),
+        "retention" => crate::retention::apply(state).await,
+        "weekly_digest" => crate::digest::send_due(state, chrono::Utc::now()).await,
+        "verification_reminders" => {
+            crate::verification_reminders::send_due(state, chrono::Utc::now()).await
+        }
+        "old_notifications" => crate::notifications::purge_old(&state.pool)
+            .await
+            .map_err(|e| e.to_string()),
+        "notification_broadcasts" => crate::broadcasts::send_due(state, chrono::Utc::now()).await,
+        "unread_notifications" => {
+            crate::notifications::email_unread(state, chrono::Utc::now()).await
+        }
+        "saved_searches" => crate::saved_searches::check_due(state, chrono::Utc::now()).await,
+        "old_messages" => crate::messages::purge_old(state).await,
+        "reputation" => crate::reputation::recompute(&state.pool).await,
+        "leaderboards" => crate::leaderboards::refresh(&state.pool).await.map(|_| 0),
+        "event_reminders" => crate::events::send_reminders(state, chrono::Utc::now()).await,
+        "pool_metrics" => crate::db::sample(&state.pool).await,
+        "analytics" => crate::analytics::aggregate(&state.pool).await,
+        "daily_stats" => crate::stats::refresh_recent(&state.pool)
+            .await
+            .map(|_| 0)
+            .map_err(|e| e.to_string()),
+        _ => return None,
+    };
+    Some(result)
+}
+
+const fn minutes(n: u64) -> Duration {
+    Duration::from_secs(n * 60)
+}
+
+/// Spawn a job on a fixed interval. Jobs return how many rows they cleaned up.
+fn every<F, Fut>(state: AppState, name: &'static str, period: Duration, job: F)
+where
+    F: Fn(AppState) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<u64, String>> + Send,
+{
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        // Don't fire a burst of catch-up runs after a slow run
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            interval.tick().await;
+
+            match job(state.clone()).await {
+                Ok(0) => {}
+                Ok(n) => tracing::info!("Scheduled job {}: cleaned up {} rows", name, n),
+                Err(e) => tracing::error!("Scheduled job {} failed: {}", name, e),
+            }
+        }
+    });
+}
@@ -0,0 +1,539 @@
+use axum::{
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::db::DbRouter;
+use crate::error::ApiError;
+use crate::search_index::{IndexHits, IndexQuery, Indexer};
+
+pub const MAX_QUERY_LENGTH: usize = 200;
+pub const SEARCH_TYPES: &[&str] = &["all", "users", "posts", "projects"];
+const DEFAULT_PAGE_SIZE: i64 = 20;
+const MAX_PAGE_SIZE: i64 = 50;
+const MAX_SUGGEST_QUERY_LENGTH: usize = 50;
+const SUGGESTIONS: i64 = 5;
+
+// ts_headline marks matches with these; control characters can't come from a text field
+const HIGHLIGHT_START: char = '\u{2}';
+const HIGHLIGHT_STOP: char = '\u{3}';
+
+/// SQL for matching, ranking and highlighting against the search text bound as `$n`.
+/// Every search endpoint builds its query from these, so `q` means the same everywhere: web
+/// search syntax (`"exact phrase"`, `-word`, `or`) under the 'simple' configuration, which
+/// the `search_vector` columns are generated with.
+pub struct TextQuery {
+    param: usize,
+}
+
+impl TextQuery {
+    /// For search text bound as `$param`
+    pub fn bound_at(param: usize) -> Self {
+        Self { param }
+    }
+
+    fn tsquery(&self) -> String {
+        format!("websearch_to_tsquery('simple', ${})", self.param)
+    }
+
+    /// Whether the `tsvector` expression matches (uses its GIN index)
+    pub fn matches(&self, vector: &str) -> String {
+        format!("{} @@ {}", vector, self.tsquery())
+    }
+
+    /// How well it matches, higher first; comparable across tables since the vectors share weights
+    pub fn rank(&self, vector: &str) -> String {
+        format!("ts_rank({}, {})", vector, self.tsquery())
+    }
+
+    /// The whole text with every match marked, for `highlights` to turn into offsets
+    pub fn headline(&self, text: &str) -> String {
+        format!(
+            "ts_headline('simple', {}, {}, 'HighlightAll=true, StartSel={}, StopSel={}')",
+            text,
+            self.tsquery(),
+            HIGHLIGHT_START,
+            HIGHLIGHT_STOP
+        )
+    }
+}
+
+/// The search text from `?q=`, or a 400 if it's empty or too long
+pub fn query_text(q: &str) -> Result<&str, ApiError> {
+    let q = q.trim();
+    if q.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "q is required".to_string()).into());
+    }
+    if q.chars().count() > MAX_QUERY_LENGTH {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("q must be at most {} characters", MAX_QUERY_LENGTH),
+        )
+            .into());
+    }
+    Ok(q)
+}
+
+/// `[start, end)` character offsets of the matches marked in a `TextQuery::headline`,
+/// relative to the unmarked text
+pub fn highlights(headline: &str) -> Vec<[usize; 2]> {
+    let mut ranges = Vec::new();
+    let mut offset = 0;
+    let mut start = None;
+    for c in headline.chars() {
+        match c {
+            HIGHLIGHT_START => start = Some(offset),
+            HIGHLIGHT_STOP => {
+                if let Some(start) = start.take() {
+                    ranges.push([start, offset]);
+                }
+            }
+            _ => offset += 1,
+        }
+    }
+    ranges
+}
+
+/// How search results are ordered (`?sort=`)
+#[derive(Clone, Copy, PartialEq)]
+pub enum Sort {
+    /// Best matches first, the default
+    Relevance,
+    Newest,
+    /// What people interacted with most first; each endpoint says what counts
+    Engaged,
+}
+
+impl Sort {
+    pub fn parse(sort: Option<&str>) -> Result<Self, ApiError> {
+        match sort.unwrap_or("relevance") {
+            "relevance" => Ok(Sort::Relevance),
+            "newest" => Ok(Sort::Newest),
+            "engaged" => Ok(Sort::Engaged),
+            _ => Err((
+                StatusCode::BAD_REQUEST,
+                "sort must be relevance, newest or engaged".to_string(),
+            )
+                .into()),
+        }
+    }
+
+    /// ORDER BY for rows with `rank`, `engagement` and `created_at` columns
+    pub fn order_by(self) -> &'static str {
+        match self {
+            Sort::Relevance => "rank DESC, created_at DESC",
+            Sort::Newest => "created_at DESC, rank DESC",
+            Sort::Engaged => "engagement DESC, rank DESC, created_at DESC",
+        }
+    }
+}
+
+/// `?type=`, "all" when left out, or a 400 if it's not a type
+pub fn search_type(search_type: Option<&str>) -> Result<&str, ApiError> {
+    let search_type = search_type.unwrap_or("all");
+    if !SEARCH_TYPES.contains(&search_type) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "type must be users, posts, projects or all".to_string(),
+        )
+            .into());
+    }
+    Ok(search_type)
+}
+
+/// A 400 unless `from` comes before `to`
+pub fn check_range(from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Result<(), ApiError> {
+    match (from, to) {
+        (Some(from), Some(to)) if from >= to => Err((
+            StatusCode::BAD_REQUEST,
+            "from must be before to".to_string(),
+        )
+            .into()),
+        _ => Ok(()),
+    }
+}
+
+#[derive(Default, Deserialize)]
+pub struct SearchParams {
+    pub q: String,
+    /// "users", "posts", "projects" or "all" (the default)
+    #[serde(rename = "type")]
+    pub search_type: Option<String>,
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+    /// Only results created (users: joined) at or after this
+    pub from: Option<DateTime<Utc>>,
+    /// ...and before this
+    pub to: Option<DateTime<Utc>>,
+    /// Only posts and projects by this username
+    pub author: Option<String>,
+    /// Only projects with this status
+    pub status: Option<String>,
+    /// Only projects with this tag (any case)
+    pub tag: Option<String>,
+    /// "relevance" (the default), "newest" or "engaged": followers for users and post authors,
+    /// applications for projects
+    pub sort: Option<String>,
+}
+
+/// One hit of any type. `username` and `avatar_url` are the user's own, or the author's.
+#[derive(Serialize)]
+pub struct SearchResult {
+    #[serde(rename = "type")]
+    pub result_type: String, // "user", "post" or "project"
+    pub id: Uuid,
+    /// Display name, or the project title; for posts, the author's display name
+    pub title: String,
+    /// Bio, post content or project description
+    pub text: Option<String>,
+    /// `[start, end)` character offsets of the matches in `text`
+    pub highlights: Vec<[usize; 2]>,
+    /// Path in the frontend that shows it
+    pub link: String,
+    pub username: String,
+    pub avatar_url: Option<String>,
+    pub rank: f32,
+    pub engagement: i64,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(sqlx::FromRow)]
+struct SearchRow {
+    result_type: String,
+    id: Uuid,
+    title: String,
+    text: Option<String>,
+    headline: String,
+    link: String,
+    username: String,
+    avatar_url: Option<String>,
+    rank: f32,
+    engagement: i64,
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Serialize)]
+pub struct SearchPage {
+    pub results: Vec<SearchResult>,
+    pub page: i64,
+    pub per_page: i64,
+    pub total: i64,
+}
+
+/// FROM clause for every match of `query` ($1) under the filters, bound as $2 type, $3 from,
+/// $4 to, $5 author (lowercase), $6 status and $7 tag. `indexed` matches the ids bound as $10
+/// (hits from the search index) instead.
+fn matches_sql(query: &TextQuery, indexed: bool) -> String {
+    let condition = |vector: &str, id: &str| {
+        if indexed {
+            format!("{} = ANY($10)", id)
+        } else {
+            query.matches(vector)
+        }
+    };
+    format!(
+        r#"
+        FROM (
+            SELECT 'user' AS result_type, u.id, u.display_name AS title, u.bio AS text,
+                   '/' || u.username AS link, u.username, u.avatar_url,
+                   {user_rank} AS rank,
+                   (SELECT COUNT(*) FROM follows f WHERE f.followee_id = u.id) AS engagement,
+                   u.created_at
+            FROM users u
+            WHERE $2 IN ('all', 'users') AND u.banned_at IS NULL AND {user_matches}
+              AND ($3::timestamptz IS NULL OR u.created_at >= $3)
+              AND ($4::timestamptz IS NULL OR u.created_at < $4)
+              AND $5::text IS NULL AND $6::text IS NULL AND $7::text IS NULL
+
+            UNION ALL
+
+            SELECT 'post', p.id, u.display_name, p.content,
+                   '/' || u.username || '#post-' || p.id, u.username, u.avatar_url,
+                   {post_rank},
+                   (SELECT COUNT(*) FROM follows f WHERE f.followee_id = u.id),
+                   p.created_at
+            FROM posts p
+            JOIN users u ON u.id = p.author_id
+            WHERE $2 IN ('all', 'posts') AND u.banned_at IS NULL AND p.held_at IS NULL
+              AND {post_matches}
+              AND ($3::timestamptz IS NULL OR p.created_at >= $3)
+              AND ($4::timestamptz IS NULL OR p.created_at < $4)
+              AND ($5::text IS NULL OR u.username = $5)
+              AND $6::text IS NULL AND $7::text IS NULL
+
+            UNION ALL
+
+            SELECT 'project', p.id, p.title, p.description,
+                   '/' || u.username || '/' || p.slug, u.username, u.avatar_url,
+                   {project_rank},
+                   (SELECT COUNT(*) FROM applications a
+                    WHERE a.project_id = p.id AND a.held_at IS NULL),
+                   p.created_at
+            FROM projects p
+            JOIN users u ON u.id = p.owner_id
+            WHERE $2 IN ('all', 'projects') AND u.banned_at IS NULL AND p.held_at IS NULL
+              AND {project_matches}
+              AND ($3::timestamptz IS NULL OR p.created_at >= $3)
+              AND ($4::timestamptz IS NULL OR p.created_at < $4)
+              AND ($5::text IS NULL OR u.username = $5)
+              AND ($6::text IS NULL OR p.status = $6)
+              AND ($7::text IS NULL OR lower($7) IN (SELECT lower(t) FROM unnest(p.tags) t))
+        ) matches
+        "#,
+        user_rank = query.rank("u.search_vector"),
+        user_matches = condition("u.search_vector", "u.id"),
+        post_rank = query.rank("p.search_vector"),
+        post_matches = condition("p.search_vector", "p.id"),
+        project_rank = query.rank("p.search_vector"),
+        project_matches = condition("p.search_vector", "p.id"),
+    )
+}
+
+/// How many users, posts and projects match `q` under the filters in `params`
+pub async fn count_matches(
+    pool: &PgPool,
+    q: &str,
+    params: &SearchParams,
+) -> Result<i64, sqlx::Error> {
+    let matches = matches_sql(&TextQuery::bound_at(1), false);
+    sqlx::query_scalar(&format!("SELECT COUNT(*)::bigint {}", matches))
+        .bind(q)
+        .bind(params.search_type.as_deref().unwrap_or("all"))
+        .bind(params.from)
+        .bind(params.to)
+        .bind(params.author.as_deref().map(str::to_lowercase))
+        .bind(&params.status)
+        .bind(&params.tag)
+        .fetch_one(pool)
+        .await
+}
+
+/// Users, posts and projects matching `?q=` (web search syntax), best matches first, in one list.
+/// Banned users and everything they wrote are left out. A filter leaves out the types it doesn't
+/// apply to: `author` the users, `status` and `tag` everything but projects.
+/// With a search index configured it finds the matches (Postgres still loads and highlights
+/// them), unless it fails or `sort=engaged`, which it can't do.
+pub async fn search(
+    State(db): State<DbRouter>,
+    State(indexer): State<Indexer>,
+    Query(params): Query<SearchParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    let q = query_text(&params.q)?;
+    let search_type = search_type(params.search_type.as_deref())?;
+    let per_page = params
+        .per_page
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let page = params.page.unwrap_or(1).max(1);
+    let sort = Sort::parse(params.sort.as_deref())?;
+    check_range(params.from, params.to)?;
+    let author = params.author.as_deref().map(str::to_lowercase);
+
+    let hits = match indexer.index() {
+        Some(index) if sort != Sort::Engaged => {
+            let kinds: Vec<&str> = ["user", "post", "project"]
+                .into_iter()
+                .filter(|kind| search_type == "all" || search_type.starts_with(kind))
+                .filter(|kind| author.is_none() || *kind != "user")
+                .filter(|kind| {
+                    (params.status.is_none() && params.tag.is_none()) || *kind == "project"
+                })
+                .collect();
+            let index_query = IndexQuery {
+                q,
+                kinds: &kinds,
+                from: params.from,
+                to: params.to,
+                author: author.as_deref(),
+                status: params.status.as_deref(),
+                tag: params.tag.as_deref(),
+                newest_first: sort == Sort::Newest,
+                page,
+                per_page,
+            };
+            if kinds.is_empty() {
+                Some(IndexHits {
+                    ids: Vec::new(),
+                    total: 0,
+                })
+            } else {
+                match index.search(&index_query).await {
+                    Ok(hits) => Some(hits),
+                    Err(e) => {
+                        tracing::warn!("Search index failed, using Postgres: {}", e);
+                        None
+                    }
+                }
+            }
+        }
+        _ => None,
+    };
+
+    let query = TextQuery::bound_at(1);
+    let matches = matches_sql(&query, hits.is_some());
+
+    let (total, order, offset) = match &hits {
+        // Already paged, in the index's order
+        Some(hits) => (hits.total, "array_position($10, id)", 0),
+        None => {
+            let total = count_matches(db.read(), q, &params)
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            (total, sort.order_by(), (page - 1) * per_page)
+        }
+    };
+
+    // Highlighted after paging, so only the rows returned pay for it
+    let sql = format!(
+        r#"
+        SELECT result_type, id, title, text, {headline} AS headline, link, username, avatar_url,
+               rank, engagement, created_at
+        FROM (
+            SELECT * {matches}
+            ORDER BY {order}
+            LIMIT $8 OFFSET $9
+        ) page
+        ORDER BY {order}
+        "#,
+        headline = query.headline("COALESCE(text, '')"),
+        matches = matches,
+        order = order
+    );
+    let mut rows = sqlx::query_as::<_, SearchRow>(&sql)
+        .bind(q)
+        .bind(search_type)
+        .bind(params.from)
+        .bind(params.to)
+        .bind(&author)
+        .bind(&params.status)
+        .bind(&params.tag)
+        .bind(per_page)
+        .bind(offset);
+    if let Some(hits) = &hits {
+        rows = rows.bind(&hits.ids);
+    }
+    let rows = rows
+        .fetch_all(db.read())
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let results = rows
+        .into_iter()
+        .map(|row| SearchResult {
+            highlights: highlights(&row.headline),
+            result_type: row.result_type,
+            id: row.id,
+            title: row.title,
+            text: row.text,
+            link: row.link,
+            username: row.username,
+            avatar_url: row.avatar_url,
+            rank: row.rank,
+            engagement: row.engagement,
+            created_at: row.created_at,
+        })
+        .collect();
+
+    Ok(Json(SearchPage {
+        results,
+        page,
+        per_page,
+        total,
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct SuggestParams {
+    pub q: String,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct Suggestion {
+    #[serde(rename = "type")]
+    pub result_type: String, // "user" or "project"
+    /// Username, or the project title
+    pub label: String,
+    pub link: String,
+    /// The user's, or the project owner's
+    pub avatar_url: Option<String>,
+}
+
+/// `q` as a LIKE prefix pattern, with its own wildcards taken literally
+fn prefix_pattern(q: &str) -> String {
+    let mut pattern = String::with_capacity(q.len() + 1);
+    for c in q.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
+
+/// Up to 5 usernames and project titles starting with `?q=`, closest first. Meant to be called
+/// on every keystroke: one indexed query, no counting or highlighting, cacheable for a minute.
+pub async fn suggest(
+    State(db): State<DbRouter>,
+    Query(params): Query<SuggestParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    let q = params.q.trim().trim_start_matches('@');
+    if q.is_empty() {
+        return Ok((
+            [(header::CACHE_CONTROL, "public, max-age=60")],
+            Json(Vec::new()),
+        ));
+    }
+    if q.chars().count() > MAX_SUGGEST_QUERY_LENGTH {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("q must be at most {} characters", MAX_SUGGEST_QUERY_LENGTH),
+        )
+            .into());
+    }
+
+    // Usernames are stored lowercase; similarity puts the shortest completions first
+    let suggestions = sqlx::query_as!(
+        Suggestion,
+        r#"
+        SELECT result_type AS "result_type!", label AS "label!", link AS "link!", avatar_url
+        FROM (
+            (SELECT 'user' AS result_type, u.username AS label, '/' || u.username AS link,
+                    u.avatar_url, similarity(u.username, $2) AS score
+             FROM users u
+             WHERE u.username LIKE $1 AND u.banned_at IS NULL
+             ORDER BY score DESC, u.username
+             LIMIT $3)
+
+            UNION ALL
+
+            (SELECT 'project', p.title, '/' || u.username || '/' || p.slug, u.avatar_url,
+                    similarity(p.title, $2)
+             FROM projects p
+             JOIN users u ON u.id = p.owner_id
+             WHERE p.title ILIKE $1 AND u.banned_at IS NULL AND p.held_at IS NULL
+             ORDER BY similarity(p.title, $2) DESC, p.title
+             LIMIT $3)
+        ) suggestions
+        ORDER BY score DESC, label
+        LIMIT $3
+        "#,
+        prefix_pattern(&q.to_lowercase()),
+        q,
+        SUGGESTIONS
+    )
+    .fetch_all(db.read())
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok((
+        [(header::CACHE_CONTROL, "public, max-age=60")],
+        Json(suggestions),
+    ))
+}
@@ -0,0 +1,377 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+
+// Documents per request when reindexing
+const REINDEX_BATCH_SIZE: usize = 1000;
+
+/// What's indexed for a user, post or project. Names, avatars and links aren't: hits are loaded
+/// back from Postgres, so what's shown is never stale and banned users never show up.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SearchDocument {
+    /// "<kind>-<record_id>"
+    pub id: String,
+    pub kind: String, // "user", "post" or "project"
+    pub record_id: Uuid,
+    /// The user, or the author or owner
+    pub user_id: Uuid,
+    /// Username and display name, or the project title (empty for posts)
+    pub title: String,
+    /// Bio, post content or project description
+    pub text: String,
+    /// Username of the user, author or owner
+    pub author: String,
+    pub status: Option<String>,
+    /// Lowercase
+    pub tags: Vec<String>,
+    /// Unix seconds
+    pub created_at: i64,
+}
+
+/// A search as `GET /search` takes it; the index answers with one page of ids
+pub struct IndexQuery<'a> {
+    pub q: &'a str,
+    /// Which of "user", "post" and "project" to search
+    pub kinds: &'a [&'a str],
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    /// Lowercase username
+    pub author: Option<&'a str>,
+    pub status: Option<&'a str>,
+    pub tag: Option<&'a str>,
+    /// Otherwise best matches first
+    pub newest_first: bool,
+    pub page: i64,
+    pub per_page: i64,
+}
+
+pub struct IndexHits {
+    /// Record ids, best first
+    pub ids: Vec<Uuid>,
+    pub total: i64,
+}
+
+/// A search engine kept alongside Postgres for larger deployments. Postgres stays the source
+/// of truth: writes are best effort, and `reindex` rebuilds the index from it.
+#[async_trait]
+pub trait SearchIndex: Send + Sync {
+    /// Add or replace documents
+    async fn upsert(&self, documents: &[SearchDocument]) -> Result<(), String>;
+    async fn delete(&self, ids: &[String]) -> Result<(), String>;
+    /// Remove a user and everything they made
+    async fn delete_user(&self, user_id: Uuid) -> Result<(), String>;
+    async fn clear(&self) -> Result<(), String>;
+    async fn search(&self, query: &IndexQuery<'_>) -> Result<IndexHits, String>;
+}
+
+/// Meilisearch over its HTTP API (MEILISEARCH_URL, MEILISEARCH_API_KEY, MEILISEARCH_INDEX)
+pub struct Meilisearch {
+    http: reqwest::Client,
+    url: String,
+    api_key: Option<String>,
+    index: String,
+}
+
+impl Meilisearch {
+    /// None unless MEILISEARCH_URL is set
+    pub fn from_env(http: reqwest::Client) -> Option<Self> {
+        let url = std::env::var("MEILISEARCH_URL").ok()?;
+        Some(Self {
+            http,
+            url: url.trim_end_matches('/').to_string(),
+            api_key: std::env::var("MEILISEARCH_API_KEY").ok(),
+            index: std::env::var("MEILISEARCH_INDEX").unwrap_or_else(|_| "praxis".to_string()),
+        })
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        let request = self.http.request(
+            method,
+            format!("{}/indexes/{}{}", self.url, self.index, path),
+        );
+        match &self.api_key {
+            Some(key) => request.bearer_auth(key),
+            None => request,
+        }
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<reqwest::Response, String> {
+        request
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .map_err(|e| format!("Meilisearch: {}", e))
+    }
+
+    /// Create the index if needed and set which fields are searched, filtered and sorted on
+    pub async fn configure(&self) -> Result<(), String> {
+        let settings = serde_json::json!({
+            "searchableAttributes": ["title", "text"],
+            "filterableAttributes": ["kind", "user_id", "author", "status", "tags", "created_at"],
+            "sortableAttributes": ["created_at"],
+        });
+        self.send(
+            self.request(reqwest::Method::PATCH, "/settings")
+                .json(&settings),
+        )
+        .await
+        .map(|_| ())
+    }
+}
+
+/// A string in a Meilisearch filter
+fn quoted(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[async_trait]
+impl SearchIndex for Meilisearch {
+    async fn upsert(&self, documents: &[SearchDocument]) -> Result<(), String> {
+        if documents.is_empty() {
+            return Ok(());
+        }
+        self.send(
+            self.request(reqwest::Method::POST, "/documents?primaryKey=id")
+                .json(documents),
+        )
+        .await
+        .map(|_| ())
+    }
+
+    async fn delete(&self, ids: &[String]) -> Result<(), String> {
+        self.send(
+            self.request(reqwest::Method::POST, "/documents/delete-batch")
+                .json(ids),
+        )
+        .await
+        .map(|_| ())
+    }
+
+    async fn delete_user(&self, user_id: Uuid) -> Result<(), String> {
+        let filter = format!("user_id = {}", quoted(&user_id.to_string()));
+        self.send(
+            self.request(reqwest::Method::POST, "/documents/delete")
+                .json(&serde_json::json!({ "filter": filter })),
+        )
+        .await
+        .map(|_| ())
+    }
+
+    async fn clear(&self) -> Result<(), String> {
+        self.send(self.request(reqwest::Method::DELETE, "/documents"))
+            .await
+            .map(|_| ())
+    }
+
+    async fn search(&self, query: &IndexQuery<'_>) -> Result<IndexHits, String> {
+        let kinds: Vec<String> = query.kinds.iter().map(|kind| quoted(kind)).collect();
+        let mut filter = vec![format!("kind IN [{}]", kinds.join(", "))];
+        if let Some(from) = query.from {
+            filter.push(format!("created_at >= {}", from.timestamp()));
+        }
+        if let Some(to) = query.to {
+            filter.push(format!("created_at < {}", to.timestamp()));
+        }
+        if let Some(author) = query.author {
+            filter.push(format!("author = {}", quoted(author)));
+        }
+        if let Some(status) = query.status {
+            filter.push(format!("status = {}", quoted(status)));
+        }
+        if let Some(tag) = query.tag {
+            filter.push(format!("tags = {}", quoted(&tag.to_lowercase())));
+        }
+
+        let mut body = serde_json::json!({
+            "q": query.q,
+            "filter": filter.join(" AND "),
+            "page": query.page,
+            "hitsPerPage": query.per_page,
+            "attributesToRetrieve": ["record_id"],
+        });
+        if query.newest_first {
+            body["sort"] = serde_json::json!(["created_at:desc"]);
+        }
+
+        #[derive(Deserialize)]
+        struct Hit {
+            record_id: Uuid,
+        }
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Response {
+            hits: Vec<Hit>,
+            total_hits: i64,
+        }
+        let response: Response = self
+            .send(self.request(reqwest::Method::POST, "/search").json(&body))
+            .await?
+            .json()
+            .await
+            .map_err(|e| format!("Meilisearch: {}", e))?;
+
+        Ok(IndexHits {
+            ids: response.hits.into_iter().map(|hit| hit.record_id).collect(),
+            total: response.total_hits,
+        })
+    }
+}
+
+/// Keeps the search index (if there is one) in step with writes. Failures are logged and left
+/// for the next `reindex`, so they never fail the request that made the change.
+#[derive(Clone, Default)]
+pub struct Indexer(Option<Arc<dyn SearchIndex>>);
+
+impl Indexer {
+    pub fn new(index: Option<Arc<dyn SearchIndex>>) -> Self {
+        Self(index)
+    }
+
+    /// Meilisearch when MEILISEARCH_URL is set, otherwise nothing (search uses Postgres)
+    pub fn from_env(http: reqwest::Client) -> Self {
+        let Some(meilisearch) = Meilisearch::from_env(http) else {
+            return Self(None);
+        };
+        let meilisearch = Arc::new(meilisearch);
+        let configuring = meilisearch.clone();
+        tokio::spawn(async move {
+            if let Err(e) = configuring.configure().await {
+                tracing::error!("Failed to configure the search index: {}", e);
+            }
+        });
+        tracing::info!("Search backed by Meilisearch");
+        Self(Some(meilisearch))
+    }
+
+    pub fn index(&self) -> Option<&dyn SearchIndex> {
+        self.0.as_deref()
+    }
+
+    /// Index a post as it is now, or drop it if it's gone
+    pub async fn sync_post(&self, pool: &PgPool, id: Uuid) {
+        self.sync(pool, Scope::Post, id, "post").await;
+    }
+
+    /// Index a project as it is now, or drop it if it's gone
+    pub async fn sync_project(&self, pool: &PgPool, id: Uuid) {
+        self.sync(pool, Scope::Project, id, "project").await;
+    }
+
+    /// Reindex a user and everything they made (their username is on all of it), or drop it
+    /// all if they're deleted or banned
+    pub async fn sync_user(&self, pool: &PgPool, id: Uuid) {
+        let Some(index) = self.index() else {
+            return;
+        };
+        let result = async {
+            let documents = load(pool, Scope::User, Some(id))
+                .await
+                .map_err(|e| e.to_string())?;
+            index.delete_user(id).await?;
+            index.upsert(&documents).await
+        }
+        .await;
+        if let Err(e) = result {
+            tracing::error!("Failed to index user {}: {}", id, e);
+        }
+    }
+
+    async fn sync(&self, pool: &PgPool, scope: Scope, id: Uuid, kind: &str) {
+        let Some(index) = self.index() else {
+            return;
+        };
+        let result = async {
+            let documents = load(pool, scope, Some(id))
+                .await
+                .map_err(|e| e.to_string())?;
+            if documents.is_empty() {
+                index.delete(&[format!("{}-{}", kind, id)]).await
+            } else {
+                index.upsert(&documents).await
+            }
+        }
+        .await;
+        if let Err(e) = result {
+            tracing::error!("Failed to index {} {}: {}", kind, id, e);
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Scope {
+    All,
+    /// A user and everything they made
+    User,
+    Post,
+    Project,
+}
+
+impl Scope {
+    fn as_str(self) -> &'static str {
+        match self {
+            Scope::All => "all",
+            Scope::User => "user",
+            Scope::Post => "post",
+            Scope::Project => "project",
+        }
+    }
+}
+
+/// Documents for everything in `scope` (`id` picks the user, post or project).
+/// Banned users and what they made are left out.
+async fn load(
+    pool: &PgPool,
+    scope: Scope,
+    id: Option<Uuid>,
+) -> Result<Vec<SearchDocument>, sqlx::Error> {
+    sqlx::query_as!(
+        SearchDocument,
+        r#"
+        SELECT 'user-' || u.id AS "id!", 'user' AS "kind!", u.id AS "record_id!",
+               u.id AS "user_id!", u.username || ' ' || u.display_name AS "title!",
+               COALESCE(u.bio, '') AS "text!", u.username AS "author!", NULL::text AS status,
+               '{}'::text[] AS "tags!",
+               EXTRACT(EPOCH FROM COALESCE(u.created_at, NOW()))::bigint AS "created_at!"
+        FROM users u
+        WHERE u.banned_at IS NULL AND ($1 = 'all' OR ($1 = 'user' AND u.id = $2))
+
+        UNION ALL
+
+        SELECT 'post-' || p.id, 'post', p.id, u.id, '', p.content, u.username, NULL,
+               '{}'::text[], EXTRACT(EPOCH FROM p.created_at)::bigint
+        FROM posts p
+        JOIN users u ON u.id = p.author_id
+        WHERE u.banned_at IS NULL AND p.held_at IS NULL
+          AND ($1 = 'all' OR ($1 = 'user' AND u.id = $2) OR ($1 = 'post' AND p.id = $2))
+
+        UNION ALL
+
+        SELECT 'project-' || p.id, 'project', p.id, u.id, p.title, COALESCE(p.description, ''),
+               u.username, p.status, ARRAY(SELECT lower(t) FROM unnest(p.tags) t),
+               EXTRACT(EPOCH FROM p.created_at)::bigint
+        FROM projects p
+        JOIN users u ON u.id = p.owner_id
+        WHERE u.banned_at IS NULL AND p.held_at IS NULL
+          AND ($1 = 'all' OR ($1 = 'user' AND u.id = $2) OR ($1 = 'project' AND p.id = $2))
+        "#,
+        scope.as_str(),
+        id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Rebuild the index from Postgres. Returns the number of documents indexed.
+pub async fn reindex(pool: &PgPool, index: &dyn SearchIndex) -> Result<usize, String> {
+    let documents = load(pool, Scope::All, None)
+        .await
+        .map_err(|e| e.to_string())?;
+    index.clear().await?;
+    for batch in documents.chunks(REINDEX_BATCH_SIZE) {
+        index.upsert(batch).await?;
+    }
+    Ok(documents.len())
+}
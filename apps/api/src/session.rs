@@ -1,31 +1,105 @@
+use askama::Template;
 use axum::{
-    extract::{ConnectInfo, Path, State},
+    extract::{Path, Request, State},
     http::{HeaderMap, StatusCode},
-    response::{IntoResponse, Json},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
 };
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
-use std::net::SocketAddr;
+use std::net::IpAddr;
 use tower_sessions::Session;
 use uuid::Uuid;
 
+use crate::client_ip::ClientIp;
+use crate::email::{self, Message};
+use crate::email_preferences::EmailCategory;
+use crate::error::ApiError;
+use crate::extractors::{AdminUser, AuthUser};
+use crate::geoip::GeoIp;
+use crate::i18n::{self, EmailStrings, Locale};
+use crate::session_store::SessionBackend;
+use crate::state::AppState;
+
+/// Session key holding when the session's active_sessions row was last refreshed
+const TRACKED_AT_KEY: &str = "tracked_at";
+// A session's last_active_at is refreshed at most this often
+const TOUCH_INTERVAL_MINUTES: i64 = 5;
+
 #[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
 pub struct ActiveSession {
     pub id: Uuid,
     pub user_id: Uuid,
     pub session_id: String,
     pub user_agent: Option<String>,
+    /// e.g. "Chrome 120", parsed from the user agent
+    pub browser: Option<String>,
+    /// e.g. "macOS", "Windows 10", "iOS"
+    pub os: Option<String>,
+    /// "desktop", "mobile", "tablet", "bot" or "other"
+    pub device: Option<String>,
     pub ip_address: Option<String>,
+    /// Where `ip_address` was looked up to be, once known
+    pub city: Option<String>,
+    pub country: Option<String>,
     pub last_active_at: DateTime<Utc>,
     pub expires_at: DateTime<Utc>,
     pub created_at: DateTime<Utc>,
     pub is_current: Option<bool>, // Computed field for UI
 }
 
-// Internal helper to create a session record
+/// What a user agent string says about the device, for the sessions list
+#[derive(Debug, Default, PartialEq)]
+pub struct Device {
+    pub browser: Option<String>,
+    pub os: Option<String>,
+    pub device: Option<String>,
+}
+
+pub fn parse_user_agent(user_agent: &str) -> Device {
+    let Some(parsed) = woothee::parser::Parser::new().parse(user_agent) else {
+        return Device {
+            device: Some("other".to_string()),
+            ..Default::default()
+        };
+    };
+    let known = |value: &str| !value.is_empty() && value != woothee::woothee::VALUE_UNKNOWN;
+
+    let browser =
+        known(parsed.name).then(
+            || match parsed.version.split('.').next().filter(|v| known(v)) {
+                Some(major) => format!("{} {}", parsed.name, major),
+                None => parsed.name.to_string(),
+            },
+        );
+    let os = known(parsed.os).then(|| match parsed.os {
+        "Mac OSX" => "macOS".to_string(),
+        "iPhone" | "iPad" | "iPod" => "iOS".to_string(),
+        os => os.to_string(),
+    });
+    // Android tablets leave "Mobile" out of the user agent
+    let device = match parsed.category {
+        _ if parsed.os == "iPad" => "tablet",
+        "smartphone" if parsed.os == "Android" && !user_agent.contains("Mobile") => "tablet",
+        "smartphone" | "mobilephone" => "mobile",
+        "pc" => "desktop",
+        "crawler" => "bot",
+        _ => "other",
+    };
+
+    Device {
+        browser,
+        os,
+        device: Some(device.to_string()),
+    }
+}
+
+// Internal helper to create a session record. Its location is looked up in the background,
+// so a slow GeoIP provider doesn't hold up logging in.
 pub async fn create_session(
     pool: &PgPool,
+    geoip: &GeoIp,
     user_id: Uuid,
     session_id: String,
     headers: &HeaderMap,
@@ -36,15 +110,10 @@ pub async fn create_session(
         .get("user-agent")
         .and_then(|h| h.to_str().ok())
         .map(|s| s.to_string());
-
-    // Try to get IP from X-Forwarded-For (if behind proxy) or fallback
-    // Note: In a real deployment, you'd want to be careful about trusting headers
-    // For now we'll just take the first value if present
-    let ip_address = headers
-        .get("x-forwarded-for")
-        .and_then(|h| h.to_str().ok())
-        .map(|s| s.split(',').next().unwrap_or(s).trim().to_string())
-        .or(ip_address);
+    let device = user_agent
+        .as_deref()
+        .map(parse_user_agent)
+        .unwrap_or_default();
 
     tracing::debug!(
         "Creating/Updating session for user {}. IP: {:?}, User-Agent: {:?}",
@@ -79,16 +148,25 @@ pub async fn create_session(
 
     sqlx::query!(
         r#"
-        INSERT INTO active_sessions (user_id, session_id, user_agent, ip_address, expires_at)
-        VALUES ($1, $2, $3, $4, $5)
+        INSERT INTO active_sessions
+            (user_id, session_id, user_agent, ip_address, expires_at, browser, os, device)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
         ON CONFLICT (session_id) DO UPDATE 
-        SET last_active_at = NOW(), user_agent = $3, ip_address = $4, expires_at = $5
+        SET user_id = $1, last_active_at = NOW(), user_agent = $3, ip_address = $4, expires_at = $5,
+            browser = $6, os = $7, device = $8,
+            city = CASE WHEN active_sessions.ip_address IS DISTINCT FROM $4
+                        THEN NULL ELSE active_sessions.city END,
+            country = CASE WHEN active_sessions.ip_address IS DISTINCT FROM $4
+                           THEN NULL ELSE active_sessions.country END
         "#,
         user_id,
         session_id,
         user_agent,
         ip_address,
-        expires_at
+        expires_at,
+        device.browser,
+        device.os,
+        device.device
     )
     .execute(pool)
     .await
@@ -99,22 +177,237 @@ pub async fn create_session(
 
     tracing::debug!("Session {} tracked successfully", session_id);
 
+    let public_ip = ip_address
+        .as_deref()
+        .and_then(|ip| ip.parse::<IpAddr>().ok())
+        .filter(|ip| crate::geoip::is_public(*ip));
+    if let Some(ip) = public_ip {
+        let (pool, geoip) = (pool.clone(), geoip.clone());
+        tokio::spawn(async move { record_location(&pool, &geoip, &session_id, ip).await });
+    }
+
     Ok(())
 }
 
+async fn record_location(pool: &PgPool, geoip: &GeoIp, session_id: &str, ip: IpAddr) {
+    let location = match geoip.lookup(ip).await {
+        Ok(Some(location)) => location,
+        Ok(None) => return,
+        Err(e) => {
+            tracing::warn!("Failed to locate session {}: {}", session_id, e);
+            return;
+        }
+    };
+
+    // Unless the session has moved on to another address in the meantime
+    let result = sqlx::query!(
+        "UPDATE active_sessions SET city = $2, country = $3 WHERE session_id = $1 AND ip_address = $4",
+        session_id,
+        location.city,
+        location.country,
+        ip.to_string()
+    )
+    .execute(pool)
+    .await;
+    if let Err(e) = result {
+        tracing::error!("Failed to save location of session {}: {}", session_id, e);
+    }
+}
+
+/// Give the session a new ID, so an ID planted before a login or password change can't be
+/// used afterwards (session fixation). Its active_sessions row follows it to the new ID.
+pub async fn rotate_id(pool: &PgPool, session: &Session) -> Result<(), String> {
+    let old_id = session.id().map(|id| id.to_string());
+    session.cycle_id().await.map_err(|e| e.to_string())?;
+    session.save().await.map_err(|e| e.to_string())?;
+
+    if let (Some(old_id), Some(new_id)) = (old_id, session.id()) {
+        sqlx::query!(
+            "UPDATE active_sessions SET session_id = $2 WHERE session_id = $1",
+            old_id,
+            new_id.to_string()
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    }
+
+    Ok(())
+}
+
+// Revoke every session a user has (e.g. when they are banned)
+pub async fn revoke_user_sessions(
+    pool: &PgPool,
+    sessions: &SessionBackend,
+    user_id: Uuid,
+) -> Result<u64, String> {
+    let rows = sqlx::query!(
+        "DELETE FROM active_sessions WHERE user_id = $1 RETURNING session_id",
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let session_ids: Vec<String> = rows.into_iter().map(|s| s.session_id).collect();
+    sessions.delete_ids(&session_ids).await?;
+
+    Ok(session_ids.len() as u64)
+}
+
+/// Sign everyone out, e.g. after a leak. Returns how many sessions were revoked.
+pub async fn revoke_all_sessions(pool: &PgPool, sessions: &SessionBackend) -> Result<u64, String> {
+    let session_ids = sqlx::query_scalar!("DELETE FROM active_sessions RETURNING session_id")
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    sessions.delete_ids(&session_ids).await?;
+
+    Ok(session_ids.len() as u64)
+}
+
+/// Delete expired sessions from the session store, then any active_sessions rows
+/// whose session is gone. Returns the number of active_sessions rows removed.
+pub async fn purge_expired_sessions(
+    pool: &PgPool,
+    sessions: &SessionBackend,
+) -> Result<u64, String> {
+    match sessions {
+        SessionBackend::Postgres(_) => {
+            sqlx::query!("DELETE FROM tower_sessions.session WHERE expiry_date < NOW()")
+                .execute(pool)
+                .await
+                .map_err(|e| e.to_string())?;
+
+            // The grace period covers logins whose session hasn't been saved to the store yet
+            let result = sqlx::query!(
+                r#"
+                DELETE FROM active_sessions a
+                WHERE a.created_at < NOW() - INTERVAL '1 hour'
+                  AND NOT EXISTS (SELECT 1 FROM tower_sessions.session s WHERE s.id = a.session_id)
+                "#
+            )
+            .execute(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+
+            Ok(result.rows_affected())
+        }
+        // Redis expires sessions on its own, so only the tracking rows need cleaning up
+        SessionBackend::Redis(store) => {
+            let candidates = sqlx::query_scalar!(
+                "SELECT session_id FROM active_sessions WHERE created_at < NOW() - INTERVAL '1 hour'"
+            )
+            .fetch_all(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+
+            let gone = store.missing(&candidates).await?;
+            let result = sqlx::query!(
+                "DELETE FROM active_sessions WHERE session_id = ANY($1)",
+                &gone
+            )
+            .execute(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+
+            Ok(result.rows_affected())
+        }
+    }
+}
+
+/// Middleware keeping active_sessions in step with logged in sessions. Every few minutes
+/// it refreshes the session's last_active_at and expiry, recreating the row if tracking
+/// failed at login. A session whose row has gone since the last refresh was revoked
+/// elsewhere, so it's logged out and the request carries on without a user.
+pub async fn track_activity(
+    State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
+    session: Option<Session>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if let Some(session) = session {
+        // Tracking is best effort; never fail the request over it
+        if let Err(e) = touch(&state, &session, request.headers(), ip).await {
+            tracing::error!("Failed to track session activity: {}", e);
+        }
+    }
+    next.run(request).await
+}
+
+async fn touch(
+    state: &AppState,
+    session: &Session,
+    headers: &HeaderMap,
+    ip: IpAddr,
+) -> Result<(), String> {
+    let Some(user_id) = session
+        .get::<Uuid>("user_id")
+        .await
+        .map_err(|e| e.to_string())?
+    else {
+        return Ok(());
+    };
+    let Some(session_id) = session.id() else {
+        return Ok(());
+    };
+
+    let now = Utc::now();
+    let tracked_at = session
+        .get::<DateTime<Utc>>(TRACKED_AT_KEY)
+        .await
+        .ok()
+        .flatten();
+    if tracked_at.is_some_and(|at| now - at < chrono::Duration::minutes(TOUCH_INTERVAL_MINUTES)) {
+        return Ok(());
+    }
+
+    let expires_at =
+        DateTime::from_timestamp(session.expiry_date().unix_timestamp(), 0).unwrap_or(now);
+    let touched = sqlx::query!(
+        "UPDATE active_sessions SET last_active_at = NOW(), expires_at = $2 WHERE session_id = $1",
+        session_id.to_string(),
+        expires_at
+    )
+    .execute(&state.pool)
+    .await
+    .map_err(|e| e.to_string())?
+    .rows_affected()
+        > 0;
+
+    if !touched {
+        if tracked_at.is_some() {
+            tracing::info!("Session of user {} was revoked, logging it out", user_id);
+            return session.flush().await.map_err(|e| e.to_string());
+        }
+        create_session(
+            &state.pool,
+            &state.geoip,
+            user_id,
+            session_id.to_string(),
+            headers,
+            Some(ip.to_string()),
+            expires_at,
+        )
+        .await?;
+    }
+
+    session
+        .insert(TRACKED_AT_KEY, now)
+        .await
+        .map_err(|e| e.to_string())
+}
+
 // List all sessions for the current user
 pub async fn list_sessions(
     State(pool): State<PgPool>,
+    State(geoip): State<GeoIp>,
+    AuthUser { id: user_id, .. }: AuthUser,
     session: Session,
     headers: HeaderMap,
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    let user_id: Uuid = session
-        .get("user_id")
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-        .ok_or((StatusCode::UNAUTHORIZED, "Not logged in".to_string()))?;
-
+    ClientIp(ip): ClientIp,
+) -> Result<impl IntoResponse, ApiError> {
     // Ensure session has an ID (save if needed)
     if session.id().is_none() {
         tracing::warn!("Session ID missing for user {} in list_sessions", user_id);
@@ -131,7 +424,7 @@ pub async fn list_sessions(
         ActiveSession,
         r#"
         SELECT 
-            id, user_id, session_id, user_agent, ip_address, 
+            id, user_id, session_id, user_agent, browser, os, device, ip_address, city, country,
             last_active_at, expires_at, created_at,
             (session_id = $2) as "is_current?" 
         FROM active_sessions 
@@ -159,10 +452,11 @@ pub async fn list_sessions(
         // Backfill current session
         create_session(
             &pool,
+            &geoip,
             user_id,
             current_session_id.clone(),
             &headers,
-            Some(addr.ip().to_string()),
+            Some(ip.to_string()),
             expires_at,
         )
         .await
@@ -176,7 +470,7 @@ pub async fn list_sessions(
             ActiveSession,
             r#"
             SELECT 
-                id, user_id, session_id, user_agent, ip_address, 
+                id, user_id, session_id, user_agent, browser, os, device, ip_address, city, country,
                 last_active_at, expires_at, created_at,
                 (session_id = $2) as "is_current?" 
             FROM active_sessions 
@@ -193,94 +487,289 @@ pub async fn list_sessions(
         tracing::debug!("Found {} sessions after backfill", sessions.len());
     }
 
+    // Tracked before devices were parsed on login
+    for session in sessions.iter_mut().filter(|s| s.device.is_none()) {
+        if let Some(user_agent) = &session.user_agent {
+            let device = parse_user_agent(user_agent);
+            session.browser = device.browser;
+            session.os = device.os;
+            session.device = device.device;
+        }
+    }
+
     Ok(Json(sessions))
 }
 
-// Revoke a specific session
+/// What a revoked session was, for the email telling the user about it
+struct RevokedSession {
+    session_id: String,
+    browser: Option<String>,
+    os: Option<String>,
+    ip_address: Option<String>,
+    city: Option<String>,
+    country: Option<String>,
+}
+
+struct RevokedDevice {
+    /// e.g. "Chrome 120, macOS"
+    name: String,
+    /// e.g. "Berlin, Germany", or the IP address when it wasn't located
+    place: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "email/sessions_revoked.html")]
+struct SessionsRevokedHtml<'a> {
+    t: &'a EmailStrings,
+    subject: &'a str,
+    devices: &'a [RevokedDevice],
+    sessions_link: &'a str,
+    unsubscribe_url: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "email/sessions_revoked.txt")]
+struct SessionsRevokedText<'a> {
+    t: &'a EmailStrings,
+    devices: &'a [RevokedDevice],
+    sessions_link: &'a str,
+    unsubscribe_url: &'a str,
+}
+
+/// Tell the user which sessions another of theirs just signed out, in case that wasn't them.
+/// Follows the `security` email preference; only verified addresses get it.
+async fn email_revoked(
+    state: &AppState,
+    user_id: Uuid,
+    revoked: &[RevokedSession],
+) -> Result<(), String> {
+    if revoked.is_empty() {
+        return Ok(());
+    }
+    let recipient = sqlx::query!(
+        r#"
+        SELECT l.email, u.locale
+        FROM users u
+        JOIN local_auths l ON l.user_id = u.id
+        WHERE u.id = $1 AND l.verified IS TRUE
+        "#,
+        user_id
+    )
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    let Some(recipient) = recipient else {
+        return Ok(());
+    };
+
+    let t = Locale::from_tag(&recipient.locale).email();
+    let devices: Vec<RevokedDevice> = revoked
+        .iter()
+        .map(|session| {
+            let name: Vec<&str> = [&session.browser, &session.os]
+                .into_iter()
+                .flatten()
+                .map(String::as_str)
+                .collect();
+            let place: Vec<&str> = [&session.city, &session.country]
+                .into_iter()
+                .flatten()
+                .map(String::as_str)
+                .collect();
+            RevokedDevice {
+                name: if name.is_empty() {
+                    t.sessions_revoked_unknown_device.to_string()
+                } else {
+                    name.join(", ")
+                },
+                place: if place.is_empty() {
+                    session.ip_address.clone()
+                } else {
+                    Some(place.join(", "))
+                },
+            }
+        })
+        .collect();
+    let subject = t.sessions_revoked_subject(devices.len());
+    let sessions_link = format!("{}/settings/security", state.config.frontend_url);
+
+    email::queue_optional(
+        state,
+        "sessions_revoked",
+        user_id,
+        EmailCategory::Security,
+        |unsubscribe_url| {
+            Ok(Message {
+                to: recipient.email.clone(),
+                subject: subject.clone(),
+                html: email::render(SessionsRevokedHtml {
+                    t,
+                    subject: &subject,
+                    devices: &devices,
+                    sessions_link: &sessions_link,
+                    unsubscribe_url,
+                })?,
+                text: email::render(SessionsRevokedText {
+                    t,
+                    devices: &devices,
+                    sessions_link: &sessions_link,
+                    unsubscribe_url,
+                })?,
+                unsubscribe_url: None,
+            })
+        },
+    )
+    .await
+    .map(|_| ())
+}
+
+// Revoke a specific session. Signing out another one emails the user about it.
 pub async fn revoke_session(
-    State(pool): State<PgPool>,
+    State(state): State<AppState>,
+    AuthUser { id: user_id, .. }: AuthUser,
     session: Session,
     Path(session_db_id): Path<Uuid>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    let user_id: Uuid = session
-        .get("user_id")
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-        .ok_or((StatusCode::UNAUTHORIZED, "Not logged in".to_string()))?;
-
+) -> Result<impl IntoResponse, ApiError> {
     // 1. Get the session_id string from the DB ID
-    let target_session = sqlx::query!(
-        "SELECT session_id FROM active_sessions WHERE id = $1 AND user_id = $2",
+    let target_session = sqlx::query_as!(
+        RevokedSession,
+        r#"
+        SELECT session_id, browser, os, ip_address, city, country
+        FROM active_sessions WHERE id = $1 AND user_id = $2
+        "#,
         session_db_id,
         user_id
     )
-    .fetch_optional(&pool)
+    .fetch_optional(&state.pool)
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-    .ok_or((StatusCode::NOT_FOUND, "Session not found".to_string()))?;
+    .ok_or(ApiError::new(
+        StatusCode::NOT_FOUND,
+        &i18n::SESSION_NOT_FOUND,
+    ))?;
 
     // 2. Remove from active_sessions table
     sqlx::query!("DELETE FROM active_sessions WHERE id = $1", session_db_id)
-        .execute(&pool)
+        .execute(&state.pool)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    sqlx::query!(
-        "DELETE FROM tower_sessions.session WHERE id = $1",
-        target_session.session_id
-    )
-    .execute(&pool)
-    .await
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    state
+        .sessions
+        .delete_ids(std::slice::from_ref(&target_session.session_id))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    let current_session_id = session.id().map(|id| id.to_string());
+    if current_session_id.as_ref() != Some(&target_session.session_id) {
+        if let Err(e) = email_revoked(&state, user_id, &[target_session]).await {
+            tracing::error!(
+                "Failed to email user {} about a revoked session: {}",
+                user_id,
+                e
+            );
+        }
+    }
 
     Ok(StatusCode::OK)
 }
 
-// Revoke all OTHER sessions
+// Revoke all OTHER sessions, and email the user which ones
 pub async fn revoke_all_other_sessions(
-    State(pool): State<PgPool>,
+    State(state): State<AppState>,
+    AuthUser { id: user_id, .. }: AuthUser,
     session: Session,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    let user_id: Uuid = session
-        .get("user_id")
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-        .ok_or((StatusCode::UNAUTHORIZED, "Not logged in".to_string()))?;
-
+) -> Result<impl IntoResponse, ApiError> {
     let current_session_id = session.id().map(|id| id.to_string()).ok_or((
         StatusCode::INTERNAL_SERVER_ERROR,
         "Current session ID unknown".to_string(),
     ))?;
 
-    // 1. Get all other session IDs
-    let other_sessions = sqlx::query!(
-        "SELECT session_id FROM active_sessions WHERE user_id = $1 AND session_id != $2",
+    // Deleted and returned in one go, so a session can't be signed out without being listed
+    let other_sessions = sqlx::query_as!(
+        RevokedSession,
+        r#"
+        DELETE FROM active_sessions WHERE user_id = $1 AND session_id != $2
+        RETURNING session_id, browser, os, ip_address, city, country
+        "#,
         user_id,
         current_session_id
     )
-    .fetch_all(&pool)
+    .fetch_all(&state.pool)
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    // 2. Delete from active_sessions
-    sqlx::query!(
-        "DELETE FROM active_sessions WHERE user_id = $1 AND session_id != $2",
-        user_id,
-        current_session_id
+    let other_ids: Vec<String> = other_sessions
+        .iter()
+        .map(|s| s.session_id.clone())
+        .collect();
+    if let Err(e) = state.sessions.delete_ids(&other_ids).await {
+        tracing::error!("Failed to delete sessions for user {}: {}", user_id, e);
+    }
+
+    if let Err(e) = email_revoked(&state, user_id, &other_sessions).await {
+        tracing::error!(
+            "Failed to email user {} about revoked sessions: {}",
+            user_id,
+            e
+        );
+    }
+
+    Ok(StatusCode::OK)
+}
+
+/// Every session a user has, most recently active first (admins only)
+pub async fn list_for_user(
+    State(pool): State<PgPool>,
+    _: AdminUser,
+    Path(user_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let sessions = sqlx::query_as!(
+        ActiveSession,
+        r#"
+        SELECT
+            id, user_id, session_id, user_agent, browser, os, device, ip_address, city, country,
+            last_active_at, expires_at, created_at,
+            NULL::bool as "is_current?"
+        FROM active_sessions
+        WHERE user_id = $1
+        ORDER BY last_active_at DESC
+        "#,
+        user_id
     )
-    .execute(&pool)
+    .fetch_all(&pool)
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    for s in other_sessions {
-        sqlx::query!(
-            "DELETE FROM tower_sessions.session WHERE id = $1",
-            s.session_id
-        )
-        .execute(&pool)
+    Ok(Json(sessions))
+}
+
+/// Sign a user out everywhere, e.g. when their account is compromised (admins only)
+pub async fn revoke_for_user(
+    State(state): State<AppState>,
+    AdminUser(admin): AdminUser,
+    session: Session,
+    Path(target_user_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    sqlx::query!("SELECT id FROM users WHERE id = $1", target_user_id)
+        .fetch_optional(&state.pool)
         .await
-        .ok(); // Ignore errors if already gone
-    }
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or(ApiError::new(StatusCode::NOT_FOUND, &i18n::USER_NOT_FOUND))?;
 
-    Ok(StatusCode::OK)
+    let revoked = revoke_user_sessions(&state.pool, &state.sessions, target_user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    crate::audit::record(
+        &state.pool,
+        &session,
+        admin.id,
+        "admin.sessions_revoked",
+        Some(target_user_id),
+        Some(&format!("{} sessions", revoked)),
+    )
+    .await?;
+
+    Ok(Json(serde_json::json!({ "revoked_sessions": revoked })))
 }
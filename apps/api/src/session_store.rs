@@ -0,0 +1,184 @@
+use async_trait::async_trait;
+use redis::{aio::ConnectionManager, AsyncCommands};
+use std::fmt;
+use std::str::FromStr;
+use tower_sessions::{
+    session::{Id, Record},
+    session_store, SessionStore,
+};
+use tower_sessions_sqlx_store::PostgresStore;
+
+/// Where tower-sessions keeps session data: Postgres by default,
+/// or Redis when SESSION_STORE=redis and REDIS_URL is set
+#[derive(Clone, Debug)]
+pub enum SessionBackend {
+    Postgres(PostgresStore),
+    Redis(RedisSessionStore),
+}
+
+impl SessionBackend {
+    pub fn from_env(pool: sqlx::PgPool, redis: Option<ConnectionManager>) -> Self {
+        let wants_redis = std::env::var("SESSION_STORE")
+            .map(|store| store.eq_ignore_ascii_case("redis"))
+            .unwrap_or(false);
+
+        match redis {
+            Some(conn) if wants_redis => {
+                tracing::info!("Storing sessions in Redis");
+                Self::Redis(RedisSessionStore::new(conn))
+            }
+            _ => {
+                if wants_redis {
+                    tracing::warn!("SESSION_STORE=redis but Redis isn't available, using Postgres");
+                }
+                Self::Postgres(PostgresStore::new(pool))
+            }
+        }
+    }
+
+    /// Delete sessions by the ids stored in active_sessions, logging their owners out
+    pub async fn delete_ids(&self, session_ids: &[String]) -> Result<(), String> {
+        for session_id in session_ids {
+            // Ids that don't parse were never valid sessions, so there's nothing to delete
+            let Ok(id) = Id::from_str(session_id) else {
+                continue;
+            };
+            self.delete(&id).await.map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SessionStore for SessionBackend {
+    async fn create(&self, record: &mut Record) -> session_store::Result<()> {
+        match self {
+            Self::Postgres(store) => store.create(record).await,
+            Self::Redis(store) => store.create(record).await,
+        }
+    }
+
+    async fn save(&self, record: &Record) -> session_store::Result<()> {
+        match self {
+            Self::Postgres(store) => store.save(record).await,
+            Self::Redis(store) => store.save(record).await,
+        }
+    }
+
+    async fn load(&self, session_id: &Id) -> session_store::Result<Option<Record>> {
+        match self {
+            Self::Postgres(store) => store.load(session_id).await,
+            Self::Redis(store) => store.load(session_id).await,
+        }
+    }
+
+    async fn delete(&self, session_id: &Id) -> session_store::Result<()> {
+        match self {
+            Self::Postgres(store) => store.delete(session_id).await,
+            Self::Redis(store) => store.delete(session_id).await,
+        }
+    }
+}
+
+/// Sessions as JSON under `session:<id>`, expiring with the session itself
+#[derive(Clone)]
+pub struct RedisSessionStore {
+    conn: ConnectionManager,
+}
+
+impl RedisSessionStore {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self { conn }
+    }
+
+    fn key(session_id: &str) -> String {
+        format!("session:{}", session_id)
+    }
+
+    /// The ids (as stored in active_sessions) whose session no longer exists
+    pub async fn missing(&self, session_ids: &[String]) -> Result<Vec<String>, String> {
+        if session_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut pipe = redis::pipe();
+        for session_id in session_ids {
+            pipe.exists(Self::key(session_id));
+        }
+        let exists: Vec<bool> = pipe
+            .query_async(&mut self.conn.clone())
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(session_ids
+            .iter()
+            .zip(exists)
+            .filter(|(_, exists)| !exists)
+            .map(|(session_id, _)| session_id.clone())
+            .collect())
+    }
+
+    /// SET the record with an absolute expiry; with `only_new` nothing is written if the id is taken
+    async fn write(&self, record: &Record, only_new: bool) -> session_store::Result<bool> {
+        let value = serde_json::to_string(record)
+            .map_err(|e| session_store::Error::Encode(e.to_string()))?;
+
+        let mut cmd = redis::cmd("SET");
+        cmd.arg(Self::key(&record.id.to_string())).arg(value);
+        if only_new {
+            cmd.arg("NX");
+        }
+        cmd.arg("EXAT").arg(record.expiry_date.unix_timestamp());
+
+        let reply: Option<String> = cmd
+            .query_async(&mut self.conn.clone())
+            .await
+            .map_err(|e| session_store::Error::Backend(e.to_string()))?;
+        Ok(reply.is_some())
+    }
+}
+
+impl fmt::Debug for RedisSessionStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RedisSessionStore").finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl SessionStore for RedisSessionStore {
+    async fn create(&self, record: &mut Record) -> session_store::Result<()> {
+        // Pick a new id on the (very unlikely) chance this one is taken
+        while !self.write(record, true).await? {
+            record.id = Id::default();
+        }
+        Ok(())
+    }
+
+    async fn save(&self, record: &Record) -> session_store::Result<()> {
+        self.write(record, false).await.map(|_| ())
+    }
+
+    async fn load(&self, session_id: &Id) -> session_store::Result<Option<Record>> {
+        let value: Option<String> = self
+            .conn
+            .clone()
+            .get(Self::key(&session_id.to_string()))
+            .await
+            .map_err(|e| session_store::Error::Backend(e.to_string()))?;
+
+        value
+            .map(|value| {
+                serde_json::from_str(&value)
+                    .map_err(|e| session_store::Error::Decode(e.to_string()))
+            })
+            .transpose()
+    }
+
+    async fn delete(&self, session_id: &Id) -> session_store::Result<()> {
+        self.conn
+            .clone()
+            .del::<_, ()>(Self::key(&session_id.to_string()))
+            .await
+            .map_err(|e| session_store::Error::Backend(e.to_string()))
+    }
+}
@@ -0,0 +1,293 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::PgPool;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tower_sessions::Session;
+
+use crate::error::ApiError;
+use crate::extractors::AdminUser;
+use crate::state::AppState;
+
+// Other instances pick up changes within this long
+const SETTINGS_CACHE_TTL: Duration = Duration::from_secs(30);
+// Updates write through to the shared cache, so this only bounds edits made outside the API
+const SHARED_SETTINGS_TTL: Duration = Duration::from_secs(10 * 60);
+const SHARED_SETTINGS_KEY: &str = "site_settings";
+
+// Bounds for max_post_length
+const MAX_POST_LENGTH_LIMIT: i64 = 100_000;
+// Longest automatic suspension, as for ones given by hand
+const MAX_STRIKE_SUSPENSION_HOURS: i64 = 365 * 24;
+
+/// Site-wide settings, stored one key per row in site_settings
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SiteSettings {
+    pub signups_enabled: bool,
+    /// Signups need an invite code (see /user/me/invites)
+    pub invite_only: bool,
+    /// Signups without an invite code or waitlist link join the waitlist instead
+    pub waitlist: bool,
+    pub max_post_length: i64,
+    /// Shown at the top of every page when set
+    pub maintenance_banner: Option<String>,
+    /// Accounts must verify their email before posting, creating projects or applying
+    pub require_verified_email: bool,
+    /// Reputation needed to put links in posts, 0 for none (see reputation.rs)
+    pub min_reputation_for_links: i64,
+    /// Posts one user can make in an hour, 0 for no limit (see quotas.rs)
+    pub max_posts_per_hour: i64,
+    /// Projects one user can create in a day, 0 for no limit
+    pub max_projects_per_day: i64,
+    /// Admins aren't held to the quotas above
+    pub quota_exempt_admins: bool,
+    /// Nor are users with a verified email
+    pub quota_exempt_verified: bool,
+    /// Hold posts, projects and applications that look like spam for review (see spam.rs)
+    pub spam_checks_enabled: bool,
+    /// Accounts this new (in hours) posting links to other sites are held, 0 for never
+    pub spam_new_account_hours: i64,
+    /// Links to these domains, or their subdomains, are always held
+    pub banned_domains: Vec<String>,
+    /// Strikes (upheld reports) at which a user is warned, suspended and banned; 0 skips
+    /// that step (see message_reports.rs)
+    pub strike_warning_threshold: i64,
+    pub strike_suspension_threshold: i64,
+    pub strike_ban_threshold: i64,
+    pub strike_suspension_hours: i64,
+}
+
+impl Default for SiteSettings {
+    fn default() -> Self {
+        Self {
+            signups_enabled: true,
+            invite_only: false,
+            waitlist: false,
+            max_post_length: 5000,
+            maintenance_banner: None,
+            require_verified_email: true,
+            min_reputation_for_links: 0,
+            max_posts_per_hour: 20,
+            max_projects_per_day: 5,
+            quota_exempt_admins: true,
+            quota_exempt_verified: false,
+            spam_checks_enabled: true,
+            spam_new_account_hours: 24,
+            banned_domains: Vec::new(),
+            strike_warning_threshold: 1,
+            strike_suspension_threshold: 2,
+            strike_ban_threshold: 3,
+            strike_suspension_hours: 72,
+        }
+    }
+}
+
+impl SiteSettings {
+    fn validate(&self) -> Result<(), String> {
+        if !(1..=MAX_POST_LENGTH_LIMIT).contains(&self.max_post_length) {
+            return Err(format!(
+                "max_post_length must be between 1 and {}",
+                MAX_POST_LENGTH_LIMIT
+            ));
+        }
+        if self.min_reputation_for_links < 0 {
+            return Err("min_reputation_for_links can't be negative".to_string());
+        }
+        if self.max_posts_per_hour < 0 || self.max_projects_per_day < 0 {
+            return Err("Quotas can't be negative".to_string());
+        }
+        if self.spam_new_account_hours < 0 {
+            return Err("spam_new_account_hours can't be negative".to_string());
+        }
+        if self
+            .banned_domains
+            .iter()
+            .any(|domain| domain.is_empty() || domain.contains(['/', ' ', ':']))
+        {
+            return Err("banned_domains must be bare domains, e.g. spam.example".to_string());
+        }
+        if self.strike_warning_threshold < 0
+            || self.strike_suspension_threshold < 0
+            || self.strike_ban_threshold < 0
+        {
+            return Err("Strike thresholds can't be negative".to_string());
+        }
+        if !(1..=MAX_STRIKE_SUSPENSION_HOURS).contains(&self.strike_suspension_hours) {
+            return Err(format!(
+                "strike_suspension_hours must be between 1 and {}",
+                MAX_STRIKE_SUSPENSION_HOURS
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The settings this instance last read, kept in AppState so most requests skip the
+/// database and the shared cache
+#[derive(Clone, Default)]
+pub struct SettingsCache(Arc<RwLock<Option<(Instant, SiteSettings)>>>);
+
+impl SettingsCache {
+    fn fresh(&self) -> Option<SiteSettings> {
+        match self.0.read().unwrap().as_ref() {
+            Some((loaded_at, settings)) if loaded_at.elapsed() < SETTINGS_CACHE_TTL => {
+                Some(settings.clone())
+            }
+            _ => None,
+        }
+    }
+
+    fn last_known(&self) -> SiteSettings {
+        self.0
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|(_, settings)| settings.clone())
+            .unwrap_or_default()
+    }
+
+    fn store(&self, settings: &SiteSettings) {
+        *self.0.write().unwrap() = Some((Instant::now(), settings.clone()));
+    }
+}
+
+async fn load(pool: &PgPool) -> Result<SiteSettings, String> {
+    let rows = sqlx::query!("SELECT key, value FROM site_settings")
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // Overlay stored keys on the defaults so new settings work before anyone saves them
+    let mut merged = match serde_json::to_value(SiteSettings::default()) {
+        Ok(Value::Object(map)) => map,
+        _ => Map::new(),
+    };
+    for row in rows {
+        merged.insert(row.key, row.value);
+    }
+
+    serde_json::from_value(Value::Object(merged)).map_err(|e| e.to_string())
+}
+
+/// Current settings, from the in-process cache when fresh, then the shared cache.
+/// Falls back to the last known (or default) settings if the database can't be read.
+pub async fn get(state: &AppState) -> SiteSettings {
+    if let Some(settings) = state.settings.fresh() {
+        return settings;
+    }
+
+    let loaded = match state.cache.get_json(SHARED_SETTINGS_KEY).await {
+        Some(settings) => Ok(settings),
+        None => {
+            let loaded = load(&state.pool).await;
+            if let Ok(settings) = &loaded {
+                state
+                    .cache
+                    .set_json(SHARED_SETTINGS_KEY, settings, SHARED_SETTINGS_TTL)
+                    .await;
+            }
+            loaded
+        }
+    };
+
+    match loaded {
+        Ok(settings) => {
+            state.settings.store(&settings);
+            settings
+        }
+        Err(e) => {
+            tracing::error!("Failed to load site settings: {}", e);
+            state.settings.last_known()
+        }
+    }
+}
+
+/// Public subset for the frontend (signup form, composer limits, banner)
+pub async fn get_public(State(state): State<AppState>) -> impl IntoResponse {
+    Json(get(&state).await)
+}
+
+pub async fn get_admin(
+    State(pool): State<PgPool>,
+    _: AdminUser,
+) -> Result<impl IntoResponse, ApiError> {
+    let settings = load(&pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    Ok(Json(settings))
+}
+
+/// Update some settings; the body is a partial object, e.g. {"signups_enabled": false}
+pub async fn update(
+    State(state): State<AppState>,
+    AdminUser(admin): AdminUser,
+    session: Session,
+    Json(changes): Json<Map<String, Value>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let pool = &state.pool;
+    let current = load(pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    let mut merged = match serde_json::to_value(&current) {
+        Ok(Value::Object(map)) => map,
+        _ => Map::new(),
+    };
+
+    for (key, value) in &changes {
+        if !merged.contains_key(key) {
+            return Err((StatusCode::BAD_REQUEST, format!("Unknown setting: {}", key)).into());
+        }
+        merged.insert(key.clone(), value.clone());
+    }
+
+    // Round-trip through the struct so every value is type checked
+    let updated: SiteSettings = serde_json::from_value(Value::Object(merged))
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid setting: {}", e)))?;
+    updated
+        .validate()
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    for (key, value) in &changes {
+        sqlx::query!(
+            r#"
+            INSERT INTO site_settings (key, value, updated_by, updated_at)
+            VALUES ($1, $2, $3, NOW())
+            ON CONFLICT (key) DO UPDATE SET value = $2, updated_by = $3, updated_at = NOW()
+            "#,
+            key,
+            value,
+            admin.id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    }
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    state.settings.store(&updated);
+    state
+        .cache
+        .set_json(SHARED_SETTINGS_KEY, &updated, SHARED_SETTINGS_TTL)
+        .await;
+
+    let details = Value::Object(changes).to_string();
+    crate::audit::record(
+        pool,
+        &session,
+        admin.id,
+        "admin.settings_updated",
+        None,
+        Some(&details),
+    )
+    .await?;
+
+    Ok(Json(updated))
+}
@@ -0,0 +1,338 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use tower_sessions::Session;
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::extractors::ModeratorUser;
+use crate::i18n;
+use crate::mentions::MentionSource;
+use crate::settings::SiteSettings;
+use crate::state::AppState;
+
+// This many links is too many for anything
+const MAX_LINKS: usize = 4;
+// Identical content from the same author within this many hours is a duplicate
+const DUPLICATE_WINDOW_HOURS: i32 = 24;
+const KINDS: &[&str] = &["post", "project", "application"];
+
+/// What's being checked. Duplicates are looked for among the author's own of the same kind.
+#[derive(Clone, Copy)]
+pub enum Content {
+    Post,
+    Project,
+    Application,
+}
+
+/// Held content as a moderator sees it, oldest first
+#[derive(Serialize)]
+pub struct HeldItem {
+    /// post, project or application
+    pub kind: String,
+    pub id: Uuid,
+    pub author_id: Uuid,
+    pub author_username: String,
+    /// Post content, project title and description, or application message
+    pub text: String,
+    pub reasons: Vec<String>,
+    pub held_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct ReviewHeldRequest {
+    /// true publishes it, false deletes it
+    pub approve: bool,
+}
+
+/// The links in some text: words with a scheme, or starting with www.
+fn links(text: &str) -> Vec<&str> {
+    text.split_whitespace()
+        .map(|word| {
+            word.trim_start_matches(|c: char| !c.is_alphanumeric())
+                .trim_end_matches(['.', ',', ';', ':', '!', '?', ')', ']', '\'', '"'])
+        })
+        .filter(|word| word.contains("://") || word.to_lowercase().starts_with("www."))
+        .collect()
+}
+
+fn host(link: &str) -> Option<String> {
+    let url = if link.contains("://") {
+        Url::parse(link)
+    } else {
+        Url::parse(&format!("http://{}", link))
+    };
+    url.ok()?.host_str().map(str::to_lowercase)
+}
+
+/// Why new content looks like spam, or None if it doesn't. `extra_links` are any it has
+/// besides those in `text` (e.g. an application's portfolio), checked against banned domains
+/// only. Moderators and admins aren't checked.
+pub async fn check(
+    state: &AppState,
+    settings: &SiteSettings,
+    author_id: Uuid,
+    role: &str,
+    content: Content,
+    text: &str,
+    extra_links: &[String],
+) -> Result<Option<Vec<String>>, ApiError> {
+    if !settings.spam_checks_enabled || crate::admin::can_moderate(role) {
+        return Ok(None);
+    }
+    let internal = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    let mut reasons = Vec::new();
+
+    let in_text = links(text);
+    let hosts: Vec<String> = in_text
+        .iter()
+        .copied()
+        .chain(extra_links.iter().map(String::as_str))
+        .filter_map(host)
+        .collect();
+    let banned = |host: &str| {
+        settings.banned_domains.iter().any(|domain| {
+            let domain = domain.to_lowercase();
+            host == domain || host.ends_with(&format!(".{}", domain))
+        })
+    };
+    if hosts.iter().any(|host| banned(host)) {
+        reasons.push("banned_domain".to_string());
+    }
+
+    // Mostly links, or just a lot of them
+    let link_chars: usize = in_text.iter().map(|link| link.len()).sum();
+    let text_chars = text.split_whitespace().map(str::len).sum::<usize>();
+    if in_text.len() >= MAX_LINKS || (in_text.len() >= 2 && link_chars * 2 > text_chars) {
+        reasons.push("link_density".to_string());
+    }
+
+    let own_host = Url::parse(&state.config.frontend_url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_lowercase));
+    let external = in_text
+        .iter()
+        .filter_map(|link| host(link))
+        .any(|host| Some(&host) != own_host.as_ref());
+    if external && settings.spam_new_account_hours > 0 {
+        let new_account = sqlx::query_scalar!(
+            r#"
+            SELECT COALESCE(created_at > NOW() - make_interval(hours => $2::int), FALSE) AS "new!"
+            FROM users WHERE id = $1
+            "#,
+            author_id,
+            settings.spam_new_account_hours as i32
+        )
+        .fetch_one(&state.pool)
+        .await
+        .map_err(internal)?;
+        if new_account {
+            reasons.push("new_account_links".to_string());
+        }
+    }
+
+    // Case and spacing don't make it different
+    let normalized = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let duplicate = match content {
+        Content::Post => {
+            sqlx::query_scalar!(
+                r#"
+                SELECT EXISTS(
+                    SELECT 1 FROM posts
+                    WHERE author_id = $1 AND created_at > NOW() - make_interval(hours => $3)
+                      AND lower(regexp_replace(btrim(content), '\s+', ' ', 'g')) = lower($2)
+                ) AS "exists!"
+                "#,
+                author_id,
+                normalized,
+                DUPLICATE_WINDOW_HOURS
+            )
+            .fetch_one(&state.pool)
+            .await
+        }
+        Content::Project => {
+            sqlx::query_scalar!(
+                r#"
+                SELECT EXISTS(
+                    SELECT 1 FROM projects
+                    WHERE owner_id = $1 AND created_at > NOW() - make_interval(hours => $3)
+                      AND lower(regexp_replace(btrim(title || ' ' || COALESCE(description, '')),
+                                               '\s+', ' ', 'g')) = lower($2)
+                ) AS "exists!"
+                "#,
+                author_id,
+                normalized,
+                DUPLICATE_WINDOW_HOURS
+            )
+            .fetch_one(&state.pool)
+            .await
+        }
+        Content::Application => {
+            sqlx::query_scalar!(
+                r#"
+                SELECT EXISTS(
+                    SELECT 1 FROM applications
+                    WHERE applicant_id = $1 AND created_at > NOW() - make_interval(hours => $3)
+                      AND lower(regexp_replace(btrim(message), '\s+', ' ', 'g')) = lower($2)
+                ) AS "exists!"
+                "#,
+                author_id,
+                normalized,
+                DUPLICATE_WINDOW_HOURS
+            )
+            .fetch_one(&state.pool)
+            .await
+        }
+    }
+    .map_err(internal)?;
+    if duplicate {
+        reasons.push("duplicate".to_string());
+    }
+
+    Ok(Some(reasons).filter(|reasons| !reasons.is_empty()))
+}
+
+/// Everything held for review, oldest first
+pub async fn list_held(
+    State(state): State<AppState>,
+    _: ModeratorUser,
+) -> Result<impl IntoResponse, ApiError> {
+    let items = sqlx::query_as!(
+        HeldItem,
+        r#"
+        SELECT 'post' AS "kind!", p.id AS "id!", p.author_id AS "author_id!",
+               u.username AS "author_username!", p.content AS "text!",
+               p.held_reasons AS "reasons!", p.held_at AS "held_at!"
+        FROM posts p JOIN users u ON u.id = p.author_id
+        WHERE p.held_at IS NOT NULL
+        UNION ALL
+        SELECT 'project', p.id, p.owner_id, u.username,
+               p.title || COALESCE(E'\n\n' || p.description, ''), p.held_reasons, p.held_at
+        FROM projects p JOIN users u ON u.id = p.owner_id
+        WHERE p.held_at IS NOT NULL
+        UNION ALL
+        SELECT 'application', a.id, a.applicant_id, u.username, a.message, a.held_reasons,
+               a.held_at
+        FROM applications a JOIN users u ON u.id = a.applicant_id
+        WHERE a.held_at IS NOT NULL
+        ORDER BY 7
+        "#
+    )
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(items))
+}
+
+/// Approve held content, publishing it as if just posted (mentions and application
+/// notifications go out now), or reject it, deleting it
+pub async fn review_held(
+    State(state): State<AppState>,
+    ModeratorUser(moderator): ModeratorUser,
+    session: Session,
+    Path((kind, id)): Path<(String, Uuid)>,
+    Json(payload): Json<ReviewHeldRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    if !KINDS.contains(&kind.as_str()) {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            &i18n::HELD_ITEM_NOT_FOUND,
+        ));
+    }
+    let internal = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+
+    // Released from the hold either way; rejected content is then deleted
+    let released = sqlx::query!(
+        r#"
+        WITH post AS (
+            UPDATE posts SET held_at = NULL, held_reasons = NULL
+            WHERE $1 = 'post' AND id = $2 AND held_at IS NOT NULL
+            RETURNING author_id, content AS text, image_url
+        ), project AS (
+            UPDATE projects SET held_at = NULL, held_reasons = NULL
+            WHERE $1 = 'project' AND id = $2 AND held_at IS NOT NULL
+            RETURNING owner_id AS author_id, title AS text, image_url
+        ), application AS (
+            UPDATE applications SET held_at = NULL, held_reasons = NULL
+            WHERE $1 = 'application' AND id = $2 AND held_at IS NOT NULL
+            RETURNING applicant_id AS author_id, message AS text, NULL::text AS image_url
+        )
+        SELECT author_id AS "author_id!", text AS "text!", image_url,
+               (SELECT username FROM users WHERE id = author_id) AS "author_username!"
+        FROM (
+            SELECT * FROM post UNION ALL SELECT * FROM project UNION ALL SELECT * FROM application
+        ) released
+        "#,
+        kind,
+        id
+    )
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(internal)?
+    .ok_or(ApiError::new(
+        StatusCode::NOT_FOUND,
+        &i18n::HELD_ITEM_NOT_FOUND,
+    ))?;
+
+    if payload.approve {
+        match kind.as_str() {
+            "post" => {
+                state.search_index.sync_post(&state.pool, id).await;
+                crate::mentions::notify(
+                    &state,
+                    MentionSource {
+                        content_type: "post",
+                        content_id: id,
+                        author_id: released.author_id,
+                        link: format!("/{}#post-{}", released.author_username, id),
+                        text: &released.text,
+                    },
+                )
+                .await;
+            }
+            "project" => state.search_index.sync_project(&state.pool, id).await,
+            _ => crate::applications::notify_reviewer(&state, id).await,
+        }
+    } else {
+        match kind.as_str() {
+            "post" => sqlx::query!("DELETE FROM posts WHERE id = $1", id),
+            "project" => sqlx::query!("DELETE FROM projects WHERE id = $1", id),
+            _ => sqlx::query!("DELETE FROM applications WHERE id = $1", id),
+        }
+        .execute(&state.pool)
+        .await
+        .map_err(internal)?;
+
+        // Best effort: anything left behind is picked up by the orphan cleanup
+        if let Some(image_url) = released.image_url.as_deref() {
+            if let Err(e) = crate::upload::release_uploads(&state, released.author_id, &[image_url]).await {
+                tracing::error!("Failed to delete held {} image: {}", kind, e);
+            }
+        }
+    }
+    crate::feed::invalidate(&*state.cache).await;
+
+    let details = format!("{} {}", kind, id);
+    crate::audit::record(
+        &state.pool,
+        &session,
+        moderator.id,
+        if payload.approve {
+            "moderation.held_approved"
+        } else {
+            "moderation.held_rejected"
+        },
+        Some(released.author_id),
+        Some(&details),
+    )
+    .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
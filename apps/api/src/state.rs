@@ -0,0 +1,188 @@
+use axum::extract::FromRef;
+use sqlx::PgPool;
+use std::ops::Deref;
+use std::sync::Arc;
+
+use crate::cache::{Cache, NoCache, RedisCache};
+use crate::client_ip::TrustedProxies;
+use crate::db::DbRouter;
+use crate::email::EmailSender;
+use crate::geoip::GeoIp;
+use crate::r2::{ObjectStore, R2Client};
+use crate::ws::Realtime;
+use crate::search_index::Indexer;
+use crate::session_store::SessionBackend;
+use crate::settings::SettingsCache;
+
+/// Settings read from the environment once at startup
+pub struct Config {
+    /// Where links in emails and OAuth redirects point (first entry of FRONTEND_URL)
+    pub frontend_url: String,
+    /// Every origin allowed by CORS (FRONTEND_URL is comma separated)
+    pub frontend_origins: Vec<String>,
+    pub is_production: bool,
+    /// Signs unsubscribe links (EMAIL_SIGNING_SECRET)
+    pub email_signing_secret: Vec<u8>,
+    /// Whose X-Forwarded-For to believe (TRUSTED_PROXIES)
+    pub trusted_proxies: TrustedProxies,
+}
+
+impl Config {
+    pub fn from_env() -> Self {
+        let frontend_urls =
+            std::env::var("FRONTEND_URL").unwrap_or_else(|_| "http://localhost:3000".to_string());
+        let frontend_origins: Vec<String> = frontend_urls
+            .split(',')
+            .map(|url| url.trim().to_string())
+            .filter(|url| !url.is_empty())
+            .collect();
+        let frontend_url = frontend_origins
+            .first()
+            .cloned()
+            .unwrap_or_else(|| "http://localhost:3000".to_string());
+
+        Self {
+            frontend_url,
+            frontend_origins,
+            is_production: std::env::var("RAILWAY_ENVIRONMENT").is_ok()
+                || std::env::var("RAILWAY_PUBLIC_DOMAIN").is_ok(),
+            email_signing_secret: email_signing_secret(),
+            trusted_proxies: TrustedProxies::from_env(),
+        }
+    }
+}
+
+// Without a configured secret, unsubscribe links only work until the next restart
+fn email_signing_secret() -> Vec<u8> {
+    match std::env::var("EMAIL_SIGNING_SECRET") {
+        Ok(secret) if !secret.is_empty() => secret.into_bytes(),
+        _ => {
+            tracing::warn!("EMAIL_SIGNING_SECRET not set, using a random one");
+            rand::random::<[u8; 32]>().to_vec()
+        }
+    }
+}
+
+pub struct AppStateInner {
+    pub pool: PgPool,
+    /// The DATABASE_READ_URL replica, when there is one; handlers reach it through DbRouter
+    pub replica: Option<PgPool>,
+    pub config: Config,
+    /// None when the R2_* env vars are missing (uploads are disabled)
+    pub r2_client: Option<Arc<dyn ObjectStore>>,
+    /// Shared so outgoing requests reuse connections
+    pub http_client: reqwest::Client,
+    pub email_sender: Arc<dyn EmailSender>,
+    /// Redis when REDIS_URL is set, otherwise a no-op
+    pub cache: Arc<dyn Cache>,
+    pub sessions: SessionBackend,
+    /// Open WebSocket connections (GET /ws) on this instance
+    pub realtime: Realtime,
+    /// Meilisearch when MEILISEARCH_URL is set; search falls back to Postgres without it
+    pub search_index: Indexer,
+    /// The MaxMind database at GEOIP_DATABASE, or ip-api.com without it
+    pub geoip: GeoIp,
+    /// Site settings as this instance last read them (see settings::get)
+    pub settings: SettingsCache,
+}
+
+/// Everything handlers need, built once in main and cheap to clone
+#[derive(Clone)]
+pub struct AppState(Arc<AppStateInner>);
+
+impl AppState {
+    pub async fn new(pool: PgPool, replica: Option<PgPool>, config: Config) -> Self {
+        let http_client = reqwest::Client::new();
+        let email_sender = crate::email::sender_from_env(http_client.clone(), config.is_production);
+        let r2_client = R2Client::from_env().map(|r2| Arc::new(r2) as Arc<dyn ObjectStore>);
+        let search_index = Indexer::from_env(http_client.clone());
+        let geoip = GeoIp::from_env(http_client.clone());
+
+        let redis = crate::cache::connect_from_env().await;
+        let sessions = SessionBackend::from_env(pool.clone(), redis.clone());
+        let cache: Arc<dyn Cache> = match redis {
+            Some(conn) => Arc::new(RedisCache::new(conn)),
+            None => Arc::new(NoCache),
+        };
+
+        AppStateInner {
+            pool,
+            replica,
+            config,
+            r2_client,
+            http_client,
+            email_sender,
+            cache,
+            sessions,
+            realtime: Realtime::default(),
+            search_index,
+            geoip,
+            settings: SettingsCache::default(),
+        }
+        .into()
+    }
+
+    pub fn r2(&self) -> Result<&dyn ObjectStore, String> {
+        self.r2_client
+            .as_deref()
+            .ok_or_else(|| "R2 not configured".to_string())
+    }
+}
+
+// Lets the integration tests swap in fake storage and email
+impl From<AppStateInner> for AppState {
+    fn from(inner: AppStateInner) -> Self {
+        Self(Arc::new(inner))
+    }
+}
+
+impl Deref for AppState {
+    type Target = AppStateInner;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+// Lets handlers that only touch the database keep taking State<PgPool>
+impl FromRef<AppState> for PgPool {
+    fn from_ref(state: &AppState) -> Self {
+        state.pool.clone()
+    }
+}
+
+impl FromRef<AppState> for DbRouter {
+    fn from_ref(state: &AppState) -> Self {
+        DbRouter::new(state.pool.clone(), state.replica.clone())
+    }
+}
+
+impl FromRef<AppState> for Arc<dyn Cache> {
+    fn from_ref(state: &AppState) -> Self {
+        state.cache.clone()
+    }
+}
+
+impl FromRef<AppState> for Realtime {
+    fn from_ref(state: &AppState) -> Self {
+        state.realtime.clone()
+    }
+}
+
+impl FromRef<AppState> for Indexer {
+    fn from_ref(state: &AppState) -> Self {
+        state.search_index.clone()
+    }
+}
+
+impl FromRef<AppState> for GeoIp {
+    fn from_ref(state: &AppState) -> Self {
+        state.geoip.clone()
+    }
+}
+
+impl FromRef<AppState> for SessionBackend {
+    fn from_ref(state: &AppState) -> Self {
+        state.sessions.clone()
+    }
+}
@@ -0,0 +1,136 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+use crate::error::ApiError;
+use crate::extractors::AdminUser;
+
+// How many days /admin/stats returns by default, and at most
+const DEFAULT_STATS_DAYS: i64 = 30;
+const MAX_STATS_DAYS: i64 = 365;
+
+#[derive(Deserialize)]
+pub struct StatsQuery {
+    pub days: Option<i64>,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct DailyStats {
+    pub day: chrono::NaiveDate,
+    pub signups: i32,
+    pub posts_created: i32,
+    pub projects_created: i32,
+    pub daily_active_users: i32,
+    pub weekly_active_users: i32,
+    pub storage_bytes: i64,
+}
+
+#[derive(Serialize)]
+pub struct AdminStats {
+    pub total_users: i64,
+    pub active_sessions: i64,
+    pub daily_active_users: i64,
+    pub weekly_active_users: i64,
+    pub storage_bytes: i64,
+    /// Oldest first
+    pub days: Vec<DailyStats>,
+}
+
+/// Recompute the daily_stats row for a given day (today's row is partial until the day ends)
+pub async fn refresh_day(pool: &PgPool, day: chrono::NaiveDate) -> Result<(), sqlx::Error> {
+    // Activity only keeps each session's latest timestamp, so a day's counts can drop once
+    // its users come back the next day; GREATEST keeps the highest value we observed
+    sqlx::query!(
+        r#"
+        INSERT INTO daily_stats (day, signups, posts_created, projects_created,
+                                 daily_active_users, weekly_active_users, storage_bytes, computed_at)
+        SELECT
+            $1::date,
+            (SELECT COUNT(*) FROM users WHERE created_at >= $1::date AND created_at < $1::date + 1),
+            (SELECT COUNT(*) FROM posts WHERE created_at >= $1::date AND created_at < $1::date + 1),
+            (SELECT COUNT(*) FROM projects WHERE created_at >= $1::date AND created_at < $1::date + 1),
+            (SELECT COUNT(DISTINCT user_id) FROM active_sessions
+              WHERE last_active_at >= $1::date AND last_active_at < $1::date + 1),
+            (SELECT COUNT(DISTINCT user_id) FROM active_sessions
+              WHERE last_active_at >= $1::date - 6 AND last_active_at < $1::date + 1),
+            (SELECT COALESCE(SUM(size_bytes), 0) FROM uploads),
+            NOW()
+        ON CONFLICT (day) DO UPDATE SET
+            signups = EXCLUDED.signups,
+            posts_created = EXCLUDED.posts_created,
+            projects_created = EXCLUDED.projects_created,
+            daily_active_users = GREATEST(daily_stats.daily_active_users, EXCLUDED.daily_active_users),
+            weekly_active_users = GREATEST(daily_stats.weekly_active_users, EXCLUDED.weekly_active_users),
+            storage_bytes = EXCLUDED.storage_bytes,
+            computed_at = NOW()
+        "#,
+        day
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Keep today's and yesterday's stats rows up to date (yesterday's may still be partial)
+pub async fn refresh_recent(pool: &PgPool) -> Result<(), sqlx::Error> {
+    let today = chrono::Utc::now().date_naive();
+    for day in [today - chrono::Duration::days(1), today] {
+        refresh_day(pool, day).await?;
+    }
+    Ok(())
+}
+
+/// Site metrics for the admin dashboard
+pub async fn get_stats(
+    State(pool): State<PgPool>,
+    _: AdminUser,
+    Query(query): Query<StatsQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let days = query
+        .days
+        .unwrap_or(DEFAULT_STATS_DAYS)
+        .clamp(1, MAX_STATS_DAYS);
+
+    let mut series = sqlx::query_as::<_, DailyStats>(
+        r#"
+        SELECT day, signups, posts_created, projects_created,
+               daily_active_users, weekly_active_users, storage_bytes
+        FROM daily_stats
+        WHERE day > CURRENT_DATE - $1::int
+        ORDER BY day DESC
+        "#,
+    )
+    .bind(days as i32)
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    series.reverse();
+
+    // Cheap live counters; everything else comes from the aggregated rows
+    let total_users: i64 = sqlx::query_scalar("SELECT COUNT(*)::bigint FROM users")
+        .fetch_one(&pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let active_sessions: i64 =
+        sqlx::query_scalar("SELECT COUNT(*)::bigint FROM active_sessions WHERE expires_at > NOW()")
+            .fetch_one(&pool)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let latest = series.last();
+    Ok(Json(AdminStats {
+        total_users,
+        active_sessions,
+        daily_active_users: latest.map_or(0, |d| d.daily_active_users as i64),
+        weekly_active_users: latest.map_or(0, |d| d.weekly_active_users as i64),
+        storage_bytes: latest.map_or(0, |d| d.storage_bytes),
+        days: series,
+    }))
+}
@@ -0,0 +1,208 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tower_sessions::Session;
+use uuid::Uuid;
+
+use crate::error::ApiError;
+use crate::extractors::AuthUser;
+use crate::i18n;
+use crate::notifications::NotificationKind;
+use crate::session_store::SessionBackend;
+use crate::state::AppState;
+use crate::validation::{Validate, ValidatedJson, ValidationErrors};
+
+const MAX_APPEAL_LENGTH: usize = 1000;
+
+/// An upheld report against the user, as they see it. Who reported it, and why in their
+/// own words, stays with the admins.
+#[derive(Serialize)]
+pub struct Strike {
+    pub report_id: Uuid,
+    pub category: String,
+    /// What was reported
+    pub content: String,
+    /// "actioned", "appealed", or "dismissed" once overturned on appeal
+    pub status: String,
+    pub note: Option<String>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+    pub appeal: Option<String>,
+    pub appealed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct Strikes {
+    pub strikes: i32,
+    /// Newest first
+    pub reports: Vec<Strike>,
+}
+
+#[derive(Deserialize)]
+pub struct AppealRequest {
+    pub appeal: String,
+}
+
+impl Validate for AppealRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.length("appeal", self.appeal.trim(), 1, MAX_APPEAL_LENGTH);
+        errors.into_result()
+    }
+}
+
+/// Warn, suspend or ban a user who just reached `strikes`, by the thresholds in the site
+/// settings, and let them know. Only the strongest step reached is taken, and admins are
+/// never restricted.
+pub(crate) async fn escalate(
+    state: &AppState,
+    sessions: &SessionBackend,
+    session: &Session,
+    admin_id: Uuid,
+    user_id: Uuid,
+    report_id: Uuid,
+    strikes: i32,
+) -> Result<(), ApiError> {
+    let settings = crate::settings::get(state).await;
+    let reached = |threshold: i64| threshold > 0 && i64::from(strikes) >= threshold;
+
+    let role = sqlx::query_scalar!("SELECT role FROM users WHERE id = $1", user_id)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let reason = format!("{} strikes, the last for report {}", strikes, report_id);
+    let mut suspended_until = None;
+    let action = if role == "admin" {
+        None
+    } else if reached(settings.strike_ban_threshold) {
+        crate::admin::apply_ban(state, sessions, user_id, admin_id, &reason).await?;
+        Some(("ban", "moderation.user_banned"))
+    } else if reached(settings.strike_suspension_threshold) {
+        let until = Utc::now() + Duration::hours(settings.strike_suspension_hours);
+        crate::admin::apply_suspension(&state.pool, sessions, user_id, admin_id, &reason, until)
+            .await?;
+        suspended_until = Some(until);
+        Some(("suspend", "moderation.user_suspended"))
+    } else if reached(settings.strike_warning_threshold) {
+        crate::admin::insert_moderation_action(
+            &state.pool,
+            user_id,
+            admin_id,
+            "warn",
+            &reason,
+            None,
+        )
+        .await?;
+        Some(("warn", "moderation.user_warned"))
+    } else {
+        None
+    };
+
+    if let Some((_, audit_action)) = action {
+        crate::audit::record(
+            &state.pool,
+            session,
+            admin_id,
+            audit_action,
+            Some(user_id),
+            Some(&reason),
+        )
+        .await?;
+    }
+
+    crate::notifications::notify_without_actor(
+        state,
+        user_id,
+        NotificationKind::Strike,
+        serde_json::json!({
+            "report_id": report_id,
+            "strikes": strikes,
+            "action": action.map(|(action, _)| action),
+            "suspended_until": suspended_until,
+        }),
+    )
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e).into())
+}
+
+/// The user's strike count and the upheld reports behind it, including appeals either way
+pub async fn list_mine(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+) -> Result<impl IntoResponse, ApiError> {
+    let strikes = sqlx::query_scalar!("SELECT strikes FROM users WHERE id = $1", user.id)
+        .fetch_one(&pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let reports = sqlx::query_as!(
+        Strike,
+        r#"
+        SELECT id AS report_id, category, content, status, note, reviewed_at, appeal, appealed_at,
+               created_at
+        FROM message_reports
+        WHERE sender_id = $1
+          AND (status IN ('actioned', 'appealed') OR appealed_at IS NOT NULL)
+        ORDER BY created_at DESC
+        "#,
+        user.id
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(Strikes { strikes, reports }))
+}
+
+/// Appeal an upheld report, reopening it for admin review. Each report can be appealed once.
+pub async fn appeal(
+    State(pool): State<PgPool>,
+    user: AuthUser,
+    Path(report_id): Path<Uuid>,
+    ValidatedJson(payload): ValidatedJson<AppealRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let appealed = sqlx::query!(
+        r#"
+        UPDATE message_reports SET status = 'appealed', appeal = $3, appealed_at = NOW()
+        WHERE id = $1 AND sender_id = $2 AND status = 'actioned' AND appealed_at IS NULL
+        "#,
+        report_id,
+        user.id,
+        payload.appeal.trim()
+    )
+    .execute(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    if appealed.rows_affected() == 0 {
+        // The same reports as the user's list; the rest they were never told about
+        let exists = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM message_reports
+                WHERE id = $1 AND sender_id = $2
+                  AND (status IN ('actioned', 'appealed') OR appealed_at IS NOT NULL)
+            ) AS "exists!"
+            "#,
+            report_id,
+            user.id
+        )
+        .fetch_one(&pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        return Err(if exists {
+            ApiError::new(StatusCode::CONFLICT, &i18n::ALREADY_APPEALED)
+        } else {
+            ApiError::new(StatusCode::NOT_FOUND, &i18n::REPORT_NOT_FOUND)
+        });
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -3,18 +3,24 @@ use argon2::{
     Argon2,
 };
 use axum::{
-    extract::{ConnectInfo, State},
+    extract::State,
     http::{HeaderMap, StatusCode},
     response::IntoResponse,
     Json,
 };
-use std::net::SocketAddr;
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 use totp_rs::{Algorithm, Secret, TOTP};
 use tower_sessions::Session;
 use uuid::Uuid;
 
+use crate::client_ip::ClientIp;
+use crate::error::ApiError;
+use crate::extractors::AuthUser;
+use crate::geoip::GeoIp;
+use crate::i18n;
+use crate::validation::{Validate, ValidatedJson, ValidationErrors};
+
 const TOTP_ISSUER: &str = "Praxis";
 
 // Response types
@@ -44,17 +50,36 @@ pub struct DisableTotpRequest {
     pub code: String, // Require current TOTP code to disable
 }
 
+// TOTP codes are 6 digits; backup codes are a little longer
+fn validate_code(code: &str) -> Result<(), ValidationErrors> {
+    let mut errors = ValidationErrors::default();
+    errors.length("code", code, 1, 32);
+    errors.into_result()
+}
+
+impl Validate for EnableTotpRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        validate_code(&self.code)
+    }
+}
+
+impl Validate for VerifyTotpRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        validate_code(&self.code)
+    }
+}
+
+impl Validate for DisableTotpRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        validate_code(&self.code)
+    }
+}
+
 // Setup TOTP - generates secret and returns QR code URL
 pub async fn setup_totp(
     State(pool): State<PgPool>,
-    session: Session,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    let user_id: Uuid = session
-        .get("user_id")
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-        .ok_or((StatusCode::UNAUTHORIZED, "Not logged in".to_string()))?;
-
+    AuthUser { id: user_id, .. }: AuthUser,
+) -> Result<impl IntoResponse, ApiError> {
     // Get user email for TOTP label
     let user = sqlx::query!(
         r#"SELECT u.username, la.email as "email?" FROM users u LEFT JOIN local_auths la ON u.id = la.user_id WHERE u.id = $1"#,
@@ -110,15 +135,9 @@ pub async fn setup_totp(
 // Enable TOTP - verifies code and enables 2FA
 pub async fn enable_totp(
     State(pool): State<PgPool>,
-    session: Session,
-    Json(payload): Json<EnableTotpRequest>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    let user_id: Uuid = session
-        .get("user_id")
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-        .ok_or((StatusCode::UNAUTHORIZED, "Not logged in".to_string()))?;
-
+    AuthUser { id: user_id, .. }: AuthUser,
+    ValidatedJson(payload): ValidatedJson<EnableTotpRequest>,
+) -> Result<impl IntoResponse, ApiError> {
     // Get the stored secret
     let totp_record = sqlx::query!(
         "SELECT secret FROM totp_secrets WHERE user_id = $1",
@@ -127,7 +146,10 @@ pub async fn enable_totp(
     .fetch_optional(&pool)
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-    .ok_or((StatusCode::BAD_REQUEST, "TOTP not set up".to_string()))?;
+    .ok_or(ApiError::new(
+        StatusCode::BAD_REQUEST,
+        &i18n::TOTP_NOT_SET_UP,
+    ))?;
 
     // Verify the code
     let secret = Secret::Encoded(totp_record.secret)
@@ -141,7 +163,7 @@ pub async fn enable_totp(
         .check_current(&payload.code)
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
     {
-        return Err((StatusCode::BAD_REQUEST, "Invalid code".to_string()));
+        return Err(ApiError::new(StatusCode::BAD_REQUEST, &i18n::INVALID_CODE));
     }
 
     // Enable TOTP
@@ -165,18 +187,12 @@ pub async fn enable_totp(
 // Disable TOTP
 pub async fn disable_totp(
     State(pool): State<PgPool>,
-    session: Session,
-    Json(payload): Json<DisableTotpRequest>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    let user_id: Uuid = session
-        .get("user_id")
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-        .ok_or((StatusCode::UNAUTHORIZED, "Not logged in".to_string()))?;
-
+    AuthUser { id: user_id, .. }: AuthUser,
+    ValidatedJson(payload): ValidatedJson<DisableTotpRequest>,
+) -> Result<impl IntoResponse, ApiError> {
     // Verify current code before disabling
     if !verify_totp_code(&pool, user_id, &payload.code).await? {
-        return Err((StatusCode::BAD_REQUEST, "Invalid code".to_string()));
+        return Err(ApiError::new(StatusCode::BAD_REQUEST, &i18n::INVALID_CODE));
     }
 
     // Delete TOTP secret and backup codes
@@ -196,17 +212,21 @@ pub async fn disable_totp(
 // Verify TOTP code (used during login)
 pub async fn verify_totp(
     State(pool): State<PgPool>,
+    State(geoip): State<GeoIp>,
     session: Session,
     headers: HeaderMap,
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
-    Json(payload): Json<VerifyTotpRequest>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+    ClientIp(ip): ClientIp,
+    ValidatedJson(payload): ValidatedJson<VerifyTotpRequest>,
+) -> Result<impl IntoResponse, ApiError> {
     // Get pending 2FA user ID from session
     let pending_user_id: Uuid = session
         .get("pending_2fa_user_id")
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-        .ok_or((StatusCode::BAD_REQUEST, "No 2FA pending".to_string()))?;
+        .ok_or(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            &i18n::NO_2FA_PENDING,
+        ))?;
 
     // Try TOTP code first
     let is_valid = verify_totp_code(&pool, pending_user_id, &payload.code).await?;
@@ -219,12 +239,19 @@ pub async fn verify_totp(
     };
 
     if !is_valid {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid code".to_string()));
+        return Err(ApiError::new(StatusCode::UNAUTHORIZED, &i18n::INVALID_CODE));
     }
 
-    // Complete login
+    // The account may have been restricted since the password step
+    crate::user::ensure_can_login(&pool, pending_user_id).await?;
+
+    // Complete login, under a new session ID
+    crate::session::rotate_id(&pool, &session)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
     session
-        .insert("user_id", pending_user_id.to_string())
+        .insert("user_id", pending_user_id)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
     session.remove::<String>("pending_2fa_user_id").await.ok();
@@ -239,10 +266,11 @@ pub async fn verify_totp(
         let expires_at = chrono::Utc::now() + chrono::Duration::hours(24);
         crate::session::create_session(
             &pool,
+            &geoip,
             pending_user_id,
             session_id.to_string(),
             &headers,
-            Some(addr.ip().to_string()),
+            Some(ip.to_string()),
             expires_at,
         )
         .await
@@ -258,14 +286,8 @@ pub async fn verify_totp(
 // Get TOTP status
 pub async fn get_totp_status(
     State(pool): State<PgPool>,
-    session: Session,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    let user_id: Uuid = session
-        .get("user_id")
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-        .ok_or((StatusCode::UNAUTHORIZED, "Not logged in".to_string()))?;
-
+    AuthUser { id: user_id, .. }: AuthUser,
+) -> Result<impl IntoResponse, ApiError> {
     let enabled = sqlx::query_scalar!(
         "SELECT enabled FROM totp_secrets WHERE user_id = $1",
         user_id
@@ -282,18 +304,12 @@ pub async fn get_totp_status(
 // Regenerate backup codes
 pub async fn regenerate_backup_codes(
     State(pool): State<PgPool>,
-    session: Session,
-    Json(payload): Json<VerifyTotpRequest>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    let user_id: Uuid = session
-        .get("user_id")
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-        .ok_or((StatusCode::UNAUTHORIZED, "Not logged in".to_string()))?;
-
+    AuthUser { id: user_id, .. }: AuthUser,
+    ValidatedJson(payload): ValidatedJson<VerifyTotpRequest>,
+) -> Result<impl IntoResponse, ApiError> {
     // Verify current TOTP code
     if !verify_totp_code(&pool, user_id, &payload.code).await? {
-        return Err((StatusCode::BAD_REQUEST, "Invalid code".to_string()));
+        return Err(ApiError::new(StatusCode::BAD_REQUEST, &i18n::INVALID_CODE));
     }
 
     let codes = generate_backup_codes(&pool, user_id).await?;
@@ -302,10 +318,7 @@ pub async fn regenerate_backup_codes(
 }
 
 // Helper: Generate backup codes
-async fn generate_backup_codes(
-    pool: &PgPool,
-    user_id: Uuid,
-) -> Result<Vec<String>, (StatusCode, String)> {
+async fn generate_backup_codes(pool: &PgPool, user_id: Uuid) -> Result<Vec<String>, ApiError> {
     // Delete existing codes
     sqlx::query!("DELETE FROM backup_codes WHERE user_id = $1", user_id)
         .execute(pool)
@@ -350,11 +363,7 @@ async fn generate_backup_codes(
 }
 
 // Helper: Verify TOTP code
-async fn verify_totp_code(
-    pool: &PgPool,
-    user_id: Uuid,
-    code: &str,
-) -> Result<bool, (StatusCode, String)> {
+async fn verify_totp_code(pool: &PgPool, user_id: Uuid, code: &str) -> Result<bool, ApiError> {
     let totp_record = sqlx::query!(
         "SELECT secret, enabled FROM totp_secrets WHERE user_id = $1",
         user_id
@@ -379,7 +388,7 @@ async fn verify_totp_code(
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
     totp.check_current(code)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into())
 }
 
 // Helper: Verify and consume backup code
@@ -387,7 +396,7 @@ async fn verify_and_consume_backup_code(
     pool: &PgPool,
     user_id: Uuid,
     code: &str,
-) -> Result<bool, (StatusCode, String)> {
+) -> Result<bool, ApiError> {
     let backup_codes = sqlx::query!(
         "SELECT id, code_hash FROM backup_codes WHERE user_id = $1 AND used = false",
         user_id
@@ -417,7 +426,7 @@ async fn verify_and_consume_backup_code(
 }
 
 // Check if user has 2FA enabled (for login flow)
-pub async fn has_2fa_enabled(pool: &PgPool, user_id: Uuid) -> Result<bool, (StatusCode, String)> {
+pub async fn has_2fa_enabled(pool: &PgPool, user_id: Uuid) -> Result<bool, ApiError> {
     let enabled = sqlx::query_scalar!(
         "SELECT enabled FROM totp_secrets WHERE user_id = $1",
         user_id
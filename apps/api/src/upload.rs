@@ -1,83 +1,1152 @@
 use axum::{
+    extract::{Path, Query, State},
     http::StatusCode,
     response::{IntoResponse, Json},
 };
 use axum_extra::extract::Multipart;
+use image::{codecs::webp::WebPEncoder, DynamicImage, ExtendedColorType, ImageError};
+use serde::Deserialize;
 use serde_json::json;
-use std::path::Path;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::time::Duration;
 use uuid::Uuid;
 
-use crate::r2::{create_r2_client, upload_to_r2};
+use crate::error::{ApiError, Message};
+use crate::extractors::AuthUser;
+use crate::i18n::{self, ApiMessage};
+use crate::moderation::{self, Verdict};
+use crate::r2::ObjectStore;
+use crate::state::AppState;
+use crate::validation::{Validate, ValidatedJson, ValidationErrors};
 
-pub async fn upload_image(mut multipart: Multipart) -> impl IntoResponse {
-    let mut image_url = None;
+// Default per-user storage quota (100 MB), override with UPLOAD_QUOTA_BYTES
+const DEFAULT_UPLOAD_QUOTA_BYTES: i64 = 100 * 1024 * 1024;
 
-    // Get bucket name from environment
-    let bucket_name = match std::env::var("R2_BUCKET_NAME") {
-        Ok(name) => name,
-        Err(_) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "R2_BUCKET_NAME not configured",
-            )
-                .into_response();
+// Flagged images are stored under this prefix until an admin reviews them
+const QUARANTINE_PREFIX: &str = "quarantine/";
+
+// Message attachments are stored under this prefix, which the public bucket URL must not serve;
+// they're only reachable through signed URLs (see `signed_urls`)
+const PRIVATE_PREFIX: &str = "private/messages/";
+
+// How long a signed URL for a private upload stays valid
+pub const SIGNED_URL_TTL_SECS: u64 = 60 * 60;
+
+// How long a signed URL for a quarantined upload stays valid, for its owner or a moderator
+const REVIEW_URL_TTL_SECS: u64 = 5 * 60;
+
+// Unattached uploads older than this are deleted by the cleanup job
+const ORPHAN_UPLOAD_TTL_HOURS: i64 = 24;
+
+// Image types we accept, identified by their magic bytes
+const ALLOWED_IMAGE_TYPES: &[&str] = &["image/jpeg", "image/png", "image/gif", "image/webp"];
+
+// Video types accepted for direct (presigned) uploads, with their stored extension
+const ALLOWED_VIDEO_TYPES: &[(&str, &str)] = &[
+    ("video/mp4", "mp4"),
+    ("video/webm", "webm"),
+    ("video/quicktime", "mov"),
+];
+
+// How long a presigned video upload URL stays valid
+const PRESIGNED_UPLOAD_TTL_SECS: u64 = 15 * 60;
+
+// Resized variants (name, max width/height) generated for every upload
+const RESIZED_VARIANTS: &[(&str, u32)] = &[("thumb", 256), ("medium", 1024)];
+
+// Rows in a finished image upload: the original, its resized variants and a full-size webp
+const IMAGE_GROUP_SIZE: i64 = RESIZED_VARIANTS.len() as i64 + 2;
+
+/// What an upload is for, which decides how large it may be
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UploadCategory {
+    Avatar,
+    Banner,
+    Post,
+    Video,
+    /// Direct message attachments, which are private
+    Message,
+}
+
+impl UploadCategory {
+    // Categories that go through the multipart image endpoint
+    const IMAGE_CATEGORIES: [UploadCategory; 4] =
+        [Self::Avatar, Self::Banner, Self::Post, Self::Message];
+
+    fn label(self) -> &'static ApiMessage {
+        match self {
+            Self::Avatar => &i18n::AVATAR,
+            Self::Banner => &i18n::BANNER,
+            Self::Post => &i18n::POST_IMAGE,
+            Self::Video => &i18n::VIDEO,
+            Self::Message => &i18n::MESSAGE_ATTACHMENT,
         }
+    }
+
+    /// Size limit in bytes, override with UPLOAD_LIMIT_<CATEGORY>_BYTES
+    pub fn max_bytes(self) -> usize {
+        let (var, default) = match self {
+            Self::Avatar => ("UPLOAD_LIMIT_AVATAR_BYTES", 2 * 1024 * 1024),
+            Self::Banner => ("UPLOAD_LIMIT_BANNER_BYTES", 5 * 1024 * 1024),
+            Self::Post => ("UPLOAD_LIMIT_POST_BYTES", 8 * 1024 * 1024),
+            Self::Video => ("UPLOAD_LIMIT_VIDEO_BYTES", 100 * 1024 * 1024),
+            Self::Message => ("UPLOAD_LIMIT_MESSAGE_BYTES", 8 * 1024 * 1024),
+        };
+        std::env::var(var)
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(default)
+    }
+
+    fn too_large(self) -> ApiError {
+        let message = Message::new(&i18n::UPLOAD_TOO_LARGE)
+            .arg_message("category", Message::new(self.label()))
+            .arg("mb", self.max_bytes() / (1024 * 1024));
+        (StatusCode::PAYLOAD_TOO_LARGE, message).into()
+    }
+}
+
+/// Largest image that can be uploaded through `/upload`, used to size the request body limit
+pub fn max_image_upload_bytes() -> usize {
+    UploadCategory::IMAGE_CATEGORIES
+        .iter()
+        .map(|c| c.max_bytes())
+        .max()
+        .unwrap_or(0)
+}
+
+#[derive(Deserialize)]
+pub struct UploadParams {
+    pub category: Option<UploadCategory>,
+}
+
+fn upload_quota_bytes() -> i64 {
+    std::env::var("UPLOAD_QUOTA_BYTES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_UPLOAD_QUOTA_BYTES)
+}
+
+/// Bytes this user already has stored in R2 (all variants)
+async fn storage_used(pool: &PgPool, user_id: Uuid) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT COALESCE(SUM(size_bytes), 0)::bigint as "used!" FROM uploads WHERE owner_id = $1"#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await
+}
+
+/// Identify the image type from the file's leading bytes, returning (mime type, extension).
+/// The client's declared content type must agree with what the bytes actually are.
+fn sniff_image_type(
+    data: &[u8],
+    declared_content_type: &str,
+) -> Result<(&'static str, &'static str), &'static ApiMessage> {
+    let kind = infer::get(data)
+        .filter(|k| ALLOWED_IMAGE_TYPES.contains(&k.mime_type()))
+        .ok_or(&i18n::UNSUPPORTED_IMAGE_TYPE)?;
+
+    // Some clients send the non-standard image/jpg
+    let declared = match declared_content_type.to_ascii_lowercase().as_str() {
+        "image/jpg" => "image/jpeg".to_string(),
+        other => other.to_string(),
     };
+    if declared != kind.mime_type() {
+        return Err(&i18n::IMAGE_TYPE_MISMATCH);
+    }
+
+    Ok((kind.mime_type(), kind.extension()))
+}
+
+/// Run an upload past the moderation hook, returning the labels it was flagged with.
+/// One that can't be screened is flagged too, so it's held for review rather than published.
+async fn screen(data: &[u8], content_type: &str) -> Option<Vec<String>> {
+    match moderation::moderator().screen(data, content_type).await {
+        Ok(Verdict::Allow) => None,
+        Ok(Verdict::Flag(labels)) => Some(labels),
+        Err(e) => {
+            tracing::warn!("Upload screening failed, holding for review: {}", e);
+            Some(vec!["screening_failed".to_string()])
+        }
+    }
+}
+
+/// Encode an image as (lossless) webp
+fn encode_webp(img: &DynamicImage) -> Result<Vec<u8>, ImageError> {
+    // The webp encoder only accepts 8-bit RGB(A)
+    let rgba = img.to_rgba8();
+    let mut buf = Vec::new();
+    WebPEncoder::new_lossless(&mut buf).encode(
+        &rgba,
+        rgba.width(),
+        rgba.height(),
+        ExtendedColorType::Rgba8,
+    )?;
+    Ok(buf)
+}
+
+/// Decode the original and produce webp variants: resized thumb/medium and a full-size webp
+fn generate_variants(data: &[u8]) -> Result<Vec<(&'static str, Vec<u8>)>, ImageError> {
+    let img = image::load_from_memory(data)?;
+    let mut variants = Vec::new();
+
+    for (name, max_dim) in RESIZED_VARIANTS {
+        let encoded = if img.width() > *max_dim || img.height() > *max_dim {
+            encode_webp(&img.thumbnail(*max_dim, *max_dim))?
+        } else {
+            encode_webp(&img)?
+        };
+        variants.push((*name, encoded));
+    }
+    variants.push(("webp", encode_webp(&img)?));
 
-    // Create R2 client
-    let client = create_r2_client();
+    Ok(variants)
+}
+
+pub async fn upload_image(
+    State(state): State<AppState>,
+    AuthUser { id: user_id, .. }: AuthUser,
+    Query(params): Query<UploadParams>,
+    mut multipart: Multipart,
+) -> impl IntoResponse {
+    let category = params.category.unwrap_or(UploadCategory::Post);
+    if !UploadCategory::IMAGE_CATEGORIES.contains(&category) {
+        return ApiError::new(StatusCode::BAD_REQUEST, &i18n::VIDEO_NEEDS_PRESIGN).into_response();
+    }
+    let max_bytes = category.max_bytes();
+
+    let mut uploaded = None;
 
-    while let Some(field) = multipart.next_field().await.unwrap_or(None) {
+    let r2 = match state.r2() {
+        Ok(r2) => r2,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    };
+
+    while let Some(mut field) = multipart.next_field().await.unwrap_or(None) {
         let name = field.name().unwrap_or("").to_string();
-        let file_name = field.file_name().unwrap_or("").to_string();
         let content_type = field.content_type().unwrap_or("").to_string();
 
         if name == "file" {
             // Validate content type
             if !content_type.starts_with("image/") {
-                return (StatusCode::BAD_REQUEST, "Invalid file type").into_response();
+                return ApiError::new(StatusCode::BAD_REQUEST, &i18n::INVALID_FILE_TYPE)
+                    .into_response();
             }
 
-            let data = match field.bytes().await {
-                Ok(data) => data,
-                Err(_) => {
-                    return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to read file")
+            // Read in chunks so oversized files are rejected without buffering them whole
+            let mut data = Vec::new();
+            loop {
+                match field.chunk().await {
+                    Ok(Some(chunk)) => {
+                        if data.len() + chunk.len() > max_bytes {
+                            return category.too_large().into_response();
+                        }
+                        data.extend_from_slice(&chunk);
+                    }
+                    Ok(None) => break,
+                    Err(_) => {
+                        return ApiError::new(
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                            &i18n::FILE_READ_FAILED,
+                        )
                         .into_response();
+                    }
                 }
+            }
+
+            // Trust the bytes, not the declared type or filename
+            let (content_type, ext) = match sniff_image_type(&data, &content_type) {
+                Ok(sniffed) => sniffed,
+                Err(entry) => return ApiError::new(StatusCode::BAD_REQUEST, entry).into_response(),
             };
 
-            // Generate unique filename
-            let ext = Path::new(&file_name)
-                .extension()
-                .and_then(std::ffi::OsStr::to_str)
-                .unwrap_or("jpg");
-            let new_filename = format!("{}.{}", Uuid::new_v4(), ext);
-
-            // Upload to R2
-            match upload_to_r2(
-                &client,
-                &bucket_name,
-                &new_filename,
-                data.to_vec(),
-                &content_type,
-            )
+            // Screen before anything is published; flagged images are held for admin review
+            let flagged_labels = screen(&data, content_type).await;
+            let moderation_status = if flagged_labels.is_some() {
+                "pending"
+            } else {
+                "approved"
+            };
+            let moderation_labels = flagged_labels.unwrap_or_default();
+
+            // Generate resized/webp variants off the async runtime
+            let original = data;
+            let (original, variants) = match tokio::task::spawn_blocking(move || {
+                generate_variants(&original).map(|v| (original, v))
+            })
             .await
             {
-                Ok(url) => {
-                    image_url = Some(url);
+                Ok(Ok(result)) => result,
+                Ok(Err(e)) => {
+                    tracing::warn!("Failed to process uploaded image: {}", e);
+                    return ApiError::new(StatusCode::BAD_REQUEST, &i18n::IMAGE_UNPROCESSABLE)
+                        .into_response();
                 }
                 Err(e) => {
-                    eprintln!("Failed to upload to R2: {:?}", e);
-                    return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to upload file")
-                        .into_response();
+                    return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
                 }
+            };
+
+            let size_bytes = (original.len()
+                + variants.iter().map(|(_, bytes)| bytes.len()).sum::<usize>())
+                as i64;
+
+            // Enforce per-user storage quota (all variants count)
+            let used_bytes = match storage_used(&state.pool, user_id).await {
+                Ok(used) => used,
+                Err(e) => {
+                    return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
+                }
+            };
+
+            if used_bytes + size_bytes > upload_quota_bytes() {
+                return ApiError::new(StatusCode::FORBIDDEN, &i18n::STORAGE_QUOTA_EXCEEDED)
+                    .into_response();
+            }
+
+            // Objects are namespaced by owner and grouped per upload:
+            // <user_id>/<upload_id>/original.<ext>, <user_id>/<upload_id>/thumb.webp, ...
+            // (with private/messages/ in front for message attachments,
+            // and quarantine/ in front of that while pending review)
+            let mut prefix = format!("{}/{}", user_id, Uuid::new_v4());
+            if category == UploadCategory::Message {
+                prefix.insert_str(0, PRIVATE_PREFIX);
+            }
+            if moderation_status == "pending" {
+                prefix.insert_str(0, QUARANTINE_PREFIX);
+            }
+
+            let mut objects = vec![(
+                "original",
+                format!("{}/original.{}", prefix, ext),
+                original,
+                content_type.to_string(),
+            )];
+            for (name, bytes) in variants {
+                objects.push((
+                    name,
+                    format!("{}/{}.webp", prefix, name),
+                    bytes,
+                    "image/webp".to_string(),
+                ));
+            }
+
+            let mut parent_id: Option<Uuid> = None;
+            let mut urls = HashMap::new();
+
+            for (variant, object_key, bytes, object_content_type) in objects {
+                let object_size = bytes.len() as i64;
+
+                // Upload to R2
+                let url = match r2.upload(&object_key, bytes, &object_content_type).await {
+                    Ok(url) => url,
+                    Err(e) => {
+                        eprintln!("Failed to upload to R2: {:?}", e);
+                        return ApiError::new(
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                            &i18n::UPLOAD_FAILED,
+                        )
+                        .into_response();
+                    }
+                };
+
+                // Record ownership (variants point at the original)
+                let id = match sqlx::query_scalar!(
+                    r#"
+                    INSERT INTO uploads (owner_id, object_key, url, content_type, size_bytes, variant, parent_id,
+                                         moderation_status, moderation_labels)
+                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                    RETURNING id
+                    "#,
+                    user_id,
+                    object_key,
+                    url,
+                    object_content_type,
+                    object_size,
+                    variant,
+                    parent_id,
+                    moderation_status,
+                    &moderation_labels
+                )
+                .fetch_one(&state.pool)
+                .await
+                {
+                    Ok(id) => id,
+                    Err(e) => {
+                        tracing::error!("Failed to record upload {}: {}", object_key, e);
+                        return ApiError::new(
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                            &i18n::UPLOAD_FAILED,
+                        )
+                        .into_response();
+                    }
+                };
+
+                parent_id.get_or_insert(id);
+                urls.insert(variant, url);
             }
+
+            uploaded = Some((parent_id, moderation_status, urls));
+        }
+    }
+
+    match uploaded {
+        // Don't hand out URLs for quarantined images; the client can poll GET /upload/:id
+        Some((id, "pending", _)) => (
+            StatusCode::ACCEPTED,
+            Json(json!({ "id": id, "status": "pending" })),
+        )
+            .into_response(),
+        // Private: the client sends the id with its message, and members get signed URLs
+        Some((id, status, _)) if category == UploadCategory::Message => {
+            Json(json!({ "id": id, "status": status })).into_response()
+        }
+        // `url` stays the original for existing clients
+        Some((id, status, urls)) => Json(json!({
+            "id": id,
+            "status": status,
+            "url": urls["original"],
+            "variants": urls,
+        }))
+        .into_response(),
+        None => ApiError::new(StatusCode::BAD_REQUEST, &i18n::NO_FILE_UPLOADED).into_response(),
+    }
+}
+
+/// Moderation status of one of the caller's uploads, with its URLs once approved.
+/// While it's held for review the owner gets short-lived signed URLs instead.
+pub async fn get_upload(
+    State(state): State<AppState>,
+    AuthUser { id: user_id, .. }: AuthUser,
+    Path(upload_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT variant, url, object_key, moderation_status FROM uploads
+        WHERE (id = $1 OR parent_id = $1) AND owner_id = $2
+        "#,
+        upload_id,
+        user_id
+    )
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let Some(status) = rows.first().map(|r| r.moderation_status.clone()) else {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            &i18n::UPLOAD_NOT_FOUND,
+        ));
+    };
+
+    // Nothing to show until the client confirms it
+    if status == "unconfirmed" {
+        return Ok(Json(json!({ "id": upload_id, "status": status })));
+    }
+
+    if status == "pending" {
+        let urls = review_urls(&state, rows.into_iter().map(|r| (r.variant, r.object_key)))
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+        return Ok(Json(json!({
+            "id": upload_id,
+            "status": status,
+            "url": urls.get("original"),
+            "variants": urls,
+            "expires_in": REVIEW_URL_TTL_SECS,
+        })));
+    }
+
+    // Private uploads are only ever served through signed URLs on their message
+    let private = rows.iter().any(|r| r.object_key.contains(PRIVATE_PREFIX));
+    if private {
+        return Ok(Json(json!({ "id": upload_id, "status": status })));
+    }
+
+    let urls: HashMap<String, String> = rows.into_iter().map(|r| (r.variant, r.url)).collect();
+    Ok(Json(json!({
+        "id": upload_id,
+        "status": status,
+        "url": urls.get("original"),
+        "variants": urls,
+    })))
+}
+
+#[derive(Deserialize)]
+pub struct PresignRequest {
+    pub content_type: String,
+    pub size_bytes: i64,
+}
+
+impl Validate for PresignRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.required("content_type", &self.content_type);
+        if self.size_bytes <= 0 {
+            errors.add("size_bytes", "Must be greater than 0");
         }
+        errors.into_result()
+    }
+}
+
+/// Hand out a short-lived URL for uploading a video straight to R2.
+/// The declared size is signed into the URL, so R2 rejects anything larger. The video goes
+/// under quarantine/ and can't be used until the client calls `confirm_upload`.
+pub async fn presign_upload(
+    State(state): State<AppState>,
+    AuthUser { id: user_id, .. }: AuthUser,
+    ValidatedJson(payload): ValidatedJson<PresignRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let category = UploadCategory::Video;
+    let ext = ALLOWED_VIDEO_TYPES
+        .iter()
+        .find(|(mime, _)| *mime == payload.content_type)
+        .map(|(_, ext)| *ext)
+        .ok_or(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            &i18n::UNSUPPORTED_VIDEO_TYPE,
+        ))?;
+    if payload.size_bytes as u64 > category.max_bytes() as u64 {
+        return Err(category.too_large());
+    }
+
+    let used_bytes = storage_used(&state.pool, user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if used_bytes + payload.size_bytes > upload_quota_bytes() {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            &i18n::STORAGE_QUOTA_EXCEEDED,
+        ));
     }
 
-    if let Some(url) = image_url {
-        Json(json!({ "url": url })).into_response()
-    } else {
-        (StatusCode::BAD_REQUEST, "No file uploaded").into_response()
+    let r2 = state
+        .r2()
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    let object_key = format!(
+        "{}{}/{}/original.{}",
+        QUARANTINE_PREFIX,
+        user_id,
+        Uuid::new_v4(),
+        ext
+    );
+    let (upload_url, url) = r2
+        .presign_put(
+            &object_key,
+            &payload.content_type,
+            payload.size_bytes,
+            Duration::from_secs(PRESIGNED_UPLOAD_TTL_SECS),
+        )
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to presign upload {}: {}", object_key, e);
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                &i18n::UPLOAD_PREPARE_FAILED,
+            )
+        })?;
+
+    // Recorded up front so it counts against the quota; never-confirmed videos
+    // (including ones that were never actually uploaded) go to the orphan cleanup
+    let id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO uploads (owner_id, object_key, url, content_type, size_bytes, moderation_status)
+        VALUES ($1, $2, $3, $4, $5, 'unconfirmed')
+        RETURNING id
+        "#,
+        user_id,
+        object_key,
+        url,
+        payload.content_type,
+        payload.size_bytes
+    )
+    .fetch_one(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(json!({
+        "id": id,
+        "upload_url": upload_url,
+        "expires_in": PRESIGNED_UPLOAD_TTL_SECS,
+    })))
+}
+
+/// Publish a presigned upload once the client has sent it. The object must be there and its
+/// bytes the declared type; then it's screened like images are, and flagged ones stay in
+/// quarantine for review.
+pub async fn confirm_upload(
+    State(state): State<AppState>,
+    AuthUser { id: user_id, .. }: AuthUser,
+    Path(upload_id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let upload = sqlx::query!(
+        r#"
+        SELECT object_key, content_type FROM uploads
+        WHERE id = $1 AND owner_id = $2 AND moderation_status = 'unconfirmed'
+        "#,
+        upload_id,
+        user_id
+    )
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or(ApiError::new(
+        StatusCode::NOT_FOUND,
+        &i18n::UPLOAD_NOT_FOUND,
+    ))?;
+
+    let r2 = state
+        .r2()
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    let size_bytes = r2
+        .head(&upload.object_key)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?
+        .ok_or(ApiError::new(
+            StatusCode::CONFLICT,
+            &i18n::UPLOAD_NOT_RECEIVED,
+        ))?;
+    let data = r2
+        .download(&upload.object_key)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    // Trust the bytes, not the declared type; a mismatch is deleted rather than kept around
+    if infer::get(&data).map(|kind| kind.mime_type()) != Some(upload.content_type.as_str()) {
+        delete_upload_group(&state.pool, r2, upload_id)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            &i18n::IMAGE_TYPE_MISMATCH,
+        ));
     }
+
+    if let Some(labels) = screen(&data, &upload.content_type).await {
+        sqlx::query!(
+            r#"
+            UPDATE uploads SET moderation_status = 'pending', moderation_labels = $2, size_bytes = $3
+            WHERE id = $1 AND moderation_status = 'unconfirmed'
+            "#,
+            upload_id,
+            &labels,
+            size_bytes
+        )
+        .execute(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        return Ok((
+            StatusCode::ACCEPTED,
+            Json(json!({ "id": upload_id, "status": "pending" })),
+        ));
+    }
+
+    let public_key = upload
+        .object_key
+        .strip_prefix(QUARANTINE_PREFIX)
+        .unwrap_or(&upload.object_key);
+    let url = r2
+        .copy(&upload.object_key, public_key)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    sqlx::query!(
+        r#"
+        UPDATE uploads SET object_key = $2, url = $3, moderation_status = 'approved', size_bytes = $4
+        WHERE id = $1 AND moderation_status = 'unconfirmed'
+        "#,
+        upload_id,
+        public_key,
+        url,
+        size_bytes
+    )
+    .execute(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    // The row now points at the public copy; a leftover quarantined object is harmless
+    if let Err(e) = r2.delete(&upload.object_key).await {
+        tracing::error!(
+            "Failed to delete quarantined object {}: {:?}",
+            upload.object_key,
+            e
+        );
+    }
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({ "id": upload_id, "status": "approved", "url": url })),
+    ))
+}
+
+/// Signed URLs for every variant of a private upload, keyed by variant name
+pub async fn signed_urls(
+    state: &AppState,
+    upload_id: Uuid,
+) -> Result<HashMap<String, String>, String> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT variant, object_key FROM uploads
+        WHERE (id = $1 OR parent_id = $1) AND moderation_status = 'approved'
+        "#,
+        upload_id
+    )
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    presign(
+        state,
+        rows.into_iter().map(|r| (r.variant, r.object_key)),
+        Duration::from_secs(SIGNED_URL_TTL_SECS),
+    )
+    .await
+}
+
+/// Signed URLs for a quarantined upload's (variant, object key) pairs, keyed by variant name.
+/// Its objects aren't public until it's approved; only the owner and moderators should see them.
+pub async fn review_urls(
+    state: &AppState,
+    objects: impl IntoIterator<Item = (String, String)>,
+) -> Result<HashMap<String, String>, String> {
+    presign(state, objects, Duration::from_secs(REVIEW_URL_TTL_SECS)).await
+}
+
+async fn presign(
+    state: &AppState,
+    objects: impl IntoIterator<Item = (String, String)>,
+    expires_in: Duration,
+) -> Result<HashMap<String, String>, String> {
+    let mut objects = objects.into_iter().peekable();
+    if objects.peek().is_none() {
+        return Ok(HashMap::new());
+    }
+
+    let r2 = state.r2()?;
+    let mut urls = HashMap::new();
+    for (variant, object_key) in objects {
+        let url = r2.presign_get(&object_key, expires_in).await?;
+        urls.insert(variant, url);
+    }
+
+    Ok(urls)
+}
+
+/// Attach one of the owner's private uploads to a message. Returns false, attaching nothing,
+/// if it isn't theirs, isn't private, is still under review or is already attached.
+pub async fn attach_private(
+    conn: &mut sqlx::PgConnection,
+    upload_id: Uuid,
+    owner_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE uploads SET attached_at = NOW()
+        WHERE id = $1 AND owner_id = $2 AND parent_id IS NULL AND attached_at IS NULL
+          AND moderation_status = 'approved' AND starts_with(object_key, $3)
+        "#,
+        upload_id,
+        owner_id,
+        PRIVATE_PREFIX
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+/// Reject an image URL for a post or project unless it's one of the author's own published
+/// uploads. Anything else would be deleted along with content it doesn't belong to.
+pub async fn check_owned(pool: &PgPool, owner_id: Uuid, url: &str) -> Result<(), ApiError> {
+    let owned = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS(
+            SELECT 1 FROM uploads
+            WHERE url = $1 AND owner_id = $2 AND moderation_status = 'approved'
+              AND NOT starts_with(object_key, $3)
+        ) AS "owned!"
+        "#,
+        url,
+        owner_id,
+        PRIVATE_PREFIX
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    if !owned {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            &i18n::IMAGE_NOT_OWNED,
+        ));
+    }
+    Ok(())
+}
+
+/// Mark the owner's uploads behind the given URLs (originals or any variant) as attached to
+/// content, so the orphan cleanup job leaves them alone
+pub async fn mark_attached(
+    pool: &PgPool,
+    owner_id: Uuid,
+    urls: &[&str],
+) -> Result<(), sqlx::Error> {
+    if urls.is_empty() {
+        return Ok(());
+    }
+
+    let urls: Vec<String> = urls.iter().map(|u| u.to_string()).collect();
+    sqlx::query!(
+        r#"
+        UPDATE uploads SET attached_at = NOW()
+        WHERE attached_at IS NULL
+          AND id IN (
+              SELECT COALESCE(parent_id, id) FROM uploads
+              WHERE url = ANY($1) AND owner_id = $2 AND moderation_status <> 'unconfirmed'
+          )
+        "#,
+        &urls,
+        owner_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Delete an upload (original plus all variants) from R2, then its rows.
+/// Returns false, leaving the rows in place, if any object could not be deleted.
+async fn delete_upload_group(
+    pool: &PgPool,
+    r2: &dyn ObjectStore,
+    parent_id: Uuid,
+) -> Result<bool, String> {
+    let keys = sqlx::query_scalar!(
+        "SELECT object_key FROM uploads WHERE id = $1 OR parent_id = $1",
+        parent_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let mut failed = false;
+    for key in &keys {
+        if let Err(e) = r2.delete(key).await {
+            tracing::error!("Failed to delete object {}: {:?}", key, e);
+            failed = true;
+        }
+    }
+
+    if failed {
+        return Ok(false);
+    }
+
+    // Variants are removed via ON DELETE CASCADE
+    sqlx::query!("DELETE FROM uploads WHERE id = $1", parent_id)
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(true)
+}
+
+/// Delete the owner's uploads behind the given URLs once the content using them is gone,
+/// unless a post, project or profile still shows one of their images.
+/// They are detached first, so anything that fails to delete now is picked up by the orphan cleanup.
+pub async fn release_uploads(
+    state: &AppState,
+    owner_id: Uuid,
+    urls: &[&str],
+) -> Result<(), String> {
+    let pool = &state.pool;
+    if urls.is_empty() {
+        return Ok(());
+    }
+
+    let urls: Vec<String> = urls.iter().map(|u| u.to_string()).collect();
+    let groups = sqlx::query_scalar!(
+        r#"
+        UPDATE uploads u SET attached_at = NULL
+        WHERE u.id IN (
+              SELECT COALESCE(parent_id, id) FROM uploads WHERE url = ANY($1) AND owner_id = $2
+          )
+          AND NOT EXISTS (
+              SELECT 1 FROM uploads g
+              WHERE (g.id = u.id OR g.parent_id = u.id)
+                AND (EXISTS (SELECT 1 FROM posts WHERE image_url = g.url)
+                  OR EXISTS (SELECT 1 FROM projects WHERE image_url = g.url)
+                  OR EXISTS (
+                      SELECT 1 FROM users
+                      WHERE g.url IN (avatar_url, banner_url, avatar_original_url, banner_original_url)
+                  ))
+          )
+        RETURNING u.id
+        "#,
+        &urls,
+        owner_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    if groups.is_empty() {
+        return Ok(());
+    }
+
+    let r2 = state.r2()?;
+
+    for id in groups {
+        delete_upload_group(pool, r2, id).await?;
+    }
+
+    Ok(())
+}
+
+/// Delete message attachments once their messages are gone, except ones kept as evidence
+/// in a message report
+pub async fn release_private(state: &AppState, upload_ids: &[Uuid]) -> Result<(), String> {
+    let pool = &state.pool;
+    if upload_ids.is_empty() {
+        return Ok(());
+    }
+
+    let groups = sqlx::query_scalar!(
+        r#"
+        UPDATE uploads SET attached_at = NULL
+        WHERE id = ANY($1) AND starts_with(object_key, $2)
+          AND NOT EXISTS (SELECT 1 FROM message_reports r WHERE r.attachment_id = uploads.id)
+        RETURNING id
+        "#,
+        upload_ids,
+        PRIVATE_PREFIX
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    if groups.is_empty() {
+        return Ok(());
+    }
+
+    let r2 = state.r2()?;
+
+    for id in groups {
+        delete_upload_group(pool, r2, id).await?;
+    }
+
+    Ok(())
+}
+
+/// Delete every object a user has stored in R2. Must run before the user row is deleted,
+/// since their upload rows (and with them the object keys) cascade away with it.
+pub async fn purge_user_uploads(state: &AppState, user_id: Uuid) -> Result<(), String> {
+    let pool = &state.pool;
+    let groups = sqlx::query_scalar!(
+        "SELECT id FROM uploads WHERE owner_id = $1 AND parent_id IS NULL",
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    if groups.is_empty() {
+        return Ok(());
+    }
+
+    let r2 = state.r2()?;
+
+    let mut failed = 0;
+    for id in groups {
+        if !delete_upload_group(pool, r2, id).await? {
+            failed += 1;
+        }
+    }
+
+    if failed > 0 {
+        return Err(format!("{} uploads could not be deleted from R2", failed));
+    }
+
+    Ok(())
+}
+
+/// Delete a single object from R2 by key, along with its upload row if we have one.
+/// Purging an original also purges its variants. Returns the number of objects deleted.
+pub async fn purge_object(state: &AppState, object_key: &str) -> Result<usize, String> {
+    let pool = &state.pool;
+    let r2 = state.r2()?;
+
+    let upload = sqlx::query!(
+        "SELECT id, parent_id FROM uploads WHERE object_key = $1",
+        object_key
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    match upload {
+        // Original: take the whole group with it
+        Some(u) if u.parent_id.is_none() => {
+            let count = sqlx::query_scalar!(
+                r#"SELECT COUNT(*) as "count!" FROM uploads WHERE id = $1 OR parent_id = $1"#,
+                u.id
+            )
+            .fetch_one(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+
+            if !delete_upload_group(pool, r2, u.id).await? {
+                return Err("Failed to delete object from R2".to_string());
+            }
+            Ok(count as usize)
+        }
+        // Variant, or an object we never tracked (e.g. uploaded before ownership was recorded)
+        other => {
+            r2.delete(object_key)
+                .await
+                .map_err(|e| format!("Failed to delete object from R2: {:?}", e))?;
+
+            if let Some(u) = other {
+                sqlx::query!("DELETE FROM uploads WHERE id = $1", u.id)
+                    .execute(pool)
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+            Ok(1)
+        }
+    }
+}
+
+/// Publish a quarantined upload: move its objects out of quarantine/ and mark it approved.
+/// Returns false if there is no pending upload with this id.
+pub async fn approve_quarantined(
+    state: &AppState,
+    upload_id: Uuid,
+    reviewer_id: Uuid,
+) -> Result<bool, String> {
+    let pool = &state.pool;
+    let rows = sqlx::query!(
+        r#"
+        SELECT id, object_key FROM uploads
+        WHERE (id = $1 OR parent_id = $1) AND moderation_status = 'pending'
+        "#,
+        upload_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    if rows.is_empty() {
+        return Ok(false);
+    }
+
+    let r2 = state.r2()?;
+
+    for row in rows {
+        let Some(public_key) = row.object_key.strip_prefix(QUARANTINE_PREFIX) else {
+            continue;
+        };
+
+        let url = r2
+            .copy(&row.object_key, public_key)
+            .await
+            .map_err(|e| format!("Failed to publish {}: {:?}", row.object_key, e))?;
+
+        sqlx::query!(
+            r#"
+            UPDATE uploads
+            SET object_key = $2, url = $3, moderation_status = 'approved',
+                reviewed_by = $4, reviewed_at = NOW()
+            WHERE id = $1
+            "#,
+            row.id,
+            public_key,
+            url,
+            reviewer_id
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        // The row now points at the public copy; a leftover quarantined object is harmless
+        if let Err(e) = r2.delete(&row.object_key).await {
+            tracing::error!(
+                "Failed to delete quarantined object {}: {:?}",
+                row.object_key,
+                e
+            );
+        }
+    }
+
+    Ok(true)
+}
+
+/// Reject a quarantined upload, deleting its objects and rows.
+/// Returns false if there is no pending upload with this id.
+pub async fn reject_quarantined(state: &AppState, upload_id: Uuid) -> Result<bool, String> {
+    let pool = &state.pool;
+    let pending = sqlx::query_scalar!(
+        r#"
+        SELECT id FROM uploads
+        WHERE id = $1 AND parent_id IS NULL AND moderation_status = 'pending'
+        "#,
+        upload_id
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    if pending.is_none() {
+        return Ok(false);
+    }
+
+    let r2 = state.r2()?;
+
+    if !delete_upload_group(pool, r2, upload_id).await? {
+        return Err("Failed to delete quarantined objects from R2".to_string());
+    }
+
+    Ok(true)
+}
+
+/// Delete uploads that were never attached to anything (or never confirmed) within the TTL,
+/// objects and rows, and images held for review whose upload failed part way, which no one
+/// will ever review
+pub async fn cleanup_orphaned_uploads(state: &AppState) -> Result<u64, String> {
+    let pool = &state.pool;
+    let r2 = state.r2()?;
+
+    let orphans = sqlx::query!(
+        r#"
+        SELECT u.id FROM uploads u
+        WHERE u.parent_id IS NULL
+          AND u.attached_at IS NULL
+          AND COALESCE(u.reviewed_at, u.created_at) < NOW() - make_interval(hours => $1::int)
+          AND (u.moderation_status IN ('approved', 'unconfirmed')
+            OR (u.moderation_status = 'pending' AND starts_with(u.content_type, 'image/')
+              AND (SELECT COUNT(*) FROM uploads v WHERE v.parent_id = u.id) + 1 < $2))
+        ORDER BY u.created_at
+        LIMIT 100
+        "#,
+        ORPHAN_UPLOAD_TTL_HOURS as i32,
+        IMAGE_GROUP_SIZE
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    if orphans.is_empty() {
+        return Ok(0);
+    }
+
+    let mut deleted = 0;
+
+    for orphan in orphans {
+        // Keep the rows if R2 deletion failed so the next run retries
+        if delete_upload_group(pool, r2, orphan.id).await? {
+            deleted += 1;
+        }
+    }
+
+    Ok(deleted)
 }
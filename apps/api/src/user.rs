@@ -1,14 +1,33 @@
-use crate::auth::RESERVED_USERNAMES;
+use crate::batch::{self, BatchRequest};
+use crate::cache::Cache;
+use crate::db::DbRouter;
+use crate::endorsements::SkillEndorsements;
+use crate::error::{ApiError, Message};
+use crate::extractors::{AdminUser, AuthUser};
+use crate::i18n::{self, Locale};
+use crate::state::AppState;
+use crate::validation::{
+    normalize_line, Validate, ValidatedJson, ValidationErrors, DISPLAY_NAME_MAX_LENGTH,
+};
 use axum::{
-    extract::{Json, Path, State},
-    http::StatusCode,
-    response::IntoResponse,
+    extract::{Json, Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
 };
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Duration;
 use tower_sessions::Session;
 use uuid::Uuid;
 
+// Profile edits, bans and deletions invalidate the cached copy
+const PROFILE_CACHE_TTL: Duration = Duration::from_secs(5 * 60);
+const MAX_SKILLS: usize = 20;
+// Page size bounds for the user listings
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 100;
+
 #[derive(Serialize)]
 pub struct UserProfile {
     pub id: Uuid,
@@ -32,11 +51,17 @@ pub struct UserProfile {
     pub verified: Option<bool>,
     pub pronouns: Option<String>,
     pub major: Option<String>,
+    pub skills: Vec<String>,
     pub created_at: Option<chrono::DateTime<chrono::Utc>>,
     pub has_password: bool,
+    /// Language for emails
+    pub locale: String,
+    pub reputation: i32,
+    /// Kept off GET /leaderboard
+    pub leaderboard_opt_out: bool,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 pub struct PublicUserProfile {
     pub username: String,
     pub display_name: String,
@@ -55,7 +80,50 @@ pub struct PublicUserProfile {
     pub banner_zoom: Option<f64>,
     pub pronouns: Option<String>,
     pub major: Option<String>,
+    // Cached profiles from before skills existed have none
+    #[serde(default)]
+    pub skills: Vec<String>,
     pub created_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Last profile edit (sent as Last-Modified too); missing from profiles cached before it existed
+    #[serde(default)]
+    pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// See reputation.rs; recomputed hourly
+    #[serde(default)]
+    pub reputation: i32,
+    #[serde(default)]
+    pub endorsements: Vec<SkillEndorsements>,
+}
+
+/// A member as listed in the public directory
+#[derive(Serialize)]
+pub struct DirectoryUser {
+    pub username: String,
+    pub display_name: String,
+    pub avatar_url: Option<String>,
+    pub bio: Option<String>,
+    pub pronouns: Option<String>,
+    pub major: Option<String>,
+    pub skills: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct UserPageQuery {
+    pub limit: Option<i64>,
+    /// `next_cursor` from the previous page
+    pub cursor: Option<String>,
+}
+
+impl UserPageQuery {
+    fn limit(&self) -> i64 {
+        self.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+    }
+}
+
+#[derive(Serialize)]
+pub struct UserPage<T> {
+    pub users: Vec<T>,
+    /// Pass as `cursor` for the next page; None on the last one
+    pub next_cursor: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -89,22 +157,137 @@ pub struct UpdateProfileRequest {
     pub banner_zoom: Option<f64>,
     pub pronouns: Option<String>,
     pub major: Option<String>,
+    pub locale: Option<String>,
+    /// Leave the user off the leaderboards
+    pub leaderboard_opt_out: Option<bool>,
+    /// Replaces the user's skills
+    pub skills: Option<Vec<String>>,
+}
+
+impl Validate for UpdateProfileRequest {
+    fn normalize(&mut self) {
+        let lines = [
+            &mut self.username,
+            &mut self.display_name,
+            &mut self.bio,
+            &mut self.location,
+            &mut self.pronouns,
+            &mut self.major,
+        ];
+        lines.into_iter().flatten().for_each(normalize_line);
+        self.skills.iter_mut().flatten().for_each(normalize_line);
+    }
+
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        if let Some(username) = &self.username {
+            errors.username("username", username);
+        }
+        if let Some(display_name) = &self.display_name {
+            errors.length("display_name", display_name, 1, DISPLAY_NAME_MAX_LENGTH);
+        }
+        if let Some(bio) = &self.bio {
+            errors.length("bio", bio, 0, 200);
+        }
+        if let Some(location) = &self.location {
+            errors.length("location", location, 0, 50);
+        }
+        if let Some(pronouns) = &self.pronouns {
+            errors.length("pronouns", pronouns, 0, 20);
+        }
+        if let Some(major) = &self.major {
+            errors.length("major", major, 0, 100);
+        }
+        if let Some(locale) = &self.locale {
+            errors.one_of("locale", locale, Locale::SUPPORTED);
+        }
+        if let Some(skills) = &self.skills {
+            if skills.len() > MAX_SKILLS {
+                errors.add("skills", format!("at most {} skills", MAX_SKILLS));
+            }
+            for skill in skills {
+                errors.length("skills", skill.trim(), 1, 50);
+            }
+        }
+        // Empty strings clear these fields
+        let urls = [
+            ("website", &self.website),
+            ("avatar_url", &self.avatar_url),
+            ("banner_url", &self.banner_url),
+            ("avatar_original_url", &self.avatar_original_url),
+            ("banner_original_url", &self.banner_original_url),
+        ];
+        for (field, url) in urls {
+            if let Some(url) = url.as_deref().filter(|url| !url.trim().is_empty()) {
+                errors.url(field, url);
+            }
+        }
+        errors.into_result()
+    }
+}
+
+/// Why an account can't be used right now
+pub enum AccountRestriction {
+    Banned,
+    Suspended(chrono::DateTime<chrono::Utc>),
+}
+
+impl AccountRestriction {
+    /// Short code for redirects (e.g. /login?error=account_banned)
+    pub fn code(&self) -> &'static str {
+        self.message().code()
+    }
+
+    pub fn message(&self) -> Message {
+        match self {
+            Self::Banned => Message::new(&i18n::ACCOUNT_BANNED),
+            Self::Suspended(until) => Message::new(&i18n::ACCOUNT_SUSPENDED)
+                .arg("until", until.format("%Y-%m-%d %H:%M UTC")),
+        }
+    }
+}
+
+/// Check whether a user is currently banned or suspended
+pub async fn account_restriction(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<Option<AccountRestriction>, ApiError> {
+    let user = sqlx::query!(
+        "SELECT banned_at, suspended_until FROM users WHERE id = $1",
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let Some(user) = user else {
+        return Ok(None);
+    };
+
+    if user.banned_at.is_some() {
+        return Ok(Some(AccountRestriction::Banned));
+    }
+    match user.suspended_until {
+        Some(until) if until > chrono::Utc::now() => Ok(Some(AccountRestriction::Suspended(until))),
+        _ => Ok(None),
+    }
+}
+
+/// Reject login for banned or suspended accounts
+pub async fn ensure_can_login(pool: &PgPool, user_id: Uuid) -> Result<(), ApiError> {
+    match account_restriction(pool, user_id).await? {
+        Some(restriction) => Err((StatusCode::FORBIDDEN, restriction.message()).into()),
+        None => Ok(()),
+    }
 }
 
 pub async fn get_me(
     State(pool): State<PgPool>,
     headers: axum::http::HeaderMap,
-    session: Session,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+    AuthUser { id: user_id, .. }: AuthUser,
+) -> Result<impl IntoResponse, ApiError> {
     tracing::info!("get_me: Headers: {:?}", headers);
-    // 1. Get user_id from session
-    let user_id: Uuid = match session.get("user_id").await {
-        Ok(Some(id)) => id,
-        Ok(None) => return Err((StatusCode::UNAUTHORIZED, "Not logged in".to_string())),
-        Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
-    };
-
-    // 2. Fetch user details and email (from local_auths if it exists)
+    // 1. Fetch user details and email (from local_auths if it exists)
     // using LEFT JOIN because a user might be OAuth-only (though current logic implies local_auths always has email for Google too, but let's be safe or just specific)
     // Actually, in auth.rs google_callback adds to local_auths, so we can assume local_auths exists for now, or use LEFT JOIN to be safe.
 
@@ -116,7 +299,8 @@ pub async fn get_me(
             u.avatar_crop_x, u.avatar_crop_y, u.avatar_zoom,
             u.banner_crop_x, u.banner_crop_y, u.banner_zoom,
             l.email as "email?", l.verified as "verified?",
-            u.pronouns, u.major, u.created_at as "created_at?"
+            u.pronouns, u.major, u.skills, u.created_at as "created_at?", u.locale, u.reputation,
+            u.leaderboard_opt_out
         FROM users u
         LEFT JOIN local_auths l ON u.id = l.user_id
         WHERE u.id = $1
@@ -150,71 +334,80 @@ pub async fn get_me(
             verified: u.verified,
             pronouns: u.pronouns,
             major: u.major,
+            skills: u.skills,
             created_at: u.created_at,
             has_password: u.email.is_some(),
+            locale: u.locale,
+            reputation: u.reputation,
+            leaderboard_opt_out: u.leaderboard_opt_out,
         })),
-        None => Err((StatusCode::NOT_FOUND, "User not found".to_string())),
+        None => Err(ApiError::new(StatusCode::NOT_FOUND, &i18n::USER_NOT_FOUND)),
     }
 }
 
 pub async fn update_profile(
-    State(pool): State<PgPool>,
+    State(state): State<AppState>,
+    AuthUser { id: user_id, .. }: AuthUser,
     session: Session,
     headers: axum::http::HeaderMap,
-    Json(payload): Json<UpdateProfileRequest>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+    ValidatedJson(payload): ValidatedJson<UpdateProfileRequest>,
+) -> Result<impl IntoResponse, ApiError> {
     tracing::info!("update_profile: Headers: {:?}", headers);
     tracing::info!("update_profile: Payload: {:?}", payload);
-    // 1. Get user_id from session
-    let user_id: Uuid = match session.get("user_id").await {
-        Ok(Some(id)) => id,
-        Ok(None) => return Err((StatusCode::UNAUTHORIZED, "Not logged in".to_string())),
-        Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
-    };
-
-    // 2. Build Query dynamically or check fields
+    // 1. Build Query dynamically or check fields
     // For simplicity, we can do separate updates or a COALESCE.
     // However, if username is changing, we must check uniqueness.
 
     let safe_username = payload.username.clone().map(|u| u.to_lowercase());
 
     if let Some(new_username) = &safe_username {
-        // check if username is reserved
-        if RESERVED_USERNAMES.contains(&new_username.as_str()) {
-            return Err((StatusCode::BAD_REQUEST, "Username is reserved".to_string()));
-        }
-
-        // Check if username is taken by ANOTHER user
-        let exists = sqlx::query!(
-            "SELECT id FROM users WHERE username = $1 AND id != $2",
+        // Check if username is taken by ANOTHER user, or one that looks the same
+        let lookalike = sqlx::query_scalar!(
+            "SELECT username FROM users WHERE username_skeleton = username_skeleton($1) AND id != $2",
             new_username,
             user_id
         )
-        .fetch_optional(&pool)
+        .fetch_optional(&state.pool)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-        if exists.is_some() {
-            return Err((StatusCode::CONFLICT, "Username already taken".to_string()));
+        match lookalike {
+            Some(taken) if &taken == new_username => {
+                return Err(ApiError::new(StatusCode::CONFLICT, &i18n::USERNAME_TAKEN));
+            }
+            Some(_) => {
+                return Err(ApiError::new(
+                    StatusCode::CONFLICT,
+                    &i18n::TOO_SIMILAR_TO_USERNAME,
+                ));
+            }
+            None => {}
         }
     }
 
-    // 3. Update User
+    // 2. Update User
     // Sanitize inputs
     // We do NOT use ammonia::clean here because it HTML-encodes entities (e.g. & -> &amp;),
     // which causes double-encoding issues when displayed in the frontend.
     // React handles XSS protection by default when rendering.
     // If we wanted to strip HTML tags, we should use a different approach,
     // but for now we trust the frontend/DB to handle plain text.
-    let safe_bio = payload
-        .bio
-        .as_ref()
-        // remove newlines in bio
-        .map(|s| s.replace('\n', " ").replace('\r', " "));
+    // Newlines in the bio were already turned into spaces by normalize_line
+    let safe_bio = payload.bio.as_ref();
     let safe_display_name = payload.display_name.as_ref();
     let safe_location = payload.location.as_ref();
     let safe_pronouns = payload.pronouns.as_ref();
     let safe_major = payload.major.as_deref();
+    // Trimmed, and the same skill only once whatever its case
+    let safe_skills = payload.skills.as_ref().map(|skills| {
+        let mut unique: Vec<String> = Vec::new();
+        for skill in skills.iter().map(|s| s.trim()) {
+            if !unique.iter().any(|u| u.eq_ignore_ascii_case(skill)) {
+                unique.push(skill.to_string());
+            }
+        }
+        unique
+    });
 
     let safe_website = if let Some(website) = &payload.website {
         if !website.trim().is_empty() {
@@ -225,27 +418,30 @@ pub async fn update_profile(
                 format!("https://{}", website)
             };
 
-            let client = reqwest::Client::builder()
-                .timeout(std::time::Duration::from_secs(3))
-                .build()
-                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            let client = &state.http_client;
+            let timeout = std::time::Duration::from_secs(3);
 
             // Try HEAD request first, fall back to GET? no, just HEAD for now to be fast
             // Actually many sites block HEAD, so maybe GET with range or just accept that "some exist but fail"
             // Let's try HEAD.
-            let resp = client.head(&url_string).send().await;
+            let resp = client.head(&url_string).timeout(timeout).send().await;
 
             // If HEAD fails, try GET (some servers block HEAD)
             let exists = if resp.is_ok() {
                 true
             } else {
-                client.get(&url_string).send().await.is_ok()
+                client
+                    .get(&url_string)
+                    .timeout(timeout)
+                    .send()
+                    .await
+                    .is_ok()
             };
 
             if !exists {
-                return Err((
+                return Err(ApiError::new(
                     StatusCode::BAD_REQUEST,
-                    "Website could not be reached".to_string(),
+                    &i18n::WEBSITE_UNREACHABLE,
                 ));
             }
             Some(website.clone())
@@ -263,8 +459,29 @@ pub async fn update_profile(
     // Convert Option<String> to Option<&str> for the query
     let safe_website = safe_website.as_deref();
 
-    sqlx::query!(
+    // The old values are kept in profile_revisions when any of them change
+    let renamed = sqlx::query!(
         r#"
+        WITH revision AS (
+            INSERT INTO profile_revisions (
+                user_id, changed_by, username, display_name, bio, location, website, pronouns, major,
+                avatar_url, avatar_original_url, avatar_crop_x, avatar_crop_y, avatar_zoom,
+                banner_url, banner_original_url, banner_crop_x, banner_crop_y, banner_zoom
+            )
+            SELECT
+                id, id, username, display_name, bio, location, website, pronouns, major,
+                avatar_url, avatar_original_url, avatar_crop_x, avatar_crop_y, avatar_zoom,
+                banner_url, banner_original_url, banner_crop_x, banner_crop_y, banner_zoom
+            FROM users
+            WHERE id = $16
+              AND (username, display_name, bio, location, website, pronouns, major,
+                   avatar_url, avatar_original_url, banner_url, banner_original_url)
+                  IS DISTINCT FROM
+                  (COALESCE($1, username), COALESCE($2, display_name), COALESCE($3, bio),
+                   COALESCE($4, location), COALESCE($5, website), COALESCE($17, pronouns),
+                   COALESCE($18, major), COALESCE($6, avatar_url), COALESCE($8, avatar_original_url),
+                   COALESCE($7, banner_url), COALESCE($9, banner_original_url))
+        )
         UPDATE users
         SET
             username = COALESCE($1, username),
@@ -283,8 +500,14 @@ pub async fn update_profile(
             banner_crop_y = COALESCE($14, banner_crop_y),
             banner_zoom = COALESCE($15, banner_zoom),
             pronouns = COALESCE($17, pronouns),
-            major = COALESCE($18, major)
+            major = COALESCE($18, major),
+            locale = COALESCE($19, locale),
+            skills = COALESCE($20, skills),
+            leaderboard_opt_out = COALESCE($21, leaderboard_opt_out),
+            updated_at = NOW()
+        FROM (SELECT username AS old_username FROM users WHERE id = $16) old
         WHERE id = $16
+        RETURNING old.old_username, users.username
         "#,
         safe_username, // Username usually strict validation, but assuming alphanumeric elsewhere
         safe_display_name,
@@ -303,14 +526,39 @@ pub async fn update_profile(
         payload.banner_zoom,
         user_id,
         safe_pronouns,
-        safe_major
+        safe_major,
+        payload.locale,
+        safe_skills.as_deref(),
+        payload.leaderboard_opt_out
     )
-    .execute(&pool)
+    .fetch_one(&state.pool)
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
     tracing::info!("Profile updated successfully for user_id: {}", user_id);
 
+    invalidate_profile(&*state.cache, &renamed.old_username).await;
+    invalidate_profile(&*state.cache, &renamed.username).await;
+    state.search_index.sync_user(&state.pool, user_id).await;
+    // Messages are translated from the locale cached in the session
+    if let Some(locale) = &payload.locale {
+        crate::error::cache_locale(&session, user_id, Locale::from_tag(locale)).await;
+    }
+
+    // Keep newly set profile images from being garbage collected
+    let image_urls: Vec<&str> = [
+        &payload.avatar_url,
+        &payload.banner_url,
+        &payload.avatar_original_url,
+        &payload.banner_original_url,
+    ]
+    .into_iter()
+    .filter_map(|url| url.as_deref())
+    .collect();
+    if let Err(e) = crate::upload::mark_attached(&state.pool, user_id, &image_urls).await {
+        tracing::error!("Failed to mark profile images as attached: {}", e);
+    }
+
     // Check if session ID persists (in memory)
     if let Ok(Some(check_id)) = session.get::<Uuid>("user_id").await {
         tracing::info!(
@@ -321,13 +569,17 @@ pub async fn update_profile(
         tracing::warn!("update_profile POST-UPDATE: Session LOST user_id!");
     }
 
-    Ok((StatusCode::OK, "Profile updated successfully"))
+    Ok((StatusCode::OK, Message::new(&i18n::PROFILE_UPDATED)))
 }
 
+/// Every user with their private fields, a page at a time in username order (admins only)
 pub async fn get_all(
     State(pool): State<PgPool>,
-) -> Result<Json<Vec<UserProfile>>, (StatusCode, String)> {
-    let users = sqlx::query!(
+    _: AdminUser,
+    Query(query): Query<UserPageQuery>,
+) -> Result<Json<UserPage<UserProfile>>, ApiError> {
+    let limit = query.limit();
+    let mut users = sqlx::query!(
         r#"
         SELECT
             u.id, u.username, u.display_name, u.avatar_url, u.role, u.bio, u.location, u.website, u.banner_url,
@@ -335,16 +587,27 @@ pub async fn get_all(
             u.avatar_crop_x, u.avatar_crop_y, u.avatar_zoom,
             u.banner_crop_x, u.banner_crop_y, u.banner_zoom,
             l.email as "email?", l.verified as "verified?",
-            u.pronouns, u.major, u.created_at as "created_at?"
+            u.pronouns, u.major, u.skills, u.created_at as "created_at?", u.locale, u.reputation,
+            u.leaderboard_opt_out
         FROM users u
         LEFT JOIN local_auths l ON u.id = l.user_id
-        ORDER BY u.created_at DESC
-        "#
+        WHERE ($1::text IS NULL OR u.username > $1)
+        ORDER BY u.username
+        LIMIT $2
+        "#,
+        query.cursor,
+        limit + 1
     )
     .fetch_all(&pool)
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
+    let has_more = users.len() as i64 > limit;
+    users.truncate(limit as usize);
+    let next_cursor = has_more
+        .then(|| users.last().map(|u| u.username.clone()))
+        .flatten();
+
     let profiles = users
         .into_iter()
         .map(|u| {
@@ -371,95 +634,278 @@ pub async fn get_all(
                 verified: u.verified,
                 pronouns: u.pronouns,
                 major: u.major,
+                skills: u.skills,
                 created_at: u.created_at,
                 has_password: has_pw,
+                locale: u.locale,
+                reputation: u.reputation,
+                leaderboard_opt_out: u.leaderboard_opt_out,
             }
         })
         .collect();
 
-    Ok(Json(profiles))
+    Ok(Json(UserPage {
+        users: profiles,
+        next_cursor,
+    }))
 }
 
-pub async fn delete_user(
+/// The public member directory, a page at a time in username order
+pub async fn directory(
     State(pool): State<PgPool>,
+    Query(query): Query<UserPageQuery>,
+) -> Result<Json<UserPage<DirectoryUser>>, ApiError> {
+    let limit = query.limit();
+    let mut users = sqlx::query_as!(
+        DirectoryUser,
+        r#"
+        SELECT username, display_name, avatar_url, bio, pronouns, major, skills
+        FROM users
+        WHERE banned_at IS NULL AND ($1::text IS NULL OR username > $1)
+        ORDER BY username
+        LIMIT $2
+        "#,
+        query.cursor,
+        limit + 1
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let has_more = users.len() as i64 > limit;
+    users.truncate(limit as usize);
+    let next_cursor = has_more
+        .then(|| users.last().map(|u| u.username.clone()))
+        .flatten();
+
+    Ok(Json(UserPage { users, next_cursor }))
+}
+
+pub async fn delete_user(
+    State(state): State<AppState>,
+    AdminUser(admin): AdminUser,
     session: Session,
     Path(target_user_id): Path<Uuid>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    // 1. Check if logged in
-    let user_id: Uuid = match session.get("user_id").await {
-        Ok(Some(id)) => id,
-        Ok(None) => return Err((StatusCode::UNAUTHORIZED, "Not logged in".to_string())),
-        Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
-    };
+) -> Result<impl IntoResponse, ApiError> {
+    let deleted = purge(&state, target_user_id).await?;
+
+    // The target row is gone, so keep the username in the details
+    let details = format!("deleted @{}", deleted);
+    crate::audit::record(
+        &state.pool,
+        &session,
+        admin.id,
+        "admin.user_deleted",
+        Some(target_user_id),
+        Some(&details),
+    )
+    .await?;
 
-    // 2. Check if admin
-    let requester = sqlx::query!("SELECT role FROM users WHERE id = $1", user_id)
-        .fetch_optional(&pool)
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(StatusCode::NO_CONTENT)
+}
 
-    match requester {
-        Some(u) if u.role == "admin" => {
-            // Proceed to delete
-        }
-        _ => return Err((StatusCode::FORBIDDEN, "Admins only".to_string())),
+/// Delete a user with everything they made, their stored files and their sessions.
+/// Returns their username.
+pub async fn purge(state: &AppState, user_id: Uuid) -> Result<String, ApiError> {
+    // 1. Delete their stored objects first; the upload rows cascade with the user
+    if let Err(e) = crate::upload::purge_user_uploads(state, user_id).await {
+        tracing::error!("Failed to purge uploads for user {}: {}", user_id, e);
+        return Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to delete user's uploads".to_string(),
+        )
+            .into());
     }
 
-    // 3. Delete user
-    sqlx::query!("DELETE FROM users WHERE id = $1", target_user_id)
-        .execute(&pool)
+    // 2. Log them out everywhere; their session mappings would cascade away with the user
+    crate::session::revoke_user_sessions(&state.pool, &state.sessions, user_id)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    Ok(StatusCode::NO_CONTENT)
+    // 3. Delete user
+    let deleted = sqlx::query_scalar!(
+        "DELETE FROM users WHERE id = $1 RETURNING username",
+        user_id
+    )
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or(ApiError::new(StatusCode::NOT_FOUND, &i18n::USER_NOT_FOUND))?;
+
+    invalidate_profile(&*state.cache, &deleted).await;
+    crate::feed::invalidate(&*state.cache).await;
+    state.search_index.sync_user(&state.pool, user_id).await;
+
+    Ok(deleted)
+}
+
+/// The user with this username (any case), if there is one
+pub async fn find_id(pool: &PgPool, username: &str) -> Result<Option<Uuid>, sqlx::Error> {
+    sqlx::query_scalar!(
+        "SELECT id FROM users WHERE username = $1",
+        username.to_lowercase()
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Mark a user's email verified without the emailed link. False if they have no email
+/// login to verify.
+pub async fn verify_email(pool: &PgPool, user_id: Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE local_auths
+        SET verified = TRUE, verification_token = NULL, verification_token_expires_at = NULL
+        WHERE user_id = $1
+        "#,
+        user_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
 }
 
 pub async fn get_public_profile(
     Path(username): Path<String>,
-    State(pool): State<PgPool>,
-) -> Result<Json<PublicUserProfile>, (StatusCode, String)> {
+    State(db): State<DbRouter>,
+    State(cache): State<Arc<dyn Cache>>,
+) -> Result<Response, ApiError> {
+    let username = username.to_lowercase();
+    let cache_key = format!("profile:{}", username);
+    if let Some(profile) = cache.get_json(&cache_key).await {
+        return Ok(profile_response(profile));
+    }
+
     let user = sqlx::query!(
         r#"
-        SELECT username, display_name, avatar_url, bio, location, website, banner_url, avatar_original_url, banner_original_url,
-        avatar_crop_x, avatar_crop_y, avatar_zoom, banner_crop_x, banner_crop_y, banner_zoom, pronouns, major, created_at
+        SELECT id, username, display_name, avatar_url, bio, location, website, banner_url, avatar_original_url, banner_original_url,
+        avatar_crop_x, avatar_crop_y, avatar_zoom, banner_crop_x, banner_crop_y, banner_zoom, pronouns, major, skills, created_at, updated_at, reputation
         FROM users
-        WHERE username = $1
+        WHERE username = $1 AND banned_at IS NULL
         "#,
-        username.to_lowercase()
+        username
     )
-    .fetch_optional(&pool)
+    .fetch_optional(db.read())
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    match user {
-        Some(u) => Ok(Json(PublicUserProfile {
-            username: u.username,
-            display_name: u.display_name,
-            avatar_url: u.avatar_url,
-            bio: u.bio,
-            location: u.location,
-            website: u.website,
-            banner_url: u.banner_url,
-            avatar_original_url: u.avatar_original_url,
-            banner_original_url: u.banner_original_url,
-            avatar_crop_x: u.avatar_crop_x,
-            avatar_crop_y: u.avatar_crop_y,
-            avatar_zoom: u.avatar_zoom,
-            banner_crop_x: u.banner_crop_x,
-            banner_crop_y: u.banner_crop_y,
-            banner_zoom: u.banner_zoom,
-            pronouns: u.pronouns,
-            major: u.major,
-            created_at: u.created_at,
-        })),
-        None => Err((StatusCode::NOT_FOUND, "User not found".to_string())),
+    let Some(u) = user else {
+        return Err(ApiError::new(StatusCode::NOT_FOUND, &i18n::USER_NOT_FOUND));
+    };
+    let endorsements = crate::endorsements::for_users(db.read(), &[u.id])
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .remove(&u.id)
+        .unwrap_or_default();
+    let profile = PublicUserProfile {
+        username: u.username,
+        display_name: u.display_name,
+        avatar_url: u.avatar_url,
+        bio: u.bio,
+        location: u.location,
+        website: u.website,
+        banner_url: u.banner_url,
+        avatar_original_url: u.avatar_original_url,
+        banner_original_url: u.banner_original_url,
+        avatar_crop_x: u.avatar_crop_x,
+        avatar_crop_y: u.avatar_crop_y,
+        avatar_zoom: u.avatar_zoom,
+        banner_crop_x: u.banner_crop_x,
+        banner_crop_y: u.banner_crop_y,
+        banner_zoom: u.banner_zoom,
+        pronouns: u.pronouns,
+        major: u.major,
+        skills: u.skills,
+        created_at: u.created_at,
+        updated_at: Some(u.updated_at),
+        reputation: u.reputation,
+        endorsements,
+    };
+    cache
+        .set_json(&cache_key, &profile, PROFILE_CACHE_TTL)
+        .await;
+
+    Ok(profile_response(profile))
+}
+
+fn profile_response(profile: PublicUserProfile) -> Response {
+    match profile.updated_at {
+        Some(updated_at) => (
+            [(header::LAST_MODIFIED, crate::etag::http_date(updated_at))],
+            Json(profile),
+        )
+            .into_response(),
+        None => Json(profile).into_response(),
     }
 }
 
+/// Public profiles for up to 100 user IDs at once, keyed by ID (null for unknown or banned users)
+pub async fn batch(
+    State(db): State<DbRouter>,
+    ValidatedJson(payload): ValidatedJson<BatchRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let users = sqlx::query!(
+        r#"
+        SELECT id, username, display_name, avatar_url, bio, location, website, banner_url, avatar_original_url, banner_original_url,
+        avatar_crop_x, avatar_crop_y, avatar_zoom, banner_crop_x, banner_crop_y, banner_zoom, pronouns, major, skills, created_at, updated_at, reputation
+        FROM users
+        WHERE id = ANY($1) AND banned_at IS NULL
+        "#,
+        &payload.ids
+    )
+    .fetch_all(db.read())
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let mut endorsements = crate::endorsements::for_users(db.read(), &payload.ids)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let profiles = users.into_iter().map(|u| {
+        (
+            u.id,
+            PublicUserProfile {
+                username: u.username,
+                display_name: u.display_name,
+                avatar_url: u.avatar_url,
+                bio: u.bio,
+                location: u.location,
+                website: u.website,
+                banner_url: u.banner_url,
+                avatar_original_url: u.avatar_original_url,
+                banner_original_url: u.banner_original_url,
+                avatar_crop_x: u.avatar_crop_x,
+                avatar_crop_y: u.avatar_crop_y,
+                avatar_zoom: u.avatar_zoom,
+                banner_crop_x: u.banner_crop_x,
+                banner_crop_y: u.banner_crop_y,
+                banner_zoom: u.banner_zoom,
+                pronouns: u.pronouns,
+                major: u.major,
+                skills: u.skills,
+                created_at: u.created_at,
+                updated_at: Some(u.updated_at),
+                reputation: u.reputation,
+                endorsements: endorsements.remove(&u.id).unwrap_or_default(),
+            },
+        )
+    });
+
+    Ok(Json(batch::by_id(&payload.ids, profiles)))
+}
+
+/// Drop a cached public profile, e.g. after it's edited or its owner is banned
+pub async fn invalidate_profile(cache: &dyn Cache, username: &str) {
+    cache
+        .delete(&format!("profile:{}", username.to_lowercase()))
+        .await;
+}
+
 pub async fn list_projects(
     Path(username): Path<String>,
     State(pool): State<PgPool>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+) -> Result<impl IntoResponse, ApiError> {
     let projects = sqlx::query_as!(
         UserProject,
         r#"
@@ -467,7 +913,7 @@ pub async fn list_projects(
                p.looking_for as "looking_for!: Vec<String>", p.created_at
         FROM projects p
         JOIN users u ON p.owner_id = u.id
-        WHERE u.username = $1
+        WHERE u.username = $1 AND u.banned_at IS NULL AND p.held_at IS NULL
         ORDER BY p.created_at DESC
         LIMIT 20
         "#,
@@ -482,36 +928,64 @@ pub async fn list_projects(
 
 pub async fn create_test_user(
     State(pool): State<PgPool>,
-    session: Session,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-    // 1. Check if logged in
-    let user_id: Uuid = match session.get("user_id").await {
-        Ok(Some(id)) => id,
-        Ok(None) => return Err((StatusCode::UNAUTHORIZED, "Not logged in".to_string())),
-        Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
-    };
-
-    // 2. Check if admin
-    let requester = sqlx::query!("SELECT role FROM users WHERE id = $1", user_id)
-        .fetch_optional(&pool)
+    _: AdminUser,
+) -> Result<impl IntoResponse, ApiError> {
+    let TestUser {
+        id: new_user_id,
+        username,
+        display_name,
+        email,
+    } = insert_test_user(&pool)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    match requester {
-        Some(u) if u.role == "admin" => {
-            // Proceed
-        }
-        _ => return Err((StatusCode::FORBIDDEN, "Admins only".to_string())),
-    }
+    Ok(Json(UserProfile {
+        id: new_user_id,
+        username,
+        display_name,
+        email: Some(email),
+        avatar_url: None,
+        role: "user".to_string(),
+        bio: None,
+        location: None,
+        website: None,
+        banner_url: None,
+        avatar_original_url: None,
+        banner_original_url: None,
+        avatar_crop_x: None,
+        avatar_crop_y: None,
+        avatar_zoom: None,
+        banner_crop_x: None,
+        banner_crop_y: None,
+        banner_zoom: None,
+        verified: Some(false),
+        pronouns: None,
+        major: None,
+        skills: Vec::new(),
+        created_at: Some(chrono::Utc::now()),
+        has_password: true,
+        locale: Locale::default().as_str().to_string(),
+        reputation: 0,
+        leaderboard_opt_out: false,
+    }))
+}
 
-    // 3. Create Test User
+/// A throwaway user with a random username and an email login nobody can sign in to
+pub struct TestUser {
+    pub id: Uuid,
+    pub username: String,
+    pub display_name: String,
+    pub email: String,
+}
+
+pub async fn insert_test_user(pool: &PgPool) -> Result<TestUser, sqlx::Error> {
     let random_id = Uuid::new_v4();
     let username = format!("test_user_{}", &random_id.to_string()[..8]);
     let display_name = format!("Test User {}", &random_id.to_string()[..4]);
     let email = format!("{}@example.com", username);
 
-    // Insert into users
-    let new_user_id = sqlx::query!(
+    let mut tx = pool.begin().await?;
+    let id = sqlx::query_scalar!(
         r#"
         INSERT INTO users (username, display_name, role)
         VALUES ($1, $2, 'user')
@@ -520,48 +994,27 @@ pub async fn create_test_user(
         username,
         display_name
     )
-    .fetch_one(&pool)
-    .await
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-    .id;
+    .fetch_one(&mut *tx)
+    .await?;
 
-    // Insert into local_auths (with dummy password hash)
+    // A dummy password hash, so there's no way to log in with it
     sqlx::query!(
         r#"
         INSERT INTO local_auths (user_id, email, password_hash)
         VALUES ($1, $2, $3)
         "#,
-        new_user_id,
+        id,
         email,
         "dummy_hash_for_test_user"
     )
-    .execute(&pool)
-    .await
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
 
-    Ok(Json(UserProfile {
-        id: new_user_id,
+    Ok(TestUser {
+        id,
         username,
         display_name,
-        email: Some(email),
-        avatar_url: None,
-        role: "user".to_string(),
-        bio: None,
-        location: None,
-        website: None,
-        banner_url: None,
-        avatar_original_url: None,
-        banner_original_url: None,
-        avatar_crop_x: None,
-        avatar_crop_y: None,
-        avatar_zoom: None,
-        banner_crop_x: None,
-        banner_crop_y: None,
-        banner_zoom: None,
-        verified: Some(false),
-        pronouns: None,
-        major: None,
-        created_at: Some(chrono::Utc::now()),
-        has_password: true,
-    }))
+        email,
+    })
 }
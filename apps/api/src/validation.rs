@@ -0,0 +1,263 @@
+use axum::{
+    async_trait,
+    extract::{FromRequest, Request},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::de::DeserializeOwned;
+use std::collections::BTreeMap;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::auth::RESERVED_USERNAMES;
+use crate::error::Message;
+use crate::i18n::{self, Locale};
+
+const EMAIL_MAX_LENGTH: usize = 254;
+const PASSWORD_MIN_LENGTH: usize = 6;
+// Plenty for passphrases while keeping hashing cheap
+const PASSWORD_MAX_LENGTH: usize = 128;
+const USERNAME_MIN_LENGTH: usize = 3;
+// GitHub logins become usernames on GitHub signup, and those go up to 39
+pub const USERNAME_MAX_LENGTH: usize = 39;
+const URL_MAX_LENGTH: usize = 2048;
+pub const DISPLAY_NAME_MAX_LENGTH: usize = 50;
+
+/// Request bodies that check their own fields before the handler runs
+pub trait Validate {
+    /// Tidy up text fields before they're checked, with `normalize_text` or `normalize_line`
+    fn normalize(&mut self) {}
+
+    fn validate(&self) -> Result<(), ValidationErrors>;
+}
+
+/// NFC normalize user supplied text, so the same word is always stored the same way, drop
+/// control characters and invisible ones (zero-width spaces, direction overrides) that can
+/// hide or reorder what others see, and trim it. Line breaks and tabs stay; so do zero-width
+/// joiners, which emoji sequences and some scripts need.
+pub fn normalize_text(value: &mut String) {
+    let normalized: String = value.nfc().filter(|c| !is_hidden(*c)).collect();
+    *value = normalized.trim().to_string();
+}
+
+/// `normalize_text` for one line fields like names and titles: line breaks become spaces
+pub fn normalize_line(value: &mut String) {
+    *value = value.replace(['\r', '\n', '\t'], " ");
+    normalize_text(value);
+}
+
+fn is_hidden(c: char) -> bool {
+    (c.is_control() && !matches!(c, '\n' | '\t'))
+        || matches!(
+            c,
+            '\u{00AD}'
+                | '\u{180E}'
+                | '\u{200B}'
+                | '\u{200E}'
+                | '\u{200F}'
+                | '\u{202A}'..='\u{202E}'
+                | '\u{2060}'..='\u{2064}'
+                | '\u{2066}'..='\u{2069}'
+                | '\u{FEFF}'
+        )
+}
+
+/// Lookalikes from Unicode's confusables (TR39), narrowed to the characters usernames can
+/// contain, in the order they're replaced: single characters first, so cI still reads as d
+const CONFUSABLES: &[(&str, &str)] = &[
+    ("0", "o"),
+    ("1", "l"),
+    ("i", "l"),
+    ("rn", "m"),
+    ("vv", "w"),
+    ("cl", "d"),
+];
+
+/// What a username looks like at a glance, with each lookalike in `CONFUSABLES` replaced.
+/// Usernames with the same skeleton are too easy to mistake for each other. Must match
+/// the username_skeleton() SQL function.
+pub fn username_skeleton(username: &str) -> String {
+    CONFUSABLES
+        .iter()
+        .fold(username.to_lowercase(), |skeleton, (from, to)| {
+            skeleton.replace(from, to)
+        })
+}
+
+/// Error messages per field. Responds with 422 and
+/// `{"message": "Validation failed", "errors": {"email": ["Must be a valid email address"]}}`
+#[derive(Debug, Default)]
+pub struct ValidationErrors {
+    fields: BTreeMap<&'static str, Vec<String>>,
+}
+
+impl ValidationErrors {
+    pub fn add(&mut self, field: &'static str, message: impl Into<String>) {
+        self.fields.entry(field).or_default().push(message.into());
+    }
+
+    pub fn into_result(self) -> Result<(), Self> {
+        if self.fields.is_empty() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+
+    /// Must contain something other than whitespace
+    pub fn required(&mut self, field: &'static str, value: &str) {
+        if value.trim().is_empty() {
+            self.add(field, "Required");
+        }
+    }
+
+    /// At most `max` characters, and required unless `min` is 0
+    pub fn length(&mut self, field: &'static str, value: &str, min: usize, max: usize) {
+        let len = value.chars().count();
+        if min > 0 && value.trim().is_empty() {
+            self.add(field, "Required");
+        } else if len < min {
+            self.add(field, format!("Must be at least {} characters", min));
+        } else if len > max {
+            self.add(field, format!("Must be at most {} characters", max));
+        }
+    }
+
+    pub fn email(&mut self, field: &'static str, value: &str) {
+        let valid = value.len() <= EMAIL_MAX_LENGTH
+            && !value.chars().any(char::is_whitespace)
+            && match value.split_once('@') {
+                Some((local, domain)) => {
+                    !local.is_empty()
+                        && domain.contains('.')
+                        && domain.split('.').all(|label| !label.is_empty())
+                        && !domain.contains('@')
+                }
+                None => false,
+            };
+
+        if !valid {
+            self.add(field, "Must be a valid email address");
+        }
+    }
+
+    /// Letters, numbers, underscores, hyphens and periods; usernames are case-insensitive
+    pub fn username(&mut self, field: &'static str, value: &str) {
+        let len = value.chars().count();
+        if !(USERNAME_MIN_LENGTH..=USERNAME_MAX_LENGTH).contains(&len) {
+            self.add(
+                field,
+                format!(
+                    "Must be between {} and {} characters",
+                    USERNAME_MIN_LENGTH, USERNAME_MAX_LENGTH
+                ),
+            );
+        }
+        if !value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+        {
+            self.add(
+                field,
+                "Can only contain letters, numbers, underscores, hyphens and periods",
+            );
+        }
+        if !value.chars().any(|c| c.is_ascii_alphanumeric()) {
+            self.add(field, "Must contain a letter or number");
+        }
+        let skeleton = username_skeleton(value);
+        if RESERVED_USERNAMES
+            .iter()
+            .any(|reserved| username_skeleton(reserved) == skeleton)
+        {
+            self.add(field, "That username is reserved");
+        }
+    }
+
+    pub fn password(&mut self, field: &'static str, value: &str) {
+        let len = value.chars().count();
+        if len < PASSWORD_MIN_LENGTH {
+            self.add(
+                field,
+                format!("Must be at least {} characters", PASSWORD_MIN_LENGTH),
+            );
+        } else if len > PASSWORD_MAX_LENGTH {
+            self.add(
+                field,
+                format!("Must be at most {} characters", PASSWORD_MAX_LENGTH),
+            );
+        }
+    }
+
+    /// An http(s) URL. A bare domain (example.com) is accepted as https, like the profile form.
+    pub fn url(&mut self, field: &'static str, value: &str) {
+        let with_scheme = if value.contains("://") {
+            value.to_string()
+        } else {
+            format!("https://{}", value)
+        };
+
+        let valid = value.len() <= URL_MAX_LENGTH
+            && match reqwest::Url::parse(&with_scheme) {
+                Ok(url) => {
+                    matches!(url.scheme(), "http" | "https")
+                        && url.host_str().is_some_and(|host| host.contains('.'))
+                }
+                Err(_) => false,
+            };
+
+        if !valid {
+            self.add(field, "Must be a valid URL");
+        }
+    }
+
+    pub fn one_of(&mut self, field: &'static str, value: &str, allowed: &[&str]) {
+        if !allowed.contains(&value) {
+            self.add(field, format!("Must be one of: {}", allowed.join(", ")));
+        }
+    }
+
+    pub fn range(&mut self, field: &'static str, value: i64, min: i64, max: i64) {
+        if !(min..=max).contains(&value) {
+            self.add(field, format!("Must be between {} and {}", min, max));
+        }
+    }
+}
+
+impl IntoResponse for ValidationErrors {
+    fn into_response(self) -> Response {
+        let message = Message::new(&i18n::VALIDATION_FAILED);
+        let mut response = (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(serde_json::json!({
+                "message": message.text(Locale::En),
+                "errors": self.fields,
+            })),
+        )
+            .into_response();
+        response.extensions_mut().insert(message);
+        response
+    }
+}
+
+/// Like `Json<T>`, but also runs `T::normalize` and `T::validate`, rejecting with field-level
+/// 422 errors
+pub struct ValidatedJson<T>(pub T);
+
+#[async_trait]
+impl<S, T> FromRequest<S> for ValidatedJson<T>
+where
+    S: Send + Sync,
+    T: DeserializeOwned + Validate,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(mut value) = Json::<T>::from_request(req, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        value.normalize();
+        value.validate().map_err(IntoResponse::into_response)?;
+        Ok(Self(value))
+    }
+}
@@ -0,0 +1,76 @@
+use chrono::{DateTime, Utc};
+
+use crate::auth::VERIFICATION_TOKEN_TTL_DAYS;
+use crate::i18n::Locale;
+use crate::state::AppState;
+
+// First reminder 48 hours after signup, the second 48 hours after that, then no more
+const REMINDER_INTERVAL_HOURS: i32 = 48;
+const MAX_REMINDERS: i32 = 2;
+// Older unverified accounts (e.g. from before reminders existed) are left alone
+const MAX_ACCOUNT_AGE_DAYS: i32 = 14;
+// A link about to expire is replaced, so the reminder doesn't arrive already dead
+const MIN_TOKEN_LIFE_HOURS: i32 = 24;
+
+/// Queue a reminder for every local signup still unverified at `now` that's due one.
+/// Verifying stops them, since only unverified accounts match. Returns how many were queued.
+pub async fn send_due(state: &AppState, now: DateTime<Utc>) -> Result<u64, String> {
+    // Claimed like the digest: counting the reminder first means two instances never both
+    // send it, and one that then fails to queue is skipped rather than sent twice.
+    let recipients = sqlx::query!(
+        r#"
+        UPDATE local_auths l
+        SET verification_reminders = l.verification_reminders + 1,
+            verification_reminded_at = $1::timestamptz,
+            verification_token = CASE
+                WHEN l.verification_token_expires_at > $1::timestamptz + make_interval(hours => $5)
+                THEN l.verification_token
+                ELSE gen_random_uuid()::text
+            END,
+            verification_token_expires_at = CASE
+                WHEN l.verification_token_expires_at > $1::timestamptz + make_interval(hours => $5)
+                THEN l.verification_token_expires_at
+                ELSE $1::timestamptz + make_interval(days => $6)
+            END
+        FROM users u
+        WHERE u.id = l.user_id
+          AND l.verified IS NOT TRUE
+          AND u.banned_at IS NULL
+          AND l.verification_reminders < $3
+          AND u.created_at > $1::timestamptz - make_interval(days => $4)
+          AND COALESCE(l.verification_reminded_at, u.created_at) <= $1::timestamptz - make_interval(hours => $2)
+        RETURNING l.email, l.verification_token AS "verification_token!", u.locale
+        "#,
+        now,
+        REMINDER_INTERVAL_HOURS,
+        MAX_REMINDERS,
+        MAX_ACCOUNT_AGE_DAYS,
+        MIN_TOKEN_LIFE_HOURS,
+        VERIFICATION_TOKEN_TTL_DAYS as i32
+    )
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let mut sent = 0;
+    for recipient in recipients {
+        let queued = crate::email::send_verification_reminder(
+            state,
+            &recipient.email,
+            &recipient.verification_token,
+            Locale::from_tag(&recipient.locale),
+        )
+        .await;
+
+        match queued {
+            Ok(()) => sent += 1,
+            Err(e) => tracing::error!(
+                "Failed to queue verification reminder for {}: {}",
+                recipient.email,
+                e
+            ),
+        }
+    }
+
+    Ok(sent)
+}
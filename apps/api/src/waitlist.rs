@@ -0,0 +1,249 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, PgPool};
+use tower_sessions::Session;
+use uuid::Uuid;
+
+use crate::error::{ApiError, Message};
+use crate::extractors::AdminUser;
+use crate::i18n::{self, Locale};
+use crate::state::AppState;
+use crate::validation::{normalize_text, Validate, ValidatedJson, ValidationErrors};
+
+/// How long the signup link emailed on approval stays valid
+pub const WAITLIST_TOKEN_TTL_DAYS: i64 = 14;
+pub const MAX_INTEREST_LENGTH: usize = 500;
+// Most people approved or listed at once
+const MAX_BATCH: i64 = 500;
+const DEFAULT_PAGE_SIZE: i64 = 100;
+
+#[derive(Deserialize)]
+pub struct JoinWaitlistRequest {
+    pub email: String,
+    /// What they'd like to use Praxis for
+    pub interest: Option<String>,
+    pub locale: Option<String>,
+}
+
+impl Validate for JoinWaitlistRequest {
+    fn normalize(&mut self) {
+        self.interest.iter_mut().for_each(normalize_text);
+    }
+
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.email("email", &self.email);
+        if let Some(interest) = &self.interest {
+            errors.length("interest", interest, 0, MAX_INTEREST_LENGTH);
+        }
+        errors.into_result()
+    }
+}
+
+#[derive(Serialize)]
+pub struct WaitlistEntry {
+    pub id: Uuid,
+    pub email: String,
+    pub interest: Option<String>,
+    pub locale: String,
+    pub status: String,
+    pub approved_at: Option<DateTime<Utc>>,
+    pub joined_user_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct WaitlistQuery {
+    /// waiting (the default), approved or joined
+    pub status: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct ApproveRequest {
+    /// These entries, e.g. picked from the list; approved ones get a fresh link
+    pub ids: Option<Vec<Uuid>>,
+    /// Or the next this many people waiting, longest waiting first
+    pub count: Option<i64>,
+}
+
+impl Validate for ApproveRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        match (&self.ids, self.count) {
+            (Some(ids), None) => {
+                if ids.is_empty() || ids.len() > MAX_BATCH as usize {
+                    errors.add("ids", format!("Between 1 and {} IDs", MAX_BATCH));
+                }
+            }
+            (None, Some(count)) => errors.range("count", count, 1, MAX_BATCH),
+            _ => errors.add("ids", "Give either ids or count"),
+        }
+        errors.into_result()
+    }
+}
+
+/// Put an email on the waitlist. Joining again changes nothing, so this doesn't tell
+/// anyone whether an address was already there.
+pub async fn add(
+    pool: &PgPool,
+    email: &str,
+    interest: Option<&str>,
+    locale: Locale,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO waitlist (email, interest, locale) VALUES ($1, $2, $3)
+        ON CONFLICT (email) DO NOTHING
+        "#,
+        email,
+        interest,
+        locale.as_str()
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Join the waitlist without trying to sign up
+pub async fn join(
+    State(pool): State<PgPool>,
+    ValidatedJson(payload): ValidatedJson<JoinWaitlistRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let locale = payload
+        .locale
+        .as_deref()
+        .map(Locale::from_tag)
+        .unwrap_or_default();
+    add(&pool, &payload.email, payload.interest.as_deref(), locale)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok((StatusCode::ACCEPTED, Message::new(&i18n::WAITLIST_JOINED)))
+}
+
+/// Mark the approved entry for `token` as joined by the new user. False when the token
+/// is unknown, used or expired. Run it in the signup transaction.
+pub async fn redeem(
+    conn: &mut PgConnection,
+    token: &str,
+    user_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let redeemed = sqlx::query!(
+        r#"
+        UPDATE waitlist
+        SET status = 'joined', joined_user_id = $2, token = NULL, token_expires_at = NULL
+        WHERE token = $1 AND status = 'approved' AND token_expires_at > NOW()
+        "#,
+        token.trim(),
+        user_id
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(redeemed.rows_affected() > 0)
+}
+
+/// The waitlist, longest waiting first (admins only)
+pub async fn list(
+    State(pool): State<PgPool>,
+    _: AdminUser,
+    Query(query): Query<WaitlistQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let status = query.status.as_deref().unwrap_or("waiting");
+    if !["waiting", "approved", "joined"].contains(&status) {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            &i18n::INVALID_WAITLIST_STATUS,
+        ));
+    }
+
+    let waiting = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "count!" FROM waitlist WHERE status = 'waiting'"#
+    )
+    .fetch_one(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let entries = sqlx::query_as!(
+        WaitlistEntry,
+        r#"
+        SELECT id, email, interest, locale, status, approved_at, joined_user_id, created_at
+        FROM waitlist
+        WHERE status = $1
+        ORDER BY created_at
+        LIMIT $2
+        "#,
+        status,
+        query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_BATCH)
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(serde_json::json!({
+        "waiting": waiting,
+        "entries": entries,
+    })))
+}
+
+/// Let a batch of people in: each gets an email with a signup link (admins only)
+pub async fn approve(
+    State(state): State<AppState>,
+    AdminUser(admin): AdminUser,
+    session: Session,
+    ValidatedJson(payload): ValidatedJson<ApproveRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let expires_at = Utc::now() + chrono::Duration::days(WAITLIST_TOKEN_TTL_DAYS);
+    let approved = sqlx::query!(
+        r#"
+        UPDATE waitlist
+        SET status = 'approved', token = gen_random_uuid()::text, token_expires_at = $3,
+            approved_at = NOW(), approved_by = $4
+        WHERE id IN (
+            SELECT id FROM waitlist
+            WHERE ($1::uuid[] IS NULL AND status = 'waiting')
+               OR (id = ANY($1) AND status <> 'joined')
+            ORDER BY created_at
+            LIMIT $2
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING email, locale, token as "token!"
+        "#,
+        payload.ids.as_deref(),
+        payload
+            .count
+            .unwrap_or_else(|| payload.ids.as_ref().map_or(0, |ids| ids.len() as i64)),
+        expires_at,
+        admin.id
+    )
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    for entry in &approved {
+        let locale = Locale::from_tag(&entry.locale);
+        if let Err(e) =
+            crate::email::send_waitlist_invite(&state, &entry.email, &entry.token, locale).await
+        {
+            tracing::error!("Failed to queue waitlist invite to {}: {}", entry.email, e);
+        }
+    }
+
+    let details = format!("{} approved", approved.len());
+    crate::audit::record(
+        &state.pool,
+        &session,
+        admin.id,
+        "admin.waitlist_approved",
+        None,
+        Some(&details),
+    )
+    .await?;
+
+    Ok(Json(serde_json::json!({ "approved": approved.len() })))
+}
@@ -0,0 +1,395 @@
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use tower_sessions::Session;
+use uuid::Uuid;
+
+use crate::error::{self, ApiError};
+use crate::extractors::AuthUser;
+use crate::i18n::{self, ApiMessage, Locale};
+use crate::state::AppState;
+
+// Events a slow connection can fall behind by before it starts missing some
+const CHANNEL_CAPACITY: usize = 64;
+// Keeps idle connections from being dropped by proxies along the way
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+// A client that hasn't sent anything, pongs included, for this long is gone
+const IDLE_TIMEOUT: Duration = Duration::from_secs(75);
+// Clients repeat `typing` while the user types; relaying more often than this is noise
+const TYPING_THROTTLE: Duration = Duration::from_secs(3);
+
+/// What a connection receives. New connections start subscribed to all of them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Channel {
+    Notifications,
+    Feed,
+    /// Direct messages, their reactions and deletions
+    Messages,
+    Typing,
+}
+
+impl Channel {
+    const ALL: [Channel; 4] = [
+        Channel::Notifications,
+        Channel::Feed,
+        Channel::Messages,
+        Channel::Typing,
+    ];
+}
+
+/// Pushed to connected clients as JSON, e.g. `{"type": "notification", ...}`
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    /// A new in-app notification for this user
+    Notification {
+        id: Uuid,
+        kind: String,
+        actor_id: Option<Uuid>,
+        data: serde_json::Value,
+        created_at: chrono::DateTime<chrono::Utc>,
+    },
+    /// A direct message in one of this user's conversations, including their own
+    Message(crate::messages::Message),
+    /// A message unsent by its sender, or deleted by this user for themselves
+    MessageDeleted {
+        conversation_id: Uuid,
+        message_id: Uuid,
+    },
+    /// A reaction added to or removed from a message in one of this user's conversations
+    MessageReaction {
+        conversation_id: Uuid,
+        message_id: Uuid,
+        user_id: Uuid,
+        emoji: String,
+        reacted: bool,
+        /// Counts after the change, emoji -> count
+        reactions: serde_json::Value,
+    },
+    /// Something new in the feed; clients refetch it (GET /feed) to show it
+    FeedItem {
+        item_type: &'static str,
+        id: Uuid,
+        author_id: Uuid,
+    },
+    /// Someone is typing in one of this user's conversations
+    Typing {
+        conversation_id: Uuid,
+        user_id: Uuid,
+    },
+}
+
+impl Event {
+    fn channel(&self) -> Channel {
+        match self {
+            Event::Notification { .. } => Channel::Notifications,
+            Event::Message(_) | Event::MessageDeleted { .. } | Event::MessageReaction { .. } => {
+                Channel::Messages
+            }
+            Event::FeedItem { .. } => Channel::Feed,
+            Event::Typing { .. } => Channel::Typing,
+        }
+    }
+}
+
+/// Sent by clients, e.g. `{"type": "subscribe", "channels": ["feed"]}`
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    Subscribe {
+        channels: Vec<Channel>,
+    },
+    Unsubscribe {
+        channels: Vec<Channel>,
+    },
+    /// For browsers, which can't send WebSocket pings themselves
+    Ping,
+    /// The user is typing in a conversation; sent every few seconds while they are
+    Typing {
+        conversation_id: Uuid,
+    },
+}
+
+/// Answers to a `ClientMessage`
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Reply {
+    /// Every channel the connection is subscribed to now
+    Subscribed {
+        channels: BTreeSet<Channel>,
+    },
+    Pong,
+    /// `code` is a catalog code, or "bad_request" for messages that couldn't be read
+    Error {
+        code: &'static str,
+        message: String,
+    },
+}
+
+/// Who's connected to GET /ws on this instance, by user, plus a channel for everyone.
+/// Events only reach connections on the instance that sent them.
+#[derive(Clone)]
+pub struct Realtime(Arc<RealtimeInner>);
+
+struct RealtimeInner {
+    users: Mutex<HashMap<Uuid, broadcast::Sender<Event>>>,
+    everyone: broadcast::Sender<Event>,
+}
+
+impl Default for Realtime {
+    fn default() -> Self {
+        Self(Arc::new(RealtimeInner {
+            users: Mutex::new(HashMap::new()),
+            everyone: broadcast::channel(CHANNEL_CAPACITY).0,
+        }))
+    }
+}
+
+impl Realtime {
+    fn subscribe(&self, user_id: Uuid) -> (broadcast::Receiver<Event>, broadcast::Receiver<Event>) {
+        let mut users = self.0.users.lock().unwrap();
+        let user = users
+            .entry(user_id)
+            .or_insert_with(|| broadcast::channel(CHANNEL_CAPACITY).0)
+            .subscribe();
+        (user, self.0.everyone.subscribe())
+    }
+
+    /// Forget a user once their last connection closes
+    fn unsubscribe(&self, user_id: Uuid) {
+        let mut users = self.0.users.lock().unwrap();
+        if users
+            .get(&user_id)
+            .is_some_and(|sender| sender.receiver_count() == 0)
+        {
+            users.remove(&user_id);
+        }
+    }
+
+    /// Push to every connection `user_id` has open. Does nothing if they have none.
+    pub fn send_to(&self, user_id: Uuid, event: Event) {
+        if let Some(sender) = self.0.users.lock().unwrap().get(&user_id) {
+            let _ = sender.send(event);
+        }
+    }
+
+    /// Push to every open connection
+    pub fn send_to_all(&self, event: Event) {
+        let _ = self.0.everyone.send(event);
+    }
+}
+
+/// Upgrade to the one WebSocket a client needs: it streams `Event`s to the logged in user on
+/// the channels they're subscribed to, and takes `ClientMessage`s back.
+/// The session cookie is checked here, at the upgrade; the connection lives on after that.
+pub async fn connect(
+    State(state): State<AppState>,
+    user: AuthUser,
+    session: Session,
+    headers: HeaderMap,
+    ws: WebSocketUpgrade,
+) -> Result<impl IntoResponse, ApiError> {
+    // Browsers send cookies with cross-site WebSocket requests and CORS doesn't apply,
+    // so only our own frontend may connect
+    let origin = headers
+        .get(header::ORIGIN)
+        .and_then(|origin| origin.to_str().ok());
+    if let Some(origin) = origin {
+        if !state.config.frontend_origins.iter().any(|o| o == origin) {
+            return Err(ApiError::new(
+                StatusCode::FORBIDDEN,
+                &i18n::ORIGIN_NOT_ALLOWED,
+            ));
+        }
+    }
+
+    // Error replies are translated like responses are
+    let locale = error::request_locale(&state, &session, &headers).await;
+    Ok(ws.on_upgrade(move |socket| stream(socket, state, user.id, locale)))
+}
+
+/// One open connection
+struct Connection {
+    user_id: Uuid,
+    locale: Locale,
+    channels: BTreeSet<Channel>,
+    /// When each conversation's last `typing` was relayed
+    typed: HashMap<Uuid, Instant>,
+}
+
+impl Connection {
+    async fn handle(&mut self, state: &AppState, text: &str) -> Option<Reply> {
+        let message = match serde_json::from_str(text) {
+            Ok(message) => message,
+            Err(e) => {
+                return Some(Reply::Error {
+                    code: "bad_request",
+                    message: e.to_string(),
+                })
+            }
+        };
+
+        match message {
+            ClientMessage::Subscribe { channels } => {
+                self.channels.extend(channels);
+                Some(self.subscribed())
+            }
+            ClientMessage::Unsubscribe { channels } => {
+                self.channels.retain(|channel| !channels.contains(channel));
+                Some(self.subscribed())
+            }
+            ClientMessage::Ping => Some(Reply::Pong),
+            ClientMessage::Typing { conversation_id } => {
+                let recent = self
+                    .typed
+                    .get(&conversation_id)
+                    .is_some_and(|at| at.elapsed() < TYPING_THROTTLE);
+                if recent {
+                    return None;
+                }
+                match relay_typing(state, self.user_id, conversation_id).await {
+                    Ok(true) => {
+                        self.typed.insert(conversation_id, Instant::now());
+                        None
+                    }
+                    Ok(false) => Some(self.error(&i18n::CONVERSATION_NOT_FOUND)),
+                    Err(e) => {
+                        tracing::error!("Failed to relay typing: {}", e);
+                        None
+                    }
+                }
+            }
+        }
+    }
+
+    fn error(&self, entry: &'static ApiMessage) -> Reply {
+        Reply::Error {
+            code: entry.code,
+            message: entry.text(self.locale).to_string(),
+        }
+    }
+
+    fn subscribed(&self) -> Reply {
+        Reply::Subscribed {
+            channels: self.channels.clone(),
+        }
+    }
+}
+
+/// Tell the other members of a conversation that `user_id` is typing. Only accepted
+/// conversations count, and nobody blocked either way hears it. False if `user_id`
+/// isn't in the conversation.
+async fn relay_typing(
+    state: &AppState,
+    user_id: Uuid,
+    conversation_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let members = sqlx::query!(
+        r#"
+        SELECT them.user_id AS "user_id?",
+            COALESCE(me.status = 'accepted' AND them.status = 'accepted' AND NOT EXISTS(
+                SELECT 1 FROM blocks
+                WHERE (blocker_id = me.user_id AND blocked_id = them.user_id)
+                   OR (blocker_id = them.user_id AND blocked_id = me.user_id)
+            ), FALSE) AS "hears!"
+        FROM conversation_members me
+        LEFT JOIN conversation_members them
+          ON them.conversation_id = me.conversation_id AND them.user_id <> me.user_id
+        WHERE me.conversation_id = $1 AND me.user_id = $2
+        "#,
+        conversation_id,
+        user_id
+    )
+    .fetch_all(&state.pool)
+    .await?;
+
+    for member in &members {
+        if let (Some(member_id), true) = (member.user_id, member.hears) {
+            state.realtime.send_to(
+                member_id,
+                Event::Typing {
+                    conversation_id,
+                    user_id,
+                },
+            );
+        }
+    }
+    Ok(!members.is_empty())
+}
+
+async fn send<T: Serialize>(socket: &mut WebSocket, value: &T) -> Result<(), axum::Error> {
+    let json = serde_json::to_string(value).expect("events always serialize");
+    socket.send(Message::Text(json)).await
+}
+
+async fn stream(mut socket: WebSocket, state: AppState, user_id: Uuid, locale: Locale) {
+    let realtime = state.realtime.clone();
+    let (mut own, mut everyone) = realtime.subscribe(user_id);
+    let mut connection = Connection {
+        user_id,
+        locale,
+        channels: Channel::ALL.into_iter().collect(),
+        typed: HashMap::new(),
+    };
+    let mut ping = tokio::time::interval(PING_INTERVAL);
+    let mut last_seen = Instant::now();
+
+    loop {
+        let event = tokio::select! {
+            event = own.recv() => event,
+            event = everyone.recv() => event,
+            _ = ping.tick() => {
+                if last_seen.elapsed() > IDLE_TIMEOUT
+                    || socket.send(Message::Ping(Vec::new())).await.is_err()
+                {
+                    break;
+                }
+                continue;
+            }
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                // Pings are answered automatically; anything at all shows the client is there
+                Some(Ok(message)) => {
+                    last_seen = Instant::now();
+                    let Message::Text(text) = message else {
+                        continue;
+                    };
+                    if let Some(reply) = connection.handle(&state, &text).await {
+                        if send(&mut socket, &reply).await.is_err() {
+                            break;
+                        }
+                    }
+                    continue;
+                }
+            },
+        };
+
+        match event {
+            Ok(event) if connection.channels.contains(&event.channel()) => {
+                if send(&mut socket, &event).await.is_err() {
+                    break;
+                }
+            }
+            Ok(_) => {}
+            // Missed events can be caught up on by refetching
+            Err(broadcast::error::RecvError::Lagged(n)) => {
+                tracing::debug!("WebSocket for {} skipped {} events", user_id, n);
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+
+    drop(own);
+    realtime.unsubscribe(user_id);
+}
@@ -0,0 +1,73 @@
+mod common;
+use axum::http::StatusCode;
+use common::TestApp;
+use sqlx::PgPool;
+
+// What praxis-admin runs, minus the argument parsing
+#[sqlx::test(migrations = false)]
+async fn admin_cli_tasks_reuse_the_api_code(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let ada_id = api::user::find_id(&app.pool, "ADA").await.unwrap().unwrap();
+    assert!(api::user::find_id(&app.pool, "nobody")
+        .await
+        .unwrap()
+        .is_none());
+
+    // A role change signs them out, like PATCH /admin/users/:id/role
+    let previous = api::admin::set_role(&app.pool, &app.state.sessions, ada_id, "admin")
+        .await
+        .unwrap();
+    assert_eq!(previous, "user");
+    assert_eq!(ada.get("/user/me").await.status, StatusCode::UNAUTHORIZED);
+    let err = api::admin::set_role(&app.pool, &app.state.sessions, ada_id, "owner")
+        .await
+        .unwrap_err();
+    assert_eq!(err.status(), StatusCode::BAD_REQUEST);
+    let err = api::admin::set_role(&app.pool, &app.state.sessions, ada_id, "user")
+        .await
+        .unwrap_err();
+    assert_eq!(err.to_string(), "Cannot demote the last admin");
+
+    let usernames = api::demo::seed(&app.state, 4).await.unwrap();
+    assert_eq!(usernames.len(), 4);
+    let feed = app.client().get("/feed").await.json()["items"].clone();
+    assert_eq!(feed.as_array().unwrap().len(), 8);
+    let seeded = api::user::find_id(&app.pool, &usernames[0])
+        .await
+        .unwrap()
+        .unwrap();
+    let verified: bool = sqlx::query_scalar("SELECT verified FROM local_auths WHERE user_id = $1")
+        .bind(seeded)
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    assert!(verified);
+
+    assert_eq!(
+        api::user::purge(&app.state, seeded).await.unwrap(),
+        usernames[0]
+    );
+    assert_eq!(
+        app.client().get("/feed").await.json()["items"]
+            .as_array()
+            .unwrap()
+            .len(),
+        6
+    );
+    let err = api::user::purge(&app.state, seeded).await.unwrap_err();
+    assert_eq!(err.status(), StatusCode::NOT_FOUND);
+
+    let mut bob = app.signup("bob").await;
+    let revoked = api::session::revoke_all_sessions(&app.pool, &app.state.sessions)
+        .await
+        .unwrap();
+    assert_eq!(revoked, 1);
+    assert_eq!(bob.get("/user/me").await.status, StatusCode::UNAUTHORIZED);
+
+    for (name, _) in api::scheduler::JOBS {
+        let result = api::scheduler::run(&app.state, name).await;
+        assert!(matches!(result, Some(Ok(_))), "{}: {:?}", name, result);
+    }
+    assert!(api::scheduler::run(&app.state, "nope").await.is_none());
+}
@@ -0,0 +1,70 @@
+mod common;
+use axum::http::StatusCode;
+use common::TestApp;
+use serde_json::json;
+use sqlx::PgPool;
+
+#[sqlx::test(migrations = false)]
+async fn analytics_events_are_counted_per_day_for_admins(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut visitor = app.client();
+    let mut root = app.signup_admin("root").await;
+
+    let res = visitor
+        .post(
+            "/analytics/events",
+            json!({ "events": [{ "type": "interaction", "path": "/" }] }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let res = visitor
+        .post(
+            "/analytics/events",
+            json!({ "events": [
+                { "type": "page_view", "path": "/explore?q=secret",
+                  "referrer": "https://News.example.com/item?id=1" },
+                { "type": "page_view", "path": "/explore",
+                  "referrer": "http://localhost:3000/" },
+                { "type": "page_view", "path": "/" },
+                { "type": "interaction", "path": "/", "name": "signup_button" },
+            ] }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::ACCEPTED, "{}", res.text());
+
+    // Last week's raw events are counted, then dropped
+    sqlx::query(
+        "INSERT INTO analytics_events (kind, path, created_at) VALUES ('page_view', '/', NOW() - INTERVAL '10 days')",
+    )
+    .execute(&app.pool)
+    .await
+    .unwrap();
+    assert_eq!(api::analytics::aggregate(&app.pool).await, Ok(1));
+    assert_eq!(api::analytics::aggregate(&app.pool).await, Ok(0));
+    let stored: Vec<String> = sqlx::query_scalar("SELECT path FROM analytics_events ORDER BY id")
+        .fetch_all(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(stored, ["/explore", "/explore", "/", "/"]);
+
+    let res = visitor.get("/admin/analytics").await;
+    assert_eq!(res.status, StatusCode::UNAUTHORIZED);
+    let report = root.get("/admin/analytics").await.json();
+    let days = report["days"].as_array().unwrap();
+    assert_eq!(days.len(), 2);
+    assert_eq!(days[0]["page_views"], 1);
+    assert_eq!(days[1]["page_views"], 3);
+    assert_eq!(days[1]["interactions"], 1);
+    assert_eq!(
+        report["top_pages"],
+        json!([{ "key": "/", "count": 2 }, { "key": "/explore", "count": 2 }])
+    );
+    assert_eq!(
+        report["top_interactions"],
+        json!([{ "key": "signup_button", "count": 1 }])
+    );
+    assert_eq!(
+        report["top_referrers"],
+        json!([{ "key": "news.example.com", "count": 1 }])
+    );
+}
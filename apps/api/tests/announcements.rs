@@ -0,0 +1,42 @@
+mod common;
+use axum::http::StatusCode;
+use common::TestApp;
+use serde_json::json;
+use sqlx::PgPool;
+
+#[sqlx::test(migrations = false)]
+async fn banners_stay_out_of_the_announcement_list(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut root = app.signup_admin("root").await;
+
+    let res = root
+        .post("/announcement", json!({ "content": "Welcome!" }))
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let res = root
+        .post(
+            "/announcement",
+            json!({ "content": "Down for maintenance", "kind": "banner", "severity": "warning" }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+
+    let mut client = app.client();
+    assert_eq!(
+        client.get("/announcement/banner").await.json()["content"],
+        "Down for maintenance"
+    );
+    assert_eq!(
+        client.get("/announcement").await.json()["content"],
+        "Welcome!"
+    );
+    for path in ["/announcements", "/announcements/recent"] {
+        let announcements = client.get(path).await.json();
+        assert_eq!(announcements.as_array().unwrap().len(), 1, "{}", path);
+        assert_eq!(announcements[0]["content"], "Welcome!");
+    }
+    assert_eq!(
+        client.get("/announcements/count").await.json(),
+        json!({ "total": 1 })
+    );
+}
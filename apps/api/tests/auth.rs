@@ -0,0 +1,1442 @@
+mod common;
+
+use api::email_preferences::{unsubscribe_token, EmailCategory};
+use axum::http::{Method, StatusCode};
+use chrono::{Duration, Utc};
+use common::{TestApp, PASSWORD};
+use serde_json::json;
+use sqlx::PgPool;
+use std::str::FromStr;
+use tower_sessions::{session::Id, SessionStore};
+
+#[sqlx::test(migrations = false)]
+async fn signup_logs_in_and_emails_a_verification_link(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut client = app.client();
+
+    let res = client
+        .post(
+            "/auth/signup",
+            json!({
+                "email": "ada@example.com",
+                "password": PASSWORD,
+                "username": "Ada",
+                "display_name": "Ada Lovelace",
+            }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+
+    app.run_jobs().await;
+    let email = app.mailer.last_to("ada@example.com").unwrap();
+    assert_eq!(email.subject, "Verify your email");
+
+    let me = client.get("/user/me").await;
+    assert_eq!(me.status, StatusCode::OK);
+    assert_eq!(me.json()["username"], "ada");
+    assert_eq!(me.json()["verified"], false);
+
+    let res = client
+        .post("/auth/verify-email", json!({ "token": email.token() }))
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    assert_eq!(client.get("/user/me").await.json()["verified"], true);
+
+    // Tokens are single use
+    let res = client
+        .post("/auth/verify-email", json!({ "token": email.token() }))
+        .await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+}
+
+#[sqlx::test(migrations = false)]
+async fn signup_rejects_taken_email_and_username(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    app.signup("grace").await;
+
+    let res = app
+        .client()
+        .post(
+            "/auth/signup",
+            json!({
+                "email": "grace@example.com",
+                "password": PASSWORD,
+                "username": "someone_else",
+                "display_name": "Someone",
+            }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::CONFLICT);
+
+    let res = app
+        .client()
+        .post(
+            "/auth/signup",
+            json!({
+                "email": "other@example.com",
+                "password": PASSWORD,
+                "username": "GRACE",
+                "display_name": "Grace",
+            }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::CONFLICT);
+}
+
+#[sqlx::test(migrations = false)]
+async fn signup_reports_invalid_fields(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+
+    let res = app
+        .client()
+        .post(
+            "/auth/signup",
+            json!({
+                "email": "not-an-email",
+                "password": "short",
+                "username": "admin",
+                "display_name": "Admin",
+            }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let errors = &res.json()["errors"];
+    assert!(errors["email"].is_array());
+    assert!(errors["password"].is_array());
+    assert_eq!(errors["username"][0], "That username is reserved");
+    assert!(errors.get("display_name").is_none());
+}
+
+#[sqlx::test(migrations = false)]
+async fn text_is_normalized_and_lookalike_usernames_are_refused(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ado = app.signup("ado").await;
+
+    let signup = |username: &str, display_name: &str| {
+        json!({
+            "email": format!("{}@example.com", username.replace(['_', '.'], "x")),
+            "password": PASSWORD,
+            "username": username,
+            "display_name": display_name,
+        })
+    };
+    let res = app.client().post("/auth/signup", signup("ad0", "Ado")).await;
+    assert_eq!(res.status, StatusCode::CONFLICT);
+    assert_eq!(res.text(), "That username is too similar to an existing one");
+
+    let res = app.client().post("/auth/signup", signup("l0gin", "Login")).await;
+    assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(res.json()["errors"]["username"][0], "That username is reserved");
+    let res = app.client().post("/auth/signup", signup("_._", "Dots")).await;
+    assert_eq!(res.json()["errors"]["username"][0], "Must contain a letter or number");
+    // Nothing but invisible characters is no name at all
+    let res = app
+        .client()
+        .post("/auth/signup", signup("empty", "\u{200B}\u{202E} "))
+        .await;
+    assert_eq!(res.json()["errors"]["display_name"][0], "Required");
+
+    // Decomposed accents are stored composed, and hidden characters are dropped
+    let res = ado
+        .post(
+            "/user/profile",
+            json!({ "display_name": " Ade\u{301}le\u{200B} ", "bio": "Line one\nline two" }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    let me = ado.get("/user/me").await.json();
+    assert_eq!(me["display_name"], "Ad\u{e9}le");
+    assert_eq!(me["bio"], "Line one line two");
+
+    let mut bob = app.signup("bob").await;
+    let res = bob.post("/user/profile", json!({ "username": "ad0" })).await;
+    assert_eq!(res.status, StatusCode::CONFLICT);
+
+    let res = bob
+        .post("/posts", json!({ "content": "\u{202E}Hello\r\nworld\u{0}" }))
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let content: String = sqlx::query_scalar("SELECT content FROM posts")
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(content, "Hello\nworld");
+
+    let res = bob
+        .post("/projects", json!({ "title": "Line\nbreak", "tags": [" rust\u{FEFF}"] }))
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let (title, tags): (String, Vec<String>) = sqlx::query_as("SELECT title, tags FROM projects")
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(title, "Line break");
+    assert_eq!(tags, ["rust"]);
+}
+
+#[sqlx::test(migrations = false)]
+async fn lookalikes_follow_the_confusables_table(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    app.signup("dave").await;
+
+    let signup = json!({
+        "email": "clave@example.com",
+        "password": PASSWORD,
+        "username": "cIave",
+        "display_name": "Clave",
+    });
+    let res = app.client().post("/auth/signup", signup).await;
+    assert_eq!(res.status, StatusCode::CONFLICT);
+    assert_eq!(
+        res.text(),
+        "That username is too similar to an existing one"
+    );
+
+    // The SQL function that fills users.username_skeleton agrees with the Rust one
+    for username in ["d4ve", "cl1ve", "rnivv0", "i1l", "ada.lovelace", "clrnvv"] {
+        let skeleton: String = sqlx::query_scalar("SELECT username_skeleton($1)")
+            .bind(username)
+            .fetch_one(&app.pool)
+            .await
+            .unwrap();
+        assert_eq!(skeleton, api::validation::username_skeleton(username));
+    }
+}
+
+#[sqlx::test(migrations = false)]
+async fn google_and_github_usernames_get_the_signup_checks(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    app.signup("ada").await;
+    app.signup("dave").await;
+
+    for (wanted, username) in [
+        ("Grace.Hopper+news", "grace.hoppernews"),
+        ("ADA", "ada2"),
+        ("clave", "clave2"),
+        ("login", "login2"),
+        ("al", "al2"),
+        ("+++", "user2"),
+    ] {
+        assert_eq!(
+            api::auth::oauth_username(&app.pool, wanted).await.unwrap(),
+            username
+        );
+    }
+    let long = api::auth::oauth_username(&app.pool, &"a".repeat(60))
+        .await
+        .unwrap();
+    assert_eq!(long.len(), 36);
+}
+
+#[sqlx::test(migrations = false)]
+async fn login_and_logout(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    app.signup("linus").await;
+    let mut client = app.client();
+
+    assert_eq!(
+        client.get("/user/me").await.status,
+        StatusCode::UNAUTHORIZED
+    );
+
+    let res = client
+        .post(
+            "/auth/login",
+            json!({ "email": "linus@example.com", "password": "wrong password" }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::UNAUTHORIZED);
+
+    let res = client
+        .post(
+            "/auth/login",
+            json!({ "email": "linus@example.com", "password": PASSWORD }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    assert_eq!(res.json()["success"], true);
+    assert_eq!(client.get("/user/me").await.json()["username"], "linus");
+
+    let res = client.post("/auth/logout", json!({})).await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert_eq!(
+        client.get("/user/me").await.status,
+        StatusCode::UNAUTHORIZED
+    );
+}
+
+#[sqlx::test(migrations = false)]
+async fn password_reset_via_emailed_link(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    app.signup("barbara").await;
+    let mut client = app.client();
+
+    let res = client
+        .post(
+            "/auth/forgot-password",
+            json!({ "email": "barbara@example.com" }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::OK);
+
+    app.run_jobs().await;
+    let email = app.mailer.last_to("barbara@example.com").unwrap();
+    assert_eq!(email.subject, "Reset your password");
+    // The plaintext alternative carries the same link
+    assert!(email.text.contains(&format!("token={}", email.token())));
+    assert!(email.text.contains("expires in 1 hour."));
+
+    let res = client
+        .post(
+            "/auth/reset-password",
+            json!({ "token": email.token(), "new_password": "a brand new password" }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+
+    let res = client
+        .post(
+            "/auth/login",
+            json!({ "email": "barbara@example.com", "password": PASSWORD }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::UNAUTHORIZED);
+
+    let res = client
+        .post(
+            "/auth/login",
+            json!({ "email": "barbara@example.com", "password": "a brand new password" }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::OK);
+}
+
+#[sqlx::test(migrations = false)]
+async fn failed_emails_are_retried(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    app.signup("katherine").await;
+    let mut client = app.client();
+
+    app.mailer.fail_next(1);
+    let res = client
+        .post(
+            "/auth/forgot-password",
+            json!({ "email": "katherine@example.com" }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::OK);
+
+    app.run_jobs().await;
+    let last = app.mailer.last_to("katherine@example.com").unwrap();
+    assert_eq!(last.subject, "Verify your email");
+
+    // The retry is scheduled with a backoff; bring it forward
+    sqlx::query("UPDATE jobs SET run_at = NOW() WHERE completed_at IS NULL")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    app.run_jobs().await;
+
+    let last = app.mailer.last_to("katherine@example.com").unwrap();
+    assert_eq!(last.subject, "Reset your password");
+}
+
+#[sqlx::test(migrations = false)]
+async fn unverified_signups_get_two_reminders(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    app.signup("verified").await;
+    let mut client = app.client();
+    let res = client
+        .post(
+            "/auth/signup",
+            json!({
+                "email": "forgetful@example.com",
+                "password": PASSWORD,
+                "username": "forgetful",
+                "display_name": "forgetful",
+            }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    app.run_jobs().await;
+    sqlx::query("UPDATE users SET created_at = NOW() - INTERVAL '49 hours'")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+
+    let now = Utc::now();
+    let remind = |now| api::verification_reminders::send_due(&app.state, now);
+    assert_eq!(remind(now).await.unwrap(), 1);
+    app.run_jobs().await;
+    let reminder = app.mailer.last_to("forgetful@example.com").unwrap();
+    assert_eq!(reminder.subject, "Confirm your email to finish signing up");
+
+    // Not again until another 48 hours have passed, and never more than twice
+    assert_eq!(remind(now + Duration::hours(1)).await.unwrap(), 0);
+    assert_eq!(remind(now + Duration::hours(49)).await.unwrap(), 1);
+    assert_eq!(remind(now + Duration::hours(100)).await.unwrap(), 0);
+
+    let res = client
+        .post("/auth/verify-email", json!({ "token": reminder.token() }))
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+}
+
+#[sqlx::test(migrations = false)]
+async fn dev_mailbox_lists_captured_emails(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    app.signup("hedy").await;
+    app.signup("joan").await;
+    let mut client = app.client();
+
+    let emails = client.get("/dev/mailbox").await.json();
+    assert_eq!(emails.as_array().unwrap().len(), 2);
+    assert_eq!(emails[0]["to"], "joan@example.com");
+
+    let emails = client.get("/dev/mailbox?to=hedy@example.com").await.json();
+    let emails = emails.as_array().unwrap();
+    assert_eq!(emails.len(), 1);
+    assert_eq!(emails[0]["subject"], "Verify your email");
+    assert!(emails[0]["text"].as_str().unwrap().contains("token="));
+}
+
+#[sqlx::test(migrations = false)]
+async fn admins_can_see_and_resend_a_users_emails(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut user = app.signup("margaret").await;
+    let user_id = user.get("/user/me").await.json()["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let mut admin = app.signup_admin("root").await;
+
+    let path = format!("/admin/users/{}/emails", user_id);
+    assert_eq!(user.get(&path).await.status, StatusCode::FORBIDDEN);
+
+    let emails = admin.get(&path).await.json();
+    let emails = emails.as_array().unwrap();
+    assert_eq!(emails.len(), 1);
+    assert_eq!(emails[0]["kind"], "verify_email");
+    assert_eq!(emails[0]["status"], "sent");
+    assert_eq!(emails[0]["provider"], "memory");
+    assert_eq!(emails[0]["attempts"], 1);
+    // Bodies hold credentials, so they're never listed
+    assert!(emails[0].get("message").is_none());
+
+    let original = app.mailer.last_to("margaret@example.com").unwrap();
+    let log_id = emails[0]["id"].as_str().unwrap();
+    let res = admin
+        .post(&format!("/admin/emails/{}/resend", log_id), json!({}))
+        .await;
+    assert_eq!(res.status, StatusCode::ACCEPTED, "{}", res.text());
+    app.run_jobs().await;
+
+    let resent = app.mailer.last_to("margaret@example.com").unwrap();
+    assert_eq!(resent.subject, original.subject);
+    assert_eq!(resent.token(), original.token());
+
+    let emails = admin.get(&path).await.json();
+    assert_eq!(emails.as_array().unwrap().len(), 2);
+    assert_eq!(emails[0]["resent_from"], log_id);
+    assert_eq!(emails[0]["status"], "sent");
+
+    let res = admin
+        .post(
+            &format!("/admin/emails/{}/resend", uuid::Uuid::new_v4()),
+            json!({}),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test(migrations = false)]
+async fn email_preferences_and_unsubscribe_links(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut user = app.signup("grace").await;
+    let user_id = user.get("/user/me").await.json()["id"]
+        .as_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+
+    let prefs = user.get("/user/email-preferences").await.json();
+    assert_eq!(
+        prefs,
+        json!({
+            "security": true,
+            "product_updates": true,
+            "activity": true,
+            "weekly_digest": false,
+            "timezone": "UTC",
+            "quiet_hours_start": 0,
+            "quiet_hours_end": 0
+        })
+    );
+
+    let res = user
+        .patch(
+            "/user/email-preferences",
+            json!({ "product_updates": false }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    assert_eq!(res.json()["product_updates"], false);
+    assert_eq!(res.json()["activity"], true);
+
+    // One-click unsubscribe works without logging in
+    let token = unsubscribe_token(&app.state.config, user_id, EmailCategory::Activity);
+    let mut client = app.client();
+    let res = client
+        .post(&format!("/email/unsubscribe?token={}", token), json!({}))
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    assert_eq!(res.json()["category"], "activity");
+
+    let prefs = user.get("/user/email-preferences").await.json();
+    assert_eq!(prefs["activity"], false);
+    assert_eq!(prefs["security"], true);
+
+    // The signature covers the category, so a token can't be repurposed
+    let forged = token.replacen("activity", "security", 1);
+    let res = client
+        .get(&format!("/email/unsubscribe?token={}", forged))
+        .await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+}
+
+#[sqlx::test(migrations = false)]
+async fn emails_are_sent_in_the_users_language(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut client = app.client();
+
+    let res = client
+        .post(
+            "/auth/signup",
+            json!({
+                "email": "sofia@example.com",
+                "password": PASSWORD,
+                "username": "sofia",
+                "display_name": "Sofia",
+                "locale": "es-MX",
+            }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    app.run_jobs().await;
+    let email = app.mailer.last_to("sofia@example.com").unwrap();
+    assert_eq!(email.subject, "Verifica tu correo electrónico");
+    assert!(email.html.contains(r#"<html lang="es">"#));
+
+    let res = client
+        .post("/user/profile", json!({ "locale": "fr" }))
+        .await;
+    assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let res = client
+        .post("/user/profile", json!({ "locale": "de" }))
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    assert_eq!(client.get("/user/me").await.json()["locale"], "de");
+
+    client
+        .post(
+            "/auth/forgot-password",
+            json!({ "email": "sofia@example.com" }),
+        )
+        .await;
+    app.run_jobs().await;
+    let email = app.mailer.last_to("sofia@example.com").unwrap();
+    assert_eq!(email.subject, "Setze dein Passwort zurück");
+    assert!(email.text.contains("Dieser Link ist 1 Stunde gültig."));
+}
+
+#[sqlx::test(migrations = false)]
+async fn json_clients_get_error_codes_in_their_language(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut visitor = app.client();
+    let json_in = |language| {
+        [
+            ("accept", "application/json"),
+            ("accept-language", language),
+        ]
+    };
+
+    // Other clients get plain text, as before
+    let res = visitor.get("/user/me").await;
+    assert_eq!(res.text(), "Not logged in");
+
+    let res = visitor
+        .get_with_headers("/user/me", &json_in("fr-CH, fr;q=0.9, es;q=0.8"))
+        .await;
+    assert_eq!(res.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(res.header("content-language"), Some("es"));
+    assert_eq!(
+        res.json(),
+        json!({ "code": "not_logged_in", "message": "No has iniciado sesión" })
+    );
+
+    // Messages that aren't in the catalog keep their text, with a code from the status
+    let res = visitor
+        .get_with_headers("/leaderboard?period=year", &json_in("de"))
+        .await;
+    assert_eq!(
+        res.json(),
+        json!({ "code": "bad_request", "message": "period must be week, month or all" })
+    );
+
+    let res = visitor
+        .post_with_headers(
+            "/auth/signup",
+            json!({
+                "email": "nope",
+                "password": PASSWORD,
+                "username": "sofia",
+                "display_name": "Sofia",
+            }),
+            &json_in("de"),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let body = res.json();
+    assert_eq!(body["code"], "validation_failed");
+    assert_eq!(body["message"], "Einige Felder sind ungültig");
+    assert!(body["errors"]["email"].is_array());
+
+    // Logged in, the stored locale wins over the header
+    let mut ada = app.signup("ada").await;
+    let res = ada.post("/user/profile", json!({ "locale": "de" })).await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    let res = ada
+        .get_with_headers("/user/profile/nobody", &json_in("es"))
+        .await;
+    assert_eq!(
+        res.json(),
+        json!({ "code": "user_not_found", "message": "Benutzer nicht gefunden" })
+    );
+    // Messages with values in them are translated by code, then filled in
+    let res = ada
+        .post_with_headers("/user/me/invites", json!({ "max_uses": 11 }), &json_in("es"))
+        .await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        res.json(),
+        json!({
+            "code": "invite_max_uses",
+            "message": "Eine Einladung kann höchstens 10-mal verwendet werden",
+        })
+    );
+
+    // Status messages too, still for the user logging out
+    let res = ada
+        .post_with_headers("/auth/logout", json!({}), &json_in("es"))
+        .await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert_eq!(
+        res.json(),
+        json!({ "code": "logged_out", "message": "Erfolgreich abgemeldet" })
+    );
+
+    // JSON status bodies keep their other fields
+    let res = ada
+        .post_with_headers(
+            "/auth/login",
+            json!({ "email": "ada@example.com", "password": PASSWORD }),
+            &json_in("es"),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    assert_eq!(
+        res.json(),
+        json!({ "code": "logged_in", "message": "Erfolgreich angemeldet", "success": true })
+    );
+
+    let res = visitor
+        .get_with_headers("/feed?before=nope", &json_in("es"))
+        .await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        res.json(),
+        json!({ "code": "invalid_cursor", "message": "Cursor no válido" })
+    );
+}
+
+#[sqlx::test(migrations = false)]
+async fn sessions_show_the_device_they_are_on(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+
+    let mut phone = app.client();
+    let iphone = "Mozilla/5.0 (iPhone; CPU iPhone OS 17_2 like Mac OS X) AppleWebKit/605.1.15 \
+                  (KHTML, like Gecko) Version/17.2 Mobile/15E148 Safari/604.1";
+    let res = phone
+        .post_with_headers(
+            "/auth/login",
+            json!({ "email": "ada@example.com", "password": PASSWORD }),
+            &[("user-agent", iphone)],
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+
+    let sessions = ada.get("/auth/sessions").await.json();
+    let sessions = sessions.as_array().unwrap();
+    assert_eq!(sessions.len(), 2);
+    let on_phone = sessions.iter().find(|s| s["is_current"] == false).unwrap();
+    assert_eq!(on_phone["user_agent"], iphone);
+    assert_eq!(on_phone["browser"], "Safari 17");
+    assert_eq!(on_phone["os"], "iOS");
+    assert_eq!(on_phone["device"], "mobile");
+    // Private addresses have no location to look up
+    assert_eq!(on_phone["ip_address"], "127.0.0.1");
+    assert_eq!(on_phone["city"], serde_json::Value::Null);
+    // No user agent, nothing to show
+    let here = sessions.iter().find(|s| s["is_current"] == true).unwrap();
+    assert_eq!(here["device"], serde_json::Value::Null);
+
+    // Rows tracked before parsing existed are parsed when listed
+    sqlx::query(
+        "UPDATE active_sessions SET browser = NULL, os = NULL, device = NULL, \
+         user_agent = 'Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 \
+         (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36' WHERE user_agent IS NULL",
+    )
+    .execute(&app.pool)
+    .await
+    .unwrap();
+    let sessions = ada.get("/auth/sessions").await.json();
+    let here = sessions
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|s| s["is_current"] == true)
+        .unwrap();
+    assert_eq!(here["browser"], "Chrome 120");
+    assert_eq!(here["os"], "Windows 10");
+    assert_eq!(here["device"], "desktop");
+}
+
+#[sqlx::test(migrations = false)]
+async fn signing_out_other_sessions_emails_the_account(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let login = json!({ "email": "ada@example.com", "password": PASSWORD });
+    let firefox = "Mozilla/5.0 (X11; Linux x86_64; rv:121.0) Gecko/20100101 Firefox/121.0";
+
+    let mut laptop = app.client();
+    let res = laptop
+        .post_with_headers("/auth/login", login.clone(), &[("user-agent", firefox)])
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    let sessions = ada.get("/auth/sessions").await.json();
+    let laptop_id = sessions
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|s| s["is_current"] == false)
+        .unwrap()["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let res = ada.delete(&format!("/auth/sessions/{}", laptop_id)).await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    assert_eq!(
+        laptop.get("/user/me").await.status,
+        StatusCode::UNAUTHORIZED
+    );
+    app.run_jobs().await;
+    let email = app.mailer.last_to("ada@example.com").unwrap();
+    assert_eq!(
+        email.subject,
+        "A device was signed out of your Praxis account"
+    );
+    assert!(
+        email.text.contains("- Firefox 121, Linux (127.0.0.1)"),
+        "{}",
+        email.text
+    );
+
+    // Everything else at once: one email listing both
+    for user_agent in ["Mozilla/5.0 (compatible)", firefox] {
+        let res = app
+            .client()
+            .post_with_headers("/auth/login", login.clone(), &[("user-agent", user_agent)])
+            .await;
+        assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    }
+    let res = ada.delete("/auth/sessions").await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    assert_eq!(
+        ada.get("/auth/sessions")
+            .await
+            .json()
+            .as_array()
+            .unwrap()
+            .len(),
+        1
+    );
+    app.run_jobs().await;
+    let email = app.mailer.last_to("ada@example.com").unwrap();
+    assert_eq!(
+        email.subject,
+        "2 devices were signed out of your Praxis account"
+    );
+    assert!(
+        email.text.contains("- Unknown device (127.0.0.1)"),
+        "{}",
+        email.text
+    );
+    assert!(
+        email.text.contains("- Firefox 121, Linux (127.0.0.1)"),
+        "{}",
+        email.text
+    );
+
+    // Signing yourself out isn't news
+    let sessions = ada.get("/auth/sessions").await.json();
+    let here = sessions[0]["id"].as_str().unwrap().to_string();
+    let sent = app.mailer.last_to("ada@example.com").unwrap().subject;
+    let res = ada.delete(&format!("/auth/sessions/{}", here)).await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    app.run_jobs().await;
+    assert_eq!(app.mailer.last_to("ada@example.com").unwrap().subject, sent);
+}
+
+#[sqlx::test(migrations = false)]
+async fn admins_can_export_the_user_list_as_csv(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut user = app.signup("margaret").await;
+    let res = user
+        .post(
+            "/user/profile",
+            json!({ "display_name": "=HYPERLINK(\"x\"), Esq." }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+
+    let mut admin = app.signup_admin("root").await;
+
+    assert_eq!(
+        user.get("/admin/users?format=csv").await.status,
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        admin.get("/admin/users?format=xml").await.status,
+        StatusCode::BAD_REQUEST
+    );
+
+    // Every matching user, not just a page, with formulas defused
+    let res = admin.get("/admin/users?format=csv&per_page=1").await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    assert_eq!(res.header("content-type"), Some("text/csv; charset=utf-8"));
+    assert!(res.header("etag").is_none());
+    let csv = res.text();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(
+        lines[0],
+        "id,username,display_name,role,email,verified,signup_method,banned_at,suspended_until,created_at"
+    );
+    assert_eq!(lines.len(), 3);
+    assert!(lines[1].contains(",root,root,admin,root@example.com,true,local,"));
+    assert!(lines[2].contains(",margaret,\"'=HYPERLINK(\"\"x\"\"), Esq.\",user,"));
+
+    let res = admin.get("/admin/users?format=csv&q=marg").await;
+    assert_eq!(res.text().lines().count(), 2);
+}
+
+#[sqlx::test(migrations = false)]
+async fn admins_can_download_the_whole_audit_log(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut admin = app.signup_admin("root").await;
+    // Enough to take several chunks
+    sqlx::query(
+        r#"
+        INSERT INTO audit_logs (action, details)
+        SELECT 'admin.test', 'entry ' || n || repeat('.', 100) FROM generate_series(1, 500) n
+        "#,
+    )
+    .execute(&app.pool)
+    .await
+    .unwrap();
+
+    let res = admin.get("/admin/audit-log?action=admin.test&format=csv").await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    let csv = res.text();
+    assert!(csv.starts_with("id,action,details,actor_user_id,"));
+    assert_eq!(csv.lines().count(), 501);
+
+    let res = admin.get("/admin/audit-log?action=admin.test&format=json").await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    assert_eq!(res.header("content-type"), Some("application/json"));
+    assert!(res.header("content-disposition").unwrap().contains("audit-log.json"));
+    let entries = res.json();
+    assert_eq!(entries.as_array().unwrap().len(), 500);
+    assert_eq!(entries[0]["action"], "admin.test");
+
+    // Nothing matching is still a valid file
+    let res = admin.get("/admin/audit-log?action=nope&format=json").await;
+    assert_eq!(res.json(), json!([]));
+    assert_eq!(
+        admin.get("/admin/audit-log?format=xml").await.status,
+        StatusCode::BAD_REQUEST
+    );
+}
+
+#[sqlx::test(migrations = false)]
+async fn only_admins_see_every_user_and_the_directory_is_public(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut user = app.signup("margaret").await;
+    app.signup("ada").await;
+    let mut admin = app.signup_admin("root").await;
+
+    assert_eq!(
+        app.client().get("/user/all").await.status,
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(user.get("/user/all").await.status, StatusCode::FORBIDDEN);
+    let page = admin.get("/user/all?limit=2").await.json();
+    assert_eq!(page["users"][0]["username"], "ada");
+    assert_eq!(page["users"][0]["email"], "ada@example.com");
+    assert_eq!(page["users"].as_array().unwrap().len(), 2);
+    assert_eq!(page["next_cursor"], "margaret");
+    let page = admin.get("/user/all?limit=2&cursor=margaret").await.json();
+    assert_eq!(page["users"][0]["username"], "root");
+    assert_eq!(page["next_cursor"], json!(null));
+
+    // Public fields only, and no banned members
+    sqlx::query("UPDATE users SET banned_at = NOW() WHERE username = 'margaret'")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    let page = app.client().get("/user/directory?limit=1").await.json();
+    assert_eq!(page["users"][0]["username"], "ada");
+    assert!(page["users"][0].get("email").is_none());
+    assert!(page["users"][0].get("id").is_none());
+    assert_eq!(page["next_cursor"], "ada");
+    let page = app.client().get("/user/directory?cursor=ada").await.json();
+    let usernames: Vec<&str> = page["users"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|u| u["username"].as_str().unwrap())
+        .collect();
+    assert_eq!(usernames, ["root"]);
+}
+
+#[sqlx::test(migrations = false)]
+async fn admins_can_see_how_busy_the_connection_pool_is(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut user = app.signup("margaret").await;
+    let mut admin = app.signup_admin("root").await;
+
+    assert_eq!(user.get("/admin/db/pool").await.status, StatusCode::FORBIDDEN);
+    let res = admin.get("/admin/db/pool").await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    let stats = res.json();
+    assert_eq!(
+        stats["max_connections"],
+        app.pool.options().get_max_connections()
+    );
+    assert!(stats["open"].as_u64().unwrap() >= 1);
+    assert!(stats["samples"].as_u64().unwrap() >= 1);
+    assert!(stats["acquire_ms_p50"].is_number());
+    assert_eq!(stats["acquire_timeouts"], 0);
+}
+
+#[sqlx::test(migrations = false)]
+async fn admins_can_list_and_revoke_a_users_sessions(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut user = app.signup("margaret").await;
+    let user_id = user.get("/user/me").await.json()["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let mut admin = app.signup_admin("root").await;
+
+    let path = format!("/admin/users/{}/sessions", user_id);
+    assert_eq!(user.get(&path).await.status, StatusCode::FORBIDDEN);
+    assert_eq!(user.delete(&path).await.status, StatusCode::FORBIDDEN);
+
+    let sessions = admin.get(&path).await.json();
+    let sessions = sessions.as_array().unwrap();
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0]["user_id"], user_id.as_str());
+
+    let res = admin.delete(&path).await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    assert_eq!(res.json()["revoked_sessions"], 1);
+    assert_eq!(user.get("/user/me").await.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(admin.get(&path).await.json(), json!([]));
+
+    let log = admin
+        .get("/admin/audit-log?action=admin.sessions_revoked")
+        .await
+        .json();
+    assert_eq!(log["entries"][0]["target_user_id"], user_id.as_str());
+
+    let missing = format!("/admin/users/{}/sessions", uuid::Uuid::new_v4());
+    assert_eq!(admin.delete(&missing).await.status, StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test(migrations = false)]
+async fn signup_login_and_password_changes_rotate_the_session_id(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+
+    // Logging in again from an existing session moves it to a new ID
+    let mut ada = app.signup("ada").await;
+    let before = ada.cookie().unwrap().to_string();
+    let res = ada
+        .post(
+            "/auth/login",
+            json!({ "email": "ada@example.com", "password": PASSWORD }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    let logged_in = ada.cookie().unwrap().to_string();
+    assert_ne!(logged_in, before);
+
+    let res = ada
+        .post(
+            "/auth/change-password",
+            json!({ "current_password": PASSWORD, "new_password": "another horse battery" }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    assert_ne!(ada.cookie().unwrap(), logged_in);
+
+    // The old ID no longer works, and the session list follows the new one
+    let res = app
+        .client()
+        .get_with_headers("/user/me", &[("cookie", &logged_in)])
+        .await;
+    assert_eq!(res.status, StatusCode::UNAUTHORIZED);
+    let sessions = ada.get("/auth/sessions").await.json();
+    let sessions = sessions.as_array().unwrap();
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0]["is_current"], true);
+
+    // So does signing up from an existing session
+    let before = ada.cookie().unwrap().to_string();
+    let res = ada
+        .post(
+            "/auth/signup",
+            json!({
+                "email": "bea@example.com",
+                "password": PASSWORD,
+                "username": "bea",
+                "display_name": "Bea",
+            }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    assert_eq!(ada.get("/user/me").await.json()["username"], "bea");
+    let res = app
+        .client()
+        .get_with_headers("/user/me", &[("cookie", &before)])
+        .await;
+    assert_eq!(res.status, StatusCode::UNAUTHORIZED);
+}
+
+#[sqlx::test(migrations = false)]
+async fn active_sessions_follow_the_session_store(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let stale = Utc::now() - Duration::hours(1);
+    let set_last_active = |at: chrono::DateTime<Utc>| {
+        sqlx::query("UPDATE active_sessions SET last_active_at = $1")
+            .bind(at)
+            .execute(&app.pool)
+    };
+    let last_active = || {
+        sqlx::query_scalar::<_, chrono::DateTime<Utc>>("SELECT last_active_at FROM active_sessions")
+            .fetch_one(&app.pool)
+    };
+
+    // Using the session refreshes it, but only every few minutes
+    set_last_active(stale).await.unwrap();
+    assert_eq!(ada.get("/user/me").await.status, StatusCode::OK);
+    assert!(last_active().await.unwrap() < Utc::now() - Duration::minutes(30));
+    rewind_tracking(&app).await;
+    assert_eq!(ada.get("/user/me").await.status, StatusCode::OK);
+    assert!(last_active().await.unwrap() > Utc::now() - Duration::minutes(1));
+
+    // A session whose row has gone since it was last refreshed was revoked
+    sqlx::query("DELETE FROM active_sessions")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    let id = rewind_tracking(&app).await;
+    assert_eq!(ada.get("/user/me").await.status, StatusCode::UNAUTHORIZED);
+    assert!(app.state.sessions.load(&id).await.unwrap().is_none());
+
+    // One that was never tracked, e.g. because that failed at login, is tracked now
+    let mut ada = app.client();
+    let res = ada
+        .post(
+            "/auth/login",
+            json!({ "email": "ada@example.com", "password": PASSWORD }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    sqlx::query("DELETE FROM active_sessions")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(ada.get("/user/me").await.status, StatusCode::OK);
+    assert_eq!(
+        ada.get("/auth/sessions")
+            .await
+            .json()
+            .as_array()
+            .unwrap()
+            .len(),
+        1
+    );
+}
+
+#[sqlx::test(migrations = false)]
+async fn duplicate_accounts_merge_from_an_emailed_link(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let mut old = app.signup("oldada").await;
+    old.post("/posts", json!({ "content": "From my old account" }))
+        .await;
+    old.post("/projects", json!({ "title": "Engine" })).await;
+    ada.post("/projects", json!({ "title": "Engine" })).await;
+
+    // Unknown addresses get the same answer, and no email
+    let res = ada
+        .post("/auth/merge", json!({ "email": "nobody@example.com" }))
+        .await;
+    assert_eq!(res.status, StatusCode::ACCEPTED);
+    app.run_jobs().await;
+    assert!(app.mailer.last_to("nobody@example.com").is_none());
+
+    let res = ada
+        .post("/auth/merge", json!({ "email": "oldada@example.com" }))
+        .await;
+    assert_eq!(res.status, StatusCode::ACCEPTED);
+    app.run_jobs().await;
+    let email = app.mailer.last_to("oldada@example.com").unwrap();
+    assert!(email.text.contains("@ada"), "{}", email.text);
+    let token = email.token();
+
+    // Only the account that asked can confirm
+    let res = old
+        .post("/auth/merge/confirm", json!({ "token": token }))
+        .await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN);
+    let res = ada
+        .post("/auth/merge/confirm", json!({ "token": token }))
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+
+    let feed = app.client().get("/feed").await.json()["items"].clone();
+    let authors: Vec<&str> = feed
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["author_username"].as_str().unwrap())
+        .collect();
+    assert_eq!(authors, ["ada", "ada", "ada"]);
+    let res = app.client().get("/projects/user/ada/engine").await;
+    assert_eq!(res.status, StatusCode::OK);
+    let slugs: Vec<String> = sqlx::query_scalar("SELECT slug FROM projects ORDER BY slug")
+        .fetch_all(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(slugs.len(), 2);
+    assert!(slugs[1].starts_with("engine-"), "{:?}", slugs);
+
+    // The old account is gone, along with its sessions
+    let res = app.client().get("/user/profile/oldada").await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+    assert_eq!(old.get("/user/me").await.status, StatusCode::UNAUTHORIZED);
+    let res = ada
+        .post("/auth/merge/confirm", json!({ "token": token }))
+        .await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+}
+
+#[sqlx::test(migrations = false)]
+async fn confirming_an_oauth_link_secures_an_unverified_account(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut client = app.client();
+    client
+        .post(
+            "/auth/signup",
+            json!({
+                "email": "grace@example.com",
+                "password": PASSWORD,
+                "username": "grace",
+                "display_name": "Grace",
+            }),
+        )
+        .await;
+    // What a Google sign in with the same, unverified, email leaves behind
+    sqlx::query(
+        r#"
+        INSERT INTO account_merges (user_id, provider, provider_id, provider_email, token, expires_at)
+        SELECT id, 'google', 'g-123', 'grace@example.com', 'link-token', NOW() + INTERVAL '1 hour'
+        FROM users WHERE username = 'grace'
+        "#,
+    )
+    .execute(&app.pool)
+    .await
+    .unwrap();
+
+    let res = app
+        .client()
+        .post("/auth/merge/confirm", json!({ "token": "link-token" }))
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+
+    let linked: String = sqlx::query_scalar(
+        "SELECT u.username FROM oauth_connections o JOIN users u ON u.id = o.user_id WHERE o.provider_id = 'g-123'",
+    )
+    .fetch_one(&app.pool)
+    .await
+    .unwrap();
+    assert_eq!(linked, "grace");
+    let verified: bool =
+        sqlx::query_scalar("SELECT verified FROM local_auths WHERE email = 'grace@example.com'")
+            .fetch_one(&app.pool)
+            .await
+            .unwrap();
+    assert!(verified);
+
+    // Whoever set the password never proved they own the email, so it no longer works
+    assert_eq!(client.get("/user/me").await.status, StatusCode::UNAUTHORIZED);
+    let res = app
+        .client()
+        .post(
+            "/auth/login",
+            json!({ "email": "grace@example.com", "password": PASSWORD }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::UNAUTHORIZED);
+}
+
+#[sqlx::test(migrations = false)]
+async fn profile_changes_can_be_reverted_by_the_user_or_a_moderator(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let res = ada
+        .post("/user/profile", json!({ "display_name": "Ada L", "bio": "Maths" }))
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    // Nothing a revision keeps changed, so nothing is recorded
+    ada.post("/user/profile", json!({ "locale": "de" })).await;
+    let res = ada
+        .post("/user/profile", json!({ "display_name": "Rude name" }))
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+
+    let history = ada.get("/user/me/profile/history").await.json();
+    assert_eq!(history.as_array().unwrap().len(), 2);
+    assert_eq!(history[0]["display_name"], "Ada L");
+    assert_eq!(history[1]["display_name"], "ada");
+    assert!(history[1]["bio"].is_null());
+
+    // Bob can't see or revert Ada's history
+    let mut bob = app.signup("bob").await;
+    let ada_id = ada.get("/user/me").await.json()["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let path = format!("/admin/users/{}/profile/history", ada_id);
+    assert_eq!(bob.get(&path).await.status, StatusCode::FORBIDDEN);
+    let revert = format!("/user/me/profile/history/{}/revert", history[1]["id"].as_str().unwrap());
+    assert_eq!(bob.post(&revert, json!({})).await.status, StatusCode::NOT_FOUND);
+
+    sqlx::query("UPDATE users SET role = 'moderator' WHERE username = 'bob'")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    let mut bob = app.client();
+    bob.post(
+        "/auth/login",
+        json!({ "email": "bob@example.com", "password": PASSWORD }),
+    )
+    .await;
+    let res = bob
+        .post(
+            &format!("{}/{}/revert", path, history[0]["id"].as_str().unwrap()),
+            json!({}),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    let profile = app.client().get("/user/profile/ada").await.json();
+    assert_eq!(profile["display_name"], "Ada L");
+
+    // The revert is itself a revision, made by the moderator
+    let history = bob.get(&path).await.json();
+    assert_eq!(history[0]["display_name"], "Rude name");
+    assert_eq!(history[0]["changed_by"], bob.get("/user/me").await.json()["id"]);
+
+    let res = ada.post(&revert, json!({})).await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    let me = ada.get("/user/me").await.json();
+    assert_eq!(me["display_name"], "ada");
+    assert!(me["bio"].is_null());
+    assert_eq!(me["locale"], "de");
+}
+
+/// Make the only logged in session look like it was last tracked a while ago
+async fn rewind_tracking(app: &TestApp) -> Id {
+    let session_id: String = sqlx::query_scalar("SELECT id FROM tower_sessions.session")
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    let id = Id::from_str(&session_id).unwrap();
+    let mut record = app.state.sessions.load(&id).await.unwrap().unwrap();
+    record.data.insert(
+        "tracked_at".to_string(),
+        json!(Utc::now() - Duration::minutes(10)),
+    );
+    app.state.sessions.save(&record).await.unwrap();
+    id
+}
+
+#[sqlx::test(migrations = false)]
+async fn the_web_app_passes_the_cors_preflight_for_every_method(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut client = app.client();
+    for method in [
+        Method::GET,
+        Method::POST,
+        Method::PUT,
+        Method::PATCH,
+        Method::DELETE,
+    ] {
+        let res = client
+            .preflight(
+                "/admin/users/x/role",
+                "http://localhost:3000",
+                method.clone(),
+            )
+            .await;
+        assert_eq!(res.status, StatusCode::OK, "{}", method);
+        assert_eq!(
+            res.header("access-control-allow-origin"),
+            Some("http://localhost:3000")
+        );
+        assert_eq!(res.header("access-control-allow-credentials"), Some("true"));
+        let allowed = res.header("access-control-allow-methods").unwrap();
+        assert!(allowed.contains(method.as_str()), "{}", allowed);
+    }
+
+    let res = client
+        .preflight("/admin/users/x/role", "https://evil.example", Method::PATCH)
+        .await;
+    assert_eq!(res.header("access-control-allow-origin"), None);
+}
+
+#[sqlx::test(migrations = false)]
+async fn forwarded_addresses_are_ignored_unless_from_a_trusted_proxy(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut client = app.client();
+    // Cheap to answer, so the bucket can't refill while the test runs
+    let verify = json!({ "token": "not a token" });
+
+    // A new X-Forwarded-For each time doesn't get the client a new bucket
+    for n in 0..10 {
+        let forwarded = format!("203.0.113.{}", n);
+        let res = client
+            .post_with_headers(
+                "/auth/verify-email",
+                verify.clone(),
+                &[("x-forwarded-for", &forwarded)],
+            )
+            .await;
+        assert_eq!(res.status, StatusCode::BAD_REQUEST);
+    }
+    let res = client
+        .post_with_headers(
+            "/auth/verify-email",
+            verify,
+            &[("x-forwarded-for", "203.0.113.99")],
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[sqlx::test(migrations = false)]
+async fn behind_a_trusted_proxy_the_last_hop_it_added_is_the_client(pool: PgPool) {
+    let app = TestApp::behind_proxy(pool).await;
+    app.signup("ada").await;
+    let mut client = app.client();
+    let verify = json!({ "token": "not a token" });
+
+    // Whatever the client puts in front of the proxy's entry is ignored
+    for n in 0..10 {
+        let forwarded = format!("198.51.100.{}, 10.1.0.7", n);
+        let res = client
+            .post_with_headers(
+                "/auth/verify-email",
+                verify.clone(),
+                &[("x-forwarded-for", &forwarded)],
+            )
+            .await;
+        assert_eq!(res.status, StatusCode::BAD_REQUEST);
+    }
+    let res = client
+        .post_with_headers(
+            "/auth/verify-email",
+            verify,
+            &[("x-forwarded-for", "10.1.0.7")],
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::TOO_MANY_REQUESTS);
+
+    // Another client behind the same proxy has its own bucket, and its own address
+    let mut other = app.client();
+    let res = other
+        .post_with_headers(
+            "/auth/login",
+            json!({ "email": "ada@example.com", "password": PASSWORD }),
+            &[("x-forwarded-for", "198.51.100.1, 10.1.0.8")],
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    let sessions = other.get("/auth/sessions").await.json();
+    let current = sessions
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|s| s["is_current"] == true)
+        .unwrap();
+    assert_eq!(current["ip_address"], "10.1.0.8");
+}
+
+#[sqlx::test(migrations = false)]
+async fn the_last_admin_cannot_be_merged_away(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let mut root = app.signup_admin("root").await;
+
+    let res = ada
+        .post("/auth/merge", json!({ "email": "root@example.com" }))
+        .await;
+    assert_eq!(res.status, StatusCode::ACCEPTED);
+    app.run_jobs().await;
+    let token = app.mailer.last_to("root@example.com").unwrap().token();
+
+    let res = ada
+        .post("/auth/merge/confirm", json!({ "token": token }))
+        .await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+    assert_eq!(res.text(), "Cannot merge away the last admin");
+    // Nothing happened: the admin is still signed in, and the link still works
+    assert_eq!(root.get("/user/me").await.status, StatusCode::OK);
+
+    sqlx::query("UPDATE users SET role = 'admin' WHERE username = 'ada'")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    let res = ada
+        .post("/auth/merge/confirm", json!({ "token": token }))
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    assert_eq!(root.get("/user/me").await.status, StatusCode::UNAUTHORIZED);
+}
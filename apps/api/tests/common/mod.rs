@@ -0,0 +1,658 @@
+// Shared by every integration test binary; not all of them use every helper
+#![allow(dead_code)]
+
+use api::{
+    cache::NoCache,
+    client_ip::TrustedProxies,
+    email::{EmailSender, Message},
+    r2::ObjectStore,
+    search_index::{IndexHits, IndexQuery, Indexer, SearchDocument, SearchIndex},
+    session_store::SessionBackend,
+    state::{AppState, AppStateInner, Config},
+};
+use async_trait::async_trait;
+use axum::{
+    body::Body,
+    extract::connect_info::MockConnectInfo,
+    http::{header, HeaderMap, Method, Request, StatusCode},
+    Router,
+};
+use futures_util::StreamExt;
+use ipnet::IpNet;
+use serde_json::Value;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio_tungstenite::tungstenite;
+use tower::ServiceExt;
+use tower_sessions_sqlx_store::PostgresStore;
+
+pub const PASSWORD: &str = "correct horse battery";
+
+/// In-memory stand-in for R2
+#[derive(Default)]
+pub struct MemoryStore {
+    objects: Mutex<HashMap<String, Vec<u8>>>,
+}
+
+impl MemoryStore {
+    /// What a client does with a presigned upload URL
+    pub fn put(&self, key: &str, data: Vec<u8>) {
+        self.objects.lock().unwrap().insert(key.to_string(), data);
+    }
+
+    pub fn keys(&self) -> Vec<String> {
+        let mut keys: Vec<String> = self.objects.lock().unwrap().keys().cloned().collect();
+        keys.sort();
+        keys
+    }
+}
+
+#[async_trait]
+impl ObjectStore for MemoryStore {
+    fn public_url(&self, key: &str) -> String {
+        format!("https://cdn.test/{}", key)
+    }
+
+    async fn upload(
+        &self,
+        key: &str,
+        data: Vec<u8>,
+        _content_type: &str,
+    ) -> Result<String, String> {
+        self.objects.lock().unwrap().insert(key.to_string(), data);
+        Ok(self.public_url(key))
+    }
+
+    async fn presign_put(
+        &self,
+        key: &str,
+        _content_type: &str,
+        _content_length: i64,
+        _expires_in: Duration,
+    ) -> Result<(String, String), String> {
+        Ok((
+            format!("https://upload.test/{}?signed", key),
+            self.public_url(key),
+        ))
+    }
+
+    async fn presign_get(&self, key: &str, _expires_in: Duration) -> Result<String, String> {
+        Ok(format!("https://download.test/{}?signed", key))
+    }
+
+    async fn head(&self, key: &str) -> Result<Option<i64>, String> {
+        let objects = self.objects.lock().unwrap();
+        Ok(objects.get(key).map(|data| data.len() as i64))
+    }
+
+    async fn download(&self, key: &str) -> Result<Vec<u8>, String> {
+        let objects = self.objects.lock().unwrap();
+        objects
+            .get(key)
+            .cloned()
+            .ok_or_else(|| format!("No such object: {}", key))
+    }
+
+    async fn copy(&self, from_key: &str, to_key: &str) -> Result<String, String> {
+        let mut objects = self.objects.lock().unwrap();
+        let data = objects
+            .get(from_key)
+            .cloned()
+            .ok_or_else(|| format!("No such object: {}", from_key))?;
+        objects.insert(to_key.to_string(), data);
+        Ok(self.public_url(to_key))
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), String> {
+        self.objects.lock().unwrap().remove(key);
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SentEmail {
+    pub to: String,
+    pub subject: String,
+    pub html: String,
+    pub text: String,
+}
+
+impl SentEmail {
+    /// The `token` query parameter of the link in the email
+    pub fn token(&self) -> String {
+        let start = self.html.find("token=").expect("email has no token link") + "token=".len();
+        self.html[start..]
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric() || *c == '-')
+            .collect()
+    }
+}
+
+/// Captures outgoing email instead of sending it
+#[derive(Default)]
+pub struct MemoryMailer {
+    sent: Mutex<Vec<SentEmail>>,
+    failures: AtomicUsize,
+}
+
+impl MemoryMailer {
+    /// Make the next `n` sends fail, like a provider outage
+    pub fn fail_next(&self, n: usize) {
+        self.failures.store(n, Ordering::SeqCst);
+    }
+
+    /// Most recent email sent to `to`
+    pub fn last_to(&self, to: &str) -> Option<SentEmail> {
+        self.sent
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|email| email.to == to)
+            .cloned()
+    }
+}
+
+#[async_trait]
+impl EmailSender for MemoryMailer {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    async fn send(&self, message: &Message) -> Result<Option<String>, String> {
+        let failing = self
+            .failures
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok();
+        if failing {
+            return Err("Simulated outage".to_string());
+        }
+
+        let mut sent = self.sent.lock().unwrap();
+        sent.push(SentEmail {
+            to: message.to.clone(),
+            subject: message.subject.clone(),
+            html: message.html.clone(),
+            text: message.text.clone(),
+        });
+        Ok(Some(format!("memory-{}", sent.len())))
+    }
+
+    fn mailbox(&self) -> Option<Vec<Message>> {
+        let sent = self.sent.lock().unwrap();
+        let emails = sent.iter().rev().map(|email| Message {
+            to: email.to.clone(),
+            subject: email.subject.clone(),
+            html: email.html.clone(),
+            text: email.text.clone(),
+            unsubscribe_url: None,
+        });
+        Some(emails.collect())
+    }
+}
+
+/// In-memory stand-in for Meilisearch: substring matches, in insertion order
+#[derive(Default)]
+pub struct MemoryIndex {
+    documents: Mutex<Vec<SearchDocument>>,
+    pub failing: AtomicBool,
+}
+
+impl MemoryIndex {
+    pub fn ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self
+            .documents
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|d| d.id.clone())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    fn check(&self) -> Result<(), String> {
+        if self.failing.load(Ordering::SeqCst) {
+            return Err("Simulated outage".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SearchIndex for MemoryIndex {
+    async fn upsert(&self, documents: &[SearchDocument]) -> Result<(), String> {
+        self.check()?;
+        let mut stored = self.documents.lock().unwrap();
+        for document in documents {
+            stored.retain(|d| d.id != document.id);
+            stored.push(document.clone());
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, ids: &[String]) -> Result<(), String> {
+        self.check()?;
+        self.documents
+            .lock()
+            .unwrap()
+            .retain(|d| !ids.contains(&d.id));
+        Ok(())
+    }
+
+    async fn delete_user(&self, user_id: uuid::Uuid) -> Result<(), String> {
+        self.check()?;
+        self.documents
+            .lock()
+            .unwrap()
+            .retain(|d| d.user_id != user_id);
+        Ok(())
+    }
+
+    async fn clear(&self) -> Result<(), String> {
+        self.check()?;
+        self.documents.lock().unwrap().clear();
+        Ok(())
+    }
+
+    async fn search(&self, query: &IndexQuery<'_>) -> Result<IndexHits, String> {
+        self.check()?;
+        let q = query.q.to_lowercase();
+        let documents = self.documents.lock().unwrap();
+        let mut matches: Vec<&SearchDocument> = documents
+            .iter()
+            .filter(|d| query.kinds.contains(&d.kind.as_str()))
+            .filter(|d| {
+                format!("{} {}", d.title, d.text)
+                    .to_lowercase()
+                    .contains(&q)
+            })
+            .filter(|d| query.author.is_none_or(|a| d.author == a))
+            .filter(|d| query.status.is_none_or(|s| d.status.as_deref() == Some(s)))
+            .filter(|d| query.tag.is_none_or(|t| d.tags.contains(&t.to_lowercase())))
+            .collect();
+        if query.newest_first {
+            matches.sort_by_key(|d| std::cmp::Reverse(d.created_at));
+        }
+        let ids = matches
+            .iter()
+            .skip(((query.page - 1) * query.per_page) as usize)
+            .take(query.per_page as usize)
+            .map(|d| d.record_id)
+            .collect();
+        Ok(IndexHits {
+            ids,
+            total: matches.len() as i64,
+        })
+    }
+}
+
+/// What differs between the ways `TestApp` can be built
+#[derive(Default)]
+struct Setup {
+    search_index: bool,
+    replica: Option<PgPool>,
+    production: bool,
+    trusted_proxies: Vec<IpNet>,
+}
+
+/// The full router on a fresh database (from `#[sqlx::test]`), with fake storage and email
+pub struct TestApp {
+    router: Router,
+    pub state: AppState,
+    pub pool: PgPool,
+    pub store: Arc<MemoryStore>,
+    pub mailer: Arc<MemoryMailer>,
+    /// Only wired up by `with_search_index`
+    pub index: Arc<MemoryIndex>,
+}
+
+impl TestApp {
+    pub async fn new(pool: PgPool) -> Self {
+        Self::build(pool, Setup::default()).await
+    }
+
+    /// Like `new`, with search going through an in-memory index instead of Postgres
+    pub async fn with_search_index(pool: PgPool) -> Self {
+        let setup = Setup {
+            search_index: true,
+            ..Default::default()
+        };
+        Self::build(pool, setup).await
+    }
+
+    /// Like `new`, with reads that can go to a replica going to `replica`
+    pub async fn with_replica(pool: PgPool, replica: PgPool) -> Self {
+        let setup = Setup {
+            replica: Some(replica),
+            ..Default::default()
+        };
+        Self::build(pool, setup).await
+    }
+
+    /// Like `new`, as if every request came through a proxy at 127.0.0.1
+    pub async fn behind_proxy(pool: PgPool) -> Self {
+        let setup = Setup {
+            trusted_proxies: vec!["127.0.0.1/32".parse().unwrap()],
+            ..Default::default()
+        };
+        Self::build(pool, setup).await
+    }
+
+    /// Like `new`, configured the way it runs in production
+    pub async fn in_production(pool: PgPool) -> Self {
+        let setup = Setup {
+            production: true,
+            ..Default::default()
+        };
+        Self::build(pool, setup).await
+    }
+
+    async fn build(pool: PgPool, setup: Setup) -> Self {
+        api::migrate(&pool).await.expect("Failed to run migrations");
+
+        let store = Arc::new(MemoryStore::default());
+        let mailer = Arc::new(MemoryMailer::default());
+        let index = Arc::new(MemoryIndex::default());
+        let state: AppState = AppStateInner {
+            pool: pool.clone(),
+            replica: setup.replica,
+            config: Config {
+                frontend_url: "http://localhost:3000".to_string(),
+                frontend_origins: vec!["http://localhost:3000".to_string()],
+                is_production: setup.production,
+                email_signing_secret: b"test secret".to_vec(),
+                trusted_proxies: TrustedProxies::new(setup.trusted_proxies),
+            },
+            r2_client: Some(store.clone()),
+            http_client: reqwest::Client::new(),
+            email_sender: mailer.clone(),
+            cache: Arc::new(NoCache),
+            sessions: SessionBackend::Postgres(PostgresStore::new(pool.clone())),
+            realtime: Default::default(),
+            search_index: Indexer::new(setup.search_index.then(|| index.clone() as Arc<dyn SearchIndex>)),
+            geoip: Default::default(),
+            settings: Default::default(),
+        }
+        .into();
+
+        // Handlers read the peer address, which only exists on a real connection
+        let peer = SocketAddr::from(([127, 0, 0, 1], 0));
+        let router = api::app(state.clone()).layer(MockConnectInfo(peer));
+
+        Self {
+            router,
+            state,
+            pool,
+            store,
+            mailer,
+            index,
+        }
+    }
+
+    /// Run queued jobs (e.g. sending email) until none are due, like the worker would
+    pub async fn run_jobs(&self) {
+        while api::jobs::run_due(&self.state)
+            .await
+            .expect("Failed to run jobs")
+            > 0
+        {}
+    }
+
+    /// Serve the router on a local port, for tests that need a real connection (WebSockets)
+    pub async fn serve(&self) -> SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let router = self.router.clone();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        addr
+    }
+
+    /// A client with an empty cookie jar, i.e. logged out
+    pub fn client(&self) -> TestClient {
+        TestClient {
+            router: self.router.clone(),
+            cookie: None,
+        }
+    }
+
+    /// Sign up and verify a new account, returning a client logged in as it
+    pub async fn signup(&self, username: &str) -> TestClient {
+        let email = format!("{}@example.com", username);
+        let mut client = self.client();
+
+        let res = client
+            .post(
+                "/auth/signup",
+                serde_json::json!({
+                    "email": email,
+                    "password": PASSWORD,
+                    "username": username,
+                    "display_name": username,
+                }),
+            )
+            .await;
+        assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+
+        self.run_jobs().await;
+        let token = self
+            .mailer
+            .last_to(&email)
+            .expect("no verification email")
+            .token();
+        let res = client
+            .post("/auth/verify-email", serde_json::json!({ "token": token }))
+            .await;
+        assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+
+        client
+    }
+
+    /// Like `signup`, for an admin. The role is set before anything caches it in the session.
+    pub async fn signup_admin(&self, username: &str) -> TestClient {
+        let client = self.signup(username).await;
+        sqlx::query("UPDATE users SET role = 'admin' WHERE username = $1")
+            .bind(username)
+            .execute(&self.pool)
+            .await
+            .unwrap();
+        client
+    }
+}
+
+/// Sends requests through the router, keeping the session cookie between them like a browser
+pub struct TestClient {
+    router: Router,
+    cookie: Option<String>,
+}
+
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+impl TestResponse {
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    pub fn json(&self) -> Value {
+        serde_json::from_slice(&self.body)
+            .unwrap_or_else(|e| panic!("Response is not JSON ({}): {}", e, self.text()))
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).and_then(|value| value.to_str().ok())
+    }
+}
+
+impl TestClient {
+    /// The session cookie (`name=value`), once logged in
+    pub fn cookie(&self) -> Option<&str> {
+        self.cookie.as_deref()
+    }
+
+    pub async fn get(&mut self, path: &str) -> TestResponse {
+        self.get_with_headers(path, &[]).await
+    }
+
+    pub async fn get_with_headers(&mut self, path: &str, headers: &[(&str, &str)]) -> TestResponse {
+        let mut request = Request::builder().method(Method::GET).uri(path);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        self.send_request(request, Body::empty()).await
+    }
+
+    pub async fn delete(&mut self, path: &str) -> TestResponse {
+        self.send(Method::DELETE, path, None, Body::empty()).await
+    }
+
+    pub async fn post(&mut self, path: &str, json: Value) -> TestResponse {
+        self.send(
+            Method::POST,
+            path,
+            Some("application/json".to_string()),
+            Body::from(json.to_string()),
+        )
+        .await
+    }
+
+    pub async fn post_with_headers(
+        &mut self,
+        path: &str,
+        json: Value,
+        headers: &[(&str, &str)],
+    ) -> TestResponse {
+        let mut request = Request::builder()
+            .method(Method::POST)
+            .uri(path)
+            .header(header::CONTENT_TYPE, "application/json");
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        self.send_request(request, Body::from(json.to_string()))
+            .await
+    }
+
+    pub async fn patch(&mut self, path: &str, json: Value) -> TestResponse {
+        self.send(
+            Method::PATCH,
+            path,
+            Some("application/json".to_string()),
+            Body::from(json.to_string()),
+        )
+        .await
+    }
+
+    /// The CORS preflight a browser sends from `origin` before a `method` request
+    pub async fn preflight(&mut self, path: &str, origin: &str, method: Method) -> TestResponse {
+        let request = Request::builder()
+            .method(Method::OPTIONS)
+            .uri(path)
+            .header(header::ORIGIN, origin)
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, method.as_str())
+            .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "content-type");
+        self.send_request(request, Body::empty()).await
+    }
+
+    /// POST a single file as the `file` field of a multipart form
+    pub async fn post_file(
+        &mut self,
+        path: &str,
+        filename: &str,
+        content_type: &str,
+        data: &[u8],
+    ) -> TestResponse {
+        let boundary = "praxis-test-boundary";
+        let mut body = format!(
+            "--{b}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{f}\"\r\nContent-Type: {t}\r\n\r\n",
+            b = boundary,
+            f = filename,
+            t = content_type
+        )
+        .into_bytes();
+        body.extend_from_slice(data);
+        body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+
+        self.send(
+            Method::POST,
+            path,
+            Some(format!("multipart/form-data; boundary={}", boundary)),
+            Body::from(body),
+        )
+        .await
+    }
+
+    async fn send(
+        &mut self,
+        method: Method,
+        path: &str,
+        content_type: Option<String>,
+        body: Body,
+    ) -> TestResponse {
+        let mut request = Request::builder().method(method).uri(path);
+        if let Some(content_type) = content_type {
+            request = request.header(header::CONTENT_TYPE, content_type);
+        }
+        self.send_request(request, body).await
+    }
+
+    async fn send_request(
+        &mut self,
+        mut request: axum::http::request::Builder,
+        body: Body,
+    ) -> TestResponse {
+        if let Some(cookie) = &self.cookie {
+            request = request.header(header::COOKIE, cookie);
+        }
+
+        let response = self
+            .router
+            .clone()
+            .oneshot(request.body(body).unwrap())
+            .await
+            .unwrap();
+
+        // Only the session cookie is ever set; keep its name=value part
+        if let Some(set_cookie) = response.headers().get(header::SET_COOKIE) {
+            let cookie = set_cookie.to_str().unwrap();
+            self.cookie = cookie.split(';').next().map(|c| c.to_string());
+        }
+
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap()
+            .to_vec();
+
+        TestResponse {
+            status,
+            headers,
+            body,
+        }
+    }
+}
+
+/// The next JSON event pushed over a WebSocket, skipping pings
+pub async fn next_event<S>(socket: &mut S) -> serde_json::Value
+where
+    S: futures_util::Stream<Item = Result<tungstenite::Message, tungstenite::Error>> + Unpin,
+{
+    loop {
+        let message = tokio::time::timeout(Duration::from_secs(5), socket.next())
+            .await
+            .expect("no event")
+            .unwrap()
+            .unwrap();
+        if let tungstenite::Message::Text(text) = message {
+            return serde_json::from_str(&text).unwrap();
+        }
+    }
+}
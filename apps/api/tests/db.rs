@@ -0,0 +1,126 @@
+mod common;
+
+use api::db::{self, PoolConfig};
+use axum::http::StatusCode;
+use common::TestApp;
+use serde_json::json;
+use sqlx::postgres::PgPoolOptions;
+use sqlx::PgPool;
+use std::time::Duration;
+
+#[sqlx::test(migrations = false)]
+async fn the_statement_timeout_cancels_slow_queries(pool: PgPool) {
+    let config = PoolConfig {
+        max_connections: 1,
+        min_connections: 0,
+        acquire_timeout: Duration::from_secs(5),
+        statement_timeout: Some(Duration::from_millis(100)),
+        slow_query: Duration::from_millis(50),
+        pgbouncer: false,
+    };
+    let pool = db::connect((*pool.connect_options()).clone(), &config)
+        .await
+        .unwrap();
+
+    let err = sqlx::query("SELECT pg_sleep(1)")
+        .execute(&pool)
+        .await
+        .unwrap_err();
+    let code = err.as_database_error().and_then(|e| e.code());
+    assert_eq!(code.as_deref(), Some("57014"), "{}", err);
+
+    // The connection survives the cancelled statement
+    let one: i32 = sqlx::query_scalar("SELECT 1").fetch_one(&pool).await.unwrap();
+    assert_eq!(one, 1);
+}
+
+#[sqlx::test(migrations = false)]
+async fn pgbouncer_mode_connects_without_caching_statements(pool: PgPool) {
+    let config = PoolConfig {
+        max_connections: 1,
+        min_connections: 0,
+        acquire_timeout: Duration::from_secs(5),
+        statement_timeout: Some(Duration::from_millis(100)),
+        slow_query: Duration::from_millis(500),
+        pgbouncer: true,
+    };
+    let pool = db::connect((*pool.connect_options()).clone(), &config)
+        .await
+        .unwrap();
+
+    // The same query prepared again each time still works
+    for n in 0..3 {
+        let got: i32 = sqlx::query_scalar("SELECT $1::int")
+            .bind(n)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(got, n);
+    }
+
+    // The timeout isn't sent as a startup parameter, which PgBouncer would reject
+    let timeout: String = sqlx::query_scalar("SHOW statement_timeout")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_ne!(timeout, "100ms");
+}
+
+#[sqlx::test(migrations = false)]
+async fn read_only_pages_are_served_from_the_replica(pool: PgPool) {
+    // A read only view of the same database stands in for a replica
+    let replica = PgPoolOptions::new().connect_lazy_with(
+        (*pool.connect_options())
+            .clone()
+            .options([("default_transaction_read_only", "on")]),
+    );
+    let app = TestApp::with_replica(pool, replica.clone()).await;
+
+    // Writes still go to the primary
+    let mut ada = app.signup("ada").await;
+    let res = ada.post("/posts", json!({ "content": "Hello" })).await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    assert_eq!(replica.size(), 0);
+
+    let feed = app.client().get("/feed").await;
+    assert_eq!(feed.status, StatusCode::OK, "{}", feed.text());
+    assert_eq!(feed.json()["items"][0]["content"], "Hello");
+    assert!(replica.size() > 0);
+
+    for path in ["/user/profile/ada", "/search?q=hello", "/search/suggest?q=ad"] {
+        let res = app.client().get(path).await;
+        assert_eq!(res.status, StatusCode::OK, "{}: {}", path, res.text());
+    }
+}
+
+#[sqlx::test(migrations = false)]
+async fn database_errors_are_hidden_from_clients_in_production(pool: PgPool) {
+    let app = TestApp::in_production(pool).await;
+    sqlx::query("DROP TABLE feed_items")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+
+    let res = app.client().get("/feed").await;
+    assert_eq!(res.status, StatusCode::INTERNAL_SERVER_ERROR);
+    let error_id = res.header("x-error-id").unwrap().to_string();
+    assert_eq!(
+        res.text(),
+        format!("Internal Server Error (error ID {})", error_id)
+    );
+    assert!(!res.text().contains("feed_items"));
+}
+
+#[sqlx::test(migrations = false)]
+async fn database_errors_are_shown_in_development(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    sqlx::query("DROP TABLE feed_items")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+
+    let res = app.client().get("/feed").await;
+    assert_eq!(res.status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(res.header("x-error-id").is_some());
+    assert!(res.text().contains("feed_items"), "{}", res.text());
+}
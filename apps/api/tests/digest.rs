@@ -0,0 +1,102 @@
+mod common;
+
+use axum::http::StatusCode;
+use chrono::{Datelike, Duration, TimeZone, Utc};
+use common::TestApp;
+use serde_json::json;
+use sqlx::PgPool;
+
+#[sqlx::test(migrations = false)]
+async fn weekly_digest_goes_out_monday_morning_local_time(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+
+    // The next Monday 10:00 UTC, less than a week away
+    let now = Utc::now();
+    let days_ahead = (7 - now.weekday().num_days_from_monday() as i64) % 7;
+    let mut monday = Utc.from_utc_datetime(
+        &(now + Duration::days(days_ahead))
+            .date_naive()
+            .and_hms_opt(10, 0, 0)
+            .unwrap(),
+    );
+    if monday <= now {
+        monday += Duration::days(7);
+    }
+
+    let mut bob = app.signup("bob").await;
+    let res = bob
+        .patch("/user/email-preferences", json!({ "weekly_digest": true }))
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    let mut carol = app.signup("carol").await;
+    let res = carol
+        .patch(
+            "/user/email-preferences",
+            json!({ "weekly_digest": true, "timezone": "Pacific/Honolulu" }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    let res = carol
+        .patch(
+            "/user/email-preferences",
+            json!({ "timezone": "Mars/Olympus" }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let res = bob.post("/projects", json!({ "title": "Compiler" })).await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let project_id = res.json()["id"].as_str().unwrap().to_string();
+    let mut dave = app.signup("dave").await;
+    let res = dave
+        .post(
+            &format!("/projects/{}/apply", project_id),
+            json!({ "message": "I'd like to help", "links": [] }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let res = dave
+        .post("/posts", json!({ "content": "Shipped a linker" }))
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+
+    // Everything happened during the week before that Monday
+    sqlx::query("UPDATE applications SET created_at = $1")
+        .bind(monday - Duration::hours(1))
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    sqlx::query("UPDATE posts SET created_at = $1")
+        .bind(monday - Duration::hours(1))
+        .execute(&app.pool)
+        .await
+        .unwrap();
+
+    // 10am in UTC, but midnight in Honolulu
+    assert_eq!(api::digest::send_due(&app.state, monday).await, Ok(1));
+    app.run_jobs().await;
+    let digest = app.mailer.last_to("bob@example.com").unwrap();
+    assert_eq!(digest.subject, "Your week on Praxis");
+    assert!(digest.text.contains("dave applied to Compiler"));
+    assert!(digest.text.contains("dave: Shipped a linker"));
+    assert!(digest.text.contains("/api/email/unsubscribe?token="));
+    // dave never opted in
+    assert_ne!(
+        app.mailer.last_to("dave@example.com").unwrap().subject,
+        digest.subject
+    );
+
+    // Only one a week
+    assert_eq!(
+        api::digest::send_due(&app.state, monday + Duration::hours(1)).await,
+        Ok(0)
+    );
+
+    // 9:30am in Honolulu
+    let later = monday + Duration::hours(9) + Duration::minutes(30);
+    assert_eq!(api::digest::send_due(&app.state, later).await, Ok(1));
+    app.run_jobs().await;
+    let digest = app.mailer.last_to("carol@example.com").unwrap();
+    assert!(digest.text.contains("dave: Shipped a linker"));
+    assert!(!digest.text.contains("applied to"));
+}
@@ -0,0 +1,99 @@
+mod common;
+use axum::http::StatusCode;
+use chrono::{Duration, Utc};
+use common::TestApp;
+use serde_json::json;
+use sqlx::PgPool;
+
+#[sqlx::test(migrations = false)]
+async fn events_fill_up_show_in_the_feed_and_remind_attendees(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let mut bob = app.signup("bob").await;
+    let mut cy = app.signup("cy_").await;
+    let starts_at = Utc::now() + Duration::hours(30);
+
+    let res = ada
+        .post(
+            "/events",
+            json!({
+                "title": "Yesterday's meetup",
+                "starts_at": Utc::now() - Duration::days(1),
+                "ends_at": Utc::now(),
+            }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let res = ada
+        .post(
+            "/events",
+            json!({
+                "title": "Rust meetup",
+                "starts_at": starts_at,
+                "ends_at": starts_at + Duration::hours(2),
+                "online_url": "https://meet.example.com/rust",
+                "capacity": 1,
+            }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let id = res.json()["id"].as_str().unwrap().to_string();
+
+    let feed = bob.get("/feed?type=events").await.json()["items"].clone();
+    assert_eq!(feed.as_array().unwrap().len(), 1);
+    assert_eq!(feed[0]["type"], "event");
+    assert_eq!(feed[0]["title"], "Rust meetup");
+    assert!(feed[0]["starts_at"].is_string());
+    assert_eq!(bob.get("/feed?type=posts").await.json()["items"], json!([]));
+
+    // One place: taking it again is fine, a second person is turned away
+    let res = bob.post(&format!("/events/{}/rsvp", id), json!({})).await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    let res = bob.post(&format!("/events/{}/rsvp", id), json!({})).await;
+    assert_eq!(res.json()["going"], 1);
+    let res = cy.post(&format!("/events/{}/rsvp", id), json!({})).await;
+    assert_eq!(res.status, StatusCode::CONFLICT);
+    assert_eq!(res.text(), "This event is full");
+
+    let res = ada
+        .patch(&format!("/events/{}", id), json!({ "capacity": 0 }))
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    let res = cy.post(&format!("/events/{}/rsvp", id), json!({})).await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    let res = cy.delete(&format!("/events/{}/rsvp", id)).await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT);
+    let res = cy
+        .patch(&format!("/events/{}", id), json!({ "title": "Mine now" }))
+        .await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+
+    let event = bob.get(&format!("/events/{}", id)).await.json();
+    assert_eq!(event["going"], 1);
+    assert_eq!(event["viewer_going"], true);
+    assert_eq!(event["capacity"], json!(null));
+    assert_eq!(event["host_username"], "ada");
+    let attendees = ada.get(&format!("/events/{}/attendees", id)).await.json();
+    assert_eq!(attendees.as_array().unwrap().len(), 1);
+    assert_eq!(attendees[0]["username"], "bob");
+
+    // Reminded once, a day before
+    let remind = |now| api::events::send_reminders(&app.state, now);
+    assert_eq!(remind(Utc::now()).await, Ok(0));
+    assert_eq!(remind(starts_at - Duration::hours(20)).await, Ok(1));
+    assert_eq!(remind(starts_at - Duration::hours(19)).await, Ok(0));
+    app.run_jobs().await;
+    let email = app.mailer.last_to("bob@example.com").unwrap();
+    assert_eq!(email.subject, "Reminder: Rust meetup starts soon");
+    assert!(email.html.contains(&format!("/events/{}", id)));
+    let notifications = bob.get("/notifications").await.json();
+    assert_eq!(notifications[0]["kind"], "event_reminder");
+
+    let res = cy.delete(&format!("/events/{}", id)).await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN);
+    let res = ada.delete(&format!("/events/{}", id)).await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT);
+    assert_eq!(bob.get("/feed").await.json()["items"], json!([]));
+    let res = bob.get(&format!("/events/{}", id)).await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+}
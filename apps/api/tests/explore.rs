@@ -0,0 +1,141 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::TestApp;
+use serde_json::json;
+use sqlx::PgPool;
+
+#[sqlx::test(migrations = false)]
+async fn explore_returns_every_section(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let mut bob = app.signup("bob").await;
+    let mut cy = app.signup("cy_").await;
+
+    cy.post("/user/ada/follow", json!({})).await;
+    for (client, content) in [(&mut bob, "Bob's post"), (&mut ada, "Ada's post")] {
+        let res = client.post("/posts", json!({ "content": content })).await;
+        assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    }
+    let mut project_ids = Vec::new();
+    for (title, tags) in [
+        ("Compiler", json!(["Rust"])),
+        ("Linker", json!(["rust", "c"])),
+    ] {
+        let res = ada
+            .post("/projects", json!({ "title": title, "tags": tags }))
+            .await;
+        assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+        project_ids.push(res.json()["id"].as_str().unwrap().to_string());
+    }
+    // An application makes the older project the most recently active
+    let res = bob
+        .post(
+            &format!("/projects/{}/apply", project_ids[0]),
+            json!({ "message": "Count me in", "links": [] }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+
+    let res = app.client().get("/explore").await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    let explore = res.json();
+
+    // Ada has a follower
+    assert_eq!(explore["trending_posts"][0]["content"], "Ada's post");
+    assert_eq!(explore["trending_posts"].as_array().unwrap().len(), 2);
+    assert_eq!(explore["active_projects"][0]["title"], "Compiler");
+    assert_eq!(explore["active_projects"][1]["title"], "Linker");
+    assert_eq!(explore["new_members"][0]["username"], "cy_");
+    assert_eq!(explore["new_members"].as_array().unwrap().len(), 3);
+    assert_eq!(
+        explore["popular_tags"],
+        json!([{ "tag": "Rust", "projects": 2 }, { "tag": "c", "projects": 1 }])
+    );
+}
+
+#[sqlx::test(migrations = false)]
+async fn leaderboards_rank_by_period_and_leave_out_those_who_opt_out(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let mut bob = app.signup("bob").await;
+    for content in ["One", "Two"] {
+        let res = ada.post("/posts", json!({ "content": content })).await;
+        assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    }
+    let res = bob.post("/posts", json!({ "content": "Hi" })).await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    // One of ada's posts is from before this week
+    sqlx::query("UPDATE posts SET created_at = NOW() - INTERVAL '10 days' WHERE content = 'One'")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    let res = bob.post("/projects", json!({ "title": "Robots" })).await;
+    let status = format!("/projects/{}/status", res.json()["id"].as_str().unwrap());
+    let res = bob.patch(&status, json!({ "status": "completed" })).await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+
+    api::reputation::recompute(&app.pool).await.unwrap();
+    api::leaderboards::refresh(&app.pool).await.unwrap();
+
+    let standings = |board: serde_json::Value| -> Vec<(String, i64)> {
+        board["entries"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| {
+                (
+                    e["username"].as_str().unwrap().to_string(),
+                    e["score"].as_i64().unwrap(),
+                )
+            })
+            .collect()
+    };
+    let board = app
+        .client()
+        .get("/leaderboard?period=month&metric=posts")
+        .await
+        .json();
+    assert_eq!(board["entries"][0]["rank"], 1);
+    assert_eq!(
+        standings(board),
+        [("ada".to_string(), 2), ("bob".to_string(), 1)]
+    );
+    let board = app
+        .client()
+        .get("/leaderboard?period=week&metric=posts")
+        .await
+        .json();
+    assert_eq!(
+        standings(board),
+        [("ada".to_string(), 1), ("bob".to_string(), 1)]
+    );
+    for period in ["week", "all"] {
+        let board = app
+            .client()
+            .get(&format!("/leaderboard?period={}", period))
+            .await
+            .json();
+        assert_eq!(board["metric"], "reputation");
+        assert_eq!(standings(board), [("bob".to_string(), 25)]);
+    }
+    let res = app.client().get("/leaderboard?period=year").await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+
+    // Opting out takes effect before the next refresh
+    let res = ada
+        .post("/user/profile", json!({ "leaderboard_opt_out": true }))
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    assert_eq!(
+        ada.get("/user/me").await.json()["leaderboard_opt_out"],
+        true
+    );
+    let board = app
+        .client()
+        .get("/leaderboard?period=month&metric=posts")
+        .await
+        .json();
+    assert_eq!(board["entries"][0]["rank"], 1);
+    assert_eq!(standings(board), [("bob".to_string(), 1)]);
+}
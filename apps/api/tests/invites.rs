@@ -0,0 +1,94 @@
+mod common;
+use axum::http::StatusCode;
+use common::{TestApp, PASSWORD};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+fn signup(username: &str, invite_code: Option<&str>) -> Value {
+    json!({
+        "email": format!("{}@example.com", username),
+        "password": PASSWORD,
+        "username": username,
+        "display_name": username,
+        "invite_code": invite_code,
+    })
+}
+
+#[sqlx::test(migrations = false)]
+async fn invite_only_signups_are_tracked_as_referrals(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let mut root = app.signup_admin("root").await;
+    let res = root
+        .patch("/admin/settings", json!({ "invite_only": true }))
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+
+    let res = ada
+        .post("/user/me/invites", json!({ "max_uses": 11 }))
+        .await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+    let res = ada.post("/user/me/invites", json!({})).await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let code = res.json()["code"].as_str().unwrap().to_string();
+    assert_eq!(code.len(), 8);
+    // Admins aren't held to the same limits
+    let res = root
+        .post("/user/me/invites", json!({ "max_uses": 500 }))
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+
+    let res = app.client().post("/auth/signup", signup("bob", None)).await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN);
+    assert_eq!(res.text(), "Signups are invite only");
+    let res = app
+        .client()
+        .post("/auth/signup", signup("bob", Some("NOPE2345")))
+        .await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM users WHERE username = 'bob'")
+            .fetch_one(&app.pool)
+            .await
+            .unwrap(),
+        0
+    );
+
+    let lowercase = format!(" {} ", code.to_lowercase());
+    let res = app
+        .client()
+        .post("/auth/signup", signup("bob", Some(&lowercase)))
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    // One use only
+    let res = app
+        .client()
+        .post("/auth/signup", signup("carol", Some(&code)))
+        .await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+
+    let invites = ada.get("/user/me/invites").await.json();
+    assert_eq!(invites["referral_count"], 1);
+    assert_eq!(invites["referrals"][0]["username"], "bob");
+    assert_eq!(invites["referrals"][0]["code"], code.as_str());
+    assert_eq!(invites["invites"][0]["uses"], 1);
+    assert_eq!(invites["available_uses"], 0);
+
+    // Revoked codes stop working
+    let invite = ada
+        .post("/user/me/invites", json!({ "max_uses": 3 }))
+        .await
+        .json();
+    let res = ada
+        .delete(&format!(
+            "/user/me/invites/{}",
+            invite["id"].as_str().unwrap()
+        ))
+        .await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT);
+    let res = app
+        .client()
+        .post("/auth/signup", signup("carol", invite["code"].as_str()))
+        .await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,143 @@
+mod common;
+use axum::http::StatusCode;
+use common::TestApp;
+use serde_json::json;
+use sqlx::PgPool;
+
+#[sqlx::test(migrations = false)]
+async fn listings_are_filtered_and_take_applications(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let mut bob = app.signup("bob").await;
+    let mut cy = app.signup("cy_").await;
+
+    let res = ada
+        .post("/projects", json!({ "title": "Compiler" }))
+        .await;
+    let project_id = res.json()["id"].as_str().unwrap().to_string();
+    let res = bob
+        .post(
+            "/listings",
+            json!({
+                "role": "Parser engineer",
+                "compensation_type": "paid",
+                "project_id": project_id,
+            }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+    let res = ada
+        .post(
+            "/listings",
+            json!({
+                "role": "Parser engineer",
+                "description": "Help us write a fast parser",
+                "skills": ["Rust"],
+                "compensation_type": "paid",
+                "compensation": "$40/hour",
+                "project_id": project_id,
+            }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let listing_id = res.json()["id"].as_str().unwrap().to_string();
+    let res = bob
+        .post(
+            "/listings",
+            json!({ "role": "Designer", "compensation_type": "volunteer" }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+
+    let roles = |listings: serde_json::Value| -> Vec<String> {
+        listings
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|l| l["role"].as_str().unwrap().to_string())
+            .collect()
+    };
+    assert_eq!(
+        roles(cy.get("/listings").await.json()),
+        ["Designer", "Parser engineer"]
+    );
+    assert_eq!(
+        roles(cy.get("/listings?q=parser").await.json()),
+        ["Parser engineer"]
+    );
+    assert_eq!(
+        roles(cy.get("/listings?skill=rust").await.json()),
+        ["Parser engineer"]
+    );
+    assert_eq!(
+        roles(cy.get("/listings?compensation_type=volunteer").await.json()),
+        ["Designer"]
+    );
+    let res = cy.get("/listings?compensation_type=equity").await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+    let listing = cy.get(&format!("/listings/{}", listing_id)).await.json();
+    assert_eq!(listing["project_title"], "Compiler");
+    assert_eq!(listing["compensation"], "$40/hour");
+
+    // Applications work like they do for projects
+    let apply = json!({ "message": "I write parsers", "links": [] });
+    let res = ada
+        .post(&format!("/listings/{}/apply", listing_id), apply.clone())
+        .await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+    let res = cy
+        .post(&format!("/listings/{}/apply", listing_id), apply.clone())
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let application_id = res.json()["id"].as_str().unwrap().to_string();
+    let res = cy
+        .post(&format!("/listings/{}/apply", listing_id), apply.clone())
+        .await;
+    assert_eq!(res.status, StatusCode::CONFLICT);
+    assert_eq!(res.text(), "You have already applied to this listing");
+    let notifications = ada.get("/notifications").await.json();
+    assert_eq!(notifications[0]["kind"], "application");
+    assert_eq!(notifications[0]["data"]["listing_role"], "Parser engineer");
+
+    let res = bob
+        .get(&format!("/listings/{}/applications", listing_id))
+        .await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+    let applications = ada
+        .get(&format!("/listings/{}/applications", listing_id))
+        .await
+        .json();
+    assert_eq!(applications[0]["username"], "cy_");
+    assert_eq!(applications[0]["status"], "pending");
+    let path = format!("/listings/{}/applications/{}", listing_id, application_id);
+    let res = bob.patch(&path, json!({ "status": "accepted" })).await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+    let res = ada.patch(&path, json!({ "status": "accepted" })).await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    let res = ada
+        .patch(
+            &format!("/projects/{}/applications/{}", project_id, application_id),
+            json!({ "status": "rejected" }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+
+    // Closed listings leave the board and stop taking applications
+    let res = ada
+        .patch(
+            &format!("/listings/{}", listing_id),
+            json!({ "status": "closed" }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    assert_eq!(roles(cy.get("/listings").await.json()), ["Designer"]);
+    let res = bob
+        .post(&format!("/listings/{}/apply", listing_id), apply)
+        .await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+
+    let res = bob.delete(&format!("/listings/{}", listing_id)).await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN);
+    let res = ada.delete(&format!("/listings/{}", listing_id)).await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT);
+}
@@ -0,0 +1,760 @@
+mod common;
+use axum::http::StatusCode;
+use common::{next_event, TestApp};
+use futures_util::SinkExt;
+use serde_json::json;
+use sqlx::PgPool;
+use std::io::Cursor;
+use tokio_tungstenite::tungstenite::{client::IntoClientRequest, Message};
+
+fn png() -> Vec<u8> {
+    let mut data = Vec::new();
+    image::RgbImage::from_pixel(8, 8, image::Rgb([40, 40, 200]))
+        .write_to(&mut Cursor::new(&mut data), image::ImageFormat::Png)
+        .unwrap();
+    data
+}
+
+#[sqlx::test(migrations = false)]
+async fn dm_privacy_sends_strangers_to_requests(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let mut bob = app.signup("bob").await;
+    let mut cy = app.signup("cy_").await;
+    let mut eve = app.signup("eve").await;
+
+    let res = bob
+        .patch("/messages/settings", json!({ "dm_privacy": "friends" }))
+        .await;
+    assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let res = bob
+        .patch("/messages/settings", json!({ "dm_privacy": "following" }))
+        .await;
+    assert_eq!(res.json(), json!({ "dm_privacy": "following" }));
+    let res = bob.post("/user/cy_/follow", json!({})).await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT);
+
+    let res = ada
+        .post("/messages", json!({ "to": "ada", "content": "Hi" }))
+        .await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+    let res = ada
+        .post("/messages", json!({ "to": "nobody", "content": "Hi" }))
+        .await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+
+    // ada isn't followed, so her message is a request; cy is, so his isn't
+    let res = ada
+        .post("/messages", json!({ "to": "bob", "content": "Hi bob" }))
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let conversation_id = res.json()["conversation_id"].as_str().unwrap().to_string();
+    let res = cy
+        .post("/messages", json!({ "to": "Bob", "content": "Hey" }))
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+
+    let inbox = bob.get("/messages").await.json();
+    assert_eq!(inbox.as_array().unwrap().len(), 1);
+    assert_eq!(inbox[0]["other_username"], "cy_");
+    let requests = bob.get("/messages/requests").await.json();
+    assert_eq!(requests[0]["other_username"], "ada");
+    assert_eq!(requests[0]["last_message"], "Hi bob");
+    assert_eq!(requests[0]["unread"], 1);
+    // The sender sees it in their inbox straight away
+    assert_eq!(
+        ada.get("/messages").await.json()[0]["other_username"],
+        "bob"
+    );
+
+    let res = eve
+        .get(&format!("/messages/conversations/{}", conversation_id))
+        .await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+    let res = bob
+        .post(
+            &format!("/messages/conversations/{}/accept", conversation_id),
+            json!({}),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT);
+    let messages = bob
+        .get(&format!("/messages/conversations/{}", conversation_id))
+        .await
+        .json();
+    assert_eq!(messages[0]["content"], "Hi bob");
+    let inbox = bob.get("/messages").await.json();
+    assert_eq!(inbox.as_array().unwrap().len(), 2);
+    assert_eq!(inbox[1]["unread"], 0);
+
+    // Declined conversations can't be written to
+    let res = eve
+        .post("/messages", json!({ "to": "bob", "content": "Buy now" }))
+        .await;
+    let spam_id = res.json()["conversation_id"].as_str().unwrap().to_string();
+    bob.post(
+        &format!("/messages/conversations/{}/decline", spam_id),
+        json!({}),
+    )
+    .await;
+    let res = eve
+        .post("/messages", json!({ "to": "bob", "content": "Buy now!" }))
+        .await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN);
+    assert!(bob
+        .get("/messages/requests")
+        .await
+        .json()
+        .as_array()
+        .unwrap()
+        .is_empty());
+
+    // Nobody new, but accepted conversations carry on
+    bob.patch("/messages/settings", json!({ "dm_privacy": "nobody" }))
+        .await;
+    let mut dan = app.signup("dan").await;
+    let res = dan
+        .post("/messages", json!({ "to": "bob", "content": "Hi" }))
+        .await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN);
+    let res = ada
+        .post(
+            "/messages",
+            json!({ "to": "bob", "content": "Still there?" }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+}
+
+#[sqlx::test(migrations = false)]
+async fn first_messages_sent_at_once_share_a_conversation(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let mut bob = app.signup("bob").await;
+
+    let (to_bob, to_ada) = tokio::join!(
+        ada.post("/messages", json!({ "to": "bob", "content": "Hi bob" })),
+        bob.post("/messages", json!({ "to": "ada", "content": "Hi ada" })),
+    );
+    assert_eq!(to_bob.status, StatusCode::CREATED, "{}", to_bob.text());
+    assert_eq!(to_ada.status, StatusCode::CREATED, "{}", to_ada.text());
+    assert_eq!(
+        to_bob.json()["conversation_id"],
+        to_ada.json()["conversation_id"]
+    );
+    let conversations: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM conversations")
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(conversations, 1);
+}
+
+#[sqlx::test(migrations = false)]
+async fn blocks_stop_messages_both_ways(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let mut bob = app.signup("bob").await;
+    let res = bob.post("/user/ada/follow", json!({})).await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT);
+
+    let res = ada.post("/user/bob/block", json!({})).await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT);
+    assert_eq!(ada.get("/user/blocks").await.json()[0]["username"], "bob");
+    for (client, to) in [(&mut ada, "bob"), (&mut bob, "ada")] {
+        let res = client
+            .post("/messages", json!({ "to": to, "content": "Hi" }))
+            .await;
+        assert_eq!(res.status, StatusCode::FORBIDDEN);
+    }
+    // Nor can they follow
+    let res = bob.post("/user/ada/follow", json!({})).await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN);
+
+    let res = ada.delete("/user/bob/block").await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT);
+    let res = bob
+        .post("/messages", json!({ "to": "ada", "content": "Hi" }))
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+}
+
+#[sqlx::test(migrations = false)]
+async fn attachments_are_private_to_the_conversation(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let mut bob = app.signup("bob").await;
+    let mut eve = app.signup("eve").await;
+
+    let res = ada
+        .post_file("/upload?category=message", "blue.png", "image/png", &png())
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    // No public URL, now or later
+    assert!(res.json().get("url").is_none());
+    let upload_id = res.json()["id"].as_str().unwrap().to_string();
+    assert!(ada
+        .get(&format!("/upload/{}", upload_id))
+        .await
+        .json()
+        .get("url")
+        .is_none());
+    assert!(app
+        .store
+        .keys()
+        .iter()
+        .all(|key| key.starts_with("private/messages/")));
+
+    // Only the uploader can attach it
+    let res = eve
+        .post(
+            "/messages",
+            json!({ "to": "bob", "content": "", "attachment_id": upload_id }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+
+    let res = ada
+        .post(
+            "/messages",
+            json!({ "to": "bob", "content": "", "attachment_id": upload_id }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let conversation_id = res.json()["conversation_id"].as_str().unwrap().to_string();
+
+    let messages = bob
+        .get(&format!("/messages/conversations/{}", conversation_id))
+        .await
+        .json();
+    let attachment = &messages[0]["attachment"];
+    assert_eq!(attachment["id"], upload_id.as_str());
+    let url = attachment["urls"]["original"].as_str().unwrap();
+    assert!(url.starts_with("https://download.test/private/messages/"));
+    assert!(url.ends_with("?signed"));
+    assert!(attachment["urls"]["thumb"].is_string());
+
+    // Once attached it can't be reused elsewhere
+    let res = ada
+        .post(
+            "/messages",
+            json!({ "to": "eve", "content": "", "attachment_id": upload_id }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+    let res = eve
+        .get(&format!("/messages/conversations/{}", conversation_id))
+        .await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+
+    // Without an attachment there has to be some text
+    let res = ada
+        .post("/messages", json!({ "to": "bob", "content": " " }))
+        .await;
+    assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[sqlx::test(migrations = false)]
+async fn reactions_are_counted_and_pushed_to_both_members(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let mut bob = app.signup("bob").await;
+    let mut eve = app.signup("eve").await;
+    let res = ada
+        .post("/messages", json!({ "to": "bob", "content": "Lunch?" }))
+        .await;
+    let conversation_id = res.json()["conversation_id"].as_str().unwrap().to_string();
+    let message_id = res.json()["id"].as_str().unwrap().to_string();
+    assert_eq!(res.json()["reactions"], json!({}));
+
+    let addr = app.serve().await;
+    let mut request = format!("ws://{}/ws", addr).into_client_request().unwrap();
+    let headers = request.headers_mut();
+    headers.insert("origin", "http://localhost:3000".parse().unwrap());
+    headers.insert("cookie", ada.cookie().unwrap().parse().unwrap());
+    let (mut socket, _) = tokio_tungstenite::connect_async(request).await.unwrap();
+
+    let reactions = format!("/messages/{}/reactions", message_id);
+    let res = bob.post(&reactions, json!({ "emoji": "🦀" })).await;
+    assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let res = eve.post(&reactions, json!({ "emoji": "👍" })).await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+
+    let res = bob.post(&reactions, json!({ "emoji": "👍" })).await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    let res = ada.post(&reactions, json!({ "emoji": "👍" })).await;
+    assert_eq!(res.json()["reactions"], json!({ "👍": 2 }));
+    let res = bob.post(&reactions, json!({ "emoji": "👍" })).await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    let res = bob.post(&reactions, json!({ "emoji": "🎉" })).await;
+    assert_eq!(res.json()["reactions"], json!({ "👍": 2, "🎉": 1 }));
+
+    let event = next_event(&mut socket).await;
+    assert_eq!(event["type"], "message_reaction");
+    assert_eq!(event["message_id"], message_id.as_str());
+    assert_eq!(event["emoji"], "👍");
+    assert_eq!(event["reactions"], json!({ "👍": 1 }));
+    // ada's own reaction reaches her too; bob's repeat doesn't
+    assert_eq!(
+        next_event(&mut socket).await["reactions"],
+        json!({ "👍": 2 })
+    );
+    assert_eq!(next_event(&mut socket).await["emoji"], "🎉");
+
+    // 👍, percent-encoded
+    let res = bob.delete(&format!("{}/%F0%9F%91%8D", reactions)).await;
+    assert_eq!(
+        res.json(),
+        json!({ "reacted": false, "reactions": { "👍": 1, "🎉": 1 } })
+    );
+    let event = next_event(&mut socket).await;
+    assert_eq!(event["reacted"], false);
+
+    let messages = ada
+        .get(&format!("/messages/conversations/{}", conversation_id))
+        .await
+        .json();
+    assert_eq!(messages[0]["reactions"], json!({ "👍": 1, "🎉": 1 }));
+    assert_eq!(messages[0]["viewer_reactions"], json!(["👍"]));
+}
+
+#[sqlx::test(migrations = false)]
+async fn muted_and_archived_conversations(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let mut bob = app.signup("bob").await;
+    let mut cy = app.signup("cy_").await;
+
+    let res = ada
+        .post("/messages", json!({ "to": "bob", "content": "One" }))
+        .await;
+    let with_ada = res.json()["conversation_id"].as_str().unwrap().to_string();
+    ada.post("/messages", json!({ "to": "bob", "content": "Two" }))
+        .await;
+    // One notification per conversation until it's read
+    let notifications = bob.get("/notifications").await.json();
+    assert_eq!(notifications.as_array().unwrap().len(), 1);
+    assert_eq!(notifications[0]["kind"], "message");
+    assert_eq!(
+        notifications[0]["data"]["conversation_id"],
+        with_ada.as_str()
+    );
+    bob.get(&format!("/messages/conversations/{}", with_ada))
+        .await;
+    assert_eq!(
+        bob.get("/notifications/unread-count").await.json()["count"],
+        0
+    );
+
+    let res = bob
+        .post(
+            &format!("/messages/conversations/{}/mute", with_ada),
+            json!({ "until": "2000-01-01T00:00:00Z" }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let res = bob
+        .post(
+            &format!("/messages/conversations/{}/mute", with_ada),
+            json!({}),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT);
+    ada.post("/messages", json!({ "to": "bob", "content": "Three" }))
+        .await;
+    assert_eq!(
+        bob.get("/notifications/unread-count").await.json()["count"],
+        0
+    );
+
+    // Muted conversations sort last, even when they're more recent
+    cy.post("/messages", json!({ "to": "bob", "content": "Hey" }))
+        .await;
+    ada.post("/messages", json!({ "to": "bob", "content": "Four" }))
+        .await;
+    let inbox = bob.get("/messages").await.json();
+    assert_eq!(inbox[0]["other_username"], "cy_");
+    assert_eq!(inbox[1]["other_username"], "ada");
+    assert_eq!(inbox[1]["muted"], true);
+    assert_eq!(inbox[1]["muted_until"], serde_json::Value::Null);
+    let muted = bob.get("/messages?muted=true").await.json();
+    assert_eq!(muted.as_array().unwrap().len(), 1);
+
+    // Archived until the next message, unless it's muted
+    for id in [&with_ada, inbox[0]["id"].as_str().unwrap()] {
+        let res = bob
+            .post(
+                &format!("/messages/conversations/{}/archive", id),
+                json!({}),
+            )
+            .await;
+        assert_eq!(res.status, StatusCode::NO_CONTENT);
+    }
+    assert!(bob
+        .get("/messages")
+        .await
+        .json()
+        .as_array()
+        .unwrap()
+        .is_empty());
+    assert_eq!(
+        bob.get("/messages?archived=true")
+            .await
+            .json()
+            .as_array()
+            .unwrap()
+            .len(),
+        2
+    );
+    cy.post(
+        "/messages",
+        json!({ "to": "bob", "content": "Still there?" }),
+    )
+    .await;
+    ada.post("/messages", json!({ "to": "bob", "content": "Five" }))
+        .await;
+    let inbox = bob.get("/messages").await.json();
+    assert_eq!(inbox.as_array().unwrap().len(), 1);
+    assert_eq!(inbox[0]["other_username"], "cy_");
+
+    let res = bob
+        .delete(&format!("/messages/conversations/{}/mute", with_ada))
+        .await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT);
+    let res = cy
+        .post(
+            &format!("/messages/conversations/{}/archive", with_ada),
+            json!({}),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+    ada.post("/messages", json!({ "to": "bob", "content": "Six" }))
+        .await;
+    assert_eq!(
+        bob.get("/messages").await.json()[0]["other_username"],
+        "ada"
+    );
+    // cy's, still unread, and now ada's
+    assert_eq!(
+        bob.get("/notifications/unread-count").await.json()["count"],
+        2
+    );
+}
+
+#[sqlx::test(migrations = false)]
+async fn search_covers_only_the_callers_conversations(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let mut bob = app.signup("bob").await;
+    let mut cy = app.signup("cy_").await;
+
+    let res = ada
+        .post(
+            "/messages",
+            json!({ "to": "bob", "content": "Café lunch? Lunch!" }),
+        )
+        .await;
+    let with_bob = res.json()["conversation_id"].as_str().unwrap().to_string();
+    ada.post("/messages", json!({ "to": "bob", "content": "Or dinner" }))
+        .await;
+    let res = ada
+        .post(
+            "/messages",
+            json!({ "to": "cy_", "content": "Lunch with cy" }),
+        )
+        .await;
+    let with_cy = res.json()["conversation_id"].as_str().unwrap().to_string();
+
+    let results = bob.get("/messages/search?q=lunch").await.json();
+    assert_eq!(results.as_array().unwrap().len(), 1);
+    assert_eq!(results[0]["content"], "Café lunch? Lunch!");
+    // Character offsets, so the é counts once
+    assert_eq!(results[0]["highlights"], json!([[5, 10], [12, 17]]));
+    assert_eq!(
+        ada.get("/messages/search?q=lunch")
+            .await
+            .json()
+            .as_array()
+            .unwrap()
+            .len(),
+        2
+    );
+    let results = ada
+        .get(&format!(
+            "/messages/conversations/{}/search?q=lunch",
+            with_cy
+        ))
+        .await
+        .json();
+    assert_eq!(results.as_array().unwrap().len(), 1);
+    assert_eq!(results[0]["content"], "Lunch with cy");
+    // Web search syntax
+    let results = ada.get("/messages/search?q=lunch%20-cy").await.json();
+    assert_eq!(results.as_array().unwrap().len(), 1);
+    assert_eq!(results[0]["conversation_id"], with_bob.as_str());
+
+    let res = cy
+        .get(&format!(
+            "/messages/conversations/{}/search?q=lunch",
+            with_bob
+        ))
+        .await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+    let res = cy.get("/messages/search?q=%20").await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+}
+
+#[sqlx::test(migrations = false)]
+async fn reported_messages_are_reviewed_with_every_view_logged(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let mut bob = app.signup("bob").await;
+    let res = bob
+        .post("/messages", json!({ "to": "ada", "content": "Hi" }))
+        .await;
+    let conversation_id = res.json()["conversation_id"].as_str().unwrap().to_string();
+    let res = bob
+        .post(
+            "/messages",
+            json!({ "to": "ada", "content": "Buy my course" }),
+        )
+        .await;
+    let message_id = res.json()["id"].as_str().unwrap().to_string();
+    let report_path = format!("/messages/{}/report", message_id);
+
+    let res = bob.post(&report_path, json!({ "reason": "Spam" })).await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+    let mut eve = app.signup("eve").await;
+    let res = eve.post(&report_path, json!({ "reason": "Spam" })).await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+    let res = ada.post(&report_path, json!({ "reason": "Spam" })).await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let report_id = res.json()["id"].as_str().unwrap().to_string();
+    let res = ada.post(&report_path, json!({ "reason": "Spam" })).await;
+    assert_eq!(res.status, StatusCode::CONFLICT);
+    // The report keeps the content as it was, whatever happens to the message
+    sqlx::query("UPDATE messages SET content = 'Edited' WHERE conversation_id = $1")
+        .bind(uuid::Uuid::parse_str(&conversation_id).unwrap())
+        .execute(&app.pool)
+        .await
+        .unwrap();
+
+    let res = ada.get("/admin/message-reports").await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN);
+    let mut admin = app.signup_admin("root").await;
+
+    let reports = admin.get("/admin/message-reports").await.json();
+    assert_eq!(reports[0]["sender_username"], "bob");
+    assert_eq!(reports[0]["reason"], "Spam");
+    assert!(reports[0].get("content").is_none());
+    let report = admin
+        .get(&format!("/admin/message-reports/{}", report_id))
+        .await
+        .json();
+    assert_eq!(report["content"], "Buy my course");
+    assert_eq!(report["context"].as_array().unwrap().len(), 2);
+    let log = admin
+        .get("/admin/audit-log?action=admin.message_report_viewed")
+        .await
+        .json();
+    assert_eq!(log["total"], 1);
+    assert_eq!(log["entries"][0]["target_username"], "bob");
+
+    let resolve = format!("/admin/message-reports/{}/resolve", report_id);
+    let res = admin
+        .post(&resolve, json!({ "status": "actioned", "note": "Warned" }))
+        .await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT);
+    let res = admin.post(&resolve, json!({ "status": "dismissed" })).await;
+    assert_eq!(res.status, StatusCode::CONFLICT);
+    assert!(admin
+        .get("/admin/message-reports")
+        .await
+        .json()
+        .as_array()
+        .unwrap()
+        .is_empty());
+    let resolved = admin
+        .get("/admin/message-reports?status=actioned")
+        .await
+        .json();
+    assert_eq!(resolved[0]["note"], "Warned");
+
+    let res = admin
+        .get("/admin/message-reports?status=actioned&format=csv")
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    assert_eq!(res.header("content-type"), Some("text/csv; charset=utf-8"));
+    let csv = res.text();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("id,message_id,"));
+    assert!(lines[1].starts_with(&report_id));
+    assert!(lines[1].contains(",ada,"));
+    assert!(lines[1].contains(",Warned,"));
+    assert!(!csv.contains("Buy my course"));
+}
+
+#[sqlx::test(migrations = false)]
+async fn messages_are_deleted_for_one_or_unsent_for_both(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let mut bob = app.signup("bob").await;
+    let mut eve = app.signup("eve").await;
+    let res = ada
+        .post("/messages", json!({ "to": "bob", "content": "Old news" }))
+        .await;
+    let conversation = format!(
+        "/messages/conversations/{}",
+        res.json()["conversation_id"].as_str().unwrap()
+    );
+    let old_id = res.json()["id"].as_str().unwrap().to_string();
+    let res = ada
+        .post(
+            "/messages",
+            json!({ "to": "bob", "content": "Oops, wrong chat" }),
+        )
+        .await;
+    let oops_id = res.json()["id"].as_str().unwrap().to_string();
+    let res = bob
+        .post("/messages", json!({ "to": "ada", "content": "Hm?" }))
+        .await;
+    let reply_id = res.json()["id"].as_str().unwrap().to_string();
+
+    // Delete for me
+    let res = eve.delete(&format!("/messages/{}", reply_id)).await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+    for _ in 0..2 {
+        let res = ada.delete(&format!("/messages/{}", reply_id)).await;
+        assert_eq!(res.status, StatusCode::NO_CONTENT);
+    }
+    assert_eq!(
+        ada.get(&conversation).await.json()[0]["content"],
+        "Oops, wrong chat"
+    );
+    assert_eq!(
+        ada.get("/messages").await.json()[0]["last_message"],
+        "Oops, wrong chat"
+    );
+    assert_eq!(bob.get(&conversation).await.json()[0]["content"], "Hm?");
+
+    // Unsend: only the sender, only for 10 minutes
+    let res = bob
+        .post(&format!("/messages/{}/unsend", oops_id), json!({}))
+        .await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+    let res = ada
+        .post(&format!("/messages/{}/unsend", oops_id), json!({}))
+        .await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT);
+    assert_eq!(
+        bob.get(&conversation)
+            .await
+            .json()
+            .as_array()
+            .unwrap()
+            .len(),
+        2
+    );
+    sqlx::query("UPDATE messages SET created_at = NOW() - INTERVAL '11 minutes' WHERE id = $1")
+        .bind(uuid::Uuid::parse_str(&old_id).unwrap())
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    let res = ada
+        .post(&format!("/messages/{}/unsend", old_id), json!({}))
+        .await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN);
+
+    // Retention is off unless configured
+    sqlx::query("UPDATE messages SET created_at = NOW() - INTERVAL '40 days' WHERE id = $1")
+        .bind(uuid::Uuid::parse_str(&old_id).unwrap())
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(api::messages::purge_old(&app.state).await.unwrap(), 0);
+    std::env::set_var("MESSAGE_RETENTION_DAYS", "30");
+    let purged = api::messages::purge_old(&app.state).await;
+    std::env::remove_var("MESSAGE_RETENTION_DAYS");
+    assert_eq!(purged.unwrap(), 1);
+    let messages = bob.get(&conversation).await.json();
+    assert_eq!(messages.as_array().unwrap().len(), 1);
+    assert_eq!(messages[0]["content"], "Hm?");
+}
+
+#[sqlx::test(migrations = false)]
+async fn websocket_channels_and_typing(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let mut bob = app.signup("bob").await;
+    let mut eve = app.signup("eve").await;
+    let res = eve.post("/user/profile", json!({ "locale": "de" })).await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    let res = ada
+        .post("/messages", json!({ "to": "bob", "content": "Lunch?" }))
+        .await;
+    let conversation_id = res.json()["conversation_id"].as_str().unwrap().to_string();
+    let bob_id = bob.get("/user/me").await.json()["id"].clone();
+
+    let addr = app.serve().await;
+    let connect = |cookie: &str| {
+        let mut request = format!("ws://{}/ws", addr).into_client_request().unwrap();
+        let headers = request.headers_mut();
+        headers.insert("origin", "http://localhost:3000".parse().unwrap());
+        headers.insert("cookie", cookie.parse().unwrap());
+        tokio_tungstenite::connect_async(request)
+    };
+    let (mut ada_socket, _) = connect(ada.cookie().unwrap()).await.unwrap();
+    let (mut bob_socket, _) = connect(bob.cookie().unwrap()).await.unwrap();
+    let (mut eve_socket, _) = connect(eve.cookie().unwrap()).await.unwrap();
+    let send = |value: serde_json::Value| Message::Text(value.to_string());
+
+    ada_socket
+        .send(send(json!({ "type": "ping" })))
+        .await
+        .unwrap();
+    assert_eq!(next_event(&mut ada_socket).await, json!({ "type": "pong" }));
+    ada_socket
+        .send(send(json!({ "type": "dance" })))
+        .await
+        .unwrap();
+    assert_eq!(next_event(&mut ada_socket).await["code"], "bad_request");
+    ada_socket
+        .send(send(
+            json!({ "type": "unsubscribe", "channels": ["feed", "notifications"] }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(
+        next_event(&mut ada_socket).await,
+        json!({ "type": "subscribed", "channels": ["messages", "typing"] })
+    );
+
+    // Only members hear typing, and not the one typing
+    let typing = json!({ "type": "typing", "conversation_id": conversation_id });
+    eve_socket.send(send(typing.clone())).await.unwrap();
+    assert_eq!(
+        next_event(&mut eve_socket).await,
+        json!({
+            "type": "error",
+            "code": "conversation_not_found",
+            "message": "Unterhaltung nicht gefunden",
+        })
+    );
+    bob_socket.send(send(typing.clone())).await.unwrap();
+    assert_eq!(
+        next_event(&mut ada_socket).await,
+        json!({ "type": "typing", "conversation_id": conversation_id, "user_id": bob_id })
+    );
+
+    // ada left the feed and notifications, so she only hears of bob's reply itself
+    let res = bob.post("/posts", json!({ "content": "Hello" })).await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let res = bob
+        .post("/messages", json!({ "to": "ada", "content": "Sure" }))
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    assert_eq!(next_event(&mut ada_socket).await["content"], "Sure");
+    assert_eq!(next_event(&mut bob_socket).await["type"], "feed_item");
+    assert_eq!(next_event(&mut bob_socket).await["type"], "message");
+    assert_eq!(next_event(&mut eve_socket).await["type"], "feed_item");
+}
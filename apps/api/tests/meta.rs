@@ -0,0 +1,65 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::TestApp;
+use serde_json::json;
+use sqlx::PgPool;
+
+#[sqlx::test(migrations = false)]
+async fn meta_describes_profiles_posts_and_projects(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let res = ada
+        .post(
+            "/user/profile",
+            json!({ "display_name": "Ada Lovelace", "bio": "Writes programs" }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    let res = ada
+        .post("/posts", json!({ "content": "Shipped the parser" }))
+        .await;
+    let post_id = res.json()["id"].as_str().unwrap().to_string();
+    let res = ada
+        .post(
+            "/projects",
+            json!({ "title": "Compiler", "description": "A small compiler" }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+
+    let mut client = app.client();
+    let res = client.get("/meta?path=%2FAda").await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    assert_eq!(
+        res.json(),
+        json!({
+            "title": "Ada Lovelace (@ada)",
+            "description": "Writes programs",
+            "image": null,
+            "url": "http://localhost:3000/ada",
+            "type": "profile"
+        })
+    );
+
+    let res = client.get("/meta?path=%2Fada%2Fcompiler").await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    let meta = res.json();
+    assert_eq!(meta["title"], "Compiler");
+    assert_eq!(meta["description"], "A small compiler");
+    assert_eq!(meta["url"], "http://localhost:3000/ada/compiler");
+
+    let res = client
+        .get(&format!("/meta?path=%2Fada%23post-{}", post_id))
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    assert_eq!(res.json()["description"], "Shipped the parser");
+    assert_eq!(res.json()["type"], "article");
+
+    for path in ["%2Fnobody", "%2Fada%2Fnothing", "%2Fa%2Fb%2Fc"] {
+        let res = client.get(&format!("/meta?path={}", path)).await;
+        assert_eq!(res.status, StatusCode::NOT_FOUND, "{}", path);
+    }
+    let res = client.get("/meta?path=ada").await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,491 @@
+mod common;
+
+use axum::http::StatusCode;
+use chrono::{Duration as ChronoDuration, Utc};
+use common::{next_event, TestApp};
+use serde_json::json;
+use sqlx::PgPool;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+
+#[sqlx::test(migrations = false)]
+async fn applications_notify_the_project_owner(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut owner = app.signup("guido").await;
+    let res = owner
+        .post("/projects", json!({ "title": "Interpreter" }))
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let project_id = res.json()["id"].as_str().unwrap().to_string();
+
+    let res = app.client().get("/notifications/unread-count").await;
+    assert_eq!(res.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        owner.get("/notifications/unread-count").await.json(),
+        json!({ "count": 0 })
+    );
+
+    for applicant in ["larry", "yukihiro"] {
+        let res = app
+            .signup(applicant)
+            .await
+            .post(
+                &format!("/projects/{}/apply", project_id),
+                json!({ "message": "Count me in", "links": [] }),
+            )
+            .await;
+        assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    }
+
+    assert_eq!(
+        owner.get("/notifications/unread-count").await.json(),
+        json!({ "count": 2 })
+    );
+    let notifications = owner.get("/notifications").await.json();
+    assert_eq!(notifications[0]["kind"], "application");
+    assert_eq!(notifications[0]["actor_username"], "yukihiro");
+    assert_eq!(notifications[0]["data"]["project_title"], "Interpreter");
+    let id = notifications[0]["id"].as_str().unwrap().to_string();
+
+    // Only the recipient can mark it read
+    let mut stranger = app.signup("brendan").await;
+    let res = stranger
+        .post(&format!("/notifications/{}/read", id), json!({}))
+        .await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+
+    let res = owner
+        .post(&format!("/notifications/{}/read", id), json!({}))
+        .await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT);
+    assert_eq!(
+        owner.get("/notifications/unread-count").await.json(),
+        json!({ "count": 1 })
+    );
+
+    let res = owner.post("/notifications/read-all", json!({})).await;
+    assert_eq!(res.json(), json!({ "marked": 1 }));
+    assert_eq!(
+        owner.get("/notifications/unread-count").await.json(),
+        json!({ "count": 0 })
+    );
+
+    let res = stranger.delete(&format!("/notifications/{}", id)).await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+    let res = owner.delete(&format!("/notifications/{}", id)).await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT);
+    assert_eq!(
+        owner
+            .get("/notifications")
+            .await
+            .json()
+            .as_array()
+            .unwrap()
+            .len(),
+        1
+    );
+
+    // Read ones go after 90 days, unread ones after a year
+    sqlx::query("UPDATE notifications SET created_at = NOW() - INTERVAL '100 days'")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(api::notifications::purge_old(&app.pool).await.unwrap(), 1);
+    assert!(owner
+        .get("/notifications")
+        .await
+        .json()
+        .as_array()
+        .unwrap()
+        .is_empty());
+}
+
+#[sqlx::test(migrations = false)]
+async fn notification_preferences_choose_the_channels(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut owner = app.signup("grace").await;
+    let res = owner
+        .post("/projects", json!({ "title": "Compiler" }))
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let project_id = res.json()["id"].as_str().unwrap().to_string();
+
+    assert_eq!(
+        owner.get("/notifications/preferences").await.json(),
+        json!({
+            "application": { "in_app": true, "email": false, "push": true },
+            "broadcast": { "in_app": true, "email": false, "push": false },
+            "endorsement": { "in_app": true, "email": false, "push": true },
+            "event_reminder": { "in_app": true, "email": true, "push": true },
+            "mention": { "in_app": true, "email": false, "push": true },
+            "message": { "in_app": true, "email": false, "push": true },
+            "saved_search": { "in_app": true, "email": false, "push": false },
+            "strike": { "in_app": true, "email": false, "push": false }
+        })
+    );
+    let res = owner
+        .patch("/notifications/preferences", json!({ "mentions": {} }))
+        .await;
+    assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let res = owner
+        .patch(
+            "/notifications/preferences",
+            json!({ "application": { "in_app": false, "email": true } }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    assert_eq!(
+        res.json(),
+        json!({
+            "application": { "in_app": false, "email": true, "push": true },
+            "broadcast": { "in_app": true, "email": false, "push": false },
+            "endorsement": { "in_app": true, "email": false, "push": true },
+            "event_reminder": { "in_app": true, "email": true, "push": true },
+            "mention": { "in_app": true, "email": false, "push": true },
+            "message": { "in_app": true, "email": false, "push": true },
+            "saved_search": { "in_app": true, "email": false, "push": false },
+            "strike": { "in_app": true, "email": false, "push": false }
+        })
+    );
+
+    let res = app
+        .signup("jean")
+        .await
+        .post(
+            &format!("/projects/{}/apply", project_id),
+            json!({ "message": "Count me in", "links": [] }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    app.run_jobs().await;
+
+    assert_eq!(
+        owner.get("/notifications/unread-count").await.json(),
+        json!({ "count": 0 })
+    );
+    let email = app.mailer.last_to("grace@example.com").unwrap();
+    assert_eq!(email.subject, "jean applied to Compiler");
+    assert!(email.text.contains("/grace/compiler"));
+}
+
+#[sqlx::test(migrations = false)]
+async fn unread_notifications_are_emailed_after_quiet_hours(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut owner = app.signup("linus").await;
+    let res = owner
+        .patch(
+            "/user/email-preferences",
+            json!({ "quiet_hours_start": 22, "quiet_hours_end": 7 }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    let res = owner
+        .patch("/user/email-preferences", json!({ "quiet_hours_end": 24 }))
+        .await;
+    assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let res = owner.post("/projects", json!({ "title": "Kernel" })).await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let project_id = res.json()["id"].as_str().unwrap().to_string();
+
+    for applicant in ["andrew", "richard"] {
+        let res = app
+            .signup(applicant)
+            .await
+            .post(
+                &format!("/projects/{}/apply", project_id),
+                json!({ "message": "Count me in", "links": [] }),
+            )
+            .await;
+        assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    }
+    let notifications = owner.get("/notifications").await.json();
+    assert_eq!(notifications[0]["actor_username"], "richard");
+    let id = notifications[0]["id"].as_str().unwrap();
+    let res = owner
+        .post(&format!("/notifications/{}/read", id), json!({}))
+        .await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT);
+
+    // 11pm UTC, five hours after they arrived
+    let night = Utc::now()
+        .date_naive()
+        .and_hms_opt(23, 0, 0)
+        .unwrap()
+        .and_utc();
+    sqlx::query("UPDATE notifications SET created_at = $1")
+        .bind(night - ChronoDuration::hours(5))
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(
+        api::notifications::email_unread(&app.state, night).await,
+        Ok(0)
+    );
+
+    let morning = night + ChronoDuration::hours(9);
+    assert_eq!(
+        api::notifications::email_unread(&app.state, morning).await,
+        Ok(1)
+    );
+    app.run_jobs().await;
+    let email = app.mailer.last_to("linus@example.com").unwrap();
+    assert_eq!(email.subject, "You have an unread notification on Praxis");
+    assert!(email.text.contains("andrew applied to Kernel"));
+    assert!(!email.text.contains("richard"));
+
+    // Each notification is only emailed once
+    assert_eq!(
+        api::notifications::email_unread(&app.state, morning + ChronoDuration::hours(1)).await,
+        Ok(0)
+    );
+}
+
+#[sqlx::test(migrations = false)]
+async fn mentions_in_posts_notify_each_user_once(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let mut grace = app.signup("grace").await;
+    let res = grace
+        .post(
+            "/posts",
+            json!({ "content": "Thanks @Ada and @ada. Mail @grace at hi@ada.dev, not @nobody!" }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let post_id = res.json()["id"].as_str().unwrap().to_string();
+
+    let notifications = ada.get("/notifications").await.json();
+    assert_eq!(notifications.as_array().unwrap().len(), 1);
+    assert_eq!(notifications[0]["kind"], "mention");
+    assert_eq!(notifications[0]["actor_username"], "grace");
+    assert_eq!(notifications[0]["data"]["content_type"], "post");
+    assert_eq!(
+        notifications[0]["data"]["link"],
+        format!("/grace#post-{}", post_id)
+    );
+    // Nobody is notified about mentioning themselves
+    assert_eq!(
+        grace.get("/notifications/unread-count").await.json(),
+        json!({ "count": 0 })
+    );
+}
+
+#[sqlx::test(migrations = false)]
+async fn endorsements_show_on_profiles_and_notify_the_user(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let mut grace = app.signup("grace").await;
+    let mut bob = app.signup("bob").await;
+    let res = ada
+        .post("/user/profile", json!({ "skills": ["Rust", "Design"] }))
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    sqlx::query("UPDATE users SET reputation = 50 WHERE username = 'bob'")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+
+    let res = grace
+        .post("/user/ada/endorsements", json!({ "skill": "rust" }))
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let res = grace
+        .post("/user/ada/endorsements", json!({ "skill": "RUST" }))
+        .await;
+    assert_eq!(res.status, StatusCode::CONFLICT);
+    let res = grace
+        .post("/user/ada/endorsements", json!({ "skill": "Cooking" }))
+        .await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+    let res = ada
+        .post("/user/ada/endorsements", json!({ "skill": "Rust" }))
+        .await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+    for skill in ["Rust", "Design"] {
+        let res = bob
+            .post("/user/ada/endorsements", json!({ "skill": skill }))
+            .await;
+        assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    }
+
+    let notifications = ada.get("/notifications").await.json();
+    assert_eq!(notifications.as_array().unwrap().len(), 3);
+    assert_eq!(notifications[2]["kind"], "endorsement");
+    assert_eq!(notifications[2]["actor_username"], "grace");
+    assert_eq!(notifications[2]["data"]["skill"], "Rust");
+
+    // Most endorsed first, and the endorser with more reputation first
+    let profile = app.client().get("/user/profile/ada").await.json();
+    assert_eq!(profile["endorsements"][0]["skill"], "Rust");
+    assert_eq!(profile["endorsements"][0]["count"], 2);
+    let endorsers: Vec<_> = profile["endorsements"][0]["top_endorsers"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["username"].as_str().unwrap())
+        .collect();
+    assert_eq!(endorsers, ["bob", "grace"]);
+    assert_eq!(profile["endorsements"][1]["skill"], "Design");
+    assert_eq!(profile["endorsements"][1]["count"], 1);
+
+    let res = bob.delete("/user/ada/endorsements/design").await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT);
+    let res = bob.delete("/user/ada/endorsements/design").await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+    // Endorsements of skills taken off the profile aren't shown
+    let res = ada
+        .post("/user/profile", json!({ "skills": ["Design"] }))
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    let profile = app.client().get("/user/profile/ada").await.json();
+    assert_eq!(profile["endorsements"], json!([]));
+}
+
+#[sqlx::test(migrations = false)]
+async fn admins_broadcast_to_a_segment_now_or_later(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ken = app.signup("ken").await;
+    let mut rob = app.signup("rob").await;
+    sqlx::query("UPDATE users SET locale = 'es' WHERE username = 'rob'")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    let broadcast = json!({ "content": "Maintenance tonight", "link": "/status" });
+    let res = ken
+        .post("/admin/notifications/broadcasts", broadcast.clone())
+        .await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN);
+
+    let mut admin = app.signup_admin("root").await;
+
+    let res = admin
+        .post(
+            "/admin/notifications/broadcasts",
+            json!({ "content": "Hi", "link": "https://evil.example" }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let res = admin
+        .post("/admin/notifications/broadcasts", broadcast)
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    assert_eq!(res.json()["delivered"], 2);
+    let id = res.json()["id"].as_str().unwrap().to_string();
+
+    let notifications = ken.get("/notifications").await.json();
+    assert_eq!(notifications[0]["kind"], "broadcast");
+    assert_eq!(notifications[0]["actor_username"], "root");
+    assert_eq!(notifications[0]["data"]["content"], "Maintenance tonight");
+    let notification_id = notifications[0]["id"].as_str().unwrap();
+    ken.post(
+        &format!("/notifications/{}/read", notification_id),
+        json!({}),
+    )
+    .await;
+    let report = admin
+        .get(&format!("/admin/notifications/broadcasts/{}", id))
+        .await
+        .json();
+    assert_eq!(
+        (report["delivered"].clone(), report["read"].clone()),
+        (json!(2), json!(1))
+    );
+
+    // Scheduled, to Spanish speakers only
+    let send_at = Utc::now() + ChronoDuration::hours(1);
+    let res = admin
+        .post(
+            "/admin/notifications/broadcasts",
+            json!({ "content": "Hola", "segment": { "locale": "es" }, "send_at": send_at }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    assert_eq!(res.json()["sent_at"], serde_json::Value::Null);
+    let scheduled = res.json()["id"].as_str().unwrap().to_string();
+    assert_eq!(
+        api::broadcasts::send_due(&app.state, Utc::now()).await,
+        Ok(0)
+    );
+    assert_eq!(
+        api::broadcasts::send_due(&app.state, send_at + ChronoDuration::minutes(1)).await,
+        Ok(1)
+    );
+    assert_eq!(
+        rob.get("/notifications").await.json()[0]["data"]["content"],
+        "Hola"
+    );
+    assert_eq!(
+        ken.get("/notifications")
+            .await
+            .json()
+            .as_array()
+            .unwrap()
+            .len(),
+        1
+    );
+    let res = admin
+        .delete(&format!("/admin/notifications/broadcasts/{}", scheduled))
+        .await;
+    assert_eq!(res.status, StatusCode::CONFLICT);
+
+    let res = admin
+        .post(
+            "/admin/notifications/broadcasts",
+            json!({ "content": "Never mind", "send_at": send_at }),
+        )
+        .await;
+    let cancelled = res.json()["id"].as_str().unwrap().to_string();
+    let res = admin
+        .delete(&format!("/admin/notifications/broadcasts/{}", cancelled))
+        .await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT);
+    let reports = admin.get("/admin/notifications/broadcasts").await.json();
+    assert_eq!(reports.as_array().unwrap().len(), 2);
+}
+
+#[sqlx::test(migrations = false)]
+async fn notifications_and_feed_items_are_pushed_over_websocket(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut owner = app.signup("barbara").await;
+    let res = owner.post("/projects", json!({ "title": "CLU" })).await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let project_id = res.json()["id"].as_str().unwrap().to_string();
+    let addr = app.serve().await;
+
+    let connect = |cookie: Option<&str>, origin: &str| {
+        let mut request = format!("ws://{}/ws", addr).into_client_request().unwrap();
+        let headers = request.headers_mut();
+        headers.insert("origin", origin.parse().unwrap());
+        if let Some(cookie) = cookie {
+            headers.insert("cookie", cookie.parse().unwrap());
+        }
+        tokio_tungstenite::connect_async(request)
+    };
+    assert!(connect(None, "http://localhost:3000").await.is_err());
+    assert!(connect(owner.cookie(), "https://evil.example")
+        .await
+        .is_err());
+    let (mut socket, _) = connect(owner.cookie(), "http://localhost:3000")
+        .await
+        .unwrap();
+
+    let mut applicant = app.signup("alan").await;
+    let res = applicant
+        .post(
+            &format!("/projects/{}/apply", project_id),
+            json!({ "message": "Count me in", "links": [] }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let res = applicant
+        .post("/posts", json!({ "content": "Hello" }))
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+
+    let event = next_event(&mut socket).await;
+    assert_eq!(event["type"], "notification");
+    assert_eq!(event["kind"], "application");
+    assert_eq!(event["data"]["project_title"], "CLU");
+    let event = next_event(&mut socket).await;
+    assert_eq!(event["type"], "feed_item");
+    assert_eq!(event["item_type"], "post");
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::TestApp;
+use serde_json::json;
+use sqlx::PgPool;
+use std::io::Cursor;
+
+fn png() -> Vec<u8> {
+    let mut data = Vec::new();
+    image::RgbImage::from_pixel(8, 8, image::Rgb([40, 200, 40]))
+        .write_to(&mut Cursor::new(&mut data), image::ImageFormat::Png)
+        .unwrap();
+    data
+}
+
+fn oembed_path(url: &str) -> String {
+    let encoded = url
+        .replace(':', "%3A")
+        .replace('/', "%2F")
+        .replace('#', "%23");
+    format!("/oembed?url={}", encoded)
+}
+
+#[sqlx::test(migrations = false)]
+async fn posts_and_projects_unfurl(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let res = ada
+        .post_file("/upload?category=post", "parser.png", "image/png", &png())
+        .await;
+    let image_url = res.json()["url"].as_str().unwrap().to_string();
+
+    let res = ada
+        .post(
+            "/posts",
+            json!({ "content": "Shipped the parser", "image_url": image_url }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let post_id = res.json()["id"].as_str().unwrap().to_string();
+    let res = ada
+        .post(
+            "/projects",
+            json!({ "title": "Compiler", "description": "A small compiler" }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+
+    let mut client = app.client();
+    let url = format!("http://localhost:3000/ada#post-{}", post_id);
+    let res = client.get(&oembed_path(&url)).await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    let embed = res.json();
+    assert_eq!(embed["version"], "1.0");
+    assert_eq!(embed["type"], "link");
+    assert_eq!(embed["description"], "Shipped the parser");
+    assert_eq!(embed["author_url"], "http://localhost:3000/ada");
+    assert_eq!(embed["provider_name"], "Praxis");
+    assert_eq!(embed["thumbnail_url"], image_url);
+
+    let res = client
+        .get(&oembed_path("http://localhost:3000/ada/compiler"))
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    let embed = res.json();
+    assert_eq!(embed["title"], "Compiler");
+    assert_eq!(embed["description"], "A small compiler");
+
+    // Only the frontend's own links, to things that exist
+    for url in [
+        "http://localhost:3000/ada/nothing".to_string(),
+        format!("http://localhost:3000/ada#post-{}", uuid::Uuid::new_v4()),
+        "http://localhost:3000/ada".to_string(),
+        "https://elsewhere.test/ada/compiler".to_string(),
+    ] {
+        assert_eq!(
+            client.get(&oembed_path(&url)).await.status,
+            StatusCode::NOT_FOUND,
+            "{}",
+            url
+        );
+    }
+
+    let res = client
+        .get(&format!(
+            "{}&format=xml",
+            oembed_path("http://localhost:3000/ada/compiler")
+        ))
+        .await;
+    assert_eq!(res.status, StatusCode::NOT_IMPLEMENTED);
+}
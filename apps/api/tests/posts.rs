@@ -0,0 +1,510 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::{TestApp, TestClient, PASSWORD};
+use serde_json::json;
+use sqlx::PgPool;
+use std::io::Cursor;
+
+fn png() -> Vec<u8> {
+    let mut data = Vec::new();
+    image::RgbImage::from_pixel(8, 8, image::Rgb([200, 40, 40]))
+        .write_to(&mut Cursor::new(&mut data), image::ImageFormat::Png)
+        .unwrap();
+    data
+}
+
+/// A 64 byte mp4 upload URL: (upload id, object key)
+async fn presign_video(client: &mut TestClient) -> (String, String) {
+    let res = client
+        .post(
+            "/upload/presign",
+            json!({ "content_type": "video/mp4", "size_bytes": 64 }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    let body = res.json();
+    let key = body["upload_url"]
+        .as_str()
+        .unwrap()
+        .trim_start_matches("https://upload.test/")
+        .trim_end_matches("?signed")
+        .to_string();
+    (body["id"].as_str().unwrap().to_string(), key)
+}
+
+#[sqlx::test(migrations = false)]
+async fn posting_requires_login(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+
+    let res = app
+        .client()
+        .post("/posts", json!({ "content": "hello" }))
+        .await;
+    assert_eq!(res.status, StatusCode::UNAUTHORIZED);
+}
+
+#[sqlx::test(migrations = false)]
+async fn create_list_and_delete_post(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut client = app.client();
+    let mut author = app.signup("tim").await;
+
+    let res = author.post("/posts", json!({ "content": "   " })).await;
+    assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let res = author
+        .post("/posts", json!({ "content": "First post!" }))
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let post_id = res.json()["id"].as_str().unwrap().to_string();
+
+    let posts = client.get("/posts").await.json();
+    assert_eq!(posts.as_array().unwrap().len(), 1);
+    assert_eq!(posts[0]["content"], "First post!");
+    assert_eq!(posts[0]["author_username"], "tim");
+
+    // Only the author (or staff) can delete it
+    let mut other = app.signup("vint").await;
+    let res = other.delete(&format!("/posts/{}", post_id)).await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN);
+
+    let res = author.delete(&format!("/posts/{}", post_id)).await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT, "{}", res.text());
+    assert!(client
+        .get("/posts")
+        .await
+        .json()
+        .as_array()
+        .unwrap()
+        .is_empty());
+}
+
+#[sqlx::test(migrations = false)]
+async fn post_image_is_stored_and_removed_with_the_post(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut author = app.signup("hedy").await;
+
+    let res = author
+        .post_file("/upload?category=post", "red.png", "image/png", &png())
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    let url = res.json()["url"].as_str().unwrap().to_string();
+    assert!(url.starts_with("https://cdn.test/"));
+    // Original plus its resized/webp variants
+    assert!(app.store.keys().len() > 1);
+
+    let res = author
+        .post("/posts", json!({ "content": "Look", "image_url": url }))
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let post_id = res.json()["id"].as_str().unwrap().to_string();
+
+    let res = author.delete(&format!("/posts/{}", post_id)).await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT, "{}", res.text());
+    assert!(app.store.keys().is_empty());
+}
+
+#[sqlx::test(migrations = false)]
+async fn posts_can_only_use_and_delete_their_authors_uploads(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut hedy = app.signup("hedy").await;
+    let mut mallory = app.signup("mallory").await;
+
+    let res = hedy
+        .post_file("/upload?category=post", "red.png", "image/png", &png())
+        .await;
+    let url = res.json()["url"].as_str().unwrap().to_string();
+    let stored = app.store.keys().len();
+
+    for image_url in [url.as_str(), "https://elsewhere.example/red.png"] {
+        let res = mallory
+            .post(
+                "/posts",
+                json!({ "content": "Mine now", "image_url": image_url }),
+            )
+            .await;
+        assert_eq!(res.status, StatusCode::BAD_REQUEST, "{}", res.text());
+        assert_eq!(res.text(), "Image must be one of your uploads");
+    }
+    let res = mallory
+        .post("/projects", json!({ "title": "Heist", "image_url": url }))
+        .await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST, "{}", res.text());
+
+    // An image still shown elsewhere outlives the post that's deleted
+    let mut posts = Vec::new();
+    for content in ["Look", "Look again"] {
+        let res = hedy
+            .post("/posts", json!({ "content": content, "image_url": url }))
+            .await;
+        assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+        posts.push(res.json()["id"].as_str().unwrap().to_string());
+    }
+    hedy.delete(&format!("/posts/{}", posts[0])).await;
+    assert_eq!(app.store.keys().len(), stored);
+    hedy.delete(&format!("/posts/{}", posts[1])).await;
+    assert!(app.store.keys().is_empty());
+}
+
+#[sqlx::test(migrations = false)]
+async fn post_list_is_revalidated_with_etag_and_compressed(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut author = app.signup("radia").await;
+    let content = "Spanning tree ".repeat(20);
+    let res = author.post("/posts", json!({ "content": content })).await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+
+    let mut client = app.client();
+    let res = client.get("/posts").await;
+    assert_eq!(res.status, StatusCode::OK);
+    let etag = res.header("etag").expect("list has an ETag").to_string();
+
+    // Same content, so the client can keep its copy
+    let res = client
+        .get_with_headers("/posts", &[("if-none-match", &etag)])
+        .await;
+    assert_eq!(res.status, StatusCode::NOT_MODIFIED);
+    assert!(res.body.is_empty());
+
+    // A new post changes the tag
+    let res = author.post("/posts", json!({ "content": "Another" })).await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let res = client
+        .get_with_headers("/posts", &[("if-none-match", &etag)])
+        .await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert_ne!(res.header("etag"), Some(etag.as_str()));
+
+    let res = client
+        .get_with_headers("/posts", &[("accept-encoding", "gzip")])
+        .await;
+    assert_eq!(res.status, StatusCode::OK);
+    assert_eq!(res.header("content-encoding"), Some("gzip"));
+}
+
+#[sqlx::test(migrations = false)]
+async fn the_feed_lists_posts_and_projects_newest_first(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let mut tim = app.signup("tim").await;
+    let res = ada.post("/posts", json!({ "content": "Hello" })).await;
+    let post_id = res.json()["id"].as_str().unwrap().to_string();
+    let res = ada
+        .post("/projects", json!({ "title": "Engine", "looking_for": ["Designer"] }))
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    tim.post("/posts", json!({ "content": "Hi ada" })).await;
+
+    let feed = app.client().get("/feed").await.json()["items"].clone();
+    let items: Vec<(&str, &str)> = feed
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|i| (i["type"].as_str().unwrap(), i["author_username"].as_str().unwrap()))
+        .collect();
+    assert_eq!(items, [("post", "tim"), ("project", "ada"), ("post", "ada")]);
+    assert_eq!(feed[1]["title"], "Engine");
+    assert_eq!(feed[1]["looking_for"], json!(["Designer"]));
+    assert_eq!(feed[2]["content"], "Hello");
+    assert_eq!(feed[2]["looking_for"], json!([]));
+
+    let projects = app.client().get("/feed?type=projects").await.json()["items"].clone();
+    assert_eq!(projects.as_array().unwrap().len(), 1);
+    let posts = app.client().get("/feed?type=posts").await.json()["items"].clone();
+    assert_eq!(posts.as_array().unwrap().len(), 2);
+
+    // Deleted items and banned authors drop out
+    ada.delete(&format!("/posts/{}", post_id)).await;
+    sqlx::query("UPDATE users SET banned_at = NOW() WHERE username = 'tim'")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    let feed = app.client().get("/feed").await.json()["items"].clone();
+    assert_eq!(feed.as_array().unwrap().len(), 1);
+    assert_eq!(feed[0]["type"], "project");
+}
+
+#[sqlx::test(migrations = false)]
+async fn the_feed_is_paged_with_a_cursor(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    for n in 0..5 {
+        let res = ada
+            .post("/posts", json!({ "content": format!("Post {}", n) }))
+            .await;
+        assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    }
+    // Items from the same moment are still each shown once
+    sqlx::query("UPDATE feed_items SET created_at = NOW()")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+
+    let mut seen = Vec::new();
+    let mut path = "/feed?limit=2".to_string();
+    loop {
+        let page = app.client().get(&path).await.json();
+        for item in page["items"].as_array().unwrap() {
+            seen.push(item["content"].as_str().unwrap().to_string());
+        }
+        match page["next_cursor"].as_str() {
+            Some(cursor) => path = format!("/feed?limit=2&before={}", cursor),
+            None => break,
+        }
+    }
+    seen.sort();
+    assert_eq!(seen, ["Post 0", "Post 1", "Post 2", "Post 3", "Post 4"]);
+
+    let res = app.client().get("/feed?before=yesterday").await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+}
+
+#[sqlx::test(migrations = false)]
+async fn posts_and_profiles_can_be_looked_up_in_batches(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let ada_id = ada.get("/user/me").await.json()["id"].as_str().unwrap().to_string();
+    let res = ada.post("/posts", json!({ "content": "Hello" })).await;
+    let post_id = res.json()["id"].as_str().unwrap().to_string();
+    let unknown = uuid::Uuid::new_v4().to_string();
+
+    let res = app
+        .client()
+        .post("/posts/batch", json!({ "ids": [post_id, unknown] }))
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    let posts = res.json();
+    assert_eq!(posts[&post_id]["content"], "Hello");
+    assert_eq!(posts[&post_id]["author_username"], "ada");
+    assert_eq!(posts[&unknown], json!(null));
+
+    let users = app
+        .client()
+        .post("/user/batch", json!({ "ids": [ada_id, unknown] }))
+        .await
+        .json();
+    assert_eq!(users[&ada_id]["username"], "ada");
+    assert!(users[&ada_id].get("email").is_none());
+    assert_eq!(users[&unknown], json!(null));
+
+    let too_many: Vec<String> = (0..101).map(|_| uuid::Uuid::new_v4().to_string()).collect();
+    let res = app
+        .client()
+        .post("/user/batch", json!({ "ids": too_many }))
+        .await;
+    assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let res = app.client().post("/posts/batch", json!({ "ids": [] })).await;
+    assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[sqlx::test(migrations = false)]
+async fn unverified_accounts_cannot_publish_until_allowed(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let project = ada.post("/projects", json!({ "title": "Engine" })).await.json();
+    let mut bob = app.client();
+    let res = bob
+        .post(
+            "/auth/signup",
+            json!({
+                "email": "bob@example.com",
+                "password": PASSWORD,
+                "username": "bob",
+                "display_name": "Bob",
+            }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+
+    let apply = format!("/projects/{}/apply", project["id"].as_str().unwrap());
+    for (path, body) in [
+        ("/posts", json!({ "content": "Buy now" })),
+        ("/projects", json!({ "title": "Spam" })),
+        (apply.as_str(), json!({ "message": "Hire me" })),
+    ] {
+        let res = bob.post(path, body).await;
+        assert_eq!(res.status, StatusCode::FORBIDDEN, "{}", path);
+        assert_eq!(res.json()["code"], "verification_required");
+    }
+
+    // Admins can turn the requirement off
+    let mut root = app.signup_admin("root").await;
+    let res = root
+        .patch("/admin/settings", json!({ "require_verified_email": false }))
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+
+    let res = bob.post("/posts", json!({ "content": "Hello" })).await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+}
+
+#[sqlx::test(migrations = false)]
+async fn quarantined_uploads_are_only_shown_through_signed_urls(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let mut bob = app.signup("bob").await;
+    // Held for review by the moderation hook
+    let upload_id: uuid::Uuid = sqlx::query_scalar(
+        r#"
+        INSERT INTO uploads (owner_id, object_key, url, content_type, size_bytes, variant, moderation_status)
+        SELECT id, 'quarantine/ada/1/original.png', 'https://cdn.test/quarantine/ada/1/original.png',
+               'image/png', 100, 'original', 'pending'
+        FROM users WHERE username = 'ada'
+        RETURNING id
+        "#,
+    )
+    .fetch_one(&app.pool)
+    .await
+    .unwrap();
+    let path = format!("/upload/{}", upload_id);
+
+    let upload = ada.get(&path).await.json();
+    assert_eq!(upload["status"], "pending");
+    assert_eq!(
+        upload["url"],
+        "https://download.test/quarantine/ada/1/original.png?signed"
+    );
+    assert_eq!(upload["expires_in"], 300);
+    assert_eq!(bob.get(&path).await.status, StatusCode::NOT_FOUND);
+
+    let res = bob.get("/admin/uploads/quarantine").await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN);
+    sqlx::query("UPDATE users SET role = 'admin' WHERE username = 'bob'")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    let mut bob = app.client();
+    bob.post(
+        "/auth/login",
+        json!({ "email": "bob@example.com", "password": PASSWORD }),
+    )
+    .await;
+    let queue = bob.get("/admin/uploads/quarantine").await.json();
+    assert_eq!(
+        queue[0]["url"],
+        "https://download.test/quarantine/ada/1/original.png?signed"
+    );
+    assert!(queue[0].get("object_key").is_none());
+}
+
+#[sqlx::test(migrations = false)]
+async fn held_uploads_that_failed_part_way_are_cleaned_up(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    app.signup("ada").await;
+    // Upload 1 stopped after its original; upload 2 finished and is waiting for review
+    sqlx::query(
+        r#"
+        INSERT INTO uploads (owner_id, object_key, url, content_type, size_bytes, variant, moderation_status, created_at)
+        SELECT id, 'quarantine/ada/' || n || '/original.png',
+               'https://cdn.test/quarantine/ada/' || n || '/original.png',
+               'image/png', 100, 'original', 'pending', NOW() - INTERVAL '2 days'
+        FROM users, generate_series(1, 2) AS n WHERE username = 'ada'
+        "#,
+    )
+    .execute(&app.pool)
+    .await
+    .unwrap();
+    sqlx::query(
+        r#"
+        INSERT INTO uploads (owner_id, object_key, url, content_type, size_bytes, variant, parent_id, moderation_status)
+        SELECT owner_id, 'quarantine/ada/2/' || v || '.webp', 'https://cdn.test/quarantine/ada/2/' || v || '.webp',
+               'image/webp', 100, v, id, 'pending'
+        FROM uploads, unnest(ARRAY['thumb', 'medium', 'webp']) AS v
+        WHERE object_key = 'quarantine/ada/2/original.png'
+        "#,
+    )
+    .execute(&app.pool)
+    .await
+    .unwrap();
+
+    assert_eq!(
+        api::scheduler::run(&app.state, "orphaned_uploads").await,
+        Some(Ok(1))
+    );
+
+    let keys: Vec<String> =
+        sqlx::query_scalar("SELECT object_key FROM uploads ORDER BY object_key")
+            .fetch_all(&app.pool)
+            .await
+            .unwrap();
+    assert_eq!(
+        keys,
+        [
+            "quarantine/ada/2/medium.webp",
+            "quarantine/ada/2/original.png",
+            "quarantine/ada/2/thumb.webp",
+            "quarantine/ada/2/webp.webp",
+        ]
+    );
+}
+
+#[sqlx::test(migrations = false)]
+async fn presigned_videos_are_checked_and_screened_before_use(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let mut mp4 = vec![0, 0, 0, 24];
+    mp4.extend_from_slice(b"ftypmp42");
+    mp4.resize(64, 0);
+
+    // Nothing can use it until it's been sent and confirmed
+    let (id, key) = presign_video(&mut ada).await;
+    assert!(key.starts_with("quarantine/"));
+    let confirm = format!("/upload/{}/confirm", id);
+    assert_eq!(
+        ada.get(&format!("/upload/{}", id)).await.json()["status"],
+        "unconfirmed"
+    );
+    let res = ada.post(&confirm, json!({})).await;
+    assert_eq!(res.status, StatusCode::CONFLICT, "{}", res.text());
+    let url: String = sqlx::query_scalar("SELECT url FROM uploads")
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    let res = ada
+        .post("/posts", json!({ "content": "Watch", "image_url": url }))
+        .await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST, "{}", res.text());
+
+    // Bytes that aren't the declared type are thrown away
+    app.store.put(&key, png());
+    let res = ada.post(&confirm, json!({})).await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST, "{}", res.text());
+    assert!(app.store.keys().is_empty());
+    assert_eq!(
+        ada.post(&confirm, json!({})).await.status,
+        StatusCode::NOT_FOUND
+    );
+
+    // A real video is published out of quarantine
+    let (id, key) = presign_video(&mut ada).await;
+    app.store.put(&key, mp4);
+    let res = ada
+        .post(&format!("/upload/{}/confirm", id), json!({}))
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    assert_eq!(res.json()["status"], "approved");
+    let url = res.json()["url"].as_str().unwrap().to_string();
+    assert_eq!(
+        url,
+        format!("https://cdn.test/{}", key.trim_start_matches("quarantine/"))
+    );
+    assert_eq!(app.store.keys(), [key.trim_start_matches("quarantine/")]);
+    let res = ada
+        .post("/posts", json!({ "content": "Watch", "image_url": url }))
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+
+    // Ones never confirmed are cleaned up
+    presign_video(&mut ada).await;
+    sqlx::query(
+        "UPDATE uploads SET created_at = NOW() - INTERVAL '2 days' WHERE moderation_status = 'unconfirmed'",
+    )
+    .execute(&app.pool)
+    .await
+    .unwrap();
+    assert_eq!(
+        api::scheduler::run(&app.state, "orphaned_uploads").await,
+        Some(Ok(1))
+    );
+}
@@ -0,0 +1,261 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::TestApp;
+use serde_json::json;
+use sqlx::PgPool;
+
+#[sqlx::test(migrations = false)]
+async fn recommended_projects_match_skills(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let mut bob = app.signup("bob").await;
+    let mut cy = app.signup("cy_").await;
+
+    let res = ada
+        .post(
+            "/user/profile",
+            json!({ "skills": ["Rust", " rust ", "Design"] }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    assert_eq!(
+        ada.get("/user/me").await.json()["skills"],
+        json!(["Rust", "Design"])
+    );
+
+    let mut ids = Vec::new();
+    for (title, looking_for, tags) in [
+        ("Compiler", json!(["rust developer"]), json!(["rust"])),
+        ("Website", json!(["Designer"]), json!(["design"])),
+        ("Cookbook", json!(["Chef"]), json!(["food"])),
+    ] {
+        let res = bob
+            .post(
+                "/projects",
+                json!({ "title": title, "looking_for": looking_for, "tags": tags }),
+            )
+            .await;
+        assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+        ids.push(res.json()["id"].as_str().unwrap().to_string());
+    }
+    let res = cy
+        .post("/projects", json!({ "title": "Kernel", "tags": ["Rust"] }))
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+
+    // Someone applied to the website, filling its one role; the kernel has room
+    let res = cy
+        .post(
+            &format!("/projects/{}/apply", ids[1]),
+            json!({ "message": "Count me in", "links": [] }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+
+    let res = ada.get("/projects/recommended").await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    let projects = res.json();
+    let titles: Vec<&str> = projects
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| p["title"].as_str().unwrap())
+        .collect();
+    assert_eq!(titles, ["Kernel", "Compiler", "Website"]);
+    assert_eq!(projects[0]["matched"], json!(["Rust"]));
+    assert_eq!(projects[2]["applicants"], 1);
+    assert_eq!(projects[2]["tags"], json!(["design"]));
+
+    // Applied to or blocked: gone
+    let res = ada
+        .post(
+            &format!("/projects/{}/apply", ids[0]),
+            json!({ "message": "Hi", "links": [] }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let res = cy.post("/user/ada/block", json!({})).await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT, "{}", res.text());
+    let projects = ada.get("/projects/recommended").await.json();
+    assert_eq!(projects.as_array().unwrap().len(), 1);
+    assert_eq!(projects[0]["title"], "Website");
+
+    let res = app.client().get("/projects/recommended").await;
+    assert_eq!(res.status, StatusCode::UNAUTHORIZED);
+}
+
+#[sqlx::test(migrations = false)]
+async fn public_pages_can_be_cached_until_they_change(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let res = ada.post("/projects", json!({ "title": "Engine" })).await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let mut anon = app.client();
+
+    for path in ["/user/profile/ada", "/projects/user/ada/engine", "/feed"] {
+        let res = anon.get(path).await;
+        assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+        assert_eq!(
+            res.header("cache-control"),
+            Some("public, max-age=60, stale-while-revalidate=300"),
+            "{}",
+            path
+        );
+        let etag = res.header("etag").unwrap().to_string();
+        let res = anon
+            .get_with_headers(path, &[("if-none-match", &etag)])
+            .await;
+        assert_eq!(res.status, StatusCode::NOT_MODIFIED, "{}", path);
+    }
+
+    // Logged in responses stay out of shared caches
+    let res = ada.get("/user/profile/ada").await;
+    assert_eq!(res.header("cache-control"), Some("private, no-cache"));
+
+    let mut last_modified = Vec::new();
+    for path in ["/user/profile/ada", "/projects/user/ada/engine"] {
+        let res = anon.get(path).await;
+        let since = res.header("last-modified").unwrap().to_string();
+        let res = anon
+            .get_with_headers(path, &[("if-modified-since", &since)])
+            .await;
+        assert_eq!(res.status, StatusCode::NOT_MODIFIED, "{}", path);
+        last_modified.push((path, since));
+    }
+
+    // Editing the profile changes both pages (the project shows the owner's name)
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+    let res = ada
+        .post("/user/profile", json!({ "display_name": "Ada L" }))
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    for (path, since) in last_modified {
+        let res = anon
+            .get_with_headers(path, &[("if-modified-since", &since)])
+            .await;
+        assert_eq!(res.status, StatusCode::OK, "{}", path);
+        assert_ne!(res.header("last-modified"), Some(since.as_str()));
+    }
+}
+
+#[sqlx::test(migrations = false)]
+async fn projects_can_start_from_a_template(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let mut bob = app.signup("bob").await;
+    let mut root = app.signup_admin("root").await;
+
+    let templates = app.client().get("/project-templates").await.json();
+    let names: Vec<&str> = templates
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|template| template["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["Hackathon", "Open-Source Tool", "Research Group"]);
+    let hackathon = templates[0]["id"].as_str().unwrap().to_string();
+    let research = templates[2]["id"].as_str().unwrap().to_string();
+
+    // The template fills in whatever is left out
+    let res = ada
+        .post(
+            &format!("/projects/from-template/{}", hackathon),
+            json!({ "title": "Weekend Bot" }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let project_id = res.json()["id"].as_str().unwrap().to_string();
+    let project = ada.get("/projects/user/ada/weekend-bot").await.json();
+    assert!(project["description"]
+        .as_str()
+        .unwrap()
+        .starts_with("## The idea"));
+    assert_eq!(project["looking_for"], templates[0]["looking_for"]);
+    assert_eq!(project["tags"], json!(["hackathon"]));
+
+    let res = ada
+        .post(
+            &format!("/projects/from-template/{}", research),
+            json!({ "title": "Lab", "description": "Bees", "tags": ["biology"] }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let project = ada.get("/projects/user/ada/lab").await.json();
+    assert_eq!(project["description"], "Bees");
+    assert_eq!(project["tags"], json!(["biology"]));
+    assert_eq!(project["looking_for"], templates[2]["looking_for"]);
+
+    // Milestones are listed in order and checked off by the owner
+    let milestones_url = format!("/projects/{}/milestones", project_id);
+    let milestones = app.client().get(&milestones_url).await.json();
+    assert_eq!(
+        milestones,
+        json!([
+            { "id": milestones[0]["id"], "title": "Pick the idea", "completed_at": null },
+            { "id": milestones[1]["id"], "title": "Working prototype", "completed_at": null },
+            { "id": milestones[2]["id"], "title": "Demo ready", "completed_at": null },
+            { "id": milestones[3]["id"], "title": "Submitted", "completed_at": null },
+        ])
+    );
+    let milestone_url = format!(
+        "{}/{}",
+        milestones_url,
+        milestones[0]["id"].as_str().unwrap()
+    );
+    let res = bob.patch(&milestone_url, json!({ "done": true })).await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+    let res = ada.patch(&milestone_url, json!({ "done": true })).await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    let completed_at = res.json()["completed_at"].clone();
+    assert!(completed_at.is_string());
+    let res = ada.patch(&milestone_url, json!({ "done": true })).await;
+    assert_eq!(res.json()["completed_at"], completed_at);
+    let res = ada.patch(&milestone_url, json!({ "done": false })).await;
+    assert_eq!(res.json()["completed_at"], json!(null));
+
+    // Admins manage the catalog
+    let template = json!({ "name": "Game Jam", "milestones": ["Theme", "Playable"] });
+    let res = ada.post("/admin/project-templates", template.clone()).await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN);
+    let res = root
+        .post("/admin/project-templates", template.clone())
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let game_jam = res.json()["id"].as_str().unwrap().to_string();
+    let res = root
+        .post_with_headers(
+            "/admin/project-templates",
+            template,
+            &[("accept", "application/json")],
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::CONFLICT);
+    assert_eq!(res.json()["code"], "template_name_taken");
+    let res = root
+        .post(
+            "/admin/project-templates",
+            json!({ "name": "Big", "tags": vec!["tag"; 11] }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let template_url = format!("/admin/project-templates/{}", game_jam);
+    let res = root
+        .patch(&template_url, json!({ "summary": "Make a game" }))
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    assert_eq!(res.json()["summary"], "Make a game");
+    assert_eq!(res.json()["milestones"], json!(["Theme", "Playable"]));
+    assert_eq!(
+        root.delete(&template_url).await.status,
+        StatusCode::NO_CONTENT
+    );
+    let res = ada
+        .post(
+            &format!("/projects/from-template/{}", game_jam),
+            json!({ "title": "Snake" }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+}
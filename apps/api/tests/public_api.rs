@@ -0,0 +1,79 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::TestApp;
+use serde_json::json;
+use sqlx::PgPool;
+
+#[sqlx::test(migrations = false)]
+async fn public_api_needs_a_live_key_and_counts_its_use(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let res = ada.post("/projects", json!({ "title": "Compiler" })).await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let mut admin = app.signup_admin("root").await;
+
+    assert_eq!(
+        ada.post("/admin/api-keys", json!({ "name": "Stats bot" }))
+            .await
+            .status,
+        StatusCode::FORBIDDEN
+    );
+    let res = admin
+        .post("/admin/api-keys", json!({ "name": "Stats bot" }))
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let issued = res.json();
+    let key = issued["key"].as_str().unwrap().to_string();
+    let bearer = format!("Bearer {}", key);
+
+    let mut bot = app.client();
+    assert_eq!(
+        bot.get("/public/v1/projects").await.status,
+        StatusCode::UNAUTHORIZED
+    );
+    let res = bot
+        .get_with_headers("/public/v1/projects", &[("authorization", "Bearer prx_nope")])
+        .await;
+    assert_eq!(res.status, StatusCode::UNAUTHORIZED);
+
+    let res = bot
+        .get_with_headers("/public/v1/projects", &[("authorization", &bearer)])
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    assert_eq!(res.json()[0]["title"], "Compiler");
+    // Limited per key, more strictly than the global limit
+    assert_eq!(res.header("x-ratelimit-limit"), Some("120"));
+    let res = bot
+        .get_with_headers("/public/v1/users/ada", &[("x-api-key", &key)])
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    assert_eq!(res.json()["username"], "ada");
+
+    // Admins see usage, but never the key again
+    let keys = admin.get("/admin/api-keys").await.json();
+    assert_eq!(keys[0]["name"], "Stats bot");
+    assert_eq!(keys[0]["key_prefix"], issued["key_prefix"]);
+    assert!(key.starts_with(keys[0]["key_prefix"].as_str().unwrap()));
+    assert!(keys[0].get("key").is_none());
+    assert_eq!(keys[0]["requests_today"], 2);
+    assert_eq!(keys[0]["requests_total"], 2);
+    assert!(keys[0]["last_used_at"].is_string());
+
+    let path = format!("/admin/api-keys/{}", issued["id"].as_str().unwrap());
+    assert_eq!(admin.delete(&path).await.status, StatusCode::NO_CONTENT);
+    let res = bot
+        .get_with_headers("/public/v1/projects", &[("authorization", &bearer)])
+        .await;
+    assert_eq!(res.status, StatusCode::UNAUTHORIZED);
+    assert!(admin.get("/admin/api-keys").await.json()[0]["revoked_at"].is_string());
+
+    let log = admin.get("/admin/audit-log?action=admin.").await.json();
+    let actions: Vec<&str> = log["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["action"].as_str().unwrap())
+        .collect();
+    assert_eq!(actions, ["admin.api_key_revoked", "admin.api_key_created"]);
+}
@@ -0,0 +1,72 @@
+mod common;
+use axum::http::StatusCode;
+use common::TestApp;
+use serde_json::json;
+use sqlx::PgPool;
+
+#[sqlx::test(migrations = false)]
+async fn quotas_limit_posts_and_projects_unless_exempt(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let mut root = app.signup_admin("root").await;
+
+    let res = root
+        .patch("/admin/settings", json!({ "max_posts_per_hour": -1 }))
+        .await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+    let res = root
+        .patch(
+            "/admin/settings",
+            json!({ "max_posts_per_hour": 2, "max_projects_per_day": 1 }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+
+    for content in ["One", "Two"] {
+        let res = ada.post("/posts", json!({ "content": content })).await;
+        assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    }
+    let res = ada.post("/posts", json!({ "content": "Three" })).await;
+    assert_eq!(res.status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(
+        res.text(),
+        "You can make up to 2 posts an hour. Try again in 1 hour."
+    );
+    let res = ada.post("/projects", json!({ "title": "Compiler" })).await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let res = ada.post("/projects", json!({ "title": "Linker" })).await;
+    assert_eq!(res.status, StatusCode::TOO_MANY_REQUESTS);
+    assert!(res
+        .text()
+        .starts_with("You can create up to 1 projects a day. Try again in "));
+
+    // Admins are exempt by default
+    for content in ["One", "Two", "Three"] {
+        let res = root.post("/posts", json!({ "content": content })).await;
+        assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    }
+
+    // Older posts fall out of the window
+    sqlx::query("UPDATE posts SET created_at = created_at - INTERVAL '50 minutes'")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    let res = ada.post("/posts", json!({ "content": "Three" })).await;
+    assert_eq!(
+        res.text(),
+        "You can make up to 2 posts an hour. Try again in 10 minutes."
+    );
+    sqlx::query("UPDATE posts SET created_at = created_at - INTERVAL '10 minutes'")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    let res = ada.post("/posts", json!({ "content": "Three" })).await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+
+    let res = root
+        .patch("/admin/settings", json!({ "quota_exempt_verified": true }))
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    let res = ada.post("/projects", json!({ "title": "Linker" })).await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+}
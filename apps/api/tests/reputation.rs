@@ -0,0 +1,94 @@
+mod common;
+use axum::http::StatusCode;
+use common::TestApp;
+use serde_json::json;
+use sqlx::PgPool;
+
+#[sqlx::test(migrations = false)]
+async fn reputation_adds_up_and_gates_links(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let mut bob = app.signup("bob").await;
+    let mut cy = app.signup("cy_").await;
+    let mut root = app.signup_admin("root").await;
+    let res = root
+        .patch("/admin/settings", json!({ "min_reputation_for_links": 30 }))
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+
+    let res = ada
+        .post(
+            "/posts",
+            json!({ "content": "Read this: https://example.com" }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN);
+    assert_eq!(res.text(), "You need 30 reputation to post links");
+    let res = ada
+        .post("/posts", json!({ "content": "No links here" }))
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let res = root
+        .post("/posts", json!({ "content": "See www.example.com" }))
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+
+    // Two reactions from bob on one message count once; ada's own doesn't count
+    let res = ada
+        .post("/messages", json!({ "to": "bob", "content": "Lunch?" }))
+        .await;
+    let reactions = format!("/messages/{}/reactions", res.json()["id"].as_str().unwrap());
+    for emoji in ["👍", "🎉"] {
+        let res = bob.post(&reactions, json!({ "emoji": emoji })).await;
+        assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    }
+    let res = ada.post(&reactions, json!({ "emoji": "👍" })).await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+
+    // ada's application to bob's project is accepted, and ada completes her own project
+    let res = bob.post("/projects", json!({ "title": "Robots" })).await;
+    let bobs_project = res.json()["id"].as_str().unwrap().to_string();
+    let res = ada
+        .post(
+            &format!("/projects/{}/apply", bobs_project),
+            json!({ "message": "Pick me", "links": [] }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let review = format!(
+        "/projects/{}/applications/{}",
+        bobs_project,
+        res.json()["id"].as_str().unwrap()
+    );
+    let res = ada.patch(&review, json!({ "status": "accepted" })).await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+    let res = bob.patch(&review, json!({ "status": "maybe" })).await;
+    assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let res = bob.patch(&review, json!({ "status": "accepted" })).await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+
+    let res = ada.post("/projects", json!({ "title": "Rockets" })).await;
+    let status = format!("/projects/{}/status", res.json()["id"].as_str().unwrap());
+    let res = cy.patch(&status, json!({ "status": "completed" })).await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+    let res = ada.patch(&status, json!({ "status": "completed" })).await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+
+    assert_eq!(api::reputation::recompute(&app.pool).await.unwrap(), 1);
+    assert_eq!(ada.get("/user/me").await.json()["reputation"], 36);
+    assert_eq!(cy.get("/user/profile/ada").await.json()["reputation"], 36);
+    // Nothing changed, so nothing is written again
+    assert_eq!(api::reputation::recompute(&app.pool).await.unwrap(), 0);
+
+    let res = ada
+        .post(
+            "/posts",
+            json!({ "content": "Read this: https://example.com" }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let res = bob
+        .post("/posts", json!({ "content": "(www.example.com)" }))
+        .await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN);
+}
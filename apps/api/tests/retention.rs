@@ -0,0 +1,86 @@
+mod common;
+use axum::http::StatusCode;
+use common::TestApp;
+use serde_json::json;
+use sqlx::PgPool;
+
+#[sqlx::test(migrations = false)]
+async fn retention_policies_delete_old_data(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let mut root = app.signup_admin("root").await;
+
+    let res = ada.get("/admin/retention").await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN);
+    let policies = root.get("/admin/retention").await.json();
+    assert_eq!(policies.as_array().unwrap().len(), 5);
+    assert_eq!(policies[3]["data_class"], "email_log");
+    assert_eq!(policies[3]["days"], 30);
+    assert_eq!(policies[0]["days"], json!(null));
+
+    for (changes, error) in [
+        (json!({ "chat": 5 }), "Unknown data class: chat"),
+        (
+            json!({ "analytics_events": 1 }),
+            "analytics_events must be null or from 2 to 36500 days",
+        ),
+        (
+            json!({ "sessions": "soon" }),
+            "sessions must be null or from 1 to 36500 days",
+        ),
+    ] {
+        let res = root.patch("/admin/retention", changes).await;
+        assert_eq!(res.status, StatusCode::BAD_REQUEST);
+        assert_eq!(res.text(), error);
+    }
+
+    // A stale session, an old audit entry and content held for too long
+    let res = ada
+        .post("/posts", json!({ "content": "Visit https://ada.example" }))
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    sqlx::query("UPDATE posts SET held_at = NOW() - INTERVAL '8 days'")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    sqlx::query(
+        r#"
+        UPDATE active_sessions SET last_active_at = NOW() - INTERVAL '40 days'
+        WHERE user_id = (SELECT id FROM users WHERE username = 'ada')
+        "#,
+    )
+    .execute(&app.pool)
+    .await
+    .unwrap();
+
+    // Nothing has a limit yet
+    assert_eq!(api::retention::apply(&app.state).await, Ok(0));
+
+    let res = root
+        .patch(
+            "/admin/retention",
+            json!({ "sessions": 30, "audit_log": 10, "held_content": 7 }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    assert_eq!(res.json()[0]["days"], 30);
+    sqlx::query("UPDATE audit_logs SET created_at = NOW() - INTERVAL '11 days'")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+
+    assert_eq!(api::retention::apply(&app.state).await, Ok(3));
+    assert_eq!(ada.get("/user/me").await.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(root.get("/user/me").await.status, StatusCode::OK);
+    let posts: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM posts")
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(posts, 0);
+    let audit: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM audit_logs")
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(audit, 0);
+    assert_eq!(api::retention::apply(&app.state).await, Ok(0));
+}
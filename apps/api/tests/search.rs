@@ -0,0 +1,354 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::TestApp;
+use serde_json::json;
+use sqlx::PgPool;
+
+#[sqlx::test(migrations = false)]
+async fn search_ranks_users_posts_and_projects_together(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada_lovelace").await;
+    let mut bob = app.signup("bob").await;
+    let mut client = app.client();
+
+    let res = ada
+        .post(
+            "/projects",
+            json!({ "title": "Analytical engine", "description": "Notes on the engine" }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let res = bob
+        .post(
+            "/posts",
+            json!({ "content": "Reading about the analytical engine" }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let post_id = res.json()["id"].as_str().unwrap().to_string();
+    bob.post("/posts", json!({ "content": "Unrelated" })).await;
+
+    let page = client.get("/search?q=analytical%20engine").await.json();
+    assert_eq!(page["total"], 2);
+    let results = page["results"].as_array().unwrap();
+    // A title match outranks the same words in a post
+    assert_eq!(results[0]["type"], "project");
+    assert_eq!(results[0]["title"], "Analytical engine");
+    assert_eq!(results[0]["link"], "/ada_lovelace/analytical-engine");
+    assert_eq!(results[1]["type"], "post");
+    assert_eq!(results[1]["username"], "bob");
+    assert_eq!(results[1]["link"], format!("/bob#post-{}", post_id));
+    // "Reading about the analytical engine"
+    assert_eq!(results[1]["highlights"], json!([[18, 28], [29, 35]]));
+
+    let page = client.get("/search?q=lovelace").await.json();
+    assert_eq!(page["total"], 1);
+    assert_eq!(page["results"][0]["type"], "user");
+    assert_eq!(page["results"][0]["link"], "/ada_lovelace");
+
+    // Narrowed to one type, and paginated
+    let page = client.get("/search?q=engine&type=posts").await.json();
+    assert_eq!(page["total"], 1);
+    assert_eq!(page["results"][0]["type"], "post");
+    let page = client
+        .get("/search?q=engine&per_page=1&page=2")
+        .await
+        .json();
+    assert_eq!(page["total"], 2);
+    assert_eq!(page["results"].as_array().unwrap().len(), 1);
+    assert_eq!(page["results"][0]["type"], "post");
+
+    let res = client.get("/search?q=engine&type=comments").await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+    let res = client.get("/search?q=%20").await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+
+    // Banned users and their content drop out
+    sqlx::query("UPDATE users SET banned_at = NOW() WHERE username = 'ada_lovelace'")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    let page = client.get("/search?q=engine").await.json();
+    assert_eq!(page["total"], 1);
+    assert_eq!(page["results"][0]["type"], "post");
+    let page = client.get("/search?q=lovelace").await.json();
+    assert_eq!(page["total"], 0);
+}
+
+#[sqlx::test(migrations = false)]
+async fn suggest_completes_usernames_and_project_titles(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut grace = app.signup("grace").await;
+    app.signup("gracehopper").await;
+    app.signup("gr_x").await;
+    let mut client = app.client();
+
+    let res = grace
+        .post("/projects", json!({ "title": "Graceful shutdown" }))
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+
+    let res = client.get("/search/suggest?q=Grace").await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    assert_eq!(res.header("cache-control"), Some("public, max-age=60"));
+    let suggestions = res.json();
+    let labels: Vec<&str> = suggestions
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| s["label"].as_str().unwrap())
+        .collect();
+    // Closest completions first
+    assert_eq!(labels, ["grace", "gracehopper", "Graceful shutdown"]);
+    assert_eq!(suggestions[2]["type"], "project");
+    assert_eq!(suggestions[2]["link"], "/grace/graceful-shutdown");
+
+    // LIKE wildcards in q are literal
+    let suggestions = client.get("/search/suggest?q=gr_").await.json();
+    assert_eq!(suggestions.as_array().unwrap().len(), 1);
+    assert_eq!(suggestions[0]["label"], "gr_x");
+
+    for i in 0..6 {
+        app.signup(&format!("gracie{}", i)).await;
+    }
+    let suggestions = client.get("/search/suggest?q=gra").await.json();
+    assert_eq!(suggestions.as_array().unwrap().len(), 5);
+
+    let suggestions = client.get("/search/suggest?q=%20").await.json();
+    assert!(suggestions.as_array().unwrap().is_empty());
+}
+
+#[sqlx::test(migrations = false)]
+async fn search_filters_and_sorts(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let mut bob = app.signup("bob").await;
+    let mut client = app.client();
+
+    for (client, title, tags) in [
+        (&mut ada, "Rust compiler", json!(["Compilers"])),
+        (&mut bob, "Rust game", json!(["games"])),
+    ] {
+        let res = client
+            .post("/projects", json!({ "title": title, "tags": tags }))
+            .await;
+        assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    }
+    let res = ada.post("/posts", json!({ "content": "Rust tips" })).await;
+    let post_id = res.json()["id"].as_str().unwrap().to_string();
+    // Make the post the oldest match, and give bob's game an application
+    sqlx::query("UPDATE posts SET created_at = NOW() - INTERVAL '30 days' WHERE id = $1::uuid")
+        .bind(&post_id)
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    let game = client.get("/search?q=game").await.json()["results"][0]["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let res = ada
+        .post(
+            &format!("/projects/{}/apply", game),
+            json!({ "message": "Count me in", "links": [] }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+
+    let titles = |page: &serde_json::Value| -> Vec<String> {
+        page["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["title"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    let page = client.get("/search?q=rust&author=ADA").await.json();
+    assert_eq!(titles(&page), ["Rust compiler", "ada"]);
+    let page = client.get("/search?q=rust&tag=compilers").await.json();
+    assert_eq!(titles(&page), ["Rust compiler"]);
+    let page = client
+        .get("/search?q=rust&status=open&author=bob")
+        .await
+        .json();
+    assert_eq!(titles(&page), ["Rust game"]);
+    let page = client
+        .get("/search?q=rust&from=2000-01-01T00:00:00Z&to=2100-01-01T00:00:00Z&sort=newest")
+        .await
+        .json();
+    assert_eq!(titles(&page), ["Rust game", "Rust compiler", "ada"]);
+    let page = client
+        .get(&format!(
+            "/search?q=rust&from={}",
+            (chrono::Utc::now() - chrono::Duration::days(1)).format("%Y-%m-%dT%H:%M:%SZ")
+        ))
+        .await
+        .json();
+    assert_eq!(page["total"], 2);
+    let page = client
+        .get("/search?q=rust&type=projects&sort=engaged")
+        .await
+        .json();
+    assert_eq!(titles(&page), ["Rust game", "Rust compiler"]);
+    assert_eq!(page["results"][0]["engagement"], 1);
+
+    let res = client.get("/search?q=rust&sort=popular").await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+    let res = client
+        .get("/search?q=rust&from=2026-02-01T00:00:00Z&to=2026-01-01T00:00:00Z")
+        .await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+}
+
+#[sqlx::test(migrations = false)]
+async fn search_index_is_kept_in_step_and_falls_back_to_postgres(pool: PgPool) {
+    let app = TestApp::with_search_index(pool).await;
+    let mut ada = app.signup("ada").await;
+    let mut client = app.client();
+
+    let res = ada
+        .post("/posts", json!({ "content": "Building an engine" }))
+        .await;
+    let post_id = res.json()["id"].as_str().unwrap().to_string();
+    let res = ada
+        .post("/projects", json!({ "title": "Engine", "tags": ["Rust"] }))
+        .await;
+    let project_id = res.json()["id"].as_str().unwrap().to_string();
+    let user_id: uuid::Uuid = sqlx::query_scalar("SELECT id FROM users WHERE username = 'ada'")
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    let mut expected = vec![
+        format!("post-{}", post_id),
+        format!("project-{}", project_id),
+        format!("user-{}", user_id),
+    ];
+    expected.sort();
+    assert_eq!(app.index.ids(), expected);
+
+    // The index finds partial words, which Postgres full-text search doesn't;
+    // results still come back loaded from Postgres
+    let page = client.get("/search?q=ngin").await.json();
+    assert_eq!(page["total"], 2);
+    assert_eq!(page["results"][0]["type"], "post");
+    assert_eq!(page["results"][0]["link"], format!("/ada#post-{}", post_id));
+    assert_eq!(page["results"][1]["link"], "/ada/engine");
+    let page = client.get("/search?q=ngin&tag=rust").await.json();
+    assert_eq!(page["total"], 1);
+    assert_eq!(page["results"][0]["type"], "project");
+
+    // If the index is down, Postgres answers
+    app.index
+        .failing
+        .store(true, std::sync::atomic::Ordering::SeqCst);
+    let page = client.get("/search?q=ngin").await.json();
+    assert_eq!(page["total"], 0);
+    let page = client.get("/search?q=engine").await.json();
+    assert_eq!(page["total"], 2);
+    app.index
+        .failing
+        .store(false, std::sync::atomic::Ordering::SeqCst);
+
+    let res = ada.delete(&format!("/posts/{}", post_id)).await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT);
+    assert!(!app.index.ids().contains(&format!("post-{}", post_id)));
+
+    // Banning drops the user and everything they made; reindexing rebuilds from Postgres
+    sqlx::query("UPDATE users SET banned_at = NOW() WHERE username = 'ada'")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    app.signup("bob").await;
+    let count = api::search_index::reindex(&app.pool, &*app.index)
+        .await
+        .unwrap();
+    assert_eq!(count, 1);
+    assert_eq!(app.index.ids().len(), 1);
+}
+
+#[sqlx::test(migrations = false)]
+async fn saved_searches_notify_about_new_results(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let mut bob = app.signup("bob").await;
+
+    let res = ada
+        .post("/search/saved", json!({ "q": "rust", "type": "comments" }))
+        .await;
+    assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let res = ada
+        .post(
+            "/search/saved",
+            json!({ "q": " rust ", "type": "projects", "tag": "games" }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let saved = res.json();
+    assert_eq!(saved["q"], "rust");
+    let id = saved["id"].as_str().unwrap().to_string();
+    assert_eq!(
+        ada.get("/search/saved")
+            .await
+            .json()
+            .as_array()
+            .unwrap()
+            .len(),
+        1
+    );
+
+    for (title, tags) in [
+        ("Rust game", json!(["Games"])),
+        ("Rust compiler", json!(["compilers"])),
+        ("Go game", json!(["games"])),
+    ] {
+        let res = bob
+            .post("/projects", json!({ "title": title, "tags": tags }))
+            .await;
+        assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    }
+
+    // Not due until it's gone an hour unchecked
+    let check = || api::saved_searches::check_due(&app.state, chrono::Utc::now());
+    assert_eq!(check().await, Ok(0));
+    sqlx::query("UPDATE saved_searches SET checked_at = NOW() - INTERVAL '2 hours'")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(check().await, Ok(1));
+    let notifications = ada.get("/notifications").await.json();
+    assert_eq!(notifications[0]["kind"], "saved_search");
+    assert_eq!(notifications[0]["data"]["saved_search_id"], id.as_str());
+    assert_eq!(notifications[0]["data"]["new_results"], 1);
+    // Checked just now, and nothing new since
+    sqlx::query("UPDATE saved_searches SET checked_at = checked_at - INTERVAL '2 hours'")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    sqlx::query("UPDATE projects SET created_at = NOW() - INTERVAL '3 hours'")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(check().await, Ok(0));
+
+    let res = bob.delete(&format!("/search/saved/{}", id)).await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+    let res = ada.delete(&format!("/search/saved/{}", id)).await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT);
+    assert!(ada
+        .get("/search/saved")
+        .await
+        .json()
+        .as_array()
+        .unwrap()
+        .is_empty());
+
+    for i in 0..20 {
+        let res = ada
+            .post("/search/saved", json!({ "q": format!("query {}", i) }))
+            .await;
+        assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    }
+    let res = ada.post("/search/saved", json!({ "q": "one more" })).await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,174 @@
+mod common;
+use axum::http::StatusCode;
+use common::TestApp;
+use serde_json::json;
+use sqlx::PgPool;
+
+#[sqlx::test(migrations = false)]
+async fn spam_checks_hold_content_until_a_moderator_reviews_it(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let mut bob = app.signup("bob").await;
+    let mut root = app.signup_admin("root").await;
+
+    let res = root
+        .patch(
+            "/admin/settings",
+            json!({ "banned_domains": ["https://spam.example"] }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+    let res = root
+        .patch(
+            "/admin/settings",
+            json!({ "banned_domains": ["spam.example"] }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+
+    // A new account's links are held: it looks posted to ada but nobody else sees it
+    let res = ada
+        .post(
+            "/posts",
+            json!({ "content": "My blog: https://ada.example" }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let first_post = res.json()["id"].as_str().unwrap().to_string();
+    assert_eq!(
+        ada.get("/posts/user/ada")
+            .await
+            .json()
+            .as_array()
+            .unwrap()
+            .len(),
+        1
+    );
+    assert_eq!(bob.get("/posts/user/ada").await.json(), json!([]));
+    assert_eq!(app.client().get("/feed").await.json()["items"], json!([]));
+
+    sqlx::query("UPDATE users SET created_at = NOW() - INTERVAL '2 days' WHERE username = 'ada'")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    let res = ada
+        .post(
+            "/posts",
+            json!({ "content": "Deals at https://shop.spam.example today" }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let banned_post = res.json()["id"].as_str().unwrap().to_string();
+
+    // Duplicates ignore case and spacing
+    let res = ada
+        .post("/posts", json!({ "content": "Hello world" }))
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let res = ada
+        .post("/posts", json!({ "content": "hello   WORLD" }))
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+
+    // Applications are checked against their links too, and the owner hears nothing yet
+    let res = bob.post("/projects", json!({ "title": "Robots" })).await;
+    let project_id = res.json()["id"].as_str().unwrap().to_string();
+    let res = ada
+        .post(
+            &format!("/projects/{}/apply", project_id),
+            json!({ "message": "Pick me", "links": ["https://spam.example/me"] }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    let application_id = res.json()["id"].as_str().unwrap().to_string();
+    assert_eq!(bob.get("/notifications").await.json(), json!([]));
+
+    // Moderators aren't checked
+    let res = root
+        .post("/posts", json!({ "content": "https://spam.example" }))
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+
+    let feed = app.client().get("/feed").await.json()["items"].clone();
+    let contents: Vec<&str> = feed
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["content"].as_str().unwrap_or_default())
+        .collect();
+    assert_eq!(contents, ["https://spam.example", "", "Hello world"]);
+
+    let res = ada.get("/admin/held").await;
+    assert_eq!(res.status, StatusCode::FORBIDDEN);
+    let held = root.get("/admin/held").await.json();
+    let held: Vec<(&str, &str, &serde_json::Value)> = held
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| {
+            (
+                item["kind"].as_str().unwrap(),
+                item["author_username"].as_str().unwrap(),
+                &item["reasons"],
+            )
+        })
+        .collect();
+    assert_eq!(
+        held,
+        [
+            ("post", "ada", &json!(["new_account_links"])),
+            ("post", "ada", &json!(["banned_domain"])),
+            ("post", "ada", &json!(["duplicate"])),
+            ("application", "ada", &json!(["banned_domain"])),
+        ]
+    );
+
+    // Approving publishes; rejecting deletes
+    let review = |kind: &str, id: &str| format!("/admin/held/{}/{}/review", kind, id);
+    let res = root
+        .post(&review("post", &first_post), json!({ "approve": true }))
+        .await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT, "{}", res.text());
+    let feed = app.client().get("/feed").await.json()["items"].clone();
+    assert_eq!(feed[0]["content"], "https://spam.example");
+    assert_eq!(feed[3]["content"], "My blog: https://ada.example");
+    let res = root
+        .post(&review("post", &first_post), json!({ "approve": true }))
+        .await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+    let res = root
+        .post(&review("event", &first_post), json!({ "approve": true }))
+        .await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+
+    let res = root
+        .post(&review("post", &banned_post), json!({ "approve": false }))
+        .await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT, "{}", res.text());
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM posts WHERE id = $1::uuid)")
+        .bind(&banned_post)
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    assert!(!exists);
+
+    let res = root
+        .post(
+            &review("application", &application_id),
+            json!({ "approve": true }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT, "{}", res.text());
+    let notifications = bob.get("/notifications").await.json();
+    assert_eq!(notifications[0]["kind"], "application");
+    assert_eq!(notifications[0]["data"]["project_title"], "Robots");
+    assert_eq!(
+        root.get("/admin/held")
+            .await
+            .json()
+            .as_array()
+            .unwrap()
+            .len(),
+        1
+    );
+}
@@ -0,0 +1,137 @@
+mod common;
+use axum::http::StatusCode;
+use common::TestApp;
+use serde_json::json;
+use sqlx::PgPool;
+
+#[sqlx::test(migrations = false)]
+async fn upheld_reports_add_strikes_that_escalate_and_can_be_appealed(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut ada = app.signup("ada").await;
+    let mut bob = app.signup("bob").await;
+    let mut root = app.signup_admin("root").await;
+
+    let mut reports = Vec::new();
+    for content in ["You're useless", "Buy my course", "Still useless"] {
+        let res = bob
+            .post("/messages", json!({ "to": "ada", "content": content }))
+            .await;
+        let message_id = res.json()["id"].as_str().unwrap().to_string();
+        let res = ada
+            .post(
+                &format!("/messages/{}/report", message_id),
+                json!({ "category": "harassment", "reason": "Rude" }),
+            )
+            .await;
+        assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+        reports.push(res.json()["id"].as_str().unwrap().to_string());
+    }
+    let res = bob
+        .post("/messages", json!({ "to": "ada", "content": "Hi" }))
+        .await;
+    let res = ada
+        .post(
+            &format!("/messages/{}/report", res.json()["id"].as_str().unwrap()),
+            json!({ "category": "rude", "reason": "Rude" }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let listed = root
+        .get("/admin/message-reports?category=harassment")
+        .await
+        .json();
+    assert_eq!(listed.as_array().unwrap().len(), 3);
+    assert_eq!(listed[0]["category"], "harassment");
+    let listed = root
+        .get("/admin/message-reports?category=spam")
+        .await
+        .json();
+    assert_eq!(listed, json!([]));
+
+    // Warned at one strike and suspended at two, never banned
+    let res = root
+        .patch("/admin/settings", json!({ "strike_suspension_hours": 0 }))
+        .await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+    let res = root
+        .patch(
+            "/admin/settings",
+            json!({ "strike_suspension_threshold": 2, "strike_ban_threshold": 0 }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+
+    let resolve = |id: &str| format!("/admin/message-reports/{}/resolve", id);
+    let res = root
+        .post(&resolve(&reports[0]), json!({ "status": "actioned" }))
+        .await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT, "{}", res.text());
+    let notifications = bob.get("/notifications").await.json();
+    assert_eq!(notifications[0]["kind"], "strike");
+    assert_eq!(notifications[0]["data"]["action"], "warn");
+    let strikes = bob.get("/user/me/strikes").await.json();
+    assert_eq!(strikes["strikes"], 1);
+    assert_eq!(strikes["reports"][0]["category"], "harassment");
+    assert!(strikes["reports"][0].get("reason").is_none());
+
+    // An appeal reopens the report; overturning it takes the strike back
+    let appeal = |id: &str| format!("/user/me/strikes/{}/appeal", id);
+    let res = ada
+        .post_with_headers(
+            &appeal(&reports[0]),
+            json!({ "appeal": "Not mine" }),
+            &[("accept", "application/json")],
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+    assert_eq!(res.json()["code"], "report_not_found");
+    let res = bob
+        .post(
+            &appeal(&reports[1]),
+            json!({ "appeal": "Not reviewed yet" }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+    let res = bob
+        .post(&appeal(&reports[0]), json!({ "appeal": "It was a joke" }))
+        .await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT, "{}", res.text());
+    let res = bob
+        .post(&appeal(&reports[0]), json!({ "appeal": "Really" }))
+        .await;
+    assert_eq!(res.status, StatusCode::CONFLICT);
+    let appealed = root
+        .get("/admin/message-reports?status=appealed")
+        .await
+        .json();
+    assert_eq!(appealed[0]["appeal"], "It was a joke");
+    let res = root
+        .post(&resolve(&reports[0]), json!({ "status": "dismissed" }))
+        .await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT, "{}", res.text());
+    let strikes = bob.get("/user/me/strikes").await.json();
+    assert_eq!(strikes["strikes"], 0);
+    assert_eq!(strikes["reports"][0]["status"], "dismissed");
+
+    for id in &reports[1..] {
+        let res = root
+            .post(&resolve(id), json!({ "status": "actioned" }))
+            .await;
+        assert_eq!(res.status, StatusCode::NO_CONTENT, "{}", res.text());
+    }
+    let res = bob.get("/user/me/strikes").await;
+    assert_eq!(res.status, StatusCode::UNAUTHORIZED);
+    let actions: Vec<String> = sqlx::query_scalar(
+        "SELECT action FROM moderation_actions WHERE reason LIKE '%strikes%' ORDER BY created_at",
+    )
+    .fetch_all(&app.pool)
+    .await
+    .unwrap();
+    assert_eq!(actions, ["warn", "warn", "suspend"]);
+    let suspended: bool =
+        sqlx::query_scalar("SELECT suspended_until > NOW() FROM users WHERE username = 'bob'")
+            .fetch_one(&app.pool)
+            .await
+            .unwrap();
+    assert!(suspended);
+}
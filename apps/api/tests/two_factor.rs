@@ -0,0 +1,116 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::{TestApp, TestClient, PASSWORD};
+use serde_json::json;
+use sqlx::PgPool;
+use totp_rs::{Algorithm, Secret, TOTP};
+
+fn current_code(secret: &str) -> String {
+    let secret = Secret::Encoded(secret.to_string()).to_bytes().unwrap();
+    TOTP::new(Algorithm::SHA1, 6, 1, 30, secret, None, String::new())
+        .unwrap()
+        .generate_current()
+        .unwrap()
+}
+
+/// Set up and enable TOTP, returning the secret and backup codes
+async fn enable_2fa(client: &mut TestClient) -> (String, Vec<String>) {
+    let res = client.post("/auth/totp/setup", json!({})).await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    let secret = res.json()["secret"].as_str().unwrap().to_string();
+
+    let res = client
+        .post(
+            "/auth/totp/enable",
+            json!({ "code": current_code(&secret) }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    let backup_codes = res.json()["backup_codes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|code| code.as_str().unwrap().to_string())
+        .collect();
+
+    (secret, backup_codes)
+}
+
+async fn login(client: &mut TestClient, email: &str) {
+    let res = client
+        .post(
+            "/auth/login",
+            json!({ "email": email, "password": PASSWORD }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    assert_eq!(res.json()["requires_2fa"], true);
+}
+
+#[sqlx::test(migrations = false)]
+async fn login_requires_totp_once_enabled(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut owner = app.signup("alan").await;
+    let (secret, _) = enable_2fa(&mut owner).await;
+    assert_eq!(owner.get("/auth/totp/status").await.json()["enabled"], true);
+
+    let mut client = app.client();
+    login(&mut client, "alan@example.com").await;
+
+    // Password alone doesn't log in
+    assert_eq!(
+        client.get("/user/me").await.status,
+        StatusCode::UNAUTHORIZED
+    );
+
+    let res = client
+        .post("/auth/totp/verify", json!({ "code": "000000" }))
+        .await;
+    assert_eq!(res.status, StatusCode::UNAUTHORIZED);
+
+    // Completing the login moves it to a new session ID
+    let pending = client.cookie().unwrap().to_string();
+    let res = client
+        .post(
+            "/auth/totp/verify",
+            json!({ "code": current_code(&secret) }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    assert_ne!(client.cookie().unwrap(), pending);
+    assert_eq!(client.get("/user/me").await.json()["username"], "alan");
+}
+
+#[sqlx::test(migrations = false)]
+async fn backup_codes_work_once(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut owner = app.signup("margaret").await;
+    let (_, backup_codes) = enable_2fa(&mut owner).await;
+    assert!(!backup_codes.is_empty());
+
+    let mut client = app.client();
+    login(&mut client, "margaret@example.com").await;
+    let res = client
+        .post("/auth/totp/verify", json!({ "code": backup_codes[0] }))
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+
+    let mut client = app.client();
+    login(&mut client, "margaret@example.com").await;
+    let res = client
+        .post("/auth/totp/verify", json!({ "code": backup_codes[0] }))
+        .await;
+    assert_eq!(res.status, StatusCode::UNAUTHORIZED);
+}
+
+#[sqlx::test(migrations = false)]
+async fn verify_without_pending_login_is_rejected(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+
+    let res = app
+        .client()
+        .post("/auth/totp/verify", json!({ "code": "123456" }))
+        .await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+}
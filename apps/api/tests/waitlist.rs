@@ -0,0 +1,90 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::{TestApp, PASSWORD};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+fn signup(username: &str, waitlist_token: Option<&str>) -> Value {
+    json!({
+        "email": format!("{}@example.com", username),
+        "password": PASSWORD,
+        "username": username,
+        "display_name": username,
+        "interest": "Finding a team for my thesis",
+        "waitlist_token": waitlist_token,
+    })
+}
+
+#[sqlx::test(migrations = false)]
+async fn waitlisted_signups_get_in_once_approved(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let mut root = app.signup_admin("root").await;
+    let res = root
+        .patch("/admin/settings", json!({ "waitlist": true }))
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+
+    for username in ["ada", "bob"] {
+        let res = app
+            .client()
+            .post("/auth/signup", signup(username, None))
+            .await;
+        assert_eq!(res.status, StatusCode::ACCEPTED, "{}", res.text());
+    }
+    let res = app
+        .client()
+        .post("/auth/waitlist", json!({ "email": "carol@example.com" }))
+        .await;
+    assert_eq!(res.status, StatusCode::ACCEPTED);
+    // Joining twice is the same as once
+    app.client().post("/auth/signup", signup("ada", None)).await;
+    let users: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users")
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(users, 1);
+
+    let waitlist = root.get("/admin/waitlist").await.json();
+    assert_eq!(waitlist["waiting"], 3);
+    assert_eq!(waitlist["entries"][0]["email"], "ada@example.com");
+    assert_eq!(
+        waitlist["entries"][0]["interest"],
+        "Finding a team for my thesis"
+    );
+
+    let res = app.client().get("/user/me").await;
+    assert_eq!(res.status, StatusCode::UNAUTHORIZED);
+    let res = root
+        .post("/admin/waitlist/approve", json!({ "count": 2 }))
+        .await;
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    assert_eq!(res.json()["approved"], 2);
+    app.run_jobs().await;
+    assert!(app.mailer.last_to("carol@example.com").is_none());
+    let email = app.mailer.last_to("ada@example.com").unwrap();
+    assert_eq!(email.subject, "Your Praxis invitation is here");
+    let token = email.token();
+
+    let res = app
+        .client()
+        .post("/auth/signup", signup("ada", Some("not-a-token")))
+        .await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+    let res = app
+        .client()
+        .post("/auth/signup", signup("ada", Some(&token)))
+        .await;
+    assert_eq!(res.status, StatusCode::CREATED, "{}", res.text());
+    // Each link signs up one account
+    let res = app
+        .client()
+        .post("/auth/signup", signup("ada2", Some(&token)))
+        .await;
+    assert_eq!(res.status, StatusCode::BAD_REQUEST);
+
+    let joined = root.get("/admin/waitlist?status=joined").await.json();
+    assert_eq!(joined["entries"][0]["email"], "ada@example.com");
+    assert!(joined["entries"][0]["joined_user_id"].is_string());
+    assert_eq!(root.get("/admin/waitlist").await.json()["waiting"], 1);
+}
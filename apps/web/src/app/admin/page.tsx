@@ -103,15 +103,24 @@ export default function AdminPage() {
     const fetchUsers = async () => {
         setLoadingUsers(true);
         try {
-            const res = await fetch(`${API_URL}/user/all`, {
-                credentials: 'include',
-            });
-            if (res.ok) {
-                const data = await res.json();
-                setUsers(data);
-            } else {
-                showToast('Failed to load users', 'error');
-            }
+            // /user/all comes a page at a time; the table shows everyone
+            const all: UserProfile[] = [];
+            let cursor: string | null = null;
+            do {
+                const params = new URLSearchParams({ limit: '100' });
+                if (cursor) params.set('cursor', cursor);
+                const res = await fetch(`${API_URL}/user/all?${params}`, {
+                    credentials: 'include',
+                });
+                if (!res.ok) {
+                    showToast('Failed to load users', 'error');
+                    return;
+                }
+                const data: { users: UserProfile[]; next_cursor: string | null } = await res.json();
+                all.push(...data.users);
+                cursor = data.next_cursor;
+            } while (cursor);
+            setUsers(all);
         } catch (err) {
             console.error(err);
             showToast('Failed to load users', 'error');
@@ -123,12 +132,12 @@ export default function AdminPage() {
     const fetchAuditLogs = async () => {
         setLoadingLogs(true);
         try {
-            const res = await fetch(`${API_URL}/admin/audit-logs?limit=150`, {
+            const res = await fetch(`${API_URL}/admin/audit-log?per_page=150`, {
                 credentials: 'include',
             });
             if (res.ok) {
                 const data = await res.json();
-                setLogs(data);
+                setLogs(data.entries);
             } else {
                 showToast('Failed to load audit log', 'error');
             }
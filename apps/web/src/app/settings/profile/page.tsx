@@ -325,7 +325,8 @@ export default function ProfilePage() {
             const filename = `cropped_image.${ext}`;
             croppedFormData.append('file', croppedBlob, filename);
 
-            const resCropped = await fetch(`${process.env.NEXT_PUBLIC_API_URL || 'http://localhost:8080'}/upload`, {
+            const category = type === 'avatar_url' ? 'avatar' : 'banner';
+            const resCropped = await fetch(`${process.env.NEXT_PUBLIC_API_URL || 'http://localhost:8080'}/upload?category=${category}`, {
                 method: 'POST',
                 credentials: 'include',
                 body: croppedFormData,
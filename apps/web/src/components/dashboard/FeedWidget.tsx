@@ -44,7 +44,7 @@ export function FeedWidget({ user }: FeedWidgetProps) {
             );
             if (res.ok) {
                 const data = await res.json();
-                setFeed(data);
+                setFeed(data.items);
             }
         } catch (error) {
             console.error('Failed to fetch feed:', error);
@@ -10,7 +10,8 @@ import { Card } from '@/components/ui/card';
 import { Trash2, UserPlus } from 'lucide-react';
 
 interface User {
-    id: string;
+    // Only admins get ids (and emails); everyone else sees the public directory
+    id?: string;
     username: string;
     display_name: string;
     email?: string;
@@ -26,18 +27,23 @@ export function UserListWidget({ currentUser }: UserListWidgetProps) {
     const [users, setUsers] = useState<User[]>([]);
     const [loading, setLoading] = useState(true);
     const { showToast } = useToast();
+    const role = currentUser?.role;
 
     useEffect(() => {
+        if (!role) return;
+
         const fetchUsers = async () => {
             try {
-                const res = await fetch(`${process.env.NEXT_PUBLIC_API_URL || 'http://localhost:8080'}/user/all`, {
+                // /user/all is admin only; everyone else gets the public directory
+                const path = role === 'admin' ? '/user/all' : '/user/directory';
+                const res = await fetch(`${process.env.NEXT_PUBLIC_API_URL || 'http://localhost:8080'}${path}`, {
                     credentials: 'include',
                 });
 
                 if (!res.ok) throw new Error('Failed to fetch users');
 
                 const data = await res.json();
-                setUsers(data);
+                setUsers(data.users);
             } catch (err) {
                 console.error(err);
                 showToast('Could not load user list', 'error');
@@ -47,7 +53,7 @@ export function UserListWidget({ currentUser }: UserListWidgetProps) {
         };
 
         fetchUsers();
-    }, [showToast]);
+    }, [role, showToast]);
 
     const handleDelete = async (userId: string) => {
         if (!confirm('Are you sure you want to delete this user?')) return;
@@ -127,7 +133,7 @@ export function UserListWidget({ currentUser }: UserListWidgetProps) {
                     <p className="text-muted-foreground text-sm text-center py-4">No users found.</p>
                 ) : (
                     users.map((user) => (
-                        <div key={user.id} className="group relative flex items-center justify-between p-2 rounded-lg hover:bg-muted/50 transition-colors">
+                        <div key={user.username} className="group relative flex items-center justify-between p-2 rounded-lg hover:bg-muted/50 transition-colors">
                             {/* Overlay Link */}
                             <Link href={`/${user.username}`} className="absolute inset-0 z-0" aria-label={`View profile of ${user.display_name || user.username}`} />
 
@@ -147,14 +153,14 @@ export function UserListWidget({ currentUser }: UserListWidgetProps) {
                                 </div>
                             </div>
 
-                            {currentUser?.role === 'admin' && (
+                            {currentUser?.role === 'admin' && user.id && (
                                 <Button
                                     variant="ghost"
                                     size="icon"
                                     onClick={(e) => {
                                         e.preventDefault();
                                         e.stopPropagation();
-                                        handleDelete(user.id);
+                                        handleDelete(user.id!);
                                     }}
                                     className="relative z-20 opacity-0 group-hover:opacity-100 focus:opacity-100 transition-opacity h-8 w-8 text-muted-foreground hover:text-destructive"
                                     title="Delete User"